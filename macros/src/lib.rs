@@ -0,0 +1,187 @@
+//! The `#[mollusk_test]` attribute of `mollusk-on-demand`, re-exported as
+//! `mollusk_on_demand::mollusk_test` with the `macros` feature.
+
+use {
+    proc_macro::TokenStream,
+    proc_macro2::TokenStream as TokenStream2,
+    quote::{format_ident, quote},
+    syn::{meta::ParseNestedMeta, parse_macro_input, Expr, ExprArray, FnArg, ItemFn, LitStr},
+};
+
+/// Shortcut constructors of `RpcAccountStore` that `rpc` can name.
+const CLUSTERS: [&str; 4] = ["mainnet", "devnet", "testnet", "localnet"];
+
+#[derive(Default)]
+struct Args {
+    rpc: Option<LitStr>,
+    fixtures: Option<LitStr>,
+    programs: Vec<Expr>,
+    allow_warnings: bool,
+}
+
+impl Args {
+    fn parse(&mut self, meta: ParseNestedMeta) -> syn::Result<()> {
+        if meta.path.is_ident("rpc") {
+            self.rpc = Some(meta.value()?.parse()?);
+        } else if meta.path.is_ident("fixtures") {
+            self.fixtures = Some(meta.value()?.parse()?);
+        } else if meta.path.is_ident("programs") {
+            let programs: ExprArray = meta.value()?.parse()?;
+            self.programs = programs.elems.into_iter().collect();
+        } else if meta.path.is_ident("allow_warnings") {
+            self.allow_warnings = true;
+        } else {
+            return Err(meta.error(
+                "expected `rpc`, `fixtures`, `programs` or `allow_warnings`",
+            ));
+        }
+        Ok(())
+    }
+
+    /// The expression building the store, before fixtures and programs.
+    fn store(&self) -> TokenStream2 {
+        let value = self.rpc.as_ref().map(LitStr::value);
+        match value.as_deref() {
+            None | Some("env") => quote! {
+                ::mollusk_on_demand::RpcAccountStore::from_env()
+                    .unwrap_or_else(|e| panic!("mollusk_test: {e}"))
+            },
+            Some(cluster) if CLUSTERS.contains(&cluster) => {
+                let constructor = format_ident!("{}", cluster);
+                quote! { ::mollusk_on_demand::RpcAccountStore::#constructor() }
+            }
+            Some(_) => {
+                let rpc = &self.rpc;
+                quote! { ::mollusk_on_demand::RpcAccountStore::new(#rpc) }
+            }
+        }
+    }
+}
+
+/// Turn an async test taking the store into a `#[tokio::test]`.
+///
+/// ```rust,ignore
+/// use mollusk_on_demand::{mollusk_test, RpcAccountStore, RpcError};
+/// use mollusk_svm::Mollusk;
+///
+/// #[mollusk_test(rpc = "mainnet", fixtures = "tests/fixtures", programs = [amm::ID])]
+/// async fn swap(mollusk: &mut Mollusk, store: &mut RpcAccountStore) -> Result<(), RpcError> {
+///     store.get_or_fetch_many(&[pool, vault_a, vault_b]).await?;
+///     let result = mollusk.process_instruction(&ix, &store.instruction_accounts(&ix)?);
+///     assert!(result.program_result.is_ok());
+///     Ok(())
+/// }
+/// ```
+///
+/// The test gets a store, and optionally a `Mollusk` before it, as mutable
+/// references. Arguments, all optional:
+///
+/// - `rpc`: `"mainnet"`, `"devnet"`, `"testnet"` or `"localnet"` for the shortcut
+///   constructors, `"env"` for `RpcAccountStore::from_env()` (the default), or an RPC URL.
+/// - `fixtures`: a directory, relative to the package, that accounts are recorded to
+///   and replayed from. Each test gets its own subdirectory named after its module
+///   path and name.
+/// - `programs`: program ids to fetch and load into `Mollusk` before the test runs.
+/// - `allow_warnings`: don't fail the test if the store recorded warnings.
+///
+/// The expansion only uses public APIs of `mollusk-on-demand`, `mollusk-svm` and
+/// `tokio`, which the test crate must depend on.
+#[proc_macro_attribute]
+pub fn mollusk_test(args: TokenStream, item: TokenStream) -> TokenStream {
+    let mut parsed = Args::default();
+    let parser = syn::meta::parser(|meta| parsed.parse(meta));
+    parse_macro_input!(args with parser);
+    let function = parse_macro_input!(item as ItemFn);
+    expand(&parsed, function)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+fn expand(args: &Args, function: ItemFn) -> syn::Result<TokenStream2> {
+    let ItemFn {
+        attrs,
+        vis,
+        sig,
+        block,
+    } = function;
+    if sig.asyncness.is_none() {
+        return Err(syn::Error::new_spanned(sig.fn_token, "mollusk_test functions must be async"));
+    }
+    if !sig.generics.params.is_empty() {
+        return Err(syn::Error::new_spanned(
+            &sig.generics,
+            "mollusk_test functions can't be generic",
+        ));
+    }
+    if let Some(FnArg::Receiver(receiver)) = sig.inputs.first() {
+        return Err(syn::Error::new_spanned(receiver, "mollusk_test functions can't take self"));
+    }
+    let takes_mollusk = match sig.inputs.len() {
+        1 => false,
+        2 => true,
+        _ => {
+            return Err(syn::Error::new_spanned(
+                &sig.inputs,
+                "expected `(store: &mut RpcAccountStore)` or \
+                 `(mollusk: &mut Mollusk, store: &mut RpcAccountStore)`",
+            ))
+        }
+    };
+
+    let name = &sig.ident;
+    let output = &sig.output;
+    let inputs = &sig.inputs;
+    let store = args.store();
+    let fixtures = args.fixtures.as_ref().map(|fixtures| {
+        let name = name.to_string();
+        quote! {
+            .with_fixture_dir(
+                ::std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+                    .join(#fixtures)
+                    .join(module_path!().replace("::", "/"))
+                    .join(#name),
+            )
+        }
+    });
+    let programs = (!args.programs.is_empty()).then(|| {
+        let programs = &args.programs;
+        quote! {
+            if let Err(e) = store.get_or_fetch_many(&[#(#programs),*]).await {
+                panic!("mollusk_test: {e}");
+            }
+            let mut store = store
+                .add_programs(&mut mollusk)
+                .await
+                .unwrap_or_else(|e| panic!("mollusk_test: {e}"));
+        }
+    });
+    let call = if takes_mollusk {
+        quote! { __mollusk_test(&mut mollusk, &mut store).await }
+    } else {
+        quote! { __mollusk_test(&mut store).await }
+    };
+    let mollusk = (takes_mollusk || !args.programs.is_empty())
+        .then(|| quote! { let mut mollusk = ::mollusk_svm::Mollusk::default(); });
+    let warnings = (!args.allow_warnings).then(|| {
+        quote! {
+            if let Err(e) = store.assert_no_warnings() {
+                panic!("mollusk_test: {e}");
+            }
+        }
+    });
+
+    Ok(quote! {
+        #(#attrs)*
+        #[::tokio::test]
+        #vis async fn #name() #output {
+            async fn __mollusk_test(#inputs) #output #block
+
+            #mollusk
+            let mut store = #store #fixtures;
+            #programs
+            let result = #call;
+            #warnings
+            result
+        }
+    })
+}
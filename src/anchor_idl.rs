@@ -0,0 +1,407 @@
+//! Anchor accounts built from a program's IDL and JSON values, for programs whose
+//! crates aren't at hand.
+//!
+//! Both IDL formats are read: the current one (Anchor 0.30 and later, with
+//! `address` and `discriminator` entries) and the legacy one (account types inline,
+//! `publicKey`, `{"defined": "Name"}`). Only Borsh-serialized types are supported,
+//! not zero-copy accounts.
+
+use {
+    crate::{RpcAccountStore, RpcError},
+    serde_json::{Map, Value},
+    sha2::{Digest, Sha256},
+    solana_account::Account,
+    solana_pubkey::Pubkey,
+    std::str::FromStr,
+};
+
+/// Length of an Anchor account discriminator.
+const DISCRIMINATOR_LEN: usize = 8;
+
+/// The definitions of an IDL and the Borsh bytes written so far.
+struct Encoder<'a> {
+    idl: &'a Value,
+    data: Vec<u8>,
+}
+
+/// `path` as shown in errors, `the account` for the top level.
+fn field(path: &str) -> String {
+    if path.is_empty() {
+        "the account".to_string()
+    } else {
+        format!("field `{path}`")
+    }
+}
+
+/// The path of `name` inside the value at `path`.
+fn join(path: &str, name: &str) -> String {
+    if path.is_empty() {
+        name.to_string()
+    } else {
+        format!("{path}.{name}")
+    }
+}
+
+/// The integer `value`, a JSON number or a decimal string for values JSON numbers
+/// can't hold exactly.
+fn integer<T: TryFrom<i128> + FromStr>(value: &Value, ty: &str, path: &str) -> Result<T, String> {
+    let parsed = match value {
+        Value::Number(number) => number
+            .as_i64()
+            .map(i128::from)
+            .or_else(|| number.as_u64().map(i128::from))
+            .and_then(|number| T::try_from(number).ok()),
+        Value::String(text) => text.parse().ok(),
+        _ => None,
+    };
+    parsed.ok_or_else(|| format!("{} should be a {ty}, got {value}", field(path)))
+}
+
+impl<'a> Encoder<'a> {
+    /// The type definition called `name`: an entry of `types`, or of `accounts` in
+    /// legacy IDLs, which define account types inline.
+    fn definition(&self, name: &str) -> Option<&'a Value> {
+        let idl = self.idl;
+        ["types", "accounts"].into_iter().find_map(|section| {
+            idl[section].as_array()?.iter().find(|entry| {
+                entry["name"].as_str() == Some(name) && entry.get("type").is_some()
+            })
+        })
+    }
+
+    fn length(&mut self, len: usize, path: &str) -> Result<(), String> {
+        let len = u32::try_from(len)
+            .map_err(|_| format!("{} is too long to serialize", field(path)))?;
+        self.data.extend_from_slice(&len.to_le_bytes());
+        Ok(())
+    }
+
+    /// Serialize `value` as the IDL type `ty`.
+    fn encode(&mut self, ty: &Value, value: &Value, path: &str) -> Result<(), String> {
+        let unsupported = || format!("{} has an unsupported type {ty}", field(path));
+        let Some(object) = ty.as_object() else {
+            let name = ty.as_str().ok_or_else(unsupported)?;
+            return self.primitive(name, value, path).map_err(|reason| {
+                reason.unwrap_or_else(unsupported)
+            });
+        };
+        if let Some(inner) = object.get("option") {
+            if value.is_null() {
+                self.data.push(0);
+                return Ok(());
+            }
+            self.data.push(1);
+            return self.encode(inner, value, path);
+        }
+        if let Some(item) = object.get("vec") {
+            let items = value
+                .as_array()
+                .ok_or_else(|| format!("{} should be an array, got {value}", field(path)))?;
+            self.length(items.len(), path)?;
+            for (index, item_value) in items.iter().enumerate() {
+                self.encode(item, item_value, &format!("{path}[{index}]"))?;
+            }
+            return Ok(());
+        }
+        if let Some(array) = object.get("array") {
+            let (Some(item), Some(len)) = (array.get(0), array.get(1).and_then(Value::as_u64))
+            else {
+                return Err(unsupported());
+            };
+            let items = value
+                .as_array()
+                .filter(|items| items.len() as u64 == len)
+                .ok_or_else(|| {
+                    format!("{} should be an array of {len} items, got {value}", field(path))
+                })?;
+            for (index, item_value) in items.iter().enumerate() {
+                self.encode(item, item_value, &format!("{path}[{index}]"))?;
+            }
+            return Ok(());
+        }
+        if let Some(defined) = object.get("defined") {
+            // Legacy IDLs name the type directly, current ones in an object that
+            // may also list generic arguments
+            let name = match defined {
+                Value::String(name) => name.as_str(),
+                Value::Object(defined)
+                    if defined
+                        .get("generics")
+                        .and_then(Value::as_array)
+                        .is_none_or(Vec::is_empty) =>
+                {
+                    defined.get("name").and_then(Value::as_str).ok_or_else(unsupported)?
+                }
+                _ => return Err(unsupported()),
+            };
+            return self.encode_defined(name, value, path);
+        }
+        Err(unsupported())
+    }
+
+    /// Serialize `value` as the primitive `name`. Fails with `None` if `name`
+    /// isn't a primitive this encoder knows.
+    fn primitive(&mut self, name: &str, value: &Value, path: &str) -> Result<(), Option<String>> {
+        macro_rules! int {
+            ($ty:ty) => {
+                self.data.extend_from_slice(&integer::<$ty>(value, name, path)?.to_le_bytes())
+            };
+        }
+        let expected = |what: &str| Some(format!("{} should be {what}, got {value}", field(path)));
+        match name {
+            "bool" => {
+                let flag = value.as_bool().ok_or_else(|| expected("a bool"))?;
+                self.data.push(u8::from(flag));
+            }
+            "u8" => int!(u8),
+            "i8" => int!(i8),
+            "u16" => int!(u16),
+            "i16" => int!(i16),
+            "u32" => int!(u32),
+            "i32" => int!(i32),
+            "u64" => int!(u64),
+            "i64" => int!(i64),
+            "u128" => int!(u128),
+            "i128" => int!(i128),
+            "f32" => {
+                let float = value.as_f64().ok_or_else(|| expected("a number"))?;
+                self.data.extend_from_slice(&(float as f32).to_le_bytes());
+            }
+            "f64" => {
+                let float = value.as_f64().ok_or_else(|| expected("a number"))?;
+                self.data.extend_from_slice(&float.to_le_bytes());
+            }
+            "string" => {
+                let text = value.as_str().ok_or_else(|| expected("a string"))?;
+                self.length(text.len(), path)?;
+                self.data.extend_from_slice(text.as_bytes());
+            }
+            "bytes" => {
+                let bytes = value
+                    .as_array()
+                    .and_then(|bytes| {
+                        bytes
+                            .iter()
+                            .map(|byte| byte.as_u64().and_then(|byte| u8::try_from(byte).ok()))
+                            .collect::<Option<Vec<u8>>>()
+                    })
+                    .ok_or_else(|| expected("an array of bytes"))?;
+                self.length(bytes.len(), path)?;
+                self.data.extend_from_slice(&bytes);
+            }
+            "pubkey" | "publicKey" => {
+                let pubkey = value
+                    .as_str()
+                    .and_then(|text| Pubkey::from_str(text).ok())
+                    .ok_or_else(|| expected("a base58 pubkey"))?;
+                self.data.extend_from_slice(pubkey.as_ref());
+            }
+            _ => return Err(None),
+        }
+        Ok(())
+    }
+
+    /// Serialize `value` as the type defined as `name`.
+    fn encode_defined(&mut self, name: &str, value: &Value, path: &str) -> Result<(), String> {
+        let definition = self.definition(name).ok_or_else(|| {
+            format!("{} is of type {name}, which the IDL doesn't define", field(path))
+        })?;
+        if let Some(serialization) = definition["serialization"].as_str() {
+            if serialization != "borsh" {
+                return Err(format!(
+                    "{} is of type {name}, which uses {serialization} serialization rather \
+                     than borsh",
+                    field(path)
+                ));
+            }
+        }
+        if definition["generics"].as_array().is_some_and(|generics| !generics.is_empty()) {
+            return Err(format!("{} is of the generic type {name}", field(path)));
+        }
+        let ty = &definition["type"];
+        match ty["kind"].as_str() {
+            Some("struct") => self.encode_fields(&ty["fields"], value, path),
+            Some("enum") => self.encode_enum(name, &ty["variants"], value, path),
+            Some("type") => self.encode(&ty["alias"], value, path),
+            _ => Err(format!("{} is of type {name}, of an unsupported kind {ty}", field(path))),
+        }
+    }
+
+    /// Serialize the fields of a struct or enum variant: named fields from a JSON
+    /// object, tuple fields from an array.
+    fn encode_fields(&mut self, fields: &Value, value: &Value, path: &str) -> Result<(), String> {
+        let fields = match fields {
+            Value::Null => return Ok(()),
+            Value::Array(fields) if fields.is_empty() => return Ok(()),
+            Value::Array(fields) => fields,
+            _ => return Err(format!("{} has unsupported fields {fields}", field(path))),
+        };
+        if fields.iter().all(|field| field.get("name").is_some()) {
+            let empty = Map::new();
+            let values = match value {
+                Value::Object(values) => values,
+                Value::Null => &empty,
+                _ => return Err(format!("{} should be an object, got {value}", field(path))),
+            };
+            for field_def in fields {
+                let name = field_def["name"].as_str().unwrap_or_default();
+                let field_path = join(path, name);
+                let field_value = values
+                    .get(name)
+                    .ok_or_else(|| format!("{} is missing", field(&field_path)))?;
+                self.encode(&field_def["type"], field_value, &field_path)?;
+            }
+            if let Some(unknown) = values
+                .keys()
+                .find(|key| !fields.iter().any(|field| field["name"] == key.as_str()))
+            {
+                return Err(format!("{} isn't in the IDL", field(&join(path, unknown))));
+            }
+            return Ok(());
+        }
+        let values = value
+            .as_array()
+            .filter(|values| values.len() == fields.len())
+            .ok_or_else(|| {
+                format!(
+                    "{} should be an array of {} values, got {value}",
+                    field(path),
+                    fields.len()
+                )
+            })?;
+        for (index, (ty, field_value)) in fields.iter().zip(values).enumerate() {
+            self.encode(ty, field_value, &join(path, &index.to_string()))?;
+        }
+        Ok(())
+    }
+
+    /// Serialize `value` as a variant of the enum `name`: `"Variant"` for a
+    /// variant without fields, `{"Variant": fields}` for one with.
+    fn encode_enum(
+        &mut self,
+        name: &str,
+        variants: &Value,
+        value: &Value,
+        path: &str,
+    ) -> Result<(), String> {
+        let (variant, fields) = match value {
+            Value::String(variant) => (variant.as_str(), &Value::Null),
+            Value::Object(object) if object.len() == 1 => {
+                let (variant, fields) = object.iter().next().expect("one entry");
+                (variant.as_str(), fields)
+            }
+            _ => {
+                return Err(format!(
+                    "{} should be a {name} variant, as \"Variant\" or {{\"Variant\": fields}}, \
+                     got {value}",
+                    field(path)
+                ))
+            }
+        };
+        let variants = variants.as_array().map(Vec::as_slice).unwrap_or_default();
+        let index = variants
+            .iter()
+            .position(|candidate| candidate["name"].as_str() == Some(variant))
+            .ok_or_else(|| format!("{} has no {name} variant {variant}", field(path)))?;
+        let index = u8::try_from(index)
+            .map_err(|_| format!("{} is of type {name}, with over 256 variants", field(path)))?;
+        self.data.push(index);
+        self.encode_fields(&variants[index as usize]["fields"], fields, &join(path, variant))
+    }
+}
+
+/// The account data of `account` holding `value`, and the program that owns it.
+fn build(idl_json: &str, account: &str, value: &Value) -> Result<(Pubkey, Vec<u8>), String> {
+    let idl: Value =
+        serde_json::from_str(idl_json).map_err(|e| format!("the IDL isn't valid JSON: {e}"))?;
+    let address = idl["address"]
+        .as_str()
+        .or_else(|| idl["metadata"]["address"].as_str())
+        .ok_or("the IDL doesn't give the program's address")?;
+    let program_id = Pubkey::from_str(address)
+        .map_err(|e| format!("the IDL's program address {address} is invalid: {e}"))?;
+
+    let accounts = idl["accounts"].as_array().map(Vec::as_slice).unwrap_or_default();
+    let entry = accounts
+        .iter()
+        .find(|entry| entry["name"].as_str() == Some(account))
+        .ok_or_else(|| {
+            let names: Vec<&str> =
+                accounts.iter().filter_map(|entry| entry["name"].as_str()).collect();
+            format!("the IDL has no such account (it has {})", names.join(", "))
+        })?;
+    let mut data = match entry["discriminator"].as_array() {
+        Some(bytes) => bytes
+            .iter()
+            .map(|byte| byte.as_u64().and_then(|byte| u8::try_from(byte).ok()))
+            .collect::<Option<Vec<u8>>>()
+            .filter(|bytes| bytes.len() == DISCRIMINATOR_LEN)
+            .ok_or("the IDL's discriminator isn't 8 bytes")?,
+        // Legacy IDLs leave it out: Anchor hashes the account's name
+        None => Sha256::digest(format!("account:{account}"))[..DISCRIMINATOR_LEN].to_vec(),
+    };
+
+    let mut encoder = Encoder {
+        idl: &idl,
+        data: Vec::new(),
+    };
+    encoder.encode_defined(account, value, "")?;
+    data.extend(encoder.data);
+    Ok((program_id, data))
+}
+
+impl RpcAccountStore {
+    /// Add an Anchor account at `address` holding `value`, serialized with the
+    /// layout the program's published IDL gives its `account` type, for programs
+    /// whose crates aren't at hand.
+    ///
+    /// `value` holds the struct's fields by their IDL names; integers may be
+    /// strings, to hold `u64` and `u128` values exactly, pubkeys are base58 and
+    /// options are `null` when unset. Enum values are `"Variant"`, or
+    /// `{"Variant": fields}` for a variant with fields. Every field must be given.
+    ///
+    /// ```rust,ignore
+    /// let idl = std::fs::read_to_string("tests/idl/amm.json").unwrap();
+    /// let store = RpcAccountStore::mainnet().with_anchor_account_from_idl(
+    ///     &idl,
+    ///     "PoolState",
+    ///     pool,
+    ///     json!({ "fee_bps": 30, "paused": false, "authority": authority.to_string() }),
+    /// )?;
+    /// ```
+    ///
+    /// The data is the account's 8-byte discriminator followed by the Borsh
+    /// encoding of `value`, without the padding a program may allocate for growth.
+    /// The account is owned by the IDL's program and rent-exempt at
+    /// [`rent`](Self::rent). Like any account added with
+    /// [`with_accounts`](Self::with_accounts), it's a mock.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`RpcError::AnchorIdl`] if the IDL can't be read, has no `account`,
+    /// or `value` doesn't match it; field errors name the field, as in
+    /// ``field `fees.protocol` should be a u16``. Types other than primitives,
+    /// pubkeys, options, vectors, arrays and non-generic structs and enums are
+    /// rejected the same way.
+    pub fn with_anchor_account_from_idl(
+        self,
+        idl_json: &str,
+        account: &str,
+        address: Pubkey,
+        value: Value,
+    ) -> Result<Self, RpcError> {
+        let (owner, data) =
+            build(idl_json, account, &value).map_err(|reason| RpcError::AnchorIdl {
+                account: account.to_string(),
+                reason,
+            })?;
+        let account = Account {
+            lamports: self.rent().minimum_balance(data.len()),
+            data,
+            owner,
+            executable: false,
+            rent_epoch: 0,
+        };
+        Ok(self.with_accounts([(address, account)]))
+    }
+}
@@ -0,0 +1,282 @@
+//! Exporting cloned scenarios for compute-unit benchmarks.
+//!
+//! Each scenario is a directory named after it:
+//!
+//! ```text
+//! <dir>/<name>/scenario.json           instruction and programs
+//! <dir>/<name>/accounts/<pubkey>.json  one per account, in the fixture layout
+//! <dir>/<name>/programs/<program>.so   ELFs of the programs involved
+//! ```
+
+use {
+    crate::{fixture, RpcAccountStore, RpcError},
+    base64::{engine::general_purpose::STANDARD as BASE64, Engine},
+    mollusk_svm::Mollusk,
+    serde::{Deserialize, Serialize},
+    solana_account::Account,
+    solana_instruction::{AccountMeta, Instruction},
+    solana_pubkey::Pubkey,
+    std::{
+        collections::HashSet,
+        fs, io,
+        path::{Path, PathBuf},
+        str::FromStr,
+        sync::Arc,
+    },
+};
+
+const SCENARIO_FILE: &str = "scenario.json";
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ScenarioFile {
+    name: String,
+    instruction: InstructionFile,
+    programs: Vec<ProgramFile>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct InstructionFile {
+    program_id: String,
+    accounts: Vec<MetaFile>,
+    /// Base64.
+    data: String,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct MetaFile {
+    pubkey: String,
+    is_signer: bool,
+    is_writable: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ProgramFile {
+    program_id: String,
+    loader: String,
+}
+
+/// A scenario written by [`RpcAccountStore::export_bench`], ready for
+/// `mollusk-svm-bencher`.
+///
+/// ```rust,ignore
+/// let scenarios = load_bench_scenarios("benches/scenarios")?;
+/// let mut mollusk = Mollusk::default();
+/// for scenario in &scenarios {
+///     scenario.add_programs(&mut mollusk);
+/// }
+///
+/// let mut bencher = MolluskComputeUnitBencher::new(mollusk)
+///     .must_pass(true)
+///     .out_dir("benches/results");
+/// for scenario in &scenarios {
+///     bencher = bencher.bench(scenario.as_bench());
+/// }
+/// bencher.execute();
+/// ```
+#[derive(Debug, Clone)]
+pub struct BenchScenario {
+    pub name: String,
+    pub instruction: Instruction,
+    /// The instruction's accounts, in meta order.
+    pub accounts: Vec<(Pubkey, Account)>,
+    /// `(program_id, loader, elf)` of every program exported with the scenario.
+    pub programs: Vec<(Pubkey, Pubkey, Vec<u8>)>,
+}
+
+impl BenchScenario {
+    /// Add the scenario's programs to `mollusk`.
+    pub fn add_programs(&self, mollusk: &mut Mollusk) {
+        for (program_id, loader, elf) in &self.programs {
+            mollusk.add_program_with_elf_and_loader(program_id, elf, loader);
+        }
+    }
+
+    /// The scenario in the shape `MolluskComputeUnitBencher::bench` takes.
+    pub fn as_bench(&self) -> (&str, &Instruction, &[(Pubkey, Account)]) {
+        (&self.name, &self.instruction, &self.accounts)
+    }
+}
+
+fn fixture_error(path: &Path, reason: impl ToString) -> RpcError {
+    RpcError::Fixture {
+        path: path.to_path_buf(),
+        reason: reason.to_string(),
+    }
+}
+
+fn parse_pubkey(path: &Path, pubkey: &str) -> Result<Pubkey, RpcError> {
+    Pubkey::from_str(pubkey)
+        .map_err(|e| fixture_error(path, format!("invalid pubkey {pubkey}: {e}")))
+}
+
+impl RpcAccountStore {
+    /// Write `instruction`, its accounts and the ELFs of the programs it uses to
+    /// `dir/name`, for [`load_bench_scenarios`] to run without RPC.
+    ///
+    /// Accounts come from [`instruction_accounts`](Self::instruction_accounts), so
+    /// they must be cached. Programs Mollusk provides itself (builtins) aren't
+    /// exported. Exporting under an existing name replaces that scenario; every
+    /// other scenario in `dir` is left alone. Returns the scenario's directory.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`RpcError::Fixture`] if `name` isn't a plain directory name or a
+    /// file can't be written.
+    pub fn export_bench(
+        &self,
+        mollusk: &Mollusk,
+        instruction: &Instruction,
+        name: &str,
+        dir: impl AsRef<Path>,
+    ) -> Result<PathBuf, RpcError> {
+        let scenario_dir = dir.as_ref().join(name);
+        if name.is_empty() || Path::new(name).file_name() != Some(name.as_ref()) {
+            return Err(fixture_error(
+                &scenario_dir,
+                "scenario names must be plain directory names",
+            ));
+        }
+        let accounts = self.instruction_accounts(mollusk, instruction)?;
+
+        let mut seen = HashSet::new();
+        let programs: Vec<(Pubkey, &Account, Arc<[u8]>)> =
+            std::iter::once(&instruction.program_id)
+                .chain(instruction.accounts.iter().map(|meta| &meta.pubkey))
+                .filter(|pubkey| seen.insert(**pubkey))
+                .filter_map(|pubkey| self.cache.get(pubkey).map(|account| (pubkey, account)))
+                .filter_map(|(pubkey, account)| match self.shared_elf(pubkey, account) {
+                    Ok(elf) => elf.map(|elf| Ok((*pubkey, account, elf))),
+                    Err(e) => Some(Err(e)),
+                })
+                .collect::<Result<_, _>>()?;
+
+        let write = |path: PathBuf, contents: &[u8]| -> Result<(), RpcError> {
+            fs::create_dir_all(path.parent().expect("scenario files live in a directory"))
+                .and_then(|()| fs::write(&path, contents))
+                .map_err(|e| fixture_error(&path, e))
+        };
+        // Replace the scenario wholesale, so no stale accounts or programs remain.
+        if let Err(e) = fs::remove_dir_all(&scenario_dir) {
+            if e.kind() != io::ErrorKind::NotFound {
+                return Err(fixture_error(&scenario_dir, e));
+            }
+        }
+
+        for (pubkey, account) in &accounts {
+            let path = fixture::account_path(&scenario_dir.join("accounts"), pubkey);
+            write(path, fixture::encode_account(pubkey, account).as_bytes())?;
+        }
+        for (program_id, _, elf) in &programs {
+            write(scenario_dir.join("programs").join(format!("{program_id}.so")), elf)?;
+        }
+
+        let scenario = ScenarioFile {
+            name: name.to_string(),
+            instruction: InstructionFile {
+                program_id: instruction.program_id.to_string(),
+                accounts: instruction
+                    .accounts
+                    .iter()
+                    .map(|meta| MetaFile {
+                        pubkey: meta.pubkey.to_string(),
+                        is_signer: meta.is_signer,
+                        is_writable: meta.is_writable,
+                    })
+                    .collect(),
+                data: BASE64.encode(&instruction.data),
+            },
+            programs: programs
+                .iter()
+                .map(|(program_id, account, _)| ProgramFile {
+                    program_id: program_id.to_string(),
+                    loader: account.owner.to_string(),
+                })
+                .collect(),
+        };
+        let json = serde_json::to_string_pretty(&scenario).expect("scenarios always serialize");
+        write(scenario_dir.join(SCENARIO_FILE), json.as_bytes())?;
+        Ok(scenario_dir)
+    }
+}
+
+/// Load every scenario [`export_bench`](RpcAccountStore::export_bench) wrote to
+/// `dir`, sorted by name.
+///
+/// # Errors
+///
+/// Returns [`RpcError::Fixture`] if a scenario can't be read.
+pub fn load_bench_scenarios(dir: impl AsRef<Path>) -> Result<Vec<BenchScenario>, RpcError> {
+    let dir = dir.as_ref();
+    let mut scenario_dirs = Vec::new();
+    for entry in fs::read_dir(dir).map_err(|e| fixture_error(dir, e))? {
+        let path = entry.map_err(|e| fixture_error(dir, e))?.path();
+        if path.join(SCENARIO_FILE).is_file() {
+            scenario_dirs.push(path);
+        }
+    }
+    scenario_dirs.sort();
+    scenario_dirs.iter().map(|path| load_scenario(path)).collect()
+}
+
+fn load_scenario(scenario_dir: &Path) -> Result<BenchScenario, RpcError> {
+    let path = scenario_dir.join(SCENARIO_FILE);
+    let json = fs::read_to_string(&path).map_err(|e| fixture_error(&path, e))?;
+    let scenario: ScenarioFile =
+        serde_json::from_str(&json).map_err(|e| fixture_error(&path, e))?;
+
+    let instruction = Instruction {
+        program_id: parse_pubkey(&path, &scenario.instruction.program_id)?,
+        accounts: scenario
+            .instruction
+            .accounts
+            .iter()
+            .map(|meta| {
+                Ok(AccountMeta {
+                    pubkey: parse_pubkey(&path, &meta.pubkey)?,
+                    is_signer: meta.is_signer,
+                    is_writable: meta.is_writable,
+                })
+            })
+            .collect::<Result<_, RpcError>>()?,
+        data: BASE64
+            .decode(&scenario.instruction.data)
+            .map_err(|e| fixture_error(&path, format!("invalid base64 data: {e}")))?,
+    };
+
+    let accounts_dir = scenario_dir.join("accounts");
+    let accounts = instruction
+        .accounts
+        .iter()
+        .map(|meta| {
+            let account_path = fixture::account_path(&accounts_dir, &meta.pubkey);
+            match fixture::read_account(&accounts_dir, &meta.pubkey) {
+                Ok(Some(account)) => Ok((meta.pubkey, account)),
+                Ok(None) => Err(fixture_error(&account_path, "account missing from scenario")),
+                Err(reason) => Err(fixture_error(&account_path, reason)),
+            }
+        })
+        .collect::<Result<_, _>>()?;
+
+    let programs = scenario
+        .programs
+        .iter()
+        .map(|program| {
+            let program_id = parse_pubkey(&path, &program.program_id)?;
+            let loader = parse_pubkey(&path, &program.loader)?;
+            let elf_path = scenario_dir.join("programs").join(format!("{program_id}.so"));
+            let elf = fs::read(&elf_path).map_err(|e| fixture_error(&elf_path, e))?;
+            Ok((program_id, loader, elf))
+        })
+        .collect::<Result<_, RpcError>>()?;
+
+    Ok(BenchScenario {
+        name: scenario.name,
+        instruction,
+        accounts,
+        programs,
+    })
+}
@@ -0,0 +1,256 @@
+//! `mollusk-fetch`: capture fixtures in a step with network access, for tests that
+//! replay them without one.
+//!
+//! ```text
+//! mollusk-fetch fetch --pubkeys <a,b,c> --out <dir>
+//! mollusk-fetch fetch --instruction <base64> --out <dir>
+//! mollusk-fetch program --id <program> --out <file.so>
+//! mollusk-fetch refresh --fixture <file or dir>
+//! ```
+//!
+//! Every command goes through `RpcAccountStore`, so chunking, retries, rate limits and
+//! program loading behave exactly as in tests. The store is configured like
+//! `RpcAccountStore::from_env`, from `--config` if given, and `--url` overrides the
+//! endpoint.
+//!
+//! Errors are printed to stderr as `error: <message>`, and the exit code tells what
+//! went wrong: 1 for any failure not listed here, 2 for invalid arguments, 3 if an
+//! account doesn't exist, 4 if the RPC provider failed in a way that's worth
+//! retrying.
+
+use {
+    base64::{engine::general_purpose::STANDARD as BASE64, Engine},
+    clap::{Parser, Subcommand},
+    mollusk_on_demand::{Drift, RpcAccountStore, RpcError, RpcStoreConfig},
+    mollusk_svm::Mollusk,
+    solana_instruction::Instruction,
+    solana_pubkey::Pubkey,
+    std::{
+        fs,
+        path::{Path, PathBuf},
+        process::ExitCode,
+        str::FromStr,
+    },
+};
+
+#[derive(Parser)]
+#[command(name = "mollusk-fetch", version, about = "Capture fixtures for mollusk-on-demand")]
+struct Cli {
+    /// RPC endpoint, overriding `MOLLUSK_RPC_URL` and the config file.
+    #[arg(long, global = true)]
+    url: Option<String>,
+    /// TOML store configuration, as read by `RpcStoreConfig::from_file`.
+    #[arg(long, global = true)]
+    config: Option<PathBuf>,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Record accounts into a fixture directory, keeping those already recorded.
+    Fetch {
+        /// Comma-separated pubkeys.
+        #[arg(long, value_delimiter = ',', required_unless_present = "instruction")]
+        pubkeys: Vec<Pubkey>,
+        /// A bincode-serialized `Instruction`, base64-encoded. Its accounts, program
+        /// and program data are recorded.
+        #[arg(long, conflicts_with = "pubkeys")]
+        instruction: Option<String>,
+        /// Fixture directory, as passed to `with_fixture_dir`.
+        #[arg(long)]
+        out: PathBuf,
+    },
+    /// Write the ELF of a BPF Loader v2 or v3 program.
+    Program {
+        #[arg(long)]
+        id: Pubkey,
+        #[arg(long)]
+        out: PathBuf,
+    },
+    /// Update fixtures whose accounts changed on-chain.
+    Refresh {
+        /// A fixture file, or a fixture directory to refresh every fixture of.
+        #[arg(long)]
+        fixture: PathBuf,
+    },
+}
+
+/// Why a command failed.
+enum Failure {
+    Store(RpcError),
+    Usage(String),
+    Other(String),
+}
+
+impl From<RpcError> for Failure {
+    fn from(error: RpcError) -> Self {
+        Self::Store(error)
+    }
+}
+
+impl Failure {
+    fn exit_code(&self) -> u8 {
+        match self {
+            Self::Store(error) if error.is_not_found() => 3,
+            Self::Store(error) if error.is_retryable() => 4,
+            Self::Store(_) | Self::Other(_) => 1,
+            Self::Usage(_) => 2,
+        }
+    }
+
+    fn message(&self) -> String {
+        match self {
+            Self::Store(error) => error.to_string(),
+            Self::Usage(message) | Self::Other(message) => message.clone(),
+        }
+    }
+}
+
+fn store(cli: &Cli) -> Result<RpcAccountStore, RpcError> {
+    let mut config = match &cli.config {
+        Some(path) => RpcStoreConfig::from_file(path)?,
+        None => RpcStoreConfig::default().with_env_overrides()?,
+    };
+    if let Some(url) = &cli.url {
+        config.rpc_url = url.clone();
+    }
+    // Each command picks the fixture directory itself
+    config.fixture_dir = None;
+    Ok(RpcAccountStore::from_config(config))
+}
+
+/// Summarize what a fetch recorded into `dir`.
+fn report(store: &RpcAccountStore, dir: &Path) {
+    let stats = store.stats();
+    println!(
+        "{} accounts in {}: {} fetched, {} already recorded",
+        stats.accounts_fetched + stats.accounts_from_fixtures,
+        dir.display(),
+        stats.accounts_fetched,
+        stats.accounts_from_fixtures
+    );
+}
+
+async fn fetch_pubkeys(
+    store: RpcAccountStore,
+    pubkeys: &[Pubkey],
+    out: &Path,
+) -> Result<(), Failure> {
+    let mut store = store.with_fixture_dir(out);
+    store.get_or_fetch_many(pubkeys).await?;
+    report(&store, out);
+    Ok(())
+}
+
+async fn fetch_instruction(
+    store: RpcAccountStore,
+    instruction: &str,
+    out: &Path,
+) -> Result<(), Failure> {
+    let instruction: Instruction = BASE64
+        .decode(instruction)
+        .map_err(|e| e.to_string())
+        .and_then(|bytes| bincode::deserialize(&bytes).map_err(|e| e.to_string()))
+        .map_err(|e| Failure::Usage(format!("invalid --instruction: {e}")))?;
+    let mut store = store.with_fixture_dir(out).from_instruction(&instruction).await?;
+    store.get_or_fetch(&instruction.program_id).await?;
+    let store = store.add_programs(&mut Mollusk::default()).await?;
+    report(&store, out);
+    Ok(())
+}
+
+async fn program(store: RpcAccountStore, id: &Pubkey, out: &Path) -> Result<(), Failure> {
+    let mut store = store.force_reload(&[*id]);
+    store.get_or_fetch(id).await?;
+    let store = store.add_programs(&mut Mollusk::default()).await?;
+    let elf = store.cached_program_elf(id)?;
+    fs::write(out, elf)
+        .map_err(|e| Failure::Other(format!("can't write {}: {e}", out.display())))?;
+    println!("{} bytes of {id} in {}", elf.len(), out.display());
+    Ok(())
+}
+
+/// The fixture directory and pubkeys `path` names: a single `<pubkey>.json`
+/// file, or every such file in a directory.
+fn fixture_targets(path: &Path) -> Result<(PathBuf, Vec<Pubkey>), Failure> {
+    let pubkey_of = |path: &Path| {
+        let is_json = path.extension().is_some_and(|extension| extension == "json");
+        path.file_stem()
+            .and_then(|stem| stem.to_str())
+            .filter(|_| is_json)
+            .and_then(|stem| Pubkey::from_str(stem).ok())
+    };
+    if !path.is_dir() {
+        let pubkey = pubkey_of(path).ok_or_else(|| {
+            Failure::Usage(format!("{} isn't a `<pubkey>.json` fixture", path.display()))
+        })?;
+        let dir = path.parent().map_or_else(PathBuf::new, Path::to_path_buf);
+        return Ok((dir, vec![pubkey]));
+    }
+    let entries = fs::read_dir(path)
+        .map_err(|e| Failure::Other(format!("can't read {}: {e}", path.display())))?;
+    let mut pubkeys = Vec::new();
+    for entry in entries {
+        let entry =
+            entry.map_err(|e| Failure::Other(format!("can't read {}: {e}", path.display())))?;
+        pubkeys.extend(pubkey_of(&entry.path()));
+    }
+    pubkeys.sort();
+    Ok((path.to_path_buf(), pubkeys))
+}
+
+async fn refresh(store: RpcAccountStore, fixture: &Path) -> Result<(), Failure> {
+    let (dir, pubkeys) = fixture_targets(fixture)?;
+    let mut store = store.with_fixture_dir(&dir);
+    store.get_or_fetch_many(&pubkeys).await?;
+    let report = store.verify_fresh(&pubkeys).await?;
+    println!("{report}");
+
+    let mut missing = Vec::new();
+    let mut changed = Vec::new();
+    for (pubkey, drift) in report.drifted() {
+        if *drift == Drift::Missing {
+            missing.push(*pubkey);
+        } else {
+            changed.push(*pubkey);
+        }
+    }
+    store.refresh_drifted(&report);
+    store.write_fixtures(&dir, &changed)?;
+    match missing.as_slice() {
+        [] => Ok(()),
+        [pubkey] => Err(RpcError::AccountNotFound(*pubkey).into()),
+        _ => Err(RpcError::AccountsNotFound(missing).into()),
+    }
+}
+
+async fn run(cli: Cli) -> Result<(), Failure> {
+    let store = store(&cli)?;
+    match &cli.command {
+        Command::Fetch {
+            pubkeys,
+            instruction: Some(instruction),
+            out,
+        } if pubkeys.is_empty() => fetch_instruction(store, instruction, out).await,
+        Command::Fetch { pubkeys, out, .. } => fetch_pubkeys(store, pubkeys, out).await,
+        Command::Program { id, out } => program(store, id, out).await,
+        Command::Refresh { fixture } => refresh(store, fixture).await,
+    }
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    let result = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .map_err(|e| Failure::Other(format!("can't start the runtime: {e}")))
+        .and_then(|runtime| runtime.block_on(run(cli)));
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(failure) => {
+            eprintln!("error: {}", failure.message());
+            ExitCode::from(failure.exit_code())
+        }
+    }
+}
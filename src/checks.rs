@@ -0,0 +1,80 @@
+//! Mollusk checks derived from the cached pre-state.
+
+use {
+    crate::{RpcAccountStore, RpcError},
+    mollusk_svm::result::Check,
+    solana_account::Account,
+    solana_pubkey::Pubkey,
+};
+
+impl RpcAccountStore {
+    /// Checks that each account ends up exactly as it is in the cache: same
+    /// lamports, owner, data and executable flag.
+    ///
+    /// ```rust,ignore
+    /// let mut checks = vec![Check::success()];
+    /// checks.extend(store.expect_unchanged(&[config, oracle])?);
+    /// checks.extend(store.expect_lamport_delta(&recipient, 1_000)?);
+    /// mollusk.process_and_validate_instruction(&ix, &accounts, &checks);
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns [`RpcError::AccountNotFound`] if an account isn't cached.
+    pub fn expect_unchanged(&self, pubkeys: &[Pubkey]) -> Result<Vec<Check<'_>>, RpcError> {
+        pubkeys
+            .iter()
+            .map(|pubkey| {
+                let (pubkey, account) = self.cached(pubkey)?;
+                Ok(Check::account(pubkey)
+                    .lamports(account.lamports)
+                    .owner(&account.owner)
+                    .data(&account.data)
+                    .executable(account.executable)
+                    .build())
+            })
+            .collect()
+    }
+
+    /// A check that the account's lamports change by exactly `delta`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`RpcError::AccountNotFound`] if the account isn't cached.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `delta` would take the balance below zero or past `u64::MAX`.
+    pub fn expect_lamport_delta(
+        &self,
+        pubkey: &Pubkey,
+        delta: i64,
+    ) -> Result<Vec<Check<'_>>, RpcError> {
+        let (pubkey, account) = self.cached(pubkey)?;
+        let lamports = account.lamports.checked_add_signed(delta).unwrap_or_else(|| {
+            panic!("{pubkey} holds {} lamports, can't change by {delta}", account.lamports)
+        });
+        Ok(vec![Check::account(pubkey).lamports(lamports).build()])
+    }
+
+    /// A check that the account's data holds `bytes` at `offset` afterwards.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`RpcError::AccountNotFound`] if the account isn't cached.
+    pub fn expect_data_eq<'a>(
+        &'a self,
+        pubkey: &Pubkey,
+        offset: usize,
+        bytes: &'a [u8],
+    ) -> Result<Vec<Check<'a>>, RpcError> {
+        let (pubkey, _) = self.cached(pubkey)?;
+        Ok(vec![Check::account(pubkey).data_slice(offset, bytes).build()])
+    }
+
+    fn cached(&self, pubkey: &Pubkey) -> Result<(&Pubkey, &Account), RpcError> {
+        self.cache
+            .get_key_value(pubkey)
+            .ok_or(RpcError::AccountNotFound(*pubkey))
+    }
+}
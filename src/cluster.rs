@@ -0,0 +1,92 @@
+//! Identifying clusters by their genesis hash.
+
+use {
+    serde::{Deserialize, Serialize},
+    solana_hash::Hash,
+    std::{fmt, str::FromStr},
+};
+
+const MAINNET_GENESIS_HASH: &str = "5eykt4UsFv8P8NJdTREpY1vzqKqZKvdpKuc147dw2N9d";
+const DEVNET_GENESIS_HASH: &str = "EtWTRABZaYq6iMfeYKouRu166VU2xqa1wcaWoxPkrZBG";
+const TESTNET_GENESIS_HASH: &str = "4uhcVJyU9pJkvQyS88uRDiswHXSCkY3zQawwpjk2NsNY";
+
+/// A Solana cluster, see [`RpcAccountStore::expect_cluster`](crate::RpcAccountStore::expect_cluster).
+///
+/// Parses from, and serializes as, `mainnet-beta` (or `mainnet`), `devnet`, `testnet`
+/// or a base58 genesis hash.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum Cluster {
+    Mainnet,
+    Devnet,
+    Testnet,
+    /// Any other cluster, e.g. a local validator, identified by its genesis hash.
+    Custom(Hash),
+}
+
+impl Cluster {
+    /// The cluster's genesis hash.
+    pub fn genesis_hash(&self) -> Hash {
+        match self {
+            Self::Mainnet => Hash::from_str(MAINNET_GENESIS_HASH),
+            Self::Devnet => Hash::from_str(DEVNET_GENESIS_HASH),
+            Self::Testnet => Hash::from_str(TESTNET_GENESIS_HASH),
+            Self::Custom(hash) => Ok(*hash),
+        }
+        .expect("well-known genesis hashes are valid")
+    }
+
+    /// The well-known cluster with `genesis_hash`, or [`Cluster::Custom`].
+    pub fn from_genesis_hash(genesis_hash: Hash) -> Self {
+        [Self::Mainnet, Self::Devnet, Self::Testnet]
+            .into_iter()
+            .find(|cluster| cluster.genesis_hash() == genesis_hash)
+            .unwrap_or(Self::Custom(genesis_hash))
+    }
+}
+
+impl fmt::Display for Cluster {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Mainnet => f.write_str("mainnet-beta"),
+            Self::Devnet => f.write_str("devnet"),
+            Self::Testnet => f.write_str("testnet"),
+            Self::Custom(hash) => write!(f, "cluster with genesis hash {hash}"),
+        }
+    }
+}
+
+impl FromStr for Cluster {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "mainnet" | "mainnet-beta" => Ok(Self::Mainnet),
+            "devnet" => Ok(Self::Devnet),
+            "testnet" => Ok(Self::Testnet),
+            _ => Hash::from_str(s).map(Self::Custom).map_err(|_| {
+                format!(
+                    "unknown cluster {s:?} (expected mainnet-beta, devnet, testnet or a \
+                     genesis hash)"
+                )
+            }),
+        }
+    }
+}
+
+impl TryFrom<String> for Cluster {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl From<Cluster> for String {
+    fn from(cluster: Cluster) -> Self {
+        match cluster {
+            Cluster::Custom(hash) => hash.to_string(),
+            cluster => cluster.to_string(),
+        }
+    }
+}
@@ -0,0 +1,178 @@
+//! Rust source for cached accounts, for tests that compile their accounts in instead
+//! of reading fixtures at runtime.
+
+use {
+    crate::{RpcAccountStore, RpcError},
+    solana_account::Account,
+    solana_pubkey::Pubkey,
+    std::{collections::HashSet, fs, path::Path},
+};
+
+/// Bytes of account data per line of a generated byte string.
+const BYTES_PER_LINE: usize = 48;
+
+/// Keywords that can't name the generated module.
+const KEYWORDS: [&str; 51] = [
+    "as", "async", "await", "break", "const", "continue", "crate", "dyn", "else", "enum",
+    "extern", "false", "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod", "move",
+    "mut", "pub", "ref", "return", "self", "Self", "static", "struct", "super", "trait", "true",
+    "type", "unsafe", "use", "where", "while", "abstract", "become", "box", "do", "final",
+    "macro", "override", "priv", "typeof", "unsized", "virtual", "yield", "try",
+];
+
+fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    let starts_well = chars
+        .next()
+        .is_some_and(|first| first.is_ascii_alphabetic() || first == '_');
+    starts_well
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+        && name != "_"
+        && !KEYWORDS.contains(&name)
+}
+
+/// `alice-usdc` as `ALICE_USDC`.
+fn constant_name(label: &str) -> String {
+    let name: String = label
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_uppercase()
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    if name.starts_with(|c: char| c.is_ascii_alphabetic()) {
+        name
+    } else {
+        format!("ACCOUNT_{name}")
+    }
+}
+
+/// `data` as a byte string literal, wrapped with line continuations.
+fn byte_string(data: &[u8], indent: &str) -> String {
+    let mut literal = String::from("b\"");
+    for (i, chunk) in data.chunks(BYTES_PER_LINE).enumerate() {
+        if i > 0 {
+            // A continuation skips leading whitespace, so the next line can't start
+            // with an unescaped space
+            literal.push_str(&format!("\\\n{indent}"));
+        }
+        for (j, byte) in chunk.iter().enumerate() {
+            if j == 0 && i > 0 && *byte == b' ' {
+                literal.push_str("\\x20");
+            } else {
+                literal.extend(byte.escape_ascii().map(char::from));
+            }
+        }
+    }
+    literal.push('"');
+    literal
+}
+
+fn write_account(out: &mut String, pubkey: &Pubkey, account: &Account) {
+    let Account {
+        lamports,
+        data,
+        owner,
+        executable,
+        rent_epoch,
+    } = account;
+    let data = byte_string(data, "                    ");
+    out.push_str(&format!(
+        "            (
+                Pubkey::from_str_const(\"{pubkey}\"),
+                Account {{
+                    lamports: {lamports},
+                    data: {data}.to_vec(),
+                    owner: Pubkey::from_str_const(\"{owner}\"),
+                    executable: {executable},
+                    rent_epoch: {rent_epoch},
+                }},
+            ),
+"
+    ));
+}
+
+impl RpcAccountStore {
+    /// Write every cached account into the Rust source file `path`, as a module
+    /// named `module_name` holding `pub fn accounts() -> Vec<(Pubkey, Account)>`,
+    /// sorted by pubkey, and a `pub const` pubkey per labeled account:
+    ///
+    /// ```rust,ignore
+    /// store.label(&pool, "pool");
+    /// store.generate_rust_module("tests/accounts.rs", "swap_accounts")?;
+    ///
+    /// // In the test crate
+    /// include!("accounts.rs");
+    ///
+    /// let mollusk = Mollusk::default();
+    /// let accounts = swap_accounts::accounts();
+    /// let pool = swap_accounts::POOL;
+    /// ```
+    ///
+    /// Data is embedded as byte strings, so the accounts compile back byte for byte.
+    /// The file has no inner attributes or doc comments, so it can be `include!`d
+    /// as well as declared with `#[path]`; the crate using it needs
+    /// `solana-account` and `solana-pubkey`. Labels become `SCREAMING_SNAKE_CASE`
+    /// constants, with a numeric suffix if two labels map to the same name.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`RpcError::Fixture`] if `module_name` isn't a Rust identifier or the
+    /// file can't be written.
+    pub fn generate_rust_module(
+        &self,
+        path: impl AsRef<Path>,
+        module_name: &str,
+    ) -> Result<(), RpcError> {
+        let path = path.as_ref();
+        if !is_identifier(module_name) {
+            return Err(RpcError::Fixture {
+                path: path.to_path_buf(),
+                reason: format!("`{module_name}` isn't a valid Rust module name"),
+            });
+        }
+        let mut pubkeys: Vec<&Pubkey> = self.cache.keys().collect();
+        pubkeys.sort();
+
+        let mut out = format!(
+            "// Generated by mollusk-on-demand's `RpcAccountStore::generate_rust_module`.\n\
+             // Do not edit.\n\n\
+             #[allow(dead_code, unused_imports)]\n\
+             pub mod {module_name} {{\n    \
+             use ::solana_account::Account;\n    \
+             use ::solana_pubkey::Pubkey;\n\n"
+        );
+        let mut constants = HashSet::new();
+        for pubkey in &pubkeys {
+            let Some(label) = self.label_of(pubkey) else {
+                continue;
+            };
+            let base = constant_name(label);
+            let mut name = base.clone();
+            let mut suffix = 2;
+            while !constants.insert(name.clone()) {
+                name = format!("{base}_{suffix}");
+                suffix += 1;
+            }
+            out.push_str(&format!(
+                "    pub const {name}: Pubkey = Pubkey::from_str_const(\"{pubkey}\");\n"
+            ));
+        }
+        if !constants.is_empty() {
+            out.push('\n');
+        }
+        out.push_str("    pub fn accounts() -> Vec<(Pubkey, Account)> {\n        vec![\n");
+        for pubkey in pubkeys {
+            write_account(&mut out, pubkey, &self.cache[pubkey]);
+        }
+        out.push_str("        ]\n    }\n}\n");
+
+        fs::write(path, out).map_err(|e| RpcError::Fixture {
+            path: path.to_path_buf(),
+            reason: e.to_string(),
+        })
+    }
+}
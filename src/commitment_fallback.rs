@@ -0,0 +1,99 @@
+//! Retrying accounts reported missing at lower commitments, for accounts created
+//! just before the fetch.
+
+use {
+    crate::{RpcAccountStore, RpcError},
+    solana_account::Account,
+    solana_commitment_config::CommitmentConfig,
+    solana_pubkey::Pubkey,
+    solana_rpc_client_api::request::MAX_MULTIPLE_ACCOUNTS,
+    std::{collections::HashMap, sync::MutexGuard},
+};
+
+impl RpcAccountStore {
+    /// Before concluding that an account doesn't exist, request it again at each of
+    /// `commitments` in turn until one finds it.
+    ///
+    /// A test that sends a setup transaction and waits for it to be confirmed can
+    /// still see the account missing when the read lands on a node that lags behind.
+    /// Retrying just the missing pubkeys at `processed` finds it there:
+    ///
+    /// ```rust,ignore
+    /// let store = RpcAccountStore::new(rpc_url)
+    ///     .with_commitment_fallback(&[CommitmentConfig::processed()])
+    ///     .from_instruction(&instruction)
+    ///     .await?;
+    /// ```
+    ///
+    /// [`Provenance::Fetched`](crate::Provenance) records the commitment of accounts
+    /// found by a fallback. Accounts still missing afterwards are handled as usual,
+    /// by [`allow_missing_accounts`](Self::allow_missing_accounts) or an
+    /// [`RpcError::AccountNotFound`]. The retries are `getMultipleAccounts` requests
+    /// like the others: they count against the budget and go to the endpoint the
+    /// pubkeys are [routed](Self::with_endpoint_for) to.
+    pub fn with_commitment_fallback(mut self, commitments: &[CommitmentConfig]) -> Self {
+        self.commitment_fallback = commitments.to_vec();
+        self
+    }
+
+    /// The commitments that fallbacks found accounts at, until they're cached.
+    pub(crate) fn found_at_fallback(&self) -> MutexGuard<'_, HashMap<Pubkey, CommitmentConfig>> {
+        self.found_at_fallback.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Request the accounts of `results` that are missing at the fallback
+    /// commitments, filling in those found and remembering at which commitment.
+    pub(crate) async fn fetch_missing_at_fallbacks(
+        &self,
+        results: &mut [(Pubkey, Option<Account>)],
+    ) -> Result<(), RpcError> {
+        {
+            // Forget the commitments found earlier for accounts fetched again
+            let mut found = self.found_at_fallback();
+            for (pubkey, _) in results.iter() {
+                found.remove(pubkey);
+            }
+        }
+        for commitment in &self.commitment_fallback {
+            let missing: Vec<usize> = (0..results.len())
+                .filter(|index| results[*index].1.is_none())
+                .collect();
+            if missing.is_empty() {
+                break;
+            }
+            let pubkeys: Vec<Pubkey> = missing.iter().map(|index| results[*index].0).collect();
+            for route in self.routes_of(&pubkeys) {
+                let fetcher = self.route_fetcher(route.endpoint);
+                for (chunk, positions) in route
+                    .pubkeys
+                    .chunks(MAX_MULTIPLE_ACCOUNTS)
+                    .zip(route.positions.chunks(MAX_MULTIPLE_ACCOUNTS))
+                {
+                    self.begin_request("getMultipleAccounts").await?;
+                    let accounts = fetcher
+                        .get_multiple_accounts_at(chunk, *commitment)
+                        .await
+                        .map_err(|e| self.client_error(e))?;
+                    {
+                        let mut stats = self.stats_mut();
+                        stats.accounts_fetched += chunk.len() as u64;
+                        stats.bytes_downloaded += accounts
+                            .iter()
+                            .flatten()
+                            .map(|account| account.data.len() as u64)
+                            .sum::<u64>();
+                    }
+                    for (position, account) in positions.iter().zip(accounts) {
+                        let Some(account) = account else {
+                            continue;
+                        };
+                        let result = &mut results[missing[*position]];
+                        self.found_at_fallback().insert(result.0, *commitment);
+                        result.1 = Some(account);
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
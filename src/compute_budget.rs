@@ -0,0 +1,80 @@
+//! ComputeBudget instructions of replayed transactions, and the budget they ask
+//! Mollusk for.
+
+use {
+    crate::RpcAccountStore,
+    mollusk_svm::Mollusk,
+    solana_instruction::Instruction,
+    solana_sdk_ids::compute_budget,
+};
+
+/// `ComputeBudgetInstruction::SetComputeUnitLimit`, followed by a `u32`.
+const SET_COMPUTE_UNIT_LIMIT: u8 = 2;
+
+/// `ComputeBudgetInstruction::SetComputeUnitPrice`, followed by a `u64`.
+const SET_COMPUTE_UNIT_PRICE: u8 = 3;
+
+/// Most compute units a transaction can ask for.
+const MAX_COMPUTE_UNIT_LIMIT: u32 = 1_400_000;
+
+/// The compute budget requested by the ComputeBudget instructions the store has
+/// seen, see [`RpcAccountStore::compute_budget`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ComputeBudgetRequest {
+    /// From `SetComputeUnitLimit`.
+    pub compute_unit_limit: Option<u32>,
+    /// Micro-lamports per compute unit, from `SetComputeUnitPrice`. Mollusk
+    /// doesn't charge fees, so it's only recorded.
+    pub compute_unit_price: Option<u64>,
+}
+
+/// Whether `instruction` is for the ComputeBudget program, which only configures
+/// the transaction and has nothing to fetch or run.
+pub(crate) fn is_compute_budget(instruction: &Instruction) -> bool {
+    instruction.program_id == compute_budget::ID
+}
+
+impl RpcAccountStore {
+    /// The compute unit limit and price set by the ComputeBudget instructions
+    /// passed to [`from_instruction`](Self::from_instruction) and
+    /// [`from_instructions`](Self::from_instructions), the last one winning.
+    pub fn compute_budget(&self) -> ComputeBudgetRequest {
+        self.compute_budget
+    }
+
+    /// Give `mollusk` the compute unit limit the replayed instructions set, capped
+    /// at 1.4M like the runtime does. Without a `SetComputeUnitLimit`, Mollusk's
+    /// limit is left alone.
+    ///
+    /// ```rust,ignore
+    /// // [set_compute_unit_limit, set_compute_unit_price, swap]
+    /// let mut store = RpcAccountStore::mainnet().from_instructions(&instructions).await?;
+    /// store.apply_compute_budget(&mut mollusk);
+    /// let results = store.run_chain(&mut mollusk, &instructions).await?; // runs the swap
+    /// ```
+    pub fn apply_compute_budget(&self, mollusk: &mut Mollusk) {
+        if let Some(limit) = self.compute_budget.compute_unit_limit {
+            mollusk.compute_budget.compute_unit_limit = limit.min(MAX_COMPUTE_UNIT_LIMIT).into();
+        }
+    }
+
+    /// Remember the limit and price set by the ComputeBudget instructions among
+    /// `instructions`.
+    pub(crate) fn track_compute_budget(&mut self, instructions: &[Instruction]) {
+        for instruction in instructions.iter().filter(|ix| is_compute_budget(ix)) {
+            match instruction.data.split_first() {
+                Some((&SET_COMPUTE_UNIT_LIMIT, limit)) => {
+                    if let Ok(limit) = limit.try_into() {
+                        self.compute_budget.compute_unit_limit = Some(u32::from_le_bytes(limit));
+                    }
+                }
+                Some((&SET_COMPUTE_UNIT_PRICE, price)) => {
+                    if let Ok(price) = price.try_into() {
+                        self.compute_budget.compute_unit_price = Some(u64::from_le_bytes(price));
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+}
@@ -0,0 +1,267 @@
+//! Store configuration as plain data, loadable from a TOML file.
+
+use {
+    crate::{env_var, Cluster, RpcAccountStore, RpcError, ValidationMode, MAINNET_RPC_URL},
+    serde::{Deserialize, Serialize},
+    solana_commitment_config::{CommitmentConfig, CommitmentLevel},
+    std::{
+        path::{Path, PathBuf},
+        time::Duration,
+    },
+};
+
+/// Every serializable [`RpcAccountStore`] option, so test crates can share one
+/// configuration instead of repeating the same builder calls.
+///
+/// Each field does what the builder method of the same name does, and defaults to
+/// what the store does without it. [`RpcAccountStore::from_config`] applies the
+/// fields through those methods, so further builder calls override the config:
+///
+/// ```toml
+/// # mollusk.toml
+/// rpc_url = "https://api.mainnet-beta.solana.com"
+/// commitment = "finalized"
+/// fixture_dir = "tests/fixtures"
+/// rate_limit = 10
+/// elf_validation = "warn"
+/// ```
+///
+/// Fields missing from the file keep their defaults; unknown fields are an error.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RpcStoreConfig {
+    pub rpc_url: String,
+    /// `processed`, `confirmed` or `finalized`.
+    pub commitment: CommitmentLevel,
+    pub fixture_dir: Option<PathBuf>,
+    pub offline: bool,
+    /// See [`RpcAccountStore::with_max_retry_after`].
+    pub max_retry_after_secs: u64,
+    /// Requests per second, see [`RpcAccountStore::with_rate_limit`].
+    pub rate_limit: Option<u32>,
+    pub concurrency: usize,
+    pub rpc_budget: Option<u64>,
+    pub expect_cluster: Option<Cluster>,
+    pub allow_missing_accounts: bool,
+    pub lenient: bool,
+    /// See [`RpcAccountStore::with_max_account_bytes_strict`].
+    pub max_account_bytes: Option<usize>,
+    pub check_program_data_size: bool,
+    /// `strict`, `warn` or `off`.
+    pub elf_validation: ValidationMode,
+    pub strict_mocks: bool,
+    pub deny_shadowed_mocks: bool,
+    pub auto_top_up_rent: bool,
+    pub fetch_real_sysvars: bool,
+    pub prefetch_program_data: bool,
+    pub program_accounts_limit: usize,
+    pub url_redaction: bool,
+}
+
+impl Default for RpcStoreConfig {
+    fn default() -> Self {
+        Self {
+            rpc_url: MAINNET_RPC_URL.to_string(),
+            commitment: CommitmentLevel::Confirmed,
+            fixture_dir: None,
+            offline: false,
+            max_retry_after_secs: crate::retry::DEFAULT_MAX_RETRY_AFTER.as_secs(),
+            rate_limit: None,
+            concurrency: 1,
+            rpc_budget: None,
+            expect_cluster: None,
+            allow_missing_accounts: false,
+            lenient: false,
+            max_account_bytes: None,
+            check_program_data_size: false,
+            elf_validation: ValidationMode::Strict,
+            strict_mocks: false,
+            deny_shadowed_mocks: false,
+            auto_top_up_rent: false,
+            fetch_real_sysvars: false,
+            prefetch_program_data: false,
+            program_accounts_limit: crate::program_accounts::DEFAULT_PROGRAM_ACCOUNTS_LIMIT,
+            url_redaction: true,
+        }
+    }
+}
+
+/// Parse a boolean environment variable.
+fn env_bool(name: &'static str) -> Result<Option<bool>, RpcError> {
+    let Some(value) = env_var(name) else {
+        return Ok(None);
+    };
+    match value.to_ascii_lowercase().as_str() {
+        "1" | "true" | "yes" => Ok(Some(true)),
+        "0" | "false" | "no" => Ok(Some(false)),
+        _ => Err(RpcError::InvalidEnvVar {
+            name,
+            reason: format!("expected a boolean, got {value:?}"),
+        }),
+    }
+}
+
+/// Parse a numeric environment variable.
+fn env_number<T: std::str::FromStr>(name: &'static str) -> Result<Option<T>, RpcError> {
+    let Some(value) = env_var(name) else {
+        return Ok(None);
+    };
+    value.parse().map(Some).map_err(|_| RpcError::InvalidEnvVar {
+        name,
+        reason: format!("expected a number, got {value:?}"),
+    })
+}
+
+impl RpcStoreConfig {
+    /// Read a TOML configuration file, then apply
+    /// [`with_env_overrides`](Self::with_env_overrides).
+    ///
+    /// Precedence, from lowest to highest: defaults, the file, environment
+    /// variables, builder methods called on the store.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`RpcError::InvalidConfig`] if the file can't be read or parsed, and
+    /// [`RpcError::InvalidEnvVar`] if an override is invalid.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, RpcError> {
+        let path = path.as_ref();
+        let invalid = |reason: String| RpcError::InvalidConfig {
+            path: path.to_path_buf(),
+            reason,
+        };
+        let contents = std::fs::read_to_string(path).map_err(|e| invalid(e.to_string()))?;
+        Self::from_toml(&contents)
+            .map_err(invalid)?
+            .with_env_overrides()
+    }
+
+    /// Parse a TOML configuration, without environment overrides.
+    pub fn from_toml(toml: &str) -> Result<Self, String> {
+        toml::from_str(toml).map_err(|e| e.message().to_string())
+    }
+
+    /// Override fields with the environment variables that are set:
+    ///
+    /// | Variable                  | Field         |
+    /// |---------------------------|---------------|
+    /// | `MOLLUSK_RPC_URL`         | `rpc_url`     |
+    /// | `MOLLUSK_RPC_COMMITMENT`  | `commitment`  |
+    /// | `MOLLUSK_FIXTURE_DIR`     | `fixture_dir` |
+    /// | `MOLLUSK_OFFLINE`         | `offline`     |
+    /// | `MOLLUSK_RATE_LIMIT`      | `rate_limit`  |
+    /// | `MOLLUSK_RPC_BUDGET`      | `rpc_budget`  |
+    /// | `MOLLUSK_CONCURRENCY`     | `concurrency` |
+    ///
+    /// Unset or empty variables are ignored. Booleans accept `1`/`true`/`yes` and
+    /// `0`/`false`/`no`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`RpcError::InvalidEnvVar`] if a variable can't be parsed.
+    pub fn with_env_overrides(mut self) -> Result<Self, RpcError> {
+        if let Some(url) = env_var("MOLLUSK_RPC_URL") {
+            reqwest::Url::parse(&url).map_err(|e| RpcError::InvalidEnvVar {
+                name: "MOLLUSK_RPC_URL",
+                reason: format!("malformed URL: {e}"),
+            })?;
+            self.rpc_url = url;
+        }
+        if let Some(commitment) = env_var("MOLLUSK_RPC_COMMITMENT") {
+            let config: CommitmentConfig =
+                commitment.parse().map_err(|_| RpcError::InvalidEnvVar {
+                    name: "MOLLUSK_RPC_COMMITMENT",
+                    reason: format!(
+                        "unknown commitment level {commitment:?} (expected processed, \
+                         confirmed or finalized)"
+                    ),
+                })?;
+            self.commitment = config.commitment;
+        }
+        if let Some(dir) = env_var("MOLLUSK_FIXTURE_DIR") {
+            self.fixture_dir = Some(dir.into());
+        }
+        if let Some(offline) = env_bool("MOLLUSK_OFFLINE")? {
+            self.offline = offline;
+        }
+        if let Some(rate_limit) = env_number("MOLLUSK_RATE_LIMIT")? {
+            self.rate_limit = Some(rate_limit);
+        }
+        if let Some(budget) = env_number("MOLLUSK_RPC_BUDGET")? {
+            self.rpc_budget = Some(budget);
+        }
+        if let Some(concurrency) = env_number("MOLLUSK_CONCURRENCY")? {
+            self.concurrency = concurrency;
+        }
+        Ok(self)
+    }
+}
+
+impl RpcAccountStore {
+    /// Create a store from `config`, as if each field's builder method was called.
+    ///
+    /// ```rust,ignore
+    /// let config = RpcStoreConfig::from_file("mollusk.toml")?;
+    /// let store = RpcAccountStore::from_config(config)
+    ///     .from_instruction(&instruction)
+    ///     .await?;
+    /// ```
+    pub fn from_config(config: RpcStoreConfig) -> Self {
+        let commitment = CommitmentConfig {
+            commitment: config.commitment,
+        };
+        let mut store = Self::new_with_commitment(config.rpc_url, commitment)
+            .with_concurrency(config.concurrency)
+            .with_program_accounts_limit(config.program_accounts_limit)
+            .elf_validation(config.elf_validation)
+            .with_url_redaction(config.url_redaction);
+
+        let max_retry_after = Duration::from_secs(config.max_retry_after_secs);
+        if max_retry_after != crate::retry::DEFAULT_MAX_RETRY_AFTER {
+            store = store.with_max_retry_after(max_retry_after);
+        }
+        if let Some(dir) = config.fixture_dir {
+            store = store.with_fixture_dir(dir);
+        }
+        if let Some(rate_limit) = config.rate_limit {
+            store = store.with_rate_limit(rate_limit);
+        }
+        if let Some(budget) = config.rpc_budget {
+            store = store.with_rpc_budget(budget);
+        }
+        if let Some(cluster) = config.expect_cluster {
+            store = store.expect_cluster(cluster);
+        }
+        if let Some(limit) = config.max_account_bytes {
+            store = store.with_max_account_bytes_strict(limit);
+        }
+
+        if config.offline {
+            store = store.offline();
+        }
+        if config.allow_missing_accounts {
+            store = store.allow_missing_accounts();
+        }
+        if config.lenient {
+            store = store.lenient();
+        }
+        if config.check_program_data_size {
+            store = store.check_program_data_size();
+        }
+        if config.strict_mocks {
+            store = store.strict_mocks();
+        }
+        if config.deny_shadowed_mocks {
+            store = store.deny_shadowed_mocks();
+        }
+        if config.auto_top_up_rent {
+            store = store.auto_top_up_rent();
+        }
+        if config.fetch_real_sysvars {
+            store = store.fetch_real_sysvars();
+        }
+        if config.prefetch_program_data {
+            store = store.prefetch_program_data();
+        }
+        store
+    }
+}
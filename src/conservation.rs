@@ -0,0 +1,116 @@
+//! Checking that an instruction doesn't create lamports from nothing.
+
+use {
+    crate::RpcAccountStore,
+    mollusk_svm::result::InstructionResult,
+    solana_pubkey::Pubkey,
+    std::{collections::HashSet, fmt},
+};
+
+/// Total lamports of an instruction's accounts before and after it ran, see
+/// [`RpcAccountStore::check_lamport_conservation`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LamportDelta {
+    pub before: u128,
+    pub after: u128,
+}
+
+impl LamportDelta {
+    /// Lamports the instruction removed from its accounts, e.g. by burning them.
+    pub fn burned(&self) -> u128 {
+        self.before.saturating_sub(self.after)
+    }
+}
+
+impl fmt::Display for LamportDelta {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} lamports before, {} after ({} burned)",
+            self.before,
+            self.after,
+            self.burned()
+        )
+    }
+}
+
+/// An instruction whose accounts hold more lamports afterwards than before.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConservationViolation {
+    pub before: u128,
+    pub after: u128,
+    /// Accounts whose balance grew, with how much, in result order.
+    pub increases: Vec<(Pubkey, u64)>,
+}
+
+impl ConservationViolation {
+    /// Lamports created from nothing.
+    pub fn created(&self) -> u128 {
+        self.after - self.before
+    }
+}
+
+impl fmt::Display for ConservationViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} lamports created from nothing: {} before, {} after",
+            self.created(),
+            self.before,
+            self.after
+        )?;
+        for (pubkey, increase) in &self.increases {
+            write!(f, "\n  {pubkey}: +{increase}")?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ConservationViolation {}
+
+impl RpcAccountStore {
+    /// Check that `result` didn't increase the total lamports of its accounts.
+    ///
+    /// Sums the cached pre-state against the resulting accounts, over the accounts in
+    /// the result. Accounts the instruction created count with zero lamports before,
+    /// so funding them has to come out of another account. A lower total is allowed
+    /// and reported as [`burned`](LamportDelta::burned); assert on it if the test
+    /// expects a particular burn:
+    ///
+    /// ```rust,ignore
+    /// let result = mollusk.process_instruction(&instruction, &accounts);
+    /// let delta = store.check_lamport_conservation(&result)?;
+    /// assert_eq!(delta.burned(), 0);
+    /// ```
+    pub fn check_lamport_conservation(
+        &self,
+        result: &InstructionResult,
+    ) -> Result<LamportDelta, ConservationViolation> {
+        let mut seen = HashSet::new();
+        let mut delta = LamportDelta {
+            before: 0,
+            after: 0,
+        };
+        let mut increases = Vec::new();
+        for (pubkey, after) in &result.resulting_accounts {
+            if !seen.insert(*pubkey) {
+                continue;
+            }
+            let before = self.cache.get(pubkey).map_or(0, |account| account.lamports);
+            delta.before += u128::from(before);
+            delta.after += u128::from(after.lamports);
+            if after.lamports > before {
+                increases.push((*pubkey, after.lamports - before));
+            }
+        }
+
+        if delta.after > delta.before {
+            return Err(ConservationViolation {
+                before: delta.before,
+                after: delta.after,
+                increases,
+            });
+        }
+        Ok(delta)
+    }
+}
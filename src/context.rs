@@ -0,0 +1,155 @@
+//! Lazily fetching accounts for `MolluskContext` (`context` feature).
+
+use {
+    crate::{Provenance, RpcAccountStore, RpcError},
+    mollusk_svm::{account_store::AccountStore, Mollusk, MolluskContext},
+    solana_account::Account,
+    solana_pubkey::Pubkey,
+    std::{
+        cell::{Cell, RefCell},
+        fmt,
+        future::Future,
+    },
+    tokio::runtime::Handle,
+};
+
+/// A Mollusk [`AccountStore`] that fetches accounts from RPC the first time the
+/// runtime asks for them.
+///
+/// Cached accounts are served directly; misses block on a fetch through the wrapped
+/// [`RpcAccountStore`] (so its fixtures, budget and other settings apply). Accounts
+/// written back by Mollusk go into the same cache. Fetch errors can't be reported
+/// through the store trait, so the account is treated as missing and the error is
+/// kept for [`take_errors`](Self::take_errors).
+///
+/// Blocking uses [`tokio::task::block_in_place`] when called from within a runtime,
+/// which requires the multi-threaded runtime (`#[tokio::test(flavor = "multi_thread")]`).
+/// Programs are not loaded lazily: call [`add_programs`](RpcAccountStore::add_programs)
+/// before wrapping the store.
+///
+/// ```rust,ignore
+/// let context = RpcAccountStore::new(rpc_url)
+///     .from_instruction(&instruction)
+///     .await?
+///     .add_programs(&mut mollusk)
+///     .await?
+///     .into_context(mollusk);
+///
+/// // Accounts the instruction needs that weren't fetched above are fetched now.
+/// context.process_instruction(&instruction);
+/// ```
+pub struct LazyAccountStore {
+    store: RefCell<RpcAccountStore>,
+    handle: Handle,
+    /// Off while `Mollusk::with_context` seeds the loaded programs.
+    fetch_misses: Cell<bool>,
+    served: RefCell<Vec<Pubkey>>,
+    errors: RefCell<Vec<RpcError>>,
+}
+
+impl fmt::Debug for LazyAccountStore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LazyAccountStore")
+            .field("store", &self.store.borrow())
+            .field("served", &self.served.borrow().len())
+            .field("errors", &self.errors.borrow().len())
+            .finish()
+    }
+}
+
+impl LazyAccountStore {
+    /// Wrap `store`, fetching misses on the current Tokio runtime.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called outside of a Tokio runtime; use
+    /// [`with_handle`](Self::with_handle) there.
+    pub fn new(store: RpcAccountStore) -> Self {
+        Self::with_handle(store, Handle::current())
+    }
+
+    /// Wrap `store`, fetching misses on the runtime behind `handle`.
+    pub fn with_handle(store: RpcAccountStore, handle: Handle) -> Self {
+        Self {
+            store: RefCell::new(store),
+            handle,
+            fetch_misses: Cell::new(true),
+            served: RefCell::default(),
+            errors: RefCell::default(),
+        }
+    }
+
+    /// Every pubkey the runtime asked for, in order.
+    pub fn served(&self) -> Vec<Pubkey> {
+        self.served.borrow().clone()
+    }
+
+    /// Errors from fetches triggered by the runtime since the last call.
+    pub fn take_errors(&self) -> Vec<RpcError> {
+        self.errors.take()
+    }
+
+    /// Unwrap the store, with everything fetched or written so far in its cache.
+    pub fn into_inner(self) -> RpcAccountStore {
+        self.store.into_inner()
+    }
+
+    fn block_on<F: Future>(&self, future: F) -> F::Output {
+        if Handle::try_current().is_ok() {
+            tokio::task::block_in_place(|| self.handle.block_on(future))
+        } else {
+            self.handle.block_on(future)
+        }
+    }
+}
+
+impl AccountStore for LazyAccountStore {
+    fn get_account(&self, pubkey: &Pubkey) -> Option<Account> {
+        let mut store = self.store.borrow_mut();
+        if !self.fetch_misses.get() {
+            return store.cache.get(pubkey).cloned();
+        }
+
+        self.served.borrow_mut().push(*pubkey);
+        if let Some(account) = store.cache.get(pubkey) {
+            store.mark_used(pubkey);
+            return Some(account.clone());
+        }
+        match self.block_on(store.fetch_accounts(&[*pubkey])) {
+            Ok(()) => {
+                let account = store.cache.get(pubkey).cloned();
+                if account.is_some() {
+                    store.mark_used(pubkey);
+                }
+                account
+            }
+            Err(e) => {
+                self.errors.borrow_mut().push(e);
+                None
+            }
+        }
+    }
+
+    fn store_account(&mut self, pubkey: Pubkey, account: Account) {
+        self.store
+            .get_mut()
+            .insert_account(pubkey, account, Provenance::ResultApplied {
+                instruction_index: None,
+            });
+    }
+}
+
+impl RpcAccountStore {
+    /// Hand the store to a [`MolluskContext`] that fetches missing accounts on demand.
+    ///
+    /// See [`LazyAccountStore`].
+    pub fn into_context(self, mollusk: Mollusk) -> MolluskContext<LazyAccountStore> {
+        // `with_context` looks up every loaded program to seed the store with it;
+        // that shouldn't fetch builtins from RPC.
+        let store = LazyAccountStore::new(self);
+        store.fetch_misses.set(false);
+        let context = mollusk.with_context(store);
+        context.account_store.borrow().fetch_misses.set(true);
+        context
+    }
+}
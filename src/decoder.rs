@@ -0,0 +1,104 @@
+//! Explorer-style JSON for cached accounts, through `solana-account-decoder`.
+
+use {
+    crate::{summary::SPL_TOKEN_2022, RpcAccountStore, RpcError},
+    base64::{engine::general_purpose::STANDARD as BASE64, Engine},
+    serde_json::{json, Value},
+    solana_account::Account,
+    solana_account_decoder::parse_account_data::{
+        parse_account_data_v3, AccountAdditionalDataV3, ParsedAccount, SplTokenAdditionalDataV2,
+    },
+    solana_program_pack::Pack,
+    solana_pubkey::Pubkey,
+    spl_token_interface::state::{Account as TokenAccount, Mint},
+};
+
+impl RpcAccountStore {
+    /// The cached account `pubkey` decoded the way explorers and `jsonParsed` RPC
+    /// responses show it: token accounts and mints, stake, vote, nonce, address
+    /// lookup tables, config, sysvar and upgradeable loader accounts.
+    ///
+    /// Accounts of other owners, and accounts their owner's parser rejects (such as
+    /// plain wallets), are returned as `["<base64>", "base64"]` with the owner as
+    /// `program`. Token accounts are only decoded while their mint is cached, since
+    /// amounts need the mint's decimals. Never makes an RPC request.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`RpcError::AccountNotFound`] if the account isn't cached.
+    pub fn parse_account(&self, pubkey: &Pubkey) -> Result<ParsedAccount, RpcError> {
+        let account = self
+            .cache
+            .get(pubkey)
+            .ok_or(RpcError::AccountNotFound(*pubkey))?;
+        let additional_data = AccountAdditionalDataV3 {
+            spl_token_additional_data: self
+                .mint_decimals(account)
+                .map(SplTokenAdditionalDataV2::with_decimals),
+        };
+        Ok(
+            parse_account_data_v3(pubkey, &account.owner, &account.data, Some(additional_data))
+                .unwrap_or_else(|_| ParsedAccount {
+                    program: account.owner.to_string(),
+                    parsed: json!([BASE64.encode(&account.data), "base64"]),
+                    space: account.data.len() as u64,
+                }),
+        )
+    }
+
+    /// Pretty-printed JSON of [`parse_account`](Self::parse_account) for each of
+    /// `pubkeys`, in order, with their labels:
+    ///
+    /// ```rust,ignore
+    /// println!("{}", store.dump_parsed(&[user_ata, nonce])?);
+    /// ```
+    ///
+    /// ```text
+    /// [
+    ///   {
+    ///     "label": "user_ata",
+    ///     "parsed": { "info": { ... }, "type": "account" },
+    ///     "program": "spl-token",
+    ///     "pubkey": "4Nd1mBQtrMJVYVfKf2PJy9NZUZdTAsp7D4xWLs4gDB4T",
+    ///     "space": 165
+    ///   },
+    ///   ...
+    /// ]
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns [`RpcError::AccountNotFound`] if an account isn't cached.
+    pub fn dump_parsed(&self, pubkeys: &[Pubkey]) -> Result<String, RpcError> {
+        let accounts = pubkeys
+            .iter()
+            .map(|pubkey| {
+                let parsed = self.parse_account(pubkey)?;
+                let mut entry = json!({ "pubkey": pubkey.to_string() });
+                if let Some(label) = self.label_of(pubkey) {
+                    entry["label"] = json!(label);
+                }
+                entry["program"] = json!(parsed.program);
+                entry["parsed"] = parsed.parsed;
+                entry["space"] = json!(parsed.space);
+                Ok(entry)
+            })
+            .collect::<Result<Vec<Value>, RpcError>>()?;
+        // Serializing a `Value` can't fail
+        Ok(serde_json::to_string_pretty(&accounts).unwrap_or_default())
+    }
+
+    /// Decimals of the cached mint of the token account `account`, if any.
+    fn mint_decimals(&self, account: &Account) -> Option<u8> {
+        let is_token_program =
+            account.owner == spl_token_interface::ID || account.owner == SPL_TOKEN_2022;
+        if !is_token_program || account.data.len() < TokenAccount::LEN {
+            return None;
+        }
+        let token_account =
+            TokenAccount::unpack_unchecked(&account.data[..TokenAccount::LEN]).ok()?;
+        let mint = self.cache.get(&token_account.mint)?;
+        let mint = Mint::unpack_unchecked(mint.data.get(..Mint::LEN)?).ok()?;
+        Some(mint.decimals)
+    }
+}
@@ -0,0 +1,201 @@
+//! Diffing instruction results against the cached pre-state.
+
+use {
+    crate::{labels::labeled, Provenance, RpcAccountStore},
+    mollusk_svm::result::InstructionResult,
+    solana_account::Account,
+    solana_pubkey::Pubkey,
+    std::{fmt, ops::Range},
+};
+
+/// Changed ranges closer than this many bytes are reported as one.
+const COALESCE_GAP: usize = 8;
+/// Unchanged bytes shown around each changed range.
+const CONTEXT: usize = 4;
+/// Longest hex snippet shown per side of a changed range.
+const MAX_SNIPPET: usize = 32;
+
+/// How an account's existence changed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AccountChange {
+    Created,
+    Closed,
+    Modified,
+}
+
+/// Everything that changed about one account.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AccountDiff {
+    pub pubkey: Pubkey,
+    pub change: AccountChange,
+    pub lamport_delta: i128,
+    /// `(before, after)`, if the owner changed.
+    pub owner: Option<(Pubkey, Pubkey)>,
+    /// Data length `(before, after)`.
+    pub data_len: (usize, usize),
+    /// Byte ranges whose contents differ, including bytes added or removed by a
+    /// resize.
+    pub changed_ranges: Vec<Range<usize>>,
+    /// Where the cached pre-state came from, see [`RpcAccountStore::provenance`].
+    pub provenance: Option<Provenance>,
+    /// See [`RpcAccountStore::label`].
+    label: Option<String>,
+    before: Vec<u8>,
+    after: Vec<u8>,
+}
+
+/// The result of [`RpcAccountStore::diff_result`]: one entry per changed account,
+/// in result order.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct StateDiff {
+    pub accounts: Vec<AccountDiff>,
+}
+
+impl StateDiff {
+    /// The diff for `pubkey`, if it changed.
+    pub fn account(&self, pubkey: &Pubkey) -> Option<&AccountDiff> {
+        self.accounts.iter().find(|diff| diff.pubkey == *pubkey)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.accounts.is_empty()
+    }
+}
+
+impl RpcAccountStore {
+    /// Compare the cached pre-state against the accounts in `result`.
+    ///
+    /// Accounts missing from the cache count as created; accounts left with zero
+    /// lamports count as closed.
+    pub fn diff_result(&self, result: &InstructionResult) -> StateDiff {
+        let accounts = result
+            .resulting_accounts
+            .iter()
+            .filter_map(|(pubkey, after)| {
+                let before = self.cache.get(pubkey).filter(|account| account.lamports > 0);
+                let after = Some(after).filter(|account| account.lamports > 0);
+                let mut diff = diff_account(pubkey, before, after)?;
+                diff.label = self.label_of(pubkey).map(str::to_string);
+                diff.provenance = self.provenance(pubkey).cloned();
+                Some(diff)
+            })
+            .collect();
+        StateDiff { accounts }
+    }
+}
+
+fn diff_account(
+    pubkey: &Pubkey,
+    before: Option<&Account>,
+    after: Option<&Account>,
+) -> Option<AccountDiff> {
+    let change = match (before, after) {
+        (None, None) => return None,
+        (None, Some(_)) => AccountChange::Created,
+        (Some(_), None) => AccountChange::Closed,
+        (Some(before), Some(after)) if before == after => return None,
+        (Some(_), Some(_)) => AccountChange::Modified,
+    };
+
+    let lamports = |account: Option<&Account>| account.map_or(0, |account| account.lamports);
+    let data = |account: Option<&Account>| account.map_or(Vec::new(), |a| a.data.clone());
+    let owner = match (before, after) {
+        (Some(before), Some(after)) if before.owner != after.owner => {
+            Some((before.owner, after.owner))
+        }
+        _ => None,
+    };
+    let (before_data, after_data) = (data(before), data(after));
+
+    Some(AccountDiff {
+        pubkey: *pubkey,
+        change,
+        lamport_delta: i128::from(lamports(after)) - i128::from(lamports(before)),
+        owner,
+        data_len: (before_data.len(), after_data.len()),
+        changed_ranges: changed_ranges(&before_data, &after_data),
+        provenance: None,
+        label: None,
+        before: before_data,
+        after: after_data,
+    })
+}
+
+/// Coalesced ranges where `before` and `after` differ.
+fn changed_ranges(before: &[u8], after: &[u8]) -> Vec<Range<usize>> {
+    let mut ranges: Vec<Range<usize>> = Vec::new();
+    let len = before.len().max(after.len());
+    for index in (0..len).filter(|i| before.get(*i) != after.get(*i)) {
+        match ranges.last_mut() {
+            Some(range) if index - range.end <= COALESCE_GAP => range.end = index + 1,
+            _ => ranges.push(index..index + 1),
+        }
+    }
+    ranges
+}
+
+fn hex(data: &[u8], range: Range<usize>) -> String {
+    let range = range.start.min(data.len())..range.end.min(data.len());
+    let mut hex: Vec<String> = data[range.clone()]
+        .iter()
+        .take(MAX_SNIPPET)
+        .map(|byte| format!("{byte:02x}"))
+        .collect();
+    if range.len() > MAX_SNIPPET {
+        hex.push("..".to_string());
+    }
+    if hex.is_empty() {
+        return "-".to_string();
+    }
+    hex.join(" ")
+}
+
+impl fmt::Display for AccountDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let change = match self.change {
+            AccountChange::Created => "created",
+            AccountChange::Closed => "closed",
+            AccountChange::Modified => "modified",
+        };
+        write!(f, "{} {change}", labeled(self.label.as_deref(), &self.pubkey))?;
+        if let Some(provenance) = &self.provenance {
+            write!(f, " (pre-state {provenance})")?;
+        }
+        write!(f, ": lamports {:+}", self.lamport_delta)?;
+        if let Some((before, after)) = &self.owner {
+            write!(f, ", owner {before} -> {after}")?;
+        }
+        let (before_len, after_len) = self.data_len;
+        if before_len != after_len {
+            write!(f, ", data {before_len} -> {after_len} bytes")?;
+        }
+
+        for range in &self.changed_ranges {
+            let shown = range.start.saturating_sub(CONTEXT)..range.end + CONTEXT;
+            write!(
+                f,
+                "\n  [{}..{}] {} -> {}",
+                range.start,
+                range.end,
+                hex(&self.before, shown.clone()),
+                hex(&self.after, shown),
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for StateDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.accounts.is_empty() {
+            return f.write_str("no accounts changed");
+        }
+        for (index, diff) in self.accounts.iter().enumerate() {
+            if index > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{diff}")?;
+        }
+        Ok(())
+    }
+}
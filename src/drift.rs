@@ -0,0 +1,234 @@
+//! Checking cached accounts against the live chain.
+
+use {
+    crate::{Provenance, RpcAccountStore, RpcError},
+    solana_account::Account,
+    solana_instruction::Instruction,
+    solana_pubkey::Pubkey,
+    std::fmt,
+};
+
+/// How a cached account differs from the live chain.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Drift {
+    Unchanged,
+    /// Fields are `(cached, live)` where they differ.
+    Changed {
+        lamports: Option<(u64, u64)>,
+        owner: Option<(Pubkey, Pubkey)>,
+        /// Bytes that differ, counting bytes added or removed by a resize.
+        changed_bytes: usize,
+    },
+    /// The account no longer exists on-chain.
+    Missing,
+}
+
+impl Drift {
+    pub(crate) fn between(cached: &Account, live: Option<&Account>) -> Self {
+        let Some(live) = live else {
+            return Self::Missing;
+        };
+        let lamports =
+            (cached.lamports != live.lamports).then_some((cached.lamports, live.lamports));
+        let owner = (cached.owner != live.owner).then_some((cached.owner, live.owner));
+        let changed_bytes = cached
+            .data
+            .iter()
+            .zip(&live.data)
+            .filter(|(cached, live)| cached != live)
+            .count()
+            + cached.data.len().abs_diff(live.data.len());
+        if lamports.is_none() && owner.is_none() && changed_bytes == 0 {
+            Self::Unchanged
+        } else {
+            Self::Changed {
+                lamports,
+                owner,
+                changed_bytes,
+            }
+        }
+    }
+}
+
+impl fmt::Display for Drift {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Unchanged => f.write_str("unchanged"),
+            Self::Missing => f.write_str("missing on-chain"),
+            Self::Changed {
+                lamports,
+                owner,
+                changed_bytes,
+            } => {
+                let mut changes = Vec::new();
+                if let Some((cached, live)) = lamports {
+                    changes.push(format!("lamports {cached} -> {live}"));
+                }
+                if let Some((cached, live)) = owner {
+                    changes.push(format!("owner {cached} -> {live}"));
+                }
+                if *changed_bytes > 0 {
+                    changes.push(format!("{changed_bytes} bytes of data changed"));
+                }
+                f.write_str(&changes.join(", "))
+            }
+        }
+    }
+}
+
+/// The result of [`RpcAccountStore::verify_fresh`]: one entry per checked account,
+/// in the order asked for.
+#[derive(Debug, Clone)]
+pub struct DriftReport {
+    pub accounts: Vec<(Pubkey, Drift)>,
+    /// Live state of the drifted accounts, for `refresh_drifted`.
+    live: Vec<(Pubkey, Option<Account>)>,
+}
+
+impl DriftReport {
+    /// Whether every checked account matches the chain.
+    pub fn is_fresh(&self) -> bool {
+        self.live.is_empty()
+    }
+
+    /// The accounts that differ from the chain.
+    pub fn drifted(&self) -> impl Iterator<Item = &(Pubkey, Drift)> {
+        self.accounts
+            .iter()
+            .filter(|(_, drift)| *drift != Drift::Unchanged)
+    }
+}
+
+impl fmt::Display for DriftReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_fresh() {
+            return write!(f, "all {} accounts match the chain", self.accounts.len());
+        }
+        write!(
+            f,
+            "{} of {} accounts drifted from the chain:",
+            self.live.len(),
+            self.accounts.len()
+        )?;
+        for (pubkey, drift) in self.drifted() {
+            write!(f, "\n  {pubkey}: {drift}")?;
+        }
+        Ok(())
+    }
+}
+
+impl RpcAccountStore {
+    /// Re-fetch cached accounts and report how they differ from the live chain,
+    /// without touching the cache.
+    ///
+    /// Useful before trusting a long-lived store or replayed fixtures. Pubkeys that
+    /// aren't cached are left out of the report. Apply the live state with
+    /// [`refresh_drifted`](Self::refresh_drifted):
+    ///
+    /// ```rust,ignore
+    /// let report = store.verify_fresh(&[pool, oracle]).await?;
+    /// println!("{report}");
+    /// store.refresh_drifted(&report);
+    /// ```
+    pub async fn verify_fresh(&self, pubkeys: &[Pubkey]) -> Result<DriftReport, RpcError> {
+        let mut unique = std::collections::HashSet::new();
+        let cached: Vec<Pubkey> = pubkeys
+            .iter()
+            .filter(|pubkey| self.cache.contains_key(pubkey) && unique.insert(**pubkey))
+            .copied()
+            .collect();
+
+        let mut report = DriftReport {
+            accounts: Vec::with_capacity(cached.len()),
+            live: Vec::new(),
+        };
+        if cached.is_empty() {
+            return Ok(report);
+        }
+        for (pubkey, live) in self.fetch_from_rpc(&cached).await? {
+            let drift = Drift::between(&self.cache[&pubkey], live.as_ref());
+            if drift != Drift::Unchanged {
+                report.live.push((pubkey, live));
+            }
+            report.accounts.push((pubkey, drift));
+        }
+        Ok(report)
+    }
+
+    /// [`verify_fresh`](Self::verify_fresh) for every cached account, sorted by pubkey.
+    pub async fn verify_all_fresh(&self) -> Result<DriftReport, RpcError> {
+        let mut pubkeys: Vec<Pubkey> = self.cache.keys().copied().collect();
+        pubkeys.sort();
+        self.verify_fresh(&pubkeys).await
+    }
+
+    /// Cache the live state of the accounts `report` found drifted, leaving the rest
+    /// alone. Accounts missing on-chain are removed from the cache.
+    ///
+    /// Returns the number of accounts updated or removed.
+    pub fn refresh_drifted(&mut self, report: &DriftReport) -> usize {
+        for (pubkey, live) in &report.live {
+            match live {
+                Some(account) => {
+                    let provenance = self.fetched_provenance_of(pubkey);
+                    self.insert_account(*pubkey, account.clone(), provenance);
+                }
+                None => {
+                    self.cache.remove(pubkey);
+                    self.provenance.remove(pubkey);
+                }
+            }
+        }
+        report.live.len()
+    }
+
+    /// Re-fetch the cached accounts `instruction` writes to and cache their live
+    /// state, right before running it against the chain's latest state.
+    ///
+    /// Writable accounts are the ones most likely to have moved since the store was
+    /// built, so this refreshes those alone rather than the whole cache. Mocked,
+    /// overridden and patched accounts are never refreshed, and neither are pubkeys
+    /// that aren't cached. Returns the [`verify_fresh`](Self::verify_fresh) report of the
+    /// refreshed accounts, whose [`drifted`](DriftReport::drifted) ones changed:
+    ///
+    /// ```rust,ignore
+    /// let report = store.refresh_writable(&swap_ix).await?;
+    /// println!("{report}");
+    /// let result = mollusk.process_instruction(&swap_ix, &store.instruction_accounts(&swap_ix)?);
+    /// ```
+    ///
+    /// Accounts missing on-chain are removed from the cache, as with
+    /// [`refresh_drifted`](Self::refresh_drifted).
+    pub async fn refresh_writable(
+        &mut self,
+        instruction: &Instruction,
+    ) -> Result<DriftReport, RpcError> {
+        self.refresh_writable_chain(std::slice::from_ref(instruction)).await
+    }
+
+    /// [`refresh_writable`](Self::refresh_writable) for every instruction of a chain,
+    /// in one round of requests.
+    ///
+    /// Refresh before the first instruction only: accounts written back by
+    /// [`apply_result`](Self::apply_result) are refreshed like any other.
+    pub async fn refresh_writable_chain(
+        &mut self,
+        instructions: &[Instruction],
+    ) -> Result<DriftReport, RpcError> {
+        let writable: Vec<Pubkey> = instructions
+            .iter()
+            .flat_map(|instruction| &instruction.accounts)
+            .filter(|meta| meta.is_writable)
+            .map(|meta| meta.pubkey)
+            .filter(|pubkey| {
+                !matches!(
+                    self.provenance.get(pubkey),
+                    Some(Provenance::Mocked | Provenance::Override | Provenance::Patched(_))
+                )
+            })
+            .collect();
+        let report = self.verify_fresh(&writable).await?;
+        self.refresh_drifted(&report);
+        Ok(report)
+    }
+}
@@ -0,0 +1,263 @@
+//! Comparing committed fixtures with the live chain, for scheduled CI jobs.
+
+use {
+    crate::{drift::Drift, fixture, RpcAccountStore, RpcError},
+    serde::{Serialize, Serializer},
+    solana_account::Account,
+    solana_pubkey::Pubkey,
+    std::{
+        fmt,
+        path::{Path, PathBuf},
+    },
+};
+
+/// Tag of `UpgradeableLoaderState::ProgramData`, followed by the deploy slot.
+const PROGRAM_DATA_TAG: [u8; 4] = [3, 0, 0, 0];
+
+/// How a fixture account compares with its live state, see
+/// [`RpcAccountStore::drift_check`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DriftCategory {
+    Unchanged,
+    /// Only the lamports differ, which rent and fees do all the time.
+    LamportsOnly,
+    /// The data or owner differ.
+    DataChanged,
+    /// The account no longer exists on-chain.
+    Missing,
+    /// Program data of BPF Loader v3 redeployed since the fixture was recorded.
+    ProgramUpgraded,
+}
+
+impl fmt::Display for DriftCategory {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Unchanged => "unchanged",
+            Self::LamportsOnly => "lamports only",
+            Self::DataChanged => "data changed",
+            Self::Missing => "missing",
+            Self::ProgramUpgraded => "program upgraded",
+        })
+    }
+}
+
+fn base58<S: Serializer>(pubkey: &Pubkey, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_str(pubkey)
+}
+
+/// One account of a [`FixtureDriftReport`]. Pairs are `(fixture, live)`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FixtureAccountDrift {
+    #[serde(serialize_with = "base58")]
+    pub pubkey: Pubkey,
+    pub category: DriftCategory,
+    /// Set if the lamports differ.
+    pub lamports: Option<(u64, u64)>,
+    /// Bytes that differ, counting bytes added or removed by a resize.
+    pub changed_bytes: usize,
+    /// Deploy slots of a [`ProgramUpgraded`](DriftCategory::ProgramUpgraded) account.
+    pub deploy_slots: Option<(u64, u64)>,
+}
+
+/// The slot a BPF Loader v3 program data account was deployed at.
+fn deploy_slot(account: &Account) -> Option<u64> {
+    if account.owner != mollusk_svm::program::loader_keys::LOADER_V3
+        || account.data.get(..4)? != PROGRAM_DATA_TAG
+    {
+        return None;
+    }
+    Some(u64::from_le_bytes(account.data.get(4..12)?.try_into().ok()?))
+}
+
+impl FixtureAccountDrift {
+    fn between(pubkey: Pubkey, fixture: &Account, live: Option<&Account>) -> Self {
+        let mut drift = Self {
+            pubkey,
+            category: DriftCategory::Unchanged,
+            lamports: None,
+            changed_bytes: 0,
+            deploy_slots: None,
+        };
+        let (lamports, owner, changed_bytes) = match Drift::between(fixture, live) {
+            Drift::Unchanged => return drift,
+            Drift::Missing => {
+                drift.category = DriftCategory::Missing;
+                return drift;
+            }
+            Drift::Changed {
+                lamports,
+                owner,
+                changed_bytes,
+            } => (lamports, owner, changed_bytes),
+        };
+        drift.lamports = lamports;
+        drift.changed_bytes = changed_bytes;
+        drift.category = if owner.is_none() && changed_bytes == 0 {
+            DriftCategory::LamportsOnly
+        } else {
+            DriftCategory::DataChanged
+        };
+        let slots = deploy_slot(fixture).zip(live.and_then(deploy_slot));
+        if let Some((fixture_slot, live_slot)) = slots.filter(|(before, after)| before != after) {
+            drift.category = DriftCategory::ProgramUpgraded;
+            drift.deploy_slots = Some((fixture_slot, live_slot));
+        }
+        drift
+    }
+}
+
+/// The result of [`RpcAccountStore::drift_check`]: every account of a fixture and
+/// how it compares with the chain, in pubkey order for a fixture directory and in
+/// file order for a [`save_fixture`](RpcAccountStore::save_fixture) file.
+///
+/// Serializes to JSON for dashboards with [`to_json`](Self::to_json).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FixtureDriftReport {
+    pub fixture: PathBuf,
+    pub accounts: Vec<FixtureAccountDrift>,
+}
+
+impl FixtureDriftReport {
+    /// How many accounts fall into `category`.
+    pub fn count(&self, category: DriftCategory) -> usize {
+        self.accounts
+            .iter()
+            .filter(|account| account.category == category)
+            .count()
+    }
+
+    /// The accounts that changed beyond their lamports: data changed, missing or
+    /// upgraded.
+    pub fn changed(&self) -> impl Iterator<Item = &FixtureAccountDrift> {
+        self.accounts.iter().filter(|account| {
+            !matches!(
+                account.category,
+                DriftCategory::Unchanged | DriftCategory::LamportsOnly
+            )
+        })
+    }
+
+    /// The share of the accounts that [changed](Self::changed), from 0 to 1. An
+    /// empty fixture hasn't changed.
+    pub fn changed_fraction(&self) -> f64 {
+        if self.accounts.is_empty() {
+            return 0.0;
+        }
+        self.changed().count() as f64 / self.accounts.len() as f64
+    }
+
+    /// Turn the report into an error if more than `max_fraction` of the accounts
+    /// [changed](Self::changed), to fail a CI job over fixtures that no longer
+    /// represent the chain.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`RpcError::FixturesDrifted`] with the report if
+    /// [`changed_fraction`](Self::changed_fraction) exceeds `max_fraction`.
+    pub fn fail_if_changed_fraction(self, max_fraction: f64) -> Result<Self, RpcError> {
+        if self.changed_fraction() > max_fraction {
+            return Err(RpcError::FixturesDrifted {
+                report: Box::new(self),
+                max_fraction,
+            });
+        }
+        Ok(self)
+    }
+
+    /// The report as pretty-printed JSON.
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("drift reports always serialize")
+    }
+}
+
+impl fmt::Display for FixtureDriftReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: {} of {} accounts changed",
+            self.fixture.display(),
+            self.changed().count(),
+            self.accounts.len()
+        )?;
+        let counts: Vec<String> = [
+            DriftCategory::DataChanged,
+            DriftCategory::Missing,
+            DriftCategory::ProgramUpgraded,
+            DriftCategory::LamportsOnly,
+        ]
+        .into_iter()
+        .map(|category| (category, self.count(category)))
+        .filter(|(_, count)| *count > 0)
+        .map(|(category, count)| format!("{count} {category}"))
+        .collect();
+        if !counts.is_empty() {
+            write!(f, " ({})", counts.join(", "))?;
+        }
+        for account in self.changed() {
+            write!(f, "\n  {}: {}", account.pubkey, account.category)?;
+            if let Some((fixture, live)) = account.deploy_slots {
+                write!(f, ", deployed at slot {fixture} -> {live}")?;
+            } else if account.changed_bytes > 0 {
+                write!(f, ", {} bytes", account.changed_bytes)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl RpcAccountStore {
+    /// Re-fetch every account of the fixture at `fixture_path` and report how each
+    /// compares with the chain, for a scheduled job that checks the committed
+    /// fixtures are still representative without failing regular builds.
+    ///
+    /// `fixture_path` is a [fixture directory](Self::with_fixture_dir) or a file
+    /// written by [`save_fixture`](Self::save_fixture). The store's cache is left
+    /// alone; its budget, rate limit and retries apply to the requests.
+    ///
+    /// ```rust,ignore
+    /// #[tokio::test]
+    /// #[ignore = "nightly: compares the fixtures with mainnet"]
+    /// async fn fixtures_match_mainnet() -> Result<(), RpcError> {
+    ///     let report = RpcAccountStore::mainnet().drift_check("tests/fixtures/swap").await?;
+    ///     std::fs::write("target/fixture-drift.json", report.to_json()).unwrap();
+    ///     println!("{report}");
+    ///     report.fail_if_changed_fraction(0.25)?;
+    ///     Ok(())
+    /// }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns [`RpcError::Fixture`] if the fixture can't be read, and the fetch's
+    /// errors.
+    pub async fn drift_check(
+        &self,
+        fixture_path: impl AsRef<Path>,
+    ) -> Result<FixtureDriftReport, RpcError> {
+        let path = fixture_path.as_ref();
+        let fixtures = if path.is_dir() {
+            fixture::read_dir(path)?
+        } else {
+            let mut accounts = Vec::new();
+            fixture::read_lines(path, |pubkey, account| accounts.push((pubkey, account)))?;
+            accounts
+        };
+
+        let mut report = FixtureDriftReport {
+            fixture: path.to_path_buf(),
+            accounts: Vec::with_capacity(fixtures.len()),
+        };
+        if fixtures.is_empty() {
+            return Ok(report);
+        }
+        let pubkeys: Vec<Pubkey> = fixtures.iter().map(|(pubkey, _)| *pubkey).collect();
+        let live = self.fetch_from_rpc(&pubkeys).await?;
+        for ((pubkey, fixture), (_, live)) in fixtures.iter().zip(&live) {
+            report
+                .accounts
+                .push(FixtureAccountDrift::between(*pubkey, fixture, live.as_ref()));
+        }
+        Ok(report)
+    }
+}
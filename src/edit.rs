@@ -0,0 +1,143 @@
+//! Editing cached accounts through their Borsh types.
+
+use {
+    crate::{Provenance, RpcAccountStore, RpcError},
+    borsh::{BorshDeserialize, BorshSerialize},
+    solana_pubkey::Pubkey,
+};
+
+/// Length of an Anchor account discriminator.
+const DISCRIMINATOR_LEN: usize = 8;
+
+/// How [`RpcAccountStore::edit_account_as_with`] reads and writes an account. The
+/// default edits data that is the value alone, resizing it to the edited value if
+/// that's shorter.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct EditOptions {
+    /// The data starts with an 8-byte Anchor discriminator, which is kept as is.
+    pub anchor_discriminator: bool,
+    /// Zero the tail of the data when the edited value serializes shorter than the
+    /// data, keeping the account's size, as programs that allocate a fixed `space`
+    /// expect.
+    pub pad_shorter: bool,
+    /// Let the data grow when the edited value serializes longer, topping the
+    /// lamports up to the rent-exempt minimum of the new size.
+    pub allow_growth: bool,
+}
+
+impl EditOptions {
+    /// Options for Anchor accounts: skip the discriminator and pad the tail.
+    pub fn anchor() -> Self {
+        Self {
+            anchor_discriminator: true,
+            pad_shorter: true,
+            allow_growth: false,
+        }
+    }
+}
+
+impl RpcAccountStore {
+    /// Decode the cached account `pubkey` as `T`, let `edit` change it and write it
+    /// back, for edits that are easier with the program's types than as byte
+    /// offsets.
+    ///
+    /// ```rust,ignore
+    /// store.edit_account_as::<LendingMarket>(&market, |market| {
+    ///     market.owner = me;
+    ///     market.flags |= PAUSED;
+    /// })?;
+    /// ```
+    ///
+    /// Trailing bytes after the value are allowed when decoding. See
+    /// [`edit_account_as_with`](Self::edit_account_as_with) for Anchor accounts
+    /// and for keeping the account's size.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`RpcError::AccountNotFound`] if the account isn't cached, and
+    /// [`RpcError::AccountEdit`] if it doesn't decode as `T` or the edited value
+    /// serializes longer than the data.
+    pub fn edit_account_as<T: BorshSerialize + BorshDeserialize>(
+        &mut self,
+        pubkey: &Pubkey,
+        edit: impl FnOnce(&mut T),
+    ) -> Result<(), RpcError> {
+        self.edit_account_as_with(pubkey, EditOptions::default(), edit)
+    }
+
+    /// [`edit_account_as`](Self::edit_account_as) with `options`, such as
+    /// [`EditOptions::anchor`] for an Anchor account:
+    ///
+    /// ```rust,ignore
+    /// store.edit_account_as_with::<PoolState>(&pool, EditOptions::anchor(), |pool| {
+    ///     pool.paused = true;
+    /// })?;
+    /// ```
+    ///
+    /// The account is left untouched if this fails; on success its provenance
+    /// becomes [`Provenance::Patched`].
+    ///
+    /// # Errors
+    ///
+    /// Returns [`RpcError::AccountNotFound`] if the account isn't cached, and
+    /// [`RpcError::AccountEdit`] if it's too short for a discriminator, doesn't
+    /// decode as `T` or, unless [`allow_growth`](EditOptions::allow_growth) is set,
+    /// the edited value serializes longer than the data.
+    pub fn edit_account_as_with<T: BorshSerialize + BorshDeserialize>(
+        &mut self,
+        pubkey: &Pubkey,
+        options: EditOptions,
+        edit: impl FnOnce(&mut T),
+    ) -> Result<(), RpcError> {
+        let rent = self.rent();
+        let account = self
+            .cache
+            .get_mut(pubkey)
+            .ok_or(RpcError::AccountNotFound(*pubkey))?;
+        let failed = |reason| RpcError::AccountEdit {
+            pubkey: *pubkey,
+            reason,
+        };
+        let offset = if options.anchor_discriminator {
+            DISCRIMINATOR_LEN
+        } else {
+            0
+        };
+        let Some(mut body) = account.data.get(offset..) else {
+            return Err(failed(format!(
+                "{} bytes of data, too short for a discriminator",
+                account.data.len()
+            )));
+        };
+        let body_len = body.len();
+        let mut value = T::deserialize(&mut body).map_err(|e| {
+            failed(format!("doesn't decode as {}: {e}", std::any::type_name::<T>()))
+        })?;
+        edit(&mut value);
+        let mut data = account.data[..offset].to_vec();
+        value
+            .serialize(&mut data)
+            .map_err(|e| failed(format!("the edited value doesn't serialize: {e}")))?;
+
+        let edited_len = data.len() - offset;
+        if edited_len > body_len && !options.allow_growth {
+            return Err(failed(format!(
+                "the edited value takes {edited_len} bytes, more than the {body_len} the \
+                 account holds; set allow_growth to resize it"
+            )));
+        }
+        if edited_len < body_len && options.pad_shorter {
+            data.resize(offset + body_len, 0);
+        }
+        let previous_len = account.data.len();
+        let new_len = data.len();
+        account.data = data;
+        if new_len > previous_len {
+            account.lamports = account.lamports.max(rent.minimum_balance(new_len));
+        }
+        self.provenance
+            .insert(*pubkey, Provenance::Patched("edit_account_as"));
+        self.track_memory(previous_len, new_len);
+        Ok(())
+    }
+}
@@ -0,0 +1,80 @@
+//! Construction of the JSON-RPC client used by the default constructors.
+
+use {
+    crate::{
+        retry::{RetryAfter, DEFAULT_MAX_RETRY_AFTER},
+        stats::RpcStats,
+    },
+    reqwest::header::{HeaderMap, HeaderName, HeaderValue},
+    solana_commitment_config::CommitmentConfig,
+    solana_rpc_client::{
+        http_sender::HttpSender, nonblocking::rpc_client::RpcClient, rpc_client::RpcClientConfig,
+    },
+    std::{
+        sync::{Arc, Mutex},
+        time::Duration,
+    },
+};
+
+/// Matches the timeout `RpcClient::new_with_commitment` uses.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Everything needed to (re)build the [`RpcClient`] for a store.
+///
+/// Kept around so builder methods that change how requests are sent (such as
+/// custom headers) can rebuild the client after construction.
+#[derive(Clone)]
+pub(crate) struct RpcEndpoint {
+    pub(crate) url: String,
+    pub(crate) commitment: CommitmentConfig,
+    /// Extra headers sent with every request. Values are marked sensitive and
+    /// must never be printed.
+    pub(crate) headers: HeaderMap,
+    /// Longest single wait honored for a `Retry-After` header.
+    pub(crate) max_retry_after: Duration,
+}
+
+impl RpcEndpoint {
+    pub(crate) fn new(url: String, commitment: CommitmentConfig) -> Self {
+        Self {
+            url,
+            commitment,
+            headers: HeaderMap::new(),
+            max_retry_after: DEFAULT_MAX_RETRY_AFTER,
+        }
+    }
+
+    /// Add a header, validating its name and value.
+    pub(crate) fn insert_header(&mut self, name: &str, value: &str) -> Result<(), String> {
+        let name = HeaderName::try_from(name).map_err(|_| "invalid header name".to_string())?;
+        let mut value =
+            HeaderValue::try_from(value).map_err(|_| "invalid header value".to_string())?;
+        value.set_sensitive(true);
+        self.headers.insert(name, value);
+        Ok(())
+    }
+
+    /// Build a client that sends the custom headers and waits out 429 responses,
+    /// recording those waits in `stats`.
+    pub(crate) fn client(&self, stats: Arc<Mutex<RpcStats>>) -> RpcClient {
+        let mut headers = HttpSender::default_headers();
+        headers.extend(self.headers.clone());
+        let client = reqwest::Client::builder()
+            .default_headers(headers)
+            .timeout(REQUEST_TIMEOUT)
+            .pool_idle_timeout(REQUEST_TIMEOUT)
+            .build()
+            .expect("build rpc client");
+        let client = reqwest_middleware::ClientBuilder::new(client)
+            .with(RetryAfter {
+                max_wait: self.max_retry_after,
+                stats,
+            })
+            .build();
+
+        RpcClient::new_sender(
+            HttpSender::new_with_client_with_middleware(&self.url, client),
+            RpcClientConfig::with_commitment(self.commitment),
+        )
+    }
+}
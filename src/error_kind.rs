@@ -0,0 +1,757 @@
+//! Classifying errors: transient or permanent, and by stable code.
+
+use {
+    crate::{RpcError, TokenOperationError, TokenStateError},
+    reqwest::StatusCode,
+    solana_rpc_client_api::{
+        client_error::{Error as ClientError, ErrorKind as ClientErrorKind},
+        custom_error::{
+            JSON_RPC_SERVER_ERROR_BLOCK_NOT_AVAILABLE,
+            JSON_RPC_SERVER_ERROR_BLOCK_STATUS_NOT_AVAILABLE_YET,
+            JSON_RPC_SERVER_ERROR_MIN_CONTEXT_SLOT_NOT_REACHED, JSON_RPC_SERVER_ERROR_NODE_UNHEALTHY,
+        },
+        request::RpcError as RpcRequestError,
+    },
+};
+
+/// JSON-RPC errors a node returns while it catches up; asking again later can succeed.
+const TRANSIENT_RPC_CODES: [i64; 4] = [
+    JSON_RPC_SERVER_ERROR_BLOCK_NOT_AVAILABLE,
+    JSON_RPC_SERVER_ERROR_NODE_UNHEALTHY,
+    JSON_RPC_SERVER_ERROR_BLOCK_STATUS_NOT_AVAILABLE_YET,
+    JSON_RPC_SERVER_ERROR_MIN_CONTEXT_SLOT_NOT_REACHED,
+];
+
+/// What went wrong, in broad strokes, see [`RpcError::kind`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum RpcErrorKind {
+    /// The request didn't complete: connection failure, timeout or I/O error.
+    Transport,
+    /// The provider kept answering `429 Too Many Requests`.
+    RateLimited,
+    /// The node answered with an HTTP error status or a JSON-RPC error.
+    Rpc,
+    /// An account the store needed doesn't exist.
+    NotFound,
+    /// Data from the cluster or a fixture couldn't be used, e.g. a malformed program.
+    InvalidData,
+    /// The store's configuration forbade the request: offline mode, the RPC budget,
+    /// the cluster check or an invalid setting.
+    Configuration,
+    /// The store was asked for something it can't do, e.g. an account that was
+    /// never fetched or a clock that moves backwards.
+    Usage,
+}
+
+/// Whether an HTTP response with `status` is worth sending again.
+///
+/// Shared with the retry middleware so both agree on what's transient.
+pub(crate) fn retryable_status(status: StatusCode) -> bool {
+    status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error()
+}
+
+fn client_error_kind(error: &ClientError) -> RpcErrorKind {
+    match error.kind() {
+        ClientErrorKind::Io(_) | ClientErrorKind::Middleware(_) => RpcErrorKind::Transport,
+        ClientErrorKind::Reqwest(e) => match e.status() {
+            Some(StatusCode::TOO_MANY_REQUESTS) => RpcErrorKind::RateLimited,
+            Some(_) => RpcErrorKind::Rpc,
+            None if e.is_decode() => RpcErrorKind::InvalidData,
+            None => RpcErrorKind::Transport,
+        },
+        ClientErrorKind::RpcError(RpcRequestError::ParseError(_))
+        | ClientErrorKind::SerdeJson(_) => RpcErrorKind::InvalidData,
+        ClientErrorKind::SigningError(_) => RpcErrorKind::Usage,
+        _ => RpcErrorKind::Rpc,
+    }
+}
+
+fn client_error_code(error: &ClientError) -> &'static str {
+    match error.kind() {
+        ClientErrorKind::Reqwest(e) => match e.status() {
+            Some(StatusCode::TOO_MANY_REQUESTS) => "rpc_rate_limited",
+            Some(status) if status.is_server_error() => "rpc_server_error",
+            Some(_) => "rpc_http_error",
+            None if e.is_timeout() => "rpc_timeout",
+            None if e.is_connect() => "rpc_connect_failed",
+            None if e.is_decode() => "rpc_invalid_response",
+            None => "rpc_transport",
+        },
+        ClientErrorKind::RpcError(RpcRequestError::RpcResponseError { code, .. })
+            if TRANSIENT_RPC_CODES.contains(code) =>
+        {
+            "rpc_node_behind"
+        }
+        _ => match client_error_kind(error) {
+            RpcErrorKind::Transport => "rpc_transport",
+            RpcErrorKind::RateLimited => "rpc_rate_limited",
+            RpcErrorKind::InvalidData => "rpc_invalid_response",
+            RpcErrorKind::Usage => "rpc_signing_failed",
+            _ => "rpc_error",
+        },
+    }
+}
+
+/// Program data failures by the reason `add_programs` gives; the prefixes match the
+/// messages of `validate_elf` and `program_elf`.
+fn invalid_program_data_code(reason: &str) -> &'static str {
+    const CODES: [(&str, &str); 5] = [
+        ("Data too small to be a valid ELF", "elf_too_small"),
+        ("Invalid ELF magic number", "elf_invalid_magic"),
+        ("Invalid ELF class", "elf_invalid_class"),
+        ("Program data account not found", "programdata_missing"),
+        ("Program data account too small", "programdata_too_small"),
+    ];
+    CODES
+        .iter()
+        .find(|(prefix, _)| reason.starts_with(prefix))
+        .map_or("programdata_invalid", |(_, code)| code)
+}
+
+/// Program account failures by the reason `add_programs` gives, like
+/// [`invalid_program_data_code`].
+fn malformed_program_code(reason: &str) -> &'static str {
+    if reason.starts_with("BPF Loader v3 program account too small") {
+        "program_account_too_small"
+    } else if reason.starts_with("Invalid program data pubkey") {
+        "program_data_address_invalid"
+    } else {
+        "program_malformed"
+    }
+}
+
+fn client_error_is_retryable(error: &ClientError) -> bool {
+    match error.kind() {
+        ClientErrorKind::Reqwest(e) => e.status().map_or(!e.is_decode(), retryable_status),
+        ClientErrorKind::RpcError(RpcRequestError::RpcResponseError { code, .. }) => {
+            TRANSIENT_RPC_CODES.contains(code)
+        }
+        _ => client_error_kind(error) == RpcErrorKind::Transport,
+    }
+}
+
+impl RpcError {
+    /// The broad category of this error.
+    pub fn kind(&self) -> RpcErrorKind {
+        match self {
+            Self::Client(e) => client_error_kind(e),
+            Self::AccountNotFound(_)
+            | Self::AccountsNotFound(_)
+            | Self::AccountNotFoundInInstruction(_)
+            | Self::AccountsNotFoundInInstructions(_)
+            | Self::ProgramDataNotFound { .. }
+            | Self::ProgramDataAccountsNotFound(_)
+            | Self::TooFewBlocks { .. }
+            | Self::Timeout { .. } => RpcErrorKind::NotFound,
+            Self::InvalidProgramData { .. }
+            | Self::MalformedProgram { .. }
+            | Self::InvalidSysvar { .. }
+            | Self::InvalidTokenState { .. }
+            | Self::InvalidVoteAccount { .. }
+            | Self::InvalidLookupTable { .. }
+            | Self::FixturesDrifted { .. }
+            | Self::Fixture { .. }
+            | Self::Export { .. } => RpcErrorKind::InvalidData,
+            Self::InvalidHeader { .. }
+            | Self::Offline { .. }
+            | Self::InvalidEnvVar { .. }
+            | Self::InvalidConfig { .. }
+            | Self::BudgetExceeded { .. }
+            | Self::AccountTooLarge { .. }
+            | Self::LocalProgram { .. }
+            | Self::WrongCluster { .. } => RpcErrorKind::Configuration,
+            Self::AccountNotCached { .. }
+            | Self::TooManyProgramAccounts { .. }
+            | Self::TooManyReferencedAccounts { .. }
+            | Self::NegativeSlotOffset(_)
+            | Self::PastEpoch { .. }
+            | Self::OwnerProgramsNotLoaded(_)
+            | Self::RequirementsNotMet(_)
+            | Self::InstructionLints(_)
+            | Self::TokenOperation { .. }
+            | Self::WrongOwners(_)
+            | Self::ShadowedMocks(_)
+            | Self::UnexpectedWarnings(_) => RpcErrorKind::Usage,
+            #[cfg(feature = "pubsub")]
+            Self::Pubsub(_) => RpcErrorKind::Transport,
+            #[cfg(any(feature = "pyth", feature = "switchboard"))]
+            Self::InvalidOracleAccount { .. } => RpcErrorKind::InvalidData,
+            #[cfg(feature = "stake-pool")]
+            Self::InvalidStakePool { .. } => RpcErrorKind::InvalidData,
+            #[cfg(feature = "anchor-idl")]
+            Self::AnchorIdl { .. } => RpcErrorKind::InvalidData,
+            #[cfg(feature = "borsh")]
+            Self::AccountEdit { .. } => RpcErrorKind::Usage,
+        }
+    }
+
+    /// Whether the same call may succeed if tried again later: transport failures,
+    /// rate limiting, HTTP 5xx responses and JSON-RPC errors of nodes that are
+    /// behind (node unhealthy, block or minimum context slot not available yet).
+    ///
+    /// The store already retries rate-limited and 5xx responses itself, so when
+    /// these reach the caller the provider has been failing for a while.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            Self::Client(e) => client_error_is_retryable(e),
+            _ => self.kind() == RpcErrorKind::Transport,
+        }
+    }
+
+    /// A stable, machine-readable identifier of this error, also shown in brackets at
+    /// the start of its `Display` output, e.g. `[account_not_found]`.
+    ///
+    /// Codes are lowercase `snake_case` and never change meaning: existing codes are
+    /// only ever added to, so tooling can bucket failures by them. Client errors are
+    /// broken down by cause (`rpc_rate_limited`, `rpc_timeout`, `rpc_server_error`,
+    /// ...) and program errors by what's wrong (`elf_invalid_magic`,
+    /// `programdata_missing`, ...).
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::Client(e) => client_error_code(e),
+            Self::AccountNotFound(_) | Self::AccountNotFoundInInstruction(_) => {
+                "account_not_found"
+            }
+            Self::AccountsNotFound(_) | Self::AccountsNotFoundInInstructions(_) => {
+                "accounts_not_found"
+            }
+            Self::ProgramDataNotFound { .. } | Self::ProgramDataAccountsNotFound(_) => {
+                "programdata_missing"
+            }
+            Self::InvalidProgramData { reason, .. } => invalid_program_data_code(reason),
+            Self::MalformedProgram { reason, .. } => malformed_program_code(reason),
+            Self::InvalidHeader { .. } => "invalid_header",
+            Self::Offline { .. } => "offline",
+            Self::Fixture { .. } => "fixture_error",
+            Self::InvalidEnvVar { .. } => "invalid_env_var",
+            Self::InvalidConfig { .. } => "invalid_config",
+            Self::BudgetExceeded { .. } => "budget_exceeded",
+            Self::WrongCluster { .. } => "wrong_cluster",
+            Self::AccountNotCached { .. } => "account_not_cached",
+            Self::TooManyProgramAccounts { .. } => "too_many_program_accounts",
+            Self::TooManyReferencedAccounts { .. } => "too_many_referenced_accounts",
+            Self::InvalidSysvar { .. } => "sysvar_invalid",
+            Self::TooFewBlocks { .. } => "too_few_blocks",
+            Self::NegativeSlotOffset(_) => "negative_slot_offset",
+            Self::PastEpoch { .. } => "past_epoch",
+            Self::OwnerProgramsNotLoaded(_) => "owner_programs_not_loaded",
+            Self::WrongOwners(_) => "wrong_owners",
+            Self::ShadowedMocks(_) => "shadowed_mocks",
+            Self::UnexpectedWarnings(_) => "unexpected_warnings",
+            Self::AccountTooLarge { .. } => "account_too_large",
+            Self::RequirementsNotMet(_) => "requirements_not_met",
+            Self::InstructionLints(_) => "instruction_lints",
+            Self::LocalProgram { .. } => "local_program_invalid",
+            Self::Export { .. } => "export_failed",
+            Self::InvalidTokenState { reason, .. } => match reason {
+                TokenStateError::WrongOwner { .. } => "token_wrong_owner",
+                TokenStateError::WrongLength { .. } => "token_wrong_length",
+                TokenStateError::Uninitialized => "token_uninitialized",
+                TokenStateError::Malformed(_) => "token_malformed",
+            },
+            Self::InvalidVoteAccount { .. } => "vote_account_invalid",
+            Self::InvalidLookupTable { .. } => "lookup_table_invalid",
+            Self::TokenOperation { reason, .. } => match reason {
+                TokenOperationError::MintMismatch { .. } => "token_mint_mismatch",
+                TokenOperationError::InsufficientFunds { .. } => "token_insufficient_funds",
+                TokenOperationError::Frozen(_) => "token_account_frozen",
+                TokenOperationError::Overflow => "token_amount_overflow",
+                TokenOperationError::NotNative(_) => "token_not_native",
+                TokenOperationError::LamportsBelowAmount { .. } => "token_native_lamports_short",
+            },
+            Self::FixturesDrifted { .. } => "fixtures_drifted",
+            Self::Timeout { .. } => "account_wait_timeout",
+            #[cfg(feature = "pubsub")]
+            Self::Pubsub(_) => "pubsub_error",
+            #[cfg(any(feature = "pyth", feature = "switchboard"))]
+            Self::InvalidOracleAccount { .. } => "oracle_account_invalid",
+            #[cfg(feature = "stake-pool")]
+            Self::InvalidStakePool { .. } => "stake_pool_invalid",
+            #[cfg(feature = "anchor-idl")]
+            Self::AnchorIdl { .. } => "anchor_idl_invalid",
+            #[cfg(feature = "borsh")]
+            Self::AccountEdit { .. } => "account_edit_failed",
+        }
+    }
+
+    /// Whether an account the store needed doesn't exist.
+    pub fn is_not_found(&self) -> bool {
+        self.kind() == RpcErrorKind::NotFound
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::{Cluster, FixtureDriftReport, MissingAccount, RpcStats},
+        solana_pubkey::Pubkey,
+        std::{io, path::PathBuf, time::Duration},
+    };
+
+    /// The variant of `error`. The match has no wildcard arm although `RpcError` is
+    /// `#[non_exhaustive]`, so a new variant doesn't build until it's listed here and
+    /// given an example with its code in [`examples`].
+    fn variant(error: &RpcError) -> &'static str {
+        match error {
+            RpcError::Client(_) => "Client",
+            RpcError::AccountNotFound(_) => "AccountNotFound",
+            RpcError::AccountsNotFound(_) => "AccountsNotFound",
+            RpcError::AccountNotFoundInInstruction(_) => "AccountNotFoundInInstruction",
+            RpcError::AccountsNotFoundInInstructions(_) => "AccountsNotFoundInInstructions",
+            RpcError::ProgramDataNotFound { .. } => "ProgramDataNotFound",
+            RpcError::ProgramDataAccountsNotFound(_) => "ProgramDataAccountsNotFound",
+            RpcError::InvalidProgramData { .. } => "InvalidProgramData",
+            RpcError::MalformedProgram { .. } => "MalformedProgram",
+            RpcError::InvalidHeader { .. } => "InvalidHeader",
+            RpcError::Offline { .. } => "Offline",
+            RpcError::Fixture { .. } => "Fixture",
+            RpcError::InvalidEnvVar { .. } => "InvalidEnvVar",
+            RpcError::InvalidConfig { .. } => "InvalidConfig",
+            RpcError::BudgetExceeded { .. } => "BudgetExceeded",
+            RpcError::WrongCluster { .. } => "WrongCluster",
+            RpcError::AccountNotCached { .. } => "AccountNotCached",
+            RpcError::TooManyProgramAccounts { .. } => "TooManyProgramAccounts",
+            RpcError::TooManyReferencedAccounts { .. } => "TooManyReferencedAccounts",
+            RpcError::InvalidSysvar { .. } => "InvalidSysvar",
+            RpcError::TooFewBlocks { .. } => "TooFewBlocks",
+            RpcError::NegativeSlotOffset(_) => "NegativeSlotOffset",
+            RpcError::PastEpoch { .. } => "PastEpoch",
+            RpcError::OwnerProgramsNotLoaded(_) => "OwnerProgramsNotLoaded",
+            RpcError::WrongOwners(_) => "WrongOwners",
+            RpcError::ShadowedMocks(_) => "ShadowedMocks",
+            RpcError::UnexpectedWarnings(_) => "UnexpectedWarnings",
+            RpcError::AccountTooLarge { .. } => "AccountTooLarge",
+            RpcError::RequirementsNotMet(_) => "RequirementsNotMet",
+            RpcError::InvalidTokenState { .. } => "InvalidTokenState",
+            RpcError::InvalidVoteAccount { .. } => "InvalidVoteAccount",
+            RpcError::InvalidLookupTable { .. } => "InvalidLookupTable",
+            RpcError::TokenOperation { .. } => "TokenOperation",
+            RpcError::FixturesDrifted { .. } => "FixturesDrifted",
+            RpcError::Timeout { .. } => "Timeout",
+            RpcError::LocalProgram { .. } => "LocalProgram",
+            RpcError::InstructionLints(_) => "InstructionLints",
+            RpcError::Export { .. } => "Export",
+            #[cfg(feature = "pubsub")]
+            RpcError::Pubsub(_) => "Pubsub",
+            #[cfg(any(feature = "pyth", feature = "switchboard"))]
+            RpcError::InvalidOracleAccount { .. } => "InvalidOracleAccount",
+            #[cfg(feature = "stake-pool")]
+            RpcError::InvalidStakePool { .. } => "InvalidStakePool",
+            #[cfg(feature = "anchor-idl")]
+            RpcError::AnchorIdl { .. } => "AnchorIdl",
+            #[cfg(feature = "borsh")]
+            RpcError::AccountEdit { .. } => "AccountEdit",
+        }
+    }
+
+    fn client(kind: ClientErrorKind) -> RpcError {
+        RpcError::Client(kind.into())
+    }
+
+    /// A client error for an HTTP response with `status`, as reqwest reports it.
+    fn http_status(status: u16) -> RpcError {
+        let response = http::Response::builder().status(status).body("").unwrap();
+        let error = reqwest::Response::from(response).error_for_status().unwrap_err();
+        client(ClientErrorKind::Reqwest(error))
+    }
+
+    fn rpc_response_error(code: i64) -> RpcError {
+        client(ClientErrorKind::RpcError(RpcRequestError::RpcResponseError {
+            code,
+            message: "message".to_string(),
+            data: solana_rpc_client_api::request::RpcResponseErrorData::Empty,
+        }))
+    }
+
+    fn program_data(reason: &str) -> RpcError {
+        RpcError::InvalidProgramData {
+            program: Pubkey::new_unique(),
+            reason: reason.to_string(),
+        }
+    }
+
+    fn malformed(reason: &str) -> RpcError {
+        RpcError::MalformedProgram {
+            program: Pubkey::new_unique(),
+            reason: reason.to_string(),
+        }
+    }
+
+    fn token_state(reason: TokenStateError) -> RpcError {
+        RpcError::InvalidTokenState {
+            pubkey: Pubkey::new_unique(),
+            state: "token account",
+            reason,
+        }
+    }
+
+    fn token_operation(reason: TokenOperationError) -> RpcError {
+        RpcError::TokenOperation {
+            operation: "transfer",
+            reason,
+        }
+    }
+
+    /// An error of every variant, and of every code a variant can give, with the code
+    /// it must keep. HTTP client errors are left out: they can't be built without a
+    /// response.
+    fn examples() -> Vec<(RpcError, &'static str)> {
+        let pubkey = Pubkey::new_unique();
+        let reason = || "reason".to_string();
+        let missing = MissingAccount {
+            pubkey,
+            instruction_index: 0,
+            account_index: 0,
+            is_signer: false,
+            is_writable: false,
+            label: None,
+        };
+        let node_behind = RpcRequestError::RpcResponseError {
+            code: JSON_RPC_SERVER_ERROR_NODE_UNHEALTHY,
+            message: "Node is behind".to_string(),
+            data: solana_rpc_client_api::request::RpcResponseErrorData::Empty,
+        };
+        vec![
+            (client(ClientErrorKind::Io(io::ErrorKind::ConnectionReset.into())), "rpc_transport"),
+            (client(ClientErrorKind::RpcError(node_behind)), "rpc_node_behind"),
+            (
+                client(ClientErrorKind::RpcError(RpcRequestError::ParseError(reason()))),
+                "rpc_invalid_response",
+            ),
+            (client(ClientErrorKind::Custom(reason())), "rpc_error"),
+            (RpcError::AccountNotFound(pubkey), "account_not_found"),
+            (RpcError::AccountsNotFound(vec![pubkey]), "accounts_not_found"),
+            (RpcError::AccountNotFoundInInstruction(missing.clone()), "account_not_found"),
+            (RpcError::AccountsNotFoundInInstructions(vec![missing]), "accounts_not_found"),
+            (
+                RpcError::ProgramDataNotFound {
+                    program: pubkey,
+                    program_data: pubkey,
+                },
+                "programdata_missing",
+            ),
+            (RpcError::ProgramDataAccountsNotFound(vec![(pubkey, pubkey)]), "programdata_missing"),
+            (program_data("Data too small to be a valid ELF: 3 bytes"), "elf_too_small"),
+            (program_data("Invalid ELF magic number"), "elf_invalid_magic"),
+            (program_data("Invalid ELF class: 1"), "elf_invalid_class"),
+            (program_data("Program data account not found"), "programdata_missing"),
+            (program_data("Program data account too small: 10 bytes"), "programdata_too_small"),
+            (program_data("something else"), "programdata_invalid"),
+            (
+                malformed("BPF Loader v3 program account too small: 4 bytes"),
+                "program_account_too_small",
+            ),
+            (malformed("Invalid program data pubkey: bad"), "program_data_address_invalid"),
+            (malformed("something else"), "program_malformed"),
+            (
+                RpcError::InvalidHeader {
+                    name: "x-api-key".to_string(),
+                    reason: reason(),
+                },
+                "invalid_header",
+            ),
+            (RpcError::Offline { method: "getSlot" }, "offline"),
+            (
+                RpcError::Fixture {
+                    path: PathBuf::new(),
+                    reason: reason(),
+                },
+                "fixture_error",
+            ),
+            (
+                RpcError::InvalidEnvVar {
+                    name: "RPC_URL",
+                    reason: reason(),
+                },
+                "invalid_env_var",
+            ),
+            (
+                RpcError::InvalidConfig {
+                    path: PathBuf::new(),
+                    reason: reason(),
+                },
+                "invalid_config",
+            ),
+            (
+                RpcError::BudgetExceeded {
+                    limit: 1,
+                    attempted_method: "getSlot",
+                    spent: RpcStats::default(),
+                },
+                "budget_exceeded",
+            ),
+            (
+                RpcError::WrongCluster {
+                    expected: Cluster::Mainnet,
+                    actual: Cluster::Devnet,
+                },
+                "wrong_cluster",
+            ),
+            (
+                RpcError::AccountNotCached {
+                    pubkey,
+                    instruction: 0,
+                    index: 0,
+                },
+                "account_not_cached",
+            ),
+            (
+                RpcError::TooManyProgramAccounts {
+                    program: pubkey,
+                    limit: 1,
+                },
+                "too_many_program_accounts",
+            ),
+            (
+                RpcError::TooManyReferencedAccounts {
+                    root: pubkey,
+                    limit: 1,
+                },
+                "too_many_referenced_accounts",
+            ),
+            (
+                RpcError::InvalidSysvar {
+                    sysvar: "Rent",
+                    reason: reason(),
+                },
+                "sysvar_invalid",
+            ),
+            (
+                RpcError::TooFewBlocks {
+                    depth: 2,
+                    found: 1,
+                    slots: 64,
+                },
+                "too_few_blocks",
+            ),
+            (RpcError::NegativeSlotOffset(-1), "negative_slot_offset"),
+            (RpcError::PastEpoch { epoch: 1, slot: 1 }, "past_epoch"),
+            (RpcError::OwnerProgramsNotLoaded(vec![pubkey]), "owner_programs_not_loaded"),
+            (RpcError::WrongOwners(Vec::new()), "wrong_owners"),
+            (RpcError::ShadowedMocks(Vec::new()), "shadowed_mocks"),
+            (RpcError::UnexpectedWarnings(Vec::new()), "unexpected_warnings"),
+            (
+                RpcError::AccountTooLarge {
+                    pubkey,
+                    size: 2,
+                    limit: 1,
+                },
+                "account_too_large",
+            ),
+            (RpcError::RequirementsNotMet(Vec::new()), "requirements_not_met"),
+            (
+                token_state(TokenStateError::WrongOwner {
+                    expected: vec![pubkey],
+                    actual: pubkey,
+                }),
+                "token_wrong_owner",
+            ),
+            (
+                token_state(TokenStateError::WrongLength {
+                    expected: 165,
+                    actual: 82,
+                }),
+                "token_wrong_length",
+            ),
+            (token_state(TokenStateError::Uninitialized), "token_uninitialized"),
+            (token_state(TokenStateError::Malformed(reason())), "token_malformed"),
+            (
+                RpcError::InvalidVoteAccount {
+                    pubkey,
+                    reason: reason(),
+                },
+                "vote_account_invalid",
+            ),
+            (
+                RpcError::InvalidLookupTable {
+                    pubkey,
+                    reason: reason(),
+                },
+                "lookup_table_invalid",
+            ),
+            (
+                token_operation(TokenOperationError::MintMismatch {
+                    from: pubkey,
+                    to: pubkey,
+                }),
+                "token_mint_mismatch",
+            ),
+            (
+                token_operation(TokenOperationError::InsufficientFunds {
+                    available: 1,
+                    requested: 2,
+                }),
+                "token_insufficient_funds",
+            ),
+            (token_operation(TokenOperationError::Frozen(pubkey)), "token_account_frozen"),
+            (token_operation(TokenOperationError::Overflow), "token_amount_overflow"),
+            (token_operation(TokenOperationError::NotNative(pubkey)), "token_not_native"),
+            (
+                token_operation(TokenOperationError::LamportsBelowAmount {
+                    lamports: 1,
+                    needed: 2,
+                }),
+                "token_native_lamports_short",
+            ),
+            (
+                RpcError::FixturesDrifted {
+                    report: Box::new(FixtureDriftReport {
+                        fixture: PathBuf::new(),
+                        accounts: Vec::new(),
+                    }),
+                    max_fraction: 0.5,
+                },
+                "fixtures_drifted",
+            ),
+            (
+                RpcError::Timeout {
+                    pubkey,
+                    waited: Duration::from_secs(1),
+                },
+                "account_wait_timeout",
+            ),
+            (
+                RpcError::LocalProgram {
+                    path: PathBuf::new(),
+                    reason: reason(),
+                },
+                "local_program_invalid",
+            ),
+            (RpcError::InstructionLints(Vec::new()), "instruction_lints"),
+            (
+                RpcError::Export {
+                    target: "LiteSVM",
+                    pubkey,
+                    reason: reason(),
+                },
+                "export_failed",
+            ),
+            #[cfg(feature = "pubsub")]
+            (RpcError::Pubsub(reason()), "pubsub_error"),
+            #[cfg(any(feature = "pyth", feature = "switchboard"))]
+            (
+                RpcError::InvalidOracleAccount {
+                    pubkey,
+                    oracle: "Pyth",
+                    reason: reason(),
+                },
+                "oracle_account_invalid",
+            ),
+            #[cfg(feature = "stake-pool")]
+            (
+                RpcError::InvalidStakePool {
+                    pubkey,
+                    reason: reason(),
+                },
+                "stake_pool_invalid",
+            ),
+            #[cfg(feature = "anchor-idl")]
+            (
+                RpcError::AnchorIdl {
+                    account: "Vault".to_string(),
+                    reason: reason(),
+                },
+                "anchor_idl_invalid",
+            ),
+            #[cfg(feature = "borsh")]
+            (
+                RpcError::AccountEdit {
+                    pubkey,
+                    reason: reason(),
+                },
+                "account_edit_failed",
+            ),
+        ]
+    }
+
+    #[test]
+    fn every_variant_keeps_its_code() {
+        let mut variants = std::collections::BTreeSet::new();
+        for (error, code) in examples() {
+            assert_eq!(error.code(), code, "{error:?}");
+            assert!(error.to_string().starts_with(&format!("[{code}] ")), "{error}");
+            variants.insert(variant(&error));
+        }
+        // One entry per arm of `variant` that's compiled in
+        let features = [
+            cfg!(feature = "pubsub"),
+            cfg!(any(feature = "pyth", feature = "switchboard")),
+            cfg!(feature = "stake-pool"),
+            cfg!(feature = "anchor-idl"),
+            cfg!(feature = "borsh"),
+        ];
+        let optional = features.iter().filter(|enabled| **enabled).count();
+        assert_eq!(variants.len(), 38 + optional, "{variants:?}");
+    }
+
+    #[test]
+    fn transport_failures_are_retryable() {
+        for kind in [io::ErrorKind::TimedOut, io::ErrorKind::ConnectionReset] {
+            let error = client(ClientErrorKind::Io(kind.into()));
+            assert_eq!(error.kind(), RpcErrorKind::Transport, "{error}");
+            assert!(error.is_retryable(), "{error}");
+            assert!(!error.is_not_found());
+        }
+    }
+
+    #[test]
+    fn nodes_that_are_behind_are_retryable() {
+        for code in TRANSIENT_RPC_CODES {
+            let error = rpc_response_error(code);
+            assert_eq!(error.kind(), RpcErrorKind::Rpc, "{error}");
+            assert_eq!(error.code(), "rpc_node_behind");
+            assert!(error.is_retryable(), "{error}");
+        }
+        // Invalid params won't get better by asking again
+        let error = rpc_response_error(-32602);
+        assert_eq!(error.code(), "rpc_error");
+        assert!(!error.is_retryable(), "{error}");
+    }
+
+    #[test]
+    fn rate_limits_and_server_errors_are_retryable() {
+        for status in [429, 500, 502, 503] {
+            assert!(retryable_status(StatusCode::from_u16(status).unwrap()), "{status}");
+            assert!(http_status(status).is_retryable(), "{status}");
+        }
+        let error = http_status(429);
+        assert_eq!(error.kind(), RpcErrorKind::RateLimited);
+        assert_eq!(error.code(), "rpc_rate_limited");
+        let error = http_status(503);
+        assert_eq!(error.kind(), RpcErrorKind::Rpc);
+        assert_eq!(error.code(), "rpc_server_error");
+    }
+
+    #[test]
+    fn client_errors_are_not_retryable() {
+        for status in [400, 401, 403, 404] {
+            assert!(!retryable_status(StatusCode::from_u16(status).unwrap()), "{status}");
+            let error = http_status(status);
+            assert!(!error.is_retryable(), "{status}");
+            assert_eq!((error.kind(), error.code()), (RpcErrorKind::Rpc, "rpc_http_error"));
+        }
+    }
+
+    #[test]
+    fn unparsable_responses_are_not_retryable() {
+        let error = client(ClientErrorKind::RpcError(RpcRequestError::ParseError(
+            "expected value".to_string(),
+        )));
+        assert_eq!(error.kind(), RpcErrorKind::InvalidData);
+        assert!(!error.is_retryable());
+        assert!(!error.is_not_found());
+    }
+
+    #[test]
+    fn missing_accounts_are_not_found() {
+        let pubkey = Pubkey::new_unique();
+        for error in [RpcError::AccountNotFound(pubkey), RpcError::AccountsNotFound(vec![pubkey])] {
+            assert_eq!(error.kind(), RpcErrorKind::NotFound, "{error}");
+            assert!(error.is_not_found(), "{error}");
+            assert!(!error.is_retryable(), "{error}");
+        }
+        assert!(!RpcError::Offline { method: "getSlot" }.is_not_found());
+    }
+}
@@ -0,0 +1,285 @@
+//! Seeding other test frameworks, such as LiteSVM and `ProgramTest`, with the cached
+//! accounts.
+
+use {
+    crate::{fixture, program_data_address, RpcAccountStore, RpcError},
+    solana_account::Account,
+    solana_pubkey::Pubkey,
+    solana_sdk_ids::{bpf_loader_upgradeable, native_loader, sysvar},
+    std::{fs, path::Path},
+};
+#[cfg(feature = "litesvm")]
+use litesvm::LiteSVM;
+#[cfg(feature = "program-test")]
+use {
+    solana_program_test::ProgramTest,
+    solana_rent::Rent,
+    solana_sdk_ids::bpf_loader,
+};
+
+/// The most data an account can hold, which the bank of a `ProgramTest` enforces.
+#[cfg(feature = "program-test")]
+const MAX_ACCOUNT_DATA: usize = 10 * 1024 * 1024;
+
+/// `path` as a single shell word.
+fn shell_word(path: &Path) -> String {
+    let path = path.display().to_string();
+    let is_plain = path
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || "/._-+:=@,".contains(c));
+    if is_plain && !path.is_empty() {
+        path
+    } else {
+        format!("'{}'", path.replace('\'', "'\\''"))
+    }
+}
+
+impl RpcAccountStore {
+    /// Pass every cached account to `sink`, to seed a VM other than Mollusk.
+    ///
+    /// Accounts come sorted by pubkey, executable accounts last, so a BPF Loader v3
+    /// program comes after its program data account, which most VMs need to load it.
+    ///
+    /// ```rust,ignore
+    /// let mut accounts = HashMap::new();
+    /// store.export_accounts(|pubkey, account| {
+    ///     accounts.insert(pubkey, account);
+    /// });
+    /// ```
+    pub fn export_accounts(&self, mut sink: impl FnMut(Pubkey, Account)) {
+        let mut accounts: Vec<(&Pubkey, &Account)> = self.cache.iter().collect();
+        accounts.sort_by_key(|(pubkey, account)| (account.executable, **pubkey));
+        for (pubkey, account) in accounts {
+            sink(*pubkey, account.clone());
+        }
+    }
+
+    /// Write every cached account into `svm` and load the programs among them, as
+    /// [`add_programs`](Self::add_programs) does for Mollusk.
+    ///
+    /// BPF Loader v2 and v3 programs are set with their on-chain accounts, so
+    /// LiteSVM loads them from their ELF, and programs registered with
+    /// [`with_program_from_file`](Self::with_program_from_file) are added from
+    /// their file instead. Other executable accounts, such as builtins, are skipped.
+    /// Accounts already in `svm` are overwritten.
+    ///
+    /// ```rust,ignore
+    /// let mut svm = LiteSVM::new();
+    /// let store = RpcAccountStore::mainnet().from_instruction(&ix).await?;
+    /// store.into_litesvm(&mut svm)?;
+    /// svm.send_transaction(tx).unwrap();
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns [`RpcError::InvalidProgramData`] if a program's data account isn't
+    /// cached or its ELF fails validation, [`RpcError::LocalProgram`] if a local ELF
+    /// can't be read, and [`RpcError::Export`] if LiteSVM rejects an account.
+    #[cfg(feature = "litesvm")]
+    pub fn into_litesvm(&self, svm: &mut LiteSVM) -> Result<(), RpcError> {
+        let export_error = |pubkey: Pubkey, reason: String| RpcError::Export {
+            target: "LiteSVM",
+            pubkey,
+            reason,
+        };
+        let mut accounts = Vec::new();
+        self.export_accounts(|pubkey, account| accounts.push((pubkey, account)));
+        for (pubkey, account) in accounts {
+            if account.executable {
+                if self.local_programs.contains_key(&pubkey) {
+                    continue;
+                }
+                let Some(elf) = self.shared_elf(&pubkey, &self.cache[&pubkey])? else {
+                    continue;
+                };
+                if let Some(warning) = self.check_program_elf(&pubkey, &elf)? {
+                    self.notify(&warning);
+                }
+            }
+            svm.set_account(pubkey, account)
+                .map_err(|e| export_error(pubkey, e.to_string()))?;
+        }
+
+        let mut programs: Vec<_> = self.local_programs.iter().collect();
+        programs.sort();
+        for (pubkey, path) in programs {
+            let elf = fs::read(path).map_err(|e| RpcError::LocalProgram {
+                path: path.clone(),
+                reason: e.to_string(),
+            })?;
+            if let Some(warning) = self.check_program_elf(pubkey, &elf)? {
+                self.notify(&warning);
+            }
+            svm.add_program(*pubkey, &elf)
+                .map_err(|e| export_error(*pubkey, e.to_string()))?;
+        }
+        Ok(())
+    }
+
+    /// Add every cached account to `program_test`, so a `ProgramTest` suite starts
+    /// from the same state as Mollusk tests of the store.
+    ///
+    /// BPF Loader v2 and v3 programs are added with their on-chain accounts after
+    /// their ELF is validated, and programs registered with
+    /// [`with_program_from_file`](Self::with_program_from_file) as BPF Loader v2
+    /// programs, the way `ProgramTest::add_program` adds them. Sysvars are skipped,
+    /// since the bank creates its own, and so are accounts without lamports, which
+    /// don't exist for the bank.
+    ///
+    /// ```rust,ignore
+    /// let mut program_test = ProgramTest::default();
+    /// store.apply_to_program_test(&mut program_test)?;
+    /// let (banks_client, payer, blockhash) = program_test.start().await;
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns [`RpcError::Export`] if an account holds more than the 10 MiB of
+    /// data the bank allows, [`RpcError::InvalidProgramData`] if a program's data
+    /// account isn't cached or its ELF fails validation, and
+    /// [`RpcError::LocalProgram`] if a local ELF can't be read.
+    #[cfg(feature = "program-test")]
+    pub fn apply_to_program_test(&self, program_test: &mut ProgramTest) -> Result<(), RpcError> {
+        let check_size = |pubkey: Pubkey, size: usize| {
+            if size > MAX_ACCOUNT_DATA {
+                return Err(RpcError::Export {
+                    target: "ProgramTest",
+                    pubkey,
+                    reason: format!(
+                        "{size} bytes of data, more than the {MAX_ACCOUNT_DATA} an account can hold"
+                    ),
+                });
+            }
+            Ok(())
+        };
+        let mut accounts = Vec::new();
+        self.export_accounts(|pubkey, account| accounts.push((pubkey, account)));
+        for (pubkey, account) in accounts {
+            if account.lamports == 0 || account.owner == sysvar::ID {
+                continue;
+            }
+            if account.executable {
+                if self.local_programs.contains_key(&pubkey) {
+                    continue;
+                }
+                let Some(elf) = self.shared_elf(&pubkey, &self.cache[&pubkey])? else {
+                    continue;
+                };
+                if let Some(warning) = self.check_program_elf(&pubkey, &elf)? {
+                    self.notify(&warning);
+                }
+            }
+            check_size(pubkey, account.data.len())?;
+            program_test.add_account(pubkey, account);
+        }
+
+        let mut programs: Vec<_> = self.local_programs.iter().collect();
+        programs.sort();
+        for (pubkey, path) in programs {
+            let elf = fs::read(path).map_err(|e| RpcError::LocalProgram {
+                path: path.clone(),
+                reason: e.to_string(),
+            })?;
+            if let Some(warning) = self.check_program_elf(pubkey, &elf)? {
+                self.notify(&warning);
+            }
+            check_size(*pubkey, elf.len())?;
+            program_test.add_account(
+                *pubkey,
+                Account {
+                    lamports: Rent::default().minimum_balance(elf.len()).max(1),
+                    data: elf,
+                    owner: bpf_loader::ID,
+                    executable: true,
+                    rent_epoch: 0,
+                },
+            );
+        }
+        Ok(())
+    }
+
+    /// Write the cached state into `dir` for a `solana-test-validator`
+    /// reproduction, and return the arguments that load it.
+    ///
+    /// Accounts are written as `<pubkey>.json` in the layout of
+    /// `solana account --output json`, which `--account` reads, and the ELFs of
+    /// BPF Loader v2 and v3 programs, or of programs registered with
+    /// [`with_program_from_file`](Self::with_program_from_file), as `<pubkey>.so`
+    /// for `--bpf-program`; the program data accounts of v3 programs are left out,
+    /// since the validator creates them. Other executable accounts, except builtins,
+    /// become `--clone <pubkey>`, which needs `--url` too. Sysvars and accounts
+    /// without lamports are skipped.
+    ///
+    /// Arguments come one account per line, joined with `\` continuations, so they
+    /// can be pasted after the command or into a script:
+    ///
+    /// ```rust,ignore
+    /// let args = store.export_validator_args("repro")?;
+    /// fs::write("repro/validator.sh", format!("solana-test-validator --reset \\\n{args}\n"))?;
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns [`RpcError::Fixture`] if a file can't be written,
+    /// [`RpcError::LocalProgram`] if a local ELF can't be read, and
+    /// [`RpcError::InvalidProgramData`] or [`RpcError::MalformedProgram`] if a
+    /// program's ELF can't be extracted.
+    pub fn export_validator_args(&self, dir: impl AsRef<Path>) -> Result<String, RpcError> {
+        let dir = dir.as_ref();
+        let write_error = |path: &Path, e: std::io::Error| RpcError::Fixture {
+            path: path.to_path_buf(),
+            reason: e.to_string(),
+        };
+        fs::create_dir_all(dir).map_err(|e| write_error(dir, e))?;
+
+        let mut pubkeys: Vec<&Pubkey> = self.cache.keys().collect();
+        pubkeys.extend(self.local_programs.keys());
+        pubkeys.sort();
+        pubkeys.dedup();
+
+        // Program data of the programs exported as ELFs
+        let mut program_data = Vec::new();
+        for pubkey in &pubkeys {
+            let account = self.cache.get(pubkey);
+            let is_v3_program = account.is_some_and(|account| {
+                account.executable && account.owner == bpf_loader_upgradeable::ID
+            });
+            if let (Some(account), true) = (account, is_v3_program) {
+                program_data.push(program_data_address(pubkey, account)?);
+            }
+        }
+
+        let mut args = Vec::new();
+        for pubkey in pubkeys {
+            let path = dir.join(format!("{pubkey}.so"));
+            if let Some(local) = self.local_programs.get(pubkey) {
+                let elf = fs::read(local).map_err(|e| RpcError::LocalProgram {
+                    path: local.clone(),
+                    reason: e.to_string(),
+                })?;
+                fs::write(&path, elf).map_err(|e| write_error(&path, e))?;
+                args.push(format!("--bpf-program {pubkey} {}", shell_word(&path)));
+                continue;
+            }
+            let account = &self.cache[pubkey];
+            if account.lamports == 0
+                || account.owner == sysvar::ID
+                || program_data.contains(pubkey)
+            {
+                continue;
+            }
+            if !account.executable {
+                fixture::write_account(dir, pubkey, account)
+                    .map_err(|e| write_error(&fixture::account_path(dir, pubkey), e))?;
+                let path = fixture::account_path(dir, pubkey);
+                args.push(format!("--account {pubkey} {}", shell_word(&path)));
+            } else if let Some(elf) = self.shared_elf(pubkey, account)? {
+                fs::write(&path, &elf).map_err(|e| write_error(&path, e))?;
+                args.push(format!("--bpf-program {pubkey} {}", shell_word(&path)));
+            } else if account.owner != native_loader::ID {
+                args.push(format!("--clone {pubkey}"));
+            }
+        }
+        Ok(args.join(" \\\n"))
+    }
+}
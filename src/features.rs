@@ -0,0 +1,62 @@
+//! Syncing Mollusk's feature set with the gates activated on the cluster.
+
+use {
+    crate::{RpcAccountStore, RpcError},
+    agave_feature_set::{FeatureSet, FEATURE_NAMES},
+    mollusk_svm::{program::ProgramCache, Mollusk},
+    solana_account::Account,
+    solana_pubkey::Pubkey,
+    solana_sdk_ids::feature,
+};
+
+/// The activation slot recorded in a feature account, if it has been activated.
+fn activation_slot(account: &Account) -> Option<u64> {
+    if account.owner != feature::ID {
+        return None;
+    }
+    // A feature account holds a bincode `Option<u64>`.
+    bincode::deserialize::<Option<u64>>(&account.data).ok().flatten()
+}
+
+impl RpcAccountStore {
+    /// Force feature gates on (`true`) or off (`false`) regardless of the cluster,
+    /// applied by [`with_synced_features`](Self::with_synced_features) on top of the
+    /// on-chain state.
+    ///
+    /// Later overrides of the same gate win.
+    pub fn with_feature_overrides(mut self, overrides: &[(Pubkey, bool)]) -> Self {
+        self.feature_overrides.extend_from_slice(overrides);
+        self
+    }
+
+    /// Replace Mollusk's feature set with the gates activated on the cluster.
+    ///
+    /// Fetches the feature account of every gate this version of Agave knows about;
+    /// a gate is active if its account exists and records an activation slot. Feature
+    /// accounts aren't added to the cache.
+    ///
+    /// Call this before loading programs: the syscalls available to a program are
+    /// fixed when it's added to Mollusk's program cache.
+    pub async fn with_synced_features(self, mollusk: &mut Mollusk) -> Result<Self, RpcError> {
+        let feature_ids: Vec<Pubkey> = FEATURE_NAMES.keys().copied().collect();
+        let mut feature_set = FeatureSet::all_enabled();
+        for (feature_id, account) in self.fetch_from_rpc(&feature_ids).await? {
+            match account.as_ref().and_then(activation_slot) {
+                Some(slot) => feature_set.activate(&feature_id, slot),
+                None => feature_set.deactivate(&feature_id),
+            }
+        }
+        for (feature_id, active) in &self.feature_overrides {
+            if *active {
+                feature_set.activate(feature_id, 0);
+            } else {
+                feature_set.deactivate(feature_id);
+            }
+        }
+
+        mollusk.program_cache.program_runtime_environment =
+            ProgramCache::new(&feature_set, &mollusk.compute_budget).program_runtime_environment;
+        mollusk.feature_set = feature_set;
+        Ok(self)
+    }
+}
@@ -0,0 +1,431 @@
+//! The RPC backend used by [`RpcAccountStore`](crate::RpcAccountStore).
+//!
+//! Everything the store needs from the network goes through the [`AccountFetcher`]
+//! trait. The crate implements it for the nonblocking [`RpcClient`], which is what
+//! the regular constructors use, and [`MockFetcher`](crate::testing::MockFetcher)
+//! provides an in-memory implementation for tests that must not touch the network.
+
+use {
+    async_trait::async_trait,
+    reqwest::StatusCode,
+    serde::Deserialize,
+    serde_json::json,
+    solana_account::Account,
+    solana_account_decoder_client_types::UiAccountEncoding,
+    solana_commitment_config::CommitmentConfig,
+    solana_hash::Hash,
+    solana_instruction::Instruction,
+    solana_message::Message,
+    solana_pubkey::Pubkey,
+    solana_rpc_client::nonblocking::rpc_client::RpcClient,
+    solana_rpc_client_api::{
+        client_error::{Error as ClientError, ErrorKind as ClientErrorKind, Result as ClientResult},
+        config::{
+            RpcAccountInfoConfig, RpcProgramAccountsConfig, RpcSimulateTransactionAccountsConfig,
+            RpcSimulateTransactionConfig,
+        },
+        custom_error::{
+            JSON_RPC_SERVER_ERROR_BLOCK_NOT_AVAILABLE,
+            JSON_RPC_SERVER_ERROR_BLOCK_STATUS_NOT_AVAILABLE_YET,
+            JSON_RPC_SERVER_ERROR_LONG_TERM_STORAGE_SLOT_SKIPPED,
+            JSON_RPC_SERVER_ERROR_SLOT_SKIPPED,
+        },
+        filter::{Memcmp, RpcFilterType},
+        request::{RpcError, RpcRequest},
+        response::{Response, RpcKeyedAccount},
+    },
+    solana_transaction::Transaction,
+    std::str::FromStr,
+};
+
+/// JSON-RPC error code for an unknown method.
+const METHOD_NOT_FOUND: i64 = -32601;
+
+/// JSON-RPC errors a node gives for a slot whose block it doesn't have.
+const BLOCK_UNAVAILABLE_CODES: [i64; 4] = [
+    JSON_RPC_SERVER_ERROR_BLOCK_NOT_AVAILABLE,
+    JSON_RPC_SERVER_ERROR_SLOT_SKIPPED,
+    JSON_RPC_SERVER_ERROR_LONG_TERM_STORAGE_SLOT_SKIPPED,
+    JSON_RPC_SERVER_ERROR_BLOCK_STATUS_NOT_AVAILABLE_YET,
+];
+
+/// One page of the accounts owned by a program.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ProgramAccountsPage {
+    pub accounts: Vec<(Pubkey, Account)>,
+    /// Where the next page starts, `None` on the last page.
+    pub cursor: Option<String>,
+}
+
+/// The outcome of simulating a transaction on the cluster.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TransactionSimulation {
+    /// The transaction error, `None` if it succeeded.
+    pub err: Option<String>,
+    pub logs: Vec<String>,
+    pub units_consumed: Option<u64>,
+    /// The requested accounts after the transaction, in request order.
+    pub accounts: Vec<Option<Account>>,
+}
+
+/// A source of on-chain accounts and cluster state.
+///
+/// Implementations should behave like the corresponding JSON-RPC methods: results
+/// are returned in request order, with `None` for accounts that don't exist.
+#[async_trait]
+pub trait AccountFetcher: Send + Sync {
+    /// Fetch several accounts at once (`getMultipleAccounts`).
+    ///
+    /// The store never passes more than `MAX_MULTIPLE_ACCOUNTS` pubkeys per call.
+    async fn get_multiple_accounts(&self, pubkeys: &[Pubkey])
+        -> ClientResult<Vec<Option<Account>>>;
+
+    /// Fetch several accounts at `commitment` rather than the backend's own
+    /// (`getMultipleAccounts` with a commitment), for
+    /// [`with_commitment_fallback`](crate::RpcAccountStore::with_commitment_fallback).
+    ///
+    /// The default implementation ignores `commitment`.
+    async fn get_multiple_accounts_at(
+        &self,
+        pubkeys: &[Pubkey],
+        _commitment: CommitmentConfig,
+    ) -> ClientResult<Vec<Option<Account>>> {
+        self.get_multiple_accounts(pubkeys).await
+    }
+
+    /// Fetch the current slot (`getSlot`).
+    async fn get_slot(&self) -> ClientResult<u64>;
+
+    /// Fetch the estimated production time of `slot` as a Unix timestamp
+    /// (`getBlockTime`).
+    async fn get_block_time(&self, slot: u64) -> ClientResult<i64>;
+
+    /// Fetch the hash of the cluster's genesis block (`getGenesisHash`).
+    async fn get_genesis_hash(&self) -> ClientResult<Hash>;
+
+    /// Fetch the finalized blocks between `start_slot` and `end_slot`, inclusive, in
+    /// ascending order (`getBlocks`). Skipped slots are left out.
+    async fn get_blocks(&self, start_slot: u64, end_slot: u64) -> ClientResult<Vec<u64>>;
+
+    /// Fetch the blockhash of the block at `slot` (`getBlock`, without transactions).
+    async fn get_block_hash(&self, slot: u64) -> ClientResult<Hash>;
+
+    /// Fetch every account owned by `program_id` in one response (`getProgramAccounts`).
+    async fn get_program_accounts(&self, program_id: &Pubkey)
+        -> ClientResult<Vec<(Pubkey, Account)>>;
+
+    /// Fetch up to `limit` accounts owned by `program_id`, starting at `cursor`
+    /// (`getProgramAccountsV2`, offered by some providers).
+    ///
+    /// Returns `Ok(None)` if the backend can't paginate, in which case the store
+    /// falls back to [`get_program_accounts`](Self::get_program_accounts). The default
+    /// implementation always does.
+    async fn get_program_accounts_page(
+        &self,
+        _program_id: &Pubkey,
+        _cursor: Option<&str>,
+        _limit: usize,
+    ) -> ClientResult<Option<ProgramAccountsPage>> {
+        Ok(None)
+    }
+
+    /// Fetch the accounts owned by `program_id` whose data holds `bytes` at `offset`
+    /// (`getProgramAccounts` with a `memcmp` filter).
+    ///
+    /// The default implementation fails, as if the backend couldn't filter.
+    async fn get_program_accounts_by_memcmp(
+        &self,
+        _program_id: &Pubkey,
+        _offset: usize,
+        _bytes: &[u8],
+    ) -> ClientResult<Vec<(Pubkey, Account)>> {
+        Err(ClientErrorKind::Custom("filtered getProgramAccounts is not supported".to_string())
+            .into())
+    }
+
+    /// Fetch the token accounts of `owner` under the token program `program_id`
+    /// (`getTokenAccountsByOwner`).
+    ///
+    /// The default implementation fails, as if the backend couldn't list them.
+    async fn get_token_accounts_by_owner(
+        &self,
+        _owner: &Pubkey,
+        _program_id: &Pubkey,
+    ) -> ClientResult<Vec<(Pubkey, Account)>> {
+        Err(ClientErrorKind::Custom("getTokenAccountsByOwner is not supported".to_string()).into())
+    }
+
+    /// Fetch every vote account, current and delinquent, with its activated stake in
+    /// lamports (`getVoteAccounts`).
+    ///
+    /// The default implementation fails, as if the backend couldn't list them.
+    async fn get_vote_accounts(&self) -> ClientResult<Vec<(Pubkey, u64)>> {
+        Err(ClientErrorKind::Custom("getVoteAccounts is not supported".to_string()).into())
+    }
+
+    /// Simulate an unsigned transaction made of `instructions` and paid by `payer`,
+    /// returning the post-state of `accounts` (`simulateTransaction`).
+    ///
+    /// Signatures aren't verified and the blockhash is replaced by a recent one. The
+    /// default implementation fails, as if the backend couldn't simulate.
+    async fn simulate_transaction(
+        &self,
+        _instructions: &[Instruction],
+        _payer: &Pubkey,
+        _accounts: &[Pubkey],
+    ) -> ClientResult<TransactionSimulation> {
+        Err(ClientErrorKind::Custom("simulateTransaction is not supported".to_string()).into())
+    }
+}
+
+/// The part of a `getBlock` result the store reads.
+#[derive(Deserialize)]
+struct RpcBlockHeader {
+    blockhash: String,
+}
+
+/// Result of `getProgramAccountsV2`.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RpcProgramAccountsPage {
+    accounts: Vec<RpcKeyedAccount>,
+    pagination_key: Option<String>,
+}
+
+fn decode_keyed_account(keyed: RpcKeyedAccount) -> ClientResult<(Pubkey, Account)> {
+    let pubkey = Pubkey::from_str(&keyed.pubkey)
+        .map_err(|_| RpcError::ParseError("Pubkey".to_string()))?;
+    let account = keyed
+        .account
+        .decode()
+        .ok_or_else(|| RpcError::ParseError("base64 account".to_string()))?;
+    Ok((pubkey, account))
+}
+
+/// Whether `error` means the endpoint doesn't offer the method asked for: the
+/// JSON-RPC "method not found" error, under its code or only its message, or the
+/// 404 and 400 responses of gateways that reject unknown methods over HTTP.
+fn is_method_unsupported(error: &ClientError) -> bool {
+    match error.kind() {
+        ClientErrorKind::RpcError(RpcError::RpcResponseError { code, message, .. }) => {
+            *code == METHOD_NOT_FOUND || message.to_ascii_lowercase().contains("method not found")
+        }
+        ClientErrorKind::Reqwest(e) => {
+            matches!(e.status(), Some(StatusCode::NOT_FOUND | StatusCode::BAD_REQUEST))
+        }
+        _ => false,
+    }
+}
+
+/// Whether a `getBlockTime` failure means the node has no time for the slot: the
+/// client's "Block Not Found" for a null result, or a skipped or unavailable block.
+/// Anything else, a transport failure say, is a real error.
+pub(crate) fn is_block_time_unavailable(error: &ClientError) -> bool {
+    match error.kind() {
+        ClientErrorKind::RpcError(RpcError::ForUser(message)) => {
+            message.starts_with("Block Not Found")
+        }
+        ClientErrorKind::RpcError(RpcError::RpcResponseError { code, .. }) => {
+            BLOCK_UNAVAILABLE_CODES.contains(code)
+        }
+        _ => false,
+    }
+}
+
+#[async_trait]
+impl AccountFetcher for RpcClient {
+    async fn get_multiple_accounts(
+        &self,
+        pubkeys: &[Pubkey],
+    ) -> ClientResult<Vec<Option<Account>>> {
+        RpcClient::get_multiple_accounts(self, pubkeys).await
+    }
+
+    async fn get_multiple_accounts_at(
+        &self,
+        pubkeys: &[Pubkey],
+        commitment: CommitmentConfig,
+    ) -> ClientResult<Vec<Option<Account>>> {
+        RpcClient::get_multiple_accounts_with_commitment(self, pubkeys, commitment)
+            .await
+            .map(|response| response.value)
+    }
+
+    async fn get_slot(&self) -> ClientResult<u64> {
+        RpcClient::get_slot(self).await
+    }
+
+    async fn get_block_time(&self, slot: u64) -> ClientResult<i64> {
+        RpcClient::get_block_time(self, slot).await
+    }
+
+    async fn get_genesis_hash(&self) -> ClientResult<Hash> {
+        RpcClient::get_genesis_hash(self).await
+    }
+
+    async fn get_blocks(&self, start_slot: u64, end_slot: u64) -> ClientResult<Vec<u64>> {
+        RpcClient::get_blocks_with_commitment(
+            self,
+            start_slot,
+            Some(end_slot),
+            CommitmentConfig::finalized(),
+        )
+        .await
+    }
+
+    async fn get_block_hash(&self, slot: u64) -> ClientResult<Hash> {
+        let params = json!([
+            slot,
+            {
+                "commitment": self.commitment().commitment,
+                "transactionDetails": "none",
+                "rewards": false,
+                "maxSupportedTransactionVersion": 0,
+            }
+        ]);
+        let block: RpcBlockHeader = self.send(RpcRequest::GetBlock, params).await?;
+        Hash::from_str(&block.blockhash)
+            .map_err(|_| RpcError::ParseError("Hash".to_string()).into())
+    }
+
+    async fn simulate_transaction(
+        &self,
+        instructions: &[Instruction],
+        payer: &Pubkey,
+        accounts: &[Pubkey],
+    ) -> ClientResult<TransactionSimulation> {
+        let transaction = Transaction::new_unsigned(Message::new(instructions, Some(payer)));
+        let config = RpcSimulateTransactionConfig {
+            sig_verify: false,
+            replace_recent_blockhash: true,
+            commitment: Some(self.commitment()),
+            accounts: Some(RpcSimulateTransactionAccountsConfig {
+                encoding: Some(UiAccountEncoding::Base64),
+                addresses: accounts.iter().map(Pubkey::to_string).collect(),
+            }),
+            ..RpcSimulateTransactionConfig::default()
+        };
+        let result = RpcClient::simulate_transaction_with_config(self, &transaction, config)
+            .await?
+            .value;
+
+        Ok(TransactionSimulation {
+            err: result.err.map(|err| err.to_string()),
+            logs: result.logs.unwrap_or_default(),
+            units_consumed: result.units_consumed,
+            accounts: result
+                .accounts
+                .unwrap_or_default()
+                .into_iter()
+                .map(|account| account.and_then(|account| account.decode()))
+                .collect(),
+        })
+    }
+
+    async fn get_vote_accounts(&self) -> ClientResult<Vec<(Pubkey, u64)>> {
+        let status = RpcClient::get_vote_accounts(self).await?;
+        status
+            .current
+            .into_iter()
+            .chain(status.delinquent)
+            .map(|info| {
+                let pubkey = Pubkey::from_str(&info.vote_pubkey)
+                    .map_err(|_| RpcError::ParseError("Pubkey".to_string()))?;
+                Ok((pubkey, info.activated_stake))
+            })
+            .collect()
+    }
+
+    async fn get_program_accounts(
+        &self,
+        program_id: &Pubkey,
+    ) -> ClientResult<Vec<(Pubkey, Account)>> {
+        let config = RpcProgramAccountsConfig {
+            account_config: RpcAccountInfoConfig {
+                encoding: Some(UiAccountEncoding::Base64),
+                ..RpcAccountInfoConfig::default()
+            },
+            ..RpcProgramAccountsConfig::default()
+        };
+        RpcClient::get_program_accounts_with_config(self, program_id, config).await
+    }
+
+    async fn get_program_accounts_by_memcmp(
+        &self,
+        program_id: &Pubkey,
+        offset: usize,
+        bytes: &[u8],
+    ) -> ClientResult<Vec<(Pubkey, Account)>> {
+        let config = RpcProgramAccountsConfig {
+            filters: Some(vec![RpcFilterType::Memcmp(Memcmp::new_raw_bytes(
+                offset,
+                bytes.to_vec(),
+            ))]),
+            account_config: RpcAccountInfoConfig {
+                encoding: Some(UiAccountEncoding::Base64),
+                ..RpcAccountInfoConfig::default()
+            },
+            ..RpcProgramAccountsConfig::default()
+        };
+        RpcClient::get_program_accounts_with_config(self, program_id, config).await
+    }
+
+    async fn get_token_accounts_by_owner(
+        &self,
+        owner: &Pubkey,
+        program_id: &Pubkey,
+    ) -> ClientResult<Vec<(Pubkey, Account)>> {
+        // `RpcClient::get_token_accounts_by_owner` asks for `jsonParsed`, which
+        // doesn't decode back into accounts
+        let params = json!([
+            owner.to_string(),
+            { "programId": program_id.to_string() },
+            {
+                "encoding": UiAccountEncoding::Base64,
+                "commitment": self.commitment().commitment,
+            }
+        ]);
+        let response: Response<Vec<RpcKeyedAccount>> =
+            self.send(RpcRequest::GetTokenAccountsByOwner, params).await?;
+        response.value.into_iter().map(decode_keyed_account).collect()
+    }
+
+    async fn get_program_accounts_page(
+        &self,
+        program_id: &Pubkey,
+        cursor: Option<&str>,
+        limit: usize,
+    ) -> ClientResult<Option<ProgramAccountsPage>> {
+        let params = json!([
+            program_id.to_string(),
+            {
+                "encoding": UiAccountEncoding::Base64,
+                "commitment": self.commitment().commitment,
+                "limit": limit,
+                "paginationKey": cursor,
+            }
+        ]);
+        let page: RpcProgramAccountsPage = match self
+            .send(
+                RpcRequest::Custom {
+                    method: "getProgramAccountsV2",
+                },
+                params,
+            )
+            .await
+        {
+            Ok(page) => page,
+            Err(e) if is_method_unsupported(&e) => return Ok(None),
+            Err(e) => return Err(e),
+        };
+
+        Ok(Some(ProgramAccountsPage {
+            accounts: page
+                .accounts
+                .into_iter()
+                .map(decode_keyed_account)
+                .collect::<ClientResult<_>>()?,
+            cursor: page.pagination_key,
+        }))
+    }
+}
@@ -0,0 +1,331 @@
+//! On-disk account fixtures.
+//!
+//! Accounts are stored one per file as `<pubkey>.json`, using the same JSON layout
+//! that `solana account --output json` produces and `solana-test-validator --account`
+//! accepts:
+//!
+//! ```json
+//! {
+//!   "pubkey": "...",
+//!   "account": {
+//!     "lamports": 1461600,
+//!     "data": ["<base64>", "base64"],
+//!     "owner": "...",
+//!     "executable": false,
+//!     "rentEpoch": 18446744073709551615,
+//!     "space": 82
+//!   }
+//! }
+//! ```
+//!
+//! A whole cache can also be saved to a single file, one account per line in the
+//! same layout without the indentation. It's written and read an account at a time,
+//! so that neither side holds more than one account's JSON in memory.
+
+use {
+    crate::{Provenance, RpcAccountStore, RpcError},
+    base64::{engine::general_purpose::STANDARD as BASE64, Engine},
+    serde::{Deserialize, Serialize},
+    solana_account::Account,
+    solana_pubkey::Pubkey,
+    std::{
+        ffi::OsString,
+        fs::{self, File},
+        io::{self, BufRead, BufReader, BufWriter, Write},
+        path::{Path, PathBuf},
+        str::FromStr,
+    },
+};
+
+#[derive(Serialize, Deserialize)]
+struct AccountFile {
+    pubkey: String,
+    account: UiAccount,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct UiAccount {
+    lamports: u64,
+    data: (String, String),
+    owner: String,
+    executable: bool,
+    rent_epoch: u64,
+    #[serde(default)]
+    space: Option<u64>,
+}
+
+/// Path of the fixture file for `pubkey` inside `dir`.
+pub(crate) fn account_path(dir: &Path, pubkey: &Pubkey) -> PathBuf {
+    dir.join(format!("{pubkey}.json"))
+}
+
+fn account_file(pubkey: &Pubkey, account: &Account) -> AccountFile {
+    AccountFile {
+        pubkey: pubkey.to_string(),
+        account: UiAccount {
+            lamports: account.lamports,
+            data: (BASE64.encode(&account.data), "base64".to_string()),
+            owner: account.owner.to_string(),
+            executable: account.executable,
+            rent_epoch: account.rent_epoch,
+            space: Some(account.data.len() as u64),
+        },
+    }
+}
+
+/// Serialize an account into the fixture JSON layout.
+pub(crate) fn encode_account(pubkey: &Pubkey, account: &Account) -> String {
+    serde_json::to_string_pretty(&account_file(pubkey, account))
+        .expect("account fixtures always serialize")
+}
+
+/// Parse an account from the fixture JSON layout.
+pub(crate) fn decode_account(json: &str) -> Result<(Pubkey, Account), String> {
+    let file: AccountFile = serde_json::from_str(json).map_err(|e| e.to_string())?;
+    let pubkey = Pubkey::from_str(&file.pubkey).map_err(|e| format!("invalid pubkey: {e}"))?;
+    let owner =
+        Pubkey::from_str(&file.account.owner).map_err(|e| format!("invalid owner: {e}"))?;
+    let (data, encoding) = file.account.data;
+    if encoding != "base64" {
+        return Err(format!("unsupported data encoding: {encoding}"));
+    }
+    let data = BASE64
+        .decode(data)
+        .map_err(|e| format!("invalid base64 data: {e}"))?;
+
+    Ok((
+        pubkey,
+        Account {
+            lamports: file.account.lamports,
+            data,
+            owner,
+            executable: file.account.executable,
+            rent_epoch: file.account.rent_epoch,
+        },
+    ))
+}
+
+/// Load the fixture for `pubkey` from `dir`, if one exists.
+pub(crate) fn read_account(dir: &Path, pubkey: &Pubkey) -> Result<Option<Account>, String> {
+    let path = account_path(dir, pubkey);
+    let json = match fs::read_to_string(&path) {
+        Ok(json) => json,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e.to_string()),
+    };
+    let (stored_pubkey, account) = decode_account(&json)?;
+    if stored_pubkey != *pubkey {
+        return Err(format!(
+            "fixture contains account {stored_pubkey}, expected {pubkey}"
+        ));
+    }
+    Ok(Some(account))
+}
+
+/// Write the fixture for `pubkey` into `dir`, creating the directory if needed.
+pub(crate) fn write_account(dir: &Path, pubkey: &Pubkey, account: &Account) -> io::Result<()> {
+    fs::create_dir_all(dir)?;
+    fs::write(account_path(dir, pubkey), encode_account(pubkey, account))
+}
+
+/// Where a file is written before being renamed to `path`: next to it, so the
+/// rename stays on one filesystem.
+fn temp_path(path: &Path) -> PathBuf {
+    let mut temp = OsString::from(path.as_os_str());
+    temp.push(".tmp");
+    PathBuf::from(temp)
+}
+
+/// Write `accounts` to `path`, one per line.
+fn write_lines<'a>(
+    path: &Path,
+    accounts: impl Iterator<Item = (&'a Pubkey, &'a Account)>,
+) -> io::Result<()> {
+    if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        fs::create_dir_all(dir)?;
+    }
+    let mut writer = BufWriter::new(File::create(path)?);
+    for (pubkey, account) in accounts {
+        serde_json::to_writer(&mut writer, &account_file(pubkey, account))?;
+        writer.write_all(b"\n")?;
+    }
+    writer
+        .into_inner()
+        .map_err(io::IntoInnerError::into_error)?
+        .sync_all()
+}
+
+/// Write `accounts` to `<path>.tmp`, one per line, and rename it over `path` once
+/// complete.
+pub(crate) fn replace_lines<'a>(
+    path: &Path,
+    accounts: impl Iterator<Item = (&'a Pubkey, &'a Account)>,
+) -> Result<(), RpcError> {
+    let temp = temp_path(path);
+    write_lines(&temp, accounts)
+        .and_then(|()| fs::rename(&temp, path))
+        .map_err(|e| {
+            let _ = fs::remove_file(&temp);
+            RpcError::Fixture {
+                path: path.to_path_buf(),
+                reason: e.to_string(),
+            }
+        })
+}
+
+/// Pass every account of a fixture file written by
+/// [`save_fixture`](RpcAccountStore::save_fixture) to `account`, reading it a line
+/// at a time.
+pub(crate) fn read_lines(
+    path: &Path,
+    mut account: impl FnMut(Pubkey, Account),
+) -> Result<(), RpcError> {
+    let error = |reason| RpcError::Fixture {
+        path: path.to_path_buf(),
+        reason,
+    };
+    let mut reader = BufReader::new(File::open(path).map_err(|e| error(e.to_string()))?);
+    // One buffer for every line, rather than an allocation the size of each
+    let mut line = String::new();
+    for number in 1.. {
+        line.clear();
+        if reader.read_line(&mut line).map_err(|e| error(e.to_string()))? == 0 {
+            break;
+        }
+        if line.trim().is_empty() {
+            continue;
+        }
+        let (pubkey, decoded) = decode_account(&line)
+            .map_err(|reason| error(format!("line {number}: {reason}")))?;
+        account(pubkey, decoded);
+    }
+    Ok(())
+}
+
+/// Every `<pubkey>.json` fixture in `dir`, sorted by pubkey.
+pub(crate) fn read_dir(dir: &Path) -> Result<Vec<(Pubkey, Account)>, RpcError> {
+    let error = |path: &Path, reason| RpcError::Fixture {
+        path: path.to_path_buf(),
+        reason,
+    };
+    let mut accounts = Vec::new();
+    for entry in fs::read_dir(dir).map_err(|e| error(dir, e.to_string()))? {
+        let path = entry.map_err(|e| error(dir, e.to_string()))?.path();
+        let Some(pubkey) = path
+            .extension()
+            .filter(|extension| *extension == "json")
+            .and(path.file_stem())
+            .and_then(|stem| stem.to_str())
+            .and_then(|stem| Pubkey::from_str(stem).ok())
+        else {
+            continue;
+        };
+        let account = read_account(dir, &pubkey).map_err(|reason| error(&path, reason))?;
+        accounts.extend(account.map(|account| (pubkey, account)));
+    }
+    accounts.sort_unstable_by_key(|(pubkey, _)| *pubkey);
+    Ok(accounts)
+}
+
+impl RpcAccountStore {
+    /// Cache the account of a single JSON file in the layout of
+    /// `solana account --output json`, such as those
+    /// [`export_validator_args`](Self::export_validator_args) writes. The account keeps
+    /// the pubkey recorded in the file, whatever its name.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`RpcError::Fixture`] if the file can't be read or parsed.
+    pub fn with_account_file(mut self, path: impl AsRef<Path>) -> Result<Self, RpcError> {
+        let path = path.as_ref();
+        let (pubkey, account) = fs::read_to_string(path)
+            .map_err(|e| e.to_string())
+            .and_then(|json| decode_account(&json))
+            .map_err(|reason| RpcError::Fixture {
+                path: path.to_path_buf(),
+                reason,
+            })?;
+        let path = path.to_path_buf();
+        self.insert_account(pubkey, account, Provenance::Fixture { path });
+        self.stats_mut().accounts_from_fixtures += 1;
+        Ok(self)
+    }
+
+    /// Write cached accounts into a fixture directory, as
+    /// [`with_fixture_dir`](Self::with_fixture_dir) records them, replacing existing
+    /// fixtures of the same accounts.
+    ///
+    /// Useful for capturing accounts that weren't fetched through a fixture
+    /// directory, or for refreshing fixtures from a store that re-fetched them.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`RpcError::AccountNotFound`] if an account isn't cached, and
+    /// [`RpcError::Fixture`] if a file can't be written.
+    pub fn write_fixtures(
+        &self,
+        dir: impl AsRef<Path>,
+        pubkeys: &[Pubkey],
+    ) -> Result<(), RpcError> {
+        let dir = dir.as_ref();
+        for pubkey in pubkeys {
+            let account = self
+                .cache
+                .get(pubkey)
+                .ok_or(RpcError::AccountNotFound(*pubkey))?;
+            write_account(dir, pubkey, account).map_err(|e| RpcError::Fixture {
+                path: account_path(dir, pubkey),
+                reason: e.to_string(),
+            })?;
+        }
+        Ok(())
+    }
+
+    /// Save every cached account to the single fixture file `path`, to be loaded back
+    /// with [`load_fixture`](Self::load_fixture), replacing the file if it exists.
+    ///
+    /// Accounts are written in pubkey order, one per line in the JSON layout of
+    /// [`with_fixture_dir`](Self::with_fixture_dir) files, so saving the same cache
+    /// twice gives the same file. Each account is serialized straight to the file,
+    /// which keeps large caches from being held in memory twice.
+    ///
+    /// The file is written as `<path>.tmp` and renamed into place once complete: an
+    /// interrupted save leaves the previous file, if any, untouched.
+    ///
+    /// ```rust,ignore
+    /// store.save_fixture("tests/fixtures/swap.jsonl")?;
+    /// let store = RpcAccountStore::mainnet()
+    ///     .offline()
+    ///     .load_fixture("tests/fixtures/swap.jsonl")?;
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns [`RpcError::Fixture`] if the file can't be written.
+    pub fn save_fixture(&self, path: impl AsRef<Path>) -> Result<(), RpcError> {
+        let path = path.as_ref();
+        let mut pubkeys: Vec<&Pubkey> = self.cache.keys().collect();
+        pubkeys.sort_unstable();
+
+        replace_lines(path, pubkeys.into_iter().map(|pubkey| (pubkey, &self.cache[pubkey])))
+    }
+
+    /// Cache the accounts of a fixture file written by
+    /// [`save_fixture`](Self::save_fixture), reading it a line at a time. Blank lines
+    /// are skipped.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`RpcError::Fixture`] if the file can't be read or a line can't be
+    /// parsed, with the line number.
+    pub fn load_fixture(mut self, path: impl AsRef<Path>) -> Result<Self, RpcError> {
+        let path = path.as_ref();
+        read_lines(path, |pubkey, account| {
+            let path = path.to_path_buf();
+            self.insert_account(pubkey, account, Provenance::Fixture { path });
+            self.stats_mut().accounts_from_fixtures += 1;
+        })?;
+        Ok(self)
+    }
+}
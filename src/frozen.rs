@@ -0,0 +1,423 @@
+//! A read-only view of a store, for baselines that must not change.
+
+use {
+    crate::{
+        ComputeBudgetRequest, ConservationViolation, InstructionLint, LamportDelta,
+        MemoryReport, Provenance, RecordedError, RpcAccountStore, RpcError, RpcStats,
+        StateDiff, StoreSummary, TokenMetadata, Warning,
+    },
+    mollusk_svm::{
+        result::{Check, InstructionResult},
+        Mollusk,
+    },
+    serde_json::Value,
+    solana_account::{Account, AccountSharedData},
+    solana_commitment_config::CommitmentConfig,
+    solana_instruction::Instruction,
+    solana_pubkey::Pubkey,
+    solana_rent::Rent,
+    solana_vote_interface::state::VoteStateV3,
+    spl_token_interface::state::{Account as TokenAccount, Mint},
+    std::{
+        collections::{HashMap, HashSet},
+        fmt,
+        path::{Path, PathBuf},
+        sync::{
+            atomic::{AtomicBool, Ordering},
+            Arc, Mutex,
+        },
+    },
+};
+
+/// An [`RpcAccountStore`] that can no longer change, see [`RpcAccountStore::freeze`].
+///
+/// Offers only the store's read APIs:
+/// [`instruction_accounts`](Self::instruction_accounts), the [`cache`](Self::cache),
+/// [`diff_result`](Self::diff_result), checks, exports and summaries. Everything
+/// that would change the store or talk to the RPC (fetches,
+/// [`with_accounts`](RpcAccountStore::with_accounts),
+/// [`apply_result`](RpcAccountStore::apply_result),
+/// [`reset_stats`](RpcAccountStore::reset_stats),
+/// [`verify_fresh`](RpcAccountStore::verify_fresh),
+/// [`prune_fixture_to_used`](RpcAccountStore::prune_fixture_to_used), ...) doesn't
+/// compile on it. Cloning is cheap and shares the frozen accounts.
+///
+/// ```rust,ignore
+/// let baseline = RpcAccountStore::new(rpc_url)
+///     .with_fixture_dir("tests/fixtures/swap")
+///     .from_instructions(&instructions)
+///     .await?
+///     .freeze();
+///
+/// let accounts = baseline.instruction_accounts(&mollusk, &swap)?;
+/// // Scenario-specific changes go into an unfrozen copy
+/// let mut scenario = baseline.snapshot();
+/// scenario.apply_result(&result);
+/// ```
+#[derive(Clone)]
+pub struct FrozenAccountStore {
+    store: Arc<RpcAccountStore>,
+}
+
+impl fmt::Debug for FrozenAccountStore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("FrozenAccountStore").field(&*self.store).finish()
+    }
+}
+
+impl<'a> IntoIterator for &'a FrozenAccountStore {
+    type Item = (&'a Pubkey, &'a Account);
+    type IntoIter = std::vec::IntoIter<(&'a Pubkey, &'a Account)>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.store.iter()
+    }
+}
+
+/// Forwards the listed `&self` methods to the frozen store.
+macro_rules! forward_reads {
+    ($($(#[$attr:meta])* fn $name:ident(&self $(, $arg:ident: $ty:ty)*) $(-> $ret:ty)?;)*) => {
+        impl FrozenAccountStore {
+            $(
+                $(#[$attr])*
+                #[doc = concat!("See [`RpcAccountStore::", stringify!($name), "`].")]
+                pub fn $name(&self $(, $arg: $ty)*) $(-> $ret)? {
+                    self.store.$name($($arg),*)
+                }
+            )*
+        }
+    };
+}
+
+// Only methods that neither change the store (its stats and fixtures included)
+// nor make RPC calls belong here
+forward_reads! {
+    fn snapshot(&self) -> RpcAccountStore;
+    fn rpc_url(&self) -> Option<&str>;
+    fn commitment(&self) -> Option<CommitmentConfig>;
+    fn errors(&self) -> &[RecordedError];
+    fn warnings(&self) -> &[Warning];
+    fn assert_no_warnings(&self) -> Result<(), RpcError>;
+    fn stats(&self) -> RpcStats;
+    fn provenance(&self, pubkey: &Pubkey) -> Option<&Provenance>;
+    fn label_of(&self, pubkey: &Pubkey) -> Option<&str>;
+    fn display_pubkey(&self, pubkey: &Pubkey) -> String;
+    fn synced_slot(&self) -> Option<u64>;
+    fn rent(&self) -> Rent;
+    fn cached_program_elf(&self, program: &Pubkey) -> Result<&[u8], RpcError>;
+    fn program_elf(&self, program: &Pubkey) -> Option<Arc<[u8]>>;
+    fn iter(&self) -> std::vec::IntoIter<(&Pubkey, &Account)>;
+    fn to_shared(&self) -> Vec<(Pubkey, AccountSharedData)>;
+    fn instruction_accounts(
+        &self,
+        mollusk: &Mollusk,
+        instruction: &Instruction
+    ) -> Result<Vec<(Pubkey, Account)>, RpcError>;
+    fn instructions_accounts(
+        &self,
+        mollusk: &Mollusk,
+        instructions: &[Instruction]
+    ) -> Result<Vec<(Pubkey, Account)>, RpcError>;
+    fn instruction_accounts_shared(
+        &self,
+        mollusk: &Mollusk,
+        instruction: &Instruction
+    ) -> Result<Vec<(Pubkey, AccountSharedData)>, RpcError>;
+    fn used_accounts(&self) -> HashSet<Pubkey>;
+    fn unused_accounts(&self) -> Vec<Pubkey>;
+    fn compute_budget(&self) -> ComputeBudgetRequest;
+    fn apply_compute_budget(&self, mollusk: &mut Mollusk);
+    fn diff_result(&self, result: &InstructionResult) -> StateDiff;
+    fn check_lamport_conservation(
+        &self,
+        result: &InstructionResult
+    ) -> Result<LamportDelta, ConservationViolation>;
+    fn expect_unchanged(&self, pubkeys: &[Pubkey]) -> Result<Vec<Check<'_>>, RpcError>;
+    fn expect_lamport_delta(&self, pubkey: &Pubkey, delta: i64) -> Result<Vec<Check<'_>>, RpcError>;
+    fn lint_instruction(&self, instruction: &Instruction) -> Vec<InstructionLint>;
+    fn assert_no_lints(&self, instruction: &Instruction) -> Result<(), RpcError>;
+    fn assert_owner(&self, pubkey: &Pubkey, owner: &Pubkey) -> Result<(), RpcError>;
+    fn assert_owners(&self, expected: &[(Pubkey, Pubkey)]) -> Result<(), RpcError>;
+    fn accounts_by_owner(&self, owner: &Pubkey) -> Vec<(Pubkey, &Account)>;
+    fn verify_owners_loaded(&self, mollusk: &Mollusk) -> Result<(), RpcError>;
+    fn require_accounts(&self, pubkeys: &[Pubkey]) -> Result<(), RpcError>;
+    fn require_programs(&self, programs: &[Pubkey]) -> Result<(), RpcError>;
+    fn get_token_account(&self, pubkey: &Pubkey) -> Result<TokenAccount, RpcError>;
+    fn get_mint(&self, pubkey: &Pubkey) -> Result<Mint, RpcError>;
+    fn token_balance(&self, pubkey: &Pubkey) -> Result<u64, RpcError>;
+    fn token_deltas(&self, result: &InstructionResult) -> HashMap<Pubkey, i128>;
+    fn assert_token_delta(&self, result: &InstructionResult, pubkey: &Pubkey, expected: i128);
+    fn get_token_metadata(&self, mint: &Pubkey) -> Result<TokenMetadata, RpcError>;
+    fn get_vote_state(&self, pubkey: &Pubkey) -> Result<VoteStateV3, RpcError>;
+    fn memory_report(&self) -> MemoryReport;
+    fn summary(&self) -> StoreSummary<'_>;
+    fn snapshot_json(&self, pubkeys: &[Pubkey]) -> Value;
+    fn snapshot_result_json(&self, result: &InstructionResult, pubkeys: &[Pubkey]) -> Value;
+    fn export_accounts(&self, sink: impl FnMut(Pubkey, Account));
+    fn export_validator_args(&self, dir: impl AsRef<Path>) -> Result<String, RpcError>;
+    fn export_bench(
+        &self,
+        mollusk: &Mollusk,
+        instruction: &Instruction,
+        name: &str,
+        dir: impl AsRef<Path>
+    ) -> Result<PathBuf, RpcError>;
+    fn generate_rust_module(
+        &self,
+        path: impl AsRef<Path>,
+        module_name: &str
+    ) -> Result<(), RpcError>;
+    fn save_fixture(&self, path: impl AsRef<Path>) -> Result<(), RpcError>;
+    fn write_fixtures(&self, dir: impl AsRef<Path>, pubkeys: &[Pubkey]) -> Result<(), RpcError>;
+    #[cfg(feature = "decoder")]
+    fn parse_account(
+        &self,
+        pubkey: &Pubkey
+    ) -> Result<solana_account_decoder::parse_account_data::ParsedAccount, RpcError>;
+    #[cfg(feature = "decoder")]
+    fn dump_parsed(&self, pubkeys: &[Pubkey]) -> Result<String, RpcError>;
+    #[cfg(feature = "token-2022")]
+    fn get_token_2022_account(
+        &self,
+        pubkey: &Pubkey
+    ) -> Result<
+        spl_token_2022_interface::extension::StateWithExtensionsOwned<
+            spl_token_2022_interface::state::Account,
+        >,
+        RpcError,
+    >;
+    #[cfg(feature = "token-2022")]
+    fn get_mint_2022(
+        &self,
+        pubkey: &Pubkey
+    ) -> Result<
+        spl_token_2022_interface::extension::StateWithExtensionsOwned<
+            spl_token_2022_interface::state::Mint,
+        >,
+        RpcError,
+    >;
+    #[cfg(feature = "litesvm")]
+    fn into_litesvm(&self, svm: &mut litesvm::LiteSVM) -> Result<(), RpcError>;
+    #[cfg(feature = "program-test")]
+    fn apply_to_program_test(
+        &self,
+        program_test: &mut solana_program_test::ProgramTest
+    ) -> Result<(), RpcError>;
+}
+
+/// Nothing that changes a frozen store or makes RPC calls compiles on it.
+///
+/// ```compile_fail,E0599
+/// # use {mollusk_on_demand::{testing::MockFetcher, RpcAccountStore}, mollusk_svm::Mollusk};
+/// # use {solana_instruction::Instruction, solana_pubkey::Pubkey};
+/// # let instruction = Instruction::new_with_bytes(Pubkey::default(), &[], vec![]);
+/// let mut frozen = RpcAccountStore::new_with_fetcher(MockFetcher::new()).freeze();
+/// frozen.reset_stats();
+/// ```
+///
+/// ```compile_fail,E0599
+/// # use {mollusk_on_demand::{testing::MockFetcher, RpcAccountStore}, mollusk_svm::Mollusk};
+/// # use {solana_instruction::Instruction, solana_pubkey::Pubkey};
+/// # let instruction = Instruction::new_with_bytes(Pubkey::default(), &[], vec![]);
+/// let mut frozen = RpcAccountStore::new_with_fetcher(MockFetcher::new()).freeze();
+/// frozen.prune_fixture_to_used("tests/fixtures/swap.jsonl");
+/// ```
+///
+/// ```compile_fail,E0599
+/// # use {mollusk_on_demand::{testing::MockFetcher, RpcAccountStore}, mollusk_svm::Mollusk};
+/// # use {solana_instruction::Instruction, solana_pubkey::Pubkey};
+/// # let instruction = Instruction::new_with_bytes(Pubkey::default(), &[], vec![]);
+/// let mut frozen = RpcAccountStore::new_with_fetcher(MockFetcher::new()).freeze();
+/// frozen.prune_fixture_dir_to_used("tests/fixtures/swap");
+/// ```
+///
+/// ```compile_fail,E0599
+/// # use {mollusk_on_demand::{testing::MockFetcher, RpcAccountStore}, mollusk_svm::Mollusk};
+/// # use {solana_instruction::Instruction, solana_pubkey::Pubkey};
+/// # let instruction = Instruction::new_with_bytes(Pubkey::default(), &[], vec![]);
+/// let mut frozen = RpcAccountStore::new_with_fetcher(MockFetcher::new()).freeze();
+/// let _ = frozen.verify_fresh(&[]);
+/// ```
+///
+/// ```compile_fail,E0599
+/// # use {mollusk_on_demand::{testing::MockFetcher, RpcAccountStore}, mollusk_svm::Mollusk};
+/// # use {solana_instruction::Instruction, solana_pubkey::Pubkey};
+/// # let instruction = Instruction::new_with_bytes(Pubkey::default(), &[], vec![]);
+/// let mut frozen = RpcAccountStore::new_with_fetcher(MockFetcher::new()).freeze();
+/// let _ = frozen.verify_all_fresh();
+/// ```
+///
+/// ```compile_fail,E0599
+/// # use {mollusk_on_demand::{testing::MockFetcher, RpcAccountStore}, mollusk_svm::Mollusk};
+/// # use {solana_instruction::Instruction, solana_pubkey::Pubkey};
+/// # let instruction = Instruction::new_with_bytes(Pubkey::default(), &[], vec![]);
+/// let mut frozen = RpcAccountStore::new_with_fetcher(MockFetcher::new()).freeze();
+/// let _ = frozen.drift_check("tests/fixtures/swap");
+/// ```
+///
+/// ```compile_fail,E0599
+/// # use {mollusk_on_demand::{testing::MockFetcher, RpcAccountStore}, mollusk_svm::Mollusk};
+/// # use {solana_instruction::Instruction, solana_pubkey::Pubkey};
+/// # let instruction = Instruction::new_with_bytes(Pubkey::default(), &[], vec![]);
+/// let mut frozen = RpcAccountStore::new_with_fetcher(MockFetcher::new()).freeze();
+/// let _ = frozen.compare_with_simulation(&Mollusk::default(), &[], &Pubkey::default());
+/// ```
+///
+/// ```compile_fail,E0599
+/// # use {mollusk_on_demand::{testing::MockFetcher, RpcAccountStore}, mollusk_svm::Mollusk};
+/// # use {solana_instruction::Instruction, solana_pubkey::Pubkey};
+/// # let instruction = Instruction::new_with_bytes(Pubkey::default(), &[], vec![]);
+/// let mut frozen = RpcAccountStore::new_with_fetcher(MockFetcher::new()).freeze();
+/// let _ = frozen.program_accounts_pages(Pubkey::default());
+/// ```
+///
+/// ```compile_fail,E0599
+/// # use {mollusk_on_demand::{testing::MockFetcher, RpcAccountStore}, mollusk_svm::Mollusk};
+/// # use {solana_instruction::Instruction, solana_pubkey::Pubkey};
+/// # let instruction = Instruction::new_with_bytes(Pubkey::default(), &[], vec![]);
+/// let mut frozen = RpcAccountStore::new_with_fetcher(MockFetcher::new()).freeze();
+/// let _ = frozen.get_or_fetch(&Pubkey::default());
+/// ```
+///
+/// ```compile_fail,E0599
+/// # use {mollusk_on_demand::{testing::MockFetcher, RpcAccountStore}, mollusk_svm::Mollusk};
+/// # use {solana_instruction::Instruction, solana_pubkey::Pubkey};
+/// # let instruction = Instruction::new_with_bytes(Pubkey::default(), &[], vec![]);
+/// let mut frozen = RpcAccountStore::new_with_fetcher(MockFetcher::new()).freeze();
+/// frozen.take_warnings();
+/// ```
+///
+/// ```compile_fail,E0599
+/// # use {mollusk_on_demand::{testing::MockFetcher, RpcAccountStore}, mollusk_svm::Mollusk};
+/// # use {solana_instruction::Instruction, solana_pubkey::Pubkey};
+/// # let instruction = Instruction::new_with_bytes(Pubkey::default(), &[], vec![]);
+/// let mut frozen = RpcAccountStore::new_with_fetcher(MockFetcher::new()).freeze();
+/// let _ = frozen.with_accounts([]);
+/// ```
+///
+/// ```compile_fail,E0599
+/// # use {mollusk_on_demand::{testing::MockFetcher, RpcAccountStore}, mollusk_svm::Mollusk};
+/// # use {solana_instruction::Instruction, solana_pubkey::Pubkey};
+/// # let instruction = Instruction::new_with_bytes(Pubkey::default(), &[], vec![]);
+/// let mut frozen = RpcAccountStore::new_with_fetcher(MockFetcher::new()).freeze();
+/// frozen.apply_result(&Mollusk::default().process_instruction(&instruction, &[]));
+/// ```
+///
+/// ```compile_fail,E0596
+/// # use {mollusk_on_demand::{testing::MockFetcher, RpcAccountStore}, mollusk_svm::Mollusk};
+/// # use {solana_instruction::Instruction, solana_pubkey::Pubkey};
+/// # let instruction = Instruction::new_with_bytes(Pubkey::default(), &[], vec![]);
+/// let mut frozen = RpcAccountStore::new_with_fetcher(MockFetcher::new()).freeze();
+/// frozen.cache().insert(Pubkey::default(), Default::default());
+/// ```
+#[cfg(doctest)]
+struct FrozenStoreIsReadOnly;
+
+impl FrozenAccountStore {
+    /// The frozen accounts.
+    pub fn cache(&self) -> &HashMap<Pubkey, Account> {
+        &self.store.cache
+    }
+
+    /// Unfreeze the store, copying it if clones of this one are still around.
+    pub fn thaw(self) -> RpcAccountStore {
+        Arc::try_unwrap(self.store).unwrap_or_else(|store| store.snapshot())
+    }
+}
+
+impl RpcAccountStore {
+    /// Make the store read-only, see [`FrozenAccountStore`].
+    pub fn freeze(self) -> FrozenAccountStore {
+        FrozenAccountStore {
+            store: Arc::new(self),
+        }
+    }
+
+    /// A copy of the store with the same accounts and configuration.
+    ///
+    /// The copy shares the fetcher, rate limiter and callbacks, but counts its own
+    /// [`stats`](Self::stats) from the current values on. Recorded
+    /// [`errors`](Self::errors), live subscriptions and pending
+    /// [warm-ups](Self::warm_cache) aren't copied.
+    pub fn snapshot(&self) -> Self {
+        self.copy(self.cache.clone(), Arc::new(Mutex::new(self.stats())))
+    }
+
+    /// A copy of the store's configuration holding `cache`, counting requests in
+    /// `stats`.
+    pub(crate) fn copy(
+        &self,
+        cache: HashMap<Pubkey, Account>,
+        stats: Arc<Mutex<RpcStats>>,
+    ) -> Self {
+        let copy = Self {
+            fetcher: self.fetcher.clone(),
+            endpoint: self.endpoint.clone(),
+            routed_endpoints: self.routed_endpoints.clone(),
+            routes: self.routes.clone(),
+            slot_endpoint: self.slot_endpoint,
+            commitment_fallback: self.commitment_fallback.clone(),
+            found_at_fallback: self.found_at_fallback.clone(),
+            cache,
+            provenance: self.provenance.clone(),
+            writable_accounts: self.writable_accounts.clone(),
+            compute_budget: self.compute_budget,
+            used_accounts: Mutex::new(self.used_accounts()),
+            // The copy's accounts have new buffers, so the ELFs would be copied again
+            elf_cache: Mutex::default(),
+            memory_watch: self.memory_watch,
+            allow_missing_accounts: self.allow_missing_accounts,
+            max_account_bytes: self.max_account_bytes,
+            check_program_data_size: self.check_program_data_size,
+            redact_urls: self.redact_urls,
+            elf_validation: self.elf_validation,
+            elf_validation_overrides: self.elf_validation_overrides.clone(),
+            lenient: self.lenient,
+            errors: Vec::new(),
+            warnings: self.warnings.clone(),
+            on_warning: self.on_warning.clone(),
+            strict_mocks: self.strict_mocks,
+            deny_shadowed_mocks: self.deny_shadowed_mocks,
+            checked_mocks: self.checked_mocks.clone(),
+            auto_top_up_rent: self.auto_top_up_rent,
+            prefetch_program_data: self.prefetch_program_data,
+            warmups: Default::default(),
+            labels: self.labels.clone(),
+            force_reload: self.force_reload.clone(),
+            local_programs: self.local_programs.clone(),
+            snapshot_options: self.snapshot_options,
+            loaded_programs: self.loaded_programs.clone(),
+            results_applied: self.results_applied,
+            progress: self.progress.clone(),
+            concurrency: self.concurrency,
+            rate_limiter: self.rate_limiter.clone(),
+            stats,
+            offline: self.offline,
+            rpc_budget: self.rpc_budget,
+            program_accounts_limit: self.program_accounts_limit,
+            pagination_unsupported: AtomicBool::new(
+                self.pagination_unsupported.load(Ordering::Relaxed),
+            ),
+            referenced_accounts_limit: self.referenced_accounts_limit,
+            reference_exclusions: self.reference_exclusions.clone(),
+            expected_cluster: self.expected_cluster,
+            cluster: self.cluster,
+            cluster_verified: self.cluster_verified.clone(),
+            synced_slot: self.synced_slot,
+            rent: self.rent.clone(),
+            feature_overrides: self.feature_overrides.clone(),
+            real_sysvars: self.real_sysvars,
+            fixture_dir: self.fixture_dir.clone(),
+            #[cfg(feature = "pubsub")]
+            subscriptions: {
+                let mut subscriptions = crate::pubsub::Subscriptions::default();
+                subscriptions.ws_url = self.subscriptions.ws_url.clone();
+                subscriptions
+            },
+            #[cfg(feature = "tracing")]
+            log_fetches: self.log_fetches,
+        };
+        // Record the copy's rate-limit waits in its stats
+        copy.rebuild_client()
+    }
+}
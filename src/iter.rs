@@ -0,0 +1,148 @@
+//! Using the store like a collection of accounts.
+
+use {
+    crate::{RpcAccountStore, RpcError},
+    mollusk_svm::Mollusk,
+    solana_account::{Account, AccountSharedData},
+    solana_instruction::Instruction,
+    solana_pubkey::Pubkey,
+};
+
+/// An account accepted by [`RpcAccountStore::with_accounts`]: an owned
+/// `(Pubkey, A)` pair for any `A: Into<Account>`, or a borrowed
+/// `&(Pubkey, Account)` or `&(Pubkey, AccountSharedData)`, which is cloned.
+pub trait IntoAccountEntry {
+    fn into_account_entry(self) -> (Pubkey, Account);
+}
+
+impl<A: Into<Account>> IntoAccountEntry for (Pubkey, A) {
+    fn into_account_entry(self) -> (Pubkey, Account) {
+        (self.0, self.1.into())
+    }
+}
+
+impl IntoAccountEntry for &(Pubkey, Account) {
+    fn into_account_entry(self) -> (Pubkey, Account) {
+        (self.0, self.1.clone())
+    }
+}
+
+impl IntoAccountEntry for &(Pubkey, AccountSharedData) {
+    fn into_account_entry(self) -> (Pubkey, Account) {
+        (self.0, self.1.clone().into())
+    }
+}
+
+impl RpcAccountStore {
+    /// Create a store for `rpc_url` holding `accounts` as mocks, like
+    /// [`with_accounts`](Self::with_accounts).
+    ///
+    /// Nothing is fetched until an account that isn't among them is needed:
+    ///
+    /// ```
+    /// # use {mollusk_on_demand::{Provenance, RpcAccountStore}, solana_account::Account};
+    /// # use solana_pubkey::Pubkey;
+    /// let pubkey = Pubkey::new_unique();
+    /// let accounts = [(pubkey, Account::new(1_000, 0, &Pubkey::default()))];
+    /// let store = RpcAccountStore::from_accounts("http://localhost:8899", accounts);
+    ///
+    /// assert_eq!(store.cache[&pubkey].lamports, 1_000);
+    /// assert_eq!(store.provenance(&pubkey), Some(&Provenance::Mocked));
+    /// ```
+    pub fn from_accounts(
+        rpc_url: impl Into<String>,
+        accounts: impl IntoIterator<Item = (Pubkey, Account)>,
+    ) -> Self {
+        let mut store = Self::new(rpc_url);
+        store.extend(accounts);
+        store
+    }
+
+    /// The cached accounts, sorted by pubkey.
+    ///
+    /// ```
+    /// # use mollusk_on_demand::{testing::MockFetcher, RpcAccountStore};
+    /// # use {solana_account::Account, solana_pubkey::Pubkey};
+    /// let mut pubkeys: Vec<Pubkey> = (0..8).map(|_| Pubkey::new_unique()).collect();
+    /// let store = RpcAccountStore::new_with_fetcher(MockFetcher::new()).with_accounts(
+    ///     pubkeys.iter().map(|pubkey| (*pubkey, Account::new(1, 0, &Pubkey::default()))),
+    /// );
+    ///
+    /// pubkeys.sort();
+    /// let iterated: Vec<Pubkey> = (&store).into_iter().map(|(pubkey, _)| *pubkey).collect();
+    /// assert_eq!(iterated, pubkeys);
+    /// assert!(store.iter().eq(&store));
+    /// ```
+    pub fn iter(&self) -> std::vec::IntoIter<(&Pubkey, &Account)> {
+        let mut accounts: Vec<(&Pubkey, &Account)> = self.cache.iter().collect();
+        accounts.sort_by_key(|(pubkey, _)| **pubkey);
+        accounts.into_iter()
+    }
+
+    /// Add `AccountSharedData` accounts as mocks, like
+    /// [`with_accounts`](Self::with_accounts), which also takes them owned.
+    pub fn with_shared_accounts(self, accounts: &[(Pubkey, AccountSharedData)]) -> Self {
+        self.with_accounts(accounts)
+    }
+
+    /// The cached accounts as `AccountSharedData`, sorted by pubkey, for frameworks
+    /// that take them.
+    pub fn to_shared(&self) -> Vec<(Pubkey, AccountSharedData)> {
+        self.iter()
+            .map(|(pubkey, account)| (*pubkey, account.clone().into()))
+            .collect()
+    }
+
+    /// [`instruction_accounts`](Self::instruction_accounts) as `AccountSharedData`.
+    /// The accounts are converted without copying their data again.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`RpcError::AccountNotCached`] with the position of the first meta whose
+    /// account hasn't been fetched or added.
+    pub fn instruction_accounts_shared(
+        &self,
+        mollusk: &Mollusk,
+        instruction: &Instruction,
+    ) -> Result<Vec<(Pubkey, AccountSharedData)>, RpcError> {
+        let accounts = self.instruction_accounts(mollusk, instruction)?;
+        Ok(accounts
+            .into_iter()
+            .map(|(pubkey, account)| (pubkey, account.into()))
+            .collect())
+    }
+}
+
+/// Adds the accounts as mocks, like [`RpcAccountStore::with_accounts`], including
+/// its [`MockReplacesAccount`](crate::WarningCode::MockReplacesAccount) warning.
+///
+/// ```
+/// # use mollusk_on_demand::{testing::MockFetcher, Provenance, RpcAccountStore};
+/// # use {solana_account::Account, solana_pubkey::Pubkey};
+/// let (alice, bob) = (Pubkey::new_unique(), Pubkey::new_unique());
+/// let mut store = RpcAccountStore::new_with_fetcher(MockFetcher::new());
+/// store.extend([
+///     (alice, Account::new(1_000, 0, &Pubkey::default())),
+///     (bob, Account::new(2_000, 0, &Pubkey::default())),
+/// ]);
+///
+/// assert_eq!(store.provenance(&alice), Some(&Provenance::Mocked));
+/// assert_eq!(store.provenance(&bob), Some(&Provenance::Mocked));
+/// assert!(store.warnings().is_empty());
+/// ```
+impl Extend<(Pubkey, Account)> for RpcAccountStore {
+    fn extend<T: IntoIterator<Item = (Pubkey, Account)>>(&mut self, accounts: T) {
+        for (pubkey, account) in accounts {
+            self.insert_mock(pubkey, account, "extend");
+        }
+    }
+}
+
+impl<'a> IntoIterator for &'a RpcAccountStore {
+    type Item = (&'a Pubkey, &'a Account);
+    type IntoIter = std::vec::IntoIter<(&'a Pubkey, &'a Account)>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
@@ -0,0 +1,121 @@
+//! Ids and names of well-known programs, so tests don't have to re-declare them.
+//!
+//! ```rust,ignore
+//! use mollusk_on_demand::known_programs::{ASSOCIATED_TOKEN, TOKEN};
+//!
+//! let store = RpcAccountStore::mainnet()
+//!     .with_known_programs(&[TOKEN, ASSOCIATED_TOKEN])
+//!     .await?
+//!     .add_programs(&mut mollusk)
+//!     .await?;
+//! ```
+//!
+//! To add a program, declare its constant and list it in [`ALL`].
+
+use {
+    crate::{
+        summary::{SPL_ASSOCIATED_TOKEN, SPL_TOKEN, SPL_TOKEN_2022},
+        RpcAccountStore, RpcError,
+    },
+    solana_pubkey::Pubkey,
+    solana_sdk_ids::system_program,
+};
+
+/// A well-known program.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct KnownProgram {
+    pub id: Pubkey,
+    /// Human-readable name, e.g. `SPL Token`.
+    pub name: &'static str,
+    /// What [`RpcAccountStore::label_known_programs`] labels it, e.g. `token_program`.
+    pub label: &'static str,
+    /// Whether Mollusk has the program built in, so there's nothing to fetch.
+    pub builtin: bool,
+}
+
+impl KnownProgram {
+    const fn bpf(id: Pubkey, name: &'static str, label: &'static str) -> Self {
+        Self {
+            id,
+            name,
+            label,
+            builtin: false,
+        }
+    }
+}
+
+pub const SYSTEM: KnownProgram = KnownProgram {
+    id: system_program::ID,
+    name: "System Program",
+    label: "system_program",
+    builtin: true,
+};
+pub const TOKEN: KnownProgram = KnownProgram::bpf(SPL_TOKEN, "SPL Token", "token_program");
+pub const TOKEN_2022: KnownProgram =
+    KnownProgram::bpf(SPL_TOKEN_2022, "SPL Token-2022", "token_2022_program");
+pub const ASSOCIATED_TOKEN: KnownProgram = KnownProgram::bpf(
+    SPL_ASSOCIATED_TOKEN,
+    "SPL Associated Token Account",
+    "associated_token_program",
+);
+pub const MEMO: KnownProgram = KnownProgram::bpf(
+    Pubkey::from_str_const("MemoSq4gqABAXKb96qnH8TysNcWxMyWCqXgDLGmfcHr"),
+    "SPL Memo",
+    "memo_program",
+);
+pub const MEMO_V1: KnownProgram = KnownProgram::bpf(
+    Pubkey::from_str_const("Memo1UhkJRfHyvLMcVucJwxXeuD728EqVDDwQDxFMNo"),
+    "SPL Memo v1",
+    "memo_v1_program",
+);
+pub const TOKEN_METADATA: KnownProgram = KnownProgram::bpf(
+    Pubkey::from_str_const("metaqbxxUerdq28cj1RbAWkYQm3ybzjb6a8bt518x1s"),
+    "Metaplex Token Metadata",
+    "token_metadata_program",
+);
+
+/// Every program of the registry.
+pub const ALL: [KnownProgram; 7] = [
+    SYSTEM,
+    TOKEN,
+    TOKEN_2022,
+    ASSOCIATED_TOKEN,
+    MEMO,
+    MEMO_V1,
+    TOKEN_METADATA,
+];
+
+/// The registry entry for `id`, if it's a known program.
+pub fn find(id: &Pubkey) -> Option<&'static KnownProgram> {
+    ALL.iter().find(|program| program.id == *id)
+}
+
+impl RpcAccountStore {
+    /// Label every program of the registry (see [`label`](Self::label)), keeping
+    /// labels already set for them.
+    pub fn label_known_programs(&mut self) {
+        for program in &ALL {
+            self.labels
+                .entry(program.id)
+                .or_insert_with(|| program.label.to_string());
+        }
+    }
+
+    /// Fetch `programs` in one batch, so that [`add_programs`](Self::add_programs)
+    /// loads them.
+    ///
+    /// Builtins such as the System Program are already in Mollusk and aren't
+    /// fetched. Program data of upgradeable programs is fetched by `add_programs`.
+    pub async fn with_known_programs(
+        mut self,
+        programs: &[KnownProgram],
+    ) -> Result<Self, RpcError> {
+        let ids: Vec<Pubkey> = programs
+            .iter()
+            .filter(|program| !program.builtin)
+            .map(|program| program.id)
+            .collect();
+        self.fetch_accounts(&ids).await?;
+        Ok(self)
+    }
+}
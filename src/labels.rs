@@ -0,0 +1,52 @@
+//! Human-readable names for pubkeys in reports and error messages.
+
+use {crate::RpcAccountStore, solana_pubkey::Pubkey};
+
+/// `label (4Nd1…kT3q)` if there is a label, the full pubkey otherwise.
+pub(crate) fn labeled(label: Option<&str>, pubkey: &Pubkey) -> String {
+    let Some(label) = label else {
+        return pubkey.to_string();
+    };
+    let base58 = pubkey.to_string();
+    let (head, tail) = (&base58[..4], &base58[base58.len() - 4..]);
+    format!("{label} ({head}…{tail})")
+}
+
+impl RpcAccountStore {
+    /// Name `pubkey` in the store's reports and error messages.
+    ///
+    /// The summary printed by `Display`, [`diff_result`](Self::diff_result) and
+    /// missing-account errors show labeled pubkeys as `alice_usdc (4Nd1…kT3q)`.
+    /// Labels only change formatting, never what the store does.
+    pub fn label(&mut self, pubkey: &Pubkey, name: &str) {
+        self.labels.insert(*pubkey, name.to_string());
+    }
+
+    /// [`label`](Self::label) several pubkeys.
+    pub fn with_labels(mut self, labels: &[(Pubkey, &str)]) -> Self {
+        for (pubkey, name) in labels {
+            self.label(pubkey, name);
+        }
+        self
+    }
+
+    /// Label the System, SPL Token, Token-2022, Associated Token Account, Memo and
+    /// Token Metadata programs, keeping labels already set for them.
+    ///
+    /// See [`label_known_programs`](Self::label_known_programs).
+    pub fn with_well_known_labels(mut self) -> Self {
+        self.label_known_programs();
+        self
+    }
+
+    /// The label set for `pubkey`, if any.
+    pub fn label_of(&self, pubkey: &Pubkey) -> Option<&str> {
+        self.labels.get(pubkey).map(String::as_str)
+    }
+
+    /// `pubkey` as the store's reports print it: `alice_usdc (4Nd1…kT3q)` if it's
+    /// labeled, in full otherwise.
+    pub fn display_pubkey(&self, pubkey: &Pubkey) -> String {
+        labeled(self.label_of(pubkey), pubkey)
+    }
+}
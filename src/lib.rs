@@ -90,13 +90,83 @@
 //!     .await?;
 //! ```
 //!
+//! ## Test without a network
+//!
+//! ```rust,ignore
+//! use mollusk_on_demand::testing::MockFetcher;
+//!
+//! let fetcher = MockFetcher::new().with_account(pubkey, account);
+//! let store = RpcAccountStore::new_with_fetcher(fetcher.clone())
+//!     .from_instruction(&instruction)
+//!     .await?;
+//! ```
+//!
 //! # Error Handling
 //!
 //! The crate provides detailed errors for common failure cases:
 //! - `RpcError::AccountNotFound`: Account doesn't exist (unless `allow_missing_accounts` is set)
+//! - `RpcError::AccountsNotFound`: Several accounts of one fetch don't exist, all listed
+//! - `RpcError::AccountNotFoundInInstruction` / `AccountsNotFoundInInstructions`: The
+//!   same, for accounts fetched for instructions, with where each one appears
+//! - `RpcError::ProgramDataNotFound`: A program's program data account doesn't exist
+//! - `RpcError::ProgramDataAccountsNotFound`: Several programs' program data accounts don't
+//!   exist, all listed as `(program, program_data)` pairs
 //! - `RpcError::MalformedProgram`: Program account structure is invalid
 //! - `RpcError::InvalidProgramData`: Program data account is missing or malformed
 //! - `RpcError::Client`: RPC request failed
+//! - `RpcError::InvalidHeader`: A custom HTTP header is not valid
+//! - `RpcError::Offline`: An RPC request was needed while the store is offline
+//! - `RpcError::Fixture`: A fixture file couldn't be read or written
+//! - `RpcError::InvalidEnvVar`: An environment variable read by `from_env` is invalid
+//! - `RpcError::InvalidConfig`: A config file read by `RpcStoreConfig::from_file` is invalid
+//! - `RpcError::BudgetExceeded`: A request would exceed the budget set with `with_rpc_budget`
+//! - `RpcError::WrongCluster`: The endpoint serves a different cluster than `expect_cluster` asked for
+//! - `RpcError::AccountNotCached`: `instruction_accounts` needs an account that hasn't been fetched
+//! - `RpcError::TooManyProgramAccounts`: A program owns more accounts than `with_program_accounts_limit` allows
+//! - `RpcError::TooManyReferencedAccounts`: `with_referenced_accounts` found more accounts
+//!   than `with_referenced_accounts_limit` allows
+//! - `RpcError::InvalidSysvar`: A sysvar account fetched by a `with_synced_*` method can't be parsed
+//! - `RpcError::TooFewBlocks`: `with_synced_slot_hashes` found fewer recent blocks than asked for
+//! - `RpcError::NegativeSlotOffset`: `with_slot_offset` was asked to warp backwards
+//! - `RpcError::PastEpoch`: `warp_to_epoch` was asked to warp backwards
+//! - `RpcError::OwnerProgramsNotLoaded`: `verify_owners_loaded` found writable accounts
+//!   whose owner program isn't loaded
+//! - `RpcError::WrongOwners`: `assert_owner` or `assert_owners` found accounts owned by
+//!   another program than expected
+//! - `RpcError::AccountTooLarge`: A fetched account exceeds `with_max_account_bytes_strict`
+//! - `RpcError::InvalidTokenState`: `get_token_account`, `get_mint`, `token_balance` or
+//!   `get_token_metadata` found an account of another owner, size or state
+//! - `RpcError::InvalidVoteAccount`: `get_vote_state` found an account that isn't an
+//!   initialized vote account
+//! - `RpcError::InvalidLookupTable`: `extend_lookup_table_in_cache` found an account that
+//!   isn't an active lookup table, or the table would outgrow 256 addresses
+//! - `RpcError::TokenOperation`: `transfer_tokens_in_cache` or `set_token_balance` found
+//!   accounts of different mints, a frozen account or too small a balance, or
+//!   `sync_native_in_cache` an account that isn't native or lost lamports, or
+//!   `with_wsol_account` got fewer lamports than the rent-exempt reserve
+//! - `RpcError::FixturesDrifted`: `fail_if_changed_fraction` found more fixture accounts
+//!   changed on-chain than allowed
+//! - `RpcError::Timeout`: `wait_for_account` gave up before the account appeared
+//! - `RpcError::RequirementsNotMet`: `require_accounts` or `require_programs` found
+//!   accounts that aren't cached or programs that aren't loaded
+//! - `RpcError::LocalProgram`: A program ELF registered with `with_program_from_file` or
+//!   `with_workspace_programs` can't be read
+//! - `RpcError::InstructionLints`: `assert_no_lints` found problems with an
+//!   instruction's account metas
+//! - `RpcError::Export`: Another framework rejected a cached account, in `into_litesvm`
+//!   or `apply_to_program_test`
+//! - `RpcError::ShadowedMocks`: `deny_shadowed_mocks` found mocks of accounts that exist
+//!   on-chain
+//! - `RpcError::UnexpectedWarnings`: `assert_no_warnings` found recorded warnings
+//! - `RpcError::Pubsub`: A WebSocket subscription failed (`pubsub` feature)
+//! - `RpcError::InvalidOracleAccount`: `refresh_pyth_price` or `refresh_switchboard_feed`
+//!   can't patch an account, e.g. of another layout (`pyth` and `switchboard` features)
+//! - `RpcError::InvalidStakePool`: `with_stake_pool` can't decode a stake pool or its
+//!   validator list (`stake-pool` feature)
+//! - `RpcError::AnchorIdl`: `with_anchor_account_from_idl` can't build an account from
+//!   the IDL and values given (`anchor-idl` feature)
+//! - `RpcError::AccountEdit`: `edit_account_as` can't decode an account as the type
+//!   given, or the edited value outgrows it (`borsh` feature)
 //!
 //! # Performance Considerations
 //!
@@ -104,20 +174,436 @@
 //! - Caches accounts to avoid redundant fetches
 //! - Fetches program data accounts in batches
 //! - Consider using a private RPC endpoint for heavy testing to avoid rate limits
+//!
+//! # Tracing
+//!
+//! With the `tracing` feature enabled, the store emits spans and events that can be
+//! consumed by any `tracing` subscriber. Names and fields are stable:
+//!
+//! - Spans `from_instruction` and `from_instructions` with field `pubkeys`, and
+//!   `add_programs` with field `accounts_cached`
+//! - Event `cache_lookup` with fields `requested`, `cache_hits`, `fixtures` (accounts
+//!   replayed from the fixture directory, not counted as hits) and `cache_misses`
+//! - Event `rpc_request` with fields `method`, `chunk_size`, `latency_ms`
+//! - Event `program_loaded` with fields `program`, `loader`, `elf_size`
+//! - Event `program_skipped` with fields `program`, `loader`, for programs Mollusk
+//!   already had
+//! - With `log_fetches`, event `fetch` with fields `requested`, `cache_hits`,
+//!   `fixtures`, `fetched`, `batches`, `elapsed_ms`, and event `fetched_pubkeys` with
+//!   field `pubkeys`
+//!
+//! All spans and events are emitted at `DEBUG` level under the `mollusk_on_demand`
+//! target, except `fetch`, which is `INFO`. Nothing is emitted (or measured) when the
+//! feature is disabled.
+
+#[cfg(feature = "anchor-idl")]
+mod anchor_idl;
+mod bench;
+mod checks;
+mod cluster;
+mod codegen;
+mod commitment_fallback;
+mod compute_budget;
+mod config;
+mod conservation;
+#[cfg(feature = "context")]
+mod context;
+#[cfg(feature = "decoder")]
+mod decoder;
+mod diff;
+mod drift;
+mod drift_check;
+#[cfg(feature = "borsh")]
+mod edit;
+mod endpoint;
+mod error_kind;
+mod export;
+pub mod fetcher;
+mod features;
+mod fixture;
+mod frozen;
+mod iter;
+pub mod known_programs;
+mod labels;
+mod lint;
+mod lookup_table;
+mod memory;
+mod metadata;
+mod mocks;
+#[cfg(any(feature = "pyth", feature = "switchboard"))]
+mod oracle;
+mod owners;
+mod precedence;
+mod process;
+mod program_accounts;
+#[cfg(feature = "pubsub")]
+mod pubsub;
+#[cfg(feature = "pyth")]
+mod pyth;
+mod rate_limit;
+mod redact;
+mod references;
+mod rent_exemption;
+mod requirements;
+mod retry;
+mod routing;
+mod shared;
+mod simulation;
+mod snapshot;
+#[cfg(feature = "stake-pool")]
+mod stake_pool;
+mod stats;
+mod summary;
+#[cfg(feature = "switchboard")]
+mod switchboard;
+mod sysvars;
+pub mod testing;
+mod token;
+mod token_setup;
+mod usage;
+mod vote;
+mod wait;
+mod wallet;
+mod warmup;
+mod warnings;
+mod workspace;
+
+#[cfg(feature = "context")]
+pub use context::LazyAccountStore;
+#[cfg(feature = "borsh")]
+pub use edit::EditOptions;
+#[cfg(feature = "macros")]
+pub use mollusk_on_demand_macros::mollusk_test;
+#[cfg(feature = "switchboard")]
+pub use rust_decimal::Decimal;
+pub use {
+    bench::{load_bench_scenarios, BenchScenario},
+    cluster::Cluster,
+    compute_budget::ComputeBudgetRequest,
+    config::RpcStoreConfig,
+    conservation::{ConservationViolation, LamportDelta},
+    diff::{AccountChange, AccountDiff, StateDiff},
+    drift::{Drift, DriftReport},
+    drift_check::{DriftCategory, FixtureAccountDrift, FixtureDriftReport},
+    error_kind::RpcErrorKind,
+    fetcher::{AccountFetcher, ProgramAccountsPage, TransactionSimulation},
+    frozen::FrozenAccountStore,
+    iter::IntoAccountEntry,
+    known_programs::KnownProgram,
+    lint::{InstructionLint, LintKind, LintSeverity},
+    memory::{LargeAccount, MemoryReport},
+    metadata::{TokenMetadata, TokenMetadataAccounts},
+    mocks::ShadowedMock,
+    owners::OwnerMismatch,
+    process::{
+        process_instruction_against_mainnet, process_instruction_with_store, ChainStep,
+        SlotAdvance,
+    },
+    requirements::{UnmetReason, UnmetRequirement},
+    shared::SharedAccountStore,
+    simulation::{Divergence, DivergenceReport},
+    snapshot::SnapshotOptions,
+    stats::RpcStats,
+    summary::StoreSummary,
+    sysvars::instructions_sysvar_account,
+    token::TokenStateError,
+    token_setup::TokenOperationError,
+    vote::VoteMock,
+    wallet::WalletSnapshot,
+    warmup::WarmupHandle,
+    warnings::{Warning, WarningCode},
+};
 
 use {
-    mollusk_svm::Mollusk,
+    crate::{endpoint::RpcEndpoint, rate_limit::RateLimiter, warnings::WarningCallback},
+    futures::stream::{self, StreamExt},
+    mollusk_svm::{result::InstructionResult, Mollusk},
     solana_account::Account,
     solana_commitment_config::CommitmentConfig,
     solana_instruction::Instruction,
     solana_pubkey::Pubkey,
-    solana_rpc_client::nonblocking::rpc_client::RpcClient,
-    solana_rpc_client_api::client_error::Error as ClientError,
+    solana_rpc_client_api::{client_error::Error as ClientError, request::MAX_MULTIPLE_ACCOUNTS},
+    solana_sdk_ids::sysvar,
+    solana_stake_interface::stake_history::StakeHistoryEntry,
     std::collections::{HashMap, HashSet},
     std::fmt,
+    std::path::PathBuf,
+    std::sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex, MutexGuard,
+    },
+    std::time::{Duration, SystemTime},
     thiserror::Error,
 };
 
+/// The public mainnet-beta RPC endpoint, used by [`RpcAccountStore::from_env`]
+/// when `MOLLUSK_RPC_URL` is not set.
+pub const MAINNET_RPC_URL: &str = "https://api.mainnet-beta.solana.com";
+
+/// The public devnet RPC endpoint, used by [`RpcAccountStore::devnet`].
+pub const DEVNET_RPC_URL: &str = "https://api.devnet.solana.com";
+
+/// The public testnet RPC endpoint, used by [`RpcAccountStore::testnet`].
+pub const TESTNET_RPC_URL: &str = "https://api.testnet.solana.com";
+
+/// The default `solana-test-validator` RPC endpoint, used by [`RpcAccountStore::localnet`].
+pub const LOCALNET_RPC_URL: &str = "http://127.0.0.1:8899";
+
+/// Target slot duration, used to estimate the clock's epoch start time.
+const MS_PER_SLOT: u64 = 400;
+
+/// Warp Mollusk to `slot` (if it isn't there yet) and make the clock's epoch and
+/// leader schedule epoch agree with Mollusk's epoch schedule.
+///
+/// Not every Mollusk version reconciles the epochs in `warp_to_slot`, and a warp
+/// done before the epoch schedule was synced leaves them stale.
+pub(crate) fn warp_clock(mollusk: &mut Mollusk, slot: u64) {
+    if mollusk.sysvars.clock.slot != slot {
+        mollusk.warp_to_slot(slot);
+    }
+    let epoch_schedule = &mollusk.sysvars.epoch_schedule;
+    let clock = &mut mollusk.sysvars.clock;
+    clock.slot = slot;
+    clock.epoch = epoch_schedule.get_epoch(slot);
+    clock.leader_schedule_epoch = epoch_schedule.get_leader_schedule_epoch(slot);
+}
+
+/// The seconds `slots` slots take at 400ms each.
+fn slots_to_seconds(slots: u64) -> i64 {
+    let seconds = u128::from(slots) * u128::from(MS_PER_SLOT) / 1_000;
+    i64::try_from(seconds).unwrap_or(i64::MAX)
+}
+
+/// Move Mollusk's clock `slots` slots forward, and `unix_timestamp` by the 400ms
+/// each takes. Like the rest of the clock, both stop at the largest value they hold.
+pub(crate) fn advance_clock_by_slots(mollusk: &mut Mollusk, slots: u64) {
+    advance_clock(mollusk, slots, slots_to_seconds(slots));
+}
+
+/// Move Mollusk's clock forward by `elapsed`: `unix_timestamp` by its whole seconds
+/// and the slot by the 400ms slots that fit in it, saturating like
+/// [`advance_clock_by_slots`].
+pub(crate) fn advance_clock_by_time(mollusk: &mut Mollusk, elapsed: Duration) {
+    let slots = u64::try_from(elapsed.as_millis() / u128::from(MS_PER_SLOT)).unwrap_or(u64::MAX);
+    let seconds = i64::try_from(elapsed.as_secs()).unwrap_or(i64::MAX);
+    advance_clock(mollusk, slots, seconds);
+}
+
+/// Move Mollusk's clock `slots` slots and `seconds` seconds forward.
+fn advance_clock(mollusk: &mut Mollusk, slots: u64, seconds: i64) {
+    let before = mollusk.sysvars.clock.clone();
+    let slot = before.slot.saturating_add(slots);
+    warp_clock(mollusk, slot);
+
+    let unix_timestamp = before.unix_timestamp.saturating_add(seconds);
+    let clock = &mut mollusk.sysvars.clock;
+    clock.unix_timestamp = unix_timestamp;
+    clock.epoch_start_timestamp = if clock.epoch == before.epoch {
+        before.epoch_start_timestamp
+    } else {
+        let epoch_start_slot = mollusk
+            .sysvars
+            .epoch_schedule
+            .get_first_slot_in_epoch(clock.epoch);
+        unix_timestamp.saturating_sub(slots_to_seconds(slot - epoch_start_slot))
+    };
+}
+
+/// Point missing-account errors from fetching for `instructions` at the metas that
+/// list the accounts.
+pub(crate) fn locate_missing(
+    store: &RpcAccountStore,
+    instructions: &[Instruction],
+    error: RpcError,
+) -> RpcError {
+    let locate = |pubkey: &Pubkey| {
+        instructions.iter().enumerate().find_map(|(instruction_index, ix)| {
+            let (account_index, meta) = ix
+                .accounts
+                .iter()
+                .enumerate()
+                .find(|(_, meta)| meta.pubkey == *pubkey)?;
+            Some(MissingAccount {
+                pubkey: *pubkey,
+                instruction_index,
+                account_index,
+                is_signer: meta.is_signer,
+                is_writable: meta.is_writable,
+                label: store.label_of(pubkey).map(str::to_string),
+            })
+        })
+    };
+
+    match &error {
+        RpcError::AccountNotFound(pubkey) => match locate(pubkey) {
+            Some(missing) => RpcError::AccountNotFoundInInstruction(missing),
+            None => error,
+        },
+        RpcError::AccountsNotFound(pubkeys) => {
+            match pubkeys.iter().map(locate).collect::<Option<Vec<_>>>() {
+                Some(missing) => RpcError::AccountsNotFoundInInstructions(missing),
+                None => error,
+            }
+        }
+        _ => error,
+    }
+}
+
+/// The program data address stored in a BPF Loader v3 program account.
+fn program_data_address(pubkey: &Pubkey, account: &Account) -> Result<Pubkey, RpcError> {
+    if account.data.len() < 36 {
+        return Err(RpcError::MalformedProgram {
+            program: *pubkey,
+            reason: format!(
+                "BPF Loader v3 program account too small: {} bytes (expected at least 36)",
+                account.data.len()
+            ),
+        });
+    }
+
+    Pubkey::try_from(&account.data[4..36]).map_err(|e| RpcError::MalformedProgram {
+        program: *pubkey,
+        reason: format!("Invalid program data pubkey: {}", e),
+    })
+}
+
+/// Summarize a fetch for [`RpcAccountStore::log_fetches`]; `started` is `None` if
+/// it's disabled.
+#[cfg(feature = "tracing")]
+fn log_fetch(
+    started: Option<std::time::Instant>,
+    requested: usize,
+    cache_hits: usize,
+    fetched: &[Pubkey],
+) {
+    let Some(started) = started else {
+        return;
+    };
+    let fixtures = requested - cache_hits - fetched.len();
+    let batches = fetched.len().div_ceil(MAX_MULTIPLE_ACCOUNTS);
+    let elapsed_ms = started.elapsed().as_millis() as u64;
+    tracing::info!(
+        name: "fetch",
+        requested,
+        cache_hits,
+        fixtures,
+        fetched = fetched.len(),
+        batches,
+        elapsed_ms,
+        "requested {} pubkeys: {} cached, {} from fixtures, {} fetched in {} {} ({}ms)",
+        requested,
+        cache_hits,
+        fixtures,
+        fetched.len(),
+        batches,
+        if batches == 1 { "batch" } else { "batches" },
+        elapsed_ms,
+    );
+    if !fetched.is_empty() {
+        tracing::debug!(name: "fetched_pubkeys", pubkeys = ?fetched);
+    }
+}
+
+/// How [`RpcAccountStore::add_programs`] treats program ELFs that fail validation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ValidationMode {
+    /// Fail with [`RpcError::InvalidProgramData`].
+    #[default]
+    Strict,
+    /// Load the program anyway, with a [`WarningCode::InvalidElfLoaded`] warning.
+    Warn,
+    /// Don't validate.
+    Off,
+}
+
+/// Where a cached account came from; see [`RpcAccountStore::provenance`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Provenance {
+    /// Added by the caller, e.g. with `with_accounts`.
+    Mocked,
+    /// Fetched from the RPC endpoint.
+    Fetched {
+        /// The cluster slot the store had synced to when it fetched the account,
+        /// if it synced one (see [`RpcAccountStore::with_synced_slot`]).
+        slot: Option<u64>,
+        at: SystemTime,
+        /// The endpoint of an account routed away from the store's own with
+        /// [`RpcAccountStore::with_endpoint_for`], redacted like in errors.
+        endpoint: Option<String>,
+        /// The commitment of an account found by
+        /// [`RpcAccountStore::with_commitment_fallback`] after the store's own
+        /// commitment missed it.
+        commitment: Option<CommitmentConfig>,
+    },
+    /// Replayed from a fixture file.
+    Fixture { path: PathBuf },
+    /// Made up by the store rather than read from anywhere, for the given reason.
+    Synthesized(&'static str),
+    /// Written back from an instruction's result.
+    ResultApplied {
+        /// How many results [`apply_result`](RpcAccountStore::apply_result) had
+        /// applied before this one; `None` for accounts written by `MolluskContext`.
+        instruction_index: Option<usize>,
+    },
+    /// Fetched, then topped up to the rent-exempt minimum by `auto_top_up_rent`.
+    RentToppedUp,
+    /// Added with `with_accounts_override`; fetches never replace it.
+    Override,
+    /// Added with `with_accounts_fallback`; replaced by the chain's account if the
+    /// next fetch finds one.
+    Fallback,
+    /// Cached, then rewritten in place by the given method, such as
+    /// `refresh_pyth_price` or `refresh_switchboard_feed`.
+    Patched(&'static str),
+}
+
+impl fmt::Display for Provenance {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Fetched {
+                slot,
+                endpoint,
+                commitment,
+                ..
+            } if slot.is_some() || endpoint.is_some() || commitment.is_some() => {
+                f.write_str("fetched")?;
+                if let Some(endpoint) = endpoint {
+                    write!(f, " from {endpoint}")?;
+                }
+                if let Some(slot) = slot {
+                    write!(f, " at slot {slot}")?;
+                }
+                if let Some(commitment) = commitment {
+                    write!(f, " (found at {})", commitment.commitment)?;
+                }
+                Ok(())
+            }
+            Self::Fixture { path } => write!(f, "fixture {}", path.display()),
+            Self::Synthesized(reason) => write!(f, "synthesized: {reason}"),
+            Self::Patched(method) => write!(f, "patched by {method}"),
+            Self::ResultApplied {
+                instruction_index: Some(index),
+            } => write!(f, "result of instruction {index}"),
+            _ => f.write_str(summary::provenance_label(Some(self))),
+        }
+    }
+}
+
+/// Read an environment variable, treating unset and empty values the same.
+fn env_var(name: &str) -> Option<String> {
+    std::env::var(name).ok().filter(|value| !value.is_empty())
+}
+
+/// An ELF in the store's `elf_cache`, with the address and length of the account
+/// data it was copied from.
+#[derive(Clone)]
+pub(crate) struct CachedElf {
+    elf: Arc<[u8]>,
+    source: (usize, usize),
+}
+
 /// Validates that the given data contains a valid ELF header.
 ///
 /// This performs basic validation to ensure the data is likely a valid ELF binary.
@@ -150,21 +636,313 @@ fn validate_elf(data: &[u8]) -> Result<(), String> {
 }
 
 /// Error types for RPC operations.
+///
+/// New variants may be added in minor releases; use [`kind`](Self::kind),
+/// [`is_retryable`](Self::is_retryable) and [`is_not_found`](Self::is_not_found)
+/// rather than exhaustive matches to react to categories of errors, and
+/// [`code`](Self::code) to identify one in logs.
 #[derive(Debug, Error)]
+#[non_exhaustive]
 pub enum RpcError {
-    #[error("RPC client error: {0}")]
+    #[error("[{}] RPC client error: {}", self.code(), .0)]
     Client(#[from] ClientError),
 
-    #[error("Account not found: {0}")]
+    #[error("[{}] Account not found: {}", self.code(), .0)]
     AccountNotFound(Pubkey),
 
-    #[error("Invalid program data account for program {program}: {reason}")]
+    #[error(
+        "[{}] Accounts not found: {}",
+        self.code(),
+        .0.iter().map(ToString::to_string).collect::<Vec<_>>().join(", ")
+    )]
+    AccountsNotFound(Vec<Pubkey>),
+
+    #[error("[{}] Account not found: {}", self.code(), .0)]
+    AccountNotFoundInInstruction(MissingAccount),
+
+    #[error(
+        "[{}] Accounts not found: {}",
+        self.code(),
+        .0.iter().map(ToString::to_string).collect::<Vec<_>>().join(", ")
+    )]
+    AccountsNotFoundInInstructions(Vec<MissingAccount>),
+
+    #[error(
+        "[{}] Program data account {program_data} not found while resolving programdata for \
+         program {program}",
+        self.code()
+    )]
+    ProgramDataNotFound { program: Pubkey, program_data: Pubkey },
+
+    #[error(
+        "[{}] Program data accounts not found: {}",
+        self.code(),
+        .0.iter()
+            .map(|(program, program_data)| format!("{program_data} (program {program})"))
+            .collect::<Vec<_>>()
+            .join(", ")
+    )]
+    ProgramDataAccountsNotFound(Vec<(Pubkey, Pubkey)>),
+
+    #[error("[{}] Invalid program data account for program {program}: {reason}", self.code())]
     InvalidProgramData { program: Pubkey, reason: String },
 
-    #[error("Malformed program account {program}: {reason}")]
+    #[error("[{}] Malformed program account {program}: {reason}", self.code())]
     MalformedProgram { program: Pubkey, reason: String },
+
+    #[error("[{}] Invalid HTTP header {name}: {reason}", self.code())]
+    InvalidHeader { name: String, reason: String },
+
+    #[error("[{}] RPC request {method} attempted while the store is offline", self.code())]
+    Offline { method: &'static str },
+
+    #[error("[{}] Fixture error at {}: {reason}", self.code(), path.display())]
+    Fixture { path: PathBuf, reason: String },
+
+    #[error("[{}] Invalid environment variable {name}: {reason}", self.code())]
+    InvalidEnvVar { name: &'static str, reason: String },
+
+    #[error("[{}] Invalid config file {}: {reason}", self.code(), path.display())]
+    InvalidConfig { path: PathBuf, reason: String },
+
+    #[error(
+        "[{}] RPC budget of {limit} requests exceeded by {attempted_method} (spent: {spent})",
+        self.code()
+    )]
+    BudgetExceeded {
+        limit: u64,
+        attempted_method: &'static str,
+        spent: RpcStats,
+    },
+
+    #[error(
+        "[{}] Expected the RPC endpoint to serve {expected}, but it serves {actual}",
+        self.code()
+    )]
+    WrongCluster { expected: Cluster, actual: Cluster },
+
+    #[error(
+        "[{}] Account {pubkey} (instruction {instruction}, account {index}) is not in the cache",
+        self.code()
+    )]
+    AccountNotCached {
+        pubkey: Pubkey,
+        instruction: usize,
+        index: usize,
+    },
+
+    #[error("[{}] Program {program} owns more than {limit} accounts", self.code())]
+    TooManyProgramAccounts { program: Pubkey, limit: usize },
+
+    #[error("[{}] Account {root} references more than {limit} accounts", self.code())]
+    TooManyReferencedAccounts { root: Pubkey, limit: usize },
+
+    #[error("[{}] Invalid {sysvar} sysvar account: {reason}", self.code())]
+    InvalidSysvar { sysvar: &'static str, reason: String },
+
+    #[error(
+        "[{}] Only {found} of {depth} finalized blocks in the {slots} slots before the cluster \
+         slot",
+        self.code()
+    )]
+    TooFewBlocks { depth: usize, found: usize, slots: u64 },
+
+    #[error("[{}] Can't move the clock back by {} slots", self.code(), .0.unsigned_abs())]
+    NegativeSlotOffset(i64),
+
+    #[error("[{}] Can't warp back from slot {slot} to the start of epoch {epoch}", self.code())]
+    PastEpoch { epoch: u64, slot: u64 },
+
+    #[error(
+        "[{}] Programs owning writable accounts are not loaded: {}; fetch them with \
+         `with_owner_programs()` before `add_programs`",
+        self.code(),
+        .0.iter().map(ToString::to_string).collect::<Vec<_>>().join(", ")
+    )]
+    OwnerProgramsNotLoaded(Vec<Pubkey>),
+
+    #[error(
+        "[{}] Accounts have unexpected owners: {}",
+        self.code(),
+        .0.iter().map(ToString::to_string).collect::<Vec<_>>().join(", ")
+    )]
+    WrongOwners(Vec<OwnerMismatch>),
+
+    #[error(
+        "[{}] Mocked accounts exist on-chain with different contents: {}",
+        self.code(),
+        .0.iter().map(ToString::to_string).collect::<Vec<_>>().join(", ")
+    )]
+    ShadowedMocks(Vec<ShadowedMock>),
+
+    #[error(
+        "[{}] Expected no warnings, got: {}",
+        self.code(),
+        .0.iter().map(ToString::to_string).collect::<Vec<_>>().join("; ")
+    )]
+    UnexpectedWarnings(Vec<Warning>),
+
+    #[error(
+        "[{}] Account {pubkey} has {size} bytes of data, more than the limit of {limit}",
+        self.code()
+    )]
+    AccountTooLarge {
+        pubkey: Pubkey,
+        size: usize,
+        limit: usize,
+    },
+
+    #[error(
+        "[{}] Required accounts are not in place: {}",
+        self.code(),
+        .0.iter().map(ToString::to_string).collect::<Vec<_>>().join(", ")
+    )]
+    RequirementsNotMet(Vec<UnmetRequirement>),
+
+    #[error("[{}] Account {pubkey} can't be read as a {state}: {reason}", self.code())]
+    InvalidTokenState {
+        pubkey: Pubkey,
+        state: &'static str,
+        reason: TokenStateError,
+    },
+
+    #[error("[{}] Account {pubkey} isn't a vote account: {reason}", self.code())]
+    InvalidVoteAccount { pubkey: Pubkey, reason: String },
+
+    #[error("[{}] Can't extend lookup table {pubkey}: {reason}", self.code())]
+    InvalidLookupTable { pubkey: Pubkey, reason: String },
+
+    #[error("[{}] Can't {operation} tokens in the cache: {reason}", self.code())]
+    TokenOperation {
+        operation: &'static str,
+        reason: TokenOperationError,
+    },
+
+    #[error(
+        "[{}] {} of {} accounts of {} changed on-chain, more than {}%",
+        self.code(),
+        report.changed().count(),
+        report.accounts.len(),
+        report.fixture.display(),
+        max_fraction * 100.0
+    )]
+    FixturesDrifted {
+        report: Box<FixtureDriftReport>,
+        max_fraction: f64,
+    },
+
+    #[error("[{}] Account {pubkey} still didn't exist after {waited:?}", self.code())]
+    Timeout { pubkey: Pubkey, waited: Duration },
+
+    #[error("[{}] Can't load local program {}: {reason}", self.code(), path.display())]
+    LocalProgram { path: PathBuf, reason: String },
+
+    #[error(
+        "[{}] Instruction metas have problems: {}",
+        self.code(),
+        .0.iter().map(ToString::to_string).collect::<Vec<_>>().join("; ")
+    )]
+    InstructionLints(Vec<InstructionLint>),
+
+    #[error("[{}] Can't export account {pubkey} to {target}: {reason}", self.code())]
+    Export {
+        target: &'static str,
+        pubkey: Pubkey,
+        reason: String,
+    },
+
+    #[cfg(feature = "pubsub")]
+    #[error("[{}] PubSub error: {}", self.code(), .0)]
+    Pubsub(String),
+
+    #[cfg(any(feature = "pyth", feature = "switchboard"))]
+    #[error("[{}] Can't patch {oracle} account {pubkey}: {reason}", self.code())]
+    InvalidOracleAccount {
+        pubkey: Pubkey,
+        oracle: &'static str,
+        reason: String,
+    },
+
+    #[cfg(feature = "stake-pool")]
+    #[error("[{}] Can't read stake pool account {pubkey}: {reason}", self.code())]
+    InvalidStakePool { pubkey: Pubkey, reason: String },
+
+    #[cfg(feature = "anchor-idl")]
+    #[error("[{}] Can't build Anchor account {account} from the IDL: {reason}", self.code())]
+    AnchorIdl { account: String, reason: String },
+
+    #[cfg(feature = "borsh")]
+    #[error("[{}] Can't edit account {pubkey}: {reason}", self.code())]
+    AccountEdit { pubkey: Pubkey, reason: String },
+}
+
+/// Progress of an in-flight fetch, reported after every `getMultipleAccounts` chunk.
+///
+/// See [`RpcAccountStore::with_progress`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FetchProgress {
+    /// Number of accounts requested so far in this fetch (including missing ones).
+    pub fetched: usize,
+    /// Total number of accounts this fetch will request.
+    pub total: usize,
+    /// Zero-based index of the chunk that just completed.
+    pub current_batch: usize,
+    /// Total bytes of account data downloaded so far in this fetch.
+    pub bytes_downloaded: usize,
+}
+
+/// A missing account and where it first appears in the instructions it was fetched for.
+///
+/// See [`RpcError::AccountNotFoundInInstruction`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MissingAccount {
+    pub pubkey: Pubkey,
+    /// Index of the instruction in the fetched list (0 for `from_instruction`).
+    pub instruction_index: usize,
+    /// Index of the account meta within that instruction.
+    pub account_index: usize,
+    pub is_signer: bool,
+    pub is_writable: bool,
+    /// See [`RpcAccountStore::label`].
+    pub label: Option<String>,
 }
 
+impl fmt::Display for MissingAccount {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} (instruction {}, account {}",
+            labels::labeled(self.label.as_deref(), &self.pubkey),
+            self.instruction_index,
+            self.account_index
+        )?;
+        if self.is_signer {
+            f.write_str(", signer")?;
+        }
+        if self.is_writable {
+            f.write_str(", writable")?;
+        }
+        f.write_str(")")
+    }
+}
+
+/// A failure that a [`lenient`](RpcAccountStore::lenient) store recorded and skipped.
+#[derive(Debug)]
+pub struct RecordedError {
+    /// The account or program that was skipped.
+    pub pubkey: Pubkey,
+    pub error: RpcError,
+}
+
+impl fmt::Display for RecordedError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.pubkey, self.error)
+    }
+}
+
+/// Callback invoked with [`FetchProgress`] updates.
+type ProgressCallback = Arc<dyn Fn(FetchProgress) + Send + Sync>;
+
 /// Utility for fetching accounts from Solana RPC endpoints.
 ///
 /// Fetches accounts and stores them internally in a `HashMap<Pubkey, Account>`.
@@ -175,26 +953,198 @@ pub enum RpcError {
 /// direct access to fetched accounts (e.g., for use with MolluskContext or custom
 /// account manipulation). For normal usage, prefer the builder methods.
 pub struct RpcAccountStore {
-    client: RpcClient,
+    fetcher: Arc<dyn AccountFetcher>,
+    /// The JSON-RPC endpoint behind `fetcher`, if the store was built from a URL.
+    endpoint: Option<RpcEndpoint>,
+    /// Endpoints other than the store's own, see
+    /// [`RpcAccountStore::with_endpoint_for`].
+    routed_endpoints: Vec<routing::RoutedEndpoint>,
+    /// The index in `routed_endpoints` that each routed pubkey is fetched from.
+    routes: HashMap<Pubkey, usize>,
+    /// The routed endpoint slots are synced with, see
+    /// [`RpcAccountStore::with_slot_endpoint`].
+    slot_endpoint: Option<usize>,
+    /// See [`RpcAccountStore::with_commitment_fallback`].
+    commitment_fallback: Vec<CommitmentConfig>,
+    /// The commitment of each account a fallback found, until it's cached. Shared
+    /// with copies, so warm-ups report theirs.
+    found_at_fallback: Arc<Mutex<HashMap<Pubkey, CommitmentConfig>>>,
     /// Publicly accessible cache of fetched accounts.
     ///
     /// Use this when you need direct access to accounts for custom operations.
     /// Most users should rely on the builder methods instead.
     pub cache: HashMap<Pubkey, Account>,
+    /// Where each entry in `cache` came from. Entries inserted into `cache` directly
+    /// have none.
+    provenance: HashMap<Pubkey, Provenance>,
+    /// Accounts the fetched instructions write to, see
+    /// [`RpcAccountStore::verify_owners_loaded`].
+    writable_accounts: HashSet<Pubkey>,
+    /// Set by the fetched ComputeBudget instructions, see
+    /// [`RpcAccountStore::compute_budget`].
+    compute_budget: ComputeBudgetRequest,
+    /// Cached accounts handed to Mollusk, see [`RpcAccountStore::used_accounts`].
+    used_accounts: Mutex<HashSet<Pubkey>>,
+    /// ELFs copied out of program (data) accounts, shared by loading, exports and
+    /// [`RpcAccountStore::program_elf`].
+    elf_cache: Mutex<HashMap<Pubkey, CachedElf>>,
+    /// See [`RpcAccountStore::with_memory_warning_threshold`].
+    memory_watch: Option<memory::MemoryWatch>,
     /// If true, fetching non-existent accounts will create default (empty) accounts.
     /// If false, will return an error when accounts don't exist.
     allow_missing_accounts: bool,
-    /// If true, validates program ELF headers before adding to Mollusk.
-    validate_programs: bool,
+    /// Largest account data a fetch accepts, see
+    /// [`RpcAccountStore::with_max_account_bytes_strict`].
+    max_account_bytes: Option<usize>,
+    /// If true, `max_account_bytes` applies to programs and program data too.
+    check_program_data_size: bool,
+    /// If true, errors hide the secrets in the store's URLs.
+    redact_urls: bool,
+    /// How program ELFs are validated before adding to Mollusk.
+    elf_validation: ValidationMode,
+    /// Per-program exceptions to `elf_validation`.
+    elf_validation_overrides: HashMap<Pubkey, ValidationMode>,
+    /// If true, per-account failures are recorded in `errors` instead of returned.
+    lenient: bool,
+    errors: Vec<RecordedError>,
+    /// Non-fatal issues, see [`RpcAccountStore::warnings`].
+    warnings: Vec<Warning>,
+    on_warning: Option<WarningCallback>,
+    /// If true, fetches check mocked accounts against the cluster.
+    strict_mocks: bool,
+    /// If true, shadowed mocks fail the fetch instead of warning.
+    deny_shadowed_mocks: bool,
+    /// Mocked pubkeys already checked against the cluster.
+    checked_mocks: HashSet<Pubkey>,
+    /// If true, fetched accounts below the rent-exempt minimum are topped up.
+    auto_top_up_rent: bool,
+    /// If true, fetching a BPF Loader v3 program fetches its program data too.
+    prefetch_program_data: bool,
+    /// Background fetches started by [`RpcAccountStore::warm_cache`].
+    warmups: warmup::Warmups,
+    /// Names printed for pubkeys, see [`RpcAccountStore::label`].
+    labels: HashMap<Pubkey, String>,
+    /// Programs `add_programs` replaces even if Mollusk already has them.
+    force_reload: HashSet<Pubkey>,
+    /// ELFs `add_programs` loads instead of the chain's, see
+    /// [`RpcAccountStore::with_program_from_file`].
+    local_programs: HashMap<Pubkey, PathBuf>,
+    /// How [`RpcAccountStore::snapshot_json`] shows account data.
+    snapshot_options: SnapshotOptions,
+    /// Programs `add_programs` loaded or found loaded, see
+    /// [`RpcAccountStore::require_programs`].
+    loaded_programs: HashSet<Pubkey>,
+    /// Results written back by `apply_result`, numbering them in provenance.
+    results_applied: usize,
+    /// Optional callback invoked after every fetched chunk.
+    progress: Option<ProgressCallback>,
+    /// Maximum number of `getMultipleAccounts` chunks in flight at once.
+    concurrency: usize,
+    /// Optional client-side limit on requests per second.
+    rate_limiter: Option<Arc<RateLimiter>>,
+    /// RPC usage counters, see [`RpcAccountStore::stats`]. Shared with the HTTP
+    /// client so it can record rate-limit waits.
+    stats: Arc<Mutex<RpcStats>>,
+    /// If true, every RPC request fails with [`RpcError::Offline`].
+    offline: bool,
+    /// Maximum number of RPC requests, see [`RpcAccountStore::with_rpc_budget`].
+    rpc_budget: Option<u64>,
+    /// See [`RpcAccountStore::with_program_accounts_limit`].
+    program_accounts_limit: usize,
+    /// Set once the endpoint turned out not to paginate program accounts, so later
+    /// queries go straight to `getProgramAccounts`.
+    pagination_unsupported: AtomicBool,
+    /// See [`RpcAccountStore::with_referenced_accounts_limit`].
+    referenced_accounts_limit: usize,
+    /// Pubkeys `with_referenced_accounts` never follows, see
+    /// [`RpcAccountStore::with_reference_exclusions`].
+    reference_exclusions: HashSet<Pubkey>,
+    /// Cluster to verify before the first request, see [`RpcAccountStore::expect_cluster`].
+    expected_cluster: Option<Cluster>,
+    /// The cluster picked with a shortcut constructor such as `mainnet()`.
+    cluster: Option<&'static str>,
+    /// Set once the genesis hash has been checked against `expected_cluster`.
+    cluster_verified: tokio::sync::OnceCell<()>,
+    /// The cluster slot, fetched once for all sysvar syncing.
+    synced_slot: Option<u64>,
+    /// The cluster's rent parameters, see [`RpcAccountStore::with_synced_rent`].
+    rent: Option<solana_rent::Rent>,
+    /// Gates forced on or off by `with_synced_features`.
+    feature_overrides: Vec<(Pubkey, bool)>,
+    /// Fetch sysvar metas instead of building them from Mollusk's sysvars.
+    real_sysvars: bool,
+    /// Directory that fetched accounts are recorded to and replayed from.
+    fixture_dir: Option<PathBuf>,
+    /// WebSocket subscriptions keeping selected accounts fresh.
+    #[cfg(feature = "pubsub")]
+    subscriptions: pubsub::Subscriptions,
+    /// If true, every fetch is summarized in a `fetch` event.
+    #[cfg(feature = "tracing")]
+    log_fetches: bool,
 }
 
 impl fmt::Debug for RpcAccountStore {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        f.debug_struct("RpcAccountStore")
+        let mut debug = f.debug_struct("RpcAccountStore");
+        debug
             .field("accounts_cached", &self.cache.len())
+            .field(
+                "custom_headers",
+                &self.endpoint.as_ref().map_or(0, |endpoint| endpoint.headers.len()),
+            )
             .field("allow_missing_accounts", &self.allow_missing_accounts)
-            .field("validate_programs", &self.validate_programs)
-            .finish_non_exhaustive()
+            .field("max_account_bytes", &self.max_account_bytes)
+            .field("check_program_data_size", &self.check_program_data_size)
+            .field("redact_urls", &self.redact_urls)
+            .field("routed_endpoints", &self.routed_endpoints.len())
+            .field("routes", &self.routes.len())
+            .field("commitment_fallback", &self.commitment_fallback)
+            .field("elf_validation", &self.elf_validation)
+            .field("elf_validation_overrides", &self.elf_validation_overrides)
+            .field("lenient", &self.lenient)
+            .field("errors", &self.errors.len())
+            .field("warnings", &self.warnings.len())
+            .field("on_warning", &self.on_warning.is_some())
+            .field("strict_mocks", &self.strict_mocks)
+            .field("deny_shadowed_mocks", &self.deny_shadowed_mocks)
+            .field("auto_top_up_rent", &self.auto_top_up_rent)
+            .field("prefetch_program_data", &self.prefetch_program_data)
+            .field("warmups", &self.warmups.len())
+            .field("labels", &self.labels.len())
+            .field("force_reload", &self.force_reload.len())
+            .field("local_programs", &self.local_programs)
+            .field("snapshot_options", &self.snapshot_options)
+            .field("loaded_programs", &self.loaded_programs.len())
+            .field("results_applied", &self.results_applied)
+            .field("progress", &self.progress.is_some())
+            .field("offline", &self.offline)
+            .field("rpc_budget", &self.rpc_budget)
+            .field("program_accounts_limit", &self.program_accounts_limit)
+            .field(
+                "pagination_unsupported",
+                &self.pagination_unsupported.load(Ordering::Relaxed),
+            )
+            .field("referenced_accounts_limit", &self.referenced_accounts_limit)
+            .field(
+                "memory_warning_threshold",
+                &self.memory_watch.map(|watch| watch.threshold),
+            )
+            .field("reference_exclusions", &self.reference_exclusions.len())
+            .field("cluster", &self.cluster)
+            .field("expected_cluster", &self.expected_cluster)
+            .field("feature_overrides", &self.feature_overrides.len())
+            .field("real_sysvars", &self.real_sysvars)
+            .field("fixture_dir", &self.fixture_dir)
+            .field("concurrency", &self.concurrency)
+            .field(
+                "rate_limit",
+                &self.rate_limiter.as_ref().map(|limiter| limiter.requests_per_second()),
+            );
+        #[cfg(feature = "pubsub")]
+        debug.field("subscriptions", &self.subscriptions.active());
+        #[cfg(feature = "tracing")]
+        debug.field("log_fetches", &self.log_fetches);
+        debug.finish_non_exhaustive()
     }
 }
 
@@ -208,6 +1158,47 @@ impl RpcAccountStore {
         Self::new_with_commitment(rpc_url, CommitmentConfig::confirmed())
     }
 
+    /// Create a new account fetcher for mainnet-beta.
+    ///
+    /// Uses `MOLLUSK_MAINNET_URL` if set, so a team can point every test at a
+    /// private provider, and [`MAINNET_RPC_URL`] otherwise. The endpoint's genesis
+    /// hash is checked like with [`expect_cluster`](Self::expect_cluster), which costs
+    /// one request before the first fetch.
+    pub fn mainnet() -> Self {
+        Self::for_cluster("mainnet-beta", "MOLLUSK_MAINNET_URL", MAINNET_RPC_URL)
+            .expect_cluster(Cluster::Mainnet)
+    }
+
+    /// Like [`mainnet`](Self::mainnet), for devnet: reads `MOLLUSK_DEVNET_URL`, falling
+    /// back to [`DEVNET_RPC_URL`].
+    pub fn devnet() -> Self {
+        Self::for_cluster("devnet", "MOLLUSK_DEVNET_URL", DEVNET_RPC_URL)
+            .expect_cluster(Cluster::Devnet)
+    }
+
+    /// Like [`mainnet`](Self::mainnet), for testnet: reads `MOLLUSK_TESTNET_URL`,
+    /// falling back to [`TESTNET_RPC_URL`].
+    pub fn testnet() -> Self {
+        Self::for_cluster("testnet", "MOLLUSK_TESTNET_URL", TESTNET_RPC_URL)
+            .expect_cluster(Cluster::Testnet)
+    }
+
+    /// Create a new account fetcher for a local validator: reads `MOLLUSK_LOCALNET_URL`,
+    /// falling back to [`LOCALNET_RPC_URL`].
+    ///
+    /// Local validators have no fixed genesis hash, so it isn't checked.
+    pub fn localnet() -> Self {
+        Self::for_cluster("localnet", "MOLLUSK_LOCALNET_URL", LOCALNET_RPC_URL)
+    }
+
+    /// A store for the endpoint in `env`, or `default_url` if it's unset.
+    fn for_cluster(name: &'static str, env: &str, default_url: &str) -> Self {
+        let url = env_var(env).unwrap_or_else(|| default_url.to_string());
+        let mut store = Self::new(url);
+        store.cluster = Some(name);
+        store
+    }
+
     /// Create a new account fetcher with a specific commitment level.
     ///
     /// By default:
@@ -217,12 +1208,182 @@ impl RpcAccountStore {
         rpc_url: impl Into<String>,
         commitment: CommitmentConfig,
     ) -> Self {
+        let endpoint = RpcEndpoint::new(rpc_url.into(), commitment);
+        let stats = Arc::<Mutex<RpcStats>>::default();
+        let mut store = Self::new_with_fetcher(endpoint.client(stats.clone()));
+        store.endpoint = Some(endpoint);
+        store.stats = stats;
+        store
+    }
+
+    /// Create a new account fetcher backed by a custom [`AccountFetcher`].
+    ///
+    /// Use this to serve accounts from somewhere other than a JSON-RPC endpoint,
+    /// e.g. [`MockFetcher`](testing::MockFetcher) in tests that must not touch the network.
+    pub fn new_with_fetcher(fetcher: impl AccountFetcher + 'static) -> Self {
         Self {
-            client: RpcClient::new_with_commitment(rpc_url.into(), commitment),
+            fetcher: Arc::new(fetcher),
+            endpoint: None,
+            routed_endpoints: Vec::new(),
+            routes: HashMap::new(),
+            slot_endpoint: None,
+            commitment_fallback: Vec::new(),
+            found_at_fallback: Arc::default(),
             cache: HashMap::new(),
+            provenance: HashMap::new(),
+            writable_accounts: HashSet::new(),
+            compute_budget: ComputeBudgetRequest::default(),
+            used_accounts: Mutex::default(),
+            elf_cache: Mutex::default(),
+            memory_watch: None,
             allow_missing_accounts: false,
-            validate_programs: true,
+            max_account_bytes: None,
+            check_program_data_size: false,
+            redact_urls: true,
+            elf_validation: ValidationMode::Strict,
+            elf_validation_overrides: HashMap::new(),
+            lenient: false,
+            errors: Vec::new(),
+            warnings: Vec::new(),
+            on_warning: None,
+            strict_mocks: false,
+            deny_shadowed_mocks: false,
+            checked_mocks: HashSet::new(),
+            auto_top_up_rent: false,
+            prefetch_program_data: false,
+            warmups: warmup::Warmups::default(),
+            labels: HashMap::new(),
+            force_reload: HashSet::new(),
+            local_programs: HashMap::new(),
+            snapshot_options: SnapshotOptions::default(),
+            loaded_programs: HashSet::new(),
+            results_applied: 0,
+            progress: None,
+            concurrency: 1,
+            rate_limiter: None,
+            stats: Arc::default(),
+            offline: false,
+            rpc_budget: None,
+            program_accounts_limit: program_accounts::DEFAULT_PROGRAM_ACCOUNTS_LIMIT,
+            pagination_unsupported: AtomicBool::new(false),
+            referenced_accounts_limit: references::DEFAULT_REFERENCED_ACCOUNTS_LIMIT,
+            reference_exclusions: HashSet::new(),
+            expected_cluster: None,
+            cluster: None,
+            cluster_verified: tokio::sync::OnceCell::new(),
+            synced_slot: None,
+            rent: None,
+            feature_overrides: Vec::new(),
+            real_sysvars: false,
+            fixture_dir: None,
+            #[cfg(feature = "pubsub")]
+            subscriptions: pubsub::Subscriptions::default(),
+            #[cfg(feature = "tracing")]
+            log_fetches: false,
+        }
+    }
+
+    /// Create a new account fetcher configured from environment variables.
+    ///
+    /// | Variable                 | Effect                                                  |
+    /// |--------------------------|---------------------------------------------------------|
+    /// | `MOLLUSK_RPC_URL`        | RPC endpoint (defaults to [`MAINNET_RPC_URL`])           |
+    /// | `MOLLUSK_RPC_COMMITMENT` | `processed`, `confirmed` (default) or `finalized`       |
+    /// | `MOLLUSK_FIXTURE_DIR`    | Same as [`with_fixture_dir`](Self::with_fixture_dir)   |
+    /// | `MOLLUSK_OFFLINE`        | `1`/`true`/`yes` enables [`offline`](Self::offline)     |
+    /// | `MOLLUSK_RATE_LIMIT`     | Same as [`with_rate_limit`](Self::with_rate_limit)     |
+    /// | `MOLLUSK_RPC_BUDGET`     | Same as [`with_rpc_budget`](Self::with_rpc_budget)     |
+    /// | `MOLLUSK_CONCURRENCY`    | Same as [`with_concurrency`](Self::with_concurrency)   |
+    ///
+    /// Unset or empty variables are ignored. The environment only provides the
+    /// starting configuration: builder methods called on the returned store take
+    /// precedence over it. To start from a config file instead of the defaults, use
+    /// [`RpcStoreConfig::from_file`] with [`from_config`](Self::from_config).
+    ///
+    /// # Errors
+    ///
+    /// Returns [`RpcError::InvalidEnvVar`] if a variable is set to a value that
+    /// can't be parsed (e.g. an unknown commitment level or a malformed URL).
+    pub fn from_env() -> Result<Self, RpcError> {
+        let config = RpcStoreConfig::default().with_env_overrides()?;
+        Ok(Self::from_config(config))
+    }
+
+    /// The RPC endpoint URL, or `None` for stores created with
+    /// [`new_with_fetcher`](Self::new_with_fetcher).
+    pub fn rpc_url(&self) -> Option<&str> {
+        self.endpoint.as_ref().map(|endpoint| endpoint.url.as_str())
+    }
+
+    /// The commitment level used for RPC requests, or `None` for stores created
+    /// with [`new_with_fetcher`](Self::new_with_fetcher).
+    pub fn commitment(&self) -> Option<CommitmentConfig> {
+        self.endpoint.as_ref().map(|endpoint| endpoint.commitment)
+    }
+
+    /// Send an extra HTTP header with every RPC request.
+    ///
+    /// Useful for providers that authenticate with e.g. `Authorization: Bearer ...`
+    /// instead of a token in the URL, or that need sticky-session headers. Header
+    /// values are never included in `Debug` output or error messages.
+    ///
+    /// Has no effect on stores created with [`new_with_fetcher`](Self::new_with_fetcher).
+    ///
+    /// # Errors
+    ///
+    /// Returns [`RpcError::InvalidHeader`] if the name or value is not a valid HTTP header.
+    pub fn with_header(mut self, name: &str, value: &str) -> Result<Self, RpcError> {
+        if let Some(endpoint) = &mut self.endpoint {
+            endpoint
+                .insert_header(name, value)
+                .map_err(|reason| RpcError::InvalidHeader {
+                    name: name.to_string(),
+                    reason,
+                })?;
+        }
+        Ok(self.rebuild_client())
+    }
+
+    /// Send several extra HTTP headers with every RPC request.
+    ///
+    /// See [`with_header`](Self::with_header).
+    pub fn with_headers<K, V>(
+        mut self,
+        headers: impl IntoIterator<Item = (K, V)>,
+    ) -> Result<Self, RpcError>
+    where
+        K: AsRef<str>,
+        V: AsRef<str>,
+    {
+        for (name, value) in headers {
+            self = self.with_header(name.as_ref(), value.as_ref())?;
+        }
+        Ok(self)
+    }
+
+    /// Cap how long a single `Retry-After` wait may last.
+    ///
+    /// When the provider answers `429 Too Many Requests`, the request is retried
+    /// after exactly the delay its `Retry-After` header asks for (seconds or an HTTP
+    /// date), but never longer than `max_wait`. Defaults to 60 seconds. Waits are
+    /// counted in [`RpcStats::rate_limit_waits`]. Server errors (5xx) are retried the
+    /// same way and only counted in [`RpcStats::retries`].
+    ///
+    /// Has no effect on stores created with [`new_with_fetcher`](Self::new_with_fetcher).
+    pub fn with_max_retry_after(mut self, max_wait: Duration) -> Self {
+        if let Some(endpoint) = &mut self.endpoint {
+            endpoint.max_retry_after = max_wait;
+        }
+        self.rebuild_client()
+    }
+
+    /// Replace the fetcher with a fresh client for the configured endpoint.
+    fn rebuild_client(mut self) -> Self {
+        if let Some(endpoint) = &self.endpoint {
+            self.fetcher = Arc::new(endpoint.client(self.stats.clone()));
         }
+        self.rebuild_routed_clients();
+        self
     }
 
     /// Allow missing accounts to be treated as default (empty) accounts.
@@ -234,12 +1395,195 @@ impl RpcAccountStore {
         self
     }
 
+    /// Record per-account failures instead of returning them.
+    ///
+    /// In lenient mode, fetches skip accounts that don't exist, and
+    /// [`add_programs`](Self::add_programs) skips programs that are malformed, lack
+    /// their program data or fail validation, while still loading the rest. Each
+    /// skipped item is recorded in [`errors`](Self::errors). Failures that affect a
+    /// whole request, such as network errors, are still returned. Fetches through a
+    /// [`SharedAccountStore`] stay strict.
+    pub fn lenient(mut self) -> Self {
+        self.lenient = true;
+        self
+    }
+
+    /// Failures recorded in [`lenient`](Self::lenient) mode, in the order they occurred.
+    pub fn errors(&self) -> &[RecordedError] {
+        &self.errors
+    }
+
+    /// Never make RPC requests.
+    ///
+    /// Accounts must come from the cache (e.g. [`with_accounts`](Self::with_accounts))
+    /// or the fixture directory. Any operation that would need the network fails with
+    /// [`RpcError::Offline`] instead.
+    pub fn offline(mut self) -> Self {
+        self.offline = true;
+        self
+    }
+
+    /// Fail once more than `max_requests` RPC requests would be sent.
+    ///
+    /// Every outgoing request counts, whatever its method; the request that would go
+    /// over the limit fails with [`RpcError::BudgetExceeded`] before anything is sent.
+    /// A budget of zero behaves like [`offline`](Self::offline), but with the
+    /// budget-specific error. Spending is tracked by [`stats`](Self::stats), so
+    /// [`reset_stats`](Self::reset_stats) also refills the budget.
+    pub fn with_rpc_budget(mut self, max_requests: u64) -> Self {
+        self.rpc_budget = Some(max_requests);
+        self
+    }
+
+    /// Make sure the RPC endpoint serves `cluster` before using it.
+    ///
+    /// The endpoint's `getGenesisHash` is compared against the cluster's once, right
+    /// before the store's first RPC request, which fails with
+    /// [`RpcError::WrongCluster`] if they differ. Offline stores and accounts replayed
+    /// from fixtures never trigger the check.
+    pub fn expect_cluster(mut self, cluster: Cluster) -> Self {
+        self.expected_cluster = Some(cluster);
+        self
+    }
+
+    /// Record fetched accounts to, and replay them from, a fixture directory.
+    ///
+    /// Before fetching, accounts are looked up as `<dir>/<pubkey>.json`; anything
+    /// fetched from RPC is written there afterwards. Files use the JSON layout of
+    /// `solana account --output json`, so they can also be passed to
+    /// `solana-test-validator --account`. Combine with [`offline`](Self::offline)
+    /// to guarantee tests only ever replay committed fixtures.
+    pub fn with_fixture_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.fixture_dir = Some(dir.into());
+        self
+    }
+
+    /// Fail fetches that return an account with more than `limit` bytes of data.
+    ///
+    /// A guard against mistakes such as a mistyped pubkey resolving to a huge
+    /// account: an oversized account fails the fetch with
+    /// [`RpcError::AccountTooLarge`] instead of being cached or written to a fixture.
+    /// Programs and program data, which are legitimately large, are exempt unless
+    /// [`check_program_data_size`](Self::check_program_data_size) is set.
+    pub fn with_max_account_bytes_strict(mut self, limit: usize) -> Self {
+        self.max_account_bytes = Some(limit);
+        self
+    }
+
+    /// Apply [`with_max_account_bytes_strict`](Self::with_max_account_bytes_strict)
+    /// to executable accounts and accounts owned by BPF Loader v3 as well.
+    pub fn check_program_data_size(mut self) -> Self {
+        self.check_program_data_size = true;
+        self
+    }
+
+    /// Fetch the program data of BPF Loader v3 programs as soon as a fetch returns
+    /// the programs, rather than in [`add_programs`](Self::add_programs).
+    ///
+    /// The program data addresses are only known once the programs are fetched, so
+    /// it takes a second `getMultipleAccounts` either way; with this, it's issued
+    /// right after the first, for all the programs of the fetch at once, and
+    /// `add_programs` usually finds everything cached and makes no request:
+    ///
+    /// ```rust,ignore
+    /// let store = RpcAccountStore::mainnet()
+    ///     .prefetch_program_data()
+    ///     .from_instruction(&swap_ix) // 2 requests: the accounts, then program data
+    ///     .await?
+    ///     .add_programs(&mut mollusk) // no request
+    ///     .await?;
+    /// ```
+    pub fn prefetch_program_data(mut self) -> Self {
+        self.prefetch_program_data = true;
+        self
+    }
+
     /// Skip ELF validation when adding programs to Mollusk.
     ///
     /// By default, program ELF headers are validated before adding to Mollusk.
     /// Use this to disable validation if you're confident in your program data.
-    pub fn skip_program_validation(mut self) -> Self {
-        self.validate_programs = false;
+    /// Shorthand for `elf_validation(ValidationMode::Off)`.
+    pub fn skip_program_validation(self) -> Self {
+        self.elf_validation(ValidationMode::Off)
+    }
+
+    /// How [`add_programs`](Self::add_programs) validates program ELF headers,
+    /// [`ValidationMode::Strict`] by default.
+    pub fn elf_validation(mut self, mode: ValidationMode) -> Self {
+        self.elf_validation = mode;
+        self
+    }
+
+    /// Validate the ELF of `program` with `mode` instead of the store-wide
+    /// [`elf_validation`](Self::elf_validation), e.g. to load one program with an
+    /// unusual layout while keeping the rest strict:
+    ///
+    /// ```rust,ignore
+    /// let store = RpcAccountStore::new(rpc_url)
+    ///     .elf_validation_for(legacy_program_id, ValidationMode::Warn)
+    ///     .from_instruction(&instruction)
+    ///     .await?
+    ///     .add_programs(&mut mollusk)
+    ///     .await?;
+    /// ```
+    pub fn elf_validation_for(mut self, program: Pubkey, mode: ValidationMode) -> Self {
+        self.elf_validation_overrides.insert(program, mode);
+        self
+    }
+
+    /// Load `programs` in [`add_programs`](Self::add_programs) even if Mollusk
+    /// already has them.
+    ///
+    /// By default, programs Mollusk already provides (its builtins, programs added
+    /// with e.g. `mollusk_svm_programs_token`, or by an earlier `add_programs`) are
+    /// left untouched. Use this to replace them with the cloned ELF.
+    pub fn force_reload(mut self, programs: &[Pubkey]) -> Self {
+        self.force_reload.extend(programs);
+        self
+    }
+
+    /// Issue up to `concurrency` `getMultipleAccounts` chunks at the same time.
+    ///
+    /// Defaults to 1 (sequential). Results are merged into the cache in chunk order
+    /// regardless of which request finishes first, and the first failing chunk aborts
+    /// the fetch. Concurrency never bypasses [`with_rate_limit`](Self::with_rate_limit).
+    pub fn with_concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+
+    /// Limit outgoing RPC requests to `requests_per_second`.
+    ///
+    /// Requests are spaced evenly, so this also holds when chunks are fetched
+    /// concurrently (see [`with_concurrency`](Self::with_concurrency)).
+    pub fn with_rate_limit(mut self, requests_per_second: u32) -> Self {
+        self.rate_limiter = Some(Arc::new(RateLimiter::new(requests_per_second)));
+        self
+    }
+
+    /// Summarize every fetch in an `INFO` event, to find out where a slow test spends
+    /// its time:
+    ///
+    /// ```text
+    /// requested 14 pubkeys: 9 cached, 0 from fixtures, 5 fetched in 1 batch (213ms)
+    /// ```
+    ///
+    /// The pubkeys fetched from RPC follow in a `DEBUG` event. This covers the program
+    /// data fetched by [`add_programs`](Self::add_programs) too, whose events are
+    /// emitted inside its span. Nothing is measured unless this is enabled.
+    #[cfg(feature = "tracing")]
+    pub fn log_fetches(mut self) -> Self {
+        self.log_fetches = true;
+        self
+    }
+
+    /// Report fetch progress through a callback.
+    ///
+    /// The callback is invoked after every `getMultipleAccounts` chunk with the running
+    /// totals for the current fetch, which makes it suitable for progress bars or CI
+    /// keep-alive output. It has no way to abort the fetch and should return quickly.
+    pub fn with_progress(mut self, progress: impl Fn(FetchProgress) + Send + Sync + 'static) -> Self {
+        self.progress = Some(Arc::new(progress));
         self
     }
 
@@ -247,12 +1591,27 @@ impl RpcAccountStore {
     ///
     /// Extracts all account pubkeys from the instruction's account metas
     /// and fetches them from the RPC endpoint using getMultipleAccounts.
+    ///
+    /// A ComputeBudget instruction has nothing to fetch; the limit and price it sets
+    /// are recorded instead, see [`compute_budget`](Self::compute_budget).
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(name = "from_instruction", level = "debug", skip_all, fields(pubkeys = instruction.accounts.len()))
+    )]
     pub async fn from_instruction(
         mut self,
         instruction: &Instruction,
     ) -> Result<Self, RpcError> {
+        let instructions = std::slice::from_ref(instruction);
+        self.track_writable(instructions);
+        self.track_compute_budget(instructions);
+        if compute_budget::is_compute_budget(instruction) {
+            return Ok(self);
+        }
         let pubkeys: Vec<_> = instruction.accounts.iter().map(|m| m.pubkey).collect();
-        self.fetch_accounts(&pubkeys).await?;
+        self.fetch_accounts(&pubkeys)
+            .await
+            .map_err(|e| locate_missing(&self, instructions, e))?;
         Ok(self)
     }
 
@@ -260,67 +1619,754 @@ impl RpcAccountStore {
     ///
     /// Collects all unique pubkeys across all instructions and fetches them
     /// efficiently in a batch using getMultipleAccounts.
+    ///
+    /// ComputeBudget instructions are skipped, and the limit and price they set are
+    /// recorded, so replayed transactions can run with their budget through
+    /// [`apply_compute_budget`](Self::apply_compute_budget).
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "from_instructions",
+            level = "debug",
+            skip_all,
+            fields(pubkeys = instructions.iter().map(|ix| ix.accounts.len()).sum::<usize>())
+        )
+    )]
     pub async fn from_instructions(
         mut self,
         instructions: &[Instruction],
     ) -> Result<Self, RpcError> {
         let pubkeys: HashSet<Pubkey> = instructions
             .iter()
+            .filter(|ix| !compute_budget::is_compute_budget(ix))
             .flat_map(|ix| ix.accounts.iter().map(|m| m.pubkey))
             .collect();
 
+        self.track_writable(instructions);
+        self.track_compute_budget(instructions);
         self.fetch_accounts(&pubkeys.into_iter().collect::<Vec<_>>())
-            .await?;
+            .await
+            .map_err(|e| locate_missing(&self, instructions, e))?;
         Ok(self)
     }
 
+    /// The cached account for `pubkey`, fetching it first if it isn't cached.
+    ///
+    /// Goes through the same path as the builder fetches:
+    /// - a cached account (mocked, fetched or written back) is returned without a
+    ///   request, even when [`offline`](Self::offline);
+    /// - otherwise the fixture directory is tried, then the RPC endpoint, and an
+    ///   offline store fails with [`RpcError::Offline`];
+    /// - an account missing on-chain is cached as a default account with
+    ///   [`allow_missing_accounts`](Self::allow_missing_accounts), and fails with
+    ///   [`RpcError::AccountNotFound`] otherwise (in [`lenient`](Self::lenient) mode
+    ///   it's also recorded in [`errors`](Self::errors)).
+    ///
+    /// Sysvars are fetched from the cluster like any other account.
+    ///
+    /// ```rust,ignore
+    /// let fee_vault = store.get_or_fetch(&fee_vault).await?;
+    /// println!("fee vault holds {} lamports", fee_vault.lamports);
+    /// ```
+    pub async fn get_or_fetch(&mut self, pubkey: &Pubkey) -> Result<&Account, RpcError> {
+        self.fetch_pubkeys(std::slice::from_ref(pubkey)).await?;
+        self.cache
+            .get(pubkey)
+            .ok_or(RpcError::AccountNotFound(*pubkey))
+    }
+
+    /// [`get_or_fetch`](Self::get_or_fetch) for several accounts, fetching the
+    /// uncached ones in one batch. Accounts are returned in the order of `pubkeys`.
+    ///
+    /// # Errors
+    ///
+    /// Fails with [`RpcError::AccountsNotFound`] listing every missing account if
+    /// more than one is missing.
+    pub async fn get_or_fetch_many(
+        &mut self,
+        pubkeys: &[Pubkey],
+    ) -> Result<Vec<&Account>, RpcError> {
+        let mut unique = HashSet::new();
+        let unique: Vec<Pubkey> = pubkeys
+            .iter()
+            .filter(|pubkey| unique.insert(**pubkey))
+            .copied()
+            .collect();
+        self.fetch_pubkeys(&unique).await?;
+
+        let missing: Vec<Pubkey> = unique
+            .into_iter()
+            .filter(|pubkey| !self.cache.contains_key(pubkey))
+            .collect();
+        match missing.len() {
+            0 => Ok(pubkeys.iter().map(|pubkey| &self.cache[pubkey]).collect()),
+            1 => Err(RpcError::AccountNotFound(missing[0])),
+            _ => Err(RpcError::AccountsNotFound(missing)),
+        }
+    }
+
     /// Add accounts to the store.
-    pub fn with_accounts(mut self, accounts: &[(Pubkey, Account)]) -> Self {
-        for (pubkey, account) in accounts {
-            self.cache.insert(*pubkey, account.clone());
+    ///
+    /// Takes owned `(Pubkey, A)` pairs for anything convertible into an [`Account`],
+    /// such as `AccountSharedData`, without cloning them, or a slice of
+    /// `(Pubkey, Account)` pairs, which are cloned. Seeding 1,000 165-byte accounts
+    /// from an owned `Vec` takes about 20% less time than from a slice (210 µs vs
+    /// 260 µs in a release build); `AccountSharedData` costs about as much as a slice,
+    /// since converting it copies the data.
+    ///
+    /// ```rust,ignore
+    /// let store = RpcAccountStore::new(rpc_url)
+    ///     .with_accounts(&[(mint, mint_account)])
+    ///     .with_accounts(shared_accounts); // Vec<(Pubkey, AccountSharedData)>
+    /// ```
+    pub fn with_accounts<I>(mut self, accounts: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: IntoAccountEntry,
+    {
+        for entry in accounts {
+            let (pubkey, account) = entry.into_account_entry();
+            self.insert_mock(pubkey, account, "with_accounts");
         }
         self
     }
 
+    /// Cache a mocked account for `method`, warning with
+    /// [`WarningCode::MockReplacesAccount`] if it replaces a cached one.
+    pub(crate) fn insert_mock(&mut self, pubkey: Pubkey, account: Account, method: &str) {
+        if self.cache.contains_key(&pubkey) {
+            let replaced = summary::provenance_label(self.provenance.get(&pubkey));
+            self.warn(
+                WarningCode::MockReplacesAccount,
+                format!("{method} replaced a {replaced} account"),
+                Some(pubkey),
+            );
+        }
+        self.insert_account(pubkey, account, Provenance::Mocked);
+    }
+
+    /// Write an instruction's resulting accounts back into the cache.
+    ///
+    /// Use this to chain scenarios: after running instruction A through Mollusk,
+    /// applying its result makes a later [`instruction_accounts`](Self::instruction_accounts)
+    /// for instruction B see A's state changes, including accounts A created.
+    /// Programs and sysvars are left alone, and nothing is applied if the
+    /// instruction failed, matching what the runtime would commit.
+    ///
+    /// Returns the number of accounts written.
+    pub fn apply_result(&mut self, result: &InstructionResult) -> usize {
+        if result.program_result.is_err() {
+            return 0;
+        }
+
+        let mut applied = 0;
+        for (pubkey, account) in &result.resulting_accounts {
+            if account.executable || account.owner == sysvar::ID {
+                continue;
+            }
+            let provenance = Provenance::ResultApplied {
+                instruction_index: Some(self.results_applied),
+            };
+            self.insert_account(*pubkey, account.clone(), provenance);
+            applied += 1;
+        }
+        self.results_applied += 1;
+        applied
+    }
+
+    /// The accounts for `mollusk.process_instruction(instruction, &accounts)`.
+    ///
+    /// Returns one entry per account meta, in the order of `instruction.accounts`
+    /// (duplicate metas included), cloned from the cache. Sysvar metas get accounts
+    /// built from `mollusk`'s current sysvars instead, unless the store
+    /// [fetches real sysvars](Self::fetch_real_sysvars).
+    ///
+    /// # Errors
+    ///
+    /// Returns [`RpcError::AccountNotCached`] with the position of the first meta whose
+    /// account hasn't been fetched or added.
+    pub fn instruction_accounts(
+        &self,
+        mollusk: &Mollusk,
+        instruction: &Instruction,
+    ) -> Result<Vec<(Pubkey, Account)>, RpcError> {
+        let instructions = std::slice::from_ref(instruction);
+        instruction
+            .accounts
+            .iter()
+            .enumerate()
+            .map(|(index, meta)| self.meta_account(mollusk, instructions, &meta.pubkey, 0, index))
+            .collect()
+    }
+
+    /// The accounts for `mollusk.process_instruction_chain(instructions, &accounts)`.
+    ///
+    /// Returns every account used by the chain once, in order of first appearance.
+    /// Sysvars are handled like in [`instruction_accounts`](Self::instruction_accounts).
+    ///
+    /// # Errors
+    ///
+    /// Returns [`RpcError::AccountNotCached`] with the instruction and meta index where
+    /// the first missing account appears.
+    pub fn instructions_accounts(
+        &self,
+        mollusk: &Mollusk,
+        instructions: &[Instruction],
+    ) -> Result<Vec<(Pubkey, Account)>, RpcError> {
+        let mut seen = HashSet::new();
+        let mut accounts = Vec::new();
+        for (instruction, ix) in instructions.iter().enumerate() {
+            for (index, meta) in ix.accounts.iter().enumerate() {
+                if seen.insert(meta.pubkey) {
+                    accounts.push(self.meta_account(
+                        mollusk,
+                        instructions,
+                        &meta.pubkey,
+                        instruction,
+                        index,
+                    )?);
+                }
+            }
+        }
+        Ok(accounts)
+    }
+
+    fn meta_account(
+        &self,
+        mollusk: &Mollusk,
+        instructions: &[Instruction],
+        pubkey: &Pubkey,
+        instruction: usize,
+        index: usize,
+    ) -> Result<(Pubkey, Account), RpcError> {
+        if self.synthesizes_sysvar(pubkey) {
+            if let Some(account) = sysvars::synthesized_sysvar(mollusk, instructions, pubkey) {
+                return Ok((*pubkey, account));
+            }
+        }
+        let account = self.cache.get(pubkey).ok_or(RpcError::AccountNotCached {
+            pubkey: *pubkey,
+            instruction,
+            index,
+        })?;
+        self.mark_used(pubkey);
+        Ok((*pubkey, account.clone()))
+    }
+
+    /// RPC usage recorded since the store was created (or since the last
+    /// [`reset_stats`](Self::reset_stats)).
+    pub fn stats(&self) -> RpcStats {
+        self.stats_mut().clone()
+    }
+
+    /// Clear the RPC usage counters, e.g. between scenarios that reuse one store.
+    pub fn reset_stats(&self) {
+        *self.stats_mut() = RpcStats::default();
+    }
+
+    fn stats_mut(&self) -> MutexGuard<'_, RpcStats> {
+        self.stats.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Prepare an outgoing RPC request: verifies the cluster, checks the budget,
+    /// records the request in the stats and waits for the rate limiter.
+    async fn begin_request(&self, method: &'static str) -> Result<(), RpcError> {
+        if self.offline {
+            return Err(RpcError::Offline { method });
+        }
+        if let Some(expected) = self.expected_cluster {
+            self.cluster_verified
+                .get_or_try_init(|| async {
+                    self.reserve_request("getGenesisHash").await?;
+                    let genesis_hash = self
+                        .fetcher
+                        .get_genesis_hash()
+                        .await
+                        .map_err(|e| self.client_error(e))?;
+                    if genesis_hash != expected.genesis_hash() {
+                        return Err(RpcError::WrongCluster {
+                            expected,
+                            actual: Cluster::from_genesis_hash(genesis_hash),
+                        });
+                    }
+                    Ok(())
+                })
+                .await?;
+        }
+        self.reserve_request(method).await
+    }
+
+    /// Account for a request in the budget and stats and wait for the rate limiter.
+    async fn reserve_request(&self, method: &'static str) -> Result<(), RpcError> {
+        {
+            // Check and record under one lock so concurrent chunks can't overspend.
+            let mut stats = self.stats_mut();
+            if let Some(limit) = self.rpc_budget {
+                if stats.total_requests() >= limit {
+                    return Err(RpcError::BudgetExceeded {
+                        limit,
+                        attempted_method: method,
+                        spent: stats.clone(),
+                    });
+                }
+            }
+            stats.record_request(method);
+        }
+        if let Some(rate_limiter) = &self.rate_limiter {
+            rate_limiter.acquire().await;
+        }
+        Ok(())
+    }
+
     /// Internal method to fetch accounts from RPC using `getMultipleAccounts`.
     ///
     /// Only fetches accounts that aren't already in the cache, allowing for
-    /// efficient incremental fetching.
+    /// efficient incremental fetching. Requests are split into chunks of
+    /// `MAX_MULTIPLE_ACCOUNTS`, the limit enforced by RPC nodes. Sysvars that
+    /// `instruction_accounts` builds from Mollusk are skipped.
     async fn fetch_accounts(&mut self, pubkeys: &[Pubkey]) -> Result<(), RpcError> {
-        // Filter out already cached accounts
-        let missing_pubkeys: Vec<Pubkey> = pubkeys
+        let pubkeys: Vec<Pubkey> = pubkeys
             .iter()
-            .filter(|pubkey| !self.cache.contains_key(pubkey))
+            .filter(|pubkey| !self.synthesizes_sysvar(pubkey))
             .copied()
             .collect();
+        self.fetch_pubkeys(&pubkeys).await?;
+        if self.prefetch_program_data {
+            self.prefetch_program_data_of(&pubkeys).await?;
+        }
+        Ok(())
+    }
 
-        if missing_pubkeys.is_empty() {
+    /// Cache the program data of the BPF Loader v3 programs among `pubkeys`, so
+    /// [`add_programs`](Self::add_programs) finds it without a request of its own.
+    ///
+    /// Missing program data and malformed programs are left for `add_programs` to
+    /// report.
+    async fn prefetch_program_data_of(&mut self, pubkeys: &[Pubkey]) -> Result<(), RpcError> {
+        let program_data_pubkeys: Vec<Pubkey> = pubkeys
+            .iter()
+            .filter(|pubkey| !self.local_programs.contains_key(pubkey))
+            .filter_map(|pubkey| Some((pubkey, self.cache.get(pubkey)?)))
+            .filter(|(_, account)| {
+                account.executable && account.owner == mollusk_svm::program::loader_keys::LOADER_V3
+            })
+            .filter_map(|(pubkey, account)| program_data_address(pubkey, account).ok())
+            .filter(|program_data| !self.cache.contains_key(program_data))
+            .collect();
+        if program_data_pubkeys.is_empty() {
             return Ok(());
         }
+        self.fetch_existing(&program_data_pubkeys).await
+    }
+
+    /// [`fetch_accounts`](Self::fetch_accounts) without setting sysvars aside.
+    async fn fetch_pubkeys(&mut self, pubkeys: &[Pubkey]) -> Result<(), RpcError> {
+        #[cfg(feature = "pubsub")]
+        self.sync_subscriptions();
+        self.join_warmups_of(pubkeys).await?;
 
-        let accounts = self.client.get_multiple_accounts(&missing_pubkeys).await?;
+        #[cfg(feature = "tracing")]
+        let started = self.log_fetches.then(std::time::Instant::now);
 
-        // Store fetched accounts in cache
-        for (pubkey, account_opt) in missing_pubkeys.iter().zip(accounts) {
-            match account_opt {
-                Some(account) => {
-                    self.cache.insert(*pubkey, account);
+        // Filter out already cached accounts, except fallbacks the chain may replace
+        let mut missing_pubkeys: Vec<Pubkey> = pubkeys
+            .iter()
+            .filter(|pubkey| !self.cache.contains_key(pubkey) || self.is_fallback(pubkey))
+            .copied()
+            .collect();
+
+        let cache_hits = pubkeys.len() - missing_pubkeys.len();
+        self.stats_mut().accounts_from_cache += cache_hits as u64;
+
+        // Accounts this fetch caches, checked for rent exemption at the end
+        let mut received = Vec::new();
+
+        // Replay whatever the fixture directory has before going to the network
+        if let Some(dir) = self.fixture_dir.clone() {
+            let mut remaining = Vec::with_capacity(missing_pubkeys.len());
+            for pubkey in missing_pubkeys {
+                match self.read_fixture(&pubkey)? {
+                    Some(account) => {
+                        let path = fixture::account_path(&dir, &pubkey);
+                        self.insert_account(pubkey, account, Provenance::Fixture { path });
+                        received.push(pubkey);
+                    }
+                    None => remaining.push(pubkey),
                 }
-                None => {
-                    if self.allow_missing_accounts {
-                        // Create a default (empty) account for missing accounts
-                        self.cache.insert(*pubkey, Account::default());
-                    } else {
-                        // Return an error if the account doesn't exist
-                        return Err(RpcError::AccountNotFound(*pubkey));
+            }
+            self.stats_mut().accounts_from_fixtures += received.len() as u64;
+            missing_pubkeys = remaining;
+        }
+
+        #[cfg(feature = "tracing")]
+        tracing::debug!(
+            name: "cache_lookup",
+            requested = pubkeys.len(),
+            cache_hits,
+            fixtures = received.len(),
+            cache_misses = missing_pubkeys.len(),
+        );
+
+        if self.offline {
+            let fallbacks: Vec<Pubkey> = missing_pubkeys
+                .iter()
+                .filter(|pubkey| self.is_fallback(pubkey))
+                .copied()
+                .collect();
+            for pubkey in &fallbacks {
+                self.settle_fallback(pubkey);
+            }
+            missing_pubkeys.retain(|pubkey| !fallbacks.contains(pubkey));
+        }
+
+        let mocks = self.unchecked_mocks();
+        if missing_pubkeys.is_empty() && mocks.is_empty() {
+            self.check_rent_exemption(&received);
+            #[cfg(feature = "tracing")]
+            log_fetch(started, pubkeys.len(), cache_hits, &missing_pubkeys);
+            return Ok(());
+        }
+
+        // Store fetched accounts in cache, checking mocks in the same batches
+        let requested: Vec<Pubkey> = missing_pubkeys.iter().chain(&mocks).copied().collect();
+        let mut fetched = self.fetch_from_rpc(&requested).await?;
+        self.check_mocks(fetched.split_off(missing_pubkeys.len()))?;
+        self.cache_fetched(fetched, &mut received)?;
+        self.check_rent_exemption(&received);
+
+        #[cfg(feature = "tracing")]
+        log_fetch(started, pubkeys.len(), cache_hits, &missing_pubkeys);
+        Ok(())
+    }
+
+    /// Cache the results of `getMultipleAccounts`, adding their pubkeys to
+    /// `received`: fallbacks of missing accounts are kept, and missing accounts are
+    /// skipped, synthesized or reported as the store is configured to.
+    fn cache_fetched(
+        &mut self,
+        fetched: Vec<(Pubkey, Option<Account>)>,
+        received: &mut Vec<Pubkey>,
+    ) -> Result<(), RpcError> {
+        let fetched = self.keep_fallbacks(fetched);
+        let fetched = self.skip_missing(fetched);
+        let synthesized: HashSet<Pubkey> = fetched
+            .iter()
+            .filter(|(_, account)| account.is_none())
+            .map(|(pubkey, _)| *pubkey)
+            .collect();
+        for (pubkey, account) in self.resolve_all_fetched(fetched)? {
+            let provenance = if synthesized.contains(&pubkey) {
+                Provenance::Synthesized("missing on-chain, created by allow_missing_accounts")
+            } else {
+                self.fetched_provenance_of(&pubkey)
+            };
+            self.insert_account(pubkey, account, provenance);
+            received.push(pubkey);
+        }
+        Ok(())
+    }
+
+    /// [`fetch_pubkeys`](Self::fetch_pubkeys) for accounts that may not exist: the
+    /// ones that do are cached, the others skipped whatever
+    /// [`allow_missing_accounts`](Self::allow_missing_accounts) says. An offline
+    /// store only finds fixtures.
+    async fn fetch_existing(&mut self, pubkeys: &[Pubkey]) -> Result<(), RpcError> {
+        self.join_warmups_of(pubkeys).await?;
+        let mut uncached: Vec<Pubkey> = pubkeys
+            .iter()
+            .filter(|pubkey| !self.cache.contains_key(pubkey))
+            .copied()
+            .collect();
+
+        if let Some(dir) = self.fixture_dir.clone() {
+            let mut remaining = Vec::with_capacity(uncached.len());
+            for pubkey in uncached {
+                match self.read_fixture(&pubkey)? {
+                    Some(account) => {
+                        let path = fixture::account_path(&dir, &pubkey);
+                        self.insert_account(pubkey, account, Provenance::Fixture { path });
+                        self.stats_mut().accounts_from_fixtures += 1;
                     }
+                    None => remaining.push(pubkey),
                 }
             }
+            uncached = remaining;
         }
+        if self.offline || uncached.is_empty() {
+            return Ok(());
+        }
+        for (pubkey, account) in self.fetch_from_rpc(&uncached).await? {
+            let Some(account) = account else {
+                continue;
+            };
+            let account = self.resolve_fetched(&pubkey, Some(account))?;
+            let provenance = self.fetched_provenance_of(&pubkey);
+            self.insert_account(pubkey, account, provenance);
+        }
+        Ok(())
+    }
+
+    /// Where the cached account `pubkey` came from, or `None` if it isn't cached or
+    /// was inserted into [`cache`](Self::cache) directly.
+    ///
+    /// The store's summary and [`diff_result`](Self::diff_result) reports print the
+    /// same:
+    ///
+    /// ```rust,ignore
+    /// let store = store.from_instruction(&ix).await?;
+    /// assert!(matches!(store.provenance(&pool), Some(Provenance::Fetched { .. })));
+    /// ```
+    pub fn provenance(&self, pubkey: &Pubkey) -> Option<&Provenance> {
+        if !self.cache.contains_key(pubkey) {
+            return None;
+        }
+        self.provenance.get(pubkey)
+    }
+
+    /// [`Provenance::Fetched`] for an account fetched now from the store's endpoint.
+    pub(crate) fn fetched_provenance(&self) -> Provenance {
+        Provenance::Fetched {
+            slot: self.synced_slot,
+            at: SystemTime::now(),
+            endpoint: None,
+            commitment: None,
+        }
+    }
+
+    /// [`Provenance::Fetched`] for `pubkey`, fetched now from the endpoint it's
+    /// [routed](Self::with_endpoint_for) to.
+    pub(crate) fn fetched_provenance_of(&self, pubkey: &Pubkey) -> Provenance {
+        Provenance::Fetched {
+            slot: self.synced_slot,
+            at: SystemTime::now(),
+            endpoint: self.routed_endpoint_name(pubkey),
+            commitment: self.found_at_fallback().remove(pubkey),
+        }
+    }
+
+    /// Cache `account`, remembering where it came from.
+    fn insert_account(&mut self, pubkey: Pubkey, account: Account, provenance: Provenance) {
+        if self.is_overridden(&pubkey, &provenance) {
+            return;
+        }
+        let added = account.data.len();
+        let replaced = self.cache.insert(pubkey, account);
+        self.provenance.insert(pubkey, provenance);
+        self.track_memory(replaced.map_or(0, |account| account.data.len()), added);
+    }
+
+    /// Load the fixture for `pubkey`, if a fixture directory is configured and has one.
+    fn read_fixture(&self, pubkey: &Pubkey) -> Result<Option<Account>, RpcError> {
+        let Some(dir) = &self.fixture_dir else {
+            return Ok(None);
+        };
+        fixture::read_account(dir, pubkey).map_err(|reason| RpcError::Fixture {
+            path: fixture::account_path(dir, pubkey),
+            reason,
+        })
+    }
+
+    /// Fetch `pubkeys` with `getMultipleAccounts`, without touching the cache.
+    ///
+    /// Returns the results in the order of `pubkeys`, `None` for accounts that
+    /// don't exist.
+    async fn fetch_from_rpc(
+        &self,
+        pubkeys: &[Pubkey],
+    ) -> Result<Vec<(Pubkey, Option<Account>)>, RpcError> {
+        let total = pubkeys.len();
+        let mut fetched = 0;
+        let mut bytes_downloaded = 0;
+
+        // Chunks are requested concurrently (up to `self.concurrency` at a time) and
+        // complete in any order, so results are buffered and merged in chunk order.
+        // The futures are collected up front so no closure ends up in this future's
+        // state, which would keep it from being `Send`. Pubkeys routed to other
+        // endpoints are chunked separately, see `with_endpoint_for`.
+        let routes = self.routes_of(pubkeys);
+        let requests: Vec<_> = routes
+            .iter()
+            .flat_map(|route| {
+                let fetcher = self.route_fetcher(route.endpoint);
+                route.pubkeys.chunks(MAX_MULTIPLE_ACCOUNTS).map(move |chunk| (fetcher, chunk))
+            })
+            .enumerate()
+            .map(|(index, (fetcher, chunk))| self.fetch_chunk(index, fetcher, chunk))
+            .collect();
+        let mut requests = stream::iter(requests).buffer_unordered(self.concurrency);
+
+        let mut batches = Vec::new();
+        while let Some(batch) = requests.next().await {
+            let (current_batch, chunk, accounts) = batch?;
+
+            let chunk_bytes = accounts
+                .iter()
+                .flatten()
+                .map(|account| account.data.len())
+                .sum::<usize>();
+            {
+                let mut stats = self.stats_mut();
+                stats.accounts_fetched += chunk.len() as u64;
+                stats.bytes_downloaded += chunk_bytes as u64;
+            }
+
+            fetched += chunk.len();
+            bytes_downloaded += chunk_bytes;
+            if let Some(progress) = &self.progress {
+                progress(FetchProgress {
+                    fetched,
+                    total,
+                    current_batch,
+                    bytes_downloaded,
+                });
+            }
 
+            batches.push((current_batch, chunk, accounts));
+        }
+        drop(requests);
+        batches.sort_unstable_by_key(|(index, _, _)| *index);
+
+        let results = batches
+            .into_iter()
+            .flat_map(|(_, chunk, accounts)| chunk.iter().copied().zip(accounts));
+        let mut results: Vec<_> = if routes.len() == 1 {
+            results.collect()
+        } else {
+            // Back from the order of the routes to the order of `pubkeys`
+            let mut results: Vec<_> = routes
+                .iter()
+                .flat_map(|route| route.positions.iter().copied())
+                .zip(results)
+                .collect();
+            results.sort_unstable_by_key(|(position, _)| *position);
+            results.into_iter().map(|(_, result)| result).collect()
+        };
+        if !self.commitment_fallback.is_empty() {
+            self.fetch_missing_at_fallbacks(&mut results).await?;
+        }
+        Ok(results)
+    }
+
+    /// Fetch a single `getMultipleAccounts` chunk from `fetcher`, tagged with its
+    /// index.
+    async fn fetch_chunk<'a>(
+        &self,
+        index: usize,
+        fetcher: &Arc<dyn AccountFetcher>,
+        chunk: &'a [Pubkey],
+    ) -> Result<(usize, &'a [Pubkey], Vec<Option<Account>>), RpcError> {
+        self.begin_request("getMultipleAccounts").await?;
+
+        #[cfg(feature = "tracing")]
+        let started = std::time::Instant::now();
+
+        let accounts = fetcher
+            .get_multiple_accounts(chunk)
+            .await
+            .map_err(|e| self.client_error(e))?;
+
+        #[cfg(feature = "tracing")]
+        tracing::debug!(
+            name: "rpc_request",
+            method = "getMultipleAccounts",
+            chunk_size = chunk.len(),
+            latency_ms = started.elapsed().as_millis() as u64,
+        );
+
+        Ok((index, chunk, accounts))
+    }
+
+    /// Turn a fetch result into the account to cache: records the fixture for
+    /// accounts that exist and applies `allow_missing_accounts` to ones that don't.
+    fn resolve_fetched(
+        &self,
+        pubkey: &Pubkey,
+        account: Option<Account>,
+    ) -> Result<Account, RpcError> {
+        match account {
+            Some(account) => {
+                self.check_account_size(pubkey, &account)?;
+                if let Some(dir) = &self.fixture_dir {
+                    fixture::write_account(dir, pubkey, &account).map_err(|e| {
+                        RpcError::Fixture {
+                            path: fixture::account_path(dir, pubkey),
+                            reason: e.to_string(),
+                        }
+                    })?;
+                }
+                Ok(account)
+            }
+            // Create a default (empty) account for missing accounts
+            None if self.allow_missing_accounts => Ok(Account::default()),
+            // Return an error if the account doesn't exist
+            None => Err(RpcError::AccountNotFound(*pubkey)),
+        }
+    }
+
+    /// Enforce [`with_max_account_bytes_strict`](Self::with_max_account_bytes_strict).
+    fn check_account_size(&self, pubkey: &Pubkey, account: &Account) -> Result<(), RpcError> {
+        let Some(limit) = self.max_account_bytes else {
+            return Ok(());
+        };
+        let is_program = account.executable
+            || account.owner == mollusk_svm::program::loader_keys::LOADER_V3;
+        if account.data.len() > limit && (!is_program || self.check_program_data_size) {
+            return Err(RpcError::AccountTooLarge {
+                pubkey: *pubkey,
+                size: account.data.len(),
+                limit,
+            });
+        }
         Ok(())
     }
 
+    /// [`resolve_fetched`](Self::resolve_fetched) for a whole fetch, reporting every
+    /// missing account at once: [`RpcError::AccountNotFound`] if a single one is
+    /// missing, [`RpcError::AccountsNotFound`] if several are.
+    fn resolve_all_fetched(
+        &self,
+        fetched: Vec<(Pubkey, Option<Account>)>,
+    ) -> Result<Vec<(Pubkey, Account)>, RpcError> {
+        let mut accounts = Vec::with_capacity(fetched.len());
+        let mut not_found = Vec::new();
+        for (pubkey, account) in fetched {
+            match self.resolve_fetched(&pubkey, account) {
+                Ok(account) => accounts.push((pubkey, account)),
+                Err(RpcError::AccountNotFound(pubkey)) => not_found.push(pubkey),
+                Err(e) => return Err(e),
+            }
+        }
+
+        match not_found.len() {
+            0 => Ok(accounts),
+            1 => Err(RpcError::AccountNotFound(not_found[0])),
+            _ => Err(RpcError::AccountsNotFound(not_found)),
+        }
+    }
+
+    /// In lenient mode, record accounts that don't exist and drop them from `fetched`.
+    fn skip_missing(
+        &mut self,
+        fetched: Vec<(Pubkey, Option<Account>)>,
+    ) -> Vec<(Pubkey, Option<Account>)> {
+        if !self.lenient || self.allow_missing_accounts {
+            return fetched;
+        }
+        let errors = &mut self.errors;
+        fetched
+            .into_iter()
+            .filter(|(pubkey, account)| {
+                if account.is_none() {
+                    errors.push(RecordedError {
+                        pubkey: *pubkey,
+                        error: RpcError::AccountNotFound(*pubkey),
+                    });
+                }
+                account.is_some()
+            })
+            .collect()
+    }
+
     /// Add programs to the Mollusk environment.
     ///
     /// This function fetches the program data accounts for all programs that are
@@ -328,6 +2374,10 @@ impl RpcAccountStore {
     ///
     /// Note: This is needed because mollusk-svm doesn't load the programs for CPIs directly from the accounts.
     ///
+    /// Programs Mollusk already has are skipped, unless listed in
+    /// [`force_reload`](Self::force_reload). In [`lenient`](Self::lenient) mode, programs
+    /// that can't be loaded are skipped too and recorded in [`errors`](Self::errors).
+    ///
     /// # Errors
     ///
     /// Returns an error if:
@@ -335,132 +2385,414 @@ impl RpcAccountStore {
     /// - Program data account is invalid or missing
     /// - ELF validation fails (if enabled)
     pub async fn add_programs(mut self, mollusk: &mut Mollusk) -> Result<Self, RpcError> {
+        self.load_programs(mollusk).await?;
+        Ok(self)
+    }
+
+    /// [`add_programs`](Self::add_programs) through `&mut self`.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(name = "add_programs", level = "debug", skip_all, fields(accounts_cached = self.cache.len()))
+    )]
+    async fn load_programs(&mut self, mollusk: &mut Mollusk) -> Result<(), RpcError> {
         // First pass: collect program data pubkeys that need to be fetched
         let mut program_data_pubkeys = Vec::new();
+        let mut programs = HashMap::new();
         for (pubkey, account) in self.cache.iter() {
-            if account.executable && account.owner == mollusk_svm::program::loader_keys::LOADER_V3 {
-                if account.data.len() < 36 {
-                    return Err(RpcError::MalformedProgram {
-                        program: *pubkey,
-                        reason: format!(
-                            "BPF Loader v3 program account too small: {} bytes (expected at least 36)",
-                            account.data.len()
-                        ),
-                    });
-                }
-
-                let program_data_pubkey = Pubkey::try_from(&account.data[4..36]).map_err(|e| {
-                    RpcError::MalformedProgram {
-                        program: *pubkey,
-                        reason: format!("Invalid program data pubkey: {}", e),
-                    }
-                })?;
+            if account.executable
+                && account.owner == mollusk_svm::program::loader_keys::LOADER_V3
+                && !self.local_programs.contains_key(pubkey)
+                && !self.keeps_loaded_program(mollusk, pubkey)
+            {
+                let program_data_pubkey = match program_data_address(pubkey, account) {
+                    Ok(program_data_pubkey) => program_data_pubkey,
+                    // Recorded by the second pass
+                    Err(_) if self.lenient => continue,
+                    Err(e) => return Err(e),
+                };
 
                 if !self.cache.contains_key(&program_data_pubkey) {
                     program_data_pubkeys.push(program_data_pubkey);
+                    programs.insert(program_data_pubkey, *pubkey);
                 }
             }
         }
 
         // Fetch all program data accounts at once
         if !program_data_pubkeys.is_empty() {
-            self.fetch_accounts(&program_data_pubkeys).await?;
+            self.fetch_accounts(&program_data_pubkeys)
+                .await
+                .map_err(|e| match e {
+                    RpcError::AccountNotFound(program_data) => RpcError::ProgramDataNotFound {
+                        program: programs[&program_data],
+                        program_data,
+                    },
+                    RpcError::AccountsNotFound(missing) => {
+                        let mut missing: Vec<(Pubkey, Pubkey)> = missing
+                            .into_iter()
+                            .map(|program_data| (programs[&program_data], program_data))
+                            .collect();
+                        missing.sort_unstable();
+                        RpcError::ProgramDataAccountsNotFound(missing)
+                    }
+                    e => e,
+                })?;
         }
 
         // Second pass: add programs to mollusk
+        let mut failures = Vec::new();
+        let mut warnings = Vec::new();
+        let mut loaded = Vec::new();
         for (pubkey, account) in self.cache.iter() {
-            if account.executable {
-                // For BPF Loader v2 programs, the ELF is directly in the account data
-                if account.owner == mollusk_svm::program::loader_keys::LOADER_V2 {
-                    if self.validate_programs {
-                        validate_elf(&account.data).map_err(|reason| {
-                            RpcError::InvalidProgramData {
-                                program: *pubkey,
-                                reason,
-                            }
-                        })?;
-                    }
-
-                    mollusk.add_program_with_elf_and_loader(
-                        pubkey,
-                        &account.data,
-                        &account.owner,
-                    );
+            // Loaded from disk below
+            if !account.executable || self.local_programs.contains_key(pubkey) {
+                continue;
+            }
+            if self.keeps_loaded_program(mollusk, pubkey) {
+                #[cfg(feature = "tracing")]
+                tracing::debug!(name: "program_skipped", program = %pubkey, loader = %account.owner);
+                loaded.push(*pubkey);
+                continue;
+            }
+            let checked = self.shared_elf(pubkey, account).and_then(|elf_data| {
+                elf_data
+                    .map(|elf_data| {
+                        let warning = self.check_program_elf(pubkey, &elf_data)?;
+                        Ok((elf_data, warning))
+                    })
+                    .transpose()
+            });
+            let (elf_data, warning) = match checked {
+                Ok(Some(checked)) => checked,
+                Ok(None) => continue,
+                Err(error) if self.lenient => {
+                    failures.push(RecordedError {
+                        pubkey: *pubkey,
+                        error,
+                    });
+                    continue;
                 }
-                // For BPF Loader v3
-                else if account.owner == mollusk_svm::program::loader_keys::LOADER_V3 {
-                    if account.data.len() < 36 {
-                        return Err(RpcError::MalformedProgram {
-                            program: *pubkey,
-                            reason: format!(
-                                "BPF Loader v3 program account too small: {} bytes (expected at least 36)",
-                                account.data.len()
-                            ),
-                        });
-                    }
+                Err(e) => return Err(e),
+            };
+            if let Some(warning) = warning {
+                // Mollusk may panic on the ELF, so let the callback see it first
+                self.notify(&warning);
+                warnings.push(warning);
+            }
 
-                    let program_data_pubkey = Pubkey::try_from(&account.data[4..36]).map_err(|e| {
-                        RpcError::MalformedProgram {
-                            program: *pubkey,
-                            reason: format!("Invalid program data pubkey: {}", e),
-                        }
-                    })?;
+            mollusk.add_program_with_elf_and_loader(pubkey, &elf_data, &account.owner);
+            loaded.push(*pubkey);
 
-                    let program_data_account = self.cache.get(&program_data_pubkey).ok_or_else(|| {
-                        RpcError::InvalidProgramData {
-                            program: *pubkey,
-                            reason: format!("Program data account not found: {}", program_data_pubkey),
-                        }
-                    })?;
+            #[cfg(feature = "tracing")]
+            tracing::debug!(
+                name: "program_loaded",
+                program = %pubkey,
+                loader = %account.owner,
+                elf_size = elf_data.len(),
+            );
+        }
 
-                    // The ELF starts at offset 45 in the program data account
-                    // (first 45 bytes are the ProgramData header)
-                    if program_data_account.data.len() <= 45 {
-                        return Err(RpcError::InvalidProgramData {
-                            program: *pubkey,
-                            reason: format!(
-                                "Program data account too small: {} bytes (expected > 45)",
-                                program_data_account.data.len()
-                            ),
-                        });
-                    }
+        self.load_local_programs(mollusk, &mut loaded, &mut failures, &mut warnings)?;
 
-                    let elf_data = &program_data_account.data[45..];
+        self.errors.extend(failures);
+        self.warnings.extend(warnings);
+        self.loaded_programs.extend(loaded);
+        Ok(())
+    }
 
-                    if self.validate_programs {
-                        validate_elf(elf_data).map_err(|reason| {
-                            RpcError::InvalidProgramData {
-                                program: *pubkey,
-                                reason,
-                            }
-                        })?;
-                    }
+    /// Validate the ELF of `pubkey` as its [`ValidationMode`] says, returning the
+    /// warning to record if it's loaded despite failing.
+    pub(crate) fn check_program_elf(
+        &self,
+        pubkey: &Pubkey,
+        elf_data: &[u8],
+    ) -> Result<Option<Warning>, RpcError> {
+        let mode = self
+            .elf_validation_overrides
+            .get(pubkey)
+            .copied()
+            .unwrap_or(self.elf_validation);
+        if mode == ValidationMode::Off {
+            return Ok(None);
+        }
+        match (validate_elf(elf_data), mode) {
+            (Ok(()), _) => Ok(None),
+            (Err(message), ValidationMode::Warn) => Ok(Some(Warning {
+                code: WarningCode::InvalidElfLoaded,
+                message,
+                pubkey: Some(*pubkey),
+            })),
+            (Err(reason), _) => Err(RpcError::InvalidProgramData {
+                program: *pubkey,
+                reason,
+            }),
+        }
+    }
 
-                    mollusk.add_program_with_elf_and_loader(
-                        pubkey,
-                        elf_data,
-                        &account.owner,
-                    );
-                }
+    /// Whether `pubkey` is already a program in `mollusk` that shouldn't be replaced.
+    fn keeps_loaded_program(&self, mollusk: &Mollusk, pubkey: &Pubkey) -> bool {
+        !self.force_reload.contains(pubkey) && mollusk.program_cache.load_program(pubkey).is_some()
+    }
+
+    /// The ELF of a cached program account, `None` if `account` isn't a BPF Loader
+    /// v2 or v3 program.
+    ///
+    /// For BPF Loader v3 the ELF lives in the program data account, which must be
+    /// cached too.
+    fn extract_elf<'a>(
+        &'a self,
+        pubkey: &Pubkey,
+        account: &'a Account,
+    ) -> Result<Option<&'a [u8]>, RpcError> {
+        if !account.executable {
+            return Ok(None);
+        }
+        // For BPF Loader v2 programs, the ELF is directly in the account data
+        if account.owner == mollusk_svm::program::loader_keys::LOADER_V2 {
+            return Ok(Some(&account.data));
+        }
+        if account.owner != mollusk_svm::program::loader_keys::LOADER_V3 {
+            return Ok(None);
+        }
+
+        let program_data_pubkey = program_data_address(pubkey, account)?;
+        let program_data_account = self.cache.get(&program_data_pubkey).ok_or_else(|| {
+            RpcError::InvalidProgramData {
+                program: *pubkey,
+                reason: format!("Program data account not found: {}", program_data_pubkey),
             }
+        })?;
+
+        // The ELF starts at offset 45 in the program data account
+        // (first 45 bytes are the ProgramData header)
+        if program_data_account.data.len() <= 45 {
+            return Err(RpcError::InvalidProgramData {
+                program: *pubkey,
+                reason: format!(
+                    "Program data account too small: {} bytes (expected > 45)",
+                    program_data_account.data.len()
+                ),
+            });
         }
 
-        Ok(self)
+        Ok(Some(&program_data_account.data[45..]))
+    }
+
+    /// The ELF of the cached program `program`, as [`add_programs`](Self::add_programs)
+    /// would load it. For BPF Loader v3 programs, the program data account must be
+    /// cached too, which `add_programs` takes care of.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`RpcError::AccountNotFound`] if the program isn't cached,
+    /// [`RpcError::MalformedProgram`] if it isn't a BPF Loader v2 or v3 program, and
+    /// [`RpcError::InvalidProgramData`] if its program data is missing or too small.
+    pub fn cached_program_elf(&self, program: &Pubkey) -> Result<&[u8], RpcError> {
+        let account = self
+            .cache
+            .get(program)
+            .ok_or(RpcError::AccountNotFound(*program))?;
+        self.extract_elf(program, account)?
+            .ok_or_else(|| RpcError::MalformedProgram {
+                program: *program,
+                reason: format!("Not a BPF Loader v2 or v3 program (owner {})", account.owner),
+            })
     }
 
+    /// The ELF of the cached program `program`, like
+    /// [`cached_program_elf`](Self::cached_program_elf) but owned, `None` if it
+    /// can't be extracted.
+    ///
+    /// The bytes are copied out of the program (data) account once, by
+    /// [`add_programs`](Self::add_programs) or the first call, and shared by
+    /// everything else that needs them (exports, later loads, these calls), so
+    /// holding on to the ELFs of a few multi-megabyte programs costs one copy each:
+    ///
+    /// ```rust,ignore
+    /// let elf = store.program_elf(&program_id).expect("program is cached");
+    /// std::thread::spawn(move || disassemble(&elf));
+    /// ```
+    ///
+    /// A program whose accounts were replaced in the cache since, e.g. by a
+    /// refetch, is copied again. Edits made in place through `cache` aren't noticed.
+    pub fn program_elf(&self, program: &Pubkey) -> Option<Arc<[u8]>> {
+        let account = self.cache.get(program)?;
+        self.shared_elf(program, account).ok().flatten()
+    }
+
+    /// The ELF of the cached program account `account` from `elf_cache`, copied out
+    /// of the account there first if it isn't yet. `None` like
+    /// [`extract_elf`](Self::extract_elf).
+    pub(crate) fn shared_elf(
+        &self,
+        pubkey: &Pubkey,
+        account: &Account,
+    ) -> Result<Option<Arc<[u8]>>, RpcError> {
+        let Some(elf) = self.extract_elf(pubkey, account)? else {
+            return Ok(None);
+        };
+        // A replaced account comes with a new buffer, so comparing addresses is
+        // enough to notice it without comparing megabytes of bytes
+        let source = (elf.as_ptr() as usize, elf.len());
+        let mut elfs = self.elf_cache.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(cached) = elfs.get(pubkey).filter(|cached| cached.source == source) {
+            return Ok(Some(cached.elf.clone()));
+        }
+        let elf: Arc<[u8]> = Arc::from(elf);
+        elfs.insert(
+            *pubkey,
+            CachedElf {
+                elf: elf.clone(),
+                source,
+            },
+        );
+        Ok(Some(elf))
+    }
 
     /// Sync the Mollusk environment to the current mainnet slot.
     ///
     /// This function fetches the current slot from the RPC endpoint and updates
-    /// the Mollusk instance to use that slot by calling `warp_to_slot`.
+    /// the Mollusk instance to use that slot by calling `warp_to_slot`. The clock's
+    /// `epoch` and `leader_schedule_epoch` follow Mollusk's epoch schedule; sync it
+    /// too (see [`with_synced_epoch_schedule`](Self::with_synced_epoch_schedule)) to
+    /// get mainnet's epochs. Every call fetches the slot anew, so calling it again
+    /// warps to the slot the cluster has reached since; the last one fetched is
+    /// available from [`synced_slot`](Self::synced_slot).
     ///
     /// Note: This is useful for oracles that need to be synced to the current mainnet slot.
-    pub async fn with_synced_slot(self, mollusk: &mut Mollusk) -> Result<Self, RpcError> {
-        let slot = self.client.get_slot().await?;
-        mollusk.warp_to_slot(slot);
+    pub async fn with_synced_slot(mut self, mollusk: &mut Mollusk) -> Result<Self, RpcError> {
+        let slot = self.cluster_slot().await?;
+        // Warping resets the clock's timestamps, which `with_synced_clock` may have set,
+        // so `warp_clock` only warps when the slot actually changes.
+        warp_clock(mollusk, slot);
         Ok(self)
     }
 
+    /// The cluster slot that Mollusk was last synced to, if any sync fetched one yet.
+    pub fn synced_slot(&self) -> Option<u64> {
+        self.synced_slot
+    }
+
+    /// Sync Mollusk's `Clock` sysvar to the cluster: slot, epoch and wall-clock time.
+    ///
+    /// Warps to the current slot like [`with_synced_slot`](Self::with_synced_slot)
+    /// (no need to call both), then sets `unix_timestamp` to the slot's
+    /// `getBlockTime`, or to the system time if the node has no block time for the
+    /// slot. `epoch_start_timestamp` is estimated from the slots elapsed in the epoch.
+    ///
+    /// # Errors
+    ///
+    /// Any other `getBlockTime` failure is returned, as are the budget and offline
+    /// errors of either request.
+    pub async fn with_synced_clock(mut self, mollusk: &mut Mollusk) -> Result<Self, RpcError> {
+        let slot = self.cluster_slot().await?;
+        self.begin_request("getBlockTime").await?;
+        let unix_timestamp = match self.slot_fetcher().get_block_time(slot).await {
+            Ok(unix_timestamp) => unix_timestamp,
+            Err(e) if fetcher::is_block_time_unavailable(&e) => std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map_or(0, |elapsed| elapsed.as_secs() as i64),
+            Err(e) => return Err(self.client_error(e)),
+        };
+
+        warp_clock(mollusk, slot);
+        let clock = &mut mollusk.sysvars.clock;
+        let epoch_start_slot = mollusk
+            .sysvars
+            .epoch_schedule
+            .get_first_slot_in_epoch(clock.epoch);
+        let seconds_into_epoch = slots_to_seconds(slot.saturating_sub(epoch_start_slot));
+        clock.unix_timestamp = unix_timestamp;
+        clock.epoch_start_timestamp = unix_timestamp.saturating_sub(seconds_into_epoch);
+        Ok(self)
+    }
+
+    /// Pretend `offset_slots` slots have passed since Mollusk's current clock, e.g.
+    /// after [`with_synced_clock`](Self::with_synced_clock).
+    ///
+    /// Warps forward and moves `unix_timestamp` by 400ms per slot; epoch and leader
+    /// schedule epoch follow Mollusk's epoch schedule, and `epoch_start_timestamp`
+    /// is re-estimated when an epoch boundary is crossed. Offsets too large for the
+    /// clock leave the slot and timestamp at their maximum rather than wrapping.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`RpcError::NegativeSlotOffset`] rather than warping backwards.
+    pub fn with_slot_offset(
+        self,
+        mollusk: &mut Mollusk,
+        offset_slots: i64,
+    ) -> Result<Self, RpcError> {
+        let slots =
+            u64::try_from(offset_slots).map_err(|_| RpcError::NegativeSlotOffset(offset_slots))?;
+        advance_clock_by_slots(mollusk, slots);
+        Ok(self)
+    }
+
+    /// Warp Mollusk to the first slot of `epoch`, per Mollusk's epoch schedule (the
+    /// cluster's after [`with_synced_epoch_schedule`](Self::with_synced_epoch_schedule)).
+    ///
+    /// The clock moves like with [`with_slot_offset`](Self::with_slot_offset): 400ms
+    /// per slot, with `epoch_start_timestamp` set to the new `unix_timestamp`.
+    /// Stake history is left alone; see
+    /// [`warp_to_epoch_with_stake_history`](Self::warp_to_epoch_with_stake_history).
+    ///
+    /// # Errors
+    ///
+    /// Returns [`RpcError::PastEpoch`] if Mollusk is already past the epoch's first slot.
+    pub fn warp_to_epoch(self, mollusk: &mut Mollusk, epoch: u64) -> Result<Self, RpcError> {
+        let first_slot = mollusk.sysvars.epoch_schedule.get_first_slot_in_epoch(epoch);
+        let slots = first_slot
+            .checked_sub(mollusk.sysvars.clock.slot)
+            .ok_or(RpcError::PastEpoch {
+                epoch,
+                slot: mollusk.sysvars.clock.slot,
+            })?;
+        advance_clock_by_slots(mollusk, slots);
+        Ok(self)
+    }
+
+    /// [`warp_to_epoch`](Self::warp_to_epoch), recording `entry` in the stake history
+    /// for every epoch that ends along the way.
+    ///
+    /// Stake activation and cooldown read the previous epochs' entries, which a plain
+    /// warp leaves missing.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`RpcError::PastEpoch`] if Mollusk is already past the epoch's first slot.
+    pub fn warp_to_epoch_with_stake_history(
+        self,
+        mollusk: &mut Mollusk,
+        epoch: u64,
+        entry: StakeHistoryEntry,
+    ) -> Result<Self, RpcError> {
+        let current_epoch = mollusk.sysvars.clock.epoch;
+        let store = self.warp_to_epoch(mollusk, epoch)?;
+        for ended in current_epoch..epoch {
+            mollusk.sysvars.stake_history.add(ended, entry.clone());
+        }
+        Ok(store)
+    }
+
+    /// Pretend `offset` has passed since Mollusk's current clock.
+    ///
+    /// Moves `unix_timestamp` by `offset` (whole seconds) and the slot by the number
+    /// of 400ms slots that fit in it, otherwise like
+    /// [`with_slot_offset`](Self::with_slot_offset).
+    pub fn with_time_offset(self, mollusk: &mut Mollusk, offset: Duration) -> Self {
+        advance_clock_by_time(mollusk, offset);
+        self
+    }
+
+    /// Fetch the current cluster slot and remember it as the synced slot.
+    async fn cluster_slot(&mut self) -> Result<u64, RpcError> {
+        self.begin_request("getSlot").await?;
+        let slot = self.slot_fetcher().get_slot().await.map_err(|e| self.client_error(e))?;
+        self.synced_slot = Some(slot);
+        Ok(slot)
+    }
+
     /// Deprecated: Use `with_synced_slot` instead.
     ///
     /// This method will be removed in a future version.
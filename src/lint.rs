@@ -0,0 +1,186 @@
+//! Catching mistakes in an instruction's account metas before it runs.
+
+use {
+    crate::{labels, owners::owner_name, sysvars::SYNTHESIZED_SYSVARS, RpcAccountStore, RpcError},
+    solana_instruction::Instruction,
+    solana_pubkey::Pubkey,
+    solana_sdk_ids::{system_program, sysvar},
+    std::{collections::HashSet, fmt},
+};
+
+/// How likely an [`InstructionLint`] is to be a mistake.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum LintSeverity {
+    /// Suspicious, but valid in some scenarios.
+    Warning,
+    /// The runtime would reject or silently change the meta.
+    Error,
+}
+
+impl fmt::Display for LintSeverity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Warning => "warning",
+            Self::Error => "error",
+        })
+    }
+}
+
+/// What [`RpcAccountStore::lint_instruction`] found wrong with a meta.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum LintKind {
+    /// The account isn't cached, so nothing else could be checked. Expected for
+    /// accounts the instruction creates.
+    NotCached,
+    /// A writable meta for an executable account.
+    WritableExecutable,
+    /// A writable meta for a sysvar, which the runtime passes read-only.
+    WritableSysvar,
+    /// A signer owned by a program other than the System Program. PDAs can't sign,
+    /// though a program may own an account created from a keypair.
+    ProgramOwnedSigner { owner: Pubkey },
+    /// The first writable signer, which pays the fee, has no lamports.
+    EmptyFeePayer,
+}
+
+impl LintKind {
+    pub fn severity(&self) -> LintSeverity {
+        match self {
+            Self::WritableExecutable | Self::WritableSysvar | Self::EmptyFeePayer => {
+                LintSeverity::Error
+            }
+            Self::NotCached | Self::ProgramOwnedSigner { .. } => LintSeverity::Warning,
+        }
+    }
+}
+
+impl fmt::Display for LintKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NotCached => f.write_str("not cached"),
+            Self::WritableExecutable => f.write_str("writable but executable"),
+            Self::WritableSysvar => f.write_str("writable sysvar"),
+            Self::ProgramOwnedSigner { owner } => {
+                write!(f, "signer owned by {}", owner_name(owner))
+            }
+            Self::EmptyFeePayer => f.write_str("fee payer with 0 lamports"),
+        }
+    }
+}
+
+/// A finding of [`RpcAccountStore::lint_instruction`].
+///
+/// Prints as `error: account 2, vault (4Nd1…kT3q): writable but executable`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InstructionLint {
+    pub kind: LintKind,
+    pub severity: LintSeverity,
+    /// Index of the first meta for the account.
+    pub account_index: usize,
+    pub pubkey: Pubkey,
+    /// See [`RpcAccountStore::label`].
+    pub label: Option<String>,
+}
+
+impl fmt::Display for InstructionLint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: account {}, {}: {}",
+            self.severity,
+            self.account_index,
+            labels::labeled(self.label.as_deref(), &self.pubkey),
+            self.kind
+        )
+    }
+}
+
+impl RpcAccountStore {
+    /// Check `instruction`'s account metas against the cached accounts, without any
+    /// RPC request.
+    ///
+    /// Finds writable metas for programs and sysvars, program-owned signers,
+    /// a fee payer (the first writable signer) without lamports, and accounts that
+    /// aren't cached, in meta order. Each account is checked once, at its first
+    /// meta, with the flags of all its metas combined. Sysvars that
+    /// [`instruction_accounts`](Self::instruction_accounts) builds from Mollusk
+    /// don't need to be cached.
+    ///
+    /// ```rust,ignore
+    /// for lint in store.lint_instruction(&ix) {
+    ///     println!("{lint}");
+    /// }
+    /// ```
+    pub fn lint_instruction(&self, instruction: &Instruction) -> Vec<InstructionLint> {
+        let mut lints = Vec::new();
+        let mut seen = HashSet::new();
+        let mut fee_payer_seen = false;
+        for (index, meta) in instruction.accounts.iter().enumerate() {
+            if !seen.insert(meta.pubkey) {
+                continue;
+            }
+            let metas = instruction
+                .accounts
+                .iter()
+                .filter(|other| other.pubkey == meta.pubkey);
+            let (is_signer, is_writable) = metas.fold((false, false), |(signer, writable), m| {
+                (signer || m.is_signer, writable || m.is_writable)
+            });
+            let mut lint = |kind: LintKind| {
+                lints.push(InstructionLint {
+                    kind,
+                    severity: kind.severity(),
+                    account_index: index,
+                    pubkey: meta.pubkey,
+                    label: self.label_of(&meta.pubkey).map(str::to_string),
+                })
+            };
+
+            let is_sysvar = SYNTHESIZED_SYSVARS.contains(&meta.pubkey)
+                || self
+                    .cache
+                    .get(&meta.pubkey)
+                    .is_some_and(|account| account.owner == sysvar::ID);
+            if is_writable && is_sysvar {
+                lint(LintKind::WritableSysvar);
+            }
+            let is_fee_payer = is_signer && is_writable && !fee_payer_seen;
+            fee_payer_seen |= is_fee_payer;
+
+            let Some(account) = self.cache.get(&meta.pubkey) else {
+                if !self.synthesizes_sysvar(&meta.pubkey) {
+                    lint(LintKind::NotCached);
+                }
+                continue;
+            };
+            if is_writable && account.executable {
+                lint(LintKind::WritableExecutable);
+            }
+            if is_signer && !account.executable && account.owner != system_program::ID {
+                lint(LintKind::ProgramOwnedSigner {
+                    owner: account.owner,
+                });
+            }
+            if is_fee_payer && account.lamports == 0 {
+                lint(LintKind::EmptyFeePayer);
+            }
+        }
+        lints
+    }
+
+    /// Fail on any finding of [`lint_instruction`](Self::lint_instruction), warnings
+    /// included, for suites that want every meta spelled out correctly.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`RpcError::InstructionLints`] with every finding.
+    pub fn assert_no_lints(&self, instruction: &Instruction) -> Result<(), RpcError> {
+        let lints = self.lint_instruction(instruction);
+        if lints.is_empty() {
+            Ok(())
+        } else {
+            Err(RpcError::InstructionLints(lints))
+        }
+    }
+}
@@ -0,0 +1,170 @@
+//! Address lookup tables made up in the cache, for v0 transactions over test
+//! accounts.
+//!
+//! The table state is written by hand in the layout of the ALT program's
+//! `ProgramState::LookupTable`, which `solana-address-lookup-table-interface`
+//! defines over `solana-pubkey` 4.
+
+use {
+    crate::{owners::owner_name, Provenance, RpcAccountStore, RpcError},
+    solana_account::Account,
+    solana_pubkey::Pubkey,
+    solana_sdk_ids::address_lookup_table,
+};
+
+/// Most addresses a lookup table holds.
+const LOOKUP_TABLE_MAX_ADDRESSES: usize = 256;
+
+/// Size of the table state before its addresses.
+const LOOKUP_TABLE_META_SIZE: usize = 56;
+
+/// Discriminant of `ProgramState::LookupTable`.
+const LOOKUP_TABLE_STATE: u32 = 1;
+
+/// Offset of the deactivation slot, after the discriminant.
+const DEACTIVATION_SLOT_OFFSET: usize = 4;
+
+/// Offset of the authority's `Option` tag.
+const AUTHORITY_OFFSET: usize = 21;
+
+/// The state of a table last extended at slot 0 with `addresses`, under
+/// `authority`.
+///
+/// Every address is marked as active at slot 0 already, except the 256th, which
+/// the state has no room for until slot 1.
+fn lookup_table_data(authority: &Pubkey, addresses: &[Pubkey]) -> Vec<u8> {
+    let mut data = Vec::with_capacity(LOOKUP_TABLE_META_SIZE + 32 * addresses.len());
+    data.extend_from_slice(&LOOKUP_TABLE_STATE.to_le_bytes());
+    data.extend_from_slice(&u64::MAX.to_le_bytes()); // deactivation_slot
+    data.extend_from_slice(&0u64.to_le_bytes()); // last_extended_slot
+    data.push(u8::try_from(addresses.len()).unwrap_or(u8::MAX));
+    data.push(1); // Some(authority)
+    data.extend_from_slice(authority.as_ref());
+    data.extend_from_slice(&[0; 2]); // _padding
+    for address in addresses {
+        data.extend_from_slice(address.as_ref());
+    }
+    data
+}
+
+impl RpcAccountStore {
+    /// Cache an address lookup table of `authority` holding `addresses`, without
+    /// running the ALT program's create and extend instructions, and return its
+    /// address and account.
+    ///
+    /// ```rust,ignore
+    /// let addresses = vec![pool, vault_a, vault_b];
+    /// let (key, _) = store.make_lookup_table(payer, &addresses);
+    /// let lookup = AddressLookupTableAccount { key, addresses };
+    /// let message = v0::Message::try_compile(&payer, &[swap_ix], &[lookup], blockhash)?;
+    /// ```
+    ///
+    /// The address is derived like the create instruction would, from `authority`
+    /// and the first recent slot, counting from 0, whose table isn't cached yet.
+    /// The table is active, the state decodes as the SDK's `AddressLookupTable`,
+    /// and the account is owned by the ALT program and rent-exempt at
+    /// [`rent`](Self::rent). Its addresses are all usable at any slot, as if they
+    /// had been added at slot 0; like any account added with
+    /// [`with_accounts`](Self::with_accounts), it's a mock.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `addresses` has more than 256 pubkeys.
+    pub fn make_lookup_table(
+        &mut self,
+        authority: Pubkey,
+        addresses: &[Pubkey],
+    ) -> (Pubkey, Account) {
+        assert!(
+            addresses.len() <= LOOKUP_TABLE_MAX_ADDRESSES,
+            "a lookup table holds at most {LOOKUP_TABLE_MAX_ADDRESSES} addresses, got {}",
+            addresses.len()
+        );
+        let address = (0u64..)
+            .map(|recent_slot| {
+                let seeds: &[&[u8]] = &[authority.as_ref(), &recent_slot.to_le_bytes()];
+                Pubkey::find_program_address(seeds, &address_lookup_table::ID).0
+            })
+            .find(|address| !self.cache.contains_key(address))
+            .expect("some recent slot derives an uncached address");
+        let data = lookup_table_data(&authority, addresses);
+        let account = Account {
+            lamports: self.rent().minimum_balance(data.len()),
+            data,
+            owner: address_lookup_table::ID,
+            executable: false,
+            rent_epoch: 0,
+        };
+        self.insert_account(address, account.clone(), Provenance::Mocked);
+        (address, account)
+    }
+
+    /// Append `more` to the cached lookup table `table`, made with
+    /// [`make_lookup_table`](Self::make_lookup_table) or fetched, and top its
+    /// lamports up to the rent-exempt minimum of the new size.
+    ///
+    /// The state is rewritten as for `make_lookup_table`: every address is usable at
+    /// any slot. The table is recorded as patched.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`RpcError::AccountNotFound`] if `table` isn't cached, and
+    /// [`RpcError::InvalidLookupTable`] if it isn't owned by the ALT program, isn't
+    /// an active lookup table with an authority, or would hold more than 256
+    /// addresses.
+    pub fn extend_lookup_table_in_cache(
+        &mut self,
+        table: &Pubkey,
+        more: &[Pubkey],
+    ) -> Result<(), RpcError> {
+        let rent = self.rent();
+        let account = self
+            .cache
+            .get_mut(table)
+            .ok_or(RpcError::AccountNotFound(*table))?;
+        let invalid = |reason| RpcError::InvalidLookupTable {
+            pubkey: *table,
+            reason,
+        };
+        if account.owner != address_lookup_table::ID {
+            return Err(invalid(format!(
+                "owned by {}, expected {}",
+                owner_name(&account.owner),
+                owner_name(&address_lookup_table::ID)
+            )));
+        }
+        let data = &account.data;
+        if data.len() < LOOKUP_TABLE_META_SIZE
+            || !(data.len() - LOOKUP_TABLE_META_SIZE).is_multiple_of(32)
+            || data[..4] != LOOKUP_TABLE_STATE.to_le_bytes()
+        {
+            return Err(invalid("not a lookup table".to_string()));
+        }
+        let deactivation_slot = &data[DEACTIVATION_SLOT_OFFSET..DEACTIVATION_SLOT_OFFSET + 8];
+        if deactivation_slot != u64::MAX.to_le_bytes() {
+            return Err(invalid("the table is deactivated".to_string()));
+        }
+        if data[AUTHORITY_OFFSET] != 1 {
+            return Err(invalid("the table is frozen".to_string()));
+        }
+        let authority = Pubkey::try_from(&data[AUTHORITY_OFFSET + 1..AUTHORITY_OFFSET + 33])
+            .expect("32 bytes");
+        let mut addresses: Vec<Pubkey> = data[LOOKUP_TABLE_META_SIZE..]
+            .chunks_exact(32)
+            .map(|address| Pubkey::try_from(address).expect("32 bytes"))
+            .collect();
+        addresses.extend_from_slice(more);
+        if addresses.len() > LOOKUP_TABLE_MAX_ADDRESSES {
+            return Err(invalid(format!(
+                "{} addresses, at most {LOOKUP_TABLE_MAX_ADDRESSES} fit",
+                addresses.len()
+            )));
+        }
+
+        account.data = lookup_table_data(&authority, &addresses);
+        account.lamports = account.lamports.max(rent.minimum_balance(account.data.len()));
+        self.provenance
+            .insert(*table, Provenance::Patched("extend_lookup_table_in_cache"));
+        Ok(())
+    }
+}
@@ -0,0 +1,202 @@
+//! How much memory the cache holds, and a warning once it holds too much.
+
+use {
+    crate::{labels::labeled, summary::program_label, RpcAccountStore, WarningCode},
+    solana_account::Account,
+    solana_pubkey::Pubkey,
+    solana_sdk_ids::{bpf_loader, bpf_loader_deprecated, bpf_loader_upgradeable, loader_v4},
+    std::{collections::HashMap, fmt},
+};
+
+/// Largest accounts listed by a [`MemoryReport`].
+const LARGEST_ACCOUNTS: usize = 10;
+
+/// Account data in the cache, see [`RpcAccountStore::memory_report`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MemoryReport {
+    pub accounts: usize,
+    /// Bytes of account data in the cache.
+    pub total_bytes: usize,
+    /// Bytes held by accounts of the BPF loaders, which are mostly ELFs: programs,
+    /// program data and buffers.
+    pub program_bytes: usize,
+    /// Bytes held by every other account.
+    pub account_bytes: usize,
+    /// The 10 largest accounts, largest first.
+    pub largest: Vec<LargeAccount>,
+}
+
+/// One of the largest accounts of a [`MemoryReport`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LargeAccount {
+    pub pubkey: Pubkey,
+    pub owner: Pubkey,
+    /// Bytes of account data.
+    pub size: usize,
+    /// See [`RpcAccountStore::label`].
+    pub label: Option<String>,
+}
+
+/// `bytes` in the largest unit that keeps it at or above 1.
+fn format_bytes(bytes: usize) -> String {
+    const UNITS: [&str; 3] = ["KiB", "MiB", "GiB"];
+    if bytes < 1024 {
+        return format!("{bytes} B");
+    }
+    let mut value = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{value:.1} {}", UNITS[unit])
+}
+
+impl fmt::Display for MemoryReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.accounts == 0 {
+            return f.write_str("no accounts cached");
+        }
+        write!(
+            f,
+            "{} of account data in {} accounts: {} in programs, {} in other accounts",
+            format_bytes(self.total_bytes),
+            self.accounts,
+            format_bytes(self.program_bytes),
+            format_bytes(self.account_bytes)
+        )?;
+        for account in &self.largest {
+            let owner = program_label(&account.owner)
+                .map_or_else(|| account.owner.to_string(), str::to_string);
+            write!(
+                f,
+                "\n  {:>10}  {}, owned by {owner}",
+                format_bytes(account.size),
+                labeled(account.label.as_deref(), &account.pubkey)
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Whether `account` belongs to a loader, and so holds a program's code.
+fn is_program_account(account: &Account) -> bool {
+    [bpf_loader_deprecated::ID, bpf_loader::ID, bpf_loader_upgradeable::ID, loader_v4::ID]
+        .contains(&account.owner)
+}
+
+fn cached_bytes(cache: &HashMap<Pubkey, Account>) -> usize {
+    cache.values().map(|account| account.data.len()).sum()
+}
+
+/// The warning threshold set with
+/// [`RpcAccountStore::with_memory_warning_threshold`].
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct MemoryWatch {
+    pub(crate) threshold: usize,
+    /// Cached bytes, kept up to date by the store's inserts. Accounts changed
+    /// through `cache` directly are missed, so it's checked before warning.
+    estimate: usize,
+    warned: bool,
+}
+
+impl RpcAccountStore {
+    /// Record a [`WarningCode::CacheMemoryHigh`] warning once the cache holds more
+    /// than `bytes` of account data, pointing at the largest account.
+    ///
+    /// Cloning far more than intended usually shows up as an out-of-memory kill
+    /// with nothing to go on; the warning, and the [`memory_report`](Self::memory_report)
+    /// it suggests printing, tell what filled the cache:
+    ///
+    /// ```rust,ignore
+    /// let store = RpcAccountStore::mainnet()
+    ///     .with_memory_warning_threshold(512 << 20)
+    ///     .on_warning(|warning| eprintln!("{warning}"));
+    /// ```
+    ///
+    /// The warning is recorded once per store.
+    pub fn with_memory_warning_threshold(mut self, bytes: usize) -> Self {
+        self.memory_watch = Some(MemoryWatch {
+            threshold: bytes,
+            estimate: cached_bytes(&self.cache),
+            warned: false,
+        });
+        self.track_memory(0, 0);
+        self
+    }
+
+    /// The bytes of account data in the cache, split between programs and other
+    /// accounts, with the largest accounts.
+    ///
+    /// Its `Display` output is meant for test failures:
+    ///
+    /// ```rust,ignore
+    /// assert!(result.program_result.is_ok(), "{}", store.memory_report());
+    /// // 9.1 MiB of account data in 37 accounts: 8.9 MiB in programs, 204.5 KiB in other accounts
+    /// //     3.2 MiB  orca_program_data (5Xw1…Qz9P), owned by BPF Loader v3
+    /// ```
+    pub fn memory_report(&self) -> MemoryReport {
+        let (mut total_bytes, mut program_bytes) = (0, 0);
+        let mut largest = Vec::with_capacity(self.cache.len());
+        for (pubkey, account) in &self.cache {
+            total_bytes += account.data.len();
+            if is_program_account(account) {
+                program_bytes += account.data.len();
+            }
+            largest.push((account.data.len(), *pubkey, account.owner));
+        }
+        // Largest first, ties in pubkey order so reports are stable
+        largest.sort_unstable_by(|a, b| b.0.cmp(&a.0).then_with(|| a.1.cmp(&b.1)));
+        largest.truncate(LARGEST_ACCOUNTS);
+
+        MemoryReport {
+            accounts: self.cache.len(),
+            total_bytes,
+            program_bytes,
+            account_bytes: total_bytes - program_bytes,
+            largest: largest
+                .into_iter()
+                .map(|(size, pubkey, owner)| LargeAccount {
+                    pubkey,
+                    owner,
+                    size,
+                    label: self.label_of(&pubkey).map(str::to_string),
+                })
+                .collect(),
+        }
+    }
+
+    /// Account for an insert replacing `removed` bytes of data with `added`, and
+    /// warn if the cache crossed the memory warning threshold.
+    pub(crate) fn track_memory(&mut self, removed: usize, added: usize) {
+        let Some(watch) = &mut self.memory_watch else {
+            return;
+        };
+        if watch.warned {
+            return;
+        }
+        watch.estimate = (watch.estimate + added).saturating_sub(removed);
+        if watch.estimate <= watch.threshold {
+            return;
+        }
+        watch.estimate = cached_bytes(&self.cache);
+        if watch.estimate <= watch.threshold {
+            return;
+        }
+        watch.warned = true;
+        let (total, threshold) = (watch.estimate, watch.threshold);
+
+        let largest = self.memory_report().largest.into_iter().next();
+        self.warn(
+            WarningCode::CacheMemoryHigh,
+            format!(
+                "The cache holds {} of account data, more than the {} threshold; the \
+                 largest account holds {}, see memory_report()",
+                format_bytes(total),
+                format_bytes(threshold),
+                format_bytes(largest.as_ref().map_or(0, |account| account.size)),
+            ),
+            largest.map(|account| account.pubkey),
+        );
+    }
+}
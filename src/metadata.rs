@@ -0,0 +1,157 @@
+//! Metaplex Token Metadata accounts of a mint, found by their PDAs.
+
+use {
+    crate::{known_programs::TOKEN_METADATA, RpcAccountStore, RpcError, TokenStateError},
+    solana_pubkey::Pubkey,
+};
+
+/// `Key::MetadataV1`, the first byte of metadata accounts.
+const METADATA_V1: u8 = 4;
+
+/// The Token Metadata accounts of a mint, as returned by
+/// [`RpcAccountStore::with_token_metadata`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TokenMetadataAccounts {
+    pub mint: Pubkey,
+    /// The metadata PDA, `["metadata", program, mint]`.
+    pub metadata: Pubkey,
+    /// The edition PDA, `["metadata", program, mint, "edition"]`: the master edition
+    /// of an NFT, or the edition of one of its prints.
+    pub edition: Pubkey,
+    /// Whether the metadata account exists and is cached.
+    pub has_metadata: bool,
+    /// Whether the edition account exists and is cached.
+    pub has_edition: bool,
+}
+
+/// The start of a metadata account, as read by
+/// [`RpcAccountStore::get_token_metadata`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TokenMetadata {
+    pub update_authority: Pubkey,
+    pub mint: Pubkey,
+    /// Name, symbol and URI, without the NUL padding Metaplex stores them with.
+    pub name: String,
+    pub symbol: String,
+    pub uri: String,
+}
+
+/// The metadata PDA of `mint`.
+fn metadata_address(mint: &Pubkey) -> Pubkey {
+    let program = TOKEN_METADATA.id;
+    let seeds: &[&[u8]] = &[b"metadata", program.as_ref(), mint.as_ref()];
+    Pubkey::find_program_address(seeds, &program).0
+}
+
+/// The edition PDA of `mint`.
+fn edition_address(mint: &Pubkey) -> Pubkey {
+    let program = TOKEN_METADATA.id;
+    let seeds: &[&[u8]] = &[b"metadata", program.as_ref(), mint.as_ref(), b"edition"];
+    Pubkey::find_program_address(seeds, &program).0
+}
+
+/// Reads the Borsh fields at the start of a metadata account.
+struct Reader<'a> {
+    data: &'a [u8],
+    offset: usize,
+}
+
+impl Reader<'_> {
+    fn take(&mut self, len: usize, field: &str) -> Result<&[u8], TokenStateError> {
+        let bytes = self
+            .data
+            .get(self.offset..self.offset + len)
+            .ok_or_else(|| TokenStateError::Malformed(format!("{field} is truncated")))?;
+        self.offset += len;
+        Ok(bytes)
+    }
+
+    fn pubkey(&mut self, field: &str) -> Result<Pubkey, TokenStateError> {
+        let bytes = self.take(32, field)?;
+        Ok(Pubkey::try_from(bytes).expect("32 bytes"))
+    }
+
+    fn string(&mut self, field: &str) -> Result<String, TokenStateError> {
+        let len = self.take(4, field)?;
+        let len = u32::from_le_bytes(len.try_into().expect("4 bytes")) as usize;
+        let bytes = self.take(len, field)?;
+        let string = std::str::from_utf8(bytes)
+            .map_err(|_| TokenStateError::Malformed(format!("{field} isn't UTF-8")))?;
+        Ok(string.trim_end_matches('\0').to_string())
+    }
+}
+
+impl RpcAccountStore {
+    /// Cache the Token Metadata accounts of `mint`: its metadata and, for NFTs, its
+    /// edition, whose addresses are derived from the mint.
+    ///
+    /// Fungible mints often have metadata but no edition, and many mints have
+    /// neither, so missing accounts are reported in the result instead of failing,
+    /// whatever [`allow_missing_accounts`](Self::allow_missing_accounts) says:
+    ///
+    /// ```rust,ignore
+    /// let nft = store.with_token_metadata(&mint).await?;
+    /// assert!(nft.has_metadata && nft.has_edition);
+    /// let ix = update_metadata_ix(nft.metadata, nft.edition, mint);
+    /// ```
+    ///
+    /// Cached accounts aren't fetched again, accounts in the
+    /// [fixture directory](Self::with_fixture_dir) are replayed, and an
+    /// [offline](Self::offline) store only finds those. The mint itself isn't
+    /// fetched.
+    pub async fn with_token_metadata(
+        &mut self,
+        mint: &Pubkey,
+    ) -> Result<TokenMetadataAccounts, RpcError> {
+        let metadata = metadata_address(mint);
+        let edition = edition_address(mint);
+        self.fetch_existing(&[metadata, edition]).await?;
+
+        Ok(TokenMetadataAccounts {
+            mint: *mint,
+            metadata,
+            edition,
+            has_metadata: self.cache.contains_key(&metadata),
+            has_edition: self.cache.contains_key(&edition),
+        })
+    }
+
+    /// The update authority, name, symbol and URI of the cached metadata of
+    /// `mint`, see [`with_token_metadata`](Self::with_token_metadata).
+    ///
+    /// # Errors
+    ///
+    /// Returns [`RpcError::AccountNotFound`] with the metadata PDA if it isn't
+    /// cached, and [`RpcError::InvalidTokenState`] if it isn't owned by Token
+    /// Metadata or isn't a `MetadataV1` account.
+    pub fn get_token_metadata(&self, mint: &Pubkey) -> Result<TokenMetadata, RpcError> {
+        self.token_state(&metadata_address(mint), "token metadata", |account| {
+            if account.owner != TOKEN_METADATA.id {
+                return Err(TokenStateError::WrongOwner {
+                    expected: vec![TOKEN_METADATA.id],
+                    actual: account.owner,
+                });
+            }
+            match account.data.first() {
+                Some(&METADATA_V1) => {}
+                Some(key) => {
+                    return Err(TokenStateError::Malformed(format!(
+                        "account key {key}, expected {METADATA_V1} (MetadataV1)"
+                    )))
+                }
+                None => return Err(TokenStateError::Uninitialized),
+            }
+            let mut reader = Reader {
+                data: &account.data,
+                offset: 1,
+            };
+            Ok(TokenMetadata {
+                update_authority: reader.pubkey("update authority")?,
+                mint: reader.pubkey("mint")?,
+                name: reader.string("name")?,
+                symbol: reader.string("symbol")?,
+                uri: reader.string("uri")?,
+            })
+        })
+    }
+}
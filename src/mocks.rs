@@ -0,0 +1,125 @@
+//! Catching mocks that shadow accounts which exist on-chain.
+
+use {
+    crate::{Provenance, RpcAccountStore, RpcError, WarningCode},
+    solana_account::Account,
+    solana_pubkey::Pubkey,
+    std::fmt,
+};
+
+/// A mocked account whose pubkey exists on-chain with different contents.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShadowedMock {
+    pub pubkey: Pubkey,
+    /// `(mocked, on_chain)` lamports.
+    pub lamports: (u64, u64),
+    /// `(mocked, on_chain)` owner.
+    pub owner: (Pubkey, Pubkey),
+    /// `(mocked, on_chain)` data length.
+    pub data_len: (usize, usize),
+}
+
+impl ShadowedMock {
+    fn new(pubkey: Pubkey, mocked: &Account, on_chain: &Account) -> Self {
+        Self {
+            pubkey,
+            lamports: (mocked.lamports, on_chain.lamports),
+            owner: (mocked.owner, on_chain.owner),
+            data_len: (mocked.data.len(), on_chain.data.len()),
+        }
+    }
+
+    /// What differs, e.g. `mocked vs on-chain: lamports 5 vs 1000`.
+    fn differences(&self) -> String {
+        let mut changes = Vec::new();
+        if self.lamports.0 != self.lamports.1 {
+            changes.push(format!("lamports {} vs {}", self.lamports.0, self.lamports.1));
+        }
+        if self.owner.0 != self.owner.1 {
+            changes.push(format!("owner {} vs {}", self.owner.0, self.owner.1));
+        }
+        if self.data_len.0 != self.data_len.1 {
+            changes.push(format!("data {} vs {} bytes", self.data_len.0, self.data_len.1));
+        }
+        if changes.is_empty() {
+            changes.push("data differs".to_string());
+        }
+        format!("mocked vs on-chain: {}", changes.join(", "))
+    }
+}
+
+impl fmt::Display for ShadowedMock {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({})", self.pubkey, self.differences())
+    }
+}
+
+impl RpcAccountStore {
+    /// Check mocked accounts against the cluster.
+    ///
+    /// The next fetch also requests every pubkey mocked with
+    /// [`with_accounts`](Self::with_accounts) that hasn't been checked yet, in the same
+    /// `getMultipleAccounts` batches, and records a [`WarningCode::MockShadowsAccount`]
+    /// warning for each one that exists on-chain with different contents. Mocks of
+    /// pubkeys that don't exist on-chain pass.
+    ///
+    /// Costs an RPC request only if a fetch wouldn't have made one anyway.
+    pub fn strict_mocks(mut self) -> Self {
+        self.strict_mocks = true;
+        self
+    }
+
+    /// Like [`strict_mocks`](Self::strict_mocks), but fail the fetch with
+    /// [`RpcError::ShadowedMocks`] instead of recording warnings.
+    pub fn deny_shadowed_mocks(mut self) -> Self {
+        self.strict_mocks = true;
+        self.deny_shadowed_mocks = true;
+        self
+    }
+
+    /// Mocked pubkeys the next fetch should check, sorted.
+    pub(crate) fn unchecked_mocks(&self) -> Vec<Pubkey> {
+        if !self.strict_mocks {
+            return Vec::new();
+        }
+        let mut mocks: Vec<Pubkey> = self
+            .provenance
+            .iter()
+            .filter(|(pubkey, provenance)| {
+                **provenance == Provenance::Mocked && !self.checked_mocks.contains(pubkey)
+            })
+            .map(|(pubkey, _)| *pubkey)
+            .collect();
+        mocks.sort();
+        mocks
+    }
+
+    /// Compare mocks with the accounts fetched for them.
+    pub(crate) fn check_mocks(
+        &mut self,
+        on_chain: Vec<(Pubkey, Option<Account>)>,
+    ) -> Result<(), RpcError> {
+        let mut shadowed = Vec::new();
+        for (pubkey, account) in on_chain {
+            self.checked_mocks.insert(pubkey);
+            let (Some(on_chain), Some(mocked)) = (account, self.cache.get(&pubkey)) else {
+                continue;
+            };
+            if *mocked != on_chain {
+                shadowed.push(ShadowedMock::new(pubkey, mocked, &on_chain));
+            }
+        }
+
+        if self.deny_shadowed_mocks && !shadowed.is_empty() {
+            return Err(RpcError::ShadowedMocks(shadowed));
+        }
+        for mock in shadowed {
+            self.warn(
+                WarningCode::MockShadowsAccount,
+                format!("mock shadows an on-chain account, {}", mock.differences()),
+                Some(mock.pubkey),
+            );
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,68 @@
+//! Patching cached oracle accounts in place, shared by the Pyth and Switchboard
+//! helpers.
+//!
+//! An oracle's module only parses its layouts into an [`OraclePatch`], the bytes to
+//! write over the account's data; looking up the account, applying the patch and
+//! recording its provenance happen here.
+
+use {
+    crate::{Provenance, RpcAccountStore, RpcError},
+    solana_pubkey::Pubkey,
+};
+
+/// Writes to make to an oracle account's data, at offsets its layout gives.
+#[derive(Default)]
+pub(crate) struct OraclePatch {
+    writes: Vec<(usize, Vec<u8>)>,
+}
+
+impl OraclePatch {
+    /// Write `bytes`, a value's little-endian bytes, at `offset`.
+    pub(crate) fn set(&mut self, offset: usize, bytes: impl AsRef<[u8]>) {
+        self.writes.push((offset, bytes.as_ref().to_vec()));
+    }
+
+    fn apply(self, data: &mut [u8]) -> Result<(), String> {
+        let len = data.len();
+        for (offset, bytes) in self.writes {
+            let field = data
+                .get_mut(offset..offset + bytes.len())
+                .ok_or_else(|| format!("{len} bytes of data, too short for its layout"))?;
+            field.copy_from_slice(&bytes);
+        }
+        Ok(())
+    }
+}
+
+/// The first 8 bytes of `data` as hex, which name the layout of Anchor accounts.
+pub(crate) fn discriminator(data: &[u8]) -> String {
+    let bytes: Vec<String> = data.iter().take(8).map(|byte| format!("{byte:02x}")).collect();
+    format!("[{}]", bytes.join(" "))
+}
+
+impl RpcAccountStore {
+    /// Rewrite the cached `oracle` account `pubkey` with the patch `parse` makes of
+    /// its data, recording `method` as having patched it.
+    pub(crate) fn patch_oracle(
+        &mut self,
+        pubkey: &Pubkey,
+        oracle: &'static str,
+        method: &'static str,
+        parse: impl FnOnce(&[u8]) -> Result<OraclePatch, String>,
+    ) -> Result<(), RpcError> {
+        let invalid = |reason| RpcError::InvalidOracleAccount {
+            pubkey: *pubkey,
+            oracle,
+            reason,
+        };
+        let account = self
+            .cache
+            .get_mut(pubkey)
+            .ok_or(RpcError::AccountNotFound(*pubkey))?;
+        parse(&account.data)
+            .and_then(|patch| patch.apply(&mut account.data))
+            .map_err(invalid)?;
+        self.provenance.insert(*pubkey, Provenance::Patched(method));
+        Ok(())
+    }
+}
@@ -0,0 +1,166 @@
+//! Checking who owns cached accounts, and that the programs owning writable
+//! accounts are loaded.
+
+use {
+    crate::{labels, summary::program_label, RpcAccountStore, RpcError},
+    mollusk_svm::Mollusk,
+    solana_account::Account,
+    solana_instruction::Instruction,
+    solana_pubkey::Pubkey,
+    solana_sdk_ids::sysvar,
+    std::{collections::BTreeSet, fmt},
+};
+
+/// A cached account owned by another program than expected.
+///
+/// See [`RpcError::WrongOwners`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OwnerMismatch {
+    pub pubkey: Pubkey,
+    /// See [`RpcAccountStore::label`].
+    pub label: Option<String>,
+    pub expected: Pubkey,
+    pub actual: Pubkey,
+}
+
+/// `owner`, followed by its name if it's a well-known program.
+pub(crate) fn owner_name(owner: &Pubkey) -> String {
+    match program_label(owner) {
+        Some(name) => format!("{owner} ({name})"),
+        None => owner.to_string(),
+    }
+}
+
+impl fmt::Display for OwnerMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} is owned by {}, expected {}",
+            labels::labeled(self.label.as_deref(), &self.pubkey),
+            owner_name(&self.actual),
+            owner_name(&self.expected)
+        )
+    }
+}
+
+impl RpcAccountStore {
+    /// Check that the cached account `pubkey` is owned by `owner`, without any RPC
+    /// request.
+    ///
+    /// Catches the classic setup mistake of an account that belongs to SPL Token
+    /// when the test assumes Token-2022, or the other way around.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`RpcError::AccountNotFound`] if the account isn't cached, and
+    /// [`RpcError::WrongOwners`] with the actual owner if it's owned by another
+    /// program.
+    pub fn assert_owner(&self, pubkey: &Pubkey, owner: &Pubkey) -> Result<(), RpcError> {
+        self.assert_owners(&[(*pubkey, *owner)])
+    }
+
+    /// [`assert_owner`](Self::assert_owner) for several `(account, owner)` pairs,
+    /// reporting every mismatch at once.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`RpcError::AccountNotFound`] or [`RpcError::AccountsNotFound`] if
+    /// accounts aren't cached, and [`RpcError::WrongOwners`] otherwise if any account
+    /// is owned by another program than expected.
+    pub fn assert_owners(&self, expected: &[(Pubkey, Pubkey)]) -> Result<(), RpcError> {
+        let mut missing = Vec::new();
+        let mut mismatches = Vec::new();
+        for (pubkey, owner) in expected {
+            match self.cache.get(pubkey) {
+                None => missing.push(*pubkey),
+                Some(account) if account.owner != *owner => mismatches.push(OwnerMismatch {
+                    pubkey: *pubkey,
+                    label: self.label_of(pubkey).map(str::to_string),
+                    expected: *owner,
+                    actual: account.owner,
+                }),
+                Some(_) => {}
+            }
+        }
+        match (missing.as_slice(), mismatches.is_empty()) {
+            ([], true) => Ok(()),
+            ([], false) => Err(RpcError::WrongOwners(mismatches)),
+            ([pubkey], _) => Err(RpcError::AccountNotFound(*pubkey)),
+            _ => Err(RpcError::AccountsNotFound(missing)),
+        }
+    }
+
+    /// The cached accounts owned by `owner`, sorted by pubkey.
+    pub fn accounts_by_owner(&self, owner: &Pubkey) -> Vec<(Pubkey, &Account)> {
+        let mut accounts: Vec<(Pubkey, &Account)> = self
+            .cache
+            .iter()
+            .filter(|(_, account)| account.owner == *owner)
+            .map(|(pubkey, account)| (*pubkey, account))
+            .collect();
+        accounts.sort_by_key(|(pubkey, _)| *pubkey);
+        accounts
+    }
+
+    /// Fetch the programs owning the writable accounts of the instructions fetched so
+    /// far, so that [`add_programs`](Self::add_programs) loads them too.
+    ///
+    /// ```rust,ignore
+    /// let store = RpcAccountStore::new(rpc_url)
+    ///     .from_instruction(&instruction)
+    ///     .await?
+    ///     .with_owner_programs()
+    ///     .await?
+    ///     .add_programs(&mut mollusk)
+    ///     .await?;
+    /// ```
+    pub async fn with_owner_programs(mut self) -> Result<Self, RpcError> {
+        let owners: Vec<Pubkey> = self.writable_owners().into_iter().collect();
+        self.fetch_accounts(&owners).await?;
+        Ok(self)
+    }
+
+    /// Check that every program owning a writable account of the instructions
+    /// fetched so far is loaded into `mollusk`, as a builtin or through
+    /// [`add_programs`](Self::add_programs).
+    ///
+    /// Writes to an account whose owner isn't loaded fail mid-execution with an
+    /// unhelpful owner or privilege error; this reports the culprits up front.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`RpcError::OwnerProgramsNotLoaded`] listing each missing program.
+    pub fn verify_owners_loaded(&self, mollusk: &Mollusk) -> Result<(), RpcError> {
+        let missing: Vec<Pubkey> = self
+            .writable_owners()
+            .into_iter()
+            .filter(|owner| mollusk.program_cache.load_program(owner).is_none())
+            .collect();
+        if missing.is_empty() {
+            Ok(())
+        } else {
+            Err(RpcError::OwnerProgramsNotLoaded(missing))
+        }
+    }
+
+    /// Remember which accounts `instructions` write to.
+    pub(crate) fn track_writable(&mut self, instructions: &[Instruction]) {
+        self.writable_accounts.extend(
+            instructions
+                .iter()
+                .flat_map(|ix| ix.accounts.iter())
+                .filter(|meta| meta.is_writable)
+                .map(|meta| meta.pubkey),
+        );
+    }
+
+    /// Owners of cached, non-executable accounts that were seen as writable.
+    fn writable_owners(&self) -> BTreeSet<Pubkey> {
+        self.writable_accounts
+            .iter()
+            .filter_map(|pubkey| self.cache.get(pubkey))
+            .filter(|account| !account.executable && account.owner != sysvar::ID)
+            .map(|account| account.owner)
+            .collect()
+    }
+}
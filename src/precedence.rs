@@ -0,0 +1,84 @@
+//! Mocks that win over, or give way to, the chain regardless of call order.
+
+use {
+    crate::{IntoAccountEntry, Provenance, RpcAccountStore},
+    solana_account::Account,
+    solana_pubkey::Pubkey,
+};
+
+impl RpcAccountStore {
+    /// Add accounts that take precedence over the chain.
+    ///
+    /// Unlike [`with_accounts`](Self::with_accounts), order doesn't matter: an
+    /// override replaces an account fetched earlier, and later fetches, fixtures and
+    /// refreshes leave it alone. Results written back from instructions still apply.
+    pub fn with_accounts_override<I>(mut self, accounts: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: IntoAccountEntry,
+    {
+        for entry in accounts {
+            let (pubkey, account) = entry.into_account_entry();
+            self.insert_account(pubkey, account, Provenance::Override);
+        }
+        self
+    }
+
+    /// Add accounts to use only if the chain doesn't have them.
+    ///
+    /// The next fetch that asks for one of these pubkeys still looks it up (in the
+    /// fixture directory, then on-chain) and caches what it finds instead; the
+    /// fallback is kept, and treated as a mock from then on, only if the account
+    /// doesn't exist, even without
+    /// [`allow_missing_accounts`](Self::allow_missing_accounts). Offline stores use the
+    /// fallback unless a fixture has the account. Accounts already cached are kept.
+    pub fn with_accounts_fallback<I>(mut self, accounts: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: IntoAccountEntry,
+    {
+        for entry in accounts {
+            let (pubkey, account) = entry.into_account_entry();
+            if !self.cache.contains_key(&pubkey) {
+                self.insert_account(pubkey, account, Provenance::Fallback);
+            }
+        }
+        self
+    }
+
+    /// Whether `pubkey` holds a fallback the chain hasn't been checked for yet.
+    pub(crate) fn is_fallback(&self, pubkey: &Pubkey) -> bool {
+        self.provenance.get(pubkey) == Some(&Provenance::Fallback)
+    }
+
+    /// Whether an insert with `provenance` must leave the account at `pubkey` alone.
+    pub(crate) fn is_overridden(&self, pubkey: &Pubkey, provenance: &Provenance) -> bool {
+        matches!(provenance, Provenance::Fetched { .. } | Provenance::Fixture { .. })
+            && self.provenance.get(pubkey) == Some(&Provenance::Override)
+    }
+
+    /// Keep the fallbacks of accounts `fetched` found missing, turning them into plain
+    /// mocks, and return the rest.
+    pub(crate) fn keep_fallbacks(
+        &mut self,
+        fetched: Vec<(Pubkey, Option<Account>)>,
+    ) -> Vec<(Pubkey, Option<Account>)> {
+        fetched
+            .into_iter()
+            .filter(|(pubkey, account)| {
+                if account.is_some() || !self.is_fallback(pubkey) {
+                    return true;
+                }
+                self.settle_fallback(pubkey);
+                false
+            })
+            .collect()
+    }
+
+    /// Use the fallback at `pubkey` as a mock from now on.
+    pub(crate) fn settle_fallback(&mut self, pubkey: &Pubkey) {
+        self.provenance.insert(*pubkey, Provenance::Mocked);
+        // The chain doesn't have it, so there's nothing for `strict_mocks` to compare
+        self.checked_mocks.insert(*pubkey);
+    }
+}
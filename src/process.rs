@@ -0,0 +1,206 @@
+//! One-call helpers for running instructions against live accounts.
+
+use {
+    crate::{compute_budget::is_compute_budget, RpcAccountStore, RpcError},
+    mollusk_svm::{result::InstructionResult, Mollusk},
+    solana_instruction::Instruction,
+    solana_pubkey::Pubkey,
+    std::{collections::HashSet, time::Duration},
+};
+
+/// How far to move Mollusk's clock before a step of
+/// [`run_chain_with_slot_advance`](RpcAccountStore::run_chain_with_slot_advance).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SlotAdvance {
+    /// Run in the same slot as the previous step.
+    #[default]
+    None,
+    /// Move forward this many slots, like [`with_slot_offset`](RpcAccountStore::with_slot_offset).
+    Slots(u64),
+    /// Move forward this many seconds, like [`with_time_offset`](RpcAccountStore::with_time_offset).
+    Seconds(u64),
+}
+
+impl SlotAdvance {
+    /// Move Mollusk's clock forward by this much.
+    fn apply(self, mollusk: &mut Mollusk) {
+        match self {
+            Self::None => {}
+            Self::Slots(slots) => crate::advance_clock_by_slots(mollusk, slots),
+            Self::Seconds(seconds) => {
+                crate::advance_clock_by_time(mollusk, Duration::from_secs(seconds))
+            }
+        }
+    }
+}
+
+/// One step of a chain run with
+/// [`run_chain_with_slot_advance`](RpcAccountStore::run_chain_with_slot_advance).
+#[derive(Debug)]
+pub struct ChainStep {
+    /// The slot the instruction ran in.
+    pub slot: u64,
+    /// The instruction's result.
+    pub result: InstructionResult,
+}
+
+/// Run `instruction` with the accounts in `store`.
+///
+/// The store must already hold the instruction's accounts (e.g. after
+/// [`from_instruction`](RpcAccountStore::from_instruction)) and its programs must be
+/// loaded into `mollusk` (see [`add_programs`](RpcAccountStore::add_programs)).
+///
+/// # Errors
+///
+/// Only store errors are returned as `Err`; whether the instruction itself succeeded
+/// is reported by the [`InstructionResult`].
+pub fn process_instruction_with_store(
+    mollusk: &Mollusk,
+    store: &RpcAccountStore,
+    instruction: &Instruction,
+) -> Result<InstructionResult, RpcError> {
+    let accounts = store.instruction_accounts(mollusk, instruction)?;
+    Ok(mollusk.process_instruction(instruction, &accounts))
+}
+
+/// Fetch everything `instruction` needs from `rpc_url`, load its programs into
+/// `mollusk` and run it.
+///
+/// Shorthand for quick experiments; build an [`RpcAccountStore`] yourself to
+/// configure fetching or to reuse accounts between instructions.
+///
+/// ```rust,ignore
+/// let mut mollusk = Mollusk::default();
+/// let result = process_instruction_against_mainnet(&mut mollusk, MAINNET_RPC_URL, &ix).await?;
+/// assert!(result.program_result.is_ok());
+/// ```
+pub async fn process_instruction_against_mainnet(
+    mollusk: &mut Mollusk,
+    rpc_url: impl Into<String>,
+    instruction: &Instruction,
+) -> Result<InstructionResult, RpcError> {
+    let mut store = RpcAccountStore::new(rpc_url);
+    let instructions = std::slice::from_ref(instruction);
+    store
+        .fetch_accounts(&chain_pubkeys(mollusk, instructions))
+        .await
+        .map_err(|e| crate::locate_missing(&store, instructions, e))?;
+    let store = store.add_programs(mollusk).await?;
+    process_instruction_with_store(mollusk, &store, instruction)
+}
+
+/// Every account the instructions use, plus their programs unless Mollusk already
+/// has them (`add_programs` only loads programs it finds in the cache).
+fn chain_pubkeys(mollusk: &Mollusk, instructions: &[Instruction]) -> Vec<Pubkey> {
+    let pubkeys: HashSet<Pubkey> = instructions
+        .iter()
+        .filter(|ix| !is_compute_budget(ix))
+        .flat_map(|ix| {
+            let program = mollusk
+                .program_cache
+                .load_program(&ix.program_id)
+                .is_none()
+                .then_some(ix.program_id);
+            ix.accounts.iter().map(|meta| meta.pubkey).chain(program)
+        })
+        .collect();
+    pubkeys.into_iter().collect()
+}
+
+impl RpcAccountStore {
+    /// Run `instructions` one after another, carrying state from each to the next.
+    ///
+    /// Fetches whatever the chain needs that isn't cached yet, loads its programs
+    /// into `mollusk`, then processes the instructions in order, applying each
+    /// successful result to the cache (see [`apply_result`](Self::apply_result))
+    /// before the next one runs.
+    ///
+    /// Returns one result per processed instruction. Processing stops at the first
+    /// instruction that fails, whose result is the last one returned.
+    ///
+    /// ComputeBudget instructions are skipped, as in a replayed transaction they
+    /// only set its budget; use [`apply_compute_budget`](Self::apply_compute_budget)
+    /// to run with it.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error only if fetching accounts or loading programs fails.
+    pub async fn run_chain(
+        &mut self,
+        mollusk: &mut Mollusk,
+        instructions: &[Instruction],
+    ) -> Result<Vec<InstructionResult>, RpcError> {
+        self.track_writable(instructions);
+        self.fetch_accounts(&chain_pubkeys(mollusk, instructions))
+            .await
+            .map_err(|e| crate::locate_missing(self, instructions, e))?;
+        self.load_programs(mollusk).await?;
+
+        let mut results = Vec::with_capacity(instructions.len());
+        for instruction in instructions.iter().filter(|ix| !is_compute_budget(ix)) {
+            let result = process_instruction_with_store(mollusk, self, instruction)?;
+            let failed = result.program_result.is_err();
+            self.apply_result(&result);
+            results.push(result);
+            if failed {
+                break;
+            }
+        }
+        Ok(results)
+    }
+
+    /// [`run_chain`](Self::run_chain), moving Mollusk's clock forward before each
+    /// step as its [`SlotAdvance`] says.
+    ///
+    /// For protocols where an instruction must run strictly later than the previous
+    /// one (cooldowns, staleness windows). The clock moves like with
+    /// [`with_slot_offset`](Self::with_slot_offset), and each step records the slot it
+    /// ran in. ComputeBudget steps are skipped like in `run_chain`, but their advance
+    /// still moves the clock before the next step:
+    ///
+    /// ```rust,ignore
+    /// let steps = store
+    ///     .run_chain_with_slot_advance(
+    ///         &mut mollusk,
+    ///         &[(deposit, SlotAdvance::None), (withdraw, SlotAdvance::Slots(150))],
+    ///     )
+    ///     .await?;
+    /// assert!(steps[1].result.program_result.is_ok());
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns an error only if fetching accounts or loading programs fails.
+    pub async fn run_chain_with_slot_advance(
+        &mut self,
+        mollusk: &mut Mollusk,
+        steps: &[(Instruction, SlotAdvance)],
+    ) -> Result<Vec<ChainStep>, RpcError> {
+        let instructions: Vec<Instruction> = steps.iter().map(|(ix, _)| ix.clone()).collect();
+        self.track_writable(&instructions);
+        self.fetch_accounts(&chain_pubkeys(mollusk, &instructions))
+            .await
+            .map_err(|e| crate::locate_missing(self, &instructions, e))?;
+        self.load_programs(mollusk).await?;
+
+        let mut results = Vec::with_capacity(steps.len());
+        for (instruction, advance) in steps {
+            advance.apply(mollusk);
+            if is_compute_budget(instruction) {
+                continue;
+            }
+
+            let result = process_instruction_with_store(mollusk, self, instruction)?;
+            let failed = result.program_result.is_err();
+            self.apply_result(&result);
+            results.push(ChainStep {
+                slot: mollusk.sysvars.clock.slot,
+                result,
+            });
+            if failed {
+                break;
+            }
+        }
+        Ok(results)
+    }
+}
@@ -0,0 +1,162 @@
+//! Fetching every account owned by a program.
+
+use {
+    crate::{fetcher::ProgramAccountsPage, RpcAccountStore, RpcError},
+    futures::{
+        pin_mut,
+        stream::{self, Stream, StreamExt},
+    },
+    solana_pubkey::Pubkey,
+    solana_rpc_client_api::client_error::{Error as ClientError, ErrorKind as ClientErrorKind},
+    std::sync::atomic::Ordering,
+};
+
+/// Accounts requested per page from providers that paginate.
+const PAGE_SIZE: usize = 1_000;
+
+/// Default for [`RpcAccountStore::with_program_accounts_limit`].
+pub(crate) const DEFAULT_PROGRAM_ACCOUNTS_LIMIT: usize = 10_000;
+
+enum Cursor {
+    First,
+    Next(String),
+    Done,
+}
+
+impl RpcAccountStore {
+    /// Refuse to load more than `limit` accounts for a single program.
+    ///
+    /// Applies to [`with_program_accounts`](Self::with_program_accounts), and to
+    /// [`program_accounts_pages`](Self::program_accounts_pages) when the provider can't
+    /// paginate and the whole result arrives at once. Defaults to 10,000.
+    pub fn with_program_accounts_limit(mut self, limit: usize) -> Self {
+        self.program_accounts_limit = limit;
+        self
+    }
+
+    /// Fetch and cache every account owned by `program_id`.
+    ///
+    /// Uses the paginated `getProgramAccountsV2` where the provider offers it and
+    /// plain `getProgramAccounts` otherwise. The store remembers an endpoint that
+    /// doesn't paginate, so only its first query spends a request on finding out.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`RpcError::TooManyProgramAccounts`] as soon as the program turns out
+    /// to own more accounts than the [limit](Self::with_program_accounts_limit).
+    pub async fn with_program_accounts(mut self, program_id: &Pubkey) -> Result<Self, RpcError> {
+        let mut accounts = Vec::new();
+        {
+            let pages = self.program_accounts_pages(*program_id);
+            pin_mut!(pages);
+            while let Some(page) = pages.next().await {
+                accounts.extend(page?.accounts);
+                if accounts.len() > self.program_accounts_limit {
+                    return Err(RpcError::TooManyProgramAccounts {
+                        program: *program_id,
+                        limit: self.program_accounts_limit,
+                    });
+                }
+            }
+        }
+
+        for (pubkey, account) in accounts {
+            let account = self.resolve_fetched(&pubkey, Some(account))?;
+            let provenance = self.fetched_provenance();
+            self.insert_account(pubkey, account, provenance);
+        }
+        Ok(self)
+    }
+
+    /// Stream the accounts owned by `program_id` page by page, without caching them.
+    ///
+    /// Lets callers process programs with more accounts than fit in memory. Providers
+    /// without pagination yield a single page holding everything, subject to the
+    /// [limit](Self::with_program_accounts_limit).
+    ///
+    /// ```rust,ignore
+    /// let pages = store.program_accounts_pages(program_id);
+    /// futures::pin_mut!(pages);
+    /// while let Some(page) = pages.next().await {
+    ///     for (pubkey, account) in page?.accounts {
+    ///         // ...
+    ///     }
+    /// }
+    /// ```
+    pub fn program_accounts_pages(
+        &self,
+        program_id: Pubkey,
+    ) -> impl Stream<Item = Result<ProgramAccountsPage, RpcError>> + '_ {
+        stream::try_unfold(Cursor::First, move |cursor| async move {
+            let cursor = match cursor {
+                Cursor::First if self.pagination_unsupported.load(Ordering::Relaxed) => {
+                    let page = self.fetch_unpaginated(&program_id).await?;
+                    self.record_page(&page);
+                    return Ok(Some((page, Cursor::Done)));
+                }
+                Cursor::First => None,
+                Cursor::Next(cursor) => Some(cursor),
+                Cursor::Done => return Ok(None),
+            };
+
+            self.begin_request("getProgramAccountsV2").await?;
+            let page = match self
+                .fetcher
+                .get_program_accounts_page(&program_id, cursor.as_deref(), PAGE_SIZE)
+                .await
+                .map_err(|e| self.client_error(e))?
+            {
+                Some(page) => page,
+                None if cursor.is_none() => {
+                    // Don't spend a request on probing this endpoint again
+                    self.pagination_unsupported.store(true, Ordering::Relaxed);
+                    self.fetch_unpaginated(&program_id).await?
+                }
+                None => {
+                    return Err(ClientError::from(ClientErrorKind::Custom(
+                        "provider stopped paginating getProgramAccountsV2".to_string(),
+                    ))
+                    .into())
+                }
+            };
+
+            self.record_page(&page);
+            let next = match &page.cursor {
+                Some(cursor) => Cursor::Next(cursor.clone()),
+                None => Cursor::Done,
+            };
+            Ok(Some((page, next)))
+        })
+    }
+
+    /// Count the accounts of `page` and their data in the stats.
+    fn record_page(&self, page: &ProgramAccountsPage) {
+        let mut stats = self.stats_mut();
+        stats.accounts_fetched += page.accounts.len() as u64;
+        stats.bytes_downloaded += page
+            .accounts
+            .iter()
+            .map(|(_, account)| account.data.len() as u64)
+            .sum::<u64>();
+    }
+
+    /// Fall back to a single `getProgramAccounts` request.
+    async fn fetch_unpaginated(&self, program_id: &Pubkey) -> Result<ProgramAccountsPage, RpcError> {
+        self.begin_request("getProgramAccounts").await?;
+        let accounts = self
+            .fetcher
+            .get_program_accounts(program_id)
+            .await
+            .map_err(|e| self.client_error(e))?;
+        if accounts.len() > self.program_accounts_limit {
+            return Err(RpcError::TooManyProgramAccounts {
+                program: *program_id,
+                limit: self.program_accounts_limit,
+            });
+        }
+        Ok(ProgramAccountsPage {
+            accounts,
+            cursor: None,
+        })
+    }
+}
@@ -0,0 +1,182 @@
+//! Live account refresh over WebSocket (`pubsub` feature).
+
+use {
+    crate::{RpcAccountStore, RpcError},
+    futures::stream::{self, StreamExt},
+    solana_account::Account,
+    solana_account_decoder_client_types::UiAccountEncoding,
+    solana_commitment_config::CommitmentConfig,
+    solana_pubkey::Pubkey,
+    solana_pubsub_client::nonblocking::pubsub_client::PubsubClient,
+    solana_rpc_client_api::config::RpcAccountInfoConfig,
+    std::{
+        collections::HashMap,
+        sync::{Arc, Mutex},
+    },
+    tokio::{sync::oneshot, task::JoinHandle},
+};
+
+/// Background `accountSubscribe` tasks and the updates they have received but
+/// that haven't been applied to the cache yet.
+#[derive(Default)]
+pub(crate) struct Subscriptions {
+    pending: Arc<Mutex<HashMap<Pubkey, Account>>>,
+    tasks: Vec<JoinHandle<()>>,
+    pub(crate) ws_url: Option<String>,
+}
+
+impl Subscriptions {
+    pub(crate) fn active(&self) -> usize {
+        self.tasks.iter().filter(|task| !task.is_finished()).count()
+    }
+
+    fn stop(&mut self) {
+        for task in self.tasks.drain(..) {
+            task.abort();
+        }
+    }
+}
+
+impl Drop for Subscriptions {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+/// Derive the WebSocket URL for an HTTP RPC URL, following the Solana CLI
+/// convention: `http` becomes `ws`, `https` becomes `wss`, and an explicit port is
+/// incremented by one (8899 -> 8900).
+fn websocket_url(rpc_url: &str) -> Option<String> {
+    let mut url = reqwest::Url::parse(rpc_url).ok()?;
+    let scheme = match url.scheme() {
+        "http" => "ws",
+        "https" => "wss",
+        _ => return None,
+    };
+    url.set_scheme(scheme).ok()?;
+    if let Some(port) = url.port() {
+        url.set_port(Some(port + 1)).ok()?;
+    }
+    Some(url.to_string())
+}
+
+impl RpcAccountStore {
+    /// Use `ws_url` for [`subscribe`](Self::subscribe) instead of deriving it from the
+    /// RPC URL.
+    ///
+    /// Required for stores created with [`new_with_fetcher`](Self::new_with_fetcher).
+    pub fn with_ws_url(mut self, ws_url: impl Into<String>) -> Self {
+        self.subscriptions.ws_url = Some(ws_url.into());
+        self
+    }
+
+    /// Keep the given accounts fresh by subscribing to them over WebSocket.
+    ///
+    /// Opens an `accountSubscribe` subscription per pubkey on a background task.
+    /// Notifications are buffered and copied into [`cache`](Self::cache) by
+    /// [`sync_subscriptions`](Self::sync_subscriptions), which every fetch also calls
+    /// first. Note that Mollusk only ever sees a snapshot: accounts are copied out of
+    /// the cache when they are handed to it, so later notifications don't affect an
+    /// instruction that is already being processed.
+    ///
+    /// Requires a Tokio runtime. The subscriptions live until
+    /// [`stop_subscriptions`](Self::stop_subscriptions) is called or the store is dropped.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if no WebSocket URL is known, the store is offline, or
+    /// connecting or subscribing fails.
+    pub async fn subscribe(mut self, pubkeys: &[Pubkey]) -> Result<Self, RpcError> {
+        let ws_url = self
+            .subscriptions
+            .ws_url
+            .clone()
+            .or_else(|| self.rpc_url().and_then(websocket_url))
+            .ok_or_else(|| {
+                RpcError::Pubsub("no WebSocket URL known, use `with_ws_url`".to_string())
+            })?;
+
+        for _ in pubkeys {
+            self.begin_request("accountSubscribe").await?;
+        }
+
+        let config = RpcAccountInfoConfig {
+            encoding: Some(UiAccountEncoding::Base64),
+            commitment: Some(self.commitment().unwrap_or(CommitmentConfig::confirmed())),
+            ..RpcAccountInfoConfig::default()
+        };
+        let pubkeys = pubkeys.to_vec();
+        let pending = self.subscriptions.pending.clone();
+        let (ready_tx, ready_rx) = oneshot::channel();
+
+        let task = tokio::spawn(async move {
+            let client = match PubsubClient::new(&ws_url).await {
+                Ok(client) => client,
+                Err(e) => {
+                    let _ = ready_tx.send(Err(e.to_string()));
+                    return;
+                }
+            };
+
+            let mut streams = Vec::with_capacity(pubkeys.len());
+            for pubkey in &pubkeys {
+                match client.account_subscribe(pubkey, Some(config.clone())).await {
+                    Ok((stream, _unsubscribe)) => {
+                        streams.push(stream.map(move |response| (pubkey, response)))
+                    }
+                    Err(e) => {
+                        let _ = ready_tx.send(Err(format!("subscribing to {pubkey}: {e}")));
+                        return;
+                    }
+                }
+            }
+            let _ = ready_tx.send(Ok(()));
+
+            let mut notifications = stream::select_all(streams);
+            while let Some((pubkey, response)) = notifications.next().await {
+                if let Some(account) = response.value.decode::<Account>() {
+                    pending
+                        .lock()
+                        .unwrap_or_else(|e| e.into_inner())
+                        .insert(*pubkey, account);
+                }
+            }
+        });
+
+        match ready_rx.await {
+            Ok(Ok(())) => {
+                self.subscriptions.tasks.push(task);
+                Ok(self)
+            }
+            Ok(Err(reason)) => Err(RpcError::Pubsub(self.redact(reason))),
+            Err(_) => Err(RpcError::Pubsub("subscription task exited".to_string())),
+        }
+    }
+
+    /// Copy buffered subscription updates into the cache.
+    ///
+    /// Returns the number of accounts that were updated.
+    pub fn sync_subscriptions(&mut self) -> usize {
+        let updates = std::mem::take(
+            &mut *self
+                .subscriptions
+                .pending
+                .lock()
+                .unwrap_or_else(|e| e.into_inner()),
+        );
+        let updated = updates.len();
+        for (pubkey, account) in updates {
+            let provenance = self.fetched_provenance();
+            self.insert_account(pubkey, account, provenance);
+        }
+        updated
+    }
+
+    /// Close all WebSocket subscriptions.
+    ///
+    /// Updates that already arrived are still applied by the next
+    /// [`sync_subscriptions`](Self::sync_subscriptions).
+    pub fn stop_subscriptions(&mut self) {
+        self.subscriptions.stop();
+    }
+}
@@ -0,0 +1,164 @@
+//! Making cloned Pyth prices fresh for Mollusk's clock.
+//!
+//! A price account fetched from mainnet was published at a slot and time already in
+//! the past, so programs that check staleness reject it once the clock moves on.
+//! Two layouts are supported: the classic oracle's price accounts and the pull
+//! oracle's `PriceUpdateV2` accounts.
+
+use {
+    crate::{
+        oracle::{self, OraclePatch},
+        RpcAccountStore, RpcError,
+    },
+    mollusk_svm::Mollusk,
+    solana_pubkey::Pubkey,
+};
+
+/// `magic` of classic Pyth accounts.
+const CLASSIC_MAGIC: u32 = 0xa1b2_c3d4;
+/// `atype` of classic price accounts, as opposed to mapping or product accounts.
+const CLASSIC_PRICE_TYPE: u32 = 3;
+/// Size of a classic price account, with its 32 publisher components.
+const CLASSIC_PRICE_LEN: usize = 3312;
+
+// Offsets of the classic price account fields this module rewrites
+const CLASSIC_LAST_SLOT: usize = 32;
+const CLASSIC_VALID_SLOT: usize = 40;
+const CLASSIC_TIMESTAMP: usize = 96;
+const CLASSIC_AGG_PRICE: usize = 208;
+const CLASSIC_AGG_CONF: usize = 216;
+const CLASSIC_AGG_PUB_SLOT: usize = 232;
+
+/// Anchor discriminator of `PriceUpdateV2`, `sha256("account:PriceUpdateV2")[..8]`.
+const PRICE_UPDATE_V2: [u8; 8] = [34, 241, 35, 99, 157, 126, 244, 205];
+/// Where the `VerificationLevel` enum starts, after the discriminator and the write
+/// authority. Its size depends on the variant, so the fields after it move.
+const V2_VERIFICATION_LEVEL: usize = 40;
+
+// Offsets in the price message, from its start
+const V2_PRICE: usize = 32;
+const V2_CONF: usize = 40;
+const V2_PUBLISH_TIME: usize = 52;
+const V2_PREV_PUBLISH_TIME: usize = 60;
+/// `posted_slot` follows the 84-byte price message.
+const V2_POSTED_SLOT: usize = 84;
+
+fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    let bytes = data.get(offset..offset + 4)?;
+    Some(u32::from_le_bytes(bytes.try_into().ok()?))
+}
+
+/// The patch making the price account `data` look published at `mollusk`'s clock,
+/// also setting the price and confidence if given.
+fn price_patch(
+    data: &[u8],
+    mollusk: &Mollusk,
+    price: Option<(i64, u64)>,
+) -> Result<OraclePatch, String> {
+    let clock = &mollusk.sysvars.clock;
+    let mut patch = OraclePatch::default();
+    let magic = read_u32(data, 0);
+    let (price_offset, conf_offset) = if magic == Some(CLASSIC_MAGIC) {
+        let atype = read_u32(data, 8);
+        if atype != Some(CLASSIC_PRICE_TYPE) {
+            return Err(format!(
+                "classic Pyth account of type {}, not a price account",
+                atype.map_or("?".to_string(), |atype| atype.to_string())
+            ));
+        }
+        if data.len() < CLASSIC_PRICE_LEN {
+            return Err(format!(
+                "classic price account of {} bytes, expected {CLASSIC_PRICE_LEN}",
+                data.len()
+            ));
+        }
+        for offset in [CLASSIC_LAST_SLOT, CLASSIC_VALID_SLOT, CLASSIC_AGG_PUB_SLOT] {
+            patch.set(offset, clock.slot.to_le_bytes());
+        }
+        patch.set(CLASSIC_TIMESTAMP, clock.unix_timestamp.to_le_bytes());
+        (CLASSIC_AGG_PRICE, CLASSIC_AGG_CONF)
+    } else if data.starts_with(&PRICE_UPDATE_V2) {
+        // `Partial { num_signatures: u8 }` or `Full`
+        let message = match data.get(V2_VERIFICATION_LEVEL) {
+            Some(0) => V2_VERIFICATION_LEVEL + 2,
+            Some(1) => V2_VERIFICATION_LEVEL + 1,
+            level => {
+                return Err(format!(
+                    "PriceUpdateV2 with unknown verification level {}",
+                    level.map_or("?".to_string(), |level| level.to_string())
+                ))
+            }
+        };
+        if data.len() < message + V2_POSTED_SLOT + 8 {
+            return Err(format!("PriceUpdateV2 of {} bytes is truncated", data.len()));
+        }
+        patch.set(message + V2_PUBLISH_TIME, clock.unix_timestamp.to_le_bytes());
+        patch.set(message + V2_PREV_PUBLISH_TIME, clock.unix_timestamp.to_le_bytes());
+        patch.set(message + V2_POSTED_SLOT, clock.slot.to_le_bytes());
+        (message + V2_PRICE, message + V2_CONF)
+    } else {
+        return Err(format!(
+            "unknown layout: magic 0x{}, discriminator {}",
+            magic.map_or("?".to_string(), |magic| format!("{magic:08x}")),
+            oracle::discriminator(data)
+        ));
+    };
+    if let Some((price, conf)) = price {
+        patch.set(price_offset, price.to_le_bytes());
+        patch.set(conf_offset, conf.to_le_bytes());
+    }
+    Ok(patch)
+}
+
+impl RpcAccountStore {
+    /// Make the cached Pyth price account `pubkey` look just published at
+    /// `mollusk`'s current clock, so staleness checks accept it.
+    ///
+    /// Classic price accounts get their last, valid and aggregate publish slots set
+    /// to the clock's slot and their timestamp to its Unix timestamp; `PriceUpdateV2`
+    /// accounts get their publish times and posted slot. The price itself is kept,
+    /// see [`set_pyth_price`](Self::set_pyth_price) to change it. Made after
+    /// [`with_synced_clock`](Self::with_synced_clock), the account agrees with the
+    /// chain's time:
+    ///
+    /// ```rust,ignore
+    /// let mut store = store.with_synced_clock(&mut mollusk).await?;
+    /// store.refresh_pyth_price(&sol_usd, &mollusk)?;
+    /// ```
+    ///
+    /// The account's provenance becomes [`Provenance::Patched`](crate::Provenance::Patched),
+    /// so [`refresh_writable`](Self::refresh_writable) doesn't undo the change.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`RpcError::AccountNotFound`] if the account isn't cached, and
+    /// [`RpcError::InvalidOracleAccount`] with its magic and discriminator if it
+    /// isn't a price account of either layout.
+    pub fn refresh_pyth_price(
+        &mut self,
+        pubkey: &Pubkey,
+        mollusk: &Mollusk,
+    ) -> Result<(), RpcError> {
+        self.patch_oracle(pubkey, "Pyth price", "refresh_pyth_price", |data| {
+            price_patch(data, mollusk, None)
+        })
+    }
+
+    /// [`refresh_pyth_price`](Self::refresh_pyth_price), also setting the aggregate
+    /// price and confidence, in the feed's own exponent.
+    ///
+    /// # Errors
+    ///
+    /// Same as [`refresh_pyth_price`](Self::refresh_pyth_price).
+    pub fn set_pyth_price(
+        &mut self,
+        pubkey: &Pubkey,
+        mollusk: &Mollusk,
+        price: i64,
+        conf: u64,
+    ) -> Result<(), RpcError> {
+        self.patch_oracle(pubkey, "Pyth price", "set_pyth_price", |data| {
+            price_patch(data, mollusk, Some((price, conf)))
+        })
+    }
+}
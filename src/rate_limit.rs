@@ -0,0 +1,43 @@
+//! Client-side pacing of outgoing RPC requests.
+
+use {
+    std::{
+        sync::Mutex,
+        time::{Duration, Instant},
+    },
+    tokio::time::sleep_until,
+};
+
+/// Spaces requests at least `1 / requests_per_second` apart.
+///
+/// Each caller reserves the next free slot before it starts waiting, so concurrent
+/// requests queue up behind each other instead of all firing at once.
+#[derive(Debug)]
+pub(crate) struct RateLimiter {
+    interval: Duration,
+    next_slot: Mutex<Instant>,
+}
+
+impl RateLimiter {
+    pub(crate) fn new(requests_per_second: u32) -> Self {
+        Self {
+            interval: Duration::from_secs(1) / requests_per_second.max(1),
+            next_slot: Mutex::new(Instant::now()),
+        }
+    }
+
+    pub(crate) fn requests_per_second(&self) -> u32 {
+        (Duration::from_secs(1).as_nanos() / self.interval.as_nanos().max(1)) as u32
+    }
+
+    /// Wait until this caller is allowed to send a request.
+    pub(crate) async fn acquire(&self) {
+        let slot = {
+            let mut next_slot = self.next_slot.lock().unwrap_or_else(|e| e.into_inner());
+            let slot = (*next_slot).max(Instant::now());
+            *next_slot = slot + self.interval;
+            slot
+        };
+        sleep_until(slot.into()).await;
+    }
+}
@@ -0,0 +1,128 @@
+//! Keeping API keys embedded in RPC URLs out of error messages.
+
+use {
+    crate::{RpcAccountStore, RpcError},
+    reqwest::Url,
+    solana_rpc_client_api::client_error::{Error as ClientError, ErrorKind as ClientErrorKind},
+};
+
+/// Replaces every secret-looking part of a URL.
+const REDACTED: &str = "...redacted...";
+
+/// Whether a path segment looks like an API key, as in `https://host/v2/<key>`:
+/// long, URL-safe and containing digits, unlike `v2` or `mainnet-beta`.
+fn is_key_like(segment: &str) -> bool {
+    segment.len() >= 16
+        && segment
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        && segment.chars().any(|c| c.is_ascii_digit())
+}
+
+/// Redact the credentials, query string and key-like path segments of `url`.
+fn redact_url(url: &mut Url) {
+    if url.password().is_some() {
+        let _ = url.set_password(Some(REDACTED));
+    }
+    if !url.username().is_empty() {
+        let _ = url.set_username(REDACTED);
+    }
+    if url.query().is_some() {
+        url.set_query(Some(REDACTED));
+    }
+    if let Some(segments) = url.path_segments() {
+        let segments: Vec<&str> = segments
+            .map(|segment| if is_key_like(segment) { REDACTED } else { segment })
+            .collect();
+        url.set_path(&segments.join("/"));
+    }
+}
+
+/// `url` with its secrets redacted, for display.
+pub(crate) fn redacted_url(url: &str) -> String {
+    match Url::parse(url) {
+        Ok(mut url) => {
+            redact_url(&mut url);
+            url.to_string()
+        }
+        Err(_) => REDACTED.to_string(),
+    }
+}
+
+/// The parts of `url` that [`redact_url`] would replace.
+fn secrets(url: &str) -> Vec<String> {
+    let Ok(url) = Url::parse(url) else {
+        // Can't tell the key apart, so treat the whole URL as one
+        return vec![url.to_string()];
+    };
+    let mut secrets: Vec<String> = url
+        .path_segments()
+        .into_iter()
+        .flatten()
+        .filter(|segment| is_key_like(segment))
+        .map(str::to_string)
+        .collect();
+    secrets.extend(url.query().map(str::to_string));
+    secrets.extend(url.password().map(str::to_string));
+    if !url.username().is_empty() {
+        secrets.push(url.username().to_string());
+    }
+    secrets.retain(|secret| !secret.is_empty());
+    secrets
+}
+
+/// Replace the secrets of `urls` wherever they appear in `text`.
+fn redact_text<'a>(mut text: String, urls: impl IntoIterator<Item = &'a str>) -> String {
+    for url in urls {
+        for secret in secrets(url) {
+            text = text.replace(&secret, REDACTED);
+        }
+    }
+    text
+}
+
+impl RpcAccountStore {
+    /// Whether errors hide the secrets in RPC URLs, on by default.
+    ///
+    /// Providers often put API keys in the URL (`https://rpc.example.com/?api-key=...`
+    /// or `https://example.rpc.com/<key>/`), and HTTP errors quote the URL they
+    /// failed on. While redaction is on, the query string, credentials and key-like
+    /// path segments of those URLs are replaced by `...redacted...` in every error the
+    /// store returns, both in `Display` and `Debug` output. Turn it off to see full
+    /// URLs when debugging connectivity locally.
+    pub fn with_url_redaction(mut self, enabled: bool) -> Self {
+        self.redact_urls = enabled;
+        self
+    }
+
+    /// Convert an error of the fetcher, redacting the URL it carries.
+    pub(crate) fn client_error(&self, mut error: ClientError) -> RpcError {
+        if self.redact_urls {
+            match error.kind.as_mut() {
+                ClientErrorKind::Reqwest(e) => {
+                    if let Some(url) = e.url_mut() {
+                        redact_url(url);
+                    }
+                }
+                ClientErrorKind::Custom(message) => {
+                    *message = self.redact(std::mem::take(message));
+                }
+                _ => {}
+            }
+        }
+        RpcError::Client(error)
+    }
+
+    /// Replace the secrets of the store's URLs in an error message.
+    pub(crate) fn redact(&self, text: String) -> String {
+        if !self.redact_urls {
+            return text;
+        }
+        let rpc_url = self.endpoint.as_ref().map(|endpoint| endpoint.url.as_str());
+        #[cfg(feature = "pubsub")]
+        let ws_url = self.subscriptions.ws_url.as_deref();
+        #[cfg(not(feature = "pubsub"))]
+        let ws_url = None;
+        redact_text(text, rpc_url.into_iter().chain(ws_url).chain(self.routed_urls()))
+    }
+}
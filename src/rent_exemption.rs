@@ -0,0 +1,77 @@
+//! Catching cloned accounts that hold less than the rent-exempt minimum.
+
+use {
+    crate::{Provenance, RpcAccountStore, WarningCode},
+    solana_pubkey::Pubkey,
+};
+
+impl RpcAccountStore {
+    /// Raise the lamports of fetched accounts that aren't rent-exempt to the minimum.
+    ///
+    /// Mainnet still has a few legacy accounts below the rent-exempt minimum, and
+    /// Mollusk's rent checks can fail instructions that touch them in ways that look
+    /// like program bugs. Every fetch records a [`WarningCode::NotRentExempt`] warning
+    /// listing such accounts either way; with this set, their lamports are topped up
+    /// to the minimum for their data length under [`rent`](Self::rent) as well, so
+    /// sync rent first if the cluster's parameters matter.
+    pub fn auto_top_up_rent(mut self) -> Self {
+        self.auto_top_up_rent = true;
+        self
+    }
+
+    /// Warn about, and optionally top up, the accounts a fetch just cached.
+    ///
+    /// Executable accounts and placeholders without data or lamports are exempt.
+    pub(crate) fn check_rent_exemption(&mut self, pubkeys: &[Pubkey]) {
+        let rent = self.rent();
+        let mut offenders = Vec::new();
+        for pubkey in pubkeys {
+            let Some(account) = self.cache.get_mut(pubkey) else {
+                continue;
+            };
+            if account.executable || (account.data.is_empty() && account.lamports == 0) {
+                continue;
+            }
+            let minimum = rent.minimum_balance(account.data.len());
+            if account.lamports >= minimum {
+                continue;
+            }
+            offenders.push((*pubkey, account.lamports, minimum));
+            if self.auto_top_up_rent {
+                account.lamports = minimum;
+                self.provenance.insert(*pubkey, Provenance::RentToppedUp);
+            }
+        }
+
+        let action = match (self.auto_top_up_rent, offenders.len()) {
+            (true, _) => "topped up to the minimum",
+            (false, 1) => "writing to it may fail Mollusk's rent checks",
+            (false, _) => "writing to them may fail Mollusk's rent checks",
+        };
+        let (message, pubkey) = match offenders.as_slice() {
+            [] => return,
+            [(pubkey, lamports, minimum)] => (
+                format!(
+                    "account holds {lamports} of the {minimum} lamports rent exemption \
+                     needs; {action}"
+                ),
+                Some(*pubkey),
+            ),
+            _ => {
+                let listed: Vec<String> = offenders
+                    .iter()
+                    .map(|(pubkey, lamports, minimum)| {
+                        format!("{pubkey} ({lamports} of {minimum} lamports)")
+                    })
+                    .collect();
+                let count = offenders.len();
+                let message = format!(
+                    "{count} accounts hold less than the rent-exempt minimum; {action}: {}",
+                    listed.join(", ")
+                );
+                (message, None)
+            }
+        };
+        self.warn(WarningCode::NotRentExempt, message, pubkey);
+    }
+}
@@ -0,0 +1,118 @@
+//! Checking up front that a scenario's accounts and programs are in place.
+
+use {
+    crate::{labels, RpcAccountStore, RpcError},
+    solana_pubkey::Pubkey,
+    std::fmt,
+};
+
+/// Why [`RpcAccountStore::require_accounts`] or
+/// [`require_programs`](RpcAccountStore::require_programs) rejected a pubkey.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum UnmetReason {
+    /// The account isn't cached.
+    NotCached,
+    /// The account is cached but isn't executable, so it can't be a program.
+    NotExecutable,
+    /// The program is cached but [`add_programs`](RpcAccountStore::add_programs)
+    /// hasn't loaded it.
+    NotLoaded,
+}
+
+impl fmt::Display for UnmetReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::NotCached => "not cached",
+            Self::NotExecutable => "not executable",
+            Self::NotLoaded => "not loaded by add_programs",
+        })
+    }
+}
+
+/// A required account or program that isn't in place.
+///
+/// See [`RpcError::RequirementsNotMet`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnmetRequirement {
+    pub pubkey: Pubkey,
+    /// See [`RpcAccountStore::label`].
+    pub label: Option<String>,
+    pub reason: UnmetReason,
+}
+
+impl fmt::Display for UnmetRequirement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let pubkey = labels::labeled(self.label.as_deref(), &self.pubkey);
+        write!(f, "{pubkey} ({})", self.reason)
+    }
+}
+
+impl RpcAccountStore {
+    /// Check that every account in `pubkeys` is cached, without any RPC request.
+    ///
+    /// Mollusk stops at the first account it's missing; this reports all of them
+    /// before anything runs:
+    ///
+    /// ```rust,ignore
+    /// store.require_accounts(&[pool, vault_a, vault_b, oracle])?;
+    /// let result = mollusk.process_instruction(&ix, &store.instruction_accounts(&ix)?);
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns [`RpcError::RequirementsNotMet`] listing each missing account, in the
+    /// order given.
+    pub fn require_accounts(&self, pubkeys: &[Pubkey]) -> Result<(), RpcError> {
+        self.require(pubkeys, |pubkey| {
+            (!self.cache.contains_key(pubkey)).then_some(UnmetReason::NotCached)
+        })
+    }
+
+    /// Like [`require_accounts`](Self::require_accounts), but also check that each
+    /// program is executable and was loaded into Mollusk by
+    /// [`add_programs`](Self::add_programs), either by this store or because Mollusk
+    /// already had it.
+    ///
+    /// Only programs the store caches are tracked: builtins such as the System
+    /// Program are reported as not cached. Use
+    /// [`verify_owners_loaded`](Self::verify_owners_loaded) to check against a
+    /// `Mollusk` directly.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`RpcError::RequirementsNotMet`] listing each program that isn't in
+    /// place and why, in the order given.
+    pub fn require_programs(&self, programs: &[Pubkey]) -> Result<(), RpcError> {
+        self.require(programs, |program| match self.cache.get(program) {
+            None => Some(UnmetReason::NotCached),
+            Some(account) if !account.executable => Some(UnmetReason::NotExecutable),
+            Some(_) if !self.loaded_programs.contains(program) => Some(UnmetReason::NotLoaded),
+            Some(_) => None,
+        })
+    }
+
+    fn require(
+        &self,
+        pubkeys: &[Pubkey],
+        check: impl Fn(&Pubkey) -> Option<UnmetReason>,
+    ) -> Result<(), RpcError> {
+        let mut seen = std::collections::HashSet::new();
+        let unmet: Vec<UnmetRequirement> = pubkeys
+            .iter()
+            .filter(|pubkey| seen.insert(**pubkey))
+            .filter_map(|pubkey| {
+                check(pubkey).map(|reason| UnmetRequirement {
+                    pubkey: *pubkey,
+                    label: self.label_of(pubkey).map(str::to_string),
+                    reason,
+                })
+            })
+            .collect();
+        if unmet.is_empty() {
+            Ok(())
+        } else {
+            Err(RpcError::RequirementsNotMet(unmet))
+        }
+    }
+}
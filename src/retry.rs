@@ -0,0 +1,105 @@
+//! Waiting out HTTP 429 (and 5xx) responses the way the provider asks us to.
+
+use {
+    crate::{error_kind::retryable_status, stats::RpcStats},
+    async_trait::async_trait,
+    http::Extensions,
+    reqwest::{
+        header::{HeaderMap, RETRY_AFTER},
+        Request, Response, StatusCode,
+    },
+    reqwest_middleware::{Middleware, Next},
+    std::{
+        sync::{Arc, Mutex},
+        time::{Duration, SystemTime},
+    },
+};
+
+/// Default cap on a single rate-limit wait.
+pub(crate) const DEFAULT_MAX_RETRY_AFTER: Duration = Duration::from_secs(60);
+
+/// Retried attempts before the 429 or 5xx is returned as an error.
+const MAX_RATE_LIMIT_RETRIES: u32 = 5;
+
+/// First wait when the provider sends no usable `Retry-After`; doubled on every
+/// further attempt.
+const INITIAL_BACKOFF: Duration = Duration::from_millis(500);
+
+/// Retries requests answered with `429 Too Many Requests` or a server error, the
+/// statuses [`RpcError::is_retryable`](crate::RpcError::is_retryable) considers
+/// transient.
+///
+/// Sleeps for exactly as long as the `Retry-After` header asks (delta-seconds or
+/// HTTP date), capped at `max_wait`, and falls back to exponential backoff when the
+/// header is missing. Once the retries are exhausted the response is turned into an
+/// error, so `HttpSender` doesn't start its own retry loop on top.
+pub(crate) struct RetryAfter {
+    pub(crate) max_wait: Duration,
+    pub(crate) stats: Arc<Mutex<RpcStats>>,
+}
+
+impl RetryAfter {
+    fn record_wait(&self, status: StatusCode, wait: Duration) {
+        let mut stats = self.stats.lock().unwrap_or_else(|e| e.into_inner());
+        stats.retries += 1;
+        if status == StatusCode::TOO_MANY_REQUESTS {
+            stats.rate_limit_waits += 1;
+            stats.rate_limit_wait_time += wait;
+        }
+    }
+}
+
+#[async_trait]
+impl Middleware for RetryAfter {
+    async fn handle(
+        &self,
+        req: Request,
+        extensions: &mut Extensions,
+        next: Next<'_>,
+    ) -> reqwest_middleware::Result<Response> {
+        let mut attempt = 0;
+        loop {
+            // Streaming bodies can't be replayed; JSON-RPC bodies always can.
+            let Some(request) = req.try_clone() else {
+                return next.run(req, extensions).await;
+            };
+            let response = next.clone().run(request, extensions).await?;
+            let status = response.status();
+            if !retryable_status(status) {
+                return Ok(response);
+            }
+            if attempt == MAX_RATE_LIMIT_RETRIES {
+                let error = response
+                    .error_for_status()
+                    .expect_err("retryable statuses are error statuses");
+                return Err(error.into());
+            }
+
+            let wait = retry_after(response.headers(), SystemTime::now())
+                .unwrap_or(INITIAL_BACKOFF * 2u32.pow(attempt))
+                .min(self.max_wait);
+            self.record_wait(status, wait);
+            #[cfg(feature = "tracing")]
+            tracing::debug!(
+                name: "rate_limited",
+                status = status.as_u16(),
+                attempt,
+                wait_ms = wait.as_millis() as u64,
+            );
+            tokio::time::sleep(wait).await;
+            attempt += 1;
+        }
+    }
+}
+
+/// Parse a `Retry-After` header given as delta-seconds or as an HTTP date.
+///
+/// Dates in the past mean "retry now".
+fn retry_after(headers: &HeaderMap, now: SystemTime) -> Option<Duration> {
+    let value = headers.get(RETRY_AFTER)?.to_str().ok()?.trim();
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+    let date = httpdate::parse_http_date(value).ok()?;
+    Some(date.duration_since(now).unwrap_or_default())
+}
@@ -0,0 +1,202 @@
+//! Fetching some accounts from another endpoint than the store's own, for tests
+//! that need accounts of several clusters.
+
+use {
+    crate::{
+        endpoint::RpcEndpoint, fetcher::AccountFetcher, redact::redacted_url, RpcAccountStore,
+    },
+    solana_commitment_config::CommitmentConfig,
+    solana_pubkey::Pubkey,
+    std::sync::Arc,
+};
+
+/// An endpoint some pubkeys are fetched from, see
+/// [`RpcAccountStore::with_endpoint_for`].
+#[derive(Clone)]
+pub(crate) struct RoutedEndpoint {
+    /// The endpoint's URL, or the name its fetcher was added under.
+    pub(crate) name: String,
+    /// The endpoint behind `fetcher`, if it was routed to by URL.
+    pub(crate) endpoint: Option<RpcEndpoint>,
+    pub(crate) fetcher: Arc<dyn AccountFetcher>,
+}
+
+/// The pubkeys of a fetch that go to the same endpoint, with their positions among
+/// the pubkeys asked for.
+pub(crate) struct Route {
+    /// Index into the routed endpoints, `None` for the store's own.
+    pub(crate) endpoint: Option<usize>,
+    pub(crate) pubkeys: Vec<Pubkey>,
+    pub(crate) positions: Vec<usize>,
+}
+
+impl RpcAccountStore {
+    /// Fetch `pubkeys` from the JSON-RPC endpoint at `url` instead of the store's
+    /// own, for tests that need accounts of two clusters, such as a protocol on
+    /// mainnet and an oracle that only publishes on devnet.
+    ///
+    /// ```rust,ignore
+    /// let store = RpcAccountStore::mainnet()
+    ///     .with_endpoint_for(&[price_feed], "https://api.devnet.solana.com")
+    ///     .from_instruction(&swap_ix) // `price_feed` from devnet, the rest from mainnet
+    ///     .await?;
+    /// ```
+    ///
+    /// Every fetch of accounts splits its `getMultipleAccounts` requests by endpoint
+    /// and caches the results together; [`Provenance::Fetched`](crate::Provenance)
+    /// records the endpoint of routed accounts. Requests count against the store's
+    /// budget and rate limit. Other requests (slots, blocks, program account lists,
+    /// simulations) and the [cluster check](Self::expect_cluster) stay on the
+    /// store's endpoint; see [`with_slot_endpoint`](Self::with_slot_endpoint) to
+    /// sync the slot with this one.
+    ///
+    /// The endpoint uses the store's commitment, but not its
+    /// [custom headers](Self::with_header), which are often credentials meant for
+    /// one provider. Routing a pubkey again moves it to the latest endpoint.
+    pub fn with_endpoint_for(self, pubkeys: &[Pubkey], url: &str) -> Self {
+        let mut endpoint = RpcEndpoint::new(
+            url.to_string(),
+            self.endpoint
+                .as_ref()
+                .map_or_else(CommitmentConfig::confirmed, |endpoint| endpoint.commitment),
+        );
+        if let Some(own) = &self.endpoint {
+            endpoint.max_retry_after = own.max_retry_after;
+        }
+        let fetcher = Arc::new(endpoint.client(self.stats.clone()));
+        self.add_route(pubkeys, url.to_string(), Some(endpoint), fetcher)
+    }
+
+    /// [`with_endpoint_for`](Self::with_endpoint_for) with a custom
+    /// [`AccountFetcher`], such as a second
+    /// [`MockFetcher`](crate::testing::MockFetcher), known as `name` in provenance
+    /// and to [`with_slot_endpoint`](Self::with_slot_endpoint).
+    pub fn with_fetcher_for(
+        self,
+        pubkeys: &[Pubkey],
+        name: &str,
+        fetcher: impl AccountFetcher + 'static,
+    ) -> Self {
+        self.add_route(pubkeys, name.to_string(), None, Arc::new(fetcher))
+    }
+
+    /// Sync the slot, and the block time and block hashes that go with it, with the
+    /// routed endpoint `name` rather than the store's own: the URL passed to
+    /// [`with_endpoint_for`](Self::with_endpoint_for) or the name passed to
+    /// [`with_fetcher_for`](Self::with_fetcher_for).
+    ///
+    /// This covers [`with_synced_slot`](Self::with_synced_slot),
+    /// [`with_synced_clock`](Self::with_synced_clock),
+    /// [`with_synced_slot_hashes`](Self::with_synced_slot_hashes) and
+    /// [`with_synced_recent_blockhashes`](Self::with_synced_recent_blockhashes).
+    /// Sysvar accounts are fetched like any account, from wherever they're routed.
+    ///
+    /// # Panics
+    ///
+    /// Panics if no endpoint was added as `name`.
+    pub fn with_slot_endpoint(mut self, name: &str) -> Self {
+        let index = self
+            .routed_endpoints
+            .iter()
+            .position(|endpoint| endpoint.name == name)
+            .unwrap_or_else(|| panic!("no endpoint was routed to as {name:?}"));
+        self.slot_endpoint = Some(index);
+        self
+    }
+
+    fn add_route(
+        mut self,
+        pubkeys: &[Pubkey],
+        name: String,
+        endpoint: Option<RpcEndpoint>,
+        fetcher: Arc<dyn AccountFetcher>,
+    ) -> Self {
+        let endpoint = RoutedEndpoint {
+            name,
+            endpoint,
+            fetcher,
+        };
+        let index = match self
+            .routed_endpoints
+            .iter()
+            .position(|routed| routed.name == endpoint.name)
+        {
+            Some(index) => {
+                self.routed_endpoints[index] = endpoint;
+                index
+            }
+            None => {
+                self.routed_endpoints.push(endpoint);
+                self.routed_endpoints.len() - 1
+            }
+        };
+        self.routes.extend(pubkeys.iter().map(|pubkey| (*pubkey, index)));
+        self
+    }
+
+    /// The fetcher that slots and blocks are requested from.
+    pub(crate) fn slot_fetcher(&self) -> &Arc<dyn AccountFetcher> {
+        self.slot_endpoint
+            .map_or(&self.fetcher, |index| &self.routed_endpoints[index].fetcher)
+    }
+
+    /// The fetcher of the route `endpoint`.
+    pub(crate) fn route_fetcher(&self, endpoint: Option<usize>) -> &Arc<dyn AccountFetcher> {
+        endpoint.map_or(&self.fetcher, |index| &self.routed_endpoints[index].fetcher)
+    }
+
+    /// `pubkeys` split by endpoint, the store's own first.
+    pub(crate) fn routes_of(&self, pubkeys: &[Pubkey]) -> Vec<Route> {
+        let mut routes = vec![Route {
+            endpoint: None,
+            pubkeys: Vec::new(),
+            positions: Vec::new(),
+        }];
+        for (position, pubkey) in pubkeys.iter().enumerate() {
+            let endpoint = self.routes.get(pubkey).copied();
+            let route = match routes.iter().position(|route| route.endpoint == endpoint) {
+                Some(route) => &mut routes[route],
+                None => {
+                    routes.push(Route {
+                        endpoint,
+                        pubkeys: Vec::new(),
+                        positions: Vec::new(),
+                    });
+                    routes.last_mut().expect("just pushed")
+                }
+            };
+            route.pubkeys.push(*pubkey);
+            route.positions.push(position);
+        }
+        routes.retain(|route| !route.pubkeys.is_empty());
+        routes
+    }
+
+    /// The endpoint `pubkey` is routed to, as shown in its provenance.
+    pub(crate) fn routed_endpoint_name(&self, pubkey: &Pubkey) -> Option<String> {
+        let endpoint = &self.routed_endpoints[*self.routes.get(pubkey)?];
+        Some(if endpoint.endpoint.is_some() && self.redact_urls {
+            redacted_url(&endpoint.name)
+        } else {
+            endpoint.name.clone()
+        })
+    }
+
+    /// URLs of the routed endpoints, whose secrets errors must not show.
+    pub(crate) fn routed_urls(&self) -> impl Iterator<Item = &str> {
+        self.routed_endpoints
+            .iter()
+            .filter(|endpoint| endpoint.endpoint.is_some())
+            .map(|endpoint| endpoint.name.as_str())
+    }
+
+    /// Rebuild the clients of the endpoints routed to by URL, for a copy counting
+    /// requests in other stats.
+    pub(crate) fn rebuild_routed_clients(&mut self) {
+        for routed in &mut self.routed_endpoints {
+            if let Some(endpoint) = &routed.endpoint {
+                routed.fetcher = Arc::new(endpoint.client(self.stats.clone()));
+            }
+        }
+    }
+}
@@ -0,0 +1,222 @@
+//! A store that can be fetched into from several tasks at once.
+
+use {
+    crate::{locate_missing, RpcAccountStore, RpcError, RpcStats},
+    futures::{
+        channel::oneshot,
+        future::{FutureExt, Shared},
+    },
+    solana_account::Account,
+    solana_instruction::Instruction,
+    solana_pubkey::Pubkey,
+    std::{
+        collections::{HashMap, HashSet},
+        fmt,
+        sync::{Mutex, MutexGuard},
+    },
+};
+
+/// Resolves once the fetch that claimed a pubkey has finished (or was dropped).
+type InFlight = Shared<oneshot::Receiver<()>>;
+
+#[derive(Default)]
+struct SharedState {
+    cache: HashMap<Pubkey, Account>,
+    in_flight: HashMap<Pubkey, InFlight>,
+}
+
+/// An [`RpcAccountStore`] that fetches through `&self`, for sharing behind an
+/// [`Arc`](std::sync::Arc) between concurrently running tests.
+///
+/// Concurrent fetches never request the same pubkey twice: a pubkey that another
+/// task is already fetching is waited on instead, and every caller then reads the
+/// shared result. If that fetch fails, waiting tasks fetch the pubkey themselves.
+///
+/// ```rust,ignore
+/// let store = Arc::new(SharedAccountStore::new(RpcAccountStore::new(rpc_url)));
+///
+/// let tasks = instructions.into_iter().map(|ix| {
+///     let store = store.clone();
+///     tokio::spawn(async move { store.fetch_instruction(&ix).await })
+/// });
+/// ```
+///
+/// Builder configuration (fixtures, concurrency, rate limits, ...) of the wrapped
+/// store applies to every fetch. Use [`into_inner`](Self::into_inner) to get the
+/// store back, e.g. to call [`add_programs`](RpcAccountStore::add_programs).
+pub struct SharedAccountStore {
+    store: RpcAccountStore,
+    state: Mutex<SharedState>,
+}
+
+impl fmt::Debug for SharedAccountStore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let state = self.state();
+        f.debug_struct("SharedAccountStore")
+            .field("store", &self.store)
+            .field("accounts_cached", &state.cache.len())
+            .field("in_flight", &state.in_flight.len())
+            .finish()
+    }
+}
+
+impl From<RpcAccountStore> for SharedAccountStore {
+    fn from(store: RpcAccountStore) -> Self {
+        Self::new(store)
+    }
+}
+
+/// Pubkeys a fetch has claimed. Dropping it (also when the fetch is cancelled)
+/// releases them and wakes everyone waiting on them.
+struct Claim<'a> {
+    state: &'a Mutex<SharedState>,
+    pubkeys: Vec<Pubkey>,
+    _done: oneshot::Sender<()>,
+}
+
+impl Drop for Claim<'_> {
+    fn drop(&mut self) {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        for pubkey in &self.pubkeys {
+            state.in_flight.remove(pubkey);
+        }
+    }
+}
+
+impl SharedAccountStore {
+    /// Wrap `store`, taking over the accounts already in its cache.
+    pub fn new(mut store: RpcAccountStore) -> Self {
+        let cache = std::mem::take(&mut store.cache);
+        Self {
+            store,
+            state: Mutex::new(SharedState {
+                cache,
+                in_flight: HashMap::new(),
+            }),
+        }
+    }
+
+    /// Fetch accounts required by an instruction.
+    pub async fn fetch_instruction(&self, instruction: &Instruction) -> Result<(), RpcError> {
+        let pubkeys: Vec<Pubkey> = instruction.accounts.iter().map(|m| m.pubkey).collect();
+        self.fetch_accounts(&pubkeys)
+            .await
+            .map_err(|e| locate_missing(&self.store, std::slice::from_ref(instruction), e))
+    }
+
+    /// Fetch accounts required by multiple instructions.
+    pub async fn fetch_instructions(&self, instructions: &[Instruction]) -> Result<(), RpcError> {
+        let pubkeys: Vec<Pubkey> = instructions
+            .iter()
+            .flat_map(|ix| ix.accounts.iter().map(|m| m.pubkey))
+            .collect();
+        self.fetch_accounts(&pubkeys)
+            .await
+            .map_err(|e| locate_missing(&self.store, instructions, e))
+    }
+
+    /// Fetch every pubkey that isn't cached yet.
+    pub async fn fetch_accounts(&self, pubkeys: &[Pubkey]) -> Result<(), RpcError> {
+        let mut pending: Vec<Pubkey> = pubkeys
+            .iter()
+            .filter(|pubkey| !self.store.synthesizes_sysvar(pubkey))
+            .copied()
+            .collect::<HashSet<_>>()
+            .into_iter()
+            .collect();
+        let mut cache_hits = 0;
+
+        while !pending.is_empty() {
+            let (claim, waits) = {
+                let mut state = self.state();
+                let mut claimed = Vec::new();
+                let mut waits = Vec::new();
+                for pubkey in pending.drain(..) {
+                    if state.cache.contains_key(&pubkey) {
+                        cache_hits += 1;
+                    } else if let Some(in_flight) = state.in_flight.get(&pubkey) {
+                        waits.push((pubkey, in_flight.clone()));
+                    } else {
+                        claimed.push(pubkey);
+                    }
+                }
+
+                let claim = (!claimed.is_empty()).then(|| {
+                    let (done, in_flight) = oneshot::channel();
+                    let in_flight = in_flight.shared();
+                    for pubkey in &claimed {
+                        state.in_flight.insert(*pubkey, in_flight.clone());
+                    }
+                    Claim {
+                        state: &self.state,
+                        pubkeys: claimed,
+                        _done: done,
+                    }
+                });
+                (claim, waits)
+            };
+
+            if let Some(claim) = claim {
+                let accounts = self.fetch_claimed(&claim.pubkeys).await?;
+                self.state().cache.extend(accounts);
+            }
+
+            // Whatever another task was fetching is either cached now or, if that
+            // fetch failed, claimed by us on the next round.
+            for (pubkey, in_flight) in waits {
+                let _ = in_flight.await;
+                pending.push(pubkey);
+            }
+        }
+
+        self.store.stats_mut().accounts_from_cache += cache_hits;
+        Ok(())
+    }
+
+    /// Resolve claimed pubkeys from fixtures, then RPC.
+    async fn fetch_claimed(&self, pubkeys: &[Pubkey]) -> Result<Vec<(Pubkey, Account)>, RpcError> {
+        let mut accounts = Vec::with_capacity(pubkeys.len());
+        let mut missing = Vec::new();
+        for pubkey in pubkeys {
+            match self.store.read_fixture(pubkey)? {
+                Some(account) => accounts.push((*pubkey, account)),
+                None => missing.push(*pubkey),
+            }
+        }
+        self.store.stats_mut().accounts_from_fixtures += accounts.len() as u64;
+
+        if !missing.is_empty() {
+            let fetched = self.store.fetch_from_rpc(&missing).await?;
+            accounts.extend(self.store.resolve_all_fetched(fetched)?);
+        }
+        Ok(accounts)
+    }
+
+    /// A copy of a cached account.
+    pub fn get_account(&self, pubkey: &Pubkey) -> Option<Account> {
+        self.state().cache.get(pubkey).cloned()
+    }
+
+    /// RPC usage recorded by the wrapped store.
+    pub fn stats(&self) -> RpcStats {
+        self.store.stats()
+    }
+
+    /// Unwrap the store, with everything fetched so far in its cache.
+    pub fn into_inner(self) -> RpcAccountStore {
+        let mut store = self.store;
+        let state = self.state.into_inner().unwrap_or_else(|e| e.into_inner());
+        for (pubkey, account) in state.cache {
+            if !store.provenance.contains_key(&pubkey) {
+                let fetched = store.fetched_provenance_of(&pubkey);
+                store.provenance.insert(pubkey, fetched);
+            }
+            store.cache.insert(pubkey, account);
+        }
+        store
+    }
+
+    fn state(&self) -> MutexGuard<'_, SharedState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
@@ -0,0 +1,231 @@
+//! Comparing Mollusk runs against the cluster's `simulateTransaction`.
+
+use {
+    crate::{fetcher::TransactionSimulation, RpcAccountStore, RpcError},
+    mollusk_svm::{result::InstructionResult, Mollusk},
+    solana_account::Account,
+    solana_instruction::Instruction,
+    solana_pubkey::Pubkey,
+    std::fmt,
+};
+
+/// One way a Mollusk run differed from the cluster's simulation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Divergence {
+    /// One side succeeded and the other failed. Errors are `None` on success.
+    Status {
+        mollusk: Option<String>,
+        simulation: Option<String>,
+    },
+    /// The first log line that differs, `None` where one side's logs ended.
+    Logs {
+        line: usize,
+        mollusk: Option<String>,
+        simulation: Option<String>,
+    },
+    ComputeUnits { mollusk: u64, simulation: u64 },
+    /// An account ended up in a different state. `None` means closed.
+    Account {
+        pubkey: Pubkey,
+        mollusk: Option<Account>,
+        simulation: Option<Account>,
+    },
+}
+
+/// The result of [`RpcAccountStore::compare_with_simulation`].
+#[derive(Debug)]
+pub struct DivergenceReport {
+    /// The Mollusk run of the instructions.
+    pub mollusk: InstructionResult,
+    /// The cluster's simulation, or why it couldn't be run.
+    pub simulation: Result<TransactionSimulation, String>,
+    /// Everything that differs; empty if the simulation couldn't be run.
+    pub divergences: Vec<Divergence>,
+}
+
+impl DivergenceReport {
+    /// Whether the simulation ran and Mollusk disagreed with it.
+    pub fn diverged(&self) -> bool {
+        !self.divergences.is_empty()
+    }
+}
+
+impl fmt::Display for DivergenceReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Err(reason) = &self.simulation {
+            return write!(f, "simulation unavailable ({reason}), compared nothing");
+        }
+        if self.divergences.is_empty() {
+            return f.write_str("Mollusk matches the simulation");
+        }
+
+        writeln!(f, "Mollusk diverges from the simulation:")?;
+        for divergence in &self.divergences {
+            match divergence {
+                Divergence::Status {
+                    mollusk,
+                    simulation,
+                } => writeln!(
+                    f,
+                    "  status: Mollusk {}, simulation {}",
+                    mollusk.as_deref().unwrap_or("succeeded"),
+                    simulation.as_deref().unwrap_or("succeeded"),
+                )?,
+                Divergence::Logs {
+                    line,
+                    mollusk,
+                    simulation,
+                } => writeln!(
+                    f,
+                    "  log line {line}: Mollusk {:?}, simulation {:?}",
+                    mollusk.as_deref().unwrap_or("<end>"),
+                    simulation.as_deref().unwrap_or("<end>"),
+                )?,
+                Divergence::ComputeUnits {
+                    mollusk,
+                    simulation,
+                } => writeln!(f, "  compute units: Mollusk {mollusk}, simulation {simulation}")?,
+                Divergence::Account {
+                    pubkey,
+                    mollusk,
+                    simulation,
+                } => writeln!(
+                    f,
+                    "  account {pubkey}: Mollusk {}, simulation {}",
+                    describe(mollusk.as_ref()),
+                    describe(simulation.as_ref()),
+                )?,
+            }
+        }
+        Ok(())
+    }
+}
+
+fn describe(account: Option<&Account>) -> String {
+    match account {
+        Some(account) => format!(
+            "{} lamports, {} bytes, owner {}",
+            account.lamports,
+            account.data.len(),
+            account.owner
+        ),
+        None => "closed".to_string(),
+    }
+}
+
+/// Closed accounts come back from Mollusk with zero lamports and from RPC as `None`.
+fn open(account: Option<&Account>) -> Option<&Account> {
+    account.filter(|account| account.lamports > 0)
+}
+
+impl RpcAccountStore {
+    /// Run `instructions` through Mollusk and through the cluster's
+    /// `simulateTransaction` (paid by `payer`, without signature checks) and report
+    /// every difference: status, logs, compute units and the post-state of each
+    /// account the instructions use.
+    ///
+    /// The instructions' accounts must be cached. Logs are only compared when
+    /// `mollusk.logger` is set. If the simulation can't be run (network failure,
+    /// offline store, exhausted budget, ...), the report holds the Mollusk result and
+    /// the reason instead of failing.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`RpcError::AccountNotCached`] if an account hasn't been fetched.
+    pub async fn compare_with_simulation(
+        &self,
+        mollusk: &Mollusk,
+        instructions: &[Instruction],
+        payer: &Pubkey,
+    ) -> Result<DivergenceReport, RpcError> {
+        let accounts = self.instructions_accounts(mollusk, instructions)?;
+        let logged_before = mollusk
+            .logger
+            .as_ref()
+            .map(|logger| logger.borrow().get_recorded_content().len());
+        let result = mollusk.process_instruction_chain(instructions, &accounts);
+        let mollusk_logs = mollusk
+            .logger
+            .as_ref()
+            .zip(logged_before)
+            .map(|(logger, before)| logger.borrow().get_recorded_content()[before..].to_vec());
+
+        let pubkeys: Vec<Pubkey> = result
+            .resulting_accounts
+            .iter()
+            .map(|(pubkey, _)| *pubkey)
+            .collect();
+        let simulation = match self.simulate(instructions, payer, &pubkeys).await {
+            Ok(simulation) => simulation,
+            Err(e) => {
+                return Ok(DivergenceReport {
+                    mollusk: result,
+                    simulation: Err(e.to_string()),
+                    divergences: Vec::new(),
+                })
+            }
+        };
+
+        let mut divergences = Vec::new();
+        let mollusk_err = result.raw_result.as_ref().err().map(ToString::to_string);
+        if mollusk_err.is_some() != simulation.err.is_some() {
+            divergences.push(Divergence::Status {
+                mollusk: mollusk_err,
+                simulation: simulation.err.clone(),
+            });
+        }
+
+        if let Some(mollusk_logs) = mollusk_logs {
+            let lines = mollusk_logs.len().max(simulation.logs.len());
+            let differs = |line: &usize| mollusk_logs.get(*line) != simulation.logs.get(*line);
+            if let Some(line) = (0..lines).find(differs) {
+                divergences.push(Divergence::Logs {
+                    line,
+                    mollusk: mollusk_logs.get(line).cloned(),
+                    simulation: simulation.logs.get(line).cloned(),
+                });
+            }
+        }
+
+        if let Some(units) = simulation.units_consumed {
+            if units != result.compute_units_consumed {
+                divergences.push(Divergence::ComputeUnits {
+                    mollusk: result.compute_units_consumed,
+                    simulation: units,
+                });
+            }
+        }
+
+        for ((pubkey, account), simulated) in
+            result.resulting_accounts.iter().zip(&simulation.accounts)
+        {
+            let (account, simulated) = (open(Some(account)), open(simulated.as_ref()));
+            if account != simulated {
+                divergences.push(Divergence::Account {
+                    pubkey: *pubkey,
+                    mollusk: account.cloned(),
+                    simulation: simulated.cloned(),
+                });
+            }
+        }
+
+        Ok(DivergenceReport {
+            mollusk: result,
+            simulation: Ok(simulation),
+            divergences,
+        })
+    }
+
+    async fn simulate(
+        &self,
+        instructions: &[Instruction],
+        payer: &Pubkey,
+        accounts: &[Pubkey],
+    ) -> Result<TransactionSimulation, RpcError> {
+        self.begin_request("simulateTransaction").await?;
+        self.fetcher
+            .simulate_transaction(instructions, payer, accounts)
+            .await
+            .map_err(|e| self.client_error(e))
+    }
+}
@@ -0,0 +1,133 @@
+//! Deterministic JSON of accounts, for snapshot-testing tools such as `insta`.
+
+use {
+    crate::RpcAccountStore,
+    base64::{engine::general_purpose::STANDARD as BASE64, Engine},
+    mollusk_svm::result::InstructionResult,
+    serde_json::{Map, Value},
+    solana_account::Account,
+    solana_pubkey::Pubkey,
+};
+
+/// What [`RpcAccountStore::snapshot_json`] shows of account data, see
+/// [`RpcAccountStore::with_snapshot_options`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SnapshotOptions {
+    /// Leading bytes of data shown as hex in `data_preview`, which ends with `…` if
+    /// the data is longer. 0 leaves the preview out.
+    pub preview_bytes: usize,
+    /// Whether `data` holds the full data as base64.
+    pub full_data: bool,
+}
+
+impl Default for SnapshotOptions {
+    fn default() -> Self {
+        Self {
+            preview_bytes: 32,
+            full_data: true,
+        }
+    }
+}
+
+fn account_json(pubkey: &Pubkey, account: Option<&Account>, options: &SnapshotOptions) -> Value {
+    let Some(account) = account else {
+        return Value::Null;
+    };
+    // Keys are inserted in sorted order, whether or not `serde_json` preserves it
+    let mut entry = Map::new();
+    if options.full_data {
+        entry.insert("data".into(), BASE64.encode(&account.data).into());
+    }
+    entry.insert("data_len".into(), account.data.len().into());
+    if options.preview_bytes > 0 {
+        let mut preview: String = account
+            .data
+            .iter()
+            .take(options.preview_bytes)
+            .map(|byte| format!("{byte:02x}"))
+            .collect();
+        if account.data.len() > options.preview_bytes {
+            preview.push('…');
+        }
+        entry.insert("data_preview".into(), preview.into());
+    }
+    entry.insert("executable".into(), account.executable.into());
+    entry.insert("lamports".into(), account.lamports.into());
+    entry.insert("owner".into(), account.owner.to_string().into());
+    entry.insert("pubkey".into(), pubkey.to_string().into());
+    Value::Object(entry)
+}
+
+impl RpcAccountStore {
+    /// Show account data in [`snapshot_json`](Self::snapshot_json) and
+    /// [`snapshot_result_json`](Self::snapshot_result_json) as `options` say, e.g.
+    /// only as a preview to keep snapshots of large accounts short.
+    pub fn with_snapshot_options(mut self, options: SnapshotOptions) -> Self {
+        self.snapshot_options = options;
+        self
+    }
+
+    /// The cached `pubkeys` as JSON that only changes when the accounts do, for
+    /// snapshot tests:
+    ///
+    /// ```rust,ignore
+    /// insta::assert_json_snapshot!(store.snapshot_json(&[pool, vault]));
+    /// ```
+    ///
+    /// ```text
+    /// {
+    ///   "pool": {
+    ///     "data": "AQAAAAAAAAA...",
+    ///     "data_len": 324,
+    ///     "data_preview": "0100000000000000…",
+    ///     "executable": false,
+    ///     "lamports": 3647040,
+    ///     "owner": "9W959DqEETiGZocYWCQPaJ6sBmUzgfxXfqGeTEdp3aQP",
+    ///     "pubkey": "58oQChx4yWmvKdwLLZzBi4ChoCc2fqCUWBkwMihLYQo2"
+    ///   },
+    ///   ...
+    /// }
+    /// ```
+    ///
+    /// Accounts are keyed by label, or by pubkey if they have none or share their
+    /// label with an account before them, and keys are sorted. `rent_epoch`, which
+    /// differs between fetches of the same account, is left out. Accounts that
+    /// aren't cached are `null`. What `data` and `data_preview` hold depends on
+    /// [`with_snapshot_options`](Self::with_snapshot_options).
+    pub fn snapshot_json(&self, pubkeys: &[Pubkey]) -> Value {
+        self.snapshot_entries(pubkeys, |pubkey| self.cache.get(pubkey))
+    }
+
+    /// [`snapshot_json`](Self::snapshot_json) of the accounts as `result` left them.
+    /// Accounts the instruction didn't receive are shown as cached.
+    pub fn snapshot_result_json(&self, result: &InstructionResult, pubkeys: &[Pubkey]) -> Value {
+        let account = |pubkey: &Pubkey| {
+            result
+                .resulting_accounts
+                .iter()
+                .find(|(resulting, _)| resulting == pubkey)
+                .map(|(_, account)| account)
+                .or_else(|| self.cache.get(pubkey))
+        };
+        self.snapshot_entries(pubkeys, account)
+    }
+
+    fn snapshot_entries<'a>(
+        &self,
+        pubkeys: &[Pubkey],
+        account: impl Fn(&Pubkey) -> Option<&'a Account>,
+    ) -> Value {
+        let mut entries = Vec::new();
+        for pubkey in pubkeys {
+            let key = match self.label_of(pubkey) {
+                Some(label) if !entries.iter().any(|(key, _)| key == label) => label.to_string(),
+                _ => pubkey.to_string(),
+            };
+            if !entries.iter().any(|(existing, _)| *existing == key) {
+                entries.push((key, account_json(pubkey, account(pubkey), &self.snapshot_options)));
+            }
+        }
+        entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+        Value::Object(entries.into_iter().collect())
+    }
+}
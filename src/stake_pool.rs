@@ -0,0 +1,130 @@
+//! Cloning an SPL stake pool with every account its instructions touch.
+//!
+//! `spl-stake-pool` is built on `solana-pubkey` 4, so its keys are converted through
+//! their bytes.
+
+use {
+    crate::{RpcAccountStore, RpcError, WarningCode},
+    solana_borsh::v1::try_from_slice_unchecked,
+    solana_pubkey::Pubkey,
+    spl_stake_pool::{
+        find_stake_program_address, find_transient_stake_program_address,
+        state::{StakePool, StakeStatus, ValidatorList},
+    },
+    std::num::NonZeroU32,
+};
+
+/// The store's pubkey of an `spl-stake-pool` one.
+fn pubkey(key: impl AsRef<[u8]>) -> Pubkey {
+    Pubkey::try_from(key.as_ref()).expect("pubkeys have 32 bytes")
+}
+
+impl RpcAccountStore {
+    /// Fetch and cache the SPL stake pool `pool` with everything it refers to: its
+    /// program, validator list, reserve stake, pool mint and manager fee account, and
+    /// for each validator of the list its vote account, validator stake account and
+    /// transient stake account.
+    ///
+    /// ```rust,ignore
+    /// let store = RpcAccountStore::mainnet()
+    ///     .with_stake_pool(&jito_pool)
+    ///     .await?
+    ///     .add_programs(&mut mollusk)
+    ///     .await?;
+    /// ```
+    ///
+    /// Stake accounts are derived from the list's seeds, and only looked up when the
+    /// list says they hold stake: transient stake accounts exist while stake is
+    /// being moved, and validator stake accounts go away once a validator is
+    /// removed. The ones that should exist but don't, and closed vote accounts, are
+    /// recorded as [`WarningCode::StakePoolAccountMissing`] warnings, so pools caught
+    /// between epochs still load. The pool's withdraw and deposit authorities are
+    /// PDAs without accounts and aren't fetched.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`RpcError::InvalidStakePool`] if the pool or its validator list
+    /// doesn't decode, and the fetch's errors, such as [`RpcError::AccountNotFound`]
+    /// for the pool or an account it names.
+    pub async fn with_stake_pool(mut self, pool: &Pubkey) -> Result<Self, RpcError> {
+        self.fetch_pubkeys(std::slice::from_ref(pool)).await?;
+        let account = self
+            .cache
+            .get(pool)
+            .ok_or(RpcError::AccountNotFound(*pool))?;
+        let program_id = account.owner;
+        let invalid = |pubkey: &Pubkey, reason: String| RpcError::InvalidStakePool {
+            pubkey: *pubkey,
+            reason,
+        };
+        let state = try_from_slice_unchecked::<StakePool>(&account.data)
+            .map_err(|e| invalid(pool, e.to_string()))?;
+        if !state.is_valid() {
+            return Err(invalid(pool, "not an initialized stake pool".to_string()));
+        }
+
+        let validator_list = pubkey(state.validator_list);
+        self.fetch_accounts(&[
+            program_id,
+            validator_list,
+            pubkey(state.reserve_stake),
+            pubkey(state.pool_mint),
+            pubkey(state.manager_fee_account),
+        ])
+        .await?;
+        let list = try_from_slice_unchecked::<ValidatorList>(&self.cache[&validator_list].data)
+            .map_err(|e| invalid(&validator_list, e.to_string()))?;
+        if !list.header.is_valid() {
+            return Err(invalid(
+                &validator_list,
+                "not an initialized validator list".to_string(),
+            ));
+        }
+
+        // Accounts of each validator, and what they are for the warnings
+        let program = program_id.to_bytes().into();
+        let pool_address = pool.to_bytes().into();
+        let mut expected = Vec::new();
+        for validator in &list.validators {
+            let vote = pubkey(validator.vote_account_address);
+            expected.push((vote, "vote account".to_string()));
+            let status = StakeStatus::try_from(validator.status)
+                .map_err(|e| invalid(&validator_list, e.to_string()))?;
+            if !matches!(
+                status,
+                StakeStatus::DeactivatingTransient | StakeStatus::ReadyForRemoval
+            ) {
+                let seed = NonZeroU32::new(u32::from(validator.validator_seed_suffix));
+                let (stake, _) = find_stake_program_address(
+                    &program,
+                    &validator.vote_account_address,
+                    &pool_address,
+                    seed,
+                );
+                expected.push((pubkey(stake), format!("validator stake account of {vote}")));
+            }
+            if u64::from(validator.transient_stake_lamports) > 0 {
+                let (transient, _) = find_transient_stake_program_address(
+                    &program,
+                    &validator.vote_account_address,
+                    &pool_address,
+                    u64::from(validator.transient_seed_suffix),
+                );
+                expected.push((pubkey(transient), format!("transient stake account of {vote}")));
+            }
+        }
+
+        let pubkeys: Vec<Pubkey> = expected.iter().map(|(pubkey, _)| *pubkey).collect();
+        self.fetch_existing(&pubkeys).await?;
+        for (pubkey, what) in expected {
+            if !self.cache.contains_key(&pubkey) {
+                self.warn(
+                    WarningCode::StakePoolAccountMissing,
+                    format!("Stake pool {pool} lists a {what} that doesn't exist"),
+                    Some(pubkey),
+                );
+            }
+        }
+        Ok(self)
+    }
+}
@@ -0,0 +1,69 @@
+//! Counters describing the RPC traffic a store has generated.
+
+use std::{collections::BTreeMap, fmt, time::Duration};
+
+/// RPC usage recorded by an [`RpcAccountStore`](crate::RpcAccountStore).
+///
+/// Obtained through [`RpcAccountStore::stats`](crate::RpcAccountStore::stats) and
+/// cleared with [`RpcAccountStore::reset_stats`](crate::RpcAccountStore::reset_stats).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RpcStats {
+    /// Requests issued, keyed by JSON-RPC method name.
+    pub requests: BTreeMap<&'static str, u64>,
+    /// Accounts requested from the RPC endpoint (including ones that didn't exist).
+    pub accounts_fetched: u64,
+    /// Account lookups served from the cache without an RPC request.
+    pub accounts_from_cache: u64,
+    /// Accounts replayed from the fixture directory without an RPC request.
+    pub accounts_from_fixtures: u64,
+    /// Total bytes of account data downloaded.
+    pub bytes_downloaded: u64,
+    /// Requests that were retried after a transient failure.
+    pub retries: u64,
+    /// Times a `429 Too Many Requests` response was waited out.
+    pub rate_limit_waits: u64,
+    /// Total time spent in those waits.
+    pub rate_limit_wait_time: Duration,
+}
+
+impl RpcStats {
+    /// Total number of requests across all methods.
+    pub fn total_requests(&self) -> u64 {
+        self.requests.values().sum()
+    }
+
+    /// Number of requests issued for a single method, e.g. `"getMultipleAccounts"`.
+    pub fn requests_for(&self, method: &str) -> u64 {
+        self.requests.get(method).copied().unwrap_or(0)
+    }
+
+    pub(crate) fn record_request(&mut self, method: &'static str) {
+        *self.requests.entry(method).or_default() += 1;
+    }
+}
+
+impl fmt::Display for RpcStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} RPC requests", self.total_requests())?;
+        if !self.requests.is_empty() {
+            let per_method = self
+                .requests
+                .iter()
+                .map(|(method, count)| format!("{method}: {count}"))
+                .collect::<Vec<_>>();
+            write!(f, " ({})", per_method.join(", "))?;
+        }
+        write!(
+            f,
+            ", {} accounts fetched, {} served from cache, {} replayed from fixtures, \
+             {} bytes downloaded, {} retries, {} rate-limit waits ({:?})",
+            self.accounts_fetched,
+            self.accounts_from_cache,
+            self.accounts_from_fixtures,
+            self.bytes_downloaded,
+            self.retries,
+            self.rate_limit_waits,
+            self.rate_limit_wait_time
+        )
+    }
+}
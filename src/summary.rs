@@ -0,0 +1,188 @@
+//! A readable overview of the cache, grouped by owner program.
+
+use {
+    crate::{Provenance, RpcAccountStore},
+    solana_account::Account,
+    solana_pubkey::Pubkey,
+    solana_sdk_ids::{
+        address_lookup_table, bpf_loader, bpf_loader_deprecated, bpf_loader_upgradeable,
+        compute_budget, config, feature, loader_v4, native_loader, stake, system_program,
+        sysvar, vote,
+    },
+    std::{collections::BTreeMap, fmt},
+};
+
+/// Accounts listed per owner unless the summary is verbose.
+const MAX_LISTED_PER_OWNER: usize = 5;
+
+pub(crate) const SPL_TOKEN: Pubkey =
+    Pubkey::from_str_const("TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA");
+pub(crate) const SPL_TOKEN_2022: Pubkey =
+    Pubkey::from_str_const("TokenzQdBNbLqP5VEhdkAS6EPFLC1PHnBqCXEpPxuEb");
+pub(crate) const SPL_ASSOCIATED_TOKEN: Pubkey =
+    Pubkey::from_str_const("ATokenGPvbdGVxr1b2hvZbsiqW5xWH25efTNsLJA8knL");
+
+/// A name for well-known owner programs.
+pub(crate) fn program_label(owner: &Pubkey) -> Option<&'static str> {
+    let labels = [
+        (system_program::ID, "System Program"),
+        (native_loader::ID, "Native Loader"),
+        (bpf_loader_deprecated::ID, "BPF Loader v1"),
+        (bpf_loader::ID, "BPF Loader v2"),
+        (bpf_loader_upgradeable::ID, "BPF Loader v3"),
+        (loader_v4::ID, "Loader v4"),
+        (sysvar::ID, "Sysvar"),
+        (vote::ID, "Vote Program"),
+        (stake::ID, "Stake Program"),
+        (config::ID, "Config Program"),
+        (feature::ID, "Feature Program"),
+        (compute_budget::ID, "Compute Budget Program"),
+        (address_lookup_table::ID, "Address Lookup Table Program"),
+        (SPL_TOKEN, "SPL Token"),
+        (SPL_TOKEN_2022, "SPL Token-2022"),
+        (SPL_ASSOCIATED_TOKEN, "SPL Associated Token Account"),
+    ];
+    labels
+        .iter()
+        .find(|(id, _)| id == owner)
+        .map(|(_, label)| *label)
+}
+
+pub(crate) fn provenance_label(provenance: Option<&Provenance>) -> &'static str {
+    match provenance {
+        Some(Provenance::Mocked) => "mocked",
+        Some(Provenance::Fetched { .. }) => "fetched",
+        Some(Provenance::Fixture { .. }) => "fixture",
+        Some(Provenance::Synthesized(_)) => "synthesized",
+        Some(Provenance::ResultApplied { .. }) => "result",
+        Some(Provenance::RentToppedUp) => "topped up",
+        Some(Provenance::Override) => "override",
+        Some(Provenance::Fallback) => "fallback",
+        Some(Provenance::Patched(_)) => "patched",
+        // Inserted into `cache` directly
+        None => "inserted",
+    }
+}
+
+fn plural(count: usize, noun: &str) -> String {
+    if count == 1 {
+        format!("{count} {noun}")
+    } else {
+        format!("{count} {noun}s")
+    }
+}
+
+/// The cache of an [`RpcAccountStore`], grouped by owner program; see
+/// [`RpcAccountStore::summary`].
+pub struct StoreSummary<'a> {
+    store: &'a RpcAccountStore,
+    verbose: bool,
+}
+
+impl StoreSummary<'_> {
+    /// List every account instead of the first few per owner.
+    pub fn verbose(mut self) -> Self {
+        self.verbose = true;
+        self
+    }
+}
+
+impl fmt::Display for StoreSummary<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut owners: BTreeMap<Pubkey, Vec<(&Pubkey, &Account)>> = BTreeMap::new();
+        for (pubkey, account) in &self.store.cache {
+            owners.entry(account.owner).or_default().push((pubkey, account));
+        }
+        if owners.is_empty() {
+            return f.write_str("no accounts cached");
+        }
+
+        write!(
+            f,
+            "{} under {}",
+            plural(self.store.cache.len(), "account"),
+            plural(owners.len(), "owner")
+        )?;
+        for (owner, mut accounts) in owners {
+            accounts.sort_by_key(|(pubkey, _)| **pubkey);
+
+            match self.store.label_of(&owner) {
+                Some(_) => write!(f, "\n{}", self.store.display_pubkey(&owner))?,
+                None => {
+                    write!(f, "\n{owner}")?;
+                    if let Some(label) = program_label(&owner) {
+                        write!(f, " ({label})")?;
+                    }
+                }
+            }
+            let bytes: usize = accounts.iter().map(|(_, account)| account.data.len()).sum();
+            write!(f, ": {}, {bytes} bytes", plural(accounts.len(), "account"))?;
+            let executable = accounts.iter().filter(|(_, account)| account.executable).count();
+            if executable > 0 {
+                write!(f, ", {executable} executable")?;
+            }
+
+            let mut origins: BTreeMap<&str, usize> = BTreeMap::new();
+            for (pubkey, _) in &accounts {
+                *origins
+                    .entry(provenance_label(self.store.provenance.get(pubkey)))
+                    .or_default() += 1;
+            }
+            let origins: Vec<String> = origins
+                .iter()
+                .map(|(origin, count)| format!("{count} {origin}"))
+                .collect();
+            write!(f, " ({})", origins.join(", "))?;
+
+            let listed = if self.verbose {
+                accounts.len()
+            } else {
+                MAX_LISTED_PER_OWNER
+            };
+            for (pubkey, account) in accounts.iter().take(listed) {
+                write!(
+                    f,
+                    "\n  {}: {} bytes, ",
+                    self.store.display_pubkey(pubkey),
+                    account.data.len(),
+                )?;
+                match self.store.provenance.get(pubkey) {
+                    Some(provenance) => write!(f, "{provenance}")?,
+                    None => f.write_str(provenance_label(None))?,
+                }
+                if account.executable {
+                    f.write_str(", executable")?;
+                }
+            }
+            if accounts.len() > listed {
+                write!(f, "\n  ... and {} more", accounts.len() - listed)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl RpcAccountStore {
+    /// An overview of the cache, grouped by owner program.
+    ///
+    /// Each owner lists its account count, total data size, executables and where
+    /// the accounts came from (mocked, fetched, fixture, ...), followed by its first
+    /// few accounts. `Display` for the store prints the same; use
+    /// [`verbose`](StoreSummary::verbose) to list every account:
+    ///
+    /// ```rust,ignore
+    /// println!("{}", store.summary().verbose());
+    /// ```
+    pub fn summary(&self) -> StoreSummary<'_> {
+        StoreSummary {
+            store: self,
+            verbose: false,
+        }
+    }
+}
+
+impl fmt::Display for RpcAccountStore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.summary().fmt(f)
+    }
+}
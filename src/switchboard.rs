@@ -0,0 +1,207 @@
+//! Making cloned Switchboard feeds fresh for Mollusk's clock.
+//!
+//! Both the v2 `AggregatorAccountData` and the on-demand `PullFeedAccountData`
+//! layouts are supported. Offsets are from the start of the account, discriminator
+//! included.
+
+use {
+    crate::{
+        oracle::{self, OraclePatch},
+        RpcAccountStore, RpcError,
+    },
+    mollusk_svm::Mollusk,
+    rust_decimal::Decimal,
+    solana_pubkey::Pubkey,
+};
+
+/// Anchor discriminator of v2 aggregators, `sha256("account:AggregatorAccountData")[..8]`.
+const AGGREGATOR: [u8; 8] = [217, 230, 65, 101, 201, 162, 27, 125];
+/// Size of a v2 aggregator.
+const AGGREGATOR_LEN: usize = 3851;
+/// Where the aggregator's `latest_confirmed_round` starts.
+const LATEST_ROUND: usize = 341;
+
+// Offsets in an `AggregatorRound`, from its start. Rounds are packed, so a
+// `SwitchboardDecimal` takes 20 bytes: an `i128` mantissa and a `u32` scale.
+const ROUND_NUM_SUCCESS: usize = 0;
+const ROUND_OPEN_SLOT: usize = 9;
+const ROUND_OPEN_TIMESTAMP: usize = 17;
+const ROUND_RESULT: usize = 25;
+const ROUND_STD_DEVIATION: usize = 45;
+const ROUND_MIN_RESPONSE: usize = 65;
+const ROUND_MAX_RESPONSE: usize = 85;
+const ROUND_MEDIANS: usize = 617;
+const SWITCHBOARD_DECIMAL_LEN: usize = 20;
+/// Oracle responses a round holds at most.
+const ROUND_MAX_ORACLES: usize = 16;
+
+/// Anchor discriminator of on-demand feeds, `sha256("account:PullFeedAccountData")[..8]`.
+const PULL_FEED: [u8; 8] = [196, 27, 108, 196, 10, 215, 219, 40];
+/// Size of an on-demand feed.
+const PULL_FEED_LEN: usize = 3208;
+/// Decimal places of on-demand values, which are `i128`s.
+const PULL_FEED_DECIMALS: u32 = 18;
+
+// An on-demand feed starts with its 32 `OracleSubmission`s, 64 bytes each
+const SUBMISSIONS: usize = 8;
+const SUBMISSION_LEN: usize = 64;
+const MAX_SUBMISSIONS: usize = 32;
+const SUBMISSION_SLOT: usize = 32;
+const SUBMISSION_LANDED_AT: usize = 40;
+const SUBMISSION_VALUE: usize = 48;
+const LAST_UPDATE_TIMESTAMP: usize = 2216;
+/// The Unix timestamp of each submission, in an array of their own.
+const SUBMISSION_TIMESTAMPS: usize = 2952;
+
+// The feed's `CurrentResult`
+const RESULT: usize = 2264;
+const RESULT_VALUE: usize = RESULT;
+const RESULT_STD_DEV: usize = RESULT + 16;
+const RESULT_MEAN: usize = RESULT + 32;
+const RESULT_RANGE: usize = RESULT + 48;
+const RESULT_MIN_VALUE: usize = RESULT + 64;
+const RESULT_MAX_VALUE: usize = RESULT + 80;
+const RESULT_SLOT: usize = RESULT + 104;
+const RESULT_MIN_SLOT: usize = RESULT + 112;
+const RESULT_MAX_SLOT: usize = RESULT + 120;
+
+/// The patch making the aggregator `data` fresh at `mollusk`'s clock, setting every
+/// response of the latest round to `value` if given.
+fn aggregator_patch(
+    data: &[u8],
+    mollusk: &Mollusk,
+    value: Option<Decimal>,
+) -> Result<OraclePatch, String> {
+    if data.len() < AGGREGATOR_LEN {
+        return Err(format!(
+            "v2 aggregator of {} bytes, expected {AGGREGATOR_LEN}",
+            data.len()
+        ));
+    }
+    let clock = &mollusk.sysvars.clock;
+    let mut patch = OraclePatch::default();
+    patch.set(LATEST_ROUND + ROUND_OPEN_SLOT, clock.slot.to_le_bytes());
+    patch.set(LATEST_ROUND + ROUND_OPEN_TIMESTAMP, clock.unix_timestamp.to_le_bytes());
+    if let Some(value) = value {
+        let decimal = |patch: &mut OraclePatch, offset: usize, value: Decimal| {
+            patch.set(offset, value.mantissa().to_le_bytes());
+            patch.set(offset + 16, value.scale().to_le_bytes());
+        };
+        for offset in [ROUND_RESULT, ROUND_MIN_RESPONSE, ROUND_MAX_RESPONSE] {
+            decimal(&mut patch, LATEST_ROUND + offset, value);
+        }
+        decimal(&mut patch, LATEST_ROUND + ROUND_STD_DEVIATION, Decimal::ZERO);
+        // The median each oracle reported, which the result is the median of
+        let num_success = LATEST_ROUND + ROUND_NUM_SUCCESS;
+        let responses = u32::from_le_bytes([
+            data[num_success],
+            data[num_success + 1],
+            data[num_success + 2],
+            data[num_success + 3],
+        ]) as usize;
+        for response in 0..responses.min(ROUND_MAX_ORACLES) {
+            let offset = LATEST_ROUND + ROUND_MEDIANS + response * SWITCHBOARD_DECIMAL_LEN;
+            decimal(&mut patch, offset, value);
+        }
+    }
+    Ok(patch)
+}
+
+/// The patch making the on-demand feed `data` fresh at `mollusk`'s clock, setting
+/// every submission to `value` if given.
+fn pull_feed_patch(
+    data: &[u8],
+    mollusk: &Mollusk,
+    value: Option<Decimal>,
+) -> Result<OraclePatch, String> {
+    if data.len() < PULL_FEED_LEN {
+        return Err(format!(
+            "on-demand feed of {} bytes, expected {PULL_FEED_LEN}",
+            data.len()
+        ));
+    }
+    let value = value
+        .map(|value| {
+            let value = value.round_dp(PULL_FEED_DECIMALS);
+            10i128
+                .checked_pow(PULL_FEED_DECIMALS - value.scale())
+                .and_then(|factor| value.mantissa().checked_mul(factor))
+                .ok_or_else(|| format!("{value} doesn't fit the feed's 18-decimal values"))
+        })
+        .transpose()?;
+
+    let clock = &mollusk.sysvars.clock;
+    let mut patch = OraclePatch::default();
+    for submission in 0..MAX_SUBMISSIONS {
+        let start = SUBMISSIONS + submission * SUBMISSION_LEN;
+        // Submissions end at the first one without a slot
+        if data[start + SUBMISSION_SLOT..start + SUBMISSION_SLOT + 8] == [0; 8] {
+            break;
+        }
+        patch.set(start + SUBMISSION_SLOT, clock.slot.to_le_bytes());
+        patch.set(start + SUBMISSION_LANDED_AT, clock.slot.to_le_bytes());
+        patch.set(SUBMISSION_TIMESTAMPS + submission * 8, clock.unix_timestamp.to_le_bytes());
+        if let Some(value) = value {
+            patch.set(start + SUBMISSION_VALUE, value.to_le_bytes());
+        }
+    }
+    for offset in [RESULT_SLOT, RESULT_MIN_SLOT, RESULT_MAX_SLOT] {
+        patch.set(offset, clock.slot.to_le_bytes());
+    }
+    patch.set(LAST_UPDATE_TIMESTAMP, clock.unix_timestamp.to_le_bytes());
+    if let Some(value) = value {
+        for offset in [RESULT_VALUE, RESULT_MEAN, RESULT_MIN_VALUE, RESULT_MAX_VALUE] {
+            patch.set(offset, value.to_le_bytes());
+        }
+        patch.set(RESULT_STD_DEV, 0i128.to_le_bytes());
+        patch.set(RESULT_RANGE, 0i128.to_le_bytes());
+    }
+    Ok(patch)
+}
+
+impl RpcAccountStore {
+    /// Make the cached Switchboard feed `pubkey` look just updated at `mollusk`'s
+    /// current clock, so staleness checks accept it, and set its value to
+    /// `new_value` if given.
+    ///
+    /// For a v2 aggregator, the latest confirmed round gets the clock's slot and
+    /// Unix timestamp as its open slot and timestamp. For an on-demand feed, every
+    /// oracle submission and the current result get the clock's slot, and the
+    /// submissions and the last update its timestamp. A new value replaces the
+    /// result and every response it was computed from, with a standard deviation
+    /// and range of zero, so the feed's statistics stay consistent:
+    ///
+    /// ```rust,ignore
+    /// let mut store = store.with_synced_clock(&mut mollusk).await?;
+    /// store.refresh_switchboard_feed(&sol_usd, &mollusk, Some(Decimal::new(14512, 2)))?;
+    /// ```
+    ///
+    /// As with [`refresh_pyth_price`](Self::refresh_pyth_price), the account's
+    /// provenance becomes [`Provenance::Patched`](crate::Provenance::Patched).
+    ///
+    /// # Errors
+    ///
+    /// Returns [`RpcError::AccountNotFound`] if the account isn't cached, and
+    /// [`RpcError::InvalidOracleAccount`] with its discriminator if it is of
+    /// neither layout, or if `new_value` doesn't fit an on-demand feed's `i128`
+    /// with 18 decimals.
+    pub fn refresh_switchboard_feed(
+        &mut self,
+        pubkey: &Pubkey,
+        mollusk: &Mollusk,
+        new_value: Option<Decimal>,
+    ) -> Result<(), RpcError> {
+        self.patch_oracle(pubkey, "Switchboard feed", "refresh_switchboard_feed", |data| {
+            if data.starts_with(&AGGREGATOR) {
+                aggregator_patch(data, mollusk, new_value)
+            } else if data.starts_with(&PULL_FEED) {
+                pull_feed_patch(data, mollusk, new_value)
+            } else {
+                Err(format!(
+                    "unknown layout: discriminator {}",
+                    oracle::discriminator(data)
+                ))
+            }
+        })
+    }
+}
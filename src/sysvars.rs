@@ -0,0 +1,368 @@
+//! Syncing Mollusk's sysvars from the cluster's sysvar accounts.
+
+use {
+    crate::{RpcAccountStore, RpcError, WarningCode},
+    futures::{stream, StreamExt, TryStreamExt},
+    mollusk_svm::Mollusk,
+    serde::de::DeserializeOwned,
+    solana_account::Account,
+    solana_epoch_rewards::EpochRewards,
+    solana_epoch_schedule::EpochSchedule,
+    solana_hash::Hash,
+    solana_instruction::{BorrowedAccountMeta, BorrowedInstruction, Instruction},
+    solana_instructions_sysvar::{construct_instructions_data, store_current_index_checked},
+    solana_last_restart_slot::LastRestartSlot,
+    solana_pubkey::Pubkey,
+    solana_rent::Rent,
+    solana_sdk_ids::sysvar,
+    solana_slot_hashes::{SlotHashes, MAX_ENTRIES as SLOT_HASHES_MAX_ENTRIES},
+    solana_stake_interface::stake_history::StakeHistory,
+};
+
+/// How many times `with_synced_slot_hashes` doubles its `getBlocks` window, from
+/// twice the depth up to 32 times.
+const MAX_WINDOW_DOUBLINGS: u32 = 4;
+
+/// Sysvars that `instruction_accounts` builds from Mollusk rather than the cache.
+pub(crate) const SYNTHESIZED_SYSVARS: [Pubkey; 8] = [
+    sysvar::clock::ID,
+    sysvar::epoch_rewards::ID,
+    sysvar::epoch_schedule::ID,
+    sysvar::instructions::ID,
+    sysvar::last_restart_slot::ID,
+    sysvar::rent::ID,
+    sysvar::slot_hashes::ID,
+    sysvar::stake_history::ID,
+];
+
+/// The account Mollusk would see for sysvar `pubkey` while running `instructions`.
+pub(crate) fn synthesized_sysvar(
+    mollusk: &Mollusk,
+    instructions: &[Instruction],
+    pubkey: &Pubkey,
+) -> Option<Account> {
+    let sysvars = &mollusk.sysvars;
+    let (_, account) = match *pubkey {
+        sysvar::clock::ID => sysvars.keyed_account_for_clock_sysvar(),
+        sysvar::epoch_rewards::ID => sysvars.keyed_account_for_epoch_rewards_sysvar(),
+        sysvar::epoch_schedule::ID => sysvars.keyed_account_for_epoch_schedule_sysvar(),
+        sysvar::last_restart_slot::ID => sysvars.keyed_account_for_last_restart_slot_sysvar(),
+        sysvar::rent::ID => sysvars.keyed_account_for_rent_sysvar(),
+        sysvar::slot_hashes::ID => sysvars.keyed_account_for_slot_hashes_sysvar(),
+        sysvar::stake_history::ID => sysvars.keyed_account_for_stake_history_sysvar(),
+        sysvar::instructions::ID => {
+            let data = instructions_sysvar_data(instructions, 0);
+            return Some(instructions_sysvar(&sysvars.rent, data));
+        }
+        _ => return None,
+    };
+    Some(account)
+}
+
+/// The `Instructions` sysvar account of a transaction made of `instructions`, as
+/// seen while instruction `current_index` executes.
+///
+/// The data uses the on-chain layout that `load_instruction_at_checked` and
+/// `load_current_index_checked` read. Mollusk doesn't provide this sysvar;
+/// [`instruction_accounts`](RpcAccountStore::instruction_accounts) builds it for a
+/// transaction holding just the one instruction, so replace that to test others:
+///
+/// ```rust,ignore
+/// let mut accounts = store.instruction_accounts(&mollusk, &ix)?;
+/// let sysvar = instructions_sysvar_account(&[ed25519_ix, ix.clone()], 1);
+/// accounts.retain(|(pubkey, _)| *pubkey != sysvar.0);
+/// accounts.push(sysvar);
+/// ```
+pub fn instructions_sysvar_account(
+    instructions: &[Instruction],
+    current_index: u16,
+) -> (Pubkey, Account) {
+    let data = instructions_sysvar_data(instructions, current_index);
+    (sysvar::instructions::ID, instructions_sysvar(&Rent::default(), data))
+}
+
+fn instructions_sysvar(rent: &Rent, data: Vec<u8>) -> Account {
+    Account {
+        lamports: rent.minimum_balance(data.len()),
+        data,
+        owner: sysvar::ID,
+        executable: false,
+        rent_epoch: 0,
+    }
+}
+
+fn instructions_sysvar_data(instructions: &[Instruction], current_index: u16) -> Vec<u8> {
+    let borrowed: Vec<BorrowedInstruction> = instructions
+        .iter()
+        .map(|ix| BorrowedInstruction {
+            program_id: &ix.program_id,
+            accounts: ix
+                .accounts
+                .iter()
+                .map(|meta| BorrowedAccountMeta {
+                    pubkey: &meta.pubkey,
+                    is_signer: meta.is_signer,
+                    is_writable: meta.is_writable,
+                })
+                .collect(),
+            data: &ix.data,
+        })
+        .collect();
+    let mut data = construct_instructions_data(&borrowed);
+    store_current_index_checked(&mut data, current_index)
+        .expect("the instructions sysvar ends with room for the current index");
+    data
+}
+
+impl RpcAccountStore {
+    /// Fetch sysvars listed as account metas from the cluster like any other account.
+    ///
+    /// By default the store never fetches the sysvars Mollusk simulates (`Clock`,
+    /// `Rent`, `Instructions`, ...): [`instruction_accounts`](Self::instruction_accounts)
+    /// builds them from Mollusk's current sysvars, so they agree with what the
+    /// runtime reports through syscalls, including after the `with_synced_*` methods.
+    /// With this option metas get the cluster's accounts as of the fetch; Mollusk's
+    /// own sysvar values are unaffected.
+    pub fn fetch_real_sysvars(mut self) -> Self {
+        self.real_sysvars = true;
+        self
+    }
+
+    /// Whether `pubkey` is a sysvar built from Mollusk instead of fetched.
+    pub(crate) fn synthesizes_sysvar(&self, pubkey: &Pubkey) -> bool {
+        !self.real_sysvars && SYNTHESIZED_SYSVARS.contains(pubkey)
+    }
+
+    /// Install the cluster's `Rent` sysvar into Mollusk.
+    ///
+    /// Fetches the rent sysvar account (through the cache, so fixtures apply) and
+    /// uses its parameters for Mollusk's rent checks and for [`rent`](Self::rent). If
+    /// the account can't be parsed, `Rent::default()` is used instead and a
+    /// [`WarningCode::RentSysvarUnparsable`] warning is recorded.
+    pub async fn with_synced_rent(mut self, mollusk: &mut Mollusk) -> Result<Self, RpcError> {
+        let rent = match self.fetch_sysvar::<Rent>(&sysvar::rent::ID, "Rent").await {
+            Ok(rent) => rent,
+            Err(RpcError::InvalidSysvar { .. }) => {
+                #[cfg(feature = "tracing")]
+                tracing::warn!(name: "rent_sysvar_unparsable", "using Rent::default()");
+                self.warn(
+                    WarningCode::RentSysvarUnparsable,
+                    "Rent sysvar can't be parsed, using Rent::default()".to_string(),
+                    Some(sysvar::rent::ID),
+                );
+                Rent::default()
+            }
+            Err(e) => return Err(e),
+        };
+
+        mollusk.sysvars.rent = rent.clone();
+        self.rent = Some(rent);
+        Ok(self)
+    }
+
+    /// The rent parameters the store assumes: the cluster's after
+    /// [`with_synced_rent`](Self::with_synced_rent), `Rent::default()` before.
+    pub fn rent(&self) -> Rent {
+        self.rent.clone().unwrap_or_default()
+    }
+
+    /// Install the cluster's `EpochSchedule` sysvar into Mollusk.
+    ///
+    /// The clock's epoch and leader schedule epoch are recomputed for its current
+    /// slot, so the order relative to [`with_synced_slot`](Self::with_synced_slot)
+    /// doesn't matter.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`RpcError::InvalidSysvar`] if the account can't be parsed.
+    pub async fn with_synced_epoch_schedule(
+        mut self,
+        mollusk: &mut Mollusk,
+    ) -> Result<Self, RpcError> {
+        mollusk.sysvars.epoch_schedule = self
+            .fetch_sysvar::<EpochSchedule>(&sysvar::epoch_schedule::ID, "EpochSchedule")
+            .await?;
+        crate::warp_clock(mollusk, mollusk.sysvars.clock.slot);
+        Ok(self)
+    }
+
+    /// Install the cluster's `StakeHistory` sysvar into Mollusk.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`RpcError::InvalidSysvar`] if the account can't be parsed.
+    pub async fn with_synced_stake_history(
+        mut self,
+        mollusk: &mut Mollusk,
+    ) -> Result<Self, RpcError> {
+        mollusk.sysvars.stake_history = self
+            .fetch_sysvar::<StakeHistory>(&sysvar::stake_history::ID, "StakeHistory")
+            .await?;
+        Ok(self)
+    }
+
+    /// Fill Mollusk's `SlotHashes` sysvar with the blockhashes of the `depth` most
+    /// recent finalized blocks, newest first as on-chain.
+    ///
+    /// Warps to the current slot like [`with_synced_slot`](Self::with_synced_slot)
+    /// first, since warping afterwards would overwrite the entries. Skipped slots have
+    /// no entry. Every block costs a `getBlock` request (subject to the rate limit and
+    /// concurrency settings), so keep `depth` small; 32 covers most recency checks.
+    /// At most 512 entries are kept, like on-chain.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`RpcError::TooFewBlocks`] if the `32 * depth` slots before the cluster
+    /// slot hold fewer than `depth` finalized blocks.
+    pub async fn with_synced_slot_hashes(
+        mut self,
+        mollusk: &mut Mollusk,
+        depth: usize,
+    ) -> Result<Self, RpcError> {
+        let slot = self.cluster_slot().await?;
+        let depth = depth.min(SLOT_HASHES_MAX_ENTRIES);
+
+        // Skipped slots leave gaps, so widen the range until it holds enough blocks, up
+        // to a bound rather than listing the whole chain when blocks aren't available.
+        let newest = slot.saturating_sub(1);
+        let mut window = depth as u64 * 2;
+        let mut doublings = 0;
+        let mut slots = loop {
+            let start = newest.saturating_sub(window);
+            self.begin_request("getBlocks").await?;
+            let slots = self
+                .slot_fetcher()
+                .get_blocks(start, newest)
+                .await
+                .map_err(|e| self.client_error(e))?;
+            if slots.len() >= depth || start == 0 {
+                break slots;
+            }
+            if doublings == MAX_WINDOW_DOUBLINGS {
+                return Err(RpcError::TooFewBlocks {
+                    depth,
+                    found: slots.len(),
+                    slots: window,
+                });
+            }
+            window *= 2;
+            doublings += 1;
+        };
+        let slots = slots.split_off(slots.len().saturating_sub(depth));
+
+        let requests: Vec<_> = slots
+            .into_iter()
+            .map(|slot| self.fetch_block_hash(slot))
+            .collect();
+        let slot_hashes: Vec<(u64, Hash)> = stream::iter(requests)
+            .buffered(self.concurrency)
+            .try_collect()
+            .await?;
+
+        crate::warp_clock(mollusk, slot);
+        mollusk.sysvars.slot_hashes = SlotHashes::new(&slot_hashes);
+        Ok(self)
+    }
+
+    /// Install the cluster's `EpochRewards` sysvar (partitioned rewards status) into
+    /// Mollusk.
+    ///
+    /// Clusters that don't have the sysvar account leave Mollusk's default in place,
+    /// with a [`WarningCode::SysvarMissing`] warning.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`RpcError::InvalidSysvar`] if the account exists but can't be parsed.
+    pub async fn with_synced_epoch_rewards(
+        mut self,
+        mollusk: &mut Mollusk,
+    ) -> Result<Self, RpcError> {
+        if let Some(epoch_rewards) = self
+            .fetch_optional_sysvar::<EpochRewards>(&sysvar::epoch_rewards::ID, "EpochRewards")
+            .await?
+        {
+            mollusk.sysvars.epoch_rewards = epoch_rewards;
+        }
+        Ok(self)
+    }
+
+    /// Install the cluster's `LastRestartSlot` sysvar into Mollusk.
+    ///
+    /// Like [`with_synced_epoch_rewards`](Self::with_synced_epoch_rewards), a missing
+    /// sysvar account only records a warning.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`RpcError::InvalidSysvar`] if the account exists but can't be parsed.
+    pub async fn with_synced_last_restart_slot(
+        mut self,
+        mollusk: &mut Mollusk,
+    ) -> Result<Self, RpcError> {
+        if let Some(last_restart_slot) = self
+            .fetch_optional_sysvar::<LastRestartSlot>(
+                &sysvar::last_restart_slot::ID,
+                "LastRestartSlot",
+            )
+            .await?
+        {
+            mollusk.sysvars.last_restart_slot = last_restart_slot;
+        }
+        Ok(self)
+    }
+
+    async fn fetch_block_hash(&self, slot: u64) -> Result<(u64, Hash), RpcError> {
+        self.begin_request("getBlock").await?;
+        let hash = self
+            .slot_fetcher()
+            .get_block_hash(slot)
+            .await
+            .map_err(|e| self.client_error(e))?;
+        Ok((slot, hash))
+    }
+
+    /// Fetch a sysvar account through the cache and deserialize it.
+    async fn fetch_sysvar<T: DeserializeOwned>(
+        &mut self,
+        id: &Pubkey,
+        name: &'static str,
+    ) -> Result<T, RpcError> {
+        self.fetch_pubkeys(&[*id]).await?;
+        let data = self.cache.get(id).map(|account| &account.data[..]).unwrap_or_default();
+        bincode::deserialize(data).map_err(|e| RpcError::InvalidSysvar {
+            sysvar: name,
+            reason: e.to_string(),
+        })
+    }
+
+    /// Like [`fetch_sysvar`](Self::fetch_sysvar), but `None` if the cluster doesn't
+    /// have the sysvar account.
+    async fn fetch_optional_sysvar<T: DeserializeOwned>(
+        &mut self,
+        id: &Pubkey,
+        name: &'static str,
+    ) -> Result<Option<T>, RpcError> {
+        let value = match self.fetch_sysvar(id, name).await {
+            Ok(value) => Some(value),
+            Err(RpcError::AccountNotFound(pubkey)) if pubkey == *id => None,
+            // With `allow_missing_accounts` a missing account is cached empty, in lenient
+            // mode it isn't cached at all
+            Err(RpcError::InvalidSysvar { .. })
+                if self.cache.get(id).is_none_or(|account| account.data.is_empty()) =>
+            {
+                None
+            }
+            Err(e) => return Err(e),
+        };
+
+        if value.is_none() {
+            #[cfg(feature = "tracing")]
+            tracing::warn!(name: "sysvar_missing", sysvar = name, "keeping Mollusk's default");
+            self.warn(
+                WarningCode::SysvarMissing,
+                format!("{} sysvar not found, keeping Mollusk's default", name),
+                Some(*id),
+            );
+        }
+        Ok(value)
+    }
+}
@@ -0,0 +1,467 @@
+//! Test doubles for exercising the store without a network.
+//!
+//! ```rust,ignore
+//! use mollusk_on_demand::{testing::MockFetcher, RpcAccountStore};
+//!
+//! let fetcher = MockFetcher::new().with_account(pubkey, account);
+//! let store = RpcAccountStore::new_with_fetcher(fetcher.clone())
+//!     .from_instruction(&instruction)
+//!     .await?;
+//!
+//! assert_eq!(fetcher.calls().len(), 1);
+//! ```
+
+use {
+    crate::{
+        fetcher::{AccountFetcher, ProgramAccountsPage, TransactionSimulation},
+        token::token_account_base,
+    },
+    async_trait::async_trait,
+    solana_account::Account,
+    solana_commitment_config::{CommitmentConfig, CommitmentLevel},
+    solana_hash::Hash,
+    solana_instruction::Instruction,
+    solana_pubkey::Pubkey,
+    solana_rpc_client_api::{
+        client_error::{ErrorKind as ClientErrorKind, Result as ClientResult},
+        request::RpcError,
+    },
+    solana_sdk_ids::vote,
+    std::{
+        collections::{BTreeMap, HashMap},
+        sync::{Arc, Mutex, MutexGuard},
+        time::Duration,
+    },
+};
+
+/// Orders commitment levels from the least to the most confirmed.
+fn rank(commitment: CommitmentLevel) -> u8 {
+    match commitment {
+        CommitmentLevel::Processed => 0,
+        CommitmentLevel::Confirmed => 1,
+        CommitmentLevel::Finalized => 2,
+    }
+}
+
+/// A request received by a [`MockFetcher`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FetcherCall {
+    GetMultipleAccounts(Vec<Pubkey>),
+    GetMultipleAccountsAt {
+        pubkeys: Vec<Pubkey>,
+        commitment: CommitmentLevel,
+    },
+    GetSlot,
+    GetBlockTime(u64),
+    GetGenesisHash,
+    GetBlocks {
+        start_slot: u64,
+        end_slot: u64,
+    },
+    GetBlock(u64),
+    GetProgramAccounts(Pubkey),
+    GetProgramAccountsPage {
+        program_id: Pubkey,
+        cursor: Option<String>,
+    },
+    SimulateTransaction {
+        instructions: Vec<Instruction>,
+        payer: Pubkey,
+    },
+    GetVoteAccounts,
+    GetProgramAccountsByMemcmp {
+        program_id: Pubkey,
+        offset: usize,
+        bytes: Vec<u8>,
+    },
+    GetTokenAccountsByOwner {
+        owner: Pubkey,
+        program_id: Pubkey,
+    },
+}
+
+#[derive(Debug, Default)]
+struct MockState {
+    accounts: HashMap<Pubkey, Account>,
+    /// Accounts only served at a commitment up to the given one.
+    unconfirmed: HashMap<Pubkey, (CommitmentLevel, Account)>,
+    slot: u64,
+    block_time: Option<i64>,
+    genesis_hash: Hash,
+    blocks: BTreeMap<u64, Hash>,
+    simulation: Option<TransactionSimulation>,
+    vote_stakes: HashMap<Pubkey, u64>,
+    calls: Vec<FetcherCall>,
+    latency: Duration,
+    page_size: Option<usize>,
+    in_flight: usize,
+    max_in_flight: usize,
+}
+
+/// An [`AccountFetcher`] that serves accounts from an in-memory map and records
+/// every call it receives.
+///
+/// Clones share the same state, so keep a clone around to inspect the recorded
+/// calls (or mutate the served accounts) after handing one to the store.
+#[derive(Debug, Clone, Default)]
+pub struct MockFetcher {
+    state: Arc<Mutex<MockState>>,
+}
+
+impl MockFetcher {
+    /// Create a mock that serves no accounts and reports slot 0 and an all-zero
+    /// genesis hash.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Serve `account` at `pubkey`.
+    pub fn with_account(self, pubkey: Pubkey, account: Account) -> Self {
+        self.set_account(pubkey, account);
+        self
+    }
+
+    /// Serve all of the given accounts.
+    pub fn with_accounts(self, accounts: impl IntoIterator<Item = (Pubkey, Account)>) -> Self {
+        self.state().accounts.extend(accounts);
+        self
+    }
+
+    /// Serve `account` at `pubkey` only to `get_multiple_accounts_at` requests at
+    /// `commitment` or a lower one, like a node that hasn't confirmed the
+    /// transaction that created it yet. `with_account_at(CommitmentLevel::Processed,
+    /// ..)` hides it from requests at `confirmed`, including `get_multiple_accounts`.
+    pub fn with_account_at(
+        self,
+        commitment: CommitmentLevel,
+        pubkey: Pubkey,
+        account: Account,
+    ) -> Self {
+        self.state().unconfirmed.insert(pubkey, (commitment, account));
+        self
+    }
+
+    /// Report `slot` from `get_slot`.
+    pub fn with_slot(self, slot: u64) -> Self {
+        self.set_slot(slot);
+        self
+    }
+
+    /// Report `unix_timestamp` from `get_block_time`, whatever the slot.
+    ///
+    /// Without this, `get_block_time` fails as if the node had no time for the slot.
+    pub fn with_block_time(self, unix_timestamp: i64) -> Self {
+        self.state().block_time = Some(unix_timestamp);
+        self
+    }
+
+    /// Report `genesis_hash` from `get_genesis_hash`, e.g.
+    /// `Cluster::Devnet.genesis_hash()` to pose as devnet.
+    pub fn with_genesis_hash(self, genesis_hash: Hash) -> Self {
+        self.state().genesis_hash = genesis_hash;
+        self
+    }
+
+    /// Report a block with `blockhash` at `slot` from `get_blocks` and
+    /// `get_block_hash`. Slots without a block count as skipped.
+    pub fn with_block(self, slot: u64, blockhash: Hash) -> Self {
+        self.state().blocks.insert(slot, blockhash);
+        self
+    }
+
+    /// Support `simulate_transaction`, reporting `simulation`'s error, logs and
+    /// compute units.
+    ///
+    /// The post-state of the requested accounts is whatever the mock serves at the
+    /// time, so changing a served account after fetching it makes the simulation
+    /// disagree with a local run. Without this, simulating fails.
+    pub fn with_simulation(self, simulation: TransactionSimulation) -> Self {
+        self.state().simulation = Some(simulation);
+        self
+    }
+
+    /// Report `stake` lamports activated on the vote account `pubkey` from
+    /// `get_vote_accounts`.
+    ///
+    /// `get_vote_accounts` lists every served account owned by the Vote program,
+    /// with no stake unless given here.
+    pub fn with_vote_stake(self, pubkey: Pubkey, stake: u64) -> Self {
+        self.state().vote_stakes.insert(pubkey, stake);
+        self
+    }
+
+    /// Delay every response by `latency`, to simulate a remote endpoint.
+    pub fn with_latency(self, latency: Duration) -> Self {
+        self.state().latency = latency;
+        self
+    }
+
+    /// Support paginated program account queries, serving at most `page_size`
+    /// accounts per page.
+    ///
+    /// Without this the mock behaves like a provider that only offers
+    /// `getProgramAccounts`.
+    pub fn with_pagination(self, page_size: usize) -> Self {
+        self.state().page_size = Some(page_size.max(1));
+        self
+    }
+
+    /// Insert or replace a served account.
+    pub fn set_account(&self, pubkey: Pubkey, account: Account) {
+        self.state().accounts.insert(pubkey, account);
+    }
+
+    /// Stop serving an account, as if it had been closed on-chain.
+    pub fn remove_account(&self, pubkey: &Pubkey) {
+        let mut state = self.state();
+        state.accounts.remove(pubkey);
+        state.unconfirmed.remove(pubkey);
+    }
+
+    /// Change the slot reported from `get_slot`.
+    pub fn set_slot(&self, slot: u64) {
+        self.state().slot = slot;
+    }
+
+    /// All calls received so far, in order.
+    pub fn calls(&self) -> Vec<FetcherCall> {
+        self.state().calls.clone()
+    }
+
+    /// Every pubkey requested through `get_multiple_accounts`, in order.
+    pub fn requested_pubkeys(&self) -> Vec<Pubkey> {
+        self.state()
+            .calls
+            .iter()
+            .filter_map(|call| match call {
+                FetcherCall::GetMultipleAccounts(pubkeys) => Some(pubkeys.clone()),
+                _ => None,
+            })
+            .flatten()
+            .collect()
+    }
+
+    /// The highest number of requests that were in flight at the same time.
+    pub fn max_in_flight(&self) -> usize {
+        self.state().max_in_flight
+    }
+
+    /// Forget all recorded calls.
+    pub fn clear_calls(&self) {
+        self.state().calls.clear();
+    }
+
+    /// Served accounts owned by `program_id`, in pubkey order.
+    fn program_accounts(&self, program_id: &Pubkey) -> Vec<(Pubkey, Account)> {
+        let mut accounts: Vec<_> = self
+            .state()
+            .accounts
+            .iter()
+            .filter(|(_, account)| account.owner == *program_id)
+            .map(|(pubkey, account)| (*pubkey, account.clone()))
+            .collect();
+        accounts.sort_unstable_by_key(|(pubkey, _)| *pubkey);
+        accounts
+    }
+
+    fn state(&self) -> MutexGuard<'_, MockState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Record `call` and hold it "in flight" for the configured latency.
+    async fn respond(&self, call: FetcherCall) {
+        let latency = {
+            let mut state = self.state();
+            state.calls.push(call);
+            state.in_flight += 1;
+            state.max_in_flight = state.max_in_flight.max(state.in_flight);
+            state.latency
+        };
+        if !latency.is_zero() {
+            tokio::time::sleep(latency).await;
+        }
+        self.state().in_flight -= 1;
+    }
+}
+
+#[async_trait]
+impl AccountFetcher for MockFetcher {
+    async fn get_multiple_accounts(
+        &self,
+        pubkeys: &[Pubkey],
+    ) -> ClientResult<Vec<Option<Account>>> {
+        self.respond(FetcherCall::GetMultipleAccounts(pubkeys.to_vec()))
+            .await;
+        let state = self.state();
+        Ok(pubkeys
+            .iter()
+            .map(|pubkey| state.accounts.get(pubkey).cloned())
+            .collect())
+    }
+
+    async fn get_multiple_accounts_at(
+        &self,
+        pubkeys: &[Pubkey],
+        commitment: CommitmentConfig,
+    ) -> ClientResult<Vec<Option<Account>>> {
+        self.respond(FetcherCall::GetMultipleAccountsAt {
+            pubkeys: pubkeys.to_vec(),
+            commitment: commitment.commitment,
+        })
+        .await;
+        let state = self.state();
+        Ok(pubkeys
+            .iter()
+            .map(|pubkey| {
+                state.accounts.get(pubkey).cloned().or_else(|| {
+                    let (up_to, account) = state.unconfirmed.get(pubkey)?;
+                    (rank(commitment.commitment) <= rank(*up_to)).then(|| account.clone())
+                })
+            })
+            .collect())
+    }
+
+    async fn get_slot(&self) -> ClientResult<u64> {
+        self.respond(FetcherCall::GetSlot).await;
+        Ok(self.state().slot)
+    }
+
+    async fn get_block_time(&self, slot: u64) -> ClientResult<i64> {
+        self.respond(FetcherCall::GetBlockTime(slot)).await;
+        // Like the RPC client when the node returns null for the slot.
+        self.state().block_time.ok_or_else(|| {
+            ClientErrorKind::RpcError(RpcError::ForUser(format!("Block Not Found: slot={slot}")))
+                .into()
+        })
+    }
+
+    async fn get_genesis_hash(&self) -> ClientResult<Hash> {
+        self.respond(FetcherCall::GetGenesisHash).await;
+        Ok(self.state().genesis_hash)
+    }
+
+    async fn get_blocks(&self, start_slot: u64, end_slot: u64) -> ClientResult<Vec<u64>> {
+        self.respond(FetcherCall::GetBlocks {
+            start_slot,
+            end_slot,
+        })
+        .await;
+        Ok(self
+            .state()
+            .blocks
+            .range(start_slot..=end_slot)
+            .map(|(slot, _)| *slot)
+            .collect())
+    }
+
+    async fn get_block_hash(&self, slot: u64) -> ClientResult<Hash> {
+        self.respond(FetcherCall::GetBlock(slot)).await;
+        self.state().blocks.get(&slot).copied().ok_or_else(|| {
+            ClientErrorKind::Custom(format!("Slot {slot} was skipped, or missing")).into()
+        })
+    }
+
+    async fn get_program_accounts(
+        &self,
+        program_id: &Pubkey,
+    ) -> ClientResult<Vec<(Pubkey, Account)>> {
+        self.respond(FetcherCall::GetProgramAccounts(*program_id))
+            .await;
+        Ok(self.program_accounts(program_id))
+    }
+
+    async fn get_program_accounts_by_memcmp(
+        &self,
+        program_id: &Pubkey,
+        offset: usize,
+        bytes: &[u8],
+    ) -> ClientResult<Vec<(Pubkey, Account)>> {
+        self.respond(FetcherCall::GetProgramAccountsByMemcmp {
+            program_id: *program_id,
+            offset,
+            bytes: bytes.to_vec(),
+        })
+        .await;
+        let mut accounts = self.program_accounts(program_id);
+        accounts.retain(|(_, account)| {
+            account.data.get(offset..offset + bytes.len()) == Some(bytes)
+        });
+        Ok(accounts)
+    }
+
+    async fn get_token_accounts_by_owner(
+        &self,
+        owner: &Pubkey,
+        program_id: &Pubkey,
+    ) -> ClientResult<Vec<(Pubkey, Account)>> {
+        self.respond(FetcherCall::GetTokenAccountsByOwner {
+            owner: *owner,
+            program_id: *program_id,
+        })
+        .await;
+        let mut accounts = self.program_accounts(program_id);
+        accounts.retain(|(_, account)| {
+            token_account_base(account).is_ok_and(|state| state.owner == *owner)
+        });
+        Ok(accounts)
+    }
+
+    async fn get_vote_accounts(&self) -> ClientResult<Vec<(Pubkey, u64)>> {
+        self.respond(FetcherCall::GetVoteAccounts).await;
+        let vote_accounts = self.program_accounts(&vote::ID);
+        let state = self.state();
+        Ok(vote_accounts
+            .into_iter()
+            .map(|(pubkey, _)| (pubkey, state.vote_stakes.get(&pubkey).copied().unwrap_or(0)))
+            .collect())
+    }
+
+    async fn simulate_transaction(
+        &self,
+        instructions: &[Instruction],
+        payer: &Pubkey,
+        accounts: &[Pubkey],
+    ) -> ClientResult<TransactionSimulation> {
+        self.respond(FetcherCall::SimulateTransaction {
+            instructions: instructions.to_vec(),
+            payer: *payer,
+        })
+        .await;
+        let state = self.state();
+        let simulation = state.simulation.clone().ok_or_else(|| {
+            ClientErrorKind::Custom("simulateTransaction is not supported".to_string())
+        })?;
+        Ok(TransactionSimulation {
+            accounts: accounts
+                .iter()
+                .map(|pubkey| state.accounts.get(pubkey).cloned())
+                .collect(),
+            ..simulation
+        })
+    }
+
+    async fn get_program_accounts_page(
+        &self,
+        program_id: &Pubkey,
+        cursor: Option<&str>,
+        limit: usize,
+    ) -> ClientResult<Option<ProgramAccountsPage>> {
+        self.respond(FetcherCall::GetProgramAccountsPage {
+            program_id: *program_id,
+            cursor: cursor.map(str::to_string),
+        })
+        .await;
+        let Some(page_size) = self.state().page_size else {
+            return Ok(None);
+        };
+
+        // The cursor is simply the offset of the next page.
+        let start = cursor.and_then(|cursor| cursor.parse().ok()).unwrap_or(0);
+        let accounts = self.program_accounts(program_id);
+        let end = accounts.len().min(start + page_size.min(limit).max(1));
+        Ok(Some(ProgramAccountsPage {
+            cursor: (end < accounts.len()).then(|| end.to_string()),
+            accounts: accounts.get(start..end).unwrap_or_default().to_vec(),
+        }))
+    }
+}
@@ -0,0 +1,321 @@
+//! Reading cached SPL Token accounts and mints.
+
+use {
+    crate::{
+        labels::labeled, owners::owner_name, summary::SPL_TOKEN_2022, RpcAccountStore,
+        RpcError,
+    },
+    mollusk_svm::result::InstructionResult,
+    solana_account::Account,
+    solana_program_pack::{IsInitialized, Pack},
+    solana_pubkey::Pubkey,
+    spl_token_interface::state::{Account as TokenAccount, Mint, Multisig},
+    std::{collections::HashMap, fmt},
+};
+
+/// Byte after the base state of a Token-2022 account with extensions that tells
+/// mints and token accounts apart (`AccountType::Account`).
+const TOKEN_2022_ACCOUNT_TYPE: u8 = 2;
+
+/// Why a cached account couldn't be read as token state.
+///
+/// See [`RpcError::InvalidTokenState`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum TokenStateError {
+    /// The account isn't owned by one of the `expected` token programs, as with a
+    /// Token-2022 account read as an SPL Token one.
+    WrongOwner { expected: Vec<Pubkey>, actual: Pubkey },
+    /// The data has the wrong size for the state, e.g. a mint read as a token account.
+    WrongLength { expected: usize, actual: usize },
+    /// The state has the right size but was never initialized.
+    Uninitialized,
+    /// The data doesn't decode as the state.
+    Malformed(String),
+}
+
+impl fmt::Display for TokenStateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::WrongOwner { expected, actual } => {
+                let expected: Vec<String> = expected.iter().map(owner_name).collect();
+                write!(
+                    f,
+                    "owned by {}, expected {}",
+                    owner_name(actual),
+                    expected.join(" or ")
+                )
+            }
+            Self::WrongLength { expected, actual } => {
+                write!(f, "{actual} bytes of data, expected {expected}")
+            }
+            Self::Uninitialized => f.write_str("not initialized"),
+            Self::Malformed(reason) => f.write_str(reason),
+        }
+    }
+}
+
+/// Decode `data` as `T`, requiring it to be initialized.
+pub(crate) fn unpack<T: Pack + IsInitialized>(data: &[u8]) -> Result<T, TokenStateError> {
+    let state =
+        T::unpack_unchecked(data).map_err(|e| TokenStateError::Malformed(e.to_string()))?;
+    if state.is_initialized() {
+        Ok(state)
+    } else {
+        Err(TokenStateError::Uninitialized)
+    }
+}
+
+/// The base layout of a token account of either program: Token-2022 accounts may
+/// carry extensions after it.
+pub(crate) fn token_account_base(account: &Account) -> Result<TokenAccount, TokenStateError> {
+    let data = &account.data;
+    let has_extensions = account.owner == SPL_TOKEN_2022
+        && data.len() > TokenAccount::LEN
+        && data.len() != Multisig::LEN;
+    if has_extensions {
+        if data[TokenAccount::LEN] != TOKEN_2022_ACCOUNT_TYPE {
+            return Err(TokenStateError::Malformed(
+                "Token-2022 extensions are not those of a token account".to_string(),
+            ));
+        }
+        return unpack(&data[..TokenAccount::LEN]);
+    }
+    if data.len() != TokenAccount::LEN {
+        return Err(TokenStateError::WrongLength {
+            expected: TokenAccount::LEN,
+            actual: data.len(),
+        });
+    }
+    unpack(data)
+}
+
+/// The amount `account` holds if it's a token account of either token program.
+fn token_amount(account: &Account) -> Option<u64> {
+    if account.owner != spl_token_interface::ID && account.owner != SPL_TOKEN_2022 {
+        return None;
+    }
+    token_account_base(account).ok().map(|state| state.amount)
+}
+
+impl RpcAccountStore {
+    /// The cached SPL Token account `pubkey`, decoded.
+    ///
+    /// ```rust,ignore
+    /// let vault = store.get_token_account(&vault)?;
+    /// assert_eq!(vault.mint, usdc_mint);
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns [`RpcError::AccountNotFound`] if the account isn't cached, and
+    /// [`RpcError::InvalidTokenState`] if it isn't owned by SPL Token, has the wrong
+    /// size or isn't initialized. Token-2022 accounts are rejected; see
+    /// `get_token_2022_account` with the `token-2022` feature.
+    pub fn get_token_account(&self, pubkey: &Pubkey) -> Result<TokenAccount, RpcError> {
+        self.token_state(pubkey, "token account", |account| {
+            Self::check_spl_token_owner(account)?;
+            token_account_base(account)
+        })
+    }
+
+    /// The cached SPL Token mint `pubkey`, decoded.
+    ///
+    /// # Errors
+    ///
+    /// Like [`get_token_account`](Self::get_token_account).
+    pub fn get_mint(&self, pubkey: &Pubkey) -> Result<Mint, RpcError> {
+        self.token_state(pubkey, "mint", |account| {
+            Self::check_spl_token_owner(account)?;
+            if account.data.len() != Mint::LEN {
+                return Err(TokenStateError::WrongLength {
+                    expected: Mint::LEN,
+                    actual: account.data.len(),
+                });
+            }
+            unpack(&account.data)
+        })
+    }
+
+    /// The amount held by the cached token account `pubkey`, of SPL Token or
+    /// Token-2022.
+    ///
+    /// ```rust,ignore
+    /// assert_eq!(store.token_balance(&user_ata)?, 1_000_000);
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Like [`get_token_account`](Self::get_token_account), but accepting accounts
+    /// of either token program.
+    pub fn token_balance(&self, pubkey: &Pubkey) -> Result<u64, RpcError> {
+        let account = self.token_state(pubkey, "token account", |account| {
+            let expected = [spl_token_interface::ID, SPL_TOKEN_2022];
+            if !expected.contains(&account.owner) {
+                return Err(TokenStateError::WrongOwner {
+                    expected: expected.to_vec(),
+                    actual: account.owner,
+                });
+            }
+            token_account_base(account)
+        })?;
+        Ok(account.amount)
+    }
+
+    /// How much the amount of each token account in `result` changed, for token
+    /// accounts of either program, Token-2022 ones with extensions included.
+    ///
+    /// The amounts before come from the cache and the amounts after from the
+    /// result's accounts. Accounts the instruction created count as empty before,
+    /// and accounts it closed as empty after. Accounts that aren't token accounts
+    /// either side are left out, and token accounts that didn't change map to 0:
+    ///
+    /// ```rust,ignore
+    /// let result = mollusk.process_instruction(&transfer, &accounts);
+    /// let deltas = store.token_deltas(&result);
+    /// assert_eq!(deltas[&alice_usdc], -1_000_000);
+    /// assert_eq!(deltas[&vault], 1_000_000);
+    /// ```
+    pub fn token_deltas(&self, result: &InstructionResult) -> HashMap<Pubkey, i128> {
+        let mut deltas = HashMap::new();
+        for (pubkey, after) in &result.resulting_accounts {
+            if deltas.contains_key(pubkey) {
+                continue;
+            }
+            let before = self.cache.get(pubkey).and_then(token_amount);
+            let after = token_amount(after);
+            if before.is_none() && after.is_none() {
+                continue;
+            }
+            let delta = i128::from(after.unwrap_or(0)) - i128::from(before.unwrap_or(0));
+            deltas.insert(*pubkey, delta);
+        }
+        deltas
+    }
+
+    /// Panic unless the token account `pubkey` changed by exactly `expected` in
+    /// `result`, as computed by [`token_deltas`](Self::token_deltas).
+    ///
+    /// ```rust,ignore
+    /// store.assert_token_delta(&result, &alice, -100);
+    /// store.assert_token_delta(&result, &vault, 100);
+    /// ```
+    ///
+    /// The message names the account by its [label](Self::label) and shows the
+    /// amounts before and after:
+    ///
+    /// ```text
+    /// expected the amount of alice (4Nd1…kT3q) to change by -100, it changed by -90 (500 -> 410)
+    /// ```
+    #[track_caller]
+    pub fn assert_token_delta(&self, result: &InstructionResult, pubkey: &Pubkey, expected: i128) {
+        let name = labeled(self.label_of(pubkey), pubkey);
+        let Some(delta) = self.token_deltas(result).get(pubkey).copied() else {
+            panic!("{name} is not a token account in the result, before or after");
+        };
+        if delta != expected {
+            let before = self.cache.get(pubkey).and_then(token_amount).unwrap_or(0);
+            let after = result.get_account(pubkey).and_then(token_amount).unwrap_or(0);
+            panic!(
+                "expected the amount of {name} to change by {expected}, it changed by {delta} \
+                 ({before} -> {after})"
+            );
+        }
+    }
+
+    fn check_spl_token_owner(account: &Account) -> Result<(), TokenStateError> {
+        if account.owner == spl_token_interface::ID {
+            Ok(())
+        } else {
+            Err(TokenStateError::WrongOwner {
+                expected: vec![spl_token_interface::ID],
+                actual: account.owner,
+            })
+        }
+    }
+
+    /// Decode the cached account `pubkey` with `decode`, naming the `state` it
+    /// should hold in errors.
+    pub(crate) fn token_state<T>(
+        &self,
+        pubkey: &Pubkey,
+        state: &'static str,
+        decode: impl FnOnce(&Account) -> Result<T, TokenStateError>,
+    ) -> Result<T, RpcError> {
+        let account = self
+            .cache
+            .get(pubkey)
+            .ok_or(RpcError::AccountNotFound(*pubkey))?;
+        decode(account).map_err(|reason| RpcError::InvalidTokenState {
+            pubkey: *pubkey,
+            state,
+            reason,
+        })
+    }
+}
+
+#[cfg(feature = "token-2022")]
+mod token_2022 {
+    use {
+        super::TokenStateError,
+        crate::{RpcAccountStore, RpcError},
+        solana_account::Account,
+        solana_program_error::ProgramError,
+        solana_program_pack::Pack,
+        solana_pubkey::Pubkey,
+        spl_token_2022_interface::{
+            extension::{BaseState, StateWithExtensionsOwned},
+            state::{Account as TokenAccount, Mint},
+        },
+    };
+
+    /// Decode a Token-2022 account or mint with its extensions.
+    fn unpack<S: BaseState + Pack>(
+        account: &Account,
+    ) -> Result<StateWithExtensionsOwned<S>, TokenStateError> {
+        if account.owner != spl_token_2022_interface::ID {
+            return Err(TokenStateError::WrongOwner {
+                expected: vec![spl_token_2022_interface::ID],
+                actual: account.owner,
+            });
+        }
+        if account.data.len() < S::LEN {
+            return Err(TokenStateError::WrongLength {
+                expected: S::LEN,
+                actual: account.data.len(),
+            });
+        }
+        StateWithExtensionsOwned::unpack(account.data.clone()).map_err(|e| match e {
+            ProgramError::UninitializedAccount => TokenStateError::Uninitialized,
+            e => TokenStateError::Malformed(e.to_string()),
+        })
+    }
+
+    impl RpcAccountStore {
+        /// The cached Token-2022 account `pubkey`, decoded with its extensions.
+        ///
+        /// # Errors
+        ///
+        /// Like [`get_token_account`](Self::get_token_account), for accounts owned
+        /// by Token-2022.
+        pub fn get_token_2022_account(
+            &self,
+            pubkey: &Pubkey,
+        ) -> Result<StateWithExtensionsOwned<TokenAccount>, RpcError> {
+            self.token_state(pubkey, "Token-2022 account", unpack)
+        }
+
+        /// The cached Token-2022 mint `pubkey`, decoded with its extensions.
+        ///
+        /// # Errors
+        ///
+        /// Like [`get_token_account`](Self::get_token_account), for mints owned by
+        /// Token-2022.
+        pub fn get_mint_2022(
+            &self,
+            pubkey: &Pubkey,
+        ) -> Result<StateWithExtensionsOwned<Mint>, RpcError> {
+            self.token_state(pubkey, "Token-2022 mint", unpack)
+        }
+    }
+}
@@ -0,0 +1,376 @@
+//! Moving token balances around the cache and mocking wrapped SOL accounts, as
+//! setup before running instructions.
+
+use {
+    crate::{
+        summary::SPL_TOKEN_2022,
+        token::{token_account_base, unpack, TokenStateError},
+        Provenance, RpcAccountStore, RpcError,
+    },
+    solana_account::Account,
+    solana_program_pack::Pack,
+    solana_pubkey::Pubkey,
+    spl_token_interface::{
+        native_mint,
+        state::{Account as TokenAccount, AccountState, Mint},
+    },
+    std::fmt,
+};
+
+/// Byte after the base state of a Token-2022 mint with extensions
+/// (`AccountType::Mint`).
+const TOKEN_2022_MINT_TYPE: u8 = 1;
+
+/// Why token balances couldn't be changed in the cache.
+///
+/// See [`RpcError::TokenOperation`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum TokenOperationError {
+    /// The two token accounts of a transfer hold different mints.
+    MintMismatch { from: Pubkey, to: Pubkey },
+    /// The token account holds less than the amount taken from it.
+    InsufficientFunds { available: u64, requested: u64 },
+    /// The token account is frozen.
+    Frozen(Pubkey),
+    /// The amount would overflow a token account or the mint supply.
+    Overflow,
+    /// The token account isn't a native (wrapped SOL) account.
+    NotNative(Pubkey),
+    /// The native account holds fewer lamports than its rent-exempt reserve plus
+    /// the amount it already records, which `SyncNative` refuses.
+    LamportsBelowAmount { lamports: u64, needed: u64 },
+}
+
+impl fmt::Display for TokenOperationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MintMismatch { from, to } => {
+                write!(f, "the source holds mint {from}, the destination mint {to}")
+            }
+            Self::InsufficientFunds {
+                available,
+                requested,
+            } => write!(f, "{requested} tokens requested, only {available} available"),
+            Self::Frozen(pubkey) => write!(f, "token account {pubkey} is frozen"),
+            Self::Overflow => f.write_str("the amount overflows a balance or the mint supply"),
+            Self::NotNative(pubkey) => write!(f, "token account {pubkey} isn't a native account"),
+            Self::LamportsBelowAmount { lamports, needed } => write!(
+                f,
+                "{lamports} lamports, less than the {needed} of the reserve and current amount"
+            ),
+        }
+    }
+}
+
+/// The base layout of a mint of either program: Token-2022 mints may carry
+/// extensions after it.
+fn mint_base(account: &Account) -> Result<Mint, TokenStateError> {
+    let expected = [spl_token_interface::ID, SPL_TOKEN_2022];
+    if !expected.contains(&account.owner) {
+        return Err(TokenStateError::WrongOwner {
+            expected: expected.to_vec(),
+            actual: account.owner,
+        });
+    }
+    let data = &account.data;
+    if account.owner == SPL_TOKEN_2022 && data.len() > TokenAccount::LEN {
+        if data[TokenAccount::LEN] != TOKEN_2022_MINT_TYPE {
+            return Err(TokenStateError::Malformed(
+                "Token-2022 extensions are not those of a mint".to_string(),
+            ));
+        }
+        return unpack(&data[..Mint::LEN]);
+    }
+    if data.len() != Mint::LEN {
+        return Err(TokenStateError::WrongLength {
+            expected: Mint::LEN,
+            actual: data.len(),
+        });
+    }
+    unpack(data)
+}
+
+impl RpcAccountStore {
+    /// Add a wrapped SOL account at `address` for `owner`, holding `lamports` in
+    /// total, as a native SPL Token account of the native mint.
+    ///
+    /// The rent-exempt reserve at [`rent`](Self::rent) is recorded in `is_native`
+    /// and the rest of the lamports is the amount, so the token program accepts
+    /// the account for transfers, closes and `SyncNative`:
+    ///
+    /// ```rust,ignore
+    /// let store = RpcAccountStore::mainnet()
+    ///     .with_wsol_account(alice_wsol, alice, 2 * LAMPORTS_PER_SOL)?
+    ///     .from_instruction(&swap)
+    ///     .await?;
+    /// ```
+    ///
+    /// Like any account added with [`with_accounts`](Self::with_accounts), it's a
+    /// mock. The native mint itself isn't added; `TransferChecked` needs it cached,
+    /// from the cluster or a fixture.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`RpcError::TokenOperation`] if `lamports` is less than the
+    /// rent-exempt reserve of a token account.
+    pub fn with_wsol_account(
+        self,
+        address: Pubkey,
+        owner: Pubkey,
+        lamports: u64,
+    ) -> Result<Self, RpcError> {
+        let reserve = self.rent().minimum_balance(TokenAccount::LEN);
+        let Some(amount) = lamports.checked_sub(reserve) else {
+            return Err(RpcError::TokenOperation {
+                operation: "wrap",
+                reason: TokenOperationError::LamportsBelowAmount {
+                    lamports,
+                    needed: reserve,
+                },
+            });
+        };
+        let state = TokenAccount {
+            mint: native_mint::ID,
+            owner,
+            amount,
+            is_native: Some(reserve).into(),
+            state: AccountState::Initialized,
+            ..TokenAccount::default()
+        };
+        let mut data = vec![0; TokenAccount::LEN];
+        TokenAccount::pack(state, &mut data).expect("the data has a token account's size");
+        let account = Account {
+            lamports,
+            data,
+            owner: spl_token_interface::ID,
+            executable: false,
+            rent_epoch: 0,
+        };
+        Ok(self.with_accounts([(address, account)]))
+    }
+
+    /// Recompute the amount of the cached native token account `pubkey` from its
+    /// lamports, as `SyncNative` does, after changing its lamports directly:
+    ///
+    /// ```rust,ignore
+    /// store.cache.get_mut(&alice_wsol).unwrap().lamports += LAMPORTS_PER_SOL;
+    /// store.sync_native_in_cache(&alice_wsol)?;
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns [`RpcError::AccountNotFound`] if the account isn't cached,
+    /// [`RpcError::InvalidTokenState`] if it isn't an initialized token account,
+    /// and [`RpcError::TokenOperation`] if it isn't native or, like `SyncNative`,
+    /// if its lamports no longer cover the reserve and the amount it records.
+    pub fn sync_native_in_cache(&mut self, pubkey: &Pubkey) -> Result<(), RpcError> {
+        let failed = |reason| RpcError::TokenOperation {
+            operation: "sync native",
+            reason,
+        };
+        let mut state = self.token_state(pubkey, "token account", token_account_base)?;
+        let Some(reserve) = Option::from(state.is_native) else {
+            return Err(failed(TokenOperationError::NotNative(*pubkey)));
+        };
+        let lamports = self.cache[pubkey].lamports;
+        let amount = lamports.saturating_sub(reserve);
+        if lamports < reserve || amount < state.amount {
+            return Err(failed(TokenOperationError::LamportsBelowAmount {
+                lamports,
+                needed: reserve.saturating_add(state.amount),
+            }));
+        }
+        state.amount = amount;
+        self.write_token_state(pubkey, &state, "sync_native_in_cache");
+        Ok(())
+    }
+
+    /// Move `amount` tokens from the cached token account `from` to `to`, as a
+    /// transfer would, without running one: both accounts are rewritten in the
+    /// cache.
+    ///
+    /// ```rust,ignore
+    /// store.transfer_tokens_in_cache(&whale_usdc, &alice_usdc, 1_000_000)?;
+    /// assert_eq!(store.token_balance(&alice_usdc)?, 1_000_000);
+    /// ```
+    ///
+    /// Accounts of either token program are accepted, and wrapped SOL moves its
+    /// lamports along with the amount. Token-2022 extensions are kept as they are
+    /// and not applied, so no transfer fee is withheld. The accounts are left
+    /// untouched if this fails; on success their provenance becomes
+    /// [`Provenance::Patched`].
+    ///
+    /// # Errors
+    ///
+    /// Returns [`RpcError::AccountNotFound`] if an account isn't cached,
+    /// [`RpcError::InvalidTokenState`] if one isn't an initialized token account,
+    /// and [`RpcError::TokenOperation`] if they hold different mints, either is
+    /// frozen, `from` holds less than `amount` or, for wrapped SOL, fewer lamports,
+    /// or the amount or lamports overflow `to`.
+    pub fn transfer_tokens_in_cache(
+        &mut self,
+        from: &Pubkey,
+        to: &Pubkey,
+        amount: u64,
+    ) -> Result<(), RpcError> {
+        let failed = |reason| RpcError::TokenOperation {
+            operation: "transfer",
+            reason,
+        };
+        let mut source = self.token_state(from, "token account", token_account_base)?;
+        let mut destination = self.token_state(to, "token account", token_account_base)?;
+        if source.mint != destination.mint {
+            return Err(failed(TokenOperationError::MintMismatch {
+                from: source.mint,
+                to: destination.mint,
+            }));
+        }
+        for (pubkey, state) in [(from, &source), (to, &destination)] {
+            if state.state == AccountState::Frozen {
+                return Err(failed(TokenOperationError::Frozen(*pubkey)));
+            }
+        }
+        if source.amount < amount {
+            return Err(failed(TokenOperationError::InsufficientFunds {
+                available: source.amount,
+                requested: amount,
+            }));
+        }
+        if from == to {
+            return Ok(());
+        }
+        let overflow = || failed(TokenOperationError::Overflow);
+        let native_lamports = match Option::<u64>::from(source.is_native) {
+            // Wrapped SOL moves with its lamports, which back the amount
+            Some(reserve) => {
+                let lamports = self.cache[from].lamports;
+                let from_lamports = lamports.checked_sub(amount).ok_or_else(|| {
+                    failed(TokenOperationError::LamportsBelowAmount {
+                        lamports,
+                        needed: reserve.saturating_add(source.amount),
+                    })
+                })?;
+                let to_lamports = self.cache[to].lamports.checked_add(amount).ok_or_else(overflow)?;
+                Some((from_lamports, to_lamports))
+            }
+            None => None,
+        };
+        source.amount -= amount;
+        destination.amount = destination.amount.checked_add(amount).ok_or_else(overflow)?;
+        if let Some((from_lamports, to_lamports)) = native_lamports {
+            for (pubkey, lamports) in [(from, from_lamports), (to, to_lamports)] {
+                self.cache
+                    .entry(*pubkey)
+                    .and_modify(|account| account.lamports = lamports);
+            }
+        }
+        self.write_token_state(from, &source, "transfer_tokens_in_cache");
+        self.write_token_state(to, &destination, "transfer_tokens_in_cache");
+        Ok(())
+    }
+
+    /// Set the amount of the cached token account `pubkey`, minting or burning the
+    /// difference so that the supply of its mint stays consistent.
+    ///
+    /// ```rust,ignore
+    /// store.set_token_balance(&alice_usdc, 5_000_000)?;
+    /// ```
+    ///
+    /// The mint must be cached too. Accounts of either token program are accepted.
+    ///
+    /// # Errors
+    ///
+    /// Like [`mint_tokens_in_cache`](Self::mint_tokens_in_cache) and
+    /// [`burn_tokens_in_cache`](Self::burn_tokens_in_cache).
+    pub fn set_token_balance(&mut self, pubkey: &Pubkey, amount: u64) -> Result<(), RpcError> {
+        let current = self.token_state(pubkey, "token account", token_account_base)?.amount;
+        if amount > current {
+            self.mint_tokens_in_cache(pubkey, amount - current)
+        } else {
+            self.burn_tokens_in_cache(pubkey, current - amount)
+        }
+    }
+
+    /// Add `amount` tokens to the cached token account `pubkey` and to the supply
+    /// of its mint, as a `MintTo` would, without checking the mint authority.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`RpcError::AccountNotFound`] if the account or its mint isn't
+    /// cached, [`RpcError::InvalidTokenState`] if they aren't an initialized token
+    /// account and mint, and [`RpcError::TokenOperation`] if the account is frozen
+    /// or the amount overflows its balance or the supply.
+    pub fn mint_tokens_in_cache(&mut self, pubkey: &Pubkey, amount: u64) -> Result<(), RpcError> {
+        let failed = |reason| RpcError::TokenOperation {
+            operation: "mint",
+            reason,
+        };
+        let (mut state, mut mint) = self.token_account_and_mint(pubkey)?;
+        if state.state == AccountState::Frozen {
+            return Err(failed(TokenOperationError::Frozen(*pubkey)));
+        }
+        let overflow = || failed(TokenOperationError::Overflow);
+        state.amount = state.amount.checked_add(amount).ok_or_else(overflow)?;
+        mint.supply = mint.supply.checked_add(amount).ok_or_else(overflow)?;
+        self.write_token_state(pubkey, &state, "mint_tokens_in_cache");
+        self.write_token_state(&state.mint, &mint, "mint_tokens_in_cache");
+        Ok(())
+    }
+
+    /// Take `amount` tokens from the cached token account `pubkey` and from the
+    /// supply of its mint, as a `Burn` would, without checking the owner.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`RpcError::AccountNotFound`] if the account or its mint isn't
+    /// cached, [`RpcError::InvalidTokenState`] if they aren't an initialized token
+    /// account and mint, and [`RpcError::TokenOperation`] if the account is frozen
+    /// or holds less than `amount`.
+    pub fn burn_tokens_in_cache(&mut self, pubkey: &Pubkey, amount: u64) -> Result<(), RpcError> {
+        let failed = |reason| RpcError::TokenOperation {
+            operation: "burn",
+            reason,
+        };
+        let (mut state, mut mint) = self.token_account_and_mint(pubkey)?;
+        if state.state == AccountState::Frozen {
+            return Err(failed(TokenOperationError::Frozen(*pubkey)));
+        }
+        if state.amount < amount {
+            return Err(failed(TokenOperationError::InsufficientFunds {
+                available: state.amount,
+                requested: amount,
+            }));
+        }
+        state.amount -= amount;
+        // A supply below the balances means the cache was already inconsistent
+        mint.supply = mint.supply.saturating_sub(amount);
+        self.write_token_state(pubkey, &state, "burn_tokens_in_cache");
+        self.write_token_state(&state.mint, &mint, "burn_tokens_in_cache");
+        Ok(())
+    }
+
+    /// The cached token account `pubkey` and its cached mint, decoded.
+    fn token_account_and_mint(&self, pubkey: &Pubkey) -> Result<(TokenAccount, Mint), RpcError> {
+        let state = self.token_state(pubkey, "token account", token_account_base)?;
+        let mint = self.token_state(&state.mint, "mint", mint_base)?;
+        Ok((state, mint))
+    }
+
+    /// Write `state` over the base layout of the cached account `pubkey`, keeping
+    /// any Token-2022 extensions after it, and mark it patched by `method`.
+    fn write_token_state<S: Pack + Clone>(
+        &mut self,
+        pubkey: &Pubkey,
+        state: &S,
+        method: &'static str,
+    ) {
+        let account = self
+            .cache
+            .get_mut(pubkey)
+            .expect("token state is decoded from the cache first");
+        S::pack(state.clone(), &mut account.data[..S::LEN])
+            .expect("decoded token state packs back into its layout");
+        self.provenance.insert(*pubkey, Provenance::Patched(method));
+    }
+}
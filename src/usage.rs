@@ -0,0 +1,145 @@
+//! Tracking which cached accounts were handed to Mollusk.
+
+use {
+    crate::{fixture, RpcAccountStore, RpcError},
+    solana_account::Account,
+    solana_pubkey::Pubkey,
+    std::{
+        collections::HashSet,
+        fs,
+        path::{Path, PathBuf},
+        str::FromStr,
+        sync::MutexGuard,
+    },
+};
+
+/// Programs and their program data are kept even if no instruction listed them:
+/// `add_programs` needs them to load the programs a test calls into.
+fn is_program(account: &Account) -> bool {
+    account.executable || account.owner == mollusk_svm::program::loader_keys::LOADER_V3
+}
+
+impl RpcAccountStore {
+    /// Every cached account handed to Mollusk so far, through
+    /// [`instruction_accounts`](Self::instruction_accounts),
+    /// [`instructions_accounts`](Self::instructions_accounts) or the lazy
+    /// `MolluskContext` store.
+    pub fn used_accounts(&self) -> HashSet<Pubkey> {
+        self.used_accounts_mut().clone()
+    }
+
+    /// Cached accounts that were never handed to Mollusk, sorted, leaving out
+    /// programs and program data.
+    ///
+    /// When replaying fixtures, these are the accounts a test doesn't need; see
+    /// [`prune_fixture_to_used`](Self::prune_fixture_to_used) and
+    /// [`prune_fixture_dir_to_used`](Self::prune_fixture_dir_to_used).
+    pub fn unused_accounts(&self) -> Vec<Pubkey> {
+        let used = self.used_accounts_mut();
+        let mut unused: Vec<Pubkey> = self
+            .cache
+            .iter()
+            .filter(|(pubkey, account)| !used.contains(pubkey) && !is_program(account))
+            .map(|(pubkey, _)| *pubkey)
+            .collect();
+        unused.sort();
+        unused
+    }
+
+    /// Rewrite the fixture file `path`, written by
+    /// [`save_fixture`](Self::save_fixture), keeping only the accounts that were handed
+    /// to Mollusk.
+    ///
+    /// Programs and program data are always kept. Run the test once, then prune:
+    ///
+    /// ```rust,ignore
+    /// let result = process_instruction_with_store(&mollusk, &store, &ix)?;
+    /// store.prune_fixture_to_used("tests/fixtures/swap.jsonl")?;
+    /// ```
+    ///
+    /// Like `save_fixture`, the file is replaced only once the new one is complete.
+    /// Returns the pubkeys of the removed accounts, sorted.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`RpcError::Fixture`] if the file can't be read, parsed or written.
+    pub fn prune_fixture_to_used(&self, path: impl AsRef<Path>) -> Result<Vec<Pubkey>, RpcError> {
+        let path = path.as_ref();
+        let used = self.used_accounts();
+        let mut kept = Vec::new();
+        let mut removed = Vec::new();
+        fixture::read_lines(path, |pubkey, account| {
+            if used.contains(&pubkey) || is_program(&account) {
+                kept.push((pubkey, account));
+            } else {
+                removed.push(pubkey);
+            }
+        })?;
+        fixture::replace_lines(path, kept.iter().map(|(pubkey, account)| (pubkey, account)))?;
+        removed.sort();
+        Ok(removed)
+    }
+
+    /// Delete the fixtures in `dir`, a [`with_fixture_dir`](Self::with_fixture_dir)
+    /// directory, of accounts that were never handed to Mollusk.
+    ///
+    /// Programs and program data are always kept, as are files that aren't named
+    /// like a fixture:
+    ///
+    /// ```rust,ignore
+    /// let result = process_instruction_with_store(&mollusk, &store, &ix)?;
+    /// store.prune_fixture_dir_to_used("tests/fixtures")?;
+    /// ```
+    ///
+    /// Returns the paths of the deleted files.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`RpcError::Fixture`] if a fixture can't be read or deleted.
+    pub fn prune_fixture_dir_to_used(
+        &self,
+        dir: impl AsRef<Path>,
+    ) -> Result<Vec<PathBuf>, RpcError> {
+        let dir = dir.as_ref();
+        let fixture_error = |path: &Path, reason: String| RpcError::Fixture {
+            path: path.to_path_buf(),
+            reason,
+        };
+
+        let used = self.used_accounts();
+        let mut removed = Vec::new();
+        for entry in fs::read_dir(dir).map_err(|e| fixture_error(dir, e.to_string()))? {
+            let path = entry.map_err(|e| fixture_error(dir, e.to_string()))?.path();
+            let Some(pubkey) = path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .and_then(|name| name.strip_suffix(".json"))
+                .and_then(|name| Pubkey::from_str(name).ok())
+            else {
+                continue;
+            };
+            if used.contains(&pubkey) {
+                continue;
+            }
+
+            let account = fixture::read_account(dir, &pubkey)
+                .map_err(|reason| fixture_error(&path, reason))?;
+            if account.as_ref().is_some_and(is_program) {
+                continue;
+            }
+            fs::remove_file(&path).map_err(|e| fixture_error(&path, e.to_string()))?;
+            removed.push(path);
+        }
+        removed.sort();
+        Ok(removed)
+    }
+
+    /// Record that `pubkey` was handed to Mollusk.
+    pub(crate) fn mark_used(&self, pubkey: &Pubkey) {
+        self.used_accounts_mut().insert(*pubkey);
+    }
+
+    fn used_accounts_mut(&self) -> MutexGuard<'_, HashSet<Pubkey>> {
+        self.used_accounts.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
@@ -0,0 +1,141 @@
+//! Vote accounts: mocking them, decoding cloned ones and cloning the cluster's
+//! biggest.
+
+use {
+    crate::{owners::owner_name, RpcAccountStore, RpcError},
+    solana_account::Account,
+    solana_pubkey::Pubkey,
+    solana_sdk_ids::vote,
+    solana_vote_interface::{
+        authorized_voters::AuthorizedVoters,
+        state::{VoteStateV3, VoteStateVersions},
+    },
+};
+
+/// The fields of a mocked vote account, see [`RpcAccountStore::with_vote_account`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct VoteMock {
+    /// The validator identity, also the authorized voter.
+    pub node_pubkey: Pubkey,
+    pub authorized_withdrawer: Pubkey,
+    /// Percentage of rewards the validator keeps, 0 to 100.
+    pub commission: u8,
+    /// Credits earned, all in epoch 0.
+    pub credits: u64,
+}
+
+impl VoteMock {
+    /// The vote state of the mock, without votes or a root slot.
+    fn vote_state(&self) -> VoteStateV3 {
+        VoteStateV3 {
+            node_pubkey: self.node_pubkey,
+            authorized_withdrawer: self.authorized_withdrawer,
+            commission: self.commission,
+            authorized_voters: AuthorizedVoters::new(0, self.node_pubkey),
+            epoch_credits: vec![(0, self.credits, 0)],
+            ..VoteStateV3::default()
+        }
+    }
+}
+
+impl RpcAccountStore {
+    /// Add a vote account at `address` holding `mock`'s vote state.
+    ///
+    /// The state is serialized the way the Vote program stores it, as the current
+    /// version of `VoteStateVersions`, into an account of the full vote state size,
+    /// owned by the Vote program and rent-exempt at [`rent`](Self::rent). Like any
+    /// account added with [`with_accounts`](Self::with_accounts), it's a mock:
+    ///
+    /// ```rust,ignore
+    /// let store = RpcAccountStore::mainnet().with_vote_account(
+    ///     vote_account,
+    ///     VoteMock {
+    ///         node_pubkey: identity,
+    ///         authorized_withdrawer: withdrawer,
+    ///         commission: 5,
+    ///         credits: 1_000,
+    ///     },
+    /// );
+    /// ```
+    pub fn with_vote_account(self, address: Pubkey, mock: VoteMock) -> Self {
+        let mut data = vec![0; VoteStateV3::size_of()];
+        let versioned = VoteStateVersions::new_v3(mock.vote_state());
+        VoteStateV3::serialize(&versioned, &mut data)
+            .expect("a vote state without votes fits the vote state size");
+        let account = Account {
+            lamports: self.rent().minimum_balance(data.len()),
+            data,
+            owner: vote::ID,
+            executable: false,
+            rent_epoch: 0,
+        };
+        self.with_accounts([(address, account)])
+    }
+
+    /// The cached vote account `pubkey`, decoded into the current vote state
+    /// version, whichever version it's stored as.
+    ///
+    /// ```rust,ignore
+    /// let state = store.get_vote_state(&vote_account)?;
+    /// assert_eq!(state.commission, 5);
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns [`RpcError::AccountNotFound`] if the account isn't cached, and
+    /// [`RpcError::InvalidVoteAccount`] if it isn't owned by the Vote program, isn't
+    /// initialized or doesn't decode.
+    pub fn get_vote_state(&self, pubkey: &Pubkey) -> Result<VoteStateV3, RpcError> {
+        let account = self
+            .cache
+            .get(pubkey)
+            .ok_or(RpcError::AccountNotFound(*pubkey))?;
+        let invalid = |reason| RpcError::InvalidVoteAccount {
+            pubkey: *pubkey,
+            reason,
+        };
+        if account.owner != vote::ID {
+            return Err(invalid(format!(
+                "owned by {}, expected {}",
+                owner_name(&account.owner),
+                owner_name(&vote::ID)
+            )));
+        }
+        let versioned: VoteStateVersions =
+            bincode::deserialize(&account.data).map_err(|e| invalid(e.to_string()))?;
+        if versioned.is_uninitialized() {
+            return Err(invalid("not initialized".to_string()));
+        }
+        Ok(versioned.convert_to_v3())
+    }
+
+    /// Fetch and cache the `limit` vote accounts with the most activated stake, as
+    /// listed by `getVoteAccounts`.
+    ///
+    /// Delinquent validators are ranked along with current ones. An
+    /// [offline](Self::offline) store fails, since the list can't come from
+    /// fixtures.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`RpcError::Offline`] for an offline store, and the fetch's errors.
+    pub async fn with_vote_accounts_from_cluster(mut self, limit: usize) -> Result<Self, RpcError> {
+        self.begin_request("getVoteAccounts").await?;
+        let mut vote_accounts = self
+            .fetcher
+            .get_vote_accounts()
+            .await
+            .map_err(|e| self.client_error(e))?;
+        // Most stake first, ties in pubkey order so the selection is stable
+        vote_accounts.sort_unstable_by(|(a, a_stake), (b, b_stake)| {
+            b_stake.cmp(a_stake).then_with(|| a.cmp(b))
+        });
+        let pubkeys: Vec<Pubkey> = vote_accounts
+            .into_iter()
+            .take(limit)
+            .map(|(pubkey, _)| pubkey)
+            .collect();
+        self.fetch_pubkeys(&pubkeys).await?;
+        Ok(self)
+    }
+}
@@ -0,0 +1,80 @@
+//! Polling for accounts that a transaction sent by the test is about to create.
+
+use {
+    crate::{RpcAccountStore, RpcError},
+    solana_account::Account,
+    solana_pubkey::Pubkey,
+    std::time::Duration,
+    tokio::time::Instant,
+};
+
+impl RpcAccountStore {
+    /// Request `pubkey` every `poll_interval` until it exists, then cache it and
+    /// return it, for flows that send a transaction to devnet and clone what it
+    /// created.
+    ///
+    /// ```rust,ignore
+    /// client.send_and_confirm_transaction(&create_pool).await?;
+    /// let pool_account = store
+    ///     .wait_for_account(&pool, Duration::from_secs(10), Duration::from_millis(500))
+    ///     .await?;
+    /// ```
+    ///
+    /// The cache isn't consulted: the account is always requested from the chain,
+    /// with `getMultipleAccounts` like any fetch, so each poll counts against the
+    /// budget and waits for the rate limiter.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`RpcError::Timeout`] if the account doesn't exist after `timeout`,
+    /// [`RpcError::Offline`] right away for an [offline](Self::offline) store, and
+    /// RPC errors.
+    pub async fn wait_for_account(
+        &mut self,
+        pubkey: &Pubkey,
+        timeout: Duration,
+        poll_interval: Duration,
+    ) -> Result<&Account, RpcError> {
+        self.wait_for_account_where(pubkey, timeout, poll_interval, |_| true)
+            .await
+    }
+
+    /// [`wait_for_account`](Self::wait_for_account), until the account exists and
+    /// `predicate` accepts it, such as a pool whose initialized flag is set.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`RpcError::Timeout`] if no account `predicate` accepts shows up
+    /// within `timeout`, and the errors of `wait_for_account`.
+    pub async fn wait_for_account_where(
+        &mut self,
+        pubkey: &Pubkey,
+        timeout: Duration,
+        poll_interval: Duration,
+        predicate: impl Fn(&Account) -> bool,
+    ) -> Result<&Account, RpcError> {
+        let started = Instant::now();
+        loop {
+            let fetched = self.fetch_from_rpc(std::slice::from_ref(pubkey)).await?;
+            if let Some(account) = fetched
+                .into_iter()
+                .find_map(|(_, account)| account)
+                .filter(|account| predicate(account))
+            {
+                let account = self.resolve_fetched(pubkey, Some(account))?;
+                let provenance = self.fetched_provenance_of(pubkey);
+                self.insert_account(*pubkey, account, provenance);
+                return Ok(&self.cache[pubkey]);
+            }
+
+            let waited = started.elapsed();
+            if waited >= timeout {
+                return Err(RpcError::Timeout {
+                    pubkey: *pubkey,
+                    waited,
+                });
+            }
+            tokio::time::sleep(poll_interval.min(timeout - waited)).await;
+        }
+    }
+}
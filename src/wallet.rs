@@ -0,0 +1,129 @@
+//! Cloning everything a wallet owns: its account, its token accounts under both
+//! token programs and its accounts in protocol programs.
+
+use {
+    crate::{summary::SPL_TOKEN_2022, RpcAccountStore, RpcError},
+    solana_account::Account,
+    solana_pubkey::Pubkey,
+};
+
+/// What [`RpcAccountStore::clone_wallet`] cached for a wallet.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WalletSnapshot {
+    pub owner: Pubkey,
+    /// Whether the wallet's own account exists; a wallet that spent all its SOL has
+    /// none.
+    pub has_owner_account: bool,
+    /// SPL Token accounts of the wallet.
+    pub token_accounts: usize,
+    /// Token-2022 accounts of the wallet.
+    pub token_2022_accounts: usize,
+    /// Accounts found in each protocol program, in the order of the filters.
+    pub program_accounts: Vec<(Pubkey, usize)>,
+}
+
+impl WalletSnapshot {
+    /// Accounts cached in all, the wallet's own included.
+    pub fn total(&self) -> usize {
+        usize::from(self.has_owner_account)
+            + self.token_accounts
+            + self.token_2022_accounts
+            + self.program_accounts.iter().map(|(_, count)| count).sum::<usize>()
+    }
+}
+
+impl RpcAccountStore {
+    /// Fetch and cache what the wallet `owner` owns, the usual start of reproducing
+    /// a user's bug: its own account, its token accounts under SPL Token and
+    /// Token-2022 (`getTokenAccountsByOwner`), and for each `(program, offset)` of
+    /// `extra_program_filters` the accounts of `program` that hold `owner` at
+    /// `offset` (`getProgramAccounts` with a `memcmp` filter).
+    ///
+    /// ```rust,ignore
+    /// // Our positions store their owner after the 8-byte Anchor discriminator
+    /// let wallet = store.clone_wallet(&user, &[(our_program, 8)]).await?;
+    /// println!("{} token accounts", wallet.token_accounts + wallet.token_2022_accounts);
+    /// ```
+    ///
+    /// The wallet's account may not exist, which [`WalletSnapshot::has_owner_account`]
+    /// reports instead of failing. Listed accounts replace cached ones like any
+    /// fetch. An [offline](Self::offline) store fails, since the lists can't come
+    /// from fixtures.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`RpcError::Offline`] for an offline store,
+    /// [`RpcError::TooManyProgramAccounts`] if a list is longer than the
+    /// [limit](Self::with_program_accounts_limit), and the fetch's errors.
+    pub async fn clone_wallet(
+        &mut self,
+        owner: &Pubkey,
+        extra_program_filters: &[(Pubkey, usize)],
+    ) -> Result<WalletSnapshot, RpcError> {
+        self.fetch_existing(std::slice::from_ref(owner)).await?;
+        let has_owner_account = self.cache.contains_key(owner);
+
+        let mut token_counts = [0; 2];
+        for (count, program_id) in token_counts
+            .iter_mut()
+            .zip([spl_token_interface::ID, SPL_TOKEN_2022])
+        {
+            self.begin_request("getTokenAccountsByOwner").await?;
+            let accounts = self
+                .fetcher
+                .get_token_accounts_by_owner(owner, &program_id)
+                .await
+                .map_err(|e| self.client_error(e))?;
+            *count = self.cache_listed(&program_id, accounts)?;
+        }
+
+        let mut program_accounts = Vec::with_capacity(extra_program_filters.len());
+        for (program_id, offset) in extra_program_filters {
+            self.begin_request("getProgramAccounts").await?;
+            let accounts = self
+                .fetcher
+                .get_program_accounts_by_memcmp(program_id, *offset, owner.as_ref())
+                .await
+                .map_err(|e| self.client_error(e))?;
+            program_accounts.push((*program_id, self.cache_listed(program_id, accounts)?));
+        }
+
+        Ok(WalletSnapshot {
+            owner: *owner,
+            has_owner_account,
+            token_accounts: token_counts[0],
+            token_2022_accounts: token_counts[1],
+            program_accounts,
+        })
+    }
+
+    /// Cache the `accounts` a query of `program_id` listed, returning how many.
+    fn cache_listed(
+        &mut self,
+        program_id: &Pubkey,
+        accounts: Vec<(Pubkey, Account)>,
+    ) -> Result<usize, RpcError> {
+        if accounts.len() > self.program_accounts_limit {
+            return Err(RpcError::TooManyProgramAccounts {
+                program: *program_id,
+                limit: self.program_accounts_limit,
+            });
+        }
+        {
+            let mut stats = self.stats_mut();
+            stats.accounts_fetched += accounts.len() as u64;
+            stats.bytes_downloaded += accounts
+                .iter()
+                .map(|(_, account)| account.data.len() as u64)
+                .sum::<u64>();
+        }
+
+        let count = accounts.len();
+        for (pubkey, account) in accounts {
+            let account = self.resolve_fetched(&pubkey, Some(account))?;
+            let provenance = self.fetched_provenance();
+            self.insert_account(pubkey, account, provenance);
+        }
+        Ok(count)
+    }
+}
@@ -0,0 +1,179 @@
+//! Fetches started in the background while a test sets up, see
+//! [`RpcAccountStore::warm_cache`].
+
+use {
+    crate::{fixture, Provenance, RpcAccountStore, RpcError},
+    solana_account::Account,
+    solana_pubkey::Pubkey,
+    std::collections::{HashMap, HashSet},
+    tokio::task::JoinHandle,
+};
+
+/// A background fetch started by [`RpcAccountStore::warm_cache`], to pass to
+/// [`join_warmup`](RpcAccountStore::join_warmup).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct WarmupHandle {
+    id: u64,
+}
+
+/// What a background fetch found: accounts replayed from fixtures, then the
+/// `getMultipleAccounts` results of the rest.
+struct Warmed {
+    fixtures: Vec<(Pubkey, Account)>,
+    fetched: Vec<(Pubkey, Option<Account>)>,
+}
+
+struct Warmup {
+    id: u64,
+    pubkeys: HashSet<Pubkey>,
+    task: JoinHandle<Result<Warmed, RpcError>>,
+}
+
+/// The store's background fetches that haven't been joined yet.
+#[derive(Default)]
+pub(crate) struct Warmups {
+    next_id: u64,
+    pending: Vec<Warmup>,
+}
+
+impl Warmups {
+    pub(crate) fn len(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// Whether a pending fetch requests `pubkey`.
+    fn fetches(&self, pubkey: &Pubkey) -> bool {
+        self.pending.iter().any(|warmup| warmup.pubkeys.contains(pubkey))
+    }
+}
+
+impl RpcAccountStore {
+    /// Start fetching `pubkeys` in the background, so the requests are under way
+    /// while the test builds its instructions, and return a handle to
+    /// [`join_warmup`](Self::join_warmup) later.
+    ///
+    /// ```rust,ignore
+    /// let mut store = RpcAccountStore::mainnet();
+    /// let warmup = store.warm_cache(vec![pool, vault_a, vault_b]);
+    /// let instructions = build_swap(&payer, &pool)?; // the fetch runs meanwhile
+    /// let mut store = store.from_instructions(&instructions).await?; // waits for it
+    /// store.join_warmup(warmup).await?; // joined by the fetch already, does nothing
+    /// ```
+    ///
+    /// Pubkeys that are cached, or already being warmed, are left out. The fetch
+    /// goes through the store's fixture directory, rate limiter and
+    /// [budget](Self::with_rpc_budget), and counts in its [`stats`](Self::stats),
+    /// but its accounts only reach the cache once it's joined: by `join_warmup`, or
+    /// by any fetch of the store that requests one of its pubkeys, which waits for
+    /// it instead of requesting the pubkeys again. Accounts cached in the meantime,
+    /// such as mocks, are kept.
+    ///
+    /// On a current-thread runtime, `#[tokio::test]`'s default, the fetch only
+    /// progresses while the test awaits something; use
+    /// `#[tokio::test(flavor = "multi_thread")]` for it to overlap setup that
+    /// doesn't. A [`SharedAccountStore`](crate::SharedAccountStore) made from the
+    /// store doesn't wait for its warm-ups, so join them first.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called outside of a Tokio runtime.
+    pub fn warm_cache(&mut self, pubkeys: Vec<Pubkey>) -> WarmupHandle {
+        let pubkeys: HashSet<Pubkey> = pubkeys
+            .into_iter()
+            .filter(|pubkey| {
+                !self.cache.contains_key(pubkey)
+                    && !self.synthesizes_sysvar(pubkey)
+                    && !self.warmups.fetches(pubkey)
+            })
+            .collect();
+        let id = self.warmups.next_id;
+        self.warmups.next_id += 1;
+
+        // A copy without accounts, sharing the fetcher, rate limiter and stats
+        let store = self.copy(HashMap::new(), self.stats.clone());
+        let requested: Vec<Pubkey> = pubkeys.iter().copied().collect();
+        let task = tokio::spawn(async move {
+            let mut fixtures = Vec::new();
+            let mut missing = Vec::new();
+            for pubkey in requested {
+                match store.read_fixture(&pubkey)? {
+                    Some(account) => fixtures.push((pubkey, account)),
+                    None => missing.push(pubkey),
+                }
+            }
+            let fetched = if missing.is_empty() {
+                Vec::new()
+            } else {
+                store.fetch_from_rpc(&missing).await?
+            };
+            Ok(Warmed { fixtures, fetched })
+        });
+        self.warmups.pending.push(Warmup { id, pubkeys, task });
+        WarmupHandle { id }
+    }
+
+    /// Wait for the background fetch of `handle` and cache its accounts, as
+    /// [`from_instruction`](Self::from_instruction) would have. Does nothing if it
+    /// was joined already.
+    ///
+    /// # Errors
+    ///
+    /// Returns the fetch's errors: [`RpcError::AccountNotFound`] or
+    /// [`RpcError::AccountsNotFound`] for missing accounts, unless
+    /// [`allow_missing_accounts`](Self::allow_missing_accounts) or
+    /// [`lenient`](Self::lenient) is set, and RPC or fixture errors.
+    ///
+    /// # Panics
+    ///
+    /// Resumes the panic of a fetch that panicked.
+    pub async fn join_warmup(&mut self, handle: WarmupHandle) -> Result<(), RpcError> {
+        match self.warmups.pending.iter().position(|warmup| warmup.id == handle.id) {
+            Some(index) => self.finish_warmup(index).await,
+            None => Ok(()),
+        }
+    }
+
+    /// Join the background fetches of any of `pubkeys`, so a fetch finds their
+    /// accounts cached rather than requesting them again.
+    pub(crate) async fn join_warmups_of(&mut self, pubkeys: &[Pubkey]) -> Result<(), RpcError> {
+        while let Some(index) = self
+            .warmups
+            .pending
+            .iter()
+            .position(|warmup| pubkeys.iter().any(|pubkey| warmup.pubkeys.contains(pubkey)))
+        {
+            self.finish_warmup(index).await?;
+        }
+        Ok(())
+    }
+
+    async fn finish_warmup(&mut self, index: usize) -> Result<(), RpcError> {
+        let warmup = self.warmups.pending.swap_remove(index);
+        let warmed = match warmup.task.await {
+            Ok(warmed) => warmed?,
+            Err(e) if e.is_panic() => std::panic::resume_unwind(e.into_panic()),
+            Err(e) => panic!("warm-up fetch didn't complete: {e}"),
+        };
+
+        let mut received = Vec::new();
+        if let Some(dir) = self.fixture_dir.clone() {
+            for (pubkey, account) in warmed.fixtures {
+                if self.cache.contains_key(&pubkey) {
+                    continue;
+                }
+                let path = fixture::account_path(&dir, &pubkey);
+                self.insert_account(pubkey, account, Provenance::Fixture { path });
+                self.stats_mut().accounts_from_fixtures += 1;
+                received.push(pubkey);
+            }
+        }
+        let fetched = warmed
+            .fetched
+            .into_iter()
+            .filter(|(pubkey, _)| !self.cache.contains_key(pubkey) || self.is_fallback(pubkey))
+            .collect();
+        self.cache_fetched(fetched, &mut received)?;
+        self.check_rent_exemption(&received);
+        Ok(())
+    }
+}
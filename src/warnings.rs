@@ -0,0 +1,122 @@
+//! Non-fatal issues the store noticed, see [`RpcAccountStore::warnings`].
+
+use {
+    crate::{RpcAccountStore, RpcError},
+    solana_pubkey::Pubkey,
+    std::{fmt, sync::Arc},
+};
+
+/// Callback invoked with every [`Warning`] as it occurs.
+pub(crate) type WarningCallback = Arc<dyn Fn(&Warning) + Send + Sync>;
+
+/// What a [`Warning`] is about. Codes are stable, so tests can match on them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum WarningCode {
+    /// The cluster's `Rent` sysvar couldn't be parsed; `Rent::default()` is used.
+    RentSysvarUnparsable,
+    /// The cluster doesn't have a sysvar account a `with_synced_*` method asked for;
+    /// Mollusk keeps its default.
+    SysvarMissing,
+    /// A program was loaded although its ELF doesn't look valid, under
+    /// [`ValidationMode::Warn`](crate::ValidationMode::Warn).
+    InvalidElfLoaded,
+    /// A mocked account exists on-chain with different contents, see
+    /// [`strict_mocks`](RpcAccountStore::strict_mocks).
+    MockShadowsAccount,
+    /// Fetched accounts hold less than the rent-exempt minimum for their size, see
+    /// [`auto_top_up_rent`](RpcAccountStore::auto_top_up_rent).
+    NotRentExempt,
+    /// [`with_accounts`](RpcAccountStore::with_accounts) replaced an account that was
+    /// already cached; see
+    /// [`with_accounts_override`](RpcAccountStore::with_accounts_override) to make
+    /// that intentional.
+    MockReplacesAccount,
+    /// A `.so` in `target/deploy` has no program id in `Anchor.toml` and no keypair
+    /// file, so [`with_workspace_programs`](RpcAccountStore::with_workspace_programs)
+    /// skipped it.
+    LocalProgramWithoutId,
+    /// A validator of a stake pool has no vote account, or no stake account where
+    /// the validator list says there is one, see `with_stake_pool` (`stake-pool`
+    /// feature).
+    StakePoolAccountMissing,
+    /// The cache holds more account data than the
+    /// [`with_memory_warning_threshold`](RpcAccountStore::with_memory_warning_threshold).
+    CacheMemoryHigh,
+}
+
+/// A non-fatal issue, recorded by the store instead of failing.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Warning {
+    pub code: WarningCode,
+    pub message: String,
+    /// The account the warning is about, if any.
+    pub pubkey: Option<Pubkey>,
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}: {}", self.code, self.message)?;
+        if let Some(pubkey) = &self.pubkey {
+            write!(f, " ({})", pubkey)?;
+        }
+        Ok(())
+    }
+}
+
+impl RpcAccountStore {
+    /// Call `on_warning` with every [`Warning`] as it occurs, e.g. to log it.
+    ///
+    /// Warnings are recorded in [`warnings`](Self::warnings) either way.
+    pub fn on_warning(mut self, on_warning: impl Fn(&Warning) + Send + Sync + 'static) -> Self {
+        self.on_warning = Some(Arc::new(on_warning));
+        self
+    }
+
+    /// Warnings recorded so far, oldest first.
+    pub fn warnings(&self) -> &[Warning] {
+        &self.warnings
+    }
+
+    /// Remove and return the warnings recorded so far.
+    pub fn take_warnings(&mut self) -> Vec<Warning> {
+        std::mem::take(&mut self.warnings)
+    }
+
+    /// Check that no warning was recorded.
+    ///
+    /// ```rust,ignore
+    /// let store = store.with_synced_rent(&mut mollusk).await?;
+    /// store.assert_no_warnings()?;
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns [`RpcError::UnexpectedWarnings`] listing every recorded warning.
+    pub fn assert_no_warnings(&self) -> Result<(), RpcError> {
+        if self.warnings.is_empty() {
+            Ok(())
+        } else {
+            Err(RpcError::UnexpectedWarnings(self.warnings.clone()))
+        }
+    }
+
+    /// Record a warning and pass it to the `on_warning` callback.
+    pub(crate) fn warn(&mut self, code: WarningCode, message: String, pubkey: Option<Pubkey>) {
+        let warning = Warning {
+            code,
+            message,
+            pubkey,
+        };
+        self.notify(&warning);
+        self.warnings.push(warning);
+    }
+
+    /// Pass `warning` to the `on_warning` callback, for callers that can only record
+    /// it later.
+    pub(crate) fn notify(&self, warning: &Warning) {
+        if let Some(on_warning) = &self.on_warning {
+            on_warning(warning);
+        }
+    }
+}
@@ -0,0 +1,208 @@
+//! Loading locally built programs instead of their on-chain versions.
+
+use {
+    crate::{RecordedError, RpcAccountStore, RpcError, Warning, WarningCode},
+    mollusk_svm::Mollusk,
+    solana_pubkey::Pubkey,
+    std::{
+        collections::HashMap,
+        fs, io,
+        path::{Path, PathBuf},
+        str::FromStr,
+    },
+};
+
+/// Program ids of the `[programs.<cluster>]` sections of `Anchor.toml`, by program
+/// name. `localnet` ids win over other clusters'.
+fn anchor_program_ids(path: &Path) -> Result<HashMap<String, Pubkey>, RpcError> {
+    let invalid = |reason: String| RpcError::InvalidConfig {
+        path: path.to_path_buf(),
+        reason,
+    };
+    let contents = match fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(HashMap::new()),
+        Err(e) => return Err(invalid(e.to_string())),
+    };
+    let anchor: toml::Table =
+        toml::from_str(&contents).map_err(|e| invalid(e.message().to_string()))?;
+    let Some(clusters) = anchor.get("programs").and_then(toml::Value::as_table) else {
+        return Ok(HashMap::new());
+    };
+    let mut clusters: Vec<(&String, &toml::Value)> = clusters.iter().collect();
+    clusters.sort_by_key(|(cluster, _)| *cluster != "localnet");
+
+    let mut ids = HashMap::new();
+    for (cluster, programs) in clusters {
+        for (name, id) in programs.as_table().into_iter().flatten() {
+            // Either `name = "<id>"` or `name = { address = "<id>", ... }`
+            let id = id
+                .as_str()
+                .or_else(|| id.get("address").and_then(toml::Value::as_str))
+                .ok_or_else(|| invalid(format!("programs.{cluster}.{name} has no address")))?;
+            let id = Pubkey::from_str(id)
+                .map_err(|e| invalid(format!("programs.{cluster}.{name} is invalid: {e}")))?;
+            ids.entry(name.clone()).or_insert(id);
+        }
+    }
+    Ok(ids)
+}
+
+/// The pubkey of the keypair file at `path`, if it exists.
+///
+/// Keypair files hold the 64 bytes of the secret key followed by the public key as
+/// a JSON array.
+fn keypair_pubkey(path: &Path) -> Result<Option<Pubkey>, RpcError> {
+    let invalid = |reason: String| RpcError::LocalProgram {
+        path: path.to_path_buf(),
+        reason,
+    };
+    let contents = match fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(invalid(e.to_string())),
+    };
+    let bytes: Vec<u8> = serde_json::from_str(&contents).map_err(|e| invalid(e.to_string()))?;
+    if bytes.len() != 64 {
+        return Err(invalid(format!("{} bytes, expected a 64-byte keypair", bytes.len())));
+    }
+    Ok(Pubkey::try_from(&bytes[32..]).ok())
+}
+
+impl RpcAccountStore {
+    /// Have [`add_programs`](Self::add_programs) load `program` from the ELF at
+    /// `path` instead of the chain, whether or not the program is cached.
+    ///
+    /// The ELF is read and validated when `add_programs` runs, like fetched ones,
+    /// and loaded with BPF Loader v3. A program Mollusk already has is kept unless
+    /// it's listed in [`force_reload`](Self::force_reload).
+    pub fn with_program_from_file(mut self, program: Pubkey, path: impl Into<PathBuf>) -> Self {
+        self.local_programs.insert(program, path.into());
+        self
+    }
+
+    /// Prefer the programs built in a cargo or Anchor workspace over their on-chain
+    /// versions, as with [`with_program_from_file`](Self::with_program_from_file).
+    ///
+    /// Every `target/deploy/<name>.so` under `workspace_root` is registered under
+    /// the id `Anchor.toml` gives `<name>` (`localnet` first), or else the pubkey of
+    /// `target/deploy/<name>-keypair.json`. ELFs with neither are skipped with a
+    /// [`WarningCode::LocalProgramWithoutId`] warning.
+    ///
+    /// ```rust,ignore
+    /// let store = RpcAccountStore::mainnet()
+    ///     .with_workspace_programs(env!("CARGO_MANIFEST_DIR"))?
+    ///     .from_instruction(&instruction)
+    ///     .await?
+    ///     .add_programs(&mut mollusk)
+    ///     .await?;
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns [`RpcError::LocalProgram`] if `target/deploy` can't be read or a
+    /// keypair file is invalid, and [`RpcError::InvalidConfig`] if `Anchor.toml` is.
+    pub fn with_workspace_programs(
+        mut self,
+        workspace_root: impl AsRef<Path>,
+    ) -> Result<Self, RpcError> {
+        let root = workspace_root.as_ref();
+        let deploy = root.join("target").join("deploy");
+        let anchor_ids = anchor_program_ids(&root.join("Anchor.toml"))?;
+        let entries = fs::read_dir(&deploy).map_err(|e| RpcError::LocalProgram {
+            path: deploy.clone(),
+            reason: e.to_string(),
+        })?;
+
+        let mut elfs = Vec::new();
+        for entry in entries {
+            let path = entry
+                .map_err(|e| RpcError::LocalProgram {
+                    path: deploy.clone(),
+                    reason: e.to_string(),
+                })?
+                .path();
+            let name = path.file_stem().and_then(|stem| stem.to_str());
+            if let (Some(name), true) = (name, path.extension().is_some_and(|ext| ext == "so")) {
+                elfs.push((name.to_string(), path.clone()));
+            }
+        }
+        elfs.sort();
+
+        for (name, path) in elfs {
+            let id = match anchor_ids.get(&name) {
+                Some(id) => Some(*id),
+                None => keypair_pubkey(&deploy.join(format!("{name}-keypair.json")))?,
+            };
+            let Some(id) = id else {
+                self.warn(
+                    WarningCode::LocalProgramWithoutId,
+                    format!("{} has no Anchor.toml entry or keypair file", path.display()),
+                    None,
+                );
+                continue;
+            };
+            self.local_programs.insert(id, path);
+        }
+        Ok(self)
+    }
+
+    /// Load the programs registered with
+    /// [`with_program_from_file`](Self::with_program_from_file), for `add_programs`.
+    pub(crate) fn load_local_programs(
+        &self,
+        mollusk: &mut Mollusk,
+        loaded: &mut Vec<Pubkey>,
+        failures: &mut Vec<RecordedError>,
+        warnings: &mut Vec<Warning>,
+    ) -> Result<(), RpcError> {
+        let mut programs: Vec<(&Pubkey, &PathBuf)> = self.local_programs.iter().collect();
+        programs.sort();
+        for (pubkey, path) in programs {
+            if self.keeps_loaded_program(mollusk, pubkey) {
+                loaded.push(*pubkey);
+                continue;
+            }
+            let checked = fs::read(path)
+                .map_err(|e| RpcError::LocalProgram {
+                    path: path.clone(),
+                    reason: e.to_string(),
+                })
+                .and_then(|elf_data| {
+                    let warning = self.check_program_elf(pubkey, &elf_data)?;
+                    Ok((elf_data, warning))
+                });
+            let (elf_data, warning) = match checked {
+                Ok(checked) => checked,
+                Err(error) if self.lenient => {
+                    failures.push(RecordedError {
+                        pubkey: *pubkey,
+                        error,
+                    });
+                    continue;
+                }
+                Err(e) => return Err(e),
+            };
+            if let Some(warning) = warning {
+                self.notify(&warning);
+                warnings.push(warning);
+            }
+
+            mollusk.add_program_with_elf_and_loader(
+                pubkey,
+                &elf_data,
+                &mollusk_svm::program::loader_keys::LOADER_V3,
+            );
+            loaded.push(*pubkey);
+
+            #[cfg(feature = "tracing")]
+            tracing::debug!(
+                name: "program_loaded",
+                program = %pubkey,
+                path = %path.display(),
+                elf_size = elf_data.len(),
+            );
+        }
+        Ok(())
+    }
+}
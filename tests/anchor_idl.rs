@@ -0,0 +1,322 @@
+//! `with_anchor_account_from_idl`: an account built from an IDL and JSON values
+//! holds the bytes Anchor writes for the same `#[account]` struct, and deserializes
+//! back into it through the current and the legacy IDL formats; values and types
+//! the IDL can't encode are errors naming their field.
+
+#![cfg(feature = "anchor-idl")]
+
+mod common;
+
+use {
+    anchor_lang::{
+        account, declare_id,
+        prelude::{AnchorDeserialize, AnchorSerialize, Pubkey},
+        AccountDeserialize, AccountSerialize, Discriminator,
+    },
+    common::store,
+    mollusk_on_demand::{testing::MockFetcher, RpcError},
+    serde_json::{json, Value},
+    solana_rent::Rent,
+};
+
+declare_id!("AMM55ShdkoGRB5jVYPjWziwk8m5MpwyDgsMWHaMSQWH6");
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq)]
+pub struct Fees {
+    pub trade_bps: u16,
+    pub protocol_bps: u16,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq)]
+pub enum Curve {
+    ConstantProduct,
+    Stable { amplification: u64 },
+    Offset(u64, i32),
+}
+
+/// A pool as the program defines it, with a field of each kind the IDL can hold.
+#[account]
+#[derive(Debug, PartialEq)]
+pub struct PoolState {
+    pub authority: Pubkey,
+    pub fee_bps: u16,
+    pub paused: bool,
+    pub liquidity: u128,
+    pub delta: i64,
+    pub price: f64,
+    pub name: String,
+    pub memo: Vec<u8>,
+    pub admin: Option<Pubkey>,
+    pub closer: Option<Pubkey>,
+    pub reserves: [u64; 2],
+    pub fees: Fees,
+    pub curve: Curve,
+    pub history: Vec<Curve>,
+}
+
+const POOL: Pubkey = Pubkey::new_from_array([1; 32]);
+const AUTHORITY: Pubkey = Pubkey::new_from_array([2; 32]);
+const ADMIN: Pubkey = Pubkey::new_from_array([3; 32]);
+
+fn pool_state() -> PoolState {
+    PoolState {
+        authority: AUTHORITY,
+        fee_bps: 30,
+        paused: false,
+        liquidity: u128::MAX - 1,
+        delta: -42,
+        price: 1.5,
+        name: "SOL-USDC".to_string(),
+        memo: vec![1, 2, 3],
+        admin: Some(ADMIN),
+        closer: None,
+        reserves: [1_000, u64::MAX],
+        fees: Fees {
+            trade_bps: 25,
+            protocol_bps: 5,
+        },
+        curve: Curve::Stable { amplification: 100 },
+        history: vec![Curve::ConstantProduct, Curve::Offset(7, -1)],
+    }
+}
+
+/// The values of [`pool_state`], by their IDL names.
+fn values() -> Value {
+    json!({
+        "authority": AUTHORITY.to_string(),
+        "fee_bps": 30,
+        "paused": false,
+        // Beyond what a JSON number holds exactly
+        "liquidity": (u128::MAX - 1).to_string(),
+        "delta": -42,
+        "price": 1.5,
+        "name": "SOL-USDC",
+        "memo": [1, 2, 3],
+        "admin": ADMIN.to_string(),
+        "closer": null,
+        "reserves": [1_000, u64::MAX.to_string()],
+        "fees": {"trade_bps": 25, "protocol_bps": 5},
+        "curve": {"Stable": {"amplification": 100}},
+        "history": ["ConstantProduct", {"Offset": [7, -1]}],
+    })
+}
+
+/// The pool's fields as an IDL lists them, in the current format or the legacy one.
+fn pool_fields(legacy: bool) -> Value {
+    let (pubkey, fees, curve) = if legacy {
+        (
+            json!("publicKey"),
+            json!({"defined": "Fees"}),
+            json!({"defined": "Curve"}),
+        )
+    } else {
+        (
+            json!("pubkey"),
+            json!({"defined": {"name": "Fees"}}),
+            json!({"defined": {"name": "Curve"}}),
+        )
+    };
+    json!([
+        {"name": "authority", "type": pubkey},
+        {"name": "fee_bps", "type": "u16"},
+        {"name": "paused", "type": "bool"},
+        {"name": "liquidity", "type": "u128"},
+        {"name": "delta", "type": "i64"},
+        {"name": "price", "type": "f64"},
+        {"name": "name", "type": "string"},
+        {"name": "memo", "type": "bytes"},
+        {"name": "admin", "type": {"option": pubkey}},
+        {"name": "closer", "type": {"option": pubkey}},
+        {"name": "reserves", "type": {"array": ["u64", 2]}},
+        {"name": "fees", "type": fees},
+        {"name": "curve", "type": curve},
+        {"name": "history", "type": {"vec": curve}},
+    ])
+}
+
+/// The types besides the pool, for either format.
+fn types() -> Value {
+    json!([
+        {
+            "name": "Fees",
+            "type": {"kind": "struct", "fields": [
+                {"name": "trade_bps", "type": "u16"},
+                {"name": "protocol_bps", "type": "u16"},
+            ]},
+        },
+        {
+            "name": "Curve",
+            "type": {"kind": "enum", "variants": [
+                {"name": "ConstantProduct"},
+                {"name": "Stable", "fields": [{"name": "amplification", "type": "u64"}]},
+                {"name": "Offset", "fields": ["u64", "i32"]},
+            ]},
+        },
+    ])
+}
+
+/// An IDL as Anchor 0.30 and later publish it, with the discriminator listed.
+fn current_idl() -> Value {
+    let mut types = types();
+    types.as_array_mut().unwrap().push(json!({
+        "name": "PoolState",
+        "type": {"kind": "struct", "fields": pool_fields(false)},
+    }));
+    json!({
+        "address": ID.to_string(),
+        "metadata": {"name": "amm", "version": "0.1.0", "spec": "0.1.0"},
+        "instructions": [],
+        "accounts": [{"name": "PoolState", "discriminator": PoolState::DISCRIMINATOR}],
+        "types": types,
+    })
+}
+
+/// An IDL in the legacy format, with the account type inline and no discriminator.
+fn legacy_idl() -> Value {
+    json!({
+        "version": "0.1.0",
+        "name": "amm",
+        "instructions": [],
+        "accounts": [{
+            "name": "PoolState",
+            "type": {"kind": "struct", "fields": pool_fields(true)},
+        }],
+        "types": types(),
+        "metadata": {"address": ID.to_string()},
+    })
+}
+
+fn build(idl: &Value, value: Value) -> Result<Vec<u8>, RpcError> {
+    let store = store(&MockFetcher::new()).with_anchor_account_from_idl(
+        &idl.to_string(),
+        "PoolState",
+        POOL,
+        value,
+    )?;
+    let account = &store.cache[&POOL];
+    assert_eq!(account.owner, ID);
+    assert_eq!(
+        account.lamports,
+        Rent::default().minimum_balance(account.data.len())
+    );
+    Ok(account.data.clone())
+}
+
+#[test]
+fn built_accounts_match_anchors_serialization() {
+    let mut expected = Vec::new();
+    pool_state().try_serialize(&mut expected).unwrap();
+
+    for idl in [current_idl(), legacy_idl()] {
+        let data = build(&idl, values()).unwrap();
+        assert_eq!(data, expected);
+        assert_eq!(
+            PoolState::try_deserialize(&mut data.as_slice()).unwrap(),
+            pool_state()
+        );
+    }
+}
+
+/// The reason building the pool from `idl` and `value` failed for.
+fn reason(idl: &Value, value: Value) -> String {
+    let error = build(idl, value).unwrap_err();
+    let RpcError::AnchorIdl { account, reason } = &error else {
+        panic!("expected AnchorIdl, got {error}");
+    };
+    assert_eq!(account, "PoolState");
+    reason.clone()
+}
+
+#[test]
+fn bad_values_name_their_field() {
+    let idl = current_idl();
+    let with = |field: &str, value: Value| {
+        let mut values = values();
+        values[field] = value;
+        values
+    };
+    for (value, expected) in [
+        (
+            with("fee_bps", json!(70_000)),
+            "field `fee_bps` should be a u16, got 70000",
+        ),
+        (
+            with("paused", json!(1)),
+            "field `paused` should be a bool, got 1",
+        ),
+        (
+            with("authority", json!("not a pubkey")),
+            "field `authority` should be a base58 pubkey, got \"not a pubkey\"",
+        ),
+        (
+            with("reserves", json!([1])),
+            "field `reserves` should be an array of 2 items, got [1]",
+        ),
+        (
+            with("fees", json!({"trade_bps": 25, "protocol_bps": "five"})),
+            "field `fees.protocol_bps` should be a u16, got \"five\"",
+        ),
+        (
+            with("history", json!(["Linear"])),
+            "field `history[0]` has no Curve variant Linear",
+        ),
+        (
+            with("fees", json!({"trade_bps": 25})),
+            "field `fees.protocol_bps` is missing",
+        ),
+        (
+            with(
+                "fees",
+                json!({"trade_bps": 25, "protocol_bps": 5, "extra": 1}),
+            ),
+            "field `fees.extra` isn't in the IDL",
+        ),
+    ] {
+        assert_eq!(reason(&idl, value), expected);
+    }
+}
+
+#[test]
+fn unsupported_idls_are_errors() {
+    // A type the encoder doesn't know
+    let mut idl = current_idl();
+    idl["types"][2]["type"]["fields"][1]["type"] = json!("u256");
+    assert_eq!(
+        reason(&idl, values()),
+        "field `fee_bps` has an unsupported type \"u256\""
+    );
+
+    // A zero-copy account
+    let mut idl = current_idl();
+    idl["types"][0]["serialization"] = json!("bytemuck");
+    assert_eq!(
+        reason(&idl, values()),
+        "field `fees` is of type Fees, which uses bytemuck serialization rather than borsh"
+    );
+
+    let mut idl = current_idl();
+    idl["types"][2]["type"]["fields"][11]["type"] = json!({"defined": {"name": "Rates"}});
+    assert_eq!(
+        reason(&idl, values()),
+        "field `fees` is of type Rates, which the IDL doesn't define"
+    );
+
+    let error = store(&MockFetcher::new())
+        .with_anchor_account_from_idl(&current_idl().to_string(), "Position", POOL, json!({}))
+        .map(|_| ())
+        .unwrap_err();
+    let RpcError::AnchorIdl {
+        reason: missing, ..
+    } = &error
+    else {
+        panic!("expected AnchorIdl, got {error}");
+    };
+    assert_eq!(missing, "the IDL has no such account (it has PoolState)");
+
+    let mut idl = legacy_idl();
+    idl["metadata"] = json!({});
+    assert_eq!(
+        reason(&idl, values()),
+        "the IDL doesn't give the program's address"
+    );
+}
@@ -0,0 +1,123 @@
+//! `apply_result`: a later instruction sees an earlier one's changes through the
+//! store alone, failed instructions change nothing, and programs and sysvars in a
+//! result are left alone.
+
+mod common;
+
+use {
+    common::{account, store},
+    mollusk_on_demand::{process_instruction_with_store, testing::MockFetcher, Provenance},
+    mollusk_svm::{result::InstructionResult, Mollusk},
+    solana_account::Account,
+    solana_instruction::{AccountMeta, Instruction},
+    solana_pubkey::Pubkey,
+    solana_sdk_ids::{bpf_loader_upgradeable, system_program, sysvar},
+};
+
+const PAYER_LAMPORTS: u64 = 10_000_000_000;
+
+fn create_account(payer: &Pubkey, new: &Pubkey, lamports: u64) -> Instruction {
+    let mut data = 0u32.to_le_bytes().to_vec();
+    data.extend_from_slice(&lamports.to_le_bytes());
+    data.extend_from_slice(&0u64.to_le_bytes());
+    data.extend_from_slice(system_program::ID.as_ref());
+    Instruction::new_with_bytes(
+        system_program::ID,
+        &data,
+        vec![AccountMeta::new(*payer, true), AccountMeta::new(*new, true)],
+    )
+}
+
+fn transfer(from: &Pubkey, to: &Pubkey, lamports: u64) -> Instruction {
+    let mut data = 2u32.to_le_bytes().to_vec();
+    data.extend_from_slice(&lamports.to_le_bytes());
+    Instruction::new_with_bytes(
+        system_program::ID,
+        &data,
+        vec![AccountMeta::new(*from, true), AccountMeta::new(*to, false)],
+    )
+}
+
+#[tokio::test]
+async fn a_created_account_is_consumed_by_the_next_instruction() {
+    let (payer, new) = (Pubkey::new_unique(), Pubkey::new_unique());
+    let fetcher = MockFetcher::new().with_account(payer, account(PAYER_LAMPORTS, 0));
+    let create = create_account(&payer, &new, 2_000_000_000);
+    let mut store = store(&fetcher)
+        .allow_missing_accounts()
+        .from_instruction(&create)
+        .await
+        .unwrap();
+    let mollusk = Mollusk::default();
+
+    let created = process_instruction_with_store(&mollusk, &store, &create).unwrap();
+    assert!(created.program_result.is_ok(), "{:?}", created.program_result);
+    assert_eq!(store.apply_result(&created), 2);
+    assert_eq!(store.cache[&new].lamports, 2_000_000_000);
+    assert_eq!(
+        store.provenance(&new),
+        Some(&Provenance::ResultApplied { instruction_index: Some(0) })
+    );
+
+    // Spending from the new account only works on the post-create state
+    let consume = transfer(&new, &payer, 500_000_000);
+    let consumed = process_instruction_with_store(&mollusk, &store, &consume).unwrap();
+    assert!(consumed.program_result.is_ok(), "{:?}", consumed.program_result);
+    store.apply_result(&consumed);
+    assert_eq!(store.cache[&new].lamports, 1_500_000_000);
+    assert_eq!(store.cache[&payer].lamports, PAYER_LAMPORTS - 1_500_000_000);
+    assert_eq!(
+        store.provenance(&payer),
+        Some(&Provenance::ResultApplied { instruction_index: Some(1) })
+    );
+    assert_eq!(fetcher.calls().len(), 1);
+}
+
+#[tokio::test]
+async fn failed_instructions_change_nothing() {
+    let (payer, recipient) = (Pubkey::new_unique(), Pubkey::new_unique());
+    let fetcher = MockFetcher::new()
+        .with_account(payer, account(PAYER_LAMPORTS, 0))
+        .with_account(recipient, account(1_000_000_000, 0));
+    let overdraft = transfer(&payer, &recipient, PAYER_LAMPORTS + 1);
+    let mut store = store(&fetcher).from_instruction(&overdraft).await.unwrap();
+
+    let result = process_instruction_with_store(&Mollusk::default(), &store, &overdraft).unwrap();
+    assert!(result.program_result.is_err());
+    assert_eq!(store.apply_result(&result), 0);
+    assert_eq!(store.cache[&payer].lamports, PAYER_LAMPORTS);
+    assert!(matches!(store.provenance(&payer), Some(Provenance::Fetched { .. })));
+}
+
+#[tokio::test]
+async fn programs_and_sysvars_are_left_alone() {
+    let program = Pubkey::new_unique();
+    let written = Pubkey::new_unique();
+    let mut store = store(&MockFetcher::new());
+    let result = InstructionResult {
+        resulting_accounts: vec![
+            (
+                program,
+                Account {
+                    executable: true,
+                    owner: bpf_loader_upgradeable::ID,
+                    ..account(1_000_000_000, 36)
+                },
+            ),
+            (
+                sysvar::clock::ID,
+                Account {
+                    owner: sysvar::ID,
+                    ..account(1_000_000_000, 40)
+                },
+            ),
+            (written, account(1_000_000_000, 0)),
+        ],
+        ..InstructionResult::default()
+    };
+
+    assert_eq!(store.apply_result(&result), 1);
+    assert!(!store.cache.contains_key(&program));
+    assert!(!store.cache.contains_key(&sysvar::clock::ID));
+    assert!(store.cache.contains_key(&written));
+}
@@ -0,0 +1,196 @@
+//! `export_bench` round trip: state cloned into a store is exported, loaded back
+//! into a fresh Mollusk without RPC and run by `mollusk-svm-bencher`; scenarios in
+//! one directory don't collide, re-exporting replaces one, and names must be plain.
+
+mod common;
+
+use {
+    common::{account, store, temp_dir},
+    mollusk_on_demand::{load_bench_scenarios, testing::MockFetcher, RpcAccountStore, RpcError},
+    mollusk_svm::Mollusk,
+    mollusk_svm_bencher::MolluskComputeUnitBencher,
+    mollusk_svm_programs_token::token2022,
+    solana_account::Account,
+    solana_instruction::{AccountMeta, Instruction},
+    solana_pubkey::Pubkey,
+    solana_rent::Rent,
+    solana_sdk_ids::{bpf_loader_upgradeable, system_program},
+};
+
+const MINT_LEN: usize = 82;
+
+/// Token-2022 deployed through the upgradeable loader, as `getMultipleAccounts`
+/// returns it: the program account and its program data.
+fn token2022_accounts() -> [(Pubkey, Account); 2] {
+    let program_data = Pubkey::new_unique();
+    let mut data = 2u32.to_le_bytes().to_vec();
+    data.extend_from_slice(program_data.as_ref());
+    let program_account = Account {
+        lamports: Rent::default().minimum_balance(data.len()),
+        data,
+        executable: true,
+        owner: bpf_loader_upgradeable::ID,
+        ..account(0, 0)
+    };
+
+    // The ProgramData header: tag, deployment slot and upgrade authority
+    let mut data = 3u32.to_le_bytes().to_vec();
+    data.extend_from_slice(&0u64.to_le_bytes());
+    data.extend_from_slice(&[0; 33]);
+    data.extend_from_slice(token2022::ELF);
+    let program_data_account = Account {
+        lamports: Rent::default().minimum_balance(data.len()),
+        data,
+        owner: bpf_loader_upgradeable::ID,
+        ..account(0, 0)
+    };
+    [(token2022::ID, program_account), (program_data, program_data_account)]
+}
+
+/// `InitializeMint2` for `mint`, with no freeze authority.
+fn initialize_mint(mint: &Pubkey) -> Instruction {
+    let mut data = vec![20, 6];
+    data.extend_from_slice(Pubkey::new_unique().as_ref());
+    data.push(0);
+    Instruction::new_with_bytes(token2022::ID, &data, vec![AccountMeta::new(*mint, false)])
+}
+
+fn transfer(from: &Pubkey, to: &Pubkey) -> Instruction {
+    let mut data = 2u32.to_le_bytes().to_vec();
+    data.extend_from_slice(&1_000u64.to_le_bytes());
+    Instruction::new_with_bytes(
+        system_program::ID,
+        &data,
+        vec![AccountMeta::new(*from, true), AccountMeta::new(*to, false)],
+    )
+}
+
+/// A store holding Token-2022 and an uninitialized mint, as if cloned from a
+/// cluster, with the program loaded into `mollusk`.
+async fn cloned(mollusk: &mut Mollusk) -> (MockFetcher, RpcAccountStore, Instruction) {
+    let mint = Pubkey::new_unique();
+    let mint_account = Account {
+        lamports: Rent::default().minimum_balance(MINT_LEN),
+        owner: token2022::ID,
+        ..account(0, MINT_LEN)
+    };
+    let fetcher = MockFetcher::new().with_account(mint, mint_account);
+    let ix = initialize_mint(&mint);
+    // The instruction's metas are fetched, the program is cloned alongside
+    let store = store(&fetcher)
+        .with_accounts(token2022_accounts())
+        .from_instruction(&ix)
+        .await
+        .unwrap()
+        .add_programs(mollusk)
+        .await
+        .unwrap();
+    (fetcher, store, ix)
+}
+
+#[tokio::test]
+async fn exported_scenarios_run_like_the_store_without_rpc() {
+    let mut mollusk = Mollusk::default();
+    let (fetcher, store, ix) = cloned(&mut mollusk).await;
+    let accounts = store.instruction_accounts(&mollusk, &ix).unwrap();
+    let expected = mollusk.process_instruction(&ix, &accounts);
+    assert!(expected.program_result.is_ok(), "{:?}", expected.program_result);
+
+    let dir = temp_dir("bench");
+    let scenario_dir = store.export_bench(&mollusk, &ix, "initialize_mint", &dir).unwrap();
+    assert_eq!(scenario_dir, dir.join("initialize_mint"));
+    fetcher.clear_calls();
+
+    let scenarios = load_bench_scenarios(&dir).unwrap();
+    assert_eq!(scenarios.len(), 1);
+    let scenario = &scenarios[0];
+    assert_eq!(scenario.name, "initialize_mint");
+    assert_eq!(scenario.instruction, ix);
+    assert_eq!(scenario.accounts, accounts);
+    assert_eq!(scenario.programs.len(), 1);
+    let (program_id, loader, elf) = &scenario.programs[0];
+    assert_eq!((*program_id, *loader), (token2022::ID, bpf_loader_upgradeable::ID));
+    assert_eq!(elf.as_slice(), token2022::ELF);
+
+    let mut fresh = Mollusk::default();
+    scenario.add_programs(&mut fresh);
+    let (_, instruction, accounts) = scenario.as_bench();
+    let replayed = fresh.process_instruction(instruction, accounts);
+    assert_eq!(replayed.program_result, expected.program_result);
+    assert_eq!(replayed.compute_units_consumed, expected.compute_units_consumed);
+    assert_eq!(replayed.resulting_accounts, expected.resulting_accounts);
+    assert!(fetcher.calls().is_empty());
+}
+
+#[tokio::test]
+async fn the_bencher_runs_the_exported_scenarios() {
+    let mut mollusk = Mollusk::default();
+    let (_, store, ix) = cloned(&mut mollusk).await;
+    let dir = temp_dir("bench");
+    let (scenarios_dir, results_dir) = (dir.join("scenarios"), dir.join("results"));
+    store.export_bench(&mollusk, &ix, "initialize_mint", &scenarios_dir).unwrap();
+
+    let scenarios = load_bench_scenarios(&scenarios_dir).unwrap();
+    let mut fresh = Mollusk::default();
+    for scenario in &scenarios {
+        scenario.add_programs(&mut fresh);
+    }
+    std::fs::create_dir_all(&results_dir).unwrap();
+    let mut bencher = MolluskComputeUnitBencher::new(fresh)
+        .must_pass(true)
+        .out_dir(results_dir.to_str().unwrap());
+    for scenario in &scenarios {
+        bencher = bencher.bench(scenario.as_bench());
+    }
+    bencher.execute();
+
+    let report = std::fs::read_to_string(results_dir.join("compute_units.md")).unwrap();
+    assert!(report.contains("initialize_mint"), "{report}");
+}
+
+#[tokio::test]
+async fn named_scenarios_share_a_directory_without_colliding() {
+    let mut mollusk = Mollusk::default();
+    let (_, mint_store, mint_ix) = cloned(&mut mollusk).await;
+    let (from, to) = (Pubkey::new_unique(), Pubkey::new_unique());
+    let wallets = [(from, account(1_000_000_000, 0)), (to, account(1_000_000_000, 0))];
+    let transfer_store =
+        RpcAccountStore::new_with_fetcher(MockFetcher::new()).with_accounts(wallets);
+    let transfer_ix = transfer(&from, &to);
+    let dir = temp_dir("bench");
+
+    mint_store.export_bench(&mollusk, &mint_ix, "initialize_mint", &dir).unwrap();
+    transfer_store.export_bench(&mollusk, &transfer_ix, "transfer", &dir).unwrap();
+    let scenarios = load_bench_scenarios(&dir).unwrap();
+    let names: Vec<&str> = scenarios.iter().map(|scenario| scenario.name.as_str()).collect();
+    assert_eq!(names, ["initialize_mint", "transfer"]);
+    assert_eq!(scenarios[0].instruction, mint_ix);
+    assert_eq!(scenarios[1].instruction, transfer_ix);
+    // The system program is a builtin
+    assert!(scenarios[1].programs.is_empty());
+
+    // Re-exporting under a name replaces only that scenario
+    transfer_store.export_bench(&mollusk, &transfer_ix, "initialize_mint", &dir).unwrap();
+    let scenarios = load_bench_scenarios(&dir).unwrap();
+    assert_eq!(scenarios.len(), 2);
+    assert_eq!(scenarios[0].instruction, transfer_ix);
+    assert!(scenarios[0].programs.is_empty());
+    assert!(!dir.join("initialize_mint").join("programs").exists());
+    assert_eq!(scenarios[1].instruction, transfer_ix);
+}
+
+#[tokio::test]
+async fn scenario_names_must_be_plain() {
+    let (from, to) = (Pubkey::new_unique(), Pubkey::new_unique());
+    let wallets = [(from, account(1_000_000_000, 0)), (to, account(1_000_000_000, 0))];
+    let store = RpcAccountStore::new_with_fetcher(MockFetcher::new()).with_accounts(wallets);
+    let dir = temp_dir("bench");
+
+    for name in ["", "a/b", "..", "."] {
+        let error = store
+            .export_bench(&Mollusk::default(), &transfer(&from, &to), name, &dir)
+            .unwrap_err();
+        assert!(matches!(error, RpcError::Fixture { .. }), "{name}: {error}");
+    }
+    assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 0);
+}
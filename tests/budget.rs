@@ -0,0 +1,87 @@
+//! `with_rpc_budget` boundaries: the last allowed request goes through, the next
+//! one fails before reaching the fetcher.
+
+mod common;
+
+use {
+    common::{pubkeys, serving, store},
+    mollusk_on_demand::RpcError,
+    mollusk_svm::Mollusk,
+};
+
+#[tokio::test]
+async fn reaching_the_limit_succeeds() {
+    let keys = pubkeys(3);
+    let fetcher = serving(&keys);
+    let mut store = store(&fetcher).with_rpc_budget(3);
+    for pubkey in &keys {
+        store.get_or_fetch(pubkey).await.unwrap();
+    }
+
+    assert_eq!(fetcher.calls().len(), 3);
+    assert_eq!(store.stats().total_requests(), 3);
+    // Cached accounts cost nothing, so the spent budget doesn't stop them
+    store.get_or_fetch(&keys[0]).await.unwrap();
+}
+
+#[tokio::test]
+async fn the_request_over_the_limit_fails_with_its_method() {
+    let keys = pubkeys(2);
+    let fetcher = serving(&keys);
+    let mut store = store(&fetcher).with_rpc_budget(1);
+    store.get_or_fetch(&keys[0]).await.unwrap();
+
+    let error = store.get_or_fetch(&keys[1]).await.map(|_| ()).unwrap_err();
+    let RpcError::BudgetExceeded {
+        limit,
+        attempted_method,
+        spent,
+    } = &error
+    else {
+        panic!("expected BudgetExceeded, got {error}");
+    };
+    assert_eq!(*limit, 1);
+    assert_eq!(*attempted_method, "getMultipleAccounts");
+    assert_eq!(spent.requests_for("getMultipleAccounts"), 1);
+    assert_eq!(error.code(), "budget_exceeded");
+    assert_eq!(fetcher.calls().len(), 1, "the failing request wasn't sent");
+
+    let error = store
+        .with_synced_slot(&mut Mollusk::default())
+        .await
+        .map(|_| ())
+        .unwrap_err();
+    assert!(
+        matches!(error, RpcError::BudgetExceeded { attempted_method: "getSlot", .. }),
+        "{error}"
+    );
+}
+
+#[tokio::test]
+async fn a_budget_of_zero_fails_with_the_budget_error() {
+    let keys = pubkeys(1);
+    let fetcher = serving(&keys);
+    let error = store(&fetcher)
+        .with_rpc_budget(0)
+        .get_or_fetch(&keys[0])
+        .await
+        .map(|_| ())
+        .unwrap_err();
+
+    assert!(
+        matches!(error, RpcError::BudgetExceeded { limit: 0, .. }),
+        "expected the budget error rather than Offline, got {error}"
+    );
+    assert!(fetcher.calls().is_empty());
+}
+
+#[tokio::test]
+async fn resetting_stats_refills_the_budget() {
+    let keys = pubkeys(2);
+    let fetcher = serving(&keys);
+    let mut store = store(&fetcher).with_rpc_budget(1);
+    store.get_or_fetch(&keys[0]).await.unwrap();
+    store.reset_stats();
+    store.get_or_fetch(&keys[1]).await.unwrap();
+    assert_eq!(fetcher.calls().len(), 2);
+}
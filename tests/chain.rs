@@ -0,0 +1,172 @@
+//! `run_chain_with_slot_advance`: every step's advance moves the clock, skipped
+//! ComputeBudget steps included, huge advances saturate, and an instruction that
+//! must wait for a later slot succeeds once the chain advances to it.
+
+mod common;
+
+use {
+    common::{account, store},
+    mollusk_on_demand::{testing::MockFetcher, SlotAdvance},
+    mollusk_svm::Mollusk,
+    solana_account::Account,
+    solana_instruction::{AccountMeta, Instruction},
+    solana_pubkey::Pubkey,
+    solana_rent::Rent,
+    solana_sdk_ids::{bpf_loader_upgradeable, compute_budget, system_program},
+};
+
+/// A system program transfer of `lamports` from `from` to `to`.
+fn transfer(from: &Pubkey, to: &Pubkey, lamports: u64) -> Instruction {
+    let mut data = 2u32.to_le_bytes().to_vec();
+    data.extend_from_slice(&lamports.to_le_bytes());
+    Instruction::new_with_bytes(
+        system_program::ID,
+        &data,
+        vec![AccountMeta::new(*from, true), AccountMeta::new(*to, false)],
+    )
+}
+
+/// A ComputeBudget `SetComputeUnitLimit`.
+fn compute_unit_limit(units: u32) -> Instruction {
+    let mut data = vec![2];
+    data.extend_from_slice(&units.to_le_bytes());
+    Instruction::new_with_bytes(compute_budget::ID, &data, vec![])
+}
+
+/// A BPF Loader v3 program deployed in `slot`, and a `Close` of its program data
+/// paying `recipient`, which the loader rejects in the slot of the deployment.
+fn close_program(slot: u64, recipient: &Pubkey) -> (MockFetcher, Instruction) {
+    let (program, program_data, authority) =
+        (Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+    let loader_account = |data: Vec<u8>, executable| Account {
+        lamports: Rent::default().minimum_balance(data.len()),
+        data,
+        owner: bpf_loader_upgradeable::ID,
+        executable,
+        rent_epoch: 0,
+    };
+    let mut data = 2u32.to_le_bytes().to_vec();
+    data.extend_from_slice(program_data.as_ref());
+    let program_account = loader_account(data, true);
+    let mut data = 3u32.to_le_bytes().to_vec();
+    data.extend_from_slice(&slot.to_le_bytes());
+    data.push(1);
+    data.extend_from_slice(authority.as_ref());
+    data.extend_from_slice(mollusk_svm_programs_token::token::ELF);
+    let program_data_account = loader_account(data, false);
+
+    let fetcher = MockFetcher::new()
+        .with_account(program, program_account)
+        .with_account(program_data, program_data_account)
+        .with_account(authority, account(1_000_000_000, 0))
+        .with_account(*recipient, account(1_000_000_000, 0));
+    let close = Instruction::new_with_bytes(
+        bpf_loader_upgradeable::ID,
+        &5u32.to_le_bytes(),
+        vec![
+            AccountMeta::new(program_data, false),
+            AccountMeta::new(*recipient, false),
+            AccountMeta::new_readonly(authority, true),
+            AccountMeta::new(program, false),
+        ],
+    );
+    (fetcher, close)
+}
+
+fn payer_and_recipient() -> (MockFetcher, Pubkey, Pubkey) {
+    let (payer, recipient) = (Pubkey::new_unique(), Pubkey::new_unique());
+    let fetcher = MockFetcher::new()
+        .with_account(payer, account(10_000_000_000, 0))
+        .with_account(recipient, account(1_000_000_000, 0));
+    (fetcher, payer, recipient)
+}
+
+#[tokio::test]
+async fn steps_run_in_the_slots_their_advances_reach() {
+    let (fetcher, payer, recipient) = payer_and_recipient();
+    let mut mollusk = Mollusk::default();
+    let start = mollusk.sysvars.clock.clone();
+    let steps = store(&fetcher)
+        .run_chain_with_slot_advance(
+            &mut mollusk,
+            &[
+                (transfer(&payer, &recipient, 1), SlotAdvance::None),
+                (transfer(&payer, &recipient, 1), SlotAdvance::Slots(10)),
+                (transfer(&payer, &recipient, 1), SlotAdvance::Seconds(2)),
+            ],
+        )
+        .await
+        .unwrap();
+
+    let slots: Vec<_> = steps.iter().map(|step| step.slot).collect();
+    assert_eq!(slots, [start.slot, start.slot + 10, start.slot + 15]);
+    assert!(steps.iter().all(|step| step.result.program_result.is_ok()));
+    assert_eq!(mollusk.sysvars.clock.unix_timestamp, start.unix_timestamp + 6);
+}
+
+#[tokio::test]
+async fn skipped_compute_budget_steps_still_advance_the_clock() {
+    let (fetcher, payer, recipient) = payer_and_recipient();
+    let mut mollusk = Mollusk::default();
+    let start = mollusk.sysvars.clock.slot;
+    let steps = store(&fetcher)
+        .run_chain_with_slot_advance(
+            &mut mollusk,
+            &[
+                (compute_unit_limit(200_000), SlotAdvance::Slots(150)),
+                (transfer(&payer, &recipient, 1), SlotAdvance::None),
+                (compute_unit_limit(200_000), SlotAdvance::Seconds(4)),
+                (transfer(&payer, &recipient, 1), SlotAdvance::Slots(1)),
+            ],
+        )
+        .await
+        .unwrap();
+
+    let slots: Vec<_> = steps.iter().map(|step| step.slot).collect();
+    assert_eq!(slots, [start + 150, start + 161]);
+}
+
+#[tokio::test]
+async fn huge_advances_saturate() {
+    let (fetcher, payer, recipient) = payer_and_recipient();
+    let mut mollusk = Mollusk::default();
+    let steps = store(&fetcher)
+        .run_chain_with_slot_advance(
+            &mut mollusk,
+            &[
+                (transfer(&payer, &recipient, 1), SlotAdvance::Seconds(u64::MAX)),
+                (transfer(&payer, &recipient, 1), SlotAdvance::Slots(u64::MAX)),
+            ],
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(steps.len(), 2);
+    assert_eq!(steps[1].slot, u64::MAX);
+    assert_eq!(mollusk.sysvars.clock.unix_timestamp, i64::MAX);
+}
+
+#[tokio::test]
+async fn a_step_that_needs_a_later_slot_succeeds_after_advancing() {
+    let mut mollusk = Mollusk::default();
+    let deployed = mollusk.sysvars.clock.slot;
+    let recipient = Pubkey::new_unique();
+    let (fetcher, close) = close_program(deployed, &recipient);
+    let mut store = store(&fetcher);
+
+    let steps = store
+        .run_chain_with_slot_advance(&mut mollusk, &[(close.clone(), SlotAdvance::None)])
+        .await
+        .unwrap();
+    assert_eq!(steps[0].slot, deployed);
+    assert!(steps[0].result.program_result.is_err());
+    assert_eq!(store.cache[&recipient].lamports, 1_000_000_000);
+
+    let steps = store
+        .run_chain_with_slot_advance(&mut mollusk, &[(close, SlotAdvance::Slots(1))])
+        .await
+        .unwrap();
+    assert_eq!(steps[0].slot, deployed + 1);
+    assert!(steps[0].result.program_result.is_ok(), "{:?}", steps[0].result.program_result);
+    assert!(store.cache[&recipient].lamports > 1_000_000_000);
+}
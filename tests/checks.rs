@@ -0,0 +1,103 @@
+//! Checks built from the cached pre-state: they pass for what a system transfer
+//! changes and leaves alone, fail for wrong expectations, and need the account to
+//! be cached.
+
+mod common;
+
+use {
+    common::{account, store},
+    mollusk_on_demand::{testing::MockFetcher, RpcAccountStore, RpcError},
+    mollusk_svm::{
+        result::{Check, Config},
+        Mollusk,
+    },
+    solana_instruction::{AccountMeta, Instruction},
+    solana_pubkey::Pubkey,
+    solana_sdk_ids::system_program,
+};
+
+const LAMPORTS: u64 = 1_000_000_000;
+
+/// A transfer from `from` to `to` that also passes `bystander`, which it ignores.
+fn transfer(from: &Pubkey, to: &Pubkey, bystander: &Pubkey, lamports: u64) -> Instruction {
+    let mut data = 2u32.to_le_bytes().to_vec();
+    data.extend_from_slice(&lamports.to_le_bytes());
+    Instruction::new_with_bytes(
+        system_program::ID,
+        &data,
+        vec![
+            AccountMeta::new(*from, true),
+            AccountMeta::new(*to, false),
+            AccountMeta::new_readonly(*bystander, false),
+        ],
+    )
+}
+
+async fn fetched() -> (RpcAccountStore, Instruction, [Pubkey; 3]) {
+    let [from, to, bystander] = [Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique()];
+    let mut data_account = account(LAMPORTS, 8);
+    data_account.data = vec![1, 2, 3, 4, 5, 6, 7, 8];
+    let fetcher = MockFetcher::new()
+        .with_account(from, account(LAMPORTS, 0))
+        .with_account(to, account(LAMPORTS, 0))
+        .with_account(bystander, data_account);
+    let ix = transfer(&from, &to, &bystander, 1_000);
+    let store = store(&fetcher).from_instruction(&ix).await.unwrap();
+    (store, ix, [from, to, bystander])
+}
+
+#[tokio::test]
+async fn generated_checks_validate_a_transfer() {
+    let (store, ix, [from, to, bystander]) = fetched().await;
+    let mut checks = vec![Check::success()];
+    checks.extend(store.expect_unchanged(&[bystander]).unwrap());
+    checks.extend(store.expect_lamport_delta(&from, -1_000).unwrap());
+    checks.extend(store.expect_lamport_delta(&to, 1_000).unwrap());
+    checks.extend(store.expect_data_eq(&bystander, 2, &[3, 4, 5]).unwrap());
+
+    let mollusk = Mollusk::default();
+    let accounts = store.instruction_accounts(&mollusk, &ix).unwrap();
+    // Panics if any check fails
+    mollusk.process_and_validate_instruction(&ix, &accounts, &checks);
+}
+
+#[tokio::test]
+async fn wrong_expectations_fail() {
+    let (store, ix, [from, to, bystander]) = fetched().await;
+    let mollusk = Mollusk::default();
+    let accounts = store.instruction_accounts(&mollusk, &ix).unwrap();
+    let result = mollusk.process_instruction(&ix, &accounts);
+    let config = Config {
+        panic: false,
+        verbose: false,
+    };
+
+    for checks in [
+        store.expect_unchanged(&[from]).unwrap(),
+        store.expect_lamport_delta(&to, 999).unwrap(),
+        store.expect_data_eq(&bystander, 0, &[9]).unwrap(),
+    ] {
+        assert!(!result.run_checks(&checks, &config, &mollusk));
+    }
+    assert!(result.run_checks(&store.expect_unchanged(&[bystander]).unwrap(), &config, &mollusk));
+}
+
+#[tokio::test]
+async fn checks_need_a_cached_account() {
+    let (store, ..) = fetched().await;
+    let missing = Pubkey::new_unique();
+    for error in [
+        store.expect_unchanged(&[missing]).map(|_| ()).unwrap_err(),
+        store.expect_lamport_delta(&missing, 1).map(|_| ()).unwrap_err(),
+        store.expect_data_eq(&missing, 0, &[]).map(|_| ()).unwrap_err(),
+    ] {
+        assert!(matches!(error, RpcError::AccountNotFound(pubkey) if pubkey == missing), "{error}");
+    }
+}
+
+#[tokio::test]
+#[should_panic(expected = "can't change by")]
+async fn a_delta_below_zero_panics() {
+    let (store, _, [from, ..]) = fetched().await;
+    let _ = store.expect_lamport_delta(&from, -(LAMPORTS as i64) - 1);
+}
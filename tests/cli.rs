@@ -0,0 +1,246 @@
+//! The `mollusk-fetch` binary against a local JSON-RPC endpoint set through
+//! `MOLLUSK_RPC_URL`: `fetch` records pubkeys and instructions into a fixture
+//! directory and skips what's recorded, `program` writes an ELF, `refresh` rewrites
+//! fixtures that drifted, and failures exit with the documented codes and an
+//! `error: ` line on stderr.
+
+#![cfg(feature = "cli")]
+
+mod common;
+
+use {
+    base64::{engine::general_purpose::STANDARD as BASE64, Engine},
+    common::{serve_http, serve_json_rpc_requests, temp_dir, HttpResponse},
+    serde_json::{json, Value},
+    solana_account::Account,
+    solana_instruction::{AccountMeta, Instruction},
+    solana_pubkey::Pubkey,
+    solana_sdk_ids::{bpf_loader_upgradeable, system_program},
+    std::{
+        collections::HashMap,
+        path::Path,
+        process::{Command, Output},
+        sync::{Arc, Mutex},
+    },
+};
+
+const ELF: &[u8] = mollusk_svm_programs_token::token2022::ELF;
+
+type Accounts = Arc<Mutex<HashMap<Pubkey, Account>>>;
+
+fn system_account(lamports: u64) -> Account {
+    Account {
+        lamports,
+        data: vec![],
+        owner: system_program::ID,
+        executable: false,
+        rent_epoch: 0,
+    }
+}
+
+/// An endpoint serving `accounts` from `getMultipleAccounts`; they can change
+/// between requests. Returns the URL and the `getMultipleAccounts` requests made.
+fn serve(accounts: &Accounts) -> (String, Arc<Mutex<usize>>) {
+    let accounts = accounts.clone();
+    let requests = Arc::new(Mutex::new(0));
+    let received = requests.clone();
+    let url = serve_json_rpc_requests(move |request| {
+        assert_eq!(request["method"], "getMultipleAccounts");
+        *received.lock().unwrap() += 1;
+        let accounts = accounts.lock().unwrap();
+        let value: Vec<Value> = request["params"][0]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|pubkey| {
+                let pubkey: Pubkey = pubkey.as_str().unwrap().parse().unwrap();
+                accounts.get(&pubkey).map_or(Value::Null, |account| {
+                    json!({
+                        "lamports": account.lamports,
+                        "data": [BASE64.encode(&account.data), "base64"],
+                        "owner": account.owner.to_string(),
+                        "executable": account.executable,
+                        "rentEpoch": 0,
+                        "space": account.data.len(),
+                    })
+                })
+            })
+            .collect();
+        let result = json!({"context": {"slot": 100}, "value": value});
+        (200, json!({"jsonrpc": "2.0", "id": 1, "result": result}).to_string())
+    });
+    (url, requests)
+}
+
+/// Run `mollusk-fetch` against `url` with `args`.
+fn run(url: &str, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_mollusk-fetch"))
+        .args(args)
+        .env("MOLLUSK_RPC_URL", url)
+        .env_remove("MOLLUSK_OFFLINE")
+        .env_remove("MOLLUSK_FIXTURE_DIR")
+        .output()
+        .unwrap()
+}
+
+fn stdout(output: &Output) -> String {
+    String::from_utf8(output.stdout.clone()).unwrap()
+}
+
+fn stderr(output: &Output) -> String {
+    String::from_utf8(output.stderr.clone()).unwrap()
+}
+
+/// The lamports recorded in the fixture of `pubkey` in `dir`.
+fn recorded_lamports(dir: &Path, pubkey: &Pubkey) -> u64 {
+    let fixture = std::fs::read_to_string(dir.join(format!("{pubkey}.json"))).unwrap();
+    let fixture: Value = serde_json::from_str(&fixture).unwrap();
+    fixture["account"]["lamports"].as_u64().unwrap()
+}
+
+#[test]
+fn fetch_records_pubkeys_once() {
+    let (a, b) = (Pubkey::new_unique(), Pubkey::new_unique());
+    let accounts: Accounts = Arc::default();
+    accounts.lock().unwrap().extend([(a, system_account(1)), (b, system_account(2))]);
+    let (url, requests) = serve(&accounts);
+    let dir = temp_dir("cli");
+    let out = dir.to_str().unwrap();
+    let pubkeys = format!("{a},{b}");
+
+    let output = run(&url, &["fetch", "--pubkeys", &pubkeys, "--out", out]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(stdout(&output), format!("2 accounts in {out}: 2 fetched, 0 already recorded\n"));
+    assert_eq!((recorded_lamports(&dir, &a), recorded_lamports(&dir, &b)), (1, 2));
+
+    let output = run(&url, &["fetch", "--pubkeys", &pubkeys, "--out", out]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(stdout(&output), format!("2 accounts in {out}: 0 fetched, 2 already recorded\n"));
+    assert_eq!(*requests.lock().unwrap(), 1);
+}
+
+#[test]
+fn fetch_records_an_instructions_accounts_and_program() {
+    let (payer, program, program_data) =
+        (Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+    let accounts: Accounts = Arc::default();
+    {
+        let mut data = 2u32.to_le_bytes().to_vec();
+        data.extend_from_slice(program_data.as_ref());
+        let program_account = Account {
+            data,
+            executable: true,
+            owner: bpf_loader_upgradeable::ID,
+            ..system_account(1_000_000_000)
+        };
+        // The ProgramData header: tag, deployment slot and upgrade authority
+        let mut data = 3u32.to_le_bytes().to_vec();
+        data.extend_from_slice(&0u64.to_le_bytes());
+        data.extend_from_slice(&[0; 33]);
+        data.extend_from_slice(ELF);
+        let program_data_account = Account {
+            data,
+            owner: bpf_loader_upgradeable::ID,
+            ..system_account(1_000_000_000)
+        };
+        accounts.lock().unwrap().extend([
+            (payer, system_account(5)),
+            (program, program_account),
+            (program_data, program_data_account),
+        ]);
+    }
+    let (url, _) = serve(&accounts);
+    let dir = temp_dir("cli");
+    let instruction =
+        Instruction::new_with_bytes(program, &[], vec![AccountMeta::new(payer, true)]);
+    let encoded = BASE64.encode(bincode::serialize(&instruction).unwrap());
+
+    let output = run(&url, &["fetch", "--instruction", &encoded, "--out", dir.to_str().unwrap()]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    for pubkey in [payer, program, program_data] {
+        assert!(dir.join(format!("{pubkey}.json")).exists(), "{pubkey}");
+    }
+
+    let elf = dir.join("program.so");
+    let id = program.to_string();
+    let output = run(&url, &["program", "--id", &id, "--out", elf.to_str().unwrap()]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(std::fs::read(&elf).unwrap(), ELF);
+    assert_eq!(
+        stdout(&output),
+        format!("{} bytes of {program} in {}\n", ELF.len(), elf.display())
+    );
+}
+
+#[test]
+fn refresh_rewrites_drifted_fixtures() {
+    let (a, b) = (Pubkey::new_unique(), Pubkey::new_unique());
+    let accounts: Accounts = Arc::default();
+    accounts.lock().unwrap().extend([(a, system_account(1)), (b, system_account(2))]);
+    let (url, _) = serve(&accounts);
+    let dir = temp_dir("cli");
+    let output =
+        run(&url, &["fetch", "--pubkeys", &format!("{a},{b}"), "--out", dir.to_str().unwrap()]);
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    accounts.lock().unwrap().insert(a, system_account(10));
+    let output = run(&url, &["refresh", "--fixture", dir.to_str().unwrap()]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).starts_with("1 of 2 accounts drifted"), "{}", stdout(&output));
+    assert_eq!((recorded_lamports(&dir, &a), recorded_lamports(&dir, &b)), (10, 2));
+
+    // A single fixture, whose account has since been closed
+    accounts.lock().unwrap().remove(&b);
+    let fixture = dir.join(format!("{b}.json"));
+    let output = run(&url, &["refresh", "--fixture", fixture.to_str().unwrap()]);
+    assert_eq!(output.status.code(), Some(3));
+    assert!(stderr(&output).starts_with("error: [account_not_found]"), "{}", stderr(&output));
+}
+
+#[test]
+fn a_missing_account_exits_with_3() {
+    let (url, _) = serve(&Arc::default());
+    let missing = Pubkey::new_unique();
+    let output = run(
+        &url,
+        &["fetch", "--pubkeys", &missing.to_string(), "--out", temp_dir("cli").to_str().unwrap()],
+    );
+    assert_eq!(output.status.code(), Some(3));
+    assert_eq!(
+        stderr(&output),
+        format!("error: [account_not_found] Account not found: {missing}\n")
+    );
+    assert!(stdout(&output).is_empty());
+}
+
+#[test]
+fn invalid_arguments_exit_with_2() {
+    let (url, requests) = serve(&Arc::default());
+    let out = temp_dir("cli");
+    let out = out.to_str().unwrap();
+
+    let output = run(&url, &["fetch", "--instruction", "not base64!", "--out", out]);
+    assert_eq!(output.status.code(), Some(2));
+    assert!(stderr(&output).starts_with("error: invalid --instruction: "), "{}", stderr(&output));
+
+    let output = run(&url, &["fetch", "--pubkeys", "not-a-pubkey", "--out", out]);
+    assert_eq!(output.status.code(), Some(2));
+    let output = run(&url, &["refresh", "--fixture", "notes.txt"]);
+    assert_eq!(output.status.code(), Some(2));
+    assert_eq!(stderr(&output), "error: notes.txt isn't a `<pubkey>.json` fixture\n");
+    assert_eq!(*requests.lock().unwrap(), 0);
+}
+
+#[test]
+fn a_rate_limited_provider_exits_with_4() {
+    let url = serve_http(|_| HttpResponse {
+        status: 429,
+        headers: vec![("retry-after", "0".to_string())],
+        body: String::new(),
+    });
+    let pubkey = Pubkey::new_unique().to_string();
+    let output =
+        run(&url, &["fetch", "--pubkeys", &pubkey, "--out", temp_dir("cli").to_str().unwrap()]);
+    assert_eq!(output.status.code(), Some(4), "{}", stderr(&output));
+    assert!(stderr(&output).starts_with("error: [rpc_rate_limited]"), "{}", stderr(&output));
+}
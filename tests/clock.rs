@@ -0,0 +1,307 @@
+//! Clock syncing and offsets: every sync fetches a fresh slot, the clock's epochs
+//! follow the epoch schedule, `getBlockTime` falls back to the system time only when
+//! the node has no time for the slot, and offsets too large for the clock saturate.
+//! `warp_to_epoch` lands on the epoch's first slot with the clock and epoch schedule
+//! agreeing, refuses to go back, and can fill in the stake history it skips.
+
+mod common;
+
+use {
+    common::{serve_json_rpc, store},
+    mollusk_on_demand::{testing::MockFetcher, RpcAccountStore, RpcError},
+    mollusk_svm::Mollusk,
+    serde_json::json,
+    solana_account::Account,
+    solana_epoch_schedule::EpochSchedule,
+    solana_rent::Rent,
+    solana_sdk_ids::sysvar,
+    solana_stake_interface::stake_history::StakeHistoryEntry,
+    std::time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+fn now() -> i64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs() as i64
+}
+
+fn result(value: serde_json::Value) -> (u16, String) {
+    (200, json!({"jsonrpc": "2.0", "id": 1, "result": value}).to_string())
+}
+
+fn error(code: i64, message: &str) -> (u16, String) {
+    let body = json!({"jsonrpc": "2.0", "id": 1, "error": {"code": code, "message": message}});
+    (200, body.to_string())
+}
+
+#[tokio::test]
+async fn each_sync_warps_to_the_slot_the_cluster_reached() {
+    let fetcher = MockFetcher::new().with_slot(1_000);
+    let mut mollusk = Mollusk::default();
+    let store = store(&fetcher).with_synced_slot(&mut mollusk).await.unwrap();
+    assert_eq!(mollusk.sysvars.clock.slot, 1_000);
+
+    fetcher.set_slot(1_500);
+    let store = store.with_synced_slot(&mut mollusk).await.unwrap();
+    assert_eq!(mollusk.sysvars.clock.slot, 1_500);
+    assert_eq!(store.synced_slot(), Some(1_500));
+
+    fetcher.set_slot(2_000);
+    let store = store.with_synced_clock(&mut mollusk).await.unwrap();
+    assert_eq!(mollusk.sysvars.clock.slot, 2_000);
+    assert_eq!(store.stats().requests_for("getSlot"), 3);
+}
+
+/// Mainnet's epoch schedule, as its sysvar account.
+fn epoch_schedule_account() -> (EpochSchedule, Account) {
+    let schedule = EpochSchedule::without_warmup();
+    let data = bincode::serialize(&schedule).unwrap();
+    let account = Account {
+        lamports: Rent::default().minimum_balance(data.len()),
+        data,
+        owner: sysvar::ID,
+        executable: false,
+        rent_epoch: 0,
+    };
+    (schedule, account)
+}
+
+fn assert_clock_matches_schedule(mollusk: &Mollusk, slot: u64) {
+    let schedule = &mollusk.sysvars.epoch_schedule;
+    let clock = &mollusk.sysvars.clock;
+    assert_eq!(clock.slot, slot);
+    assert_eq!(clock.epoch, schedule.get_epoch(slot));
+    assert_eq!(clock.leader_schedule_epoch, schedule.get_leader_schedule_epoch(slot));
+}
+
+#[tokio::test]
+async fn the_synced_clock_follows_the_epoch_schedule() {
+    let (schedule, account) = epoch_schedule_account();
+    let last_slot_of_epoch_600 = schedule.get_last_slot_in_epoch(600);
+    let fetcher = MockFetcher::new()
+        .with_slot(last_slot_of_epoch_600)
+        .with_account(sysvar::epoch_schedule::ID, account);
+    let mut mollusk = Mollusk::default();
+
+    // Mollusk's default schedule, then mainnet's for the same slot
+    let store = store(&fetcher).with_synced_slot(&mut mollusk).await.unwrap();
+    assert_clock_matches_schedule(&mollusk, last_slot_of_epoch_600);
+    let store = store.with_synced_epoch_schedule(&mut mollusk).await.unwrap();
+    assert_eq!(mollusk.sysvars.epoch_schedule, schedule);
+    assert_clock_matches_schedule(&mollusk, last_slot_of_epoch_600);
+    assert_eq!(mollusk.sysvars.clock.epoch, 600);
+
+    // A slot past the boundary moves the clock into the next epoch
+    fetcher.set_slot(last_slot_of_epoch_600 + 1);
+    let store = store.with_synced_slot(&mut mollusk).await.unwrap();
+    assert_clock_matches_schedule(&mollusk, last_slot_of_epoch_600 + 1);
+    assert_eq!(mollusk.sysvars.clock.epoch, 601);
+    assert_eq!(store.synced_slot(), Some(last_slot_of_epoch_600 + 1));
+
+    fetcher.set_slot(schedule.get_first_slot_in_epoch(602) + 7);
+    store.with_synced_clock(&mut mollusk).await.unwrap();
+    assert_clock_matches_schedule(&mollusk, schedule.get_first_slot_in_epoch(602) + 7);
+    assert_eq!(mollusk.sysvars.clock.epoch, 602);
+}
+
+#[tokio::test]
+async fn the_clock_takes_the_block_time() {
+    let fetcher = MockFetcher::new().with_slot(1_000).with_block_time(1_700_000_000);
+    let mut mollusk = Mollusk::default();
+    store(&fetcher).with_synced_clock(&mut mollusk).await.unwrap();
+    assert_eq!(mollusk.sysvars.clock.slot, 1_000);
+    assert_eq!(mollusk.sysvars.clock.unix_timestamp, 1_700_000_000);
+}
+
+#[tokio::test]
+async fn a_missing_block_time_falls_back_to_the_system_time() {
+    let fetcher = MockFetcher::new().with_slot(1_000);
+    let mut mollusk = Mollusk::default();
+    let before = now();
+    store(&fetcher).with_synced_clock(&mut mollusk).await.unwrap();
+    assert!((before..=now()).contains(&mollusk.sysvars.clock.unix_timestamp));
+}
+
+#[tokio::test]
+async fn null_and_skipped_block_times_fall_back_over_rpc() {
+    let responses = [
+        result(serde_json::Value::Null),
+        error(-32004, "Block not available for slot 1000"),
+        error(-32007, "Slot 1000 was skipped"),
+    ];
+    for block_time in responses {
+        let (url, _) = serve_json_rpc(move |method| match method {
+            "getSlot" => result(json!(1_000)),
+            "getBlockTime" => block_time.clone(),
+            method => panic!("unexpected method {method}"),
+        });
+        let mut mollusk = Mollusk::default();
+        let before = now();
+        RpcAccountStore::new(url)
+            .with_synced_clock(&mut mollusk)
+            .await
+            .unwrap();
+        assert!((before..=now()).contains(&mollusk.sysvars.clock.unix_timestamp));
+    }
+}
+
+#[tokio::test]
+async fn other_block_time_errors_are_returned() {
+    let (url, _) = serve_json_rpc(|method| match method {
+        "getSlot" => result(json!(1_000)),
+        "getBlockTime" => error(-32005, "Node is behind"),
+        method => panic!("unexpected method {method}"),
+    });
+    let error = RpcAccountStore::new(url)
+        .with_synced_clock(&mut Mollusk::default())
+        .await
+        .map(|_| ())
+        .unwrap_err();
+    assert!(matches!(error, RpcError::Client(_)), "{error}");
+}
+
+#[tokio::test]
+async fn the_budget_stops_the_block_time_request() {
+    let fetcher = MockFetcher::new().with_slot(1_000).with_block_time(1_700_000_000);
+    let mut mollusk = Mollusk::default();
+    let error = store(&fetcher)
+        .with_rpc_budget(1)
+        .with_synced_clock(&mut mollusk)
+        .await
+        .map(|_| ())
+        .unwrap_err();
+    assert!(
+        matches!(error, RpcError::BudgetExceeded { attempted_method: "getBlockTime", .. }),
+        "{error}"
+    );
+    assert_ne!(mollusk.sysvars.clock.unix_timestamp, 1_700_000_000);
+}
+
+#[tokio::test]
+async fn an_offline_store_doesnt_sync_the_clock() {
+    let fetcher = MockFetcher::new().with_slot(1_000);
+    let error = store(&fetcher)
+        .offline()
+        .with_synced_clock(&mut Mollusk::default())
+        .await
+        .map(|_| ())
+        .unwrap_err();
+    assert_eq!(error.code(), "offline", "{error}");
+    assert!(fetcher.calls().is_empty());
+}
+
+#[test]
+fn offsets_move_the_slot_and_timestamp_together() {
+    let mut mollusk = Mollusk::default();
+    let before = mollusk.sysvars.clock.clone();
+    let store = RpcAccountStore::new_with_fetcher(MockFetcher::new())
+        .with_slot_offset(&mut mollusk, 25)
+        .unwrap();
+    assert_eq!(mollusk.sysvars.clock.slot, before.slot + 25);
+    assert_eq!(mollusk.sysvars.clock.unix_timestamp, before.unix_timestamp + 10);
+
+    store.with_time_offset(&mut mollusk, Duration::from_millis(2_500));
+    assert_eq!(mollusk.sysvars.clock.slot, before.slot + 31);
+    assert_eq!(mollusk.sysvars.clock.unix_timestamp, before.unix_timestamp + 12);
+}
+
+#[test]
+fn huge_slot_offsets_saturate_instead_of_wrapping() {
+    let mut mollusk = Mollusk::default();
+    let before = mollusk.sysvars.clock.clone();
+    let store = RpcAccountStore::new_with_fetcher(MockFetcher::new())
+        .with_slot_offset(&mut mollusk, i64::MAX)
+        .unwrap();
+    let seconds = (i128::from(i64::MAX) * 400 / 1_000) as i64;
+    assert_eq!(mollusk.sysvars.clock.unix_timestamp, before.unix_timestamp + seconds);
+
+    store
+        .with_slot_offset(&mut mollusk, i64::MAX)
+        .unwrap()
+        .with_slot_offset(&mut mollusk, i64::MAX)
+        .unwrap();
+    assert_eq!(mollusk.sysvars.clock.slot, u64::MAX);
+    assert!(mollusk.sysvars.clock.unix_timestamp > before.unix_timestamp);
+}
+
+#[test]
+fn huge_time_offsets_saturate_instead_of_going_negative() {
+    let mut mollusk = Mollusk::default();
+    RpcAccountStore::new_with_fetcher(MockFetcher::new())
+        .with_time_offset(&mut mollusk, Duration::MAX);
+    assert_eq!(mollusk.sysvars.clock.slot, u64::MAX);
+    assert_eq!(mollusk.sysvars.clock.unix_timestamp, i64::MAX);
+}
+
+#[test]
+fn warping_to_an_epoch_lands_on_its_first_slot() {
+    let mut mollusk = Mollusk::default();
+    let before = mollusk.sysvars.clock.clone();
+    RpcAccountStore::new_with_fetcher(MockFetcher::new())
+        .warp_to_epoch(&mut mollusk, 20)
+        .unwrap();
+
+    let first_slot = mollusk.sysvars.epoch_schedule.get_first_slot_in_epoch(20);
+    assert_clock_matches_schedule(&mollusk, first_slot);
+    let clock = &mollusk.sysvars.clock;
+    assert_eq!(clock.epoch, 20);
+    let seconds = ((first_slot - before.slot) * 400 / 1_000) as i64;
+    assert_eq!(clock.unix_timestamp, before.unix_timestamp + seconds);
+    assert_eq!(clock.epoch_start_timestamp, clock.unix_timestamp);
+}
+
+#[tokio::test]
+async fn warping_follows_the_synced_epoch_schedule() {
+    let (schedule, account) = epoch_schedule_account();
+    let fetcher = MockFetcher::new().with_account(sysvar::epoch_schedule::ID, account);
+    let mut mollusk = Mollusk::default();
+    store(&fetcher)
+        .with_synced_epoch_schedule(&mut mollusk)
+        .await
+        .unwrap()
+        .warp_to_epoch(&mut mollusk, 700)
+        .unwrap();
+
+    assert_clock_matches_schedule(&mollusk, schedule.get_first_slot_in_epoch(700));
+    assert_eq!(mollusk.sysvars.clock.slot, 700 * schedule.slots_per_epoch);
+    assert_eq!(mollusk.sysvars.clock.epoch, 700);
+}
+
+#[test]
+fn warping_to_a_past_epoch_is_an_error() {
+    let mut mollusk = Mollusk::default();
+    let store = RpcAccountStore::new_with_fetcher(MockFetcher::new())
+        .warp_to_epoch(&mut mollusk, 5)
+        .unwrap();
+    let before = mollusk.sysvars.clock.clone();
+
+    // Already at the first slot of epoch 5
+    let store = store.warp_to_epoch(&mut mollusk, 5).unwrap();
+    assert_eq!(mollusk.sysvars.clock, before);
+    let error = store.warp_to_epoch(&mut mollusk, 4).map(|_| ()).unwrap_err();
+    let RpcError::PastEpoch { epoch, slot } = error else {
+        panic!("expected PastEpoch, got {error}");
+    };
+    assert_eq!((epoch, slot), (4, before.slot));
+    assert_eq!(mollusk.sysvars.clock, before);
+}
+
+#[test]
+fn warping_can_fill_in_the_skipped_stake_history() {
+    let mut mollusk = Mollusk::default();
+    let store = RpcAccountStore::new_with_fetcher(MockFetcher::new())
+        .warp_to_epoch(&mut mollusk, 2)
+        .unwrap();
+    let entry = StakeHistoryEntry::with_effective(1_000_000);
+
+    store
+        .warp_to_epoch_with_stake_history(&mut mollusk, 6, entry.clone())
+        .unwrap();
+    let first_slot = mollusk.sysvars.epoch_schedule.get_first_slot_in_epoch(6);
+    assert_clock_matches_schedule(&mollusk, first_slot);
+    let history = &mollusk.sysvars.stake_history;
+    for ended in 2..6 {
+        assert_eq!(history.get(ended), Some(&entry), "epoch {ended}");
+    }
+    // Epochs before the warp and the new current one keep what Mollusk had
+    assert_ne!(history.get(1), Some(&entry));
+    assert_eq!(history.get(6), None);
+}
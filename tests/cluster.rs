@@ -0,0 +1,132 @@
+//! `expect_cluster`: the endpoint's genesis hash is checked once before the first
+//! request, a mismatch names both clusters, and offline stores and fixture replays
+//! skip the check.
+
+mod common;
+
+use {
+    common::{instruction, pubkeys, serving, store, temp_dir},
+    mollusk_on_demand::{
+        testing::{FetcherCall, MockFetcher},
+        Cluster, RpcError,
+    },
+    mollusk_svm::Mollusk,
+    solana_hash::Hash,
+};
+
+fn genesis_hash_checks(fetcher: &MockFetcher) -> usize {
+    fetcher.calls().iter().filter(|call| **call == FetcherCall::GetGenesisHash).count()
+}
+
+#[tokio::test]
+async fn a_devnet_endpoint_fails_a_mainnet_store() {
+    let keys = pubkeys(2);
+    let fetcher = serving(&keys).with_genesis_hash(Cluster::Devnet.genesis_hash());
+    let error = store(&fetcher)
+        .expect_cluster(Cluster::Mainnet)
+        .from_instruction(&instruction(&keys))
+        .await
+        .map(|_| ())
+        .unwrap_err();
+
+    assert!(
+        matches!(
+            error,
+            RpcError::WrongCluster { expected: Cluster::Mainnet, actual: Cluster::Devnet }
+        ),
+        "{error}"
+    );
+    assert!(error.to_string().contains("devnet"), "{error}");
+    // Nothing was fetched from the wrong cluster
+    assert_eq!(fetcher.calls(), [FetcherCall::GetGenesisHash]);
+}
+
+#[tokio::test]
+async fn the_check_runs_once_before_the_first_request() {
+    let keys = pubkeys(3);
+    let fetcher = serving(&keys).with_genesis_hash(Cluster::Mainnet.genesis_hash());
+    let mut mollusk = Mollusk::default();
+    let mut store = store(&fetcher)
+        .expect_cluster(Cluster::Mainnet)
+        .from_instruction(&instruction(&keys[..2]))
+        .await
+        .unwrap()
+        .with_synced_slot(&mut mollusk)
+        .await
+        .unwrap();
+    store.get_or_fetch(&keys[2]).await.unwrap();
+
+    let calls = fetcher.calls();
+    assert_eq!(calls[0], FetcherCall::GetGenesisHash);
+    assert_eq!(genesis_hash_checks(&fetcher), 1);
+    assert_eq!(calls.len(), 4);
+    assert_eq!(store.stats().requests_for("getGenesisHash"), 1);
+}
+
+#[tokio::test]
+async fn unknown_genesis_hashes_are_custom_clusters() {
+    let local = Hash::new_from_array([9; 32]);
+    let keys = pubkeys(1);
+    let error = store(&serving(&keys).with_genesis_hash(local))
+        .expect_cluster(Cluster::Testnet)
+        .from_instruction(&instruction(&keys))
+        .await
+        .map(|_| ())
+        .unwrap_err();
+    let RpcError::WrongCluster { expected, actual } = error else {
+        panic!("expected WrongCluster, got {error}");
+    };
+    assert_eq!((expected, actual), (Cluster::Testnet, Cluster::Custom(local)));
+
+    let fetcher = serving(&keys).with_genesis_hash(local);
+    store(&fetcher)
+        .expect_cluster(Cluster::Custom(local))
+        .from_instruction(&instruction(&keys))
+        .await
+        .unwrap();
+    assert_eq!(Cluster::from_genesis_hash(local), Cluster::Custom(local));
+}
+
+#[tokio::test]
+async fn replays_and_offline_stores_skip_the_check() {
+    let keys = pubkeys(2);
+    let dir = temp_dir("cluster");
+    store(&serving(&keys))
+        .with_fixture_dir(&dir)
+        .from_instruction(&instruction(&keys))
+        .await
+        .unwrap();
+
+    // Devnet, but every account is replayed
+    let fetcher = MockFetcher::new().with_genesis_hash(Cluster::Devnet.genesis_hash());
+    let replayed = store(&fetcher)
+        .expect_cluster(Cluster::Mainnet)
+        .with_fixture_dir(&dir)
+        .from_instruction(&instruction(&keys))
+        .await
+        .unwrap();
+    assert_eq!(replayed.cache.len(), 2);
+    assert!(fetcher.calls().is_empty(), "{:?}", fetcher.calls());
+
+    let fetcher = MockFetcher::new().with_genesis_hash(Cluster::Devnet.genesis_hash());
+    let mut offline = store(&fetcher).expect_cluster(Cluster::Mainnet).offline();
+    let error = offline.get_or_fetch(&keys[0]).await.unwrap_err();
+    assert_eq!(error.code(), "offline", "{error}");
+    assert_eq!(genesis_hash_checks(&fetcher), 0);
+}
+
+#[test]
+fn clusters_parse_from_names_and_hashes() {
+    for (name, cluster) in [
+        ("mainnet", Cluster::Mainnet),
+        ("mainnet-beta", Cluster::Mainnet),
+        ("devnet", Cluster::Devnet),
+        ("testnet", Cluster::Testnet),
+    ] {
+        assert_eq!(name.parse::<Cluster>().unwrap(), cluster);
+        assert_eq!(Cluster::from_genesis_hash(cluster.genesis_hash()), cluster);
+    }
+    let local = Hash::new_from_array([9; 32]);
+    assert_eq!(local.to_string().parse::<Cluster>().unwrap(), Cluster::Custom(local));
+    assert!("localnet".parse::<Cluster>().unwrap_err().contains("localnet"));
+}
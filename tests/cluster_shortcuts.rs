@@ -0,0 +1,110 @@
+//! The cluster constructors: each uses its canonical public endpoint unless its
+//! `MOLLUSK_*_URL` variable points it elsewhere, expects its cluster's genesis hash
+//! (except `localnet`), and names the cluster in its `Debug` output.
+
+use {
+    mollusk_on_demand::{
+        RpcAccountStore, DEVNET_RPC_URL, LOCALNET_RPC_URL, MAINNET_RPC_URL, TESTNET_RPC_URL,
+    },
+    std::sync::{Mutex, MutexGuard},
+};
+
+/// A cluster constructor, the variable it reads, its canonical URL, and the cluster
+/// and expected cluster its `Debug` output shows.
+struct Shortcut {
+    new: fn() -> RpcAccountStore,
+    variable: &'static str,
+    url: &'static str,
+    cluster: &'static str,
+    expected: &'static str,
+}
+
+const SHORTCUTS: [Shortcut; 4] = [
+    Shortcut {
+        new: RpcAccountStore::mainnet,
+        variable: "MOLLUSK_MAINNET_URL",
+        url: MAINNET_RPC_URL,
+        cluster: "Some(\"mainnet-beta\")",
+        expected: "Some(Mainnet)",
+    },
+    Shortcut {
+        new: RpcAccountStore::devnet,
+        variable: "MOLLUSK_DEVNET_URL",
+        url: DEVNET_RPC_URL,
+        cluster: "Some(\"devnet\")",
+        expected: "Some(Devnet)",
+    },
+    Shortcut {
+        new: RpcAccountStore::testnet,
+        variable: "MOLLUSK_TESTNET_URL",
+        url: TESTNET_RPC_URL,
+        cluster: "Some(\"testnet\")",
+        expected: "Some(Testnet)",
+    },
+    Shortcut {
+        new: RpcAccountStore::localnet,
+        variable: "MOLLUSK_LOCALNET_URL",
+        url: LOCALNET_RPC_URL,
+        cluster: "Some(\"localnet\")",
+        expected: "None",
+    },
+];
+
+/// The environment is process-wide; tests holding this run one at a time.
+static ENVIRONMENT: Mutex<()> = Mutex::new(());
+
+/// Lock the environment with exactly `vars` of the cluster variables set.
+fn set_env(vars: &[(&str, &str)]) -> MutexGuard<'static, ()> {
+    let guard = ENVIRONMENT.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    for shortcut in &SHORTCUTS {
+        std::env::remove_var(shortcut.variable);
+    }
+    for (name, value) in vars {
+        std::env::set_var(name, value);
+    }
+    guard
+}
+
+#[test]
+fn each_cluster_defaults_to_its_public_endpoint() {
+    let _env = set_env(&[]);
+    for shortcut in &SHORTCUTS {
+        let store = (shortcut.new)();
+        assert_eq!(store.rpc_url(), Some(shortcut.url));
+
+        let debug = format!("{store:?}");
+        assert!(debug.contains(&format!("cluster: {}", shortcut.cluster)), "{debug}");
+        assert!(debug.contains(&format!("expected_cluster: {}", shortcut.expected)), "{debug}");
+    }
+}
+
+#[test]
+fn the_variables_point_clusters_at_other_endpoints() {
+    let _env = set_env(&[
+        ("MOLLUSK_MAINNET_URL", "https://mainnet.example.com/key"),
+        ("MOLLUSK_LOCALNET_URL", "http://127.0.0.1:9000"),
+    ]);
+    assert_eq!(RpcAccountStore::mainnet().rpc_url(), Some("https://mainnet.example.com/key"));
+    assert_eq!(RpcAccountStore::localnet().rpc_url(), Some("http://127.0.0.1:9000"));
+    // Each constructor only reads its own variable
+    assert_eq!(RpcAccountStore::devnet().rpc_url(), Some(DEVNET_RPC_URL));
+    assert_eq!(RpcAccountStore::testnet().rpc_url(), Some(TESTNET_RPC_URL));
+
+    // The cluster is still the one asked for
+    let debug = format!("{:?}", RpcAccountStore::mainnet());
+    assert!(debug.contains("cluster: Some(\"mainnet-beta\")"), "{debug}");
+    assert!(debug.contains("expected_cluster: Some(Mainnet)"), "{debug}");
+}
+
+#[test]
+fn an_empty_variable_is_ignored() {
+    let _env = set_env(&[("MOLLUSK_DEVNET_URL", "")]);
+    assert_eq!(RpcAccountStore::devnet().rpc_url(), Some(DEVNET_RPC_URL));
+}
+
+#[test]
+fn other_stores_name_no_cluster() {
+    let debug = format!("{:?}", RpcAccountStore::new(MAINNET_RPC_URL));
+    assert!(debug.contains("cluster: None"), "{debug}");
+    assert!(debug.contains("expected_cluster: None"), "{debug}");
+}
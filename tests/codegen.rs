@@ -0,0 +1,86 @@
+//! `generate_rust_module`: the module checked in under `tests/codegen` is exactly
+//! what generating from [`store`] writes, and compiled into this test it gives back
+//! the cached accounts byte for byte, with a constant per label.
+
+mod common;
+
+use {
+    common::{account, temp_dir},
+    mollusk_on_demand::{testing::MockFetcher, RpcAccountStore, RpcError},
+    solana_account::Account,
+    solana_pubkey::Pubkey,
+    std::collections::HashMap,
+};
+
+include!("codegen/generated.rs");
+
+const GENERATED: &str = include_str!("codegen/generated.rs");
+
+fn key(byte: u8) -> Pubkey {
+    Pubkey::new_from_array([byte; 32])
+}
+
+/// Accounts whose data takes every byte value and starts a continuation line with a
+/// space, an executable one, one with the largest rent epoch and an empty one,
+/// labeled so that two labels map to the same constant and one starts with a digit.
+fn store() -> RpcAccountStore {
+    let mut every_byte: Vec<u8> = (0..=255).collect();
+    every_byte[48] = b' ';
+    let program = Account {
+        executable: true,
+        owner: key(9),
+        ..account(1_141_440, 36)
+    };
+    RpcAccountStore::new_with_fetcher(MockFetcher::new())
+        .with_accounts([
+            (
+                key(1),
+                Account {
+                    data: every_byte,
+                    ..account(2_039_280, 0)
+                },
+            ),
+            (key(2), program),
+            (
+                key(3),
+                Account {
+                    rent_epoch: u64::MAX,
+                    ..account(890_880, 0)
+                },
+            ),
+            (key(4), account(1, 8)),
+        ])
+        .with_labels(&[(key(1), "alice-usdc"), (key(2), "alice_usdc"), (key(3), "1st")])
+}
+
+#[test]
+fn the_checked_in_module_is_what_generation_writes() {
+    let path = temp_dir("codegen").join("generated.rs");
+    store().generate_rust_module(&path, "swap_accounts").unwrap();
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), GENERATED);
+}
+
+#[test]
+fn the_compiled_accounts_round_trip() {
+    let accounts = swap_accounts::accounts();
+    let pubkeys: Vec<Pubkey> = accounts.iter().map(|(pubkey, _)| *pubkey).collect();
+    assert_eq!(pubkeys, [key(1), key(2), key(3), key(4)]);
+    assert_eq!(accounts.into_iter().collect::<HashMap<_, _>>(), store().cache);
+
+    assert_eq!(swap_accounts::ALICE_USDC, key(1));
+    assert_eq!(swap_accounts::ALICE_USDC_2, key(2));
+    assert_eq!(swap_accounts::ACCOUNT_1ST, key(3));
+}
+
+#[test]
+fn module_names_must_be_identifiers() {
+    let path = temp_dir("codegen").join("generated.rs");
+    for name in ["", "_", "mod", "swap-accounts", "1st"] {
+        let error = store().generate_rust_module(&path, name).unwrap_err();
+        let RpcError::Fixture { reason, .. } = &error else {
+            panic!("expected Fixture for {name:?}, got {error}");
+        };
+        assert_eq!(*reason, format!("`{name}` isn't a valid Rust module name"));
+    }
+    assert!(!path.exists());
+}
@@ -0,0 +1,186 @@
+//! `with_commitment_fallback` against a node that only has a freshly created account
+//! at `processed`: just the missing pubkeys are asked for again at each fallback
+//! commitment, the one that found it shows in provenance, and accounts no fallback
+//! finds are left to `allow_missing_accounts`.
+
+mod common;
+
+use {
+    common::{account, instruction, store},
+    mollusk_on_demand::{
+        testing::{FetcherCall, MockFetcher},
+        Provenance, RpcAccountStore, RpcError,
+    },
+    solana_account::Account,
+    solana_commitment_config::{CommitmentConfig, CommitmentLevel},
+    solana_pubkey::Pubkey,
+};
+
+fn key(byte: u8) -> Pubkey {
+    Pubkey::new_from_array([byte; 32])
+}
+
+const POOL: u8 = 1;
+/// Created by the test's setup transaction, not yet confirmed on the node read.
+const FRESH: u8 = 2;
+/// Confirmed, but not yet on the node read at the store's commitment.
+const LAGGING: u8 = 3;
+/// Exists at no commitment.
+const GONE: u8 = 4;
+
+/// A node lagging behind the setup transaction.
+fn lagging_node() -> MockFetcher {
+    MockFetcher::new()
+        .with_account(key(POOL), account(1, 8))
+        .with_account_at(CommitmentLevel::Processed, key(FRESH), account(2, 8))
+        .with_account_at(CommitmentLevel::Confirmed, key(LAGGING), account(3, 8))
+}
+
+fn retried(pubkeys: &[u8], commitment: CommitmentLevel) -> FetcherCall {
+    FetcherCall::GetMultipleAccountsAt {
+        pubkeys: pubkeys.iter().copied().map(key).collect(),
+        commitment,
+    }
+}
+
+/// The commitment `pubkey` was found at, per its provenance.
+fn found_at(store: &RpcAccountStore, pubkey: u8) -> Option<CommitmentConfig> {
+    let provenance = store.provenance(&key(pubkey));
+    let Some(Provenance::Fetched { commitment, .. }) = provenance else {
+        panic!("expected Fetched, got {provenance:?}");
+    };
+    *commitment
+}
+
+#[tokio::test]
+async fn a_fresh_account_is_found_at_processed() {
+    let fetcher = lagging_node();
+    let pubkeys = [key(POOL), key(FRESH)];
+    let error = store(&fetcher)
+        .from_instruction(&instruction(&pubkeys))
+        .await
+        .map(|_| ())
+        .unwrap_err();
+    let RpcError::AccountNotFoundInInstruction(missing) = &error else {
+        panic!("expected AccountNotFoundInInstruction, got {error}");
+    };
+    assert_eq!(missing.pubkey, key(FRESH));
+
+    fetcher.clear_calls();
+    let store = store(&fetcher)
+        .with_commitment_fallback(&[CommitmentConfig::processed()])
+        .from_instruction(&instruction(&pubkeys))
+        .await
+        .unwrap();
+    assert_eq!(
+        fetcher.calls(),
+        [
+            FetcherCall::GetMultipleAccounts(pubkeys.to_vec()),
+            retried(&[FRESH], CommitmentLevel::Processed),
+        ]
+    );
+    assert_eq!(store.cache[&key(FRESH)], account(2, 8));
+    assert_eq!(found_at(&store, FRESH), Some(CommitmentConfig::processed()));
+    assert_eq!(found_at(&store, POOL), None);
+    assert_eq!(
+        store.provenance(&key(FRESH)).unwrap().to_string(),
+        "fetched (found at processed)"
+    );
+}
+
+#[tokio::test]
+async fn fallbacks_are_tried_in_turn_until_each_account_is_found() {
+    let fetcher = lagging_node();
+    let found = store(&fetcher)
+        .with_commitment_fallback(&[CommitmentConfig::confirmed(), CommitmentConfig::processed()])
+        .from_instruction(&instruction(&[key(FRESH), key(POOL), key(LAGGING)]))
+        .await
+        .unwrap();
+    assert_eq!(
+        fetcher.calls()[1..],
+        [
+            retried(&[FRESH, LAGGING], CommitmentLevel::Confirmed),
+            retried(&[FRESH], CommitmentLevel::Processed),
+        ]
+    );
+    assert_eq!(
+        found_at(&found, LAGGING),
+        Some(CommitmentConfig::confirmed())
+    );
+    assert_eq!(found_at(&found, FRESH), Some(CommitmentConfig::processed()));
+
+    // Nothing missing, nothing retried
+    fetcher.clear_calls();
+    store(&fetcher)
+        .with_commitment_fallback(&[CommitmentConfig::processed()])
+        .from_instruction(&instruction(&[key(POOL)]))
+        .await
+        .unwrap();
+    assert_eq!(fetcher.calls().len(), 1, "{:?}", fetcher.calls());
+}
+
+#[tokio::test]
+async fn accounts_no_fallback_finds_are_still_missing() {
+    let fetcher = lagging_node();
+    let pubkeys = [key(FRESH), key(GONE)];
+    let fallback = [CommitmentConfig::processed()];
+    let error = store(&fetcher)
+        .with_commitment_fallback(&fallback)
+        .from_instruction(&instruction(&pubkeys))
+        .await
+        .map(|_| ())
+        .unwrap_err();
+    let RpcError::AccountNotFoundInInstruction(missing) = &error else {
+        panic!("expected AccountNotFoundInInstruction, got {error}");
+    };
+    assert_eq!(missing.pubkey, key(GONE));
+
+    // The fallback first, then the permissive default
+    fetcher.clear_calls();
+    let store = store(&fetcher)
+        .with_commitment_fallback(&fallback)
+        .allow_missing_accounts()
+        .from_instruction(&instruction(&pubkeys))
+        .await
+        .unwrap();
+    assert_eq!(
+        fetcher.calls()[1..],
+        [retried(&[FRESH, GONE], CommitmentLevel::Processed)]
+    );
+    assert_eq!(store.cache[&key(FRESH)], account(2, 8));
+    assert_eq!(store.cache[&key(GONE)], Account::default());
+}
+
+#[tokio::test]
+async fn routed_accounts_are_retried_at_their_endpoint() {
+    let (own, devnet) = (lagging_node(), lagging_node());
+    let store = store(&own)
+        .with_fetcher_for(&[key(FRESH)], "devnet", devnet.clone())
+        .with_commitment_fallback(&[CommitmentConfig::processed()])
+        .from_instruction(&instruction(&[key(POOL), key(FRESH)]))
+        .await
+        .unwrap();
+    assert_eq!(
+        own.calls(),
+        [FetcherCall::GetMultipleAccounts(vec![key(POOL)])]
+    );
+    assert_eq!(
+        devnet.calls(),
+        [
+            FetcherCall::GetMultipleAccounts(vec![key(FRESH)]),
+            retried(&[FRESH], CommitmentLevel::Processed),
+        ]
+    );
+    let Some(Provenance::Fetched {
+        endpoint,
+        commitment,
+        ..
+    }) = store.provenance(&key(FRESH))
+    else {
+        panic!("expected Fetched");
+    };
+    assert_eq!(
+        (endpoint.as_deref(), *commitment),
+        (Some("devnet"), Some(CommitmentConfig::processed()))
+    );
+}
@@ -0,0 +1,199 @@
+//! Helpers shared by the integration tests, which all run against `MockFetcher`.
+
+#![allow(dead_code)]
+
+use {
+    mollusk_on_demand::{testing::MockFetcher, RpcAccountStore},
+    solana_account::Account,
+    solana_instruction::{AccountMeta, Instruction},
+    solana_pubkey::Pubkey,
+    std::{
+        io::{BufRead, BufReader, Read, Write},
+        net::TcpListener,
+        path::PathBuf,
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc, Mutex,
+        },
+    },
+};
+
+/// A system-owned account holding `lamports` and `data_len` zero bytes.
+pub fn account(lamports: u64, data_len: usize) -> Account {
+    Account {
+        lamports,
+        data: vec![0; data_len],
+        owner: solana_sdk_ids::system_program::ID,
+        executable: false,
+        rent_epoch: 0,
+    }
+}
+
+/// `count` unique pubkeys.
+pub fn pubkeys(count: usize) -> Vec<Pubkey> {
+    (0..count).map(|_| Pubkey::new_unique()).collect()
+}
+
+/// A mock serving a distinct account at each of `pubkeys`, whose lamports are its
+/// position plus one.
+pub fn serving(pubkeys: &[Pubkey]) -> MockFetcher {
+    MockFetcher::new().with_accounts(
+        pubkeys
+            .iter()
+            .enumerate()
+            .map(|(index, pubkey)| (*pubkey, account(index as u64 + 1, 8))),
+    )
+}
+
+/// An instruction of a made-up program with a read-only meta for each of `pubkeys`.
+pub fn instruction(pubkeys: &[Pubkey]) -> Instruction {
+    Instruction {
+        program_id: Pubkey::new_unique(),
+        accounts: pubkeys
+            .iter()
+            .map(|pubkey| AccountMeta::new_readonly(*pubkey, false))
+            .collect(),
+        data: vec![],
+    }
+}
+
+/// A store over a clone of `fetcher`, so the test keeps the call log.
+pub fn store(fetcher: &MockFetcher) -> RpcAccountStore {
+    RpcAccountStore::new_with_fetcher(fetcher.clone())
+}
+
+/// A fresh, empty directory under the system temp dir, unique to this test run.
+pub fn temp_dir(name: &str) -> PathBuf {
+    static NEXT: AtomicUsize = AtomicUsize::new(0);
+    let dir = std::env::temp_dir().join(format!(
+        "mollusk-on-demand-{name}-{}-{}",
+        std::process::id(),
+        NEXT.fetch_add(1, Ordering::Relaxed)
+    ));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// A JSON-RPC endpoint on localhost that answers each request with the HTTP status
+/// and body `respond` returns for its method, for exercising the real `RpcClient`
+/// against responses `MockFetcher` can't produce. Returns the URL and the methods
+/// received so far.
+pub fn serve_json_rpc(
+    respond: impl Fn(&str) -> (u16, String) + Send + Sync + 'static,
+) -> (String, Arc<Mutex<Vec<String>>>) {
+    let methods = Arc::new(Mutex::new(Vec::new()));
+    let received = methods.clone();
+    let url = serve_json_rpc_requests(move |request| {
+        let method = request["method"].as_str().unwrap_or_default().to_string();
+        let response = respond(&method);
+        received.lock().unwrap().push(method);
+        response
+    });
+    (url, methods)
+}
+
+/// Like [`serve_json_rpc`], but `respond` sees the whole request, params included.
+pub fn serve_json_rpc_requests(
+    respond: impl Fn(&serde_json::Value) -> (u16, String) + Send + Sync + 'static,
+) -> String {
+    serve_http(move |request| {
+        let (status, body) = respond(&request.body);
+        HttpResponse {
+            status,
+            headers: Vec::new(),
+            body,
+        }
+    })
+}
+
+/// A JSON-RPC request received by [`serve_http`].
+pub struct HttpRequest {
+    /// The headers, names lowercased.
+    pub headers: Vec<(String, String)>,
+    pub body: serde_json::Value,
+}
+
+impl HttpRequest {
+    /// The value of the header `name`, which must be lowercase.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(header, _)| header == name)
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// What [`serve_http`] answers a request with.
+pub struct HttpResponse {
+    pub status: u16,
+    /// Headers besides the content type and length.
+    pub headers: Vec<(&'static str, String)>,
+    pub body: String,
+}
+
+/// Like [`serve_json_rpc_requests`], but `respond` also sees the request headers and
+/// can send headers back.
+pub fn serve_http(
+    respond: impl Fn(&HttpRequest) -> HttpResponse + Send + Sync + 'static,
+) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    let respond = Arc::new(respond);
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let Ok(stream) = stream else { break };
+            let respond = respond.clone();
+            std::thread::spawn(move || {
+                let mut writer = stream.try_clone().unwrap();
+                let mut reader = BufReader::new(stream);
+                // One request per iteration, until the client closes the connection
+                loop {
+                    let mut headers = Vec::new();
+                    // The request line, then the headers up to the blank line
+                    let mut line = String::new();
+                    if reader.read_line(&mut line).unwrap_or(0) == 0 {
+                        return;
+                    }
+                    loop {
+                        line.clear();
+                        if reader.read_line(&mut line).unwrap_or(0) == 0 {
+                            return;
+                        }
+                        let Some((name, value)) = line.trim_end().split_once(':') else {
+                            break;
+                        };
+                        headers.push((name.to_ascii_lowercase(), value.trim().to_string()));
+                    }
+                    let content_length = headers
+                        .iter()
+                        .find(|(name, _)| name == "content-length")
+                        .map_or(0, |(_, length)| length.parse().unwrap());
+                    let mut body = vec![0; content_length];
+                    reader.read_exact(&mut body).unwrap();
+                    let request = HttpRequest {
+                        headers,
+                        body: serde_json::from_slice(&body).unwrap(),
+                    };
+                    let response = respond(&request);
+                    let headers: String = response
+                        .headers
+                        .iter()
+                        .map(|(name, value)| format!("{name}: {value}\r\n"))
+                        .collect();
+                    let response = format!(
+                        "HTTP/1.1 {} X\r\ncontent-type: application/json\r\n{headers}\
+                         content-length: {}\r\n\r\n{}",
+                        response.status,
+                        response.body.len(),
+                        response.body
+                    );
+                    if writer.write_all(response.as_bytes()).is_err() {
+                        return;
+                    }
+                }
+            });
+        }
+    });
+    url
+}
@@ -0,0 +1,186 @@
+//! ComputeBudget instructions of replayed transactions: they're recorded rather
+//! than fetched or run, and `apply_compute_budget` gives Mollusk the limit they set.
+
+mod common;
+
+use {
+    common::{account, store},
+    mollusk_on_demand::{testing::MockFetcher, ComputeBudgetRequest, RpcError},
+    mollusk_svm::Mollusk,
+    solana_instruction::{AccountMeta, Instruction},
+    solana_message::Message,
+    solana_pubkey::Pubkey,
+    solana_sdk_ids::{compute_budget, system_program},
+};
+
+/// A system program transfer of `lamports` from `from` to `to`.
+fn transfer(from: &Pubkey, to: &Pubkey, lamports: u64) -> Instruction {
+    let mut data = 2u32.to_le_bytes().to_vec();
+    data.extend_from_slice(&lamports.to_le_bytes());
+    Instruction::new_with_bytes(
+        system_program::ID,
+        &data,
+        vec![AccountMeta::new(*from, true), AccountMeta::new(*to, false)],
+    )
+}
+
+fn compute_budget_instruction(discriminant: u8, value: &[u8]) -> Instruction {
+    let mut data = vec![discriminant];
+    data.extend_from_slice(value);
+    Instruction::new_with_bytes(compute_budget::ID, &data, vec![])
+}
+
+fn compute_unit_limit(units: u32) -> Instruction {
+    compute_budget_instruction(2, &units.to_le_bytes())
+}
+
+fn compute_unit_price(micro_lamports: u64) -> Instruction {
+    compute_budget_instruction(3, &micro_lamports.to_le_bytes())
+}
+
+/// The instructions of a transaction message as it went over the wire.
+fn replay(message: &[u8]) -> Vec<Instruction> {
+    let message: Message = bincode::deserialize(message).unwrap();
+    message
+        .instructions
+        .iter()
+        .map(|compiled| Instruction {
+            program_id: message.account_keys[usize::from(compiled.program_id_index)],
+            accounts: compiled
+                .accounts
+                .iter()
+                .map(|&index| {
+                    let index = usize::from(index);
+                    AccountMeta {
+                        pubkey: message.account_keys[index],
+                        is_signer: message.is_signer(index),
+                        is_writable: message.is_maybe_writable(index, None),
+                    }
+                })
+                .collect(),
+            data: compiled.data.clone(),
+        })
+        .collect()
+}
+
+fn payer_and_recipient() -> (MockFetcher, Pubkey, Pubkey) {
+    let (payer, recipient) = (Pubkey::new_unique(), Pubkey::new_unique());
+    let fetcher = MockFetcher::new()
+        .with_account(payer, account(10_000_000_000, 0))
+        .with_account(recipient, account(1_000_000_000, 0));
+    (fetcher, payer, recipient)
+}
+
+#[tokio::test]
+async fn a_replayed_transaction_runs_with_its_budget() {
+    let (fetcher, payer, recipient) = payer_and_recipient();
+    let captured = bincode::serialize(&Message::new(
+        &[
+            compute_unit_limit(300_000),
+            compute_unit_price(25_000),
+            transfer(&payer, &recipient, 1_000),
+        ],
+        Some(&payer),
+    ))
+    .unwrap();
+    let instructions = replay(&captured);
+
+    let mut store = store(&fetcher).from_instructions(&instructions).await.unwrap();
+    let mut requested = fetcher.requested_pubkeys();
+    requested.sort();
+    let mut expected = vec![payer, recipient];
+    expected.sort();
+    assert_eq!(requested, expected);
+    assert_eq!(
+        store.compute_budget(),
+        ComputeBudgetRequest {
+            compute_unit_limit: Some(300_000),
+            compute_unit_price: Some(25_000),
+        }
+    );
+
+    let mut mollusk = Mollusk::default();
+    store.apply_compute_budget(&mut mollusk);
+    assert_eq!(mollusk.compute_budget.compute_unit_limit, 300_000);
+    let results = store.run_chain(&mut mollusk, &instructions).await.unwrap();
+    assert_eq!(results.len(), 1);
+    assert!(results[0].program_result.is_ok(), "{:?}", results[0].program_result);
+    assert_eq!(store.cache[&recipient].lamports, 1_000_001_000);
+}
+
+#[tokio::test]
+async fn a_budget_instruction_alone_fetches_nothing() {
+    let fetcher = MockFetcher::new();
+    let store = store(&fetcher)
+        .from_instruction(&compute_unit_limit(500_000))
+        .await
+        .unwrap()
+        .from_instruction(&compute_unit_limit(600_000))
+        .await
+        .unwrap();
+
+    assert!(fetcher.calls().is_empty());
+    // The last limit wins
+    assert_eq!(store.compute_budget().compute_unit_limit, Some(600_000));
+    assert_eq!(store.compute_budget().compute_unit_price, None);
+}
+
+#[tokio::test]
+async fn limits_above_the_maximum_are_capped() {
+    let store = store(&MockFetcher::new())
+        .from_instruction(&compute_unit_limit(u32::MAX))
+        .await
+        .unwrap();
+
+    let mut mollusk = Mollusk::default();
+    mollusk.compute_budget.compute_unit_limit = 200_000;
+    store.apply_compute_budget(&mut mollusk);
+    assert_eq!(store.compute_budget().compute_unit_limit, Some(u32::MAX));
+    assert_eq!(mollusk.compute_budget.compute_unit_limit, 1_400_000);
+}
+
+#[tokio::test]
+async fn without_a_limit_mollusk_keeps_its_own() {
+    let store = store(&MockFetcher::new())
+        .from_instructions(&[
+            compute_unit_price(1),
+            // Truncated, and an instruction the store doesn't read
+            compute_budget_instruction(2, &[1, 2]),
+            compute_budget_instruction(1, &32_768u32.to_le_bytes()),
+        ])
+        .await
+        .unwrap();
+
+    let mut mollusk = Mollusk::default();
+    mollusk.compute_budget.compute_unit_limit = 200_000;
+    store.apply_compute_budget(&mut mollusk);
+    assert_eq!(mollusk.compute_budget.compute_unit_limit, 200_000);
+    assert_eq!(
+        store.compute_budget(),
+        ComputeBudgetRequest {
+            compute_unit_limit: None,
+            compute_unit_price: Some(1),
+        }
+    );
+}
+
+#[tokio::test]
+async fn missing_accounts_are_located_among_all_the_instructions() {
+    let (payer, recipient) = (Pubkey::new_unique(), Pubkey::new_unique());
+    let fetcher = MockFetcher::new().with_account(payer, account(10_000_000_000, 0));
+    let error = store(&fetcher)
+        .from_instructions(&[
+            compute_unit_limit(300_000),
+            compute_unit_price(25_000),
+            transfer(&payer, &recipient, 1_000),
+        ])
+        .await
+        .map(|_| ())
+        .unwrap_err();
+
+    let RpcError::AccountNotFoundInInstruction(missing) = &error else {
+        panic!("expected AccountNotFoundInInstruction, got {error}");
+    };
+    assert_eq!(missing.pubkey, recipient);
+    assert_eq!((missing.instruction_index, missing.account_index), (2, 1));
+}
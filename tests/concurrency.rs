@@ -0,0 +1,78 @@
+//! `with_concurrency`: chunk requests overlap up to the limit and no further, and
+//! still respect the rate limiter.
+
+mod common;
+
+use {
+    common::{instruction, pubkeys, serving, store},
+    solana_rpc_client_api::request::MAX_MULTIPLE_ACCOUNTS,
+    std::time::Duration,
+    tokio::time::Instant,
+};
+
+const LATENCY: Duration = Duration::from_millis(100);
+
+#[tokio::test(start_paused = true)]
+async fn in_flight_requests_never_exceed_the_limit() {
+    let keys = pubkeys(MAX_MULTIPLE_ACCOUNTS * 10);
+    let fetcher = serving(&keys).with_latency(LATENCY);
+    let started = Instant::now();
+    let store = store(&fetcher)
+        .with_concurrency(3)
+        .from_instruction(&instruction(&keys))
+        .await
+        .unwrap();
+
+    assert_eq!(fetcher.calls().len(), 10);
+    assert_eq!(fetcher.max_in_flight(), 3);
+    // Ten chunks three at a time take four round trips instead of ten
+    assert_eq!(started.elapsed(), LATENCY * 4);
+    for (index, pubkey) in keys.iter().enumerate() {
+        assert_eq!(store.cache[pubkey].lamports, index as u64 + 1);
+    }
+}
+
+#[tokio::test(start_paused = true)]
+async fn chunks_are_sequential_by_default() {
+    let keys = pubkeys(MAX_MULTIPLE_ACCOUNTS * 3);
+    let fetcher = serving(&keys).with_latency(LATENCY);
+    let started = Instant::now();
+    store(&fetcher)
+        .from_instruction(&instruction(&keys))
+        .await
+        .unwrap();
+
+    assert_eq!(fetcher.max_in_flight(), 1);
+    assert_eq!(started.elapsed(), LATENCY * 3);
+}
+
+#[tokio::test(start_paused = true)]
+async fn fewer_chunks_than_the_limit_all_overlap() {
+    let keys = pubkeys(MAX_MULTIPLE_ACCOUNTS * 2);
+    let fetcher = serving(&keys).with_latency(LATENCY);
+    store(&fetcher)
+        .with_concurrency(8)
+        .from_instruction(&instruction(&keys))
+        .await
+        .unwrap();
+
+    assert_eq!(fetcher.max_in_flight(), 2);
+}
+
+#[tokio::test]
+async fn concurrency_does_not_bypass_the_rate_limit() {
+    let keys = pubkeys(MAX_MULTIPLE_ACCOUNTS * 6);
+    let fetcher = serving(&keys);
+    let started = std::time::Instant::now();
+    store(&fetcher)
+        .with_concurrency(6)
+        .with_rate_limit(20)
+        .from_instruction(&instruction(&keys))
+        .await
+        .unwrap();
+
+    // Six requests at 20 per second are spaced 50ms apart: the last one can't
+    // start before 250ms have passed
+    assert_eq!(fetcher.calls().len(), 6);
+    assert!(started.elapsed() >= Duration::from_millis(250), "{:?}", started.elapsed());
+}
@@ -0,0 +1,179 @@
+//! `RpcStoreConfig`: a TOML file sets the fields it names and leaves the rest at
+//! their defaults, bad files are errors naming the path, environment variables
+//! override the file, and `from_config` builds the same store as the builder
+//! methods it stands for.
+
+mod common;
+
+use {
+    common::temp_dir,
+    mollusk_on_demand::{Cluster, RpcAccountStore, RpcError, RpcStoreConfig, ValidationMode},
+    solana_commitment_config::{CommitmentConfig, CommitmentLevel},
+    std::{
+        path::PathBuf,
+        sync::{Mutex, MutexGuard},
+        time::Duration,
+    },
+};
+
+const FILE: &str = r#"
+rpc_url = "http://localhost:8899"
+commitment = "finalized"
+fixture_dir = "tests/fixtures"
+max_retry_after_secs = 5
+rate_limit = 10
+concurrency = 4
+rpc_budget = 100
+expect_cluster = "devnet"
+lenient = true
+max_account_bytes = 1024
+elf_validation = "warn"
+auto_top_up_rent = true
+program_accounts_limit = 50
+url_redaction = false
+"#;
+
+/// The environment is process-wide; tests holding this run one at a time.
+static ENVIRONMENT: Mutex<()> = Mutex::new(());
+
+/// Lock the environment with only `vars` of the overriding variables set.
+fn set_env(vars: &[(&str, &str)]) -> MutexGuard<'static, ()> {
+    let guard = ENVIRONMENT.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    for name in ["MOLLUSK_RPC_URL", "MOLLUSK_RATE_LIMIT", "MOLLUSK_OFFLINE"] {
+        std::env::remove_var(name);
+    }
+    for (name, value) in vars {
+        std::env::set_var(name, value);
+    }
+    guard
+}
+
+/// `contents` written to a `mollusk.toml` of its own.
+fn config_file(contents: &str) -> PathBuf {
+    let path = temp_dir("config").join("mollusk.toml");
+    std::fs::write(&path, contents).unwrap();
+    path
+}
+
+#[test]
+fn a_file_sets_the_fields_it_names() {
+    let path = config_file(FILE);
+    let config = {
+        let _env = set_env(&[]);
+        RpcStoreConfig::from_file(&path).unwrap()
+    };
+    assert_eq!(
+        config,
+        RpcStoreConfig {
+            rpc_url: "http://localhost:8899".to_string(),
+            commitment: CommitmentLevel::Finalized,
+            fixture_dir: Some("tests/fixtures".into()),
+            max_retry_after_secs: 5,
+            rate_limit: Some(10),
+            concurrency: 4,
+            rpc_budget: Some(100),
+            expect_cluster: Some(Cluster::Devnet),
+            lenient: true,
+            max_account_bytes: Some(1024),
+            elf_validation: ValidationMode::Warn,
+            auto_top_up_rent: true,
+            program_accounts_limit: 50,
+            url_redaction: false,
+            ..RpcStoreConfig::default()
+        }
+    );
+}
+
+#[test]
+fn missing_fields_keep_their_defaults() {
+    assert_eq!(RpcStoreConfig::from_toml("").unwrap(), RpcStoreConfig::default());
+    assert_eq!(
+        RpcStoreConfig::from_toml("offline = true").unwrap(),
+        RpcStoreConfig {
+            offline: true,
+            ..RpcStoreConfig::default()
+        }
+    );
+}
+
+#[test]
+fn bad_files_are_errors_naming_the_path() {
+    for (contents, expected) in [
+        ("retries = 3", "unknown field `retries`"),
+        ("commitment = \"max\"", "unknown variant `max`"),
+        ("rate_limit = \"fast\"", "invalid type"),
+        ("expect_cluster = \"moonnet\"", "unknown cluster \"moonnet\""),
+    ] {
+        let path = config_file(contents);
+        let error = RpcStoreConfig::from_file(&path).unwrap_err();
+        let RpcError::InvalidConfig {
+            path: rejected,
+            reason,
+        } = &error
+        else {
+            panic!("expected InvalidConfig for {contents:?}, got {error}");
+        };
+        assert_eq!(*rejected, path);
+        assert!(reason.contains(expected), "{contents:?}: {reason}");
+        assert_eq!(error.code(), "invalid_config");
+    }
+
+    let missing = temp_dir("config").join("missing.toml");
+    let error = RpcStoreConfig::from_file(&missing).unwrap_err();
+    assert!(matches!(&error, RpcError::InvalidConfig { path, .. } if *path == missing));
+}
+
+#[test]
+fn environment_variables_override_the_file() {
+    let path = config_file(FILE);
+    let config = {
+        let _env = set_env(&[("MOLLUSK_RATE_LIMIT", "2"), ("MOLLUSK_OFFLINE", "1")]);
+        RpcStoreConfig::from_file(&path).unwrap()
+    };
+    assert_eq!(config.rate_limit, Some(2));
+    assert!(config.offline);
+    // Fields without a variable set keep the file's values
+    assert_eq!(config.rpc_url, "http://localhost:8899");
+    assert_eq!(config.concurrency, 4);
+
+    let error = {
+        let _env = set_env(&[("MOLLUSK_RPC_URL", "not a url")]);
+        RpcStoreConfig::from_file(&path).unwrap_err()
+    };
+    assert!(matches!(error, RpcError::InvalidEnvVar { name: "MOLLUSK_RPC_URL", .. }), "{error}");
+}
+
+#[test]
+fn from_config_matches_the_builder() {
+    let config = RpcStoreConfig::from_toml(FILE).unwrap();
+    let configured = RpcAccountStore::from_config(config);
+    let built = RpcAccountStore::new_with_commitment(
+        "http://localhost:8899",
+        CommitmentConfig::finalized(),
+    )
+    .with_fixture_dir("tests/fixtures")
+    .with_max_retry_after(Duration::from_secs(5))
+    .with_rate_limit(10)
+    .with_concurrency(4)
+    .with_rpc_budget(100)
+    .expect_cluster(Cluster::Devnet)
+    .lenient()
+    .with_max_account_bytes_strict(1024)
+    .elf_validation(ValidationMode::Warn)
+    .auto_top_up_rent()
+    .with_program_accounts_limit(50)
+    .with_url_redaction(false);
+
+    assert_eq!(configured.rpc_url(), built.rpc_url());
+    assert_eq!(configured.commitment(), built.commitment());
+    assert_eq!(format!("{configured:?}"), format!("{built:?}"));
+}
+
+#[test]
+fn the_default_config_is_a_default_store() {
+    let configured = RpcAccountStore::from_config(RpcStoreConfig::default());
+    let built = RpcAccountStore::new(mollusk_on_demand::MAINNET_RPC_URL);
+    assert_eq!(configured.rpc_url(), built.rpc_url());
+    assert_eq!(configured.commitment(), built.commitment());
+    assert_eq!(format!("{configured:?}"), format!("{built:?}"));
+}
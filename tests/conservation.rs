@@ -0,0 +1,111 @@
+//! `check_lamport_conservation`: a transfer keeps the total, accounts created by the
+//! instruction count with nothing before, a lower total is reported as burned, and
+//! a result holding more lamports than its accounts had is a violation.
+
+mod common;
+
+use {
+    common::{account, store},
+    mollusk_on_demand::{process_instruction_with_store, testing::MockFetcher, RpcAccountStore},
+    mollusk_svm::{result::InstructionResult, Mollusk},
+    solana_instruction::{AccountMeta, Instruction},
+    solana_pubkey::Pubkey,
+    solana_sdk_ids::system_program,
+};
+
+const LAMPORTS: u64 = 10_000_000_000;
+
+fn transfer(from: &Pubkey, to: &Pubkey, lamports: u64) -> Instruction {
+    let mut data = 2u32.to_le_bytes().to_vec();
+    data.extend_from_slice(&lamports.to_le_bytes());
+    Instruction::new_with_bytes(
+        system_program::ID,
+        &data,
+        vec![AccountMeta::new(*from, true), AccountMeta::new(*to, false)],
+    )
+}
+
+/// A store holding `from` and `to`, and the result of transferring a third of
+/// `from`'s lamports to `to`.
+async fn transferred(from: Pubkey, to: Pubkey) -> (RpcAccountStore, InstructionResult) {
+    let fetcher = MockFetcher::new()
+        .with_account(from, account(LAMPORTS, 0))
+        .with_account(to, account(LAMPORTS, 0));
+    let ix = transfer(&from, &to, LAMPORTS / 3);
+    let store = store(&fetcher).from_instruction(&ix).await.unwrap();
+    let result = process_instruction_with_store(&Mollusk::default(), &store, &ix).unwrap();
+    assert!(result.program_result.is_ok(), "{:?}", result.program_result);
+    (store, result)
+}
+
+#[tokio::test]
+async fn a_transfer_conserves_lamports() {
+    let (from, to) = (Pubkey::new_unique(), Pubkey::new_unique());
+    let (store, result) = transferred(from, to).await;
+
+    let delta = store.check_lamport_conservation(&result).unwrap();
+    assert_eq!(delta.before, 2 * u128::from(LAMPORTS));
+    assert_eq!(delta.after, delta.before);
+    assert_eq!(delta.burned(), 0);
+}
+
+#[tokio::test]
+async fn a_created_account_counts_with_nothing_before() {
+    let (payer, new) = (Pubkey::new_unique(), Pubkey::new_unique());
+    let fetcher = MockFetcher::new().with_account(payer, account(LAMPORTS, 0));
+    let mut store = store(&fetcher);
+    store.get_or_fetch_many(&[payer]).await.unwrap();
+    let mut result = InstructionResult {
+        resulting_accounts: vec![
+            (payer, account(LAMPORTS - 1_000, 0)),
+            (new, account(1_000, 0)),
+        ],
+        ..Default::default()
+    };
+    assert!(!store.cache.contains_key(&new));
+
+    let delta = store.check_lamport_conservation(&result).unwrap();
+    assert_eq!((delta.before, delta.after), (u128::from(LAMPORTS), u128::from(LAMPORTS)));
+
+    // Funded out of nothing
+    result.resulting_accounts[0].1.lamports = LAMPORTS;
+    let violation = store.check_lamport_conservation(&result).unwrap_err();
+    assert_eq!(violation.created(), 1_000);
+    assert_eq!(violation.increases, [(new, 1_000)]);
+}
+
+#[tokio::test]
+async fn a_lower_total_is_reported_as_burned() {
+    let (from, to) = (Pubkey::new_unique(), Pubkey::new_unique());
+    let (store, mut result) = transferred(from, to).await;
+    result.resulting_accounts[1].1.lamports -= 5_000;
+
+    let delta = store.check_lamport_conservation(&result).unwrap();
+    assert_eq!(delta.burned(), 5_000);
+    assert_eq!(
+        delta.to_string(),
+        format!("{} lamports before, {} after (5000 burned)", 2 * LAMPORTS, 2 * LAMPORTS - 5_000)
+    );
+}
+
+#[tokio::test]
+async fn an_inflated_result_is_a_violation() {
+    let (from, to) = (Pubkey::new_unique(), Pubkey::new_unique());
+    let (store, mut result) = transferred(from, to).await;
+    let to_index = result.resulting_accounts.iter().position(|(pubkey, _)| *pubkey == to).unwrap();
+    result.resulting_accounts[to_index].1.lamports += 42;
+
+    let violation = store.check_lamport_conservation(&result).unwrap_err();
+    assert_eq!(violation.before, 2 * u128::from(LAMPORTS));
+    assert_eq!(violation.created(), 42);
+    assert_eq!(violation.increases, [(to, LAMPORTS / 3 + 42)]);
+    assert_eq!(
+        violation.to_string(),
+        format!(
+            "42 lamports created from nothing: {} before, {} after\n  {to}: +{}",
+            2 * LAMPORTS,
+            2 * LAMPORTS + 42,
+            LAMPORTS / 3 + 42
+        )
+    );
+}
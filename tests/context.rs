@@ -0,0 +1,100 @@
+//! `LazyAccountStore`: accounts missing from the cache are fetched while Mollusk runs
+//! the instruction, written accounts land back in the store, and fetch errors are
+//! kept since the store trait can't report them.
+
+#![cfg(feature = "context")]
+
+mod common;
+
+use {
+    common::{account, store},
+    mollusk_on_demand::{
+        testing::{FetcherCall, MockFetcher},
+        LazyAccountStore, Provenance, RpcAccountStore,
+    },
+    mollusk_svm::{Mollusk, MolluskContext},
+    solana_instruction::{AccountMeta, Instruction},
+    solana_pubkey::Pubkey,
+    solana_sdk_ids::system_program,
+    std::rc::Rc,
+};
+
+fn transfer(from: &Pubkey, to: &Pubkey, lamports: u64) -> Instruction {
+    let mut data = 2u32.to_le_bytes().to_vec();
+    data.extend_from_slice(&lamports.to_le_bytes());
+    Instruction::new_with_bytes(
+        system_program::ID,
+        &data,
+        vec![AccountMeta::new(*from, true), AccountMeta::new(*to, false)],
+    )
+}
+
+fn into_store(context: MolluskContext<LazyAccountStore>) -> LazyAccountStore {
+    let MolluskContext { account_store, .. } = context;
+    Rc::try_unwrap(account_store).unwrap().into_inner()
+}
+
+/// A store holding `payer`, over a mock that also serves `recipient`.
+async fn payer_cached(payer: Pubkey, recipient: Pubkey) -> (MockFetcher, RpcAccountStore) {
+    let fetcher = MockFetcher::new()
+        .with_account(payer, account(10_000_000_000, 0))
+        .with_account(recipient, account(1_000_000_000, 0));
+    let mut store = store(&fetcher);
+    store.get_or_fetch(&payer).await.unwrap();
+    fetcher.clear_calls();
+    (fetcher, store)
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn accounts_missing_from_the_cache_are_fetched_mid_execution() {
+    let (payer, recipient) = (Pubkey::new_unique(), Pubkey::new_unique());
+    let (fetcher, store) = payer_cached(payer, recipient).await;
+    assert!(!store.cache.contains_key(&recipient));
+
+    let context = store.into_context(Mollusk::default());
+    let result = context.process_instruction(&transfer(&payer, &recipient, 1_000));
+    assert!(result.program_result.is_ok(), "{:?}", result.program_result);
+
+    // Only the recipient, and not the builtins seeded into the context
+    assert_eq!(fetcher.calls(), [FetcherCall::GetMultipleAccounts(vec![recipient])]);
+    let lazy = into_store(context);
+    assert_eq!(lazy.served(), [payer, recipient]);
+    assert!(lazy.take_errors().is_empty());
+
+    let store = lazy.into_inner();
+    assert_eq!(store.cache[&recipient].lamports, 1_000_001_000);
+    assert_eq!(
+        store.provenance(&recipient),
+        Some(&Provenance::ResultApplied { instruction_index: None })
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn fetch_errors_are_kept_for_later() {
+    let (payer, missing) = (Pubkey::new_unique(), Pubkey::new_unique());
+    let fetcher = MockFetcher::new().with_account(payer, account(10_000_000_000, 0));
+    let mut store = store(&fetcher);
+    store.get_or_fetch(&payer).await.unwrap();
+
+    let context = store.into_context(Mollusk::default());
+    context.process_instruction(&transfer(&payer, &missing, 1_000));
+
+    let lazy = into_store(context);
+    let errors = lazy.take_errors();
+    assert_eq!(errors.len(), 1);
+    assert!(errors[0].is_not_found(), "{}", errors[0]);
+    assert!(lazy.take_errors().is_empty());
+}
+
+#[test]
+fn misses_block_on_a_given_runtime_outside_of_one() {
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    let (payer, recipient) = (Pubkey::new_unique(), Pubkey::new_unique());
+    let (fetcher, store) = runtime.block_on(payer_cached(payer, recipient));
+
+    let lazy = LazyAccountStore::with_handle(store, runtime.handle().clone());
+    let context = Mollusk::default().with_context(lazy);
+    let result = context.process_instruction(&transfer(&payer, &recipient, 1_000));
+    assert!(result.program_result.is_ok(), "{:?}", result.program_result);
+    assert!(fetcher.requested_pubkeys().contains(&recipient));
+}
@@ -0,0 +1,159 @@
+//! `parse_account` and `dump_parsed` on a token account, its mint and a nonce
+//! account, snapshotting the explorer-style JSON, with unknown owners and token
+//! accounts without their mint falling back to base64.
+
+#![cfg(feature = "decoder")]
+
+mod common;
+
+use {
+    common::store,
+    mollusk_on_demand::{testing::MockFetcher, RpcAccountStore},
+    serde_json::json,
+    solana_account::Account,
+    solana_program_pack::Pack,
+    solana_pubkey::Pubkey,
+    solana_sdk_ids::system_program,
+    spl_token_interface::state::{Account as TokenAccount, AccountState, Mint},
+};
+
+fn key(byte: u8) -> Pubkey {
+    Pubkey::new_from_array([byte; 32])
+}
+
+const VAULT: u8 = 1;
+const MINT: u8 = 2;
+const WALLET: u8 = 3;
+const NONCE: u8 = 4;
+const AUTHORITY: u8 = 5;
+
+fn owned_by(owner: Pubkey, data: Vec<u8>) -> Account {
+    Account {
+        lamports: 2_039_280,
+        data,
+        owner,
+        executable: false,
+        rent_epoch: 0,
+    }
+}
+
+/// A 6-decimal mint, a token account holding 1.5 of it, and an initialized nonce
+/// account, as the chain stores them.
+fn accounts() -> [(Pubkey, Account); 3] {
+    let mut token_account = vec![0; TokenAccount::LEN];
+    TokenAccount {
+        mint: key(MINT),
+        owner: key(WALLET),
+        amount: 1_500_000,
+        state: AccountState::Initialized,
+        ..TokenAccount::default()
+    }
+    .pack_into_slice(&mut token_account);
+
+    let mut mint = vec![0; Mint::LEN];
+    Mint {
+        supply: 1_500_000,
+        decimals: 6,
+        is_initialized: true,
+        ..Mint::default()
+    }
+    .pack_into_slice(&mut mint);
+
+    // Versions::Current(State::Initialized(Data { authority, durable_nonce, fee_calculator }))
+    let mut nonce = 1u32.to_le_bytes().to_vec();
+    nonce.extend_from_slice(&1u32.to_le_bytes());
+    nonce.extend_from_slice(key(AUTHORITY).as_ref());
+    nonce.extend_from_slice(&[7; 32]);
+    nonce.extend_from_slice(&5_000u64.to_le_bytes());
+
+    [
+        (key(VAULT), owned_by(spl_token_interface::ID, token_account)),
+        (key(MINT), owned_by(spl_token_interface::ID, mint)),
+        (key(NONCE), owned_by(system_program::ID, nonce)),
+    ]
+}
+
+fn cached() -> RpcAccountStore {
+    store(&MockFetcher::new()).with_accounts(accounts())
+}
+
+#[test]
+fn a_token_account_and_a_nonce_account_parse() {
+    let store = cached().with_labels(&[(key(VAULT), "vault")]);
+    let dump = store.dump_parsed(&[key(VAULT), key(NONCE)]).unwrap();
+    let expected = json!([
+        {
+            "label": "vault",
+            "parsed": {
+                "info": {
+                    "isNative": false,
+                    "mint": key(MINT).to_string(),
+                    "owner": key(WALLET).to_string(),
+                    "state": "initialized",
+                    "tokenAmount": {
+                        "amount": "1500000",
+                        "decimals": 6,
+                        "uiAmount": 1.5,
+                        "uiAmountString": "1.5"
+                    }
+                },
+                "type": "account"
+            },
+            "program": "spl-token",
+            "pubkey": key(VAULT).to_string(),
+            "space": 165
+        },
+        {
+            "parsed": {
+                "info": {
+                    "authority": key(AUTHORITY).to_string(),
+                    "blockhash": Pubkey::new_from_array([7; 32]).to_string(),
+                    "feeCalculator": { "lamportsPerSignature": "5000" }
+                },
+                "type": "initialized"
+            },
+            "program": "nonce",
+            "pubkey": key(NONCE).to_string(),
+            "space": 80
+        }
+    ]);
+    assert_eq!(dump, serde_json::to_string_pretty(&expected).unwrap());
+}
+
+#[test]
+fn a_mint_parses() {
+    let parsed = cached().parse_account(&key(MINT)).unwrap();
+    assert_eq!(parsed.program, "spl-token");
+    assert_eq!(
+        parsed.parsed,
+        json!({
+            "info": {
+                "decimals": 6,
+                "freezeAuthority": null,
+                "isInitialized": true,
+                "mintAuthority": null,
+                "supply": "1500000"
+            },
+            "type": "mint"
+        })
+    );
+}
+
+#[test]
+fn unknown_owners_fall_back_to_base64() {
+    let owner = key(9);
+    let store = cached().with_accounts([(key(8), owned_by(owner, vec![1, 2, 3]))]);
+    let parsed = store.parse_account(&key(8)).unwrap();
+    assert_eq!(parsed.program, owner.to_string());
+    assert_eq!(parsed.parsed, json!(["AQID", "base64"]));
+    assert_eq!(parsed.space, 3);
+}
+
+#[test]
+fn a_token_account_needs_its_mint() {
+    let [vault, ..] = accounts();
+    let store = store(&MockFetcher::new()).with_accounts([vault]);
+    let parsed = store.parse_account(&key(VAULT)).unwrap();
+    assert_eq!(parsed.parsed[1], "base64");
+    assert!(store.parse_account(&key(MINT)).is_err());
+}
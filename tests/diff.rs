@@ -0,0 +1,151 @@
+//! `diff_result`: edits in the middle of a large account are coalesced into one
+//! range shown as hex, pure lamport changes have no data ranges, and created,
+//! closed, reassigned and resized accounts are called out.
+
+mod common;
+
+use {
+    common::account,
+    mollusk_on_demand::{testing::MockFetcher, AccountChange, RpcAccountStore},
+    mollusk_svm::result::InstructionResult,
+    solana_account::Account,
+    solana_pubkey::Pubkey,
+};
+
+const LAMPORTS: u64 = 1_000_000_000;
+
+/// A store over a mock with `accounts` cached.
+fn cached(accounts: impl IntoIterator<Item = (Pubkey, Account)>) -> RpcAccountStore {
+    RpcAccountStore::new_with_fetcher(MockFetcher::new()).with_accounts(accounts)
+}
+
+fn result(resulting_accounts: Vec<(Pubkey, Account)>) -> InstructionResult {
+    InstructionResult {
+        resulting_accounts,
+        ..InstructionResult::default()
+    }
+}
+
+#[test]
+fn an_edit_in_a_large_account_is_one_coalesced_range() {
+    let pubkey = Pubkey::new_unique();
+    let before = account(LAMPORTS, 10_000);
+    let mut after = before.clone();
+    after.data[5_000..5_004].copy_from_slice(&[0xde, 0xad, 0xbe, 0xef]);
+    // Close enough to the first edit to be reported with it
+    after.data[5_010] = 0xff;
+    let store = cached([(pubkey, before)]);
+
+    let diff = store.diff_result(&result(vec![(pubkey, after)]));
+    let account = diff.account(&pubkey).unwrap();
+    assert_eq!(account.change, AccountChange::Modified);
+    assert_eq!(account.lamport_delta, 0);
+    assert_eq!(account.owner, None);
+    assert_eq!(account.data_len, (10_000, 10_000));
+    assert_eq!(account.changed_ranges, vec![5_000..5_011]);
+
+    let shown = diff.to_string();
+    assert!(shown.contains(&format!("{pubkey} modified")), "{shown}");
+    assert!(shown.contains("lamports +0"), "{shown}");
+    assert!(!shown.contains("data 10000"), "{shown}");
+    // Four bytes of context on each side of the range
+    let before_hex = ["00"; 19].join(" ");
+    let after_hex = "00 00 00 00 de ad be ef 00 00 00 00 00 00 ff 00 00 00 00";
+    assert!(shown.contains(&format!("[5000..5011] {before_hex}")), "{shown}");
+    assert!(shown.ends_with(after_hex), "{shown}");
+}
+
+#[test]
+fn far_apart_edits_are_separate_ranges() {
+    let pubkey = Pubkey::new_unique();
+    let before = account(LAMPORTS, 10_000);
+    let mut after = before.clone();
+    after.data[100] = 1;
+    after.data[9_000] = 1;
+    let store = cached([(pubkey, before)]);
+
+    let diff = store.diff_result(&result(vec![(pubkey, after)]));
+    assert_eq!(diff.accounts[0].changed_ranges, [100..101, 9_000..9_001]);
+    assert_eq!(diff.to_string().lines().count(), 3);
+}
+
+#[test]
+fn a_pure_lamport_change_has_no_data_ranges() {
+    let (payer, payee) = (Pubkey::new_unique(), Pubkey::new_unique());
+    let store = cached([(payer, account(LAMPORTS, 8)), (payee, account(LAMPORTS, 8))]);
+
+    let diff = store.diff_result(&result(vec![
+        (payer, account(LAMPORTS - 500, 8)),
+        (payee, account(LAMPORTS + 500, 8)),
+    ]));
+    let deltas: Vec<i128> = diff.accounts.iter().map(|account| account.lamport_delta).collect();
+    assert_eq!(deltas, [-500, 500]);
+    for account in &diff.accounts {
+        assert_eq!(account.change, AccountChange::Modified);
+        assert!(account.changed_ranges.is_empty());
+        assert_eq!(account.data_len, (8, 8));
+    }
+
+    let shown = diff.to_string();
+    assert_eq!(shown.lines().count(), 2, "{shown}");
+    assert!(shown.contains(&format!("{payer} modified")), "{shown}");
+    assert!(shown.contains("lamports -500"), "{shown}");
+    assert!(shown.contains("lamports +500"), "{shown}");
+}
+
+#[test]
+fn created_and_closed_accounts_are_called_out() {
+    let [created, closed, reassigned, untouched] = [(); 4].map(|()| Pubkey::new_unique());
+    let owner = Pubkey::new_unique();
+    let store = cached([
+        (closed, account(LAMPORTS, 16)),
+        (reassigned, account(LAMPORTS, 0)),
+        (untouched, account(LAMPORTS, 8)),
+    ]);
+
+    let diff = store.diff_result(&result(vec![
+        (created, account(LAMPORTS, 4)),
+        (closed, account(0, 0)),
+        (
+            reassigned,
+            Account {
+                owner,
+                ..account(LAMPORTS, 32)
+            },
+        ),
+        (untouched, account(LAMPORTS, 8)),
+    ]));
+    assert_eq!(diff.accounts.len(), 3);
+    assert!(diff.account(&untouched).is_none());
+
+    let created_diff = diff.account(&created).unwrap();
+    assert_eq!(created_diff.change, AccountChange::Created);
+    assert_eq!(created_diff.lamport_delta, i128::from(LAMPORTS));
+    assert_eq!(created_diff.data_len, (0, 4));
+    let closed_diff = diff.account(&closed).unwrap();
+    assert_eq!(closed_diff.change, AccountChange::Closed);
+    assert_eq!(closed_diff.lamport_delta, -i128::from(LAMPORTS));
+    assert_eq!(closed_diff.data_len, (16, 0));
+    let reassigned_diff = diff.account(&reassigned).unwrap();
+    assert_eq!(reassigned_diff.owner.map(|(_, after)| after), Some(owner));
+    assert_eq!(reassigned_diff.data_len, (0, 32));
+    // Bytes added by the resize differ from nothing
+    assert_eq!(reassigned_diff.changed_ranges, vec![0..32]);
+
+    let shown = diff.to_string();
+    assert!(shown.contains(&format!("{created} created")), "{shown}");
+    assert!(shown.contains(&format!("{closed} closed")), "{shown}");
+    assert!(shown.contains(&format!("-> {owner}")), "{shown}");
+    assert!(shown.contains("data 0 -> 32 bytes"), "{shown}");
+    assert!(shown.contains("data 16 -> 0 bytes"), "{shown}");
+}
+
+#[test]
+fn an_unchanged_result_reports_nothing() {
+    let pubkey = Pubkey::new_unique();
+    let store = cached([(pubkey, account(LAMPORTS, 8))]);
+
+    let diff = store.diff_result(&result(vec![(pubkey, account(LAMPORTS, 8))]));
+    assert!(diff.is_empty());
+    assert_eq!(diff.to_string(), "no accounts changed");
+}
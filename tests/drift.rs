@@ -0,0 +1,135 @@
+//! `verify_fresh` against a mock that changes accounts between calls: each kind of
+//! drift is reported without touching the cache, and `refresh_drifted` applies the
+//! live state of the drifted accounts alone.
+
+mod common;
+
+use {
+    common::{account, instruction, store},
+    mollusk_on_demand::{testing::MockFetcher, Drift, RpcAccountStore},
+    solana_account::Account,
+    solana_pubkey::Pubkey,
+    solana_sdk_ids::system_program,
+};
+
+const LAMPORTS: u64 = 1_000_000_000;
+const NEW_OWNER: Pubkey = Pubkey::new_from_array([7; 32]);
+
+/// A store that fetched five accounts, after which the chain moved on: `paid`
+/// received lamports, `reassigned` moved to [`NEW_OWNER`], `written` had three bytes
+/// changed and grew by two, and `closed` disappeared.
+async fn drifted() -> (MockFetcher, RpcAccountStore, [Pubkey; 5]) {
+    let keys @ [untouched, paid, reassigned, written, closed] =
+        [(); 5].map(|()| Pubkey::new_unique());
+    let fetcher =
+        MockFetcher::new().with_accounts(keys.map(|pubkey| (pubkey, account(LAMPORTS, 8))));
+    let store = store(&fetcher).from_instruction(&instruction(&keys)).await.unwrap();
+
+    fetcher.set_account(paid, account(LAMPORTS + 5, 8));
+    fetcher.set_account(
+        reassigned,
+        Account {
+            owner: NEW_OWNER,
+            ..account(LAMPORTS, 8)
+        },
+    );
+    let mut grown = account(LAMPORTS, 10);
+    grown.data[..3].copy_from_slice(&[1, 2, 3]);
+    fetcher.set_account(written, grown);
+    fetcher.remove_account(&closed);
+    fetcher.clear_calls();
+    (fetcher, store, [untouched, paid, reassigned, written, closed])
+}
+
+#[tokio::test]
+async fn each_kind_of_drift_is_reported() {
+    let (fetcher, store, keys @ [untouched, paid, reassigned, written, closed]) = drifted().await;
+
+    let report = store.verify_fresh(&keys).await.unwrap();
+    assert!(!report.is_fresh());
+    assert_eq!(
+        report.accounts,
+        [
+            (untouched, Drift::Unchanged),
+            (
+                paid,
+                Drift::Changed {
+                    lamports: Some((LAMPORTS, LAMPORTS + 5)),
+                    owner: None,
+                    changed_bytes: 0,
+                }
+            ),
+            (
+                reassigned,
+                Drift::Changed {
+                    lamports: None,
+                    owner: Some((system_program::ID, NEW_OWNER)),
+                    changed_bytes: 0,
+                }
+            ),
+            (
+                written,
+                Drift::Changed {
+                    lamports: None,
+                    owner: None,
+                    changed_bytes: 5,
+                }
+            ),
+            (closed, Drift::Missing),
+        ]
+    );
+    assert_eq!(report.drifted().count(), 4);
+    // One request, and the cache keeps what it had
+    assert_eq!(fetcher.calls().len(), 1);
+    assert_eq!(store.cache[&paid].lamports, LAMPORTS);
+    assert!(store.cache.contains_key(&closed));
+
+    let shown = report.to_string();
+    assert_eq!(
+        shown,
+        format!(
+            "4 of 5 accounts drifted from the chain:\n  {paid}: lamports {LAMPORTS} -> {}\n  \
+             {reassigned}: owner {} -> {NEW_OWNER}\n  {written}: 5 bytes of data changed\n  \
+             {closed}: missing on-chain",
+            LAMPORTS + 5,
+            system_program::ID,
+        )
+    );
+}
+
+#[tokio::test]
+async fn refresh_drifted_applies_only_the_drifted_accounts() {
+    let (_, mut store, [untouched, paid, reassigned, written, closed]) = drifted().await;
+    let untouched_before = store.provenance(&untouched).cloned();
+
+    let report = store.verify_all_fresh().await.unwrap();
+    assert_eq!(report.accounts.len(), 5);
+    let mut sorted: Vec<Pubkey> = report.accounts.iter().map(|(pubkey, _)| *pubkey).collect();
+    sorted.sort();
+    assert_eq!(report.accounts.iter().map(|(pubkey, _)| *pubkey).collect::<Vec<_>>(), sorted);
+
+    assert_eq!(store.refresh_drifted(&report), 4);
+    assert_eq!(store.provenance(&untouched).cloned(), untouched_before);
+    assert_eq!(store.cache[&paid].lamports, LAMPORTS + 5);
+    assert_eq!(store.cache[&reassigned].owner, NEW_OWNER);
+    assert_eq!(store.cache[&written].data.len(), 10);
+    assert!(!store.cache.contains_key(&closed));
+
+    let report = store.verify_all_fresh().await.unwrap();
+    assert!(report.is_fresh());
+    assert_eq!(report.to_string(), "all 4 accounts match the chain");
+}
+
+#[tokio::test]
+async fn uncached_pubkeys_are_left_out() {
+    let (fetcher, store, [untouched, ..]) = drifted().await;
+
+    let report = store.verify_fresh(&[Pubkey::new_unique()]).await.unwrap();
+    assert!(report.accounts.is_empty());
+    assert!(fetcher.calls().is_empty());
+
+    let report = store.verify_fresh(&[untouched, Pubkey::new_unique()]).await.unwrap();
+    assert_eq!(report.accounts, [(untouched, Drift::Unchanged)]);
+    assert!(report.is_fresh());
+    assert_eq!(fetcher.requested_pubkeys(), [untouched]);
+}
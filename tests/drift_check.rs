@@ -0,0 +1,275 @@
+//! `drift_check`: a committed fixture re-fetched from a chain that moved on under a
+//! subset of its accounts, each sorted into its category, with the report's counts,
+//! threshold, printed summary and JSON.
+
+mod common;
+
+use {
+    common::{account, store, temp_dir},
+    mollusk_on_demand::{
+        testing::{FetcherCall, MockFetcher},
+        DriftCategory, FixtureAccountDrift, FixtureDriftReport, RpcError,
+    },
+    serde_json::json,
+    solana_account::Account,
+    solana_pubkey::Pubkey,
+    solana_sdk_ids::bpf_loader_upgradeable,
+    std::{
+        fs,
+        path::{Path, PathBuf},
+    },
+};
+
+const LAMPORTS: u64 = 1_000_000_000;
+
+fn key(byte: u8) -> Pubkey {
+    Pubkey::new_from_array([byte; 32])
+}
+
+const UNCHANGED: u8 = 1;
+const PAID: u8 = 2;
+const WRITTEN: u8 = 3;
+const REASSIGNED: u8 = 4;
+const CLOSED: u8 = 5;
+const UPGRADED: u8 = 6;
+const TOPPED_UP: u8 = 7;
+
+/// A BPF Loader v3 program data account deployed at `slot`, holding `elf`.
+fn program_data(slot: u64, elf: u8) -> Account {
+    let mut data = 3u32.to_le_bytes().to_vec();
+    data.extend_from_slice(&slot.to_le_bytes());
+    data.resize(45, 0);
+    data.extend_from_slice(&[elf; 64]);
+    Account {
+        data,
+        owner: bpf_loader_upgradeable::ID,
+        ..account(LAMPORTS, 0)
+    }
+}
+
+/// The accounts as the fixture recorded them.
+fn recorded() -> Vec<(Pubkey, Account)> {
+    let mut accounts: Vec<(Pubkey, Account)> = [UNCHANGED, PAID, WRITTEN, REASSIGNED, CLOSED]
+        .map(|byte| (key(byte), account(LAMPORTS, 10)))
+        .into();
+    accounts.push((key(UPGRADED), program_data(100, 1)));
+    accounts.push((key(TOPPED_UP), program_data(100, 1)));
+    accounts
+}
+
+/// The chain since: rent paid out of one account, three bytes of another written
+/// and two appended, one reassigned, one closed, a program redeployed and another's
+/// program data topped up.
+fn chain() -> MockFetcher {
+    let fetcher = MockFetcher::new().with_accounts(recorded());
+    fetcher.set_account(key(PAID), account(LAMPORTS - 5_000, 10));
+    let mut written = account(LAMPORTS, 10);
+    written.data[..3].copy_from_slice(&[1, 2, 3]);
+    written.data.extend_from_slice(&[4, 5]);
+    fetcher.set_account(key(WRITTEN), written);
+    fetcher.set_account(
+        key(REASSIGNED),
+        Account {
+            owner: key(9),
+            ..account(LAMPORTS, 10)
+        },
+    );
+    fetcher.remove_account(&key(CLOSED));
+    fetcher.set_account(key(UPGRADED), program_data(200, 2));
+    fetcher.set_account(
+        key(TOPPED_UP),
+        Account {
+            lamports: LAMPORTS + 1,
+            ..program_data(100, 1)
+        },
+    );
+    fetcher
+}
+
+/// A fixture directory of the recorded accounts.
+fn fixture_dir() -> PathBuf {
+    let dir = temp_dir("drift-check");
+    let recorded = recorded();
+    let pubkeys: Vec<Pubkey> = recorded.iter().map(|(pubkey, _)| *pubkey).collect();
+    store(&MockFetcher::new())
+        .with_accounts(recorded)
+        .write_fixtures(&dir, &pubkeys)
+        .unwrap();
+    dir
+}
+
+fn drift(byte: u8, category: DriftCategory) -> FixtureAccountDrift {
+    FixtureAccountDrift {
+        pubkey: key(byte),
+        category,
+        lamports: None,
+        changed_bytes: 0,
+        deploy_slots: None,
+    }
+}
+
+async fn check(path: &Path) -> FixtureDriftReport {
+    let fetcher = chain();
+    let checking = store(&fetcher).with_accounts([(key(UNCHANGED), account(1, 0))]);
+    let report = checking.drift_check(path).await.unwrap();
+
+    // One request for the whole fixture, and the cache left as it was
+    let calls = fetcher.calls();
+    let [FetcherCall::GetMultipleAccounts(requested)] = &calls[..] else {
+        panic!("expected one getMultipleAccounts, got {calls:?}");
+    };
+    assert_eq!(requested.len(), 7);
+    assert_eq!(checking.cache.len(), 1);
+    assert_eq!(checking.cache[&key(UNCHANGED)], account(1, 0));
+    report
+}
+
+#[tokio::test]
+async fn each_account_is_sorted_into_its_category() {
+    let dir = fixture_dir();
+    let report = check(&dir).await;
+    assert_eq!(report.fixture, dir);
+    assert_eq!(
+        report.accounts,
+        [
+            drift(UNCHANGED, DriftCategory::Unchanged),
+            FixtureAccountDrift {
+                lamports: Some((LAMPORTS, LAMPORTS - 5_000)),
+                ..drift(PAID, DriftCategory::LamportsOnly)
+            },
+            FixtureAccountDrift {
+                changed_bytes: 5,
+                ..drift(WRITTEN, DriftCategory::DataChanged)
+            },
+            drift(REASSIGNED, DriftCategory::DataChanged),
+            drift(CLOSED, DriftCategory::Missing),
+            // A byte of the deploy slot and the whole ELF
+            FixtureAccountDrift {
+                changed_bytes: 65,
+                deploy_slots: Some((100, 200)),
+                ..drift(UPGRADED, DriftCategory::ProgramUpgraded)
+            },
+            FixtureAccountDrift {
+                lamports: Some((LAMPORTS, LAMPORTS + 1)),
+                ..drift(TOPPED_UP, DriftCategory::LamportsOnly)
+            },
+        ]
+    );
+
+    assert_eq!(
+        [
+            DriftCategory::Unchanged,
+            DriftCategory::LamportsOnly,
+            DriftCategory::DataChanged,
+            DriftCategory::Missing,
+            DriftCategory::ProgramUpgraded,
+        ]
+        .map(|category| report.count(category)),
+        [1, 2, 2, 1, 1]
+    );
+    let changed: Vec<Pubkey> = report.changed().map(|account| account.pubkey).collect();
+    assert_eq!(changed, [WRITTEN, REASSIGNED, CLOSED, UPGRADED].map(key));
+    assert_eq!(report.changed_fraction(), 4.0 / 7.0);
+}
+
+#[tokio::test]
+async fn a_save_fixture_file_is_checked_in_file_order() {
+    let path = temp_dir("drift-check").join("swap.jsonl");
+    store(&MockFetcher::new())
+        .with_accounts(recorded())
+        .save_fixture(&path)
+        .unwrap();
+    let file = fs::read_to_string(&path).unwrap();
+    let reversed: Vec<&str> = file.lines().rev().collect();
+    fs::write(&path, reversed.join("\n")).unwrap();
+
+    let report = check(&path).await;
+    let order: Vec<Pubkey> = report
+        .accounts
+        .iter()
+        .map(|account| account.pubkey)
+        .collect();
+    assert_eq!(order, [7, 6, 5, 4, 3, 2, 1].map(key));
+    assert_eq!(report.accounts[0].category, DriftCategory::LamportsOnly);
+    assert_eq!(report.changed_fraction(), 4.0 / 7.0);
+}
+
+#[tokio::test]
+async fn too_many_changed_accounts_fail_the_threshold() {
+    let report = check(&fixture_dir()).await;
+    let report = report.fail_if_changed_fraction(4.0 / 7.0).unwrap();
+    let error = report.clone().fail_if_changed_fraction(0.5).unwrap_err();
+    let RpcError::FixturesDrifted {
+        report: failed,
+        max_fraction,
+    } = &error
+    else {
+        panic!("expected FixturesDrifted, got {error}");
+    };
+    assert_eq!((&**failed, *max_fraction), (&report, 0.5));
+
+    // An empty fixture hasn't changed, and isn't fetched
+    let empty = temp_dir("drift-check");
+    let fetcher = MockFetcher::new();
+    let report = store(&fetcher).drift_check(&empty).await.unwrap();
+    assert!(report.accounts.is_empty());
+    assert_eq!(report.changed_fraction(), 0.0);
+    report.fail_if_changed_fraction(0.0).unwrap();
+    assert!(fetcher.calls().is_empty());
+
+    let missing = empty.join("missing.jsonl");
+    let error = store(&fetcher).drift_check(&missing).await.unwrap_err();
+    let RpcError::Fixture { path, .. } = &error else {
+        panic!("expected Fixture, got {error}");
+    };
+    assert_eq!(path, &missing);
+}
+
+#[tokio::test]
+async fn the_report_prints_and_serializes() {
+    let dir = fixture_dir();
+    let report = check(&dir).await;
+    assert_eq!(
+        report.to_string(),
+        [
+            format!(
+                "{}: 4 of 7 accounts changed (2 data changed, 1 missing, 1 program upgraded, \
+                 2 lamports only)",
+                dir.display()
+            ),
+            format!("  {}: data changed, 5 bytes", key(WRITTEN)),
+            format!("  {}: data changed", key(REASSIGNED)),
+            format!("  {}: missing", key(CLOSED)),
+            format!(
+                "  {}: program upgraded, deployed at slot 100 -> 200",
+                key(UPGRADED)
+            ),
+        ]
+        .join("\n")
+    );
+
+    let json: serde_json::Value = serde_json::from_str(&report.to_json()).unwrap();
+    assert_eq!(json["fixture"], json!(dir));
+    assert_eq!(json["accounts"].as_array().unwrap().len(), 7);
+    assert_eq!(
+        json["accounts"][1],
+        json!({
+            "pubkey": key(PAID).to_string(),
+            "category": "lamports_only",
+            "lamports": [LAMPORTS, LAMPORTS - 5_000],
+            "changed_bytes": 0,
+            "deploy_slots": null,
+        })
+    );
+    assert_eq!(
+        json["accounts"][5],
+        json!({
+            "pubkey": key(UPGRADED).to_string(),
+            "category": "program_upgraded",
+            "lamports": null,
+            "changed_bytes": 65,
+            "deploy_slots": [100, 200],
+        })
+    );
+    assert_eq!(json["accounts"][4]["category"], "missing");
+}
@@ -0,0 +1,209 @@
+//! `edit_account_as`: a cached account decoded as a Borsh type, edited and written
+//! back at the same size, shrunk with or without its tail padded, behind an Anchor
+//! discriminator, and refused when the edit would grow it unless growth is allowed.
+
+#![cfg(feature = "borsh")]
+
+mod common;
+
+use {
+    borsh::{BorshDeserialize, BorshSerialize},
+    common::store,
+    mollusk_on_demand::{testing::MockFetcher, EditOptions, Provenance, RpcAccountStore, RpcError},
+    solana_account::Account,
+    solana_pubkey::Pubkey,
+    solana_rent::Rent,
+};
+
+const MARKET: Pubkey = Pubkey::new_from_array([1; 32]);
+const ME: Pubkey = Pubkey::new_from_array([2; 32]);
+const PROGRAM: Pubkey = Pubkey::new_from_array([9; 32]);
+const PAUSED: u8 = 1 << 2;
+const DISCRIMINATOR: [u8; 8] = [7, 6, 5, 4, 3, 2, 1, 0];
+
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq)]
+struct LendingMarket {
+    owner: Pubkey,
+    flags: u8,
+    name: String,
+}
+
+fn market(name: &str) -> LendingMarket {
+    LendingMarket {
+        owner: PROGRAM,
+        flags: 1,
+        name: name.to_string(),
+    }
+}
+
+/// A store caching `market` after `prefix`, with `padding` zero bytes of unused
+/// space after it.
+fn store_with(prefix: &[u8], market: &LendingMarket, padding: usize) -> RpcAccountStore {
+    let mut data = prefix.to_vec();
+    data.extend(borsh::to_vec(market).unwrap());
+    data.resize(data.len() + padding, 0);
+    let account = Account {
+        lamports: Rent::default().minimum_balance(data.len()),
+        data,
+        owner: PROGRAM,
+        executable: false,
+        rent_epoch: 0,
+    };
+    store(&MockFetcher::new()).with_accounts([(MARKET, account)])
+}
+
+fn decoded(store: &RpcAccountStore, offset: usize) -> LendingMarket {
+    LendingMarket::deserialize(&mut &store.cache[&MARKET].data[offset..]).unwrap()
+}
+
+#[test]
+fn an_edit_of_the_same_size_is_written_back() {
+    let mut store = store_with(&[], &market("main"), 0);
+    let before = store.cache[&MARKET].clone();
+    store
+        .edit_account_as::<LendingMarket>(&MARKET, |market| {
+            market.owner = ME;
+            market.flags |= PAUSED;
+        })
+        .unwrap();
+
+    let after = &store.cache[&MARKET];
+    assert_eq!(after.data.len(), before.data.len());
+    assert_eq!(after.lamports, before.lamports);
+    assert_eq!(
+        decoded(&store, 0),
+        LendingMarket {
+            owner: ME,
+            flags: 1 | PAUSED,
+            name: "main".to_string(),
+        }
+    );
+    assert_eq!(
+        store.provenance(&MARKET),
+        Some(&Provenance::Patched("edit_account_as"))
+    );
+}
+
+#[test]
+fn a_shorter_value_is_padded_to_the_accounts_size() {
+    let original = market("orca-whirlpool");
+    let mut store = store_with(&[], &original, 16);
+    let size = store.cache[&MARKET].data.len();
+    let padded = EditOptions {
+        pad_shorter: true,
+        ..EditOptions::default()
+    };
+    store
+        .edit_account_as_with::<LendingMarket>(&MARKET, padded, |market| {
+            market.name = "orca".to_string();
+        })
+        .unwrap();
+
+    let data = &store.cache[&MARKET].data;
+    let written = borsh::to_vec(&market("orca")).unwrap();
+    assert_eq!(data.len(), size);
+    assert_eq!(data[..written.len()], written);
+    assert!(data[written.len()..].iter().all(|byte| *byte == 0));
+    assert_eq!(decoded(&store, 0), market("orca"));
+
+    // Without padding, the data shrinks to the value
+    let mut store = store_with(&[], &original, 16);
+    store
+        .edit_account_as::<LendingMarket>(&MARKET, |market| market.name = "orca".to_string())
+        .unwrap();
+    assert_eq!(store.cache[&MARKET].data, written);
+}
+
+#[test]
+fn an_anchor_discriminator_is_kept() {
+    let mut store = store_with(&DISCRIMINATOR, &market("main"), 32);
+    let size = store.cache[&MARKET].data.len();
+    store
+        .edit_account_as_with::<LendingMarket>(&MARKET, EditOptions::anchor(), |market| {
+            market.flags |= PAUSED;
+        })
+        .unwrap();
+
+    let data = &store.cache[&MARKET].data;
+    assert_eq!(
+        (data.len(), data[..8].to_vec()),
+        (size, DISCRIMINATOR.to_vec())
+    );
+    assert_eq!(decoded(&store, 8).flags, 1 | PAUSED);
+}
+
+#[test]
+fn growth_is_an_error_unless_allowed() {
+    let mut store = store_with(&[], &market("main"), 2);
+    let before = store.cache[&MARKET].clone();
+    let error = store
+        .edit_account_as::<LendingMarket>(&MARKET, |market| {
+            market.name = "main-market".to_string();
+        })
+        .unwrap_err();
+    let RpcError::AccountEdit { pubkey, reason } = &error else {
+        panic!("expected AccountEdit, got {error}");
+    };
+    assert_eq!(*pubkey, MARKET);
+    assert_eq!(
+        reason,
+        "the edited value takes 48 bytes, more than the 43 the account holds; set \
+         allow_growth to resize it"
+    );
+    assert_eq!(store.cache[&MARKET], before);
+    assert_eq!(store.provenance(&MARKET), Some(&Provenance::Mocked));
+
+    let growing = EditOptions {
+        allow_growth: true,
+        ..EditOptions::default()
+    };
+    store
+        .edit_account_as_with::<LendingMarket>(&MARKET, growing, |market| {
+            market.name = "main-market".to_string();
+        })
+        .unwrap();
+    let after = &store.cache[&MARKET];
+    assert_eq!(after.data.len(), 48);
+    assert_eq!(after.lamports, Rent::default().minimum_balance(48));
+    assert_eq!(decoded(&store, 0), market("main-market"));
+}
+
+#[test]
+fn accounts_that_dont_decode_are_errors() {
+    let mut store = store_with(&[], &market("main"), 0).with_accounts([(
+        ME,
+        Account {
+            data: vec![1, 2, 3],
+            ..Account::default()
+        },
+    )]);
+    for (options, expected) in [
+        (
+            EditOptions::default(),
+            format!(
+                "doesn't decode as {}: ",
+                std::any::type_name::<LendingMarket>()
+            ),
+        ),
+        (
+            EditOptions::anchor(),
+            "3 bytes of data, too short for a discriminator".to_string(),
+        ),
+    ] {
+        let error = store
+            .edit_account_as_with::<LendingMarket>(&ME, options, |_| {})
+            .unwrap_err();
+        let RpcError::AccountEdit { reason, .. } = &error else {
+            panic!("expected AccountEdit, got {error}");
+        };
+        assert!(reason.starts_with(&expected), "{reason}");
+    }
+
+    let error = store
+        .edit_account_as::<LendingMarket>(&PROGRAM, |_| {})
+        .unwrap_err();
+    let RpcError::AccountNotFound(pubkey) = &error else {
+        panic!("expected AccountNotFound, got {error}");
+    };
+    assert_eq!(*pubkey, PROGRAM);
+}
@@ -0,0 +1,111 @@
+//! Program ELFs are copied out of their program data accounts once, while loading,
+//! and every later consumer shares that copy instead of allocating its own.
+//!
+//! The allocator counts ELF-sized allocations, so this file holds a single test.
+
+mod common;
+
+use {
+    common::{account, temp_dir},
+    mollusk_on_demand::{testing::MockFetcher, RpcAccountStore},
+    mollusk_svm::Mollusk,
+    solana_account::Account,
+    solana_instruction::Instruction,
+    solana_pubkey::Pubkey,
+    solana_rent::Rent,
+    solana_sdk_ids::bpf_loader_upgradeable,
+    std::{
+        alloc::{GlobalAlloc, Layout, System},
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        },
+    },
+};
+
+const ELF: &[u8] = mollusk_svm_programs_token::token2022::ELF;
+
+/// Counts the allocations at least as large as [`ELF`].
+struct CountingAllocator;
+
+static ELF_SIZED_ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        if layout.size() >= ELF.len() {
+            ELF_SIZED_ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        }
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { System.dealloc(ptr, layout) }
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        if new_size >= ELF.len() {
+            ELF_SIZED_ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        }
+        unsafe { System.realloc(ptr, layout, new_size) }
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+/// A BPF Loader v3 program and its program data account holding [`ELF`].
+fn program() -> [(Pubkey, Account); 2] {
+    let (program, program_data) = (Pubkey::new_unique(), Pubkey::new_unique());
+    let mut data = 2u32.to_le_bytes().to_vec();
+    data.extend_from_slice(program_data.as_ref());
+    let program_account = Account {
+        lamports: Rent::default().minimum_balance(data.len()),
+        data,
+        executable: true,
+        owner: bpf_loader_upgradeable::ID,
+        ..account(0, 0)
+    };
+
+    // The ProgramData header: tag, deployment slot and upgrade authority
+    let mut data = 3u32.to_le_bytes().to_vec();
+    data.extend_from_slice(&0u64.to_le_bytes());
+    data.extend_from_slice(&[0; 33]);
+    data.extend_from_slice(ELF);
+    let program_data_account = Account {
+        lamports: Rent::default().minimum_balance(data.len()),
+        data,
+        owner: bpf_loader_upgradeable::ID,
+        ..account(0, 0)
+    };
+    [(program, program_account), (program_data, program_data_account)]
+}
+
+#[tokio::test]
+async fn exports_and_lookups_share_the_elfs_copied_while_loading() {
+    let accounts: Vec<_> = (0..3).flat_map(|_| program()).collect();
+    let programs: Vec<Pubkey> = accounts.iter().step_by(2).map(|(pubkey, _)| *pubkey).collect();
+    let mut mollusk = Mollusk::default();
+    let store = RpcAccountStore::new_with_fetcher(MockFetcher::new())
+        .with_accounts(accounts)
+        .add_programs(&mut mollusk)
+        .await
+        .unwrap();
+    let dir = temp_dir("elf_cache");
+
+    // Loading copied each ELF at least once
+    let before = ELF_SIZED_ALLOCATIONS.load(Ordering::Relaxed);
+    assert!(before >= programs.len());
+    for program in &programs {
+        let elf = store.program_elf(program).unwrap();
+        assert_eq!(&elf[..], ELF);
+        assert!(Arc::ptr_eq(&elf, &store.program_elf(program).unwrap()));
+    }
+    store.export_validator_args(dir.join("validator")).unwrap();
+    for program in &programs {
+        let instruction = Instruction::new_with_bytes(*program, &[], vec![]);
+        store
+            .export_bench(&mollusk, &instruction, &program.to_string(), dir.join("bench"))
+            .unwrap();
+    }
+    assert_eq!(ELF_SIZED_ALLOCATIONS.load(Ordering::Relaxed), before);
+}
@@ -0,0 +1,153 @@
+//! ELF validation modes for a program whose ELF fails validation: `Strict` refuses
+//! it, `Warn` reports it through the warnings channel with the program attached
+//! before handing it to Mollusk, and `Off` hands it over unchecked. Per-program modes
+//! override the store's.
+//!
+//! Mollusk itself panics on an ELF this broken, so the modes that load it run
+//! under `catch_unwind`.
+
+mod common;
+
+use {
+    common::{account, store},
+    mollusk_on_demand::{
+        testing::MockFetcher, RpcAccountStore, RpcError, ValidationMode, Warning, WarningCode,
+    },
+    mollusk_svm::Mollusk,
+    solana_account::Account,
+    solana_pubkey::Pubkey,
+    solana_sdk_ids::bpf_loader_upgradeable,
+    std::{
+        panic::{catch_unwind, AssertUnwindSafe},
+        sync::{Arc, Mutex},
+    },
+};
+
+/// A program whose ELF has an invalid class byte, and its program data.
+fn invalid_program() -> (Pubkey, [(Pubkey, Account); 2]) {
+    let (program, program_data) = (Pubkey::new_unique(), Pubkey::new_unique());
+    let mut data = 2u32.to_le_bytes().to_vec();
+    data.extend_from_slice(program_data.as_ref());
+    let program_account = Account {
+        data,
+        executable: true,
+        owner: bpf_loader_upgradeable::ID,
+        ..account(1_000_000_000, 0)
+    };
+
+    let mut elf = mollusk_svm_programs_token::token2022::ELF.to_vec();
+    elf[4] = 3;
+    // The ProgramData header: tag, deployment slot and upgrade authority
+    let mut data = 3u32.to_le_bytes().to_vec();
+    data.extend_from_slice(&0u64.to_le_bytes());
+    data.extend_from_slice(&[0; 33]);
+    data.extend_from_slice(&elf);
+    let program_data_account = Account {
+        data,
+        owner: bpf_loader_upgradeable::ID,
+        ..account(1_000_000_000, 0)
+    };
+    (program, [(program, program_account), (program_data, program_data_account)])
+}
+
+/// Run `add_programs` on `store`, returning the warnings it reported and Mollusk's
+/// panic message, if it panicked.
+fn add_programs(store: RpcAccountStore) -> (Vec<Warning>, Option<String>) {
+    let reported = Arc::new(Mutex::new(Vec::new()));
+    let recorder = reported.clone();
+    let store = store.on_warning(move |warning| recorder.lock().unwrap().push(warning.clone()));
+    let runtime = tokio::runtime::Builder::new_current_thread().build().unwrap();
+
+    let outcome = catch_unwind(AssertUnwindSafe(|| {
+        runtime.block_on(store.add_programs(&mut Mollusk::default())).map(|_| ())
+    }));
+    let panic = match outcome {
+        Ok(result) => {
+            result.unwrap();
+            None
+        }
+        Err(payload) => Some(payload.downcast_ref::<String>().cloned().unwrap_or_default()),
+    };
+    let reported = reported.lock().unwrap().clone();
+    (reported, panic)
+}
+
+#[tokio::test]
+async fn strict_validation_refuses_the_program() {
+    let (program, accounts) = invalid_program();
+    let mut mollusk = Mollusk::default();
+
+    let error = store(&MockFetcher::new())
+        .with_accounts(accounts)
+        .elf_validation(ValidationMode::Strict)
+        .add_programs(&mut mollusk)
+        .await
+        .map(|_| ())
+        .unwrap_err();
+    let RpcError::InvalidProgramData {
+        program: refused,
+        reason,
+    } = &error
+    else {
+        panic!("expected InvalidProgramData, got {error}");
+    };
+    assert_eq!(*refused, program);
+    assert!(reason.contains("Invalid ELF class: 3"), "{reason}");
+    assert!(mollusk.program_cache.load_program(&program).is_none());
+}
+
+#[test]
+fn warn_reports_the_program_before_loading_it() {
+    let (program, accounts) = invalid_program();
+    let store = store(&MockFetcher::new())
+        .with_accounts(accounts)
+        .elf_validation(ValidationMode::Warn);
+
+    let (warnings, panic) = add_programs(store);
+    assert_eq!(warnings.len(), 1, "{warnings:?}");
+    assert_eq!(warnings[0].code, WarningCode::InvalidElfLoaded);
+    assert_eq!(warnings[0].pubkey, Some(program));
+    assert!(warnings[0].message.contains("Invalid ELF class: 3"), "{}", warnings[0].message);
+    // Handed to Mollusk anyway
+    assert!(panic.unwrap().contains("FailedToParse"));
+}
+
+#[test]
+fn off_hands_the_program_over_unchecked() {
+    let (_, accounts) = invalid_program();
+    let store = store(&MockFetcher::new())
+        .with_accounts(accounts)
+        .elf_validation(ValidationMode::Off);
+
+    let (warnings, panic) = add_programs(store);
+    assert!(warnings.is_empty(), "{warnings:?}");
+    assert!(panic.unwrap().contains("FailedToParse"));
+}
+
+#[tokio::test]
+async fn a_strict_program_overrides_an_off_store() {
+    let (program, accounts) = invalid_program();
+    let error = store(&MockFetcher::new())
+        .with_accounts(accounts)
+        .elf_validation(ValidationMode::Off)
+        .elf_validation_for(program, ValidationMode::Strict)
+        .add_programs(&mut Mollusk::default())
+        .await
+        .map(|_| ())
+        .unwrap_err();
+    let RpcError::InvalidProgramData { program: refused, .. } = error else {
+        panic!("expected InvalidProgramData, got {error}");
+    };
+    assert_eq!(refused, program);
+}
+
+#[test]
+fn a_warn_program_overrides_a_strict_store() {
+    let (program, accounts) = invalid_program();
+    let store = store(&MockFetcher::new())
+        .with_accounts(accounts)
+        .elf_validation_for(program, ValidationMode::Warn);
+    let (warnings, _) = add_programs(store);
+    assert_eq!(warnings.len(), 1, "{warnings:?}");
+    assert_eq!(warnings[0].pubkey, Some(program));
+}
@@ -0,0 +1,311 @@
+//! Seeding other frameworks from the store: `export_accounts` hands every cached
+//! account to a sink with programs last, `export_validator_args` writes the files
+//! and arguments of a `solana-test-validator` reproduction, `into_litesvm` seeds a
+//! bare LiteSVM well enough to run a token transfer through an upgradeable program
+//! cloned with its program data, and `apply_to_program_test` gives a bank the
+//! cloned accounts and rejects ones it can't hold.
+
+mod common;
+
+use {
+    common::{account, store, temp_dir},
+    mollusk_on_demand::{testing::MockFetcher, RpcAccountStore},
+    solana_account::Account,
+    solana_program_pack::Pack,
+    solana_pubkey::Pubkey,
+    solana_rent::Rent,
+    solana_sdk_ids::{bpf_loader, bpf_loader_upgradeable, native_loader, sysvar},
+    spl_token_interface::state::{Account as TokenAccount, AccountState, Mint},
+};
+
+const LAMPORTS: u64 = 1_000_000_000;
+const ELF: &[u8] = mollusk_svm_programs_token::token::ELF;
+const SPL_TOKEN: Pubkey = spl_token_interface::ID;
+
+fn key(byte: u8) -> Pubkey {
+    Pubkey::new_from_array([byte; 32])
+}
+
+const PROGRAM_DATA: u8 = 1;
+const ALICE: u8 = 2;
+const BOB: u8 = 3;
+const MINT: u8 = 4;
+const ALICE_TOKENS: u8 = 5;
+const BOB_TOKENS: u8 = 6;
+
+/// An upgradeable program running the SPL Token ELF at `program`: its program
+/// account and its program data account at `program_data`.
+fn upgradeable_token_program(program: Pubkey, program_data: Pubkey) -> [(Pubkey, Account); 2] {
+    let mut data = 2u32.to_le_bytes().to_vec();
+    data.extend_from_slice(program_data.as_ref());
+    let program_account = Account {
+        data,
+        executable: true,
+        owner: bpf_loader_upgradeable::ID,
+        ..account(LAMPORTS, 0)
+    };
+
+    // The ProgramData header: tag, deployment slot and upgrade authority
+    let mut data = 3u32.to_le_bytes().to_vec();
+    data.extend_from_slice(&0u64.to_le_bytes());
+    data.extend_from_slice(&[0; 33]);
+    data.extend_from_slice(ELF);
+    let program_data_account = Account {
+        data,
+        owner: bpf_loader_upgradeable::ID,
+        ..account(LAMPORTS, 0)
+    };
+    [(program, program_account), (program_data, program_data_account)]
+}
+
+fn token_owned(data: Vec<u8>) -> Account {
+    Account {
+        lamports: Rent::default().minimum_balance(data.len()),
+        owner: SPL_TOKEN,
+        data,
+        executable: false,
+        rent_epoch: 0,
+    }
+}
+
+fn token_account(mint: Pubkey, owner: Pubkey, amount: u64) -> Account {
+    let mut data = vec![0; TokenAccount::LEN];
+    TokenAccount {
+        mint,
+        owner,
+        amount,
+        state: AccountState::Initialized,
+        ..TokenAccount::default()
+    }
+    .pack_into_slice(&mut data);
+    token_owned(data)
+}
+
+/// A store holding the token program, a mint, and token accounts of 1,000 tokens
+/// for [`ALICE`] and none for [`BOB`], at [`ALICE_TOKENS`] and [`BOB_TOKENS`].
+fn token_state() -> RpcAccountStore {
+    let mut mint = vec![0; Mint::LEN];
+    Mint {
+        supply: 1_000,
+        decimals: 6,
+        is_initialized: true,
+        ..Mint::default()
+    }
+    .pack_into_slice(&mut mint);
+    store(&MockFetcher::new())
+        .with_accounts(upgradeable_token_program(SPL_TOKEN, key(PROGRAM_DATA)))
+        .with_accounts([
+            (key(ALICE), account(LAMPORTS, 0)),
+            (key(MINT), token_owned(mint)),
+            (key(ALICE_TOKENS), token_account(key(MINT), key(ALICE), 1_000)),
+            (key(BOB_TOKENS), token_account(key(MINT), key(BOB), 0)),
+        ])
+}
+
+#[test]
+fn export_accounts_hands_over_every_account_with_programs_last() {
+    let store = token_state();
+    let mut exported = Vec::new();
+    store.export_accounts(|pubkey, account| exported.push((pubkey, account)));
+
+    let order: Vec<Pubkey> = exported.iter().map(|(pubkey, _)| *pubkey).collect();
+    let mut expected = [PROGRAM_DATA, ALICE, MINT, ALICE_TOKENS, BOB_TOKENS].map(key).to_vec();
+    expected.sort();
+    expected.push(SPL_TOKEN);
+    assert_eq!(order, expected);
+    for (pubkey, account) in &exported {
+        assert_eq!(account, &store.cache[pubkey], "{pubkey}");
+    }
+}
+
+#[test]
+fn validator_args_load_every_kind_of_account() {
+    let dir = temp_dir("export").join("repro dir");
+    let (program, program_data) = (key(10), key(11));
+    let (v2_program, cloned_program, builtin, local_program) = (key(12), key(13), key(14), key(15));
+    let local_elf = dir.with_file_name("local.so");
+    std::fs::write(&local_elf, ELF).unwrap();
+    let executable = |owner: Pubkey, data: &[u8]| Account {
+        data: data.to_vec(),
+        executable: true,
+        owner,
+        ..account(LAMPORTS, 0)
+    };
+
+    let store = token_state()
+        .with_accounts(upgradeable_token_program(program, program_data))
+        .with_accounts([
+            (key(7), account(0, 0)),
+            (v2_program, executable(bpf_loader::ID, ELF)),
+            (cloned_program, executable(key(20), &[])),
+            (builtin, executable(native_loader::ID, b"builtin")),
+            (
+                key(16),
+                Account {
+                    owner: sysvar::ID,
+                    ..account(LAMPORTS, 8)
+                },
+            ),
+        ])
+        .with_program_from_file(local_program, &local_elf);
+    let args = store.export_validator_args(&dir).unwrap();
+
+    let path = |name: String| format!("'{}'", dir.join(name).display());
+    let account_arg =
+        |pubkey: Pubkey| format!("--account {pubkey} {}", path(format!("{pubkey}.json")));
+    let program_arg =
+        |pubkey: Pubkey| format!("--bpf-program {pubkey} {}", path(format!("{pubkey}.so")));
+    let mut expected: Vec<String> =
+        [ALICE, MINT, ALICE_TOKENS, BOB_TOKENS].map(|byte| account_arg(key(byte))).into();
+    // In pubkey order: SPL Token, `[6, 221, ..]`, comes before `key(10)`
+    expected.extend([
+        program_arg(SPL_TOKEN),
+        program_arg(program),
+        program_arg(v2_program),
+        format!("--clone {cloned_program}"),
+        program_arg(local_program),
+    ]);
+    assert_eq!(args, expected.join(" \\\n"));
+    for program in [program, v2_program, local_program, SPL_TOKEN] {
+        assert_eq!(std::fs::read(dir.join(format!("{program}.so"))).unwrap(), ELF);
+    }
+
+    // The account files are what `solana-test-validator --account` reads
+    let file = dir.join(format!("{}.json", key(ALICE_TOKENS)));
+    let json: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(&file).unwrap()).unwrap();
+    assert_eq!(json["pubkey"], key(ALICE_TOKENS).to_string());
+    assert_eq!(json["account"]["data"][1], "base64");
+    assert_eq!(json["account"]["space"], TokenAccount::LEN);
+    let replayed = common::store(&MockFetcher::new()).with_account_file(&file).unwrap();
+    assert_eq!(replayed.cache[&key(ALICE_TOKENS)], store.cache[&key(ALICE_TOKENS)]);
+}
+
+#[cfg(feature = "litesvm")]
+mod litesvm {
+    use {
+        super::*,
+        agave_feature_set::FeatureSet,
+        ::litesvm::LiteSVM,
+        mollusk_on_demand::RpcError,
+        solana_message::Message,
+        solana_transaction::Transaction,
+        spl_token_interface::instruction,
+    };
+
+    /// A LiteSVM with builtins and sysvars but none of its bundled programs, so the
+    /// token program can only come from the store.
+    fn bare_svm() -> LiteSVM {
+        LiteSVM::default()
+            .with_feature_set(FeatureSet::all_enabled())
+            .with_builtins()
+            .with_sysvars()
+    }
+
+    fn balance(svm: &LiteSVM, pubkey: &Pubkey) -> u64 {
+        TokenAccount::unpack(&svm.get_account(pubkey).unwrap().data).unwrap().amount
+    }
+
+    #[test]
+    fn a_token_transfer_runs_in_a_litesvm_seeded_from_the_store() {
+        let store = token_state();
+        let mut svm = bare_svm();
+        assert!(svm.get_account(&SPL_TOKEN).is_none());
+        store.into_litesvm(&mut svm).unwrap();
+        for (pubkey, account) in &store.cache {
+            assert_eq!(svm.get_account(pubkey).as_ref(), Some(account), "{pubkey}");
+        }
+
+        let transfer = instruction::transfer(
+            &SPL_TOKEN,
+            &key(ALICE_TOKENS),
+            &key(BOB_TOKENS),
+            &key(ALICE),
+            &[],
+            400,
+        )
+        .unwrap();
+        let message = Message::new_with_blockhash(
+            &[transfer],
+            Some(&key(ALICE)),
+            &svm.latest_blockhash(),
+        );
+        let result = svm.send_transaction(Transaction::new_unsigned(message));
+        assert!(result.is_ok(), "{result:?}");
+        assert_eq!(balance(&svm, &key(ALICE_TOKENS)), 600);
+        assert_eq!(balance(&svm, &key(BOB_TOKENS)), 400);
+    }
+
+    #[test]
+    fn a_program_needs_its_program_data() {
+        let [program, _] = upgradeable_token_program(SPL_TOKEN, key(PROGRAM_DATA));
+        let store = store(&MockFetcher::new()).with_accounts([program]);
+        let error = store.into_litesvm(&mut bare_svm()).unwrap_err();
+        let RpcError::InvalidProgramData { .. } = &error else {
+            panic!("expected InvalidProgramData, got {error}");
+        };
+    }
+}
+
+#[cfg(feature = "program-test")]
+mod program_test {
+    use {
+        super::*,
+        mollusk_on_demand::RpcError,
+        solana_program_test::ProgramTest,
+    };
+
+    #[tokio::test]
+    async fn a_bank_starts_with_the_cloned_accounts() {
+        let closed = Pubkey::new_unique();
+        let store = store(&MockFetcher::new()).with_accounts([
+            (key(ALICE_TOKENS), token_account(key(MINT), key(ALICE), 1_000)),
+            (closed, account(0, 0)),
+        ]);
+        let mut program_test = ProgramTest::default();
+        store.apply_to_program_test(&mut program_test).unwrap();
+
+        let (banks_client, _, _) = program_test.start().await;
+        let cloned = banks_client.get_account(key(ALICE_TOKENS)).await.unwrap().unwrap();
+        let cached = &store.cache[&key(ALICE_TOKENS)];
+        assert_eq!(
+            (cloned.lamports, &cloned.data, cloned.owner),
+            (cached.lamports, &cached.data, cached.owner)
+        );
+        assert_eq!(TokenAccount::unpack(&cloned.data).unwrap().amount, 1_000);
+        // Accounts without lamports don't exist for the bank
+        assert_eq!(banks_client.get_account(closed).await.unwrap(), None);
+    }
+
+    #[test]
+    fn accounts_the_bank_cant_hold_are_errors() {
+        let too_large = Account {
+            owner: key(9),
+            ..account(LAMPORTS, 10 * 1024 * 1024 + 1)
+        };
+        let store = store(&MockFetcher::new()).with_accounts([(key(ALICE), too_large)]);
+        let error = store.apply_to_program_test(&mut ProgramTest::default()).unwrap_err();
+        let RpcError::Export { target, pubkey, .. } = &error else {
+            panic!("expected Export, got {error}");
+        };
+        assert_eq!((*target, *pubkey), ("ProgramTest", key(ALICE)));
+        assert_eq!(
+            error.to_string(),
+            format!(
+                "[{}] Can't export account {} to ProgramTest: 10485761 bytes of data, more \
+                 than the 10485760 an account can hold",
+                error.code(),
+                key(ALICE),
+            )
+        );
+
+        // Sysvars are the bank's own
+        let oversized_sysvar = Account {
+            owner: sysvar::ID,
+            ..account(LAMPORTS, 10 * 1024 * 1024 + 1)
+        };
+        let store =
+            store.with_accounts([(key(ALICE), account(LAMPORTS, 0)), (key(9), oversized_sysvar)]);
+        store.apply_to_program_test(&mut ProgramTest::default()).unwrap();
+    }
+}
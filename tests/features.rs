@@ -0,0 +1,97 @@
+//! `with_synced_features`: gates with an activation slot on chain are active in
+//! Mollusk, pending and missing ones aren't, overrides win, and the gate accounts
+//! are fetched in chunks the RPC node accepts.
+
+mod common;
+
+use {
+    agave_feature_set::FEATURE_NAMES,
+    common::store,
+    mollusk_on_demand::testing::{FetcherCall, MockFetcher},
+    mollusk_svm::Mollusk,
+    solana_account::Account,
+    solana_pubkey::Pubkey,
+    solana_rent::Rent,
+    solana_sdk_ids::{feature, system_program},
+};
+
+/// A feature account recording `activated_at`, owned by `owner`.
+fn feature_account(activated_at: Option<u64>, owner: Pubkey) -> Account {
+    let data = bincode::serialize(&activated_at).unwrap();
+    Account {
+        lamports: Rent::default().minimum_balance(data.len()),
+        data,
+        owner,
+        executable: false,
+        rent_epoch: 0,
+    }
+}
+
+/// Five gates this version of Agave knows about.
+fn gates() -> [Pubkey; 5] {
+    let mut gates: Vec<Pubkey> = FEATURE_NAMES.keys().copied().collect();
+    gates.sort();
+    gates[..5].try_into().unwrap()
+}
+
+#[tokio::test]
+async fn activated_gates_are_active_with_their_slot() {
+    let [activated, earlier, pending, impostor, missing] = gates();
+    let fetcher = MockFetcher::new()
+        .with_account(activated, feature_account(Some(250_000_000), feature::ID))
+        .with_account(earlier, feature_account(Some(10), feature::ID))
+        .with_account(pending, feature_account(None, feature::ID))
+        .with_account(impostor, feature_account(Some(10), system_program::ID));
+
+    let mut mollusk = Mollusk::default();
+    let store = store(&fetcher).with_synced_features(&mut mollusk).await.unwrap();
+    let features = &mollusk.feature_set;
+    assert_eq!(features.activated_slot(&activated), Some(250_000_000));
+    assert_eq!(features.activated_slot(&earlier), Some(10));
+    for gate in [pending, impostor, missing] {
+        assert!(!features.is_active(&gate), "{gate}");
+    }
+    assert_eq!(features.active().len(), 2);
+    assert_eq!(features.inactive().len(), FEATURE_NAMES.len() - 2);
+    // Gate accounts stay out of the cache
+    assert!(store.cache.is_empty());
+}
+
+#[tokio::test]
+async fn overrides_win_over_the_cluster() {
+    let [activated, forced_on, toggled, ..] = gates();
+    let fetcher = MockFetcher::new()
+        .with_account(activated, feature_account(Some(100), feature::ID))
+        .with_account(toggled, feature_account(Some(100), feature::ID));
+
+    let mut mollusk = Mollusk::default();
+    store(&fetcher)
+        .with_feature_overrides(&[(activated, false), (forced_on, true), (toggled, false)])
+        .with_feature_overrides(&[(toggled, true)])
+        .with_synced_features(&mut mollusk)
+        .await
+        .unwrap();
+    let features = &mollusk.feature_set;
+    assert!(!features.is_active(&activated));
+    assert_eq!(features.activated_slot(&forced_on), Some(0));
+    // The later override of the same gate
+    assert_eq!(features.activated_slot(&toggled), Some(0));
+}
+
+#[tokio::test]
+async fn gates_are_fetched_in_chunks() {
+    let fetcher = MockFetcher::new();
+    store(&fetcher).with_synced_features(&mut Mollusk::default()).await.unwrap();
+
+    let chunks: Vec<usize> = fetcher
+        .calls()
+        .iter()
+        .map(|call| match call {
+            FetcherCall::GetMultipleAccounts(pubkeys) => pubkeys.len(),
+            call => panic!("unexpected {call:?}"),
+        })
+        .collect();
+    assert_eq!(chunks.len(), FEATURE_NAMES.len().div_ceil(100));
+    assert!(chunks.iter().all(|&len| len <= 100), "{chunks:?}");
+    assert_eq!(chunks.iter().sum::<usize>(), FEATURE_NAMES.len());
+}
@@ -0,0 +1,212 @@
+//! `save_fixture` and `load_fixture`: a cache saved to one file, in pubkey order and
+//! a line per account, loads back as it was; a failed save leaves the previous file
+//! in place; and a large cache is written and read without being held twice.
+//!
+//! Memory is measured by this binary's allocator, for the calling thread only, so
+//! tests running alongside don't count.
+
+mod common;
+
+use {
+    common::{account, store, temp_dir},
+    mollusk_on_demand::{testing::MockFetcher, Provenance, RpcAccountStore, RpcError},
+    solana_account::Account,
+    solana_pubkey::Pubkey,
+    std::{
+        alloc::{GlobalAlloc, Layout, System},
+        cell::Cell,
+        fs,
+        path::Path,
+    },
+};
+
+/// The system allocator, tracking the bytes each thread has allocated and the most
+/// it had at once.
+struct Tracking;
+
+thread_local! {
+    static ALLOCATED: Cell<isize> = const { Cell::new(0) };
+    static PEAK: Cell<isize> = const { Cell::new(0) };
+}
+
+fn track(change: isize) {
+    let allocated = ALLOCATED.get() + change;
+    ALLOCATED.set(allocated);
+    PEAK.set(PEAK.get().max(allocated));
+}
+
+unsafe impl GlobalAlloc for Tracking {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        track(layout.size() as isize);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        track(-(layout.size() as isize));
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        track(new_size as isize - layout.size() as isize);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: Tracking = Tracking;
+
+/// What `run` allocated at its peak on this thread beyond what was allocated
+/// before it, and what it left allocated.
+fn measure<T>(run: impl FnOnce() -> T) -> (T, usize, usize) {
+    let before = ALLOCATED.get();
+    PEAK.set(before);
+    let result = run();
+    let (peak, after) = (PEAK.get() - before, ALLOCATED.get() - before);
+    (result, peak.max(0) as usize, after.max(0) as usize)
+}
+
+fn key(byte: u8) -> Pubkey {
+    Pubkey::new_from_array([byte; 32])
+}
+
+/// Accounts of every shape a fixture holds, from no data to an executable.
+fn accounts() -> Vec<(Pubkey, Account)> {
+    vec![
+        (key(3), account(1, 0)),
+        (
+            key(1),
+            Account {
+                data: (0..=255).collect(),
+                owner: key(9),
+                executable: true,
+                rent_epoch: u64::MAX,
+                ..account(u64::MAX, 0)
+            },
+        ),
+        (key(2), account(2_039_280, 165)),
+    ]
+}
+
+fn saved(store: &RpcAccountStore, path: &Path) -> String {
+    store.save_fixture(path).unwrap();
+    fs::read_to_string(path).unwrap()
+}
+
+#[test]
+fn a_saved_cache_loads_back_as_it_was() {
+    let store = store(&MockFetcher::new()).with_accounts(accounts());
+    let path = temp_dir("fixture-file").join("nested/swap.jsonl");
+    let file = saved(&store, &path);
+
+    // One line per account, in pubkey order, and nothing left beside it
+    let lines: Vec<&str> = file.lines().collect();
+    assert_eq!(lines.len(), 3);
+    for (line, pubkey) in lines.iter().zip([key(1), key(2), key(3)]) {
+        assert!(line.contains(&pubkey.to_string()), "{line}");
+    }
+    assert!(!path.with_extension("jsonl.tmp").exists());
+    assert_eq!(saved(&store, &path), file);
+
+    let loaded = RpcAccountStore::new_with_fetcher(MockFetcher::new())
+        .offline()
+        .load_fixture(&path)
+        .unwrap();
+    assert_eq!(loaded.cache, store.cache);
+    assert_eq!(
+        loaded.provenance(&key(1)),
+        Some(&Provenance::Fixture { path: path.clone() })
+    );
+}
+
+#[test]
+fn a_failed_save_leaves_the_previous_file() {
+    let path = temp_dir("fixture-file").join("swap.jsonl");
+    let previous = saved(&store(&MockFetcher::new()).with_accounts(accounts()), &path);
+
+    // The temporary file can't be created where a directory is
+    fs::create_dir(path.with_extension("jsonl.tmp")).unwrap();
+    let error = store(&MockFetcher::new())
+        .with_accounts([(key(4), account(1, 10))])
+        .save_fixture(&path)
+        .unwrap_err();
+    let RpcError::Fixture { path: failed, .. } = &error else {
+        panic!("expected Fixture, got {error}");
+    };
+    assert_eq!(failed, &path);
+    assert_eq!(fs::read_to_string(&path).unwrap(), previous);
+}
+
+#[test]
+fn bad_lines_are_reported_by_number() {
+    let path = temp_dir("fixture-file").join("swap.jsonl");
+    let file = saved(&store(&MockFetcher::new()).with_accounts(accounts()), &path);
+    let mut lines: Vec<&str> = file.lines().collect();
+    // Blank lines are skipped, but still counted
+    lines.insert(1, "");
+    lines.insert(3, "{\"pubkey\": 7}");
+    fs::write(&path, lines.join("\n")).unwrap();
+
+    let error = RpcAccountStore::new_with_fetcher(MockFetcher::new())
+        .load_fixture(&path)
+        .map(|_| ())
+        .unwrap_err();
+    let RpcError::Fixture { reason, .. } = &error else {
+        panic!("expected Fixture, got {error}");
+    };
+    assert!(reason.starts_with("line 4: "), "{reason}");
+
+    let missing = path.with_file_name("missing.jsonl");
+    let error = RpcAccountStore::new_with_fetcher(MockFetcher::new())
+        .load_fixture(&missing)
+        .map(|_| ())
+        .unwrap_err();
+    let RpcError::Fixture { path, .. } = &error else {
+        panic!("expected Fixture, got {error}");
+    };
+    assert_eq!(path, &missing);
+}
+
+#[test]
+fn a_large_cache_is_streamed_both_ways() {
+    const ACCOUNTS: u8 = 48;
+    const LARGEST: usize = 1 << 20;
+    // 48 accounts of 256 KiB to 1 MiB, 30 MiB in all
+    let large = (1..=ACCOUNTS).map(|byte| {
+        let size =
+            LARGEST / 4 + usize::from(byte - 1) * (LARGEST * 3 / 4) / usize::from(ACCOUNTS - 1);
+        let data = (0..size).map(|i| (i % 251) as u8).collect();
+        (
+            key(byte),
+            Account {
+                data,
+                ..account(1, 0)
+            },
+        )
+    });
+    let store = store(&MockFetcher::new()).with_accounts(large);
+    let total: usize = store.cache.values().map(|account| account.data.len()).sum();
+    assert!(total > 24 * LARGEST, "{total}");
+    let path = temp_dir("fixture-file").join("large.jsonl");
+
+    // The largest account base64-encoded in its line, and the writer's buffer
+    let (saved, peak, _) = measure(|| store.save_fixture(&path));
+    saved.unwrap();
+    assert!(
+        peak < 4 * LARGEST,
+        "saving peaked at {peak} bytes for {total}"
+    );
+    assert!(fs::metadata(&path).unwrap().len() > total as u64);
+
+    // The cache itself, plus the line being decoded
+    let (loaded, peak, kept) = measure(|| {
+        RpcAccountStore::new_with_fetcher(MockFetcher::new())
+            .load_fixture(&path)
+            .unwrap()
+    });
+    assert!(kept >= total);
+    assert!(
+        peak < kept + 4 * LARGEST,
+        "loading peaked at {peak} bytes to keep {kept}"
+    );
+    assert_eq!(loaded.cache, store.cache);
+}
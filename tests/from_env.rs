@@ -0,0 +1,134 @@
+//! `from_env` and `RpcStoreConfig::with_env_overrides`: each `MOLLUSK_*` variable
+//! sets its field, unset or empty ones keep the defaults, invalid values are
+//! errors naming the variable, and builder methods take precedence.
+
+use {
+    mollusk_on_demand::{RpcAccountStore, RpcError, RpcStoreConfig, MAINNET_RPC_URL},
+    solana_commitment_config::{CommitmentConfig, CommitmentLevel},
+    solana_pubkey::Pubkey,
+    std::sync::{Mutex, MutexGuard},
+};
+
+const VARIABLES: [&str; 7] = [
+    "MOLLUSK_RPC_URL",
+    "MOLLUSK_RPC_COMMITMENT",
+    "MOLLUSK_FIXTURE_DIR",
+    "MOLLUSK_OFFLINE",
+    "MOLLUSK_RATE_LIMIT",
+    "MOLLUSK_RPC_BUDGET",
+    "MOLLUSK_CONCURRENCY",
+];
+
+/// The environment is process-wide; tests holding this run one at a time.
+static ENVIRONMENT: Mutex<()> = Mutex::new(());
+
+/// Lock the environment with exactly `vars` set.
+fn set_env(vars: &[(&str, &str)]) -> MutexGuard<'static, ()> {
+    let guard = ENVIRONMENT.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    for name in VARIABLES {
+        std::env::remove_var(name);
+    }
+    for (name, value) in vars {
+        std::env::set_var(name, value);
+    }
+    guard
+}
+
+fn invalid(vars: &[(&str, &str)]) -> RpcError {
+    let _env = set_env(vars);
+    RpcAccountStore::from_env().map(|_| ()).unwrap_err()
+}
+
+#[test]
+fn unset_variables_keep_the_defaults() {
+    let _env = set_env(&[]);
+    let store = RpcAccountStore::from_env().unwrap();
+    assert_eq!(store.rpc_url(), Some(MAINNET_RPC_URL));
+    assert_eq!(store.commitment(), Some(CommitmentConfig::confirmed()));
+    assert_eq!(
+        RpcStoreConfig::default().with_env_overrides().unwrap(),
+        RpcStoreConfig::default()
+    );
+}
+
+#[test]
+fn empty_variables_are_ignored() {
+    let _env = set_env(&VARIABLES.map(|name| (name, "")));
+    assert_eq!(
+        RpcStoreConfig::default().with_env_overrides().unwrap(),
+        RpcStoreConfig::default()
+    );
+}
+
+#[test]
+fn every_variable_sets_its_field() {
+    let _env = set_env(&[
+        ("MOLLUSK_RPC_URL", "http://localhost:8899"),
+        ("MOLLUSK_RPC_COMMITMENT", "finalized"),
+        ("MOLLUSK_FIXTURE_DIR", "fixtures/mainnet"),
+        ("MOLLUSK_OFFLINE", "yes"),
+        ("MOLLUSK_RATE_LIMIT", "10"),
+        ("MOLLUSK_RPC_BUDGET", "500"),
+        ("MOLLUSK_CONCURRENCY", "4"),
+    ]);
+    let config = RpcStoreConfig::default().with_env_overrides().unwrap();
+    assert_eq!(
+        config,
+        RpcStoreConfig {
+            rpc_url: "http://localhost:8899".to_string(),
+            commitment: CommitmentLevel::Finalized,
+            fixture_dir: Some("fixtures/mainnet".into()),
+            offline: true,
+            rate_limit: Some(10),
+            rpc_budget: Some(500),
+            concurrency: 4,
+            ..RpcStoreConfig::default()
+        }
+    );
+
+    let store = RpcAccountStore::from_env().unwrap();
+    assert_eq!(store.rpc_url(), Some("http://localhost:8899"));
+    assert_eq!(store.commitment(), Some(CommitmentConfig::finalized()));
+}
+
+#[test]
+fn invalid_values_name_the_variable() {
+    for (name, value) in [
+        ("MOLLUSK_RPC_URL", "not a url"),
+        ("MOLLUSK_RPC_COMMITMENT", "max"),
+        ("MOLLUSK_OFFLINE", "maybe"),
+        ("MOLLUSK_RATE_LIMIT", "fast"),
+        ("MOLLUSK_RPC_BUDGET", "-1"),
+        ("MOLLUSK_CONCURRENCY", "4.5"),
+    ] {
+        let error = invalid(&[(name, value)]);
+        let RpcError::InvalidEnvVar { name: rejected, .. } = &error else {
+            panic!("expected InvalidEnvVar for {name}, got {error}");
+        };
+        assert_eq!(*rejected, name);
+        assert_eq!(error.code(), "invalid_env_var");
+        assert!(error.to_string().contains(name), "{error}");
+    }
+}
+
+#[tokio::test]
+async fn offline_stores_fail_without_a_request() {
+    let mut store = {
+        let _env = set_env(&[("MOLLUSK_RPC_URL", "http://127.0.0.1:1"), ("MOLLUSK_OFFLINE", "1")]);
+        RpcAccountStore::from_env().unwrap()
+    };
+    let error = store.get_or_fetch(&Pubkey::new_unique()).await.unwrap_err();
+    assert_eq!(error.code(), "offline", "{error}");
+    assert_eq!(store.stats().total_requests(), 0);
+}
+
+#[tokio::test]
+async fn builder_methods_take_precedence() {
+    let mut store = {
+        let _env = set_env(&[("MOLLUSK_RPC_URL", "http://127.0.0.1:1"), ("MOLLUSK_OFFLINE", "no")]);
+        RpcAccountStore::from_env().unwrap()
+    }
+    .offline();
+    let error = store.get_or_fetch(&Pubkey::new_unique()).await.unwrap_err();
+    assert_eq!(error.code(), "offline", "{error}");
+}
@@ -0,0 +1,61 @@
+//! Frozen stores: the read APIs keep working, and the changes a test needs go into
+//! an unfrozen copy without touching the baseline.
+
+mod common;
+
+use {
+    common::{account, instruction, pubkeys, serving, store},
+    mollusk_on_demand::FrozenAccountStore,
+    mollusk_svm::Mollusk,
+    solana_pubkey::Pubkey,
+};
+
+/// A frozen store that fetched `count` accounts.
+async fn baseline(count: usize) -> (FrozenAccountStore, Vec<Pubkey>) {
+    let keys = pubkeys(count);
+    let mut store = store(&serving(&keys));
+    store.get_or_fetch_many(&keys).await.unwrap();
+    (store.freeze(), keys)
+}
+
+#[tokio::test]
+async fn the_read_apis_work_on_a_frozen_store() {
+    let (baseline, keys) = baseline(2).await;
+    let accounts = baseline
+        .instruction_accounts(&Mollusk::default(), &instruction(&keys))
+        .unwrap();
+    assert_eq!(accounts.len(), 2);
+    assert_eq!(accounts[0].1, baseline.cache()[&keys[0]]);
+    assert_eq!((&baseline).into_iter().count(), 2);
+    assert_eq!(baseline.used_accounts().len(), 2);
+    baseline.require_accounts(&keys).unwrap();
+    assert!(baseline.stats().total_requests() > 0);
+}
+
+#[tokio::test]
+async fn changes_go_into_a_snapshot_and_leave_the_baseline_alone() {
+    let (baseline, keys) = baseline(2).await;
+    let requests = baseline.stats().total_requests();
+
+    let mut scenario = baseline.snapshot().with_accounts([(keys[0], account(100, 0))]);
+    scenario.reset_stats();
+    scenario.get_or_fetch(&Pubkey::new_unique()).await.unwrap_err();
+
+    assert_eq!(scenario.cache[&keys[0]].lamports, 100);
+    assert_eq!(baseline.cache()[&keys[0]].lamports, 1);
+    assert_eq!(baseline.cache().len(), 2);
+    assert_eq!(baseline.stats().total_requests(), requests);
+}
+
+#[tokio::test]
+async fn thawing_a_shared_store_copies_it() {
+    let (baseline, keys) = baseline(1).await;
+    let clone = baseline.clone();
+
+    let thawed = clone.thaw().with_accounts([(keys[0], account(100, 0))]);
+    assert_eq!(thawed.cache[&keys[0]].lamports, 100);
+    assert_eq!(baseline.cache()[&keys[0]].lamports, 1);
+
+    let thawed = baseline.thaw();
+    assert_eq!(thawed.cache[&keys[0]].lamports, 1);
+}
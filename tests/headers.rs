@@ -0,0 +1,96 @@
+//! Custom HTTP headers: they're sent with every request the store makes, invalid
+//! ones are rejected, and their values never show up in output.
+
+mod common;
+
+use {
+    common::{serve_http, HttpResponse},
+    mollusk_on_demand::{RpcAccountStore, RpcError},
+    mollusk_svm::Mollusk,
+    serde_json::json,
+    solana_pubkey::Pubkey,
+    std::sync::{Arc, Mutex},
+};
+
+const TOKEN: &str = "Bearer s3cr3t-t0k3n";
+
+/// Each request's method with its `authorization` and `x-session` headers.
+type Received = Arc<Mutex<Vec<(String, Option<String>, Option<String>)>>>;
+
+/// An endpoint answering the methods the test uses, and what it received.
+fn recording_endpoint() -> (String, Received) {
+    let received = Arc::new(Mutex::new(Vec::new()));
+    let log = received.clone();
+    let url = serve_http(move |request| {
+        let method = request.body["method"].as_str().unwrap().to_string();
+        let result = match method.as_str() {
+            "getSlot" => json!(100),
+            "getBlockTime" => json!(1_700_000_000),
+            "getMultipleAccounts" => {
+                let count = request.body["params"][0].as_array().unwrap().len();
+                json!({"context": {"slot": 100}, "value": vec![serde_json::Value::Null; count]})
+            }
+            "getAccountInfo" => json!({"context": {"slot": 100}, "value": null}),
+            method => panic!("unexpected method {method}"),
+        };
+        log.lock().unwrap().push((
+            method,
+            request.header("authorization").map(str::to_string),
+            request.header("x-session").map(str::to_string),
+        ));
+        HttpResponse {
+            status: 200,
+            headers: Vec::new(),
+            body: json!({"jsonrpc": "2.0", "id": 1, "result": result}).to_string(),
+        }
+    });
+    (url, received)
+}
+
+#[tokio::test]
+async fn headers_reach_every_request() {
+    let (url, received) = recording_endpoint();
+    let mut mollusk = Mollusk::default();
+    let mut store = RpcAccountStore::new(url)
+        .allow_missing_accounts()
+        .with_header("Authorization", TOKEN)
+        .unwrap()
+        .with_headers([("x-session", "sticky-1")])
+        .unwrap()
+        .with_synced_slot(&mut mollusk)
+        .await
+        .unwrap()
+        .with_synced_clock(&mut mollusk)
+        .await
+        .unwrap();
+    store.get_or_fetch(&Pubkey::new_unique()).await.unwrap();
+    store.get_or_fetch_many(&[Pubkey::new_unique(), Pubkey::new_unique()]).await.unwrap();
+
+    let received = received.lock().unwrap();
+    let methods: std::collections::BTreeSet<&str> =
+        received.iter().map(|(method, ..)| method.as_str()).collect();
+    assert!(methods.contains("getSlot") && methods.contains("getBlockTime"), "{methods:?}");
+    assert!(methods.len() >= 3, "{methods:?}");
+    for (method, authorization, session) in received.iter() {
+        assert_eq!(authorization.as_deref(), Some(TOKEN), "{method}");
+        assert_eq!(session.as_deref(), Some("sticky-1"), "{method}");
+    }
+    assert!(!format!("{store:?}").contains("s3cr3t"));
+}
+
+#[test]
+fn invalid_headers_are_errors() {
+    for (name, value) in [("bad header", "value"), ("x-session", "line\nbreak s3cr3t")] {
+        let error = RpcAccountStore::new("http://localhost:8899")
+            .with_header(name, value)
+            .map(|_| ())
+            .unwrap_err();
+        let RpcError::InvalidHeader { name: rejected, .. } = &error else {
+            panic!("expected InvalidHeader, got {error}");
+        };
+        assert_eq!(rejected, name);
+        assert_eq!(error.code(), "invalid_header");
+        assert!(!error.to_string().contains("s3cr3t"), "{error}");
+        assert!(!format!("{error:?}").contains("s3cr3t"), "{error:?}");
+    }
+}
@@ -0,0 +1,88 @@
+//! `instruction_accounts` and `instructions_accounts`: accounts in meta order with
+//! duplicates for one instruction, first appearances for a chain, sysvars from
+//! Mollusk, and the position of the first account that isn't cached.
+
+mod common;
+
+use {
+    common::{instruction, pubkeys, serving, store},
+    mollusk_on_demand::{RpcAccountStore, RpcError},
+    mollusk_svm::Mollusk,
+    solana_pubkey::Pubkey,
+    solana_sdk_ids::sysvar,
+};
+
+async fn fetched(keys: &[Pubkey]) -> RpcAccountStore {
+    store(&serving(keys)).from_instruction(&instruction(keys)).await.unwrap()
+}
+
+fn not_cached(error: RpcError) -> (Pubkey, usize, usize) {
+    let RpcError::AccountNotCached { pubkey, instruction, index } = error else {
+        panic!("expected AccountNotCached, got {error}");
+    };
+    (pubkey, instruction, index)
+}
+
+#[tokio::test]
+async fn accounts_follow_the_metas_with_duplicates() {
+    let keys = pubkeys(3);
+    let store = fetched(&keys).await;
+    let metas = [keys[2], keys[0], keys[2], keys[1]];
+
+    let accounts = store.instruction_accounts(&Mollusk::default(), &instruction(&metas)).unwrap();
+    let order: Vec<Pubkey> = accounts.iter().map(|(pubkey, _)| *pubkey).collect();
+    assert_eq!(order, metas);
+    for (pubkey, account) in &accounts {
+        assert_eq!(account, &store.cache[pubkey]);
+    }
+}
+
+#[tokio::test]
+async fn the_first_missing_account_is_located() {
+    let keys = pubkeys(2);
+    let store = fetched(&keys).await;
+    let (missing, later) = (Pubkey::new_unique(), Pubkey::new_unique());
+
+    let error = store
+        .instruction_accounts(&Mollusk::default(), &instruction(&[keys[0], missing, later]))
+        .unwrap_err();
+    assert!(error.to_string().contains(&missing.to_string()), "{error}");
+    assert_eq!(not_cached(error), (missing, 0, 1));
+}
+
+#[tokio::test]
+async fn chains_list_each_account_once_and_locate_misses() {
+    let keys = pubkeys(4);
+    let store = fetched(&keys).await;
+    let chain = [instruction(&[keys[1], keys[0]]), instruction(&[keys[0], keys[3], keys[2]])];
+
+    let accounts = store.instructions_accounts(&Mollusk::default(), &chain).unwrap();
+    let order: Vec<Pubkey> = accounts.iter().map(|(pubkey, _)| *pubkey).collect();
+    assert_eq!(order, [keys[1], keys[0], keys[3], keys[2]]);
+
+    let missing = Pubkey::new_unique();
+    let chain = [instruction(&keys[..2]), instruction(&[keys[1], keys[2], missing])];
+    let error = store.instructions_accounts(&Mollusk::default(), &chain).unwrap_err();
+    assert_eq!(not_cached(error), (missing, 1, 2));
+}
+
+#[tokio::test]
+async fn sysvars_come_from_mollusk() {
+    let keys = pubkeys(1);
+    let store = fetched(&keys).await;
+    let mut mollusk = Mollusk::default();
+    mollusk.warp_to_slot(500);
+
+    let accounts = store
+        .instruction_accounts(&mollusk, &instruction(&[keys[0], sysvar::clock::ID]))
+        .unwrap();
+    assert_eq!(accounts[1].0, sysvar::clock::ID);
+    assert_eq!(mollusk.sysvars.clock.slot, 500);
+    assert_eq!(accounts[1].1.data, bincode::serialize(&mollusk.sysvars.clock).unwrap());
+
+    let shared = store
+        .instruction_accounts_shared(&mollusk, &instruction(&[keys[0], keys[0]]))
+        .unwrap();
+    assert_eq!(shared.len(), 2);
+    assert_eq!(shared[1].0, keys[0]);
+}
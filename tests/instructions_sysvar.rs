@@ -0,0 +1,123 @@
+//! The `Instructions` sysvar: `instructions_sysvar_account` writes the on-chain
+//! layout the SDK's introspection helpers read back, and `instruction_accounts`
+//! builds it for a meta instead of fetching it.
+
+mod common;
+
+use {
+    common::{account, store},
+    mollusk_on_demand::{instructions_sysvar_account, testing::MockFetcher},
+    mollusk_svm::Mollusk,
+    solana_account::Account,
+    solana_account_info::AccountInfo,
+    solana_instruction::{AccountMeta, Instruction},
+    solana_instructions_sysvar::{load_current_index_checked, load_instruction_at_checked},
+    solana_program_error::ProgramError,
+    solana_pubkey::Pubkey,
+    solana_rent::Rent,
+    solana_sdk_ids::sysvar,
+};
+
+/// Run `inspect` on `account` as a program would see it.
+fn with_account_info<T>(
+    pubkey: &Pubkey,
+    account: &Account,
+    inspect: impl FnOnce(&AccountInfo) -> T,
+) -> T {
+    let (mut lamports, mut data) = (account.lamports, account.data.clone());
+    let info = AccountInfo::new(
+        pubkey,
+        false,
+        false,
+        &mut lamports,
+        &mut data,
+        &account.owner,
+        account.executable,
+    );
+    inspect(&info)
+}
+
+/// A transaction's worth of instructions of different shapes.
+fn transaction() -> Vec<Instruction> {
+    let (signer, writable, readonly) =
+        (Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+    vec![
+        Instruction::new_with_bytes(Pubkey::new_unique(), &[1, 2, 3], Vec::new()),
+        Instruction::new_with_bytes(
+            Pubkey::new_unique(),
+            &[4; 300],
+            vec![
+                AccountMeta::new(signer, true),
+                AccountMeta::new(writable, false),
+                AccountMeta::new_readonly(readonly, false),
+                AccountMeta::new_readonly(sysvar::instructions::ID, false),
+            ],
+        ),
+        Instruction::new_with_bytes(
+            Pubkey::new_unique(),
+            &[],
+            vec![AccountMeta::new_readonly(signer, true)],
+        ),
+    ]
+}
+
+#[test]
+fn the_sdk_reads_back_every_instruction_and_the_current_index() {
+    let instructions = transaction();
+    let (pubkey, account) = instructions_sysvar_account(&instructions, 1);
+    assert_eq!(pubkey, sysvar::instructions::ID);
+    assert_eq!(account.owner, sysvar::ID);
+    assert_eq!(account.lamports, Rent::default().minimum_balance(account.data.len()));
+    // The instruction count leads the data
+    assert_eq!(account.data[..2], 3u16.to_le_bytes());
+
+    with_account_info(&pubkey, &account, |info| {
+        for (index, instruction) in instructions.iter().enumerate() {
+            assert_eq!(load_instruction_at_checked(index, info).unwrap(), *instruction);
+        }
+        assert_eq!(load_current_index_checked(info).unwrap(), 1);
+        assert_eq!(
+            load_instruction_at_checked(3, info).unwrap_err(),
+            ProgramError::InvalidArgument
+        );
+    });
+}
+
+#[test]
+fn the_current_index_is_stored_at_the_tail() {
+    let instructions = transaction();
+    let (pubkey, first) = instructions_sysvar_account(&instructions, 0);
+    let (_, last) = instructions_sysvar_account(&instructions, 2);
+
+    let tail = first.data.len() - 2;
+    assert_eq!(first.data[..tail], last.data[..tail]);
+    assert_eq!(last.data[tail..], 2u16.to_le_bytes());
+    with_account_info(&pubkey, &last, |info| {
+        assert_eq!(load_current_index_checked(info).unwrap(), 2);
+    });
+}
+
+#[tokio::test]
+async fn instruction_accounts_builds_the_sysvar_for_a_meta() {
+    let payer = Pubkey::new_unique();
+    let fetcher = MockFetcher::new().with_account(payer, account(1_000_000_000, 0));
+    let ix = Instruction::new_with_bytes(
+        Pubkey::new_unique(),
+        &[7, 7],
+        vec![
+            AccountMeta::new(payer, true),
+            AccountMeta::new_readonly(sysvar::instructions::ID, false),
+        ],
+    );
+    let store = store(&fetcher).from_instruction(&ix).await.unwrap();
+    assert_eq!(fetcher.requested_pubkeys(), [payer]);
+
+    let accounts = store.instruction_accounts(&Mollusk::default(), &ix).unwrap();
+    let (pubkey, account) = &accounts[1];
+    assert_eq!(*pubkey, sysvar::instructions::ID);
+    assert_eq!(*account, instructions_sysvar_account(std::slice::from_ref(&ix), 0).1);
+    with_account_info(pubkey, account, |info| {
+        assert_eq!(load_instruction_at_checked(0, info).unwrap(), ix);
+        assert_eq!(load_current_index_checked(info).unwrap(), 0);
+    });
+}
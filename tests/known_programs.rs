@@ -0,0 +1,147 @@
+//! `with_known_programs`: SPL Token and the Associated Token Account program are
+//! fetched in one batch, builtins aren't fetched at all, and once `add_programs`
+//! loads them Mollusk creates an associated token account and transfers into it.
+
+mod common;
+
+use {
+    common::{account, store},
+    mollusk_on_demand::{
+        known_programs::{self, ASSOCIATED_TOKEN, SYSTEM, TOKEN},
+        testing::MockFetcher,
+        RpcAccountStore,
+    },
+    mollusk_svm::{
+        program::{keyed_account_for_system_program, loader_keys::LOADER_V2},
+        Mollusk,
+    },
+    solana_account::Account,
+    solana_instruction::{AccountMeta, Instruction},
+    solana_program_pack::Pack,
+    solana_pubkey::Pubkey,
+    solana_rent::Rent,
+    solana_sdk_ids::system_program,
+    spl_token_interface::{
+        instruction,
+        state::{Account as TokenAccount, AccountState, Mint},
+    },
+};
+
+const LAMPORTS: u64 = 1_000_000_000;
+
+/// A mock serving the two programs as mainnet deploys them, with loader v2, and the
+/// System Program's native account.
+fn mainnet_programs() -> MockFetcher {
+    let (system, system_account) = keyed_account_for_system_program();
+    MockFetcher::new()
+        .with_account(system, system_account)
+        .with_account(TOKEN.id, mollusk_svm_programs_token::token::account())
+        .with_account(ASSOCIATED_TOKEN.id, mollusk_svm_programs_token::associated_token::account())
+}
+
+fn token_owned(data: Vec<u8>) -> Account {
+    Account {
+        lamports: Rent::default().minimum_balance(data.len()),
+        owner: TOKEN.id,
+        data,
+        executable: false,
+        rent_epoch: 0,
+    }
+}
+
+fn associated_token_address(wallet: &Pubkey, mint: &Pubkey) -> Pubkey {
+    let seeds: &[&[u8]] = &[wallet.as_ref(), TOKEN.id.as_ref(), mint.as_ref()];
+    Pubkey::find_program_address(seeds, &ASSOCIATED_TOKEN.id).0
+}
+
+/// `CreateIdempotent` of the Associated Token Account program.
+fn create_associated_token_account(payer: &Pubkey, wallet: &Pubkey, mint: &Pubkey) -> Instruction {
+    Instruction::new_with_bytes(
+        ASSOCIATED_TOKEN.id,
+        &[1],
+        vec![
+            AccountMeta::new(*payer, true),
+            AccountMeta::new(associated_token_address(wallet, mint), false),
+            AccountMeta::new_readonly(*wallet, false),
+            AccountMeta::new_readonly(*mint, false),
+            AccountMeta::new_readonly(system_program::ID, false),
+            AccountMeta::new_readonly(TOKEN.id, false),
+        ],
+    )
+}
+
+#[tokio::test]
+async fn known_programs_are_fetched_in_one_batch() {
+    let fetcher = mainnet_programs();
+    let store = store(&fetcher)
+        .with_known_programs(&[SYSTEM, TOKEN, ASSOCIATED_TOKEN])
+        .await
+        .unwrap();
+
+    assert_eq!(fetcher.calls().len(), 1);
+    assert_eq!(fetcher.requested_pubkeys(), [TOKEN.id, ASSOCIATED_TOKEN.id]);
+    assert_eq!(store.cache.len(), 2);
+    assert!(!store.cache.contains_key(&system_program::ID));
+    assert_eq!(known_programs::find(&ASSOCIATED_TOKEN.id), Some(&ASSOCIATED_TOKEN));
+    assert_eq!(known_programs::find(&Pubkey::new_unique()), None);
+}
+
+#[tokio::test]
+async fn loaded_programs_create_an_associated_account_and_transfer() {
+    let (alice, bob, mint) = (Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+    let alice_ata = associated_token_address(&alice, &mint);
+    let bob_ata = associated_token_address(&bob, &mint);
+
+    let mut mint_data = vec![0; Mint::LEN];
+    Mint {
+        supply: 1_000,
+        decimals: 6,
+        is_initialized: true,
+        ..Mint::default()
+    }
+    .pack_into_slice(&mut mint_data);
+    let mut alice_data = vec![0; TokenAccount::LEN];
+    TokenAccount {
+        mint,
+        owner: alice,
+        amount: 1_000,
+        state: AccountState::Initialized,
+        ..TokenAccount::default()
+    }
+    .pack_into_slice(&mut alice_data);
+
+    let create = create_associated_token_account(&alice, &bob, &mint);
+    let mut mollusk = Mollusk::default();
+    let mut store: RpcAccountStore = store(&mainnet_programs())
+        .allow_missing_accounts()
+        .with_accounts([
+            (alice, account(LAMPORTS, 0)),
+            (mint, token_owned(mint_data)),
+            (alice_ata, token_owned(alice_data)),
+        ])
+        .with_known_programs(&[TOKEN, ASSOCIATED_TOKEN])
+        .await
+        .unwrap()
+        .from_instruction(&create)
+        .await
+        .unwrap()
+        .add_programs(&mut mollusk)
+        .await
+        .unwrap();
+    assert_eq!(store.cache[&TOKEN.id].owner, LOADER_V2);
+
+    let accounts = store.instruction_accounts(&mollusk, &create).unwrap();
+    let result = mollusk.process_instruction(&create, &accounts);
+    assert!(result.program_result.is_ok(), "{:?}", result.program_result);
+    store.apply_result(&result);
+    assert_eq!(store.get_token_account(&bob_ata).unwrap().owner, bob);
+
+    let transfer =
+        instruction::transfer(&TOKEN.id, &alice_ata, &bob_ata, &alice, &[], 400).unwrap();
+    let accounts = store.instruction_accounts(&mollusk, &transfer).unwrap();
+    let result = mollusk.process_instruction(&transfer, &accounts);
+    assert!(result.program_result.is_ok(), "{:?}", result.program_result);
+    store.apply_result(&result);
+    assert_eq!(store.token_balance(&alice_ata).unwrap(), 600);
+    assert_eq!(store.token_balance(&bob_ata).unwrap(), 400);
+}
@@ -0,0 +1,103 @@
+//! Account labels: errors and diff reports print labeled pubkeys as
+//! `alice_usdc (4vJ9…kLKi)`, well-known programs can be labeled in one call without
+//! replacing the caller's labels, and labels never change what the store does.
+
+mod common;
+
+use {
+    common::{account, store},
+    mollusk_on_demand::{testing::MockFetcher, RpcAccountStore},
+    mollusk_svm::result::InstructionResult,
+    solana_instruction::{AccountMeta, Instruction},
+    solana_pubkey::Pubkey,
+    solana_sdk_ids::system_program,
+};
+
+const LAMPORTS: u64 = 1_000_000_000;
+/// `4vJ9JU1bJJE96FWSJKvHsmmFADCg4gpZQff4P3bkLKi`
+const ALICE: Pubkey = Pubkey::new_from_array([1; 32]);
+/// `8qbHbw2BbbTHBW1sbeqakYXVKRQM8Ne7pLK7m6CVfeR`
+const BOB: Pubkey = Pubkey::new_from_array([2; 32]);
+
+fn transfer(from: Pubkey, to: Pubkey) -> Instruction {
+    Instruction {
+        program_id: system_program::ID,
+        accounts: vec![AccountMeta::new(from, true), AccountMeta::new(to, false)],
+        data: vec![],
+    }
+}
+
+#[tokio::test]
+async fn a_missing_account_error_names_its_label() {
+    let fetcher = MockFetcher::new().with_account(ALICE, account(LAMPORTS, 0));
+    let error = store(&fetcher)
+        .with_labels(&[(ALICE, "alice"), (BOB, "bob_usdc")])
+        .from_instruction(&transfer(ALICE, BOB))
+        .await
+        .map(|_| ())
+        .unwrap_err();
+
+    assert_eq!(
+        error.to_string(),
+        "[account_not_found] Account not found: bob_usdc (8qbH…VfeR) (instruction 0, account 1, \
+         writable)"
+    );
+}
+
+#[test]
+fn a_diff_report_names_labeled_accounts() {
+    let mut store = RpcAccountStore::new_with_fetcher(MockFetcher::new())
+        .with_accounts([(ALICE, account(LAMPORTS, 0)), (BOB, account(LAMPORTS, 0))]);
+    store.label(&ALICE, "alice");
+    let result = InstructionResult {
+        resulting_accounts: vec![
+            (ALICE, account(LAMPORTS - 500, 0)),
+            (BOB, account(LAMPORTS + 500, 0)),
+        ],
+        ..InstructionResult::default()
+    };
+
+    assert_eq!(
+        store.diff_result(&result).to_string(),
+        format!(
+            "alice (4vJ9…kLKi) modified (pre-state mocked): lamports -500\n{BOB} modified \
+             (pre-state mocked): lamports +500"
+        )
+    );
+}
+
+#[test]
+fn well_known_labels_keep_the_callers() {
+    let token = mollusk_on_demand::known_programs::TOKEN.id;
+    let store = RpcAccountStore::new_with_fetcher(MockFetcher::new())
+        .with_labels(&[(token, "my_token")])
+        .with_well_known_labels();
+
+    assert_eq!(store.label_of(&system_program::ID), Some("system_program"));
+    assert_eq!(store.label_of(&token), Some("my_token"));
+    assert_eq!(store.display_pubkey(&ALICE), ALICE.to_string());
+    assert_eq!(
+        RpcAccountStore::new_with_fetcher(MockFetcher::new()).label_of(&system_program::ID),
+        None
+    );
+}
+
+#[tokio::test]
+async fn labels_change_nothing_but_formatting() {
+    let fetcher = MockFetcher::new()
+        .with_account(ALICE, account(LAMPORTS, 0))
+        .with_account(BOB, account(LAMPORTS, 8));
+    let plain = store(&fetcher).from_instruction(&transfer(ALICE, BOB)).await.unwrap();
+    let plain_calls = fetcher.calls();
+
+    fetcher.clear_calls();
+    let labeled = store(&fetcher)
+        .with_labels(&[(ALICE, "alice"), (BOB, "bob")])
+        .with_well_known_labels()
+        .from_instruction(&transfer(ALICE, BOB))
+        .await
+        .unwrap();
+    assert_eq!(fetcher.calls(), plain_calls);
+    assert_eq!(labeled.cache, plain.cache);
+    assert_eq!(labeled.warnings(), plain.warnings());
+}
@@ -0,0 +1,159 @@
+//! `lenient` mode: fetches and `add_programs` skip the accounts and programs that
+//! fail, record them in `errors()` and keep the rest, where a strict store returns
+//! the first failure.
+
+mod common;
+
+use {
+    common::{account, instruction, pubkeys, serving, store},
+    mollusk_on_demand::{testing::MockFetcher, RpcAccountStore, RpcError},
+    mollusk_svm::Mollusk,
+    solana_account::Account,
+    solana_pubkey::Pubkey,
+    solana_rent::Rent,
+    solana_sdk_ids::bpf_loader_upgradeable,
+};
+
+const ELF: &[u8] = mollusk_svm_programs_token::token2022::ELF;
+
+/// An upgradeable program account pointing at `program_data`.
+fn program_account(program_data: &Pubkey) -> Account {
+    let mut data = 2u32.to_le_bytes().to_vec();
+    data.extend_from_slice(program_data.as_ref());
+    Account {
+        lamports: Rent::default().minimum_balance(data.len()),
+        data,
+        executable: true,
+        owner: bpf_loader_upgradeable::ID,
+        ..account(0, 0)
+    }
+}
+
+/// A program data account holding `elf`.
+fn program_data_account(elf: &[u8]) -> Account {
+    // The ProgramData header: tag, deployment slot and upgrade authority
+    let mut data = 3u32.to_le_bytes().to_vec();
+    data.extend_from_slice(&0u64.to_le_bytes());
+    data.extend_from_slice(&[0; 33]);
+    data.extend_from_slice(elf);
+    Account {
+        lamports: Rent::default().minimum_balance(data.len()),
+        data,
+        owner: bpf_loader_upgradeable::ID,
+        ..account(0, 0)
+    }
+}
+
+/// A valid program, one whose account is malformed, one without program data and
+/// one with an invalid ELF, in that order, served by a mock. Also returns the
+/// missing program data address.
+fn programs() -> (MockFetcher, [Pubkey; 4], Pubkey) {
+    let [good, malformed, orphaned, invalid] = [(); 4].map(|()| Pubkey::new_unique());
+    let [good_data, orphaned_data, invalid_data] = [(); 3].map(|()| Pubkey::new_unique());
+    let fetcher = MockFetcher::new()
+        .with_account(good, program_account(&good_data))
+        .with_account(good_data, program_data_account(ELF))
+        .with_account(
+            malformed,
+            Account {
+                executable: true,
+                owner: bpf_loader_upgradeable::ID,
+                ..account(1_000_000_000, 3)
+            },
+        )
+        .with_account(orphaned, program_account(&orphaned_data))
+        .with_account(invalid, program_account(&invalid_data))
+        .with_account(invalid_data, program_data_account(b"not an elf"));
+    (fetcher, [good, malformed, orphaned, invalid], orphaned_data)
+}
+
+fn recorded(store: &RpcAccountStore) -> Vec<Pubkey> {
+    let mut pubkeys: Vec<Pubkey> = store.errors().iter().map(|error| error.pubkey).collect();
+    pubkeys.sort();
+    pubkeys
+}
+
+#[tokio::test]
+async fn a_strict_fetch_fails_on_missing_accounts() {
+    let keys = pubkeys(5);
+    let fetcher = serving(&keys[..3]);
+
+    let error = store(&fetcher)
+        .from_instruction(&instruction(&keys))
+        .await
+        .map(|_| ())
+        .unwrap_err();
+    let RpcError::AccountsNotFoundInInstructions(missing) = &error else {
+        panic!("expected AccountsNotFoundInInstructions, got {error}");
+    };
+    let mut missing: Vec<Pubkey> = missing.iter().map(|missing| missing.pubkey).collect();
+    missing.sort();
+    let mut expected = keys[3..].to_vec();
+    expected.sort();
+    assert_eq!(missing, expected);
+}
+
+#[tokio::test]
+async fn a_lenient_fetch_keeps_the_found_accounts_and_records_the_rest() {
+    let keys = pubkeys(5);
+    let fetcher = serving(&keys[..3]);
+
+    let store = store(&fetcher)
+        .lenient()
+        .from_instruction(&instruction(&keys))
+        .await
+        .unwrap();
+    for (index, pubkey) in keys[..3].iter().enumerate() {
+        assert_eq!(store.cache[pubkey].lamports, index as u64 + 1);
+    }
+    assert_eq!(store.cache.len(), 3);
+    let mut expected = keys[3..].to_vec();
+    expected.sort();
+    assert_eq!(recorded(&store), expected);
+    for recorded in store.errors() {
+        let RpcError::AccountNotFound(pubkey) = &recorded.error else {
+            panic!("expected AccountNotFound, got {}", recorded.error);
+        };
+        assert_eq!(*pubkey, recorded.pubkey);
+    }
+}
+
+#[tokio::test]
+async fn strict_add_programs_fails_on_a_bad_program() {
+    let (fetcher, [good, malformed, orphaned, invalid], _) = programs();
+    let store = store(&fetcher)
+        .from_instruction(&instruction(&[good, malformed, orphaned, invalid]))
+        .await
+        .unwrap();
+
+    assert!(store.add_programs(&mut Mollusk::default()).await.is_err());
+}
+
+#[tokio::test]
+async fn lenient_add_programs_skips_bad_programs_and_loads_the_rest() {
+    let (fetcher, [good, malformed, orphaned, invalid], orphaned_data) = programs();
+    let mut mollusk = Mollusk::default();
+
+    let store = store(&fetcher)
+        .lenient()
+        .from_instruction(&instruction(&[good, malformed, orphaned, invalid]))
+        .await
+        .unwrap()
+        .add_programs(&mut mollusk)
+        .await
+        .unwrap();
+    assert!(mollusk.program_cache.load_program(&good).is_some());
+    for bad in [malformed, orphaned, invalid] {
+        assert!(mollusk.program_cache.load_program(&bad).is_none(), "{bad}");
+    }
+
+    // The missing program data is recorded by its fetch, and its program when loading
+    let mut expected = vec![malformed, orphaned, orphaned_data, invalid];
+    expected.sort();
+    assert_eq!(recorded(&store), expected);
+    let invalid_error = store.errors().iter().find(|error| error.pubkey == invalid).unwrap();
+    let RpcError::InvalidProgramData { program, .. } = &invalid_error.error else {
+        panic!("expected InvalidProgramData, got {invalid_error}");
+    };
+    assert_eq!(*program, invalid);
+}
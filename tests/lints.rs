@@ -0,0 +1,191 @@
+//! `lint_instruction` and `assert_no_lints`: each kind of meta mistake is found
+//! with its severity at the account's first meta, uncached accounts are a lint of
+//! their own, and neither makes a request.
+
+mod common;
+
+use {
+    common::{account, store},
+    mollusk_on_demand::{
+        testing::MockFetcher, InstructionLint, LintKind, LintSeverity, RpcAccountStore, RpcError,
+    },
+    solana_account::Account,
+    solana_instruction::{AccountMeta, Instruction},
+    solana_pubkey::Pubkey,
+    solana_sdk_ids::{bpf_loader_upgradeable, sysvar},
+};
+
+const LAMPORTS: u64 = 1_000_000_000;
+const SPL_TOKEN: Pubkey = spl_token_interface::ID;
+
+fn key(byte: u8) -> Pubkey {
+    Pubkey::new_from_array([byte; 32])
+}
+
+const PAYER: u8 = 1;
+const PROGRAM: u8 = 2;
+const PDA: u8 = 3;
+const WALLET: u8 = 4;
+const MISSING: u8 = 5;
+const STALE_SYSVAR: u8 = 6;
+
+/// A store with an empty payer, an executable program, a token-owned PDA, a funded
+/// wallet and a cached account owned by the sysvar program.
+fn cached(fetcher: &MockFetcher) -> RpcAccountStore {
+    let program = Account {
+        executable: true,
+        owner: bpf_loader_upgradeable::ID,
+        ..account(LAMPORTS, 36)
+    };
+    let pda = Account {
+        owner: SPL_TOKEN,
+        ..account(LAMPORTS, 165)
+    };
+    let stale_sysvar = Account {
+        owner: sysvar::ID,
+        ..account(LAMPORTS, 40)
+    };
+    store(fetcher)
+        .with_accounts([
+            (key(PAYER), account(0, 0)),
+            (key(PROGRAM), program),
+            (key(PDA), pda),
+            (key(WALLET), account(LAMPORTS, 0)),
+            (key(STALE_SYSVAR), stale_sysvar),
+        ])
+        .with_labels(&[(key(PROGRAM), "amm")])
+}
+
+fn lints(store: &RpcAccountStore, metas: Vec<AccountMeta>) -> Vec<(usize, LintKind)> {
+    let instruction = Instruction::new_with_bytes(key(9), &[], metas);
+    let lints = store.lint_instruction(&instruction);
+    for lint in &lints {
+        assert_eq!(lint.severity, lint.kind.severity());
+    }
+    lints.iter().map(|lint| (lint.account_index, lint.kind)).collect()
+}
+
+#[test]
+fn each_lint_kind_is_found() {
+    let fetcher = MockFetcher::new();
+    let store = cached(&fetcher);
+    let found = lints(
+        &store,
+        vec![
+            AccountMeta::new(key(PAYER), true),
+            AccountMeta::new(key(PROGRAM), false),
+            AccountMeta::new(sysvar::clock::ID, false),
+            AccountMeta::new_readonly(key(PDA), true),
+            AccountMeta::new(key(MISSING), false),
+            AccountMeta::new(key(STALE_SYSVAR), false),
+            AccountMeta::new_readonly(key(WALLET), true),
+            AccountMeta::new_readonly(sysvar::rent::ID, false),
+        ],
+    );
+    assert_eq!(
+        found,
+        [
+            (0, LintKind::EmptyFeePayer),
+            (1, LintKind::WritableExecutable),
+            (2, LintKind::WritableSysvar),
+            (3, LintKind::ProgramOwnedSigner { owner: SPL_TOKEN }),
+            (4, LintKind::NotCached),
+            (5, LintKind::WritableSysvar),
+        ]
+    );
+    let severities = [
+        LintKind::EmptyFeePayer,
+        LintKind::WritableExecutable,
+        LintKind::WritableSysvar,
+        LintKind::ProgramOwnedSigner { owner: SPL_TOKEN },
+        LintKind::NotCached,
+    ]
+    .map(|kind| kind.severity());
+    assert_eq!(
+        severities,
+        [
+            LintSeverity::Error,
+            LintSeverity::Error,
+            LintSeverity::Error,
+            LintSeverity::Warning,
+            LintSeverity::Warning,
+        ]
+    );
+    assert!(fetcher.calls().is_empty());
+}
+
+#[test]
+fn duplicate_metas_are_checked_once_with_their_flags_combined() {
+    let store = cached(&MockFetcher::new());
+    let found = lints(
+        &store,
+        vec![
+            AccountMeta::new_readonly(key(WALLET), false),
+            AccountMeta::new_readonly(key(PROGRAM), false),
+            AccountMeta::new_readonly(key(MISSING), false),
+            AccountMeta::new(key(PROGRAM), false),
+            AccountMeta::new(key(MISSING), false),
+        ],
+    );
+    assert_eq!(found, [(1, LintKind::WritableExecutable), (2, LintKind::NotCached)]);
+}
+
+#[test]
+fn only_the_first_writable_signer_pays_the_fee() {
+    let store = cached(&MockFetcher::new());
+    let found = lints(
+        &store,
+        vec![AccountMeta::new(key(WALLET), true), AccountMeta::new(key(PAYER), true)],
+    );
+    assert!(found.is_empty(), "{found:?}");
+
+    // A readonly signer doesn't pay; the later writable one does
+    let found = lints(
+        &store,
+        vec![AccountMeta::new_readonly(key(WALLET), true), AccountMeta::new(key(PAYER), true)],
+    );
+    assert_eq!(found, [(1, LintKind::EmptyFeePayer)]);
+}
+
+#[test]
+fn assert_no_lints_reports_every_finding() {
+    let fetcher = MockFetcher::new();
+    let store = cached(&fetcher);
+    let clean = Instruction::new_with_bytes(
+        key(9),
+        &[],
+        vec![AccountMeta::new(key(WALLET), true), AccountMeta::new_readonly(key(PROGRAM), false)],
+    );
+    store.assert_no_lints(&clean).unwrap();
+
+    let instruction = Instruction::new_with_bytes(
+        key(9),
+        &[],
+        vec![AccountMeta::new(key(PROGRAM), false), AccountMeta::new_readonly(key(PDA), true)],
+    );
+    let error = store.assert_no_lints(&instruction).unwrap_err();
+    let RpcError::InstructionLints(found) = &error else {
+        panic!("expected InstructionLints, got {error}");
+    };
+    assert_eq!(
+        found[0],
+        InstructionLint {
+            kind: LintKind::WritableExecutable,
+            severity: LintSeverity::Error,
+            account_index: 0,
+            pubkey: key(PROGRAM),
+            label: Some("amm".to_string()),
+        }
+    );
+    assert_eq!(
+        error.to_string(),
+        format!(
+            "[{}] Instruction metas have problems: error: account 0, {}: writable but \
+             executable; warning: account 1, {}: signer owned by {SPL_TOKEN} (SPL Token)",
+            error.code(),
+            store.display_pubkey(&key(PROGRAM)),
+            key(PDA),
+        )
+    );
+    assert!(fetcher.calls().is_empty());
+}
@@ -0,0 +1,169 @@
+//! `make_lookup_table` and `extend_lookup_table_in_cache`: the tables decode as the
+//! SDK's `AddressLookupTable` and resolve a v0 message's indexes like tables the ALT
+//! program made, and accounts that aren't tables to extend are errors.
+//!
+//! The SDK's table is built on `solana-pubkey` 4, so its keys are compared by bytes.
+
+mod common;
+
+use {
+    common::{account, store},
+    mollusk_on_demand::{testing::MockFetcher, Provenance, RpcAccountStore, RpcError},
+    solana_account::Account,
+    solana_address_lookup_table_interface::state::AddressLookupTable,
+    solana_pubkey::Pubkey,
+    solana_rent::Rent,
+    solana_sdk_ids::{address_lookup_table, system_program},
+    solana_slot_hashes::SlotHashes,
+};
+
+const SLOT: u64 = 303_000_000;
+
+fn key(byte: u8) -> Pubkey {
+    Pubkey::new_from_array([byte; 32])
+}
+
+const AUTHORITY: u8 = 1;
+
+/// The table the create instruction would make for the authority at `recent_slot`.
+fn derived_address(recent_slot: u64) -> Pubkey {
+    let (authority, recent_slot) = (key(AUTHORITY), recent_slot.to_le_bytes());
+    let seeds: &[&[u8]] = &[authority.as_ref(), &recent_slot];
+    Pubkey::find_program_address(seeds, &address_lookup_table::ID).0
+}
+
+/// The addresses of `table` at `indexes`, resolved as the runtime loads a v0
+/// message's lookups.
+fn resolve(store: &RpcAccountStore, table: &Pubkey, indexes: &[u8]) -> Vec<Pubkey> {
+    let table = AddressLookupTable::deserialize(&store.cache[table].data).unwrap();
+    table
+        .lookup(SLOT, indexes, &SlotHashes::new(&[]))
+        .unwrap()
+        .into_iter()
+        .map(|address| Pubkey::new_from_array(address.to_bytes()))
+        .collect()
+}
+
+#[test]
+fn a_made_table_decodes_and_resolves_like_a_real_one() {
+    let mut store = store(&MockFetcher::new());
+    let addresses = [key(10), key(11), key(12)];
+    let (address, account) = store.make_lookup_table(key(AUTHORITY), &addresses);
+    assert_eq!(address, derived_address(0));
+    assert_eq!(store.cache[&address], account);
+    assert_eq!(store.provenance(&address), Some(&Provenance::Mocked));
+    assert_eq!(account.owner, address_lookup_table::ID);
+    assert_eq!(account.data.len(), 56 + 3 * 32);
+    assert_eq!(
+        account.lamports,
+        Rent::default().minimum_balance(account.data.len())
+    );
+
+    let table = AddressLookupTable::deserialize(&account.data).unwrap();
+    assert_eq!(table.meta.deactivation_slot, u64::MAX);
+    assert_eq!(
+        table.meta.authority.map(|authority| authority.to_bytes()),
+        Some(key(AUTHORITY).to_bytes())
+    );
+    let decoded: Vec<[u8; 32]> = table.addresses.iter().map(|a| a.to_bytes()).collect();
+    assert_eq!(decoded, addresses.map(|address| address.to_bytes()));
+    assert_eq!(resolve(&store, &address, &[2, 0]), [key(12), key(10)]);
+
+    // The next table of the authority takes the next recent slot
+    let (second, _) = store.make_lookup_table(key(AUTHORITY), &[key(13)]);
+    assert_eq!(second, derived_address(1));
+}
+
+#[test]
+fn an_extended_table_resolves_its_new_addresses() {
+    let mut store = store(&MockFetcher::new());
+    let (address, _) = store.make_lookup_table(key(AUTHORITY), &[key(10), key(11)]);
+    store
+        .extend_lookup_table_in_cache(&address, &[key(12), key(13)])
+        .unwrap();
+
+    let extended = &store.cache[&address];
+    let table = AddressLookupTable::deserialize(&extended.data).unwrap();
+    assert_eq!(
+        (
+            table.meta.last_extended_slot_start_index,
+            table.addresses.len()
+        ),
+        (4, 4)
+    );
+    assert_eq!(
+        table.meta.authority.map(|authority| authority.to_bytes()),
+        Some(key(AUTHORITY).to_bytes())
+    );
+    assert_eq!(extended.data.len(), 56 + 4 * 32);
+    assert_eq!(
+        extended.lamports,
+        Rent::default().minimum_balance(extended.data.len())
+    );
+    assert_eq!(resolve(&store, &address, &[3, 1]), [key(13), key(11)]);
+    assert_eq!(
+        store.provenance(&address),
+        Some(&Provenance::Patched("extend_lookup_table_in_cache"))
+    );
+}
+
+#[test]
+#[should_panic(expected = "a lookup table holds at most 256 addresses, got 257")]
+fn a_table_holds_at_most_256_addresses() {
+    let addresses: Vec<Pubkey> = (0..257).map(|_| Pubkey::new_unique()).collect();
+    store(&MockFetcher::new()).make_lookup_table(key(AUTHORITY), &addresses);
+}
+
+#[test]
+fn accounts_that_cant_be_extended_are_errors() {
+    let mut store = store(&MockFetcher::new());
+    let (full, _) = store.make_lookup_table(key(AUTHORITY), &[key(10); 255]);
+    let (deactivated, _) = store.make_lookup_table(key(AUTHORITY), &[key(10)]);
+    // Deactivated at slot 0
+    store.cache.get_mut(&deactivated).unwrap().data[4..12].fill(0);
+    let (frozen, _) = store.make_lookup_table(key(AUTHORITY), &[key(10)]);
+    // Its authority's `Option` tag
+    store.cache.get_mut(&frozen).unwrap().data[21] = 0;
+    let mut store = store.with_accounts([
+        (key(20), account(1_000_000, 100)),
+        (
+            key(21),
+            Account {
+                owner: address_lookup_table::ID,
+                ..account(1_000_000, 100)
+            },
+        ),
+    ]);
+
+    for (table, expected) in [
+        (
+            key(20),
+            format!(
+                "owned by {} (System Program), expected {} (Address Lookup Table Program)",
+                system_program::ID,
+                address_lookup_table::ID
+            ),
+        ),
+        (key(21), "not a lookup table".to_string()),
+        (deactivated, "the table is deactivated".to_string()),
+        (frozen, "the table is frozen".to_string()),
+        (full, "257 addresses, at most 256 fit".to_string()),
+    ] {
+        let error = store
+            .extend_lookup_table_in_cache(&table, &[key(11), key(12)])
+            .unwrap_err();
+        let RpcError::InvalidLookupTable { pubkey, reason } = &error else {
+            panic!("expected InvalidLookupTable, got {error}");
+        };
+        assert_eq!((pubkey, reason), (&table, &expected));
+    }
+    assert_eq!(resolve(&store, &full, &[254]), [key(10)]);
+
+    let error = store
+        .extend_lookup_table_in_cache(&key(22), &[key(11)])
+        .unwrap_err();
+    let RpcError::AccountNotFound(pubkey) = &error else {
+        panic!("expected AccountNotFound, got {error}");
+    };
+    assert_eq!(*pubkey, key(22));
+}
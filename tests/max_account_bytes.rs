@@ -0,0 +1,138 @@
+//! `with_max_account_bytes_strict`: an account with more data than the limit fails
+//! the fetch before reaching the cache, one exactly at the limit passes, and the
+//! program data `add_programs` fetches is exempt unless `check_program_data_size`.
+
+mod common;
+
+use {
+    common::{account, instruction, store},
+    mollusk_on_demand::{testing::MockFetcher, RpcError},
+    mollusk_svm::Mollusk,
+    solana_account::Account,
+    solana_pubkey::Pubkey,
+    solana_sdk_ids::bpf_loader_upgradeable,
+};
+
+const LIMIT: usize = 1024;
+const ELF: &[u8] = mollusk_svm_programs_token::token2022::ELF;
+
+/// A mock serving an upgradeable program whose program data holds the Token-2022
+/// ELF, far larger than [`LIMIT`].
+fn program() -> (MockFetcher, Pubkey, Pubkey) {
+    let (program, program_data) = (Pubkey::new_unique(), Pubkey::new_unique());
+    let mut data = 2u32.to_le_bytes().to_vec();
+    data.extend_from_slice(program_data.as_ref());
+    let program_account = Account {
+        data,
+        executable: true,
+        owner: bpf_loader_upgradeable::ID,
+        ..account(1_000_000_000, 0)
+    };
+
+    // The ProgramData header: tag, deployment slot and upgrade authority
+    let mut data = 3u32.to_le_bytes().to_vec();
+    data.extend_from_slice(&0u64.to_le_bytes());
+    data.extend_from_slice(&[0; 33]);
+    data.extend_from_slice(ELF);
+    let program_data_account = Account {
+        data,
+        owner: bpf_loader_upgradeable::ID,
+        ..account(1_000_000_000, 0)
+    };
+    let fetcher = MockFetcher::new()
+        .with_account(program, program_account)
+        .with_account(program_data, program_data_account);
+    (fetcher, program, program_data)
+}
+
+#[tokio::test]
+async fn an_account_at_the_limit_passes() {
+    let pubkey = Pubkey::new_unique();
+    let fetcher = MockFetcher::new().with_account(pubkey, account(1_000_000_000, LIMIT));
+
+    let store = store(&fetcher)
+        .with_max_account_bytes_strict(LIMIT)
+        .from_instruction(&instruction(&[pubkey]))
+        .await
+        .unwrap();
+    assert_eq!(store.cache[&pubkey].data.len(), LIMIT);
+}
+
+#[tokio::test]
+async fn an_account_over_the_limit_fails_the_fetch() {
+    let (fits, oversized) = (Pubkey::new_unique(), Pubkey::new_unique());
+    let fetcher = MockFetcher::new()
+        .with_account(fits, account(1_000_000_000, 8))
+        .with_account(oversized, account(1_000_000_000, LIMIT + 1));
+
+    let error = store(&fetcher)
+        .with_max_account_bytes_strict(LIMIT)
+        .from_instruction(&instruction(&[fits, oversized]))
+        .await
+        .map(|_| ())
+        .unwrap_err();
+    let RpcError::AccountTooLarge {
+        pubkey,
+        size,
+        limit,
+    } = &error
+    else {
+        panic!("expected AccountTooLarge, got {error}");
+    };
+    assert_eq!((*pubkey, *size, *limit), (oversized, LIMIT + 1, LIMIT));
+    assert_eq!(error.code(), "account_too_large");
+    assert!(
+        error.to_string().contains(&format!(
+            "Account {oversized} has {} bytes of data, more than the limit of {LIMIT}",
+            LIMIT + 1
+        )),
+        "{error}"
+    );
+}
+
+#[tokio::test]
+async fn without_a_limit_any_size_passes() {
+    let pubkey = Pubkey::new_unique();
+    let fetcher = MockFetcher::new().with_account(pubkey, account(1_000_000_000, 10 * LIMIT));
+
+    let store = store(&fetcher).from_instruction(&instruction(&[pubkey])).await.unwrap();
+    assert_eq!(store.cache[&pubkey].data.len(), 10 * LIMIT);
+}
+
+#[tokio::test]
+async fn program_data_is_exempt_by_default() {
+    let (fetcher, program, program_data) = program();
+    let mut mollusk = Mollusk::default();
+
+    let store = store(&fetcher)
+        .with_max_account_bytes_strict(LIMIT)
+        .from_instruction(&instruction(&[program]))
+        .await
+        .unwrap()
+        .add_programs(&mut mollusk)
+        .await
+        .unwrap();
+    assert!(store.cache[&program_data].data.len() > LIMIT);
+    assert!(mollusk.program_cache.load_program(&program).is_some());
+}
+
+#[tokio::test]
+async fn check_program_data_size_includes_program_data() {
+    let (fetcher, program, program_data) = program();
+
+    let error = store(&fetcher)
+        .with_max_account_bytes_strict(LIMIT)
+        .check_program_data_size()
+        .from_instruction(&instruction(&[program]))
+        .await
+        .unwrap()
+        .add_programs(&mut Mollusk::default())
+        .await
+        .map(|_| ())
+        .unwrap_err();
+    let RpcError::AccountTooLarge { pubkey, size, .. } = error else {
+        panic!("expected AccountTooLarge, got {error}");
+    };
+    assert_eq!(pubkey, program_data);
+    assert!(size > LIMIT);
+}
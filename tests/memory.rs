@@ -0,0 +1,167 @@
+//! `memory_report` over a cache of known sizes: totals split between loader-owned
+//! accounts and the rest, the 10 largest accounts with their labels, the printed
+//! report, and the warning once the cache crosses its threshold.
+
+mod common;
+
+use {
+    common::{account, store},
+    mollusk_on_demand::{testing::MockFetcher, LargeAccount, MemoryReport, WarningCode},
+    solana_account::Account,
+    solana_pubkey::Pubkey,
+    solana_sdk_ids::{bpf_loader_upgradeable, loader_v4, system_program},
+};
+
+fn key(byte: u8) -> Pubkey {
+    Pubkey::new_from_array([byte; 32])
+}
+
+const PROGRAM: u8 = 1;
+const PROGRAM_DATA: u8 = 2;
+const V4_PROGRAM: u8 = 3;
+
+fn owned_by(owner: Pubkey, data_len: usize) -> Account {
+    Account {
+        owner,
+        ..account(1_000_000_000, data_len)
+    }
+}
+
+/// An upgradeable program of 36 + 40 000 bytes, a loader v4 program of 10 000, and
+/// 12 system accounts of 100 to 1 100 bytes, two of them 1 100.
+fn known_sizes() -> Vec<(Pubkey, Account)> {
+    let mut accounts = vec![
+        (key(PROGRAM), owned_by(bpf_loader_upgradeable::ID, 36)),
+        (
+            key(PROGRAM_DATA),
+            owned_by(bpf_loader_upgradeable::ID, 40_000),
+        ),
+        (key(V4_PROGRAM), owned_by(loader_v4::ID, 10_000)),
+    ];
+    for byte in 10..21 {
+        accounts.push((key(byte), account(1, 100 * (usize::from(byte) - 9))));
+    }
+    accounts.push((key(21), account(1, 1_100)));
+    accounts
+}
+
+#[test]
+fn the_report_splits_programs_from_other_accounts() {
+    let mut store = store(&MockFetcher::new()).with_accounts(known_sizes());
+    store.label(&key(PROGRAM_DATA), "orca_program_data");
+    let report = store.memory_report();
+
+    // 100 + 200 + ... + 1 100, then another 1 100
+    let other = 100 * 66 + 1_100;
+    assert_eq!((report.accounts, report.total_bytes), (15, 50_036 + other));
+    assert_eq!(
+        (report.program_bytes, report.account_bytes),
+        (50_036, other)
+    );
+
+    let largest: Vec<(Pubkey, usize)> = report
+        .largest
+        .iter()
+        .map(|account| (account.pubkey, account.size))
+        .collect();
+    // Ties in pubkey order, and the 10 largest only
+    assert_eq!(
+        largest,
+        [
+            (key(PROGRAM_DATA), 40_000),
+            (key(V4_PROGRAM), 10_000),
+            (key(20), 1_100),
+            (key(21), 1_100),
+            (key(19), 1_000),
+            (key(18), 900),
+            (key(17), 800),
+            (key(16), 700),
+            (key(15), 600),
+            (key(14), 500),
+        ]
+    );
+    assert_eq!(
+        report.largest[0],
+        LargeAccount {
+            pubkey: key(PROGRAM_DATA),
+            owner: bpf_loader_upgradeable::ID,
+            size: 40_000,
+            label: Some("orca_program_data".to_string()),
+        }
+    );
+    assert_eq!(report.largest[2].owner, system_program::ID);
+    assert_eq!(report.largest[2].label, None);
+}
+
+#[test]
+fn the_report_prints_for_a_failing_test() {
+    let mut labeled = store(&MockFetcher::new()).with_accounts([
+        (
+            key(PROGRAM_DATA),
+            owned_by(bpf_loader_upgradeable::ID, 3 << 20),
+        ),
+        (key(10), account(1, 512)),
+    ]);
+    labeled.label(&key(PROGRAM_DATA), "orca_program_data");
+    let data = key(PROGRAM_DATA).to_string();
+    assert_eq!(
+        labeled.memory_report().to_string(),
+        [
+            "3.0 MiB of account data in 2 accounts: 3.0 MiB in programs, 512 B in other \
+             accounts"
+                .to_string(),
+            format!(
+                "     3.0 MiB  orca_program_data ({}…{}), owned by BPF Loader v3",
+                &data[..4],
+                &data[data.len() - 4..]
+            ),
+            format!("       512 B  {}, owned by System Program", key(10)),
+        ]
+        .join("\n")
+    );
+
+    let empty = store(&MockFetcher::new()).memory_report();
+    assert_eq!(
+        empty,
+        MemoryReport {
+            accounts: 0,
+            total_bytes: 0,
+            program_bytes: 0,
+            account_bytes: 0,
+            largest: vec![],
+        }
+    );
+    assert_eq!(empty.to_string(), "no accounts cached");
+}
+
+#[tokio::test]
+async fn crossing_the_threshold_warns_once() {
+    let fetcher = MockFetcher::new().with_account(
+        key(PROGRAM_DATA),
+        owned_by(bpf_loader_upgradeable::ID, 4_096),
+    );
+    let mut store = store(&fetcher)
+        .with_memory_warning_threshold(4_096)
+        .with_accounts([(key(10), account(1, 1_000))]);
+    assert!(store.warnings().is_empty());
+
+    // The fetch takes the cache past the threshold
+    store.get_or_fetch_many(&[key(PROGRAM_DATA)]).await.unwrap();
+    let warnings = store.warnings();
+    assert_eq!(warnings.len(), 1, "{warnings:?}");
+    assert_eq!(warnings[0].code, WarningCode::CacheMemoryHigh);
+    assert_eq!(warnings[0].pubkey, Some(key(PROGRAM_DATA)));
+    assert_eq!(
+        warnings[0].message,
+        "The cache holds 5.0 KiB of account data, more than the 4.0 KiB threshold; the \
+         largest account holds 4.0 KiB, see memory_report()"
+    );
+
+    let store = store.with_accounts([(key(11), account(1, 10_000))]);
+    assert_eq!(store.warnings().len(), 1);
+
+    // A store already past it warns as soon as the threshold is set
+    let store = store.with_memory_warning_threshold(1_000);
+    assert_eq!(store.warnings().len(), 2);
+    assert_eq!(store.warnings()[1].pubkey, Some(key(11)));
+}
@@ -0,0 +1,201 @@
+//! `with_token_metadata` and `get_token_metadata`: an NFT mint's metadata and
+//! master edition come from their PDAs in one request, a fungible mint without an
+//! edition and a mint without either aren't errors, and the metadata reads back
+//! without its NUL padding.
+
+mod common;
+
+use {
+    common::{account, store},
+    mollusk_on_demand::{
+        known_programs::TOKEN_METADATA, testing::MockFetcher, RpcError, TokenMetadata,
+        TokenMetadataAccounts, TokenStateError,
+    },
+    solana_account::Account,
+    solana_pubkey::Pubkey,
+};
+
+const LAMPORTS: u64 = 1_000_000_000;
+
+fn key(byte: u8) -> Pubkey {
+    Pubkey::new_from_array([byte; 32])
+}
+
+const NFT: u8 = 1;
+const FUNGIBLE: u8 = 2;
+const BARE: u8 = 3;
+const AUTHORITY: u8 = 4;
+
+fn metadata_pda(mint: &Pubkey) -> Pubkey {
+    let program = TOKEN_METADATA.id;
+    Pubkey::find_program_address(&[b"metadata", program.as_ref(), mint.as_ref()], &program).0
+}
+
+fn edition_pda(mint: &Pubkey) -> Pubkey {
+    let program = TOKEN_METADATA.id;
+    let seeds: &[&[u8]] = &[b"metadata", program.as_ref(), mint.as_ref(), b"edition"];
+    Pubkey::find_program_address(seeds, &program).0
+}
+
+/// A Borsh string padded with NULs to `len`, as Metaplex stores names, symbols and
+/// URIs.
+fn padded(string: &str, len: usize) -> Vec<u8> {
+    let mut bytes = (len as u32).to_le_bytes().to_vec();
+    bytes.extend_from_slice(string.as_bytes());
+    bytes.resize(4 + len, 0);
+    bytes
+}
+
+/// A `MetadataV1` account of `mint`, followed by the fields this crate doesn't read.
+fn metadata(mint: &Pubkey, name: &str, symbol: &str, uri: &str) -> Account {
+    let mut data = vec![4];
+    data.extend_from_slice(key(AUTHORITY).as_ref());
+    data.extend_from_slice(mint.as_ref());
+    data.extend(padded(name, 32));
+    data.extend(padded(symbol, 10));
+    data.extend(padded(uri, 200));
+    // Seller fee basis points, no creators, primary sale, mutable
+    data.extend_from_slice(&[0xf4, 0x01, 0, 0, 1]);
+    data.resize(607, 0);
+    Account {
+        data,
+        owner: TOKEN_METADATA.id,
+        ..account(LAMPORTS, 0)
+    }
+}
+
+/// A `MasterEditionV2` account: its key, supply and no max supply.
+fn master_edition() -> Account {
+    Account {
+        data: [&[6][..], &[0; 8], &[0]].concat(),
+        owner: TOKEN_METADATA.id,
+        ..account(LAMPORTS, 0)
+    }
+}
+
+/// A mock serving the NFT's metadata and edition, and the fungible mint's metadata.
+fn metaplex() -> MockFetcher {
+    MockFetcher::new().with_accounts([
+        (
+            metadata_pda(&key(NFT)),
+            metadata(&key(NFT), "Mad Lad #42", "MAD", "https://x/42"),
+        ),
+        (edition_pda(&key(NFT)), master_edition()),
+        (
+            metadata_pda(&key(FUNGIBLE)),
+            metadata(&key(FUNGIBLE), "USD Coin", "USDC", "https://x/usdc"),
+        ),
+    ])
+}
+
+#[tokio::test]
+async fn an_nft_has_its_metadata_and_edition_fetched_together() {
+    let fetcher = metaplex();
+    let mut store = store(&fetcher);
+    let nft = store.with_token_metadata(&key(NFT)).await.unwrap();
+    assert_eq!(
+        nft,
+        TokenMetadataAccounts {
+            mint: key(NFT),
+            metadata: metadata_pda(&key(NFT)),
+            edition: edition_pda(&key(NFT)),
+            has_metadata: true,
+            has_edition: true,
+        }
+    );
+    // One request for the two PDAs, none for the mint
+    assert_eq!(fetcher.calls().len(), 1);
+    assert_eq!(fetcher.requested_pubkeys(), [nft.metadata, nft.edition]);
+    assert_eq!(store.cache[&nft.edition], master_edition());
+
+    assert_eq!(
+        store.get_token_metadata(&key(NFT)).unwrap(),
+        TokenMetadata {
+            update_authority: key(AUTHORITY),
+            mint: key(NFT),
+            name: "Mad Lad #42".to_string(),
+            symbol: "MAD".to_string(),
+            uri: "https://x/42".to_string(),
+        }
+    );
+
+    // Cached, so not fetched again
+    fetcher.clear_calls();
+    assert_eq!(store.with_token_metadata(&key(NFT)).await.unwrap(), nft);
+    assert!(fetcher.calls().is_empty());
+}
+
+#[tokio::test]
+async fn missing_accounts_are_reported_instead_of_failing() {
+    let fetcher = metaplex();
+    let mut store = store(&fetcher);
+    let fungible = store.with_token_metadata(&key(FUNGIBLE)).await.unwrap();
+    assert!(
+        fungible.has_metadata && !fungible.has_edition,
+        "{fungible:?}"
+    );
+    assert_eq!(
+        store.get_token_metadata(&key(FUNGIBLE)).unwrap().symbol,
+        "USDC"
+    );
+
+    let bare = store.with_token_metadata(&key(BARE)).await.unwrap();
+    assert!(!bare.has_metadata && !bare.has_edition, "{bare:?}");
+    assert!(!store.cache.contains_key(&bare.metadata));
+    assert!(!store.cache.contains_key(&bare.edition));
+    let error = store.get_token_metadata(&key(BARE)).unwrap_err();
+    let RpcError::AccountNotFound(pubkey) = &error else {
+        panic!("expected AccountNotFound, got {error}");
+    };
+    assert_eq!(*pubkey, bare.metadata);
+}
+
+#[test]
+fn accounts_that_arent_metadata_are_errors() {
+    let mut wrong_key = metadata(&key(NFT), "Mad Lad #42", "MAD", "https://x/42");
+    wrong_key.data[0] = 6;
+    let mut truncated = metadata(&key(FUNGIBLE), "USD Coin", "USDC", "https://x/usdc");
+    truncated.data.truncate(1 + 64 + 36 + 14 + 100);
+    let not_metaplex = Account {
+        owner: key(9),
+        ..metadata(&key(BARE), "Fake", "FAKE", "")
+    };
+    let store = store(&MockFetcher::new()).with_accounts([
+        (metadata_pda(&key(NFT)), wrong_key),
+        (metadata_pda(&key(FUNGIBLE)), truncated),
+        (metadata_pda(&key(BARE)), not_metaplex),
+    ]);
+
+    for (mint, expected) in [
+        (
+            NFT,
+            TokenStateError::Malformed("account key 6, expected 4 (MetadataV1)".to_string()),
+        ),
+        (
+            FUNGIBLE,
+            TokenStateError::Malformed("uri is truncated".to_string()),
+        ),
+        (
+            BARE,
+            TokenStateError::WrongOwner {
+                expected: vec![TOKEN_METADATA.id],
+                actual: key(9),
+            },
+        ),
+    ] {
+        let error = store.get_token_metadata(&key(mint)).unwrap_err();
+        let RpcError::InvalidTokenState {
+            pubkey,
+            state,
+            reason,
+        } = &error
+        else {
+            panic!("expected InvalidTokenState, got {error}");
+        };
+        assert_eq!(
+            (*pubkey, *state),
+            (metadata_pda(&key(mint)), "token metadata")
+        );
+        assert_eq!(reason, &expected);
+    }
+}
@@ -0,0 +1,157 @@
+//! The fetch path against `MockFetcher`: batching, the cache, missing accounts and
+//! offline stores.
+
+mod common;
+
+use {
+    common::{account, instruction, pubkeys, serving, store},
+    mollusk_on_demand::{testing::FetcherCall, Provenance, RpcAccountStore, RpcError},
+    solana_rpc_client_api::request::MAX_MULTIPLE_ACCOUNTS,
+};
+
+#[tokio::test]
+async fn fetches_instruction_accounts_in_one_request() {
+    let keys = pubkeys(3);
+    let fetcher = serving(&keys);
+    let store = store(&fetcher)
+        .from_instruction(&instruction(&keys))
+        .await
+        .unwrap();
+
+    assert_eq!(fetcher.calls().len(), 1);
+    let mut requested = fetcher.requested_pubkeys();
+    requested.sort();
+    let mut expected = keys.clone();
+    expected.sort();
+    assert_eq!(requested, expected);
+    for (index, pubkey) in keys.iter().enumerate() {
+        assert_eq!(store.cache[pubkey].lamports, index as u64 + 1);
+        assert!(matches!(store.provenance(pubkey), Some(Provenance::Fetched { .. })));
+    }
+    assert_eq!(store.stats().requests_for("getMultipleAccounts"), 1);
+    assert_eq!(store.stats().accounts_fetched, 3);
+}
+
+#[tokio::test]
+async fn cached_accounts_are_not_requested_again() {
+    let keys = pubkeys(3);
+    let fetcher = serving(&keys);
+    let store = store(&fetcher)
+        .from_instruction(&instruction(&keys[..2]))
+        .await
+        .unwrap();
+    fetcher.clear_calls();
+
+    let store = store.from_instruction(&instruction(&keys)).await.unwrap();
+    assert_eq!(fetcher.calls(), vec![FetcherCall::GetMultipleAccounts(vec![keys[2]])]);
+    assert_eq!(store.stats().accounts_from_cache, 2);
+}
+
+#[tokio::test]
+async fn mocked_accounts_are_never_requested() {
+    let keys = pubkeys(2);
+    let fetcher = serving(&keys[1..]);
+    let store = store(&fetcher)
+        .with_accounts([(keys[0], account(42, 0))])
+        .from_instruction(&instruction(&keys))
+        .await
+        .unwrap();
+
+    assert_eq!(fetcher.requested_pubkeys(), vec![keys[1]]);
+    assert_eq!(store.cache[&keys[0]].lamports, 42);
+    assert_eq!(store.provenance(&keys[0]), Some(&Provenance::Mocked));
+}
+
+#[tokio::test]
+async fn large_fetches_are_chunked() {
+    let keys = pubkeys(MAX_MULTIPLE_ACCOUNTS * 2 + 1);
+    let fetcher = serving(&keys);
+    let store = store(&fetcher)
+        .from_instruction(&instruction(&keys))
+        .await
+        .unwrap();
+
+    let chunks: Vec<usize> = fetcher
+        .calls()
+        .iter()
+        .map(|call| match call {
+            FetcherCall::GetMultipleAccounts(chunk) => chunk.len(),
+            call => panic!("unexpected call {call:?}"),
+        })
+        .collect();
+    assert_eq!(chunks.len(), 3);
+    assert!(chunks.iter().all(|len| *len <= MAX_MULTIPLE_ACCOUNTS));
+    assert_eq!(chunks.iter().sum::<usize>(), keys.len());
+    assert_eq!(store.cache.len(), keys.len());
+}
+
+#[tokio::test]
+async fn missing_accounts_fail_unless_allowed() {
+    let keys = pubkeys(2);
+    let fetcher = serving(&keys[..1]);
+    let error = store(&fetcher)
+        .get_or_fetch_many(&keys)
+        .await
+        .map(|_| ())
+        .unwrap_err();
+    assert!(matches!(error, RpcError::AccountNotFound(pubkey) if pubkey == keys[1]));
+
+    let mut store = store(&fetcher).allow_missing_accounts();
+    let accounts = store.get_or_fetch_many(&keys).await.unwrap();
+    assert_eq!(accounts[0].lamports, 1);
+    assert_eq!(accounts[1].lamports, 0);
+}
+
+#[tokio::test]
+async fn several_missing_accounts_are_reported_together() {
+    let keys = pubkeys(3);
+    let fetcher = serving(&keys[..1]);
+    let error = store(&fetcher)
+        .get_or_fetch_many(&keys)
+        .await
+        .map(|_| ())
+        .unwrap_err();
+    let RpcError::AccountsNotFound(mut missing) = error else {
+        panic!("expected AccountsNotFound, got {error}");
+    };
+    missing.sort();
+    let mut expected = keys[1..].to_vec();
+    expected.sort();
+    assert_eq!(missing, expected);
+}
+
+#[tokio::test]
+async fn get_or_fetch_many_keeps_the_requested_order() {
+    let keys = pubkeys(4);
+    let fetcher = serving(&keys);
+    let mut store = store(&fetcher);
+    let reversed: Vec<_> = keys.iter().rev().copied().collect();
+    let lamports: Vec<u64> = store
+        .get_or_fetch_many(&reversed)
+        .await
+        .unwrap()
+        .iter()
+        .map(|account| account.lamports)
+        .collect();
+    assert_eq!(lamports, vec![4, 3, 2, 1]);
+}
+
+#[tokio::test]
+async fn offline_stores_make_no_requests() {
+    let keys = pubkeys(1);
+    let fetcher = serving(&keys);
+    let error = store(&fetcher)
+        .offline()
+        .from_instruction(&instruction(&keys))
+        .await
+        .map(|_| ())
+        .unwrap_err();
+    assert!(matches!(error, RpcError::Offline { .. }), "{error}");
+    assert!(fetcher.calls().is_empty());
+}
+
+#[tokio::test]
+async fn stores_with_a_url_keep_working() {
+    let store = RpcAccountStore::new("http://localhost:8899");
+    assert_eq!(store.rpc_url(), Some("http://localhost:8899"));
+}
@@ -0,0 +1,142 @@
+//! `strict_mocks`: a mock of an account that exists on-chain with other contents is
+//! reported, with what differs, while fictional and faithful mocks pass. The check
+//! rides along the next fetch, once per mock, and only when asked for.
+
+mod common;
+
+use {
+    common::{account, instruction, store},
+    mollusk_on_demand::{testing::MockFetcher, RpcError, ShadowedMock, WarningCode},
+    solana_account::Account,
+    solana_pubkey::Pubkey,
+    solana_sdk_ids::system_program,
+};
+
+const LAMPORTS: u64 = 1_000_000_000;
+
+/// A mock serving `mint` as an 82-byte account and `faithful` and `fetched` as empty
+/// ones, with the mocks a test would set up: `mint` as a wallet, `faithful` as it
+/// is on-chain, and `fictional`, which isn't on-chain.
+fn mocked() -> (MockFetcher, [Pubkey; 4], [(Pubkey, Account); 3]) {
+    let [mint, faithful, fictional, fetched] = [(); 4].map(|()| Pubkey::new_unique());
+    let on_chain_mint = Account {
+        owner: Pubkey::new_unique(),
+        ..account(LAMPORTS, 82)
+    };
+    let fetcher = MockFetcher::new()
+        .with_account(mint, on_chain_mint)
+        .with_account(faithful, account(LAMPORTS, 0))
+        .with_account(fetched, account(LAMPORTS, 0));
+    let mocks = [
+        (mint, account(2 * LAMPORTS, 0)),
+        (faithful, account(LAMPORTS, 0)),
+        (fictional, account(LAMPORTS, 0)),
+    ];
+    (fetcher, [mint, faithful, fictional, fetched], mocks)
+}
+
+#[tokio::test]
+async fn a_shadowing_mock_is_reported() {
+    let (fetcher, [mint, faithful, fictional, fetched], mocks) = mocked();
+    let store = store(&fetcher)
+        .with_accounts(mocks)
+        .strict_mocks()
+        .from_instruction(&instruction(&[fetched]))
+        .await
+        .unwrap();
+
+    let warnings = store.warnings();
+    assert_eq!(warnings.len(), 1, "{warnings:?}");
+    assert_eq!(warnings[0].code, WarningCode::MockShadowsAccount);
+    assert_eq!(warnings[0].pubkey, Some(mint));
+    let message = &warnings[0].message;
+    assert!(message.contains(&format!("lamports {} vs {LAMPORTS}", 2 * LAMPORTS)), "{message}");
+    assert!(message.contains(&format!("owner {}", system_program::ID)), "{message}");
+    assert!(message.contains("data 0 vs 82 bytes"), "{message}");
+    // The mock still wins
+    assert_eq!(store.cache[&mint].lamports, 2 * LAMPORTS);
+
+    // Checked in the same batch as the fetch
+    assert_eq!(fetcher.calls().len(), 1);
+    let mut requested = fetcher.requested_pubkeys();
+    requested.sort();
+    let mut expected = vec![mint, faithful, fictional, fetched];
+    expected.sort();
+    assert_eq!(requested, expected);
+}
+
+#[tokio::test]
+async fn a_fictional_mock_passes() {
+    let (fetcher, [_, _, fictional, fetched], mocks) = mocked();
+    let store = store(&fetcher)
+        .with_accounts([mocks[2].clone()])
+        .strict_mocks()
+        .from_instruction(&instruction(&[fetched]))
+        .await
+        .unwrap();
+
+    assert!(store.warnings().is_empty(), "{:?}", store.warnings());
+    assert!(fetcher.requested_pubkeys().contains(&fictional));
+    assert_eq!(store.cache[&fictional].lamports, LAMPORTS);
+}
+
+#[tokio::test]
+async fn deny_shadowed_mocks_fails_the_fetch() {
+    let (fetcher, [mint, _, _, fetched], mocks) = mocked();
+    let error = store(&fetcher)
+        .with_accounts(mocks)
+        .deny_shadowed_mocks()
+        .from_instruction(&instruction(&[fetched]))
+        .await
+        .map(|_| ())
+        .unwrap_err();
+
+    let RpcError::ShadowedMocks(shadowed) = &error else {
+        panic!("expected ShadowedMocks, got {error}");
+    };
+    assert_eq!(shadowed.len(), 1);
+    let ShadowedMock {
+        pubkey,
+        lamports,
+        data_len,
+        ..
+    } = &shadowed[0];
+    assert_eq!(*pubkey, mint);
+    assert_eq!(*lamports, (2 * LAMPORTS, LAMPORTS));
+    assert_eq!(*data_len, (0, 82));
+    assert_eq!(error.code(), "shadowed_mocks");
+}
+
+#[tokio::test]
+async fn mocks_are_checked_once_and_only_when_asked() {
+    let (fetcher, [mint, faithful, fictional, fetched], mocks) = mocked();
+    let store = store(&fetcher)
+        .with_accounts(mocks)
+        .from_instruction(&instruction(&[fetched]))
+        .await
+        .unwrap();
+    assert_eq!(fetcher.requested_pubkeys(), [fetched]);
+    assert!(store.warnings().is_empty());
+
+    fetcher.clear_calls();
+    let other = Pubkey::new_unique();
+    fetcher.set_account(other, account(LAMPORTS, 0));
+    let store = store
+        .strict_mocks()
+        .from_instruction(&instruction(&[other]))
+        .await
+        .unwrap();
+    let mut requested = fetcher.requested_pubkeys();
+    requested.sort();
+    let mut expected = vec![mint, faithful, fictional, other];
+    expected.sort();
+    assert_eq!(requested, expected);
+
+    // A later fetch doesn't check them again
+    fetcher.clear_calls();
+    let last = Pubkey::new_unique();
+    fetcher.set_account(last, account(LAMPORTS, 0));
+    let store = store.from_instruction(&instruction(&[last])).await.unwrap();
+    assert_eq!(fetcher.requested_pubkeys(), [last]);
+    assert_eq!(store.warnings().len(), 1);
+}
@@ -0,0 +1,63 @@
+//! `#[mollusk_test]` end to end: the test runs with a working store and Mollusk,
+//! and fails if the store recorded warnings unless they're allowed.
+
+#![cfg(feature = "macros")]
+
+mod common;
+
+use {
+    common::account,
+    mollusk_on_demand::{mollusk_test, RpcAccountStore, RpcError},
+    mollusk_svm::Mollusk,
+    solana_instruction::{AccountMeta, Instruction},
+    solana_pubkey::Pubkey,
+    solana_sdk_ids::system_program,
+};
+
+/// A system program transfer of `lamports` from `from` to `to`.
+fn transfer(from: &Pubkey, to: &Pubkey, lamports: u64) -> Instruction {
+    let mut data = 2u32.to_le_bytes().to_vec();
+    data.extend_from_slice(&lamports.to_le_bytes());
+    Instruction::new_with_bytes(
+        system_program::ID,
+        &data,
+        vec![AccountMeta::new(*from, true), AccountMeta::new(*to, false)],
+    )
+}
+
+#[mollusk_test(rpc = "localnet")]
+async fn the_test_runs_with_the_store_and_mollusk(
+    mollusk: &mut Mollusk,
+    store: &mut RpcAccountStore,
+) -> Result<(), RpcError> {
+    let (from, to) = (Pubkey::new_unique(), Pubkey::new_unique());
+    store.extend([(from, account(1_000_000_000, 0)), (to, account(1_000_000_000, 0))]);
+
+    let instruction = transfer(&from, &to, 1_000);
+    let accounts = store.instruction_accounts(mollusk, &instruction)?;
+    let result = mollusk.process_instruction(&instruction, &accounts);
+    assert!(result.program_result.is_ok(), "{:?}", result.program_result);
+    store.apply_result(&result);
+    assert_eq!(store.cache[&to].lamports, 1_000_001_000);
+    assert_eq!(store.stats().total_requests(), 0);
+    Ok(())
+}
+
+/// Mock one account twice, which the store warns about.
+fn replace_a_mock(store: &mut RpcAccountStore) {
+    let pubkey = Pubkey::new_unique();
+    store.extend([(pubkey, account(1_000_000_000, 0))]);
+    store.extend([(pubkey, account(2_000_000_000, 0))]);
+    assert_eq!(store.warnings().len(), 1);
+}
+
+#[mollusk_test(rpc = "localnet")]
+#[should_panic(expected = "mollusk_test")]
+async fn warnings_fail_the_test(store: &mut RpcAccountStore) {
+    replace_a_mock(store);
+}
+
+#[mollusk_test(rpc = "localnet", allow_warnings)]
+async fn allowed_warnings_dont_fail_the_test(store: &mut RpcAccountStore) {
+    replace_a_mock(store);
+}
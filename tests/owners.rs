@@ -0,0 +1,203 @@
+//! `verify_owners_loaded`: a writable account whose owner program isn't loaded is
+//! reported with a pointer to `with_owner_programs`, which fetches the owner so
+//! `add_programs` loads it. Builtins, readonly accounts and sysvars don't count.
+//!
+//! `assert_owner` and `assert_owners` catch cached accounts of the wrong program,
+//! such as a Token-2022 account set up as an SPL Token one, naming the actual owner,
+//! and `accounts_by_owner` queries the cache. None of them makes a request.
+
+mod common;
+
+use {
+    common::{account, store},
+    mollusk_on_demand::{testing::MockFetcher, RpcError},
+    mollusk_svm::Mollusk,
+    mollusk_svm_programs_token::{token, token2022},
+    solana_account::Account,
+    solana_instruction::{AccountMeta, Instruction},
+    solana_pubkey::Pubkey,
+    solana_rent::Rent,
+    solana_sdk_ids::{bpf_loader_upgradeable, sysvar},
+};
+
+/// An upgradeable program holding the Token-2022 ELF, at a fresh address.
+fn program() -> [(Pubkey, Account); 2] {
+    let (program, program_data) = (Pubkey::new_unique(), Pubkey::new_unique());
+    let mut data = 2u32.to_le_bytes().to_vec();
+    data.extend_from_slice(program_data.as_ref());
+    let program_account = Account {
+        lamports: Rent::default().minimum_balance(data.len()),
+        data,
+        executable: true,
+        owner: bpf_loader_upgradeable::ID,
+        ..account(0, 0)
+    };
+
+    // The ProgramData header: tag, deployment slot and upgrade authority
+    let mut data = 3u32.to_le_bytes().to_vec();
+    data.extend_from_slice(&0u64.to_le_bytes());
+    data.extend_from_slice(&[0; 33]);
+    data.extend_from_slice(token2022::ELF);
+    let program_data_account = Account {
+        lamports: Rent::default().minimum_balance(data.len()),
+        data,
+        owner: bpf_loader_upgradeable::ID,
+        ..account(0, 0)
+    };
+    [(program, program_account), (program_data, program_data_account)]
+}
+
+/// A rent-exempt account of `owner`.
+fn owned_by(owner: Pubkey) -> Account {
+    Account {
+        owner,
+        ..account(Rent::default().minimum_balance(8), 8)
+    }
+}
+
+#[tokio::test]
+async fn an_unloaded_owner_of_a_writable_account_is_reported() {
+    let (pool, vault, oracle, wallet) = (
+        Pubkey::new_unique(),
+        Pubkey::new_unique(),
+        Pubkey::new_unique(),
+        Pubkey::new_unique(),
+    );
+    let (owner, read_only_owner) = (Pubkey::new_unique(), Pubkey::new_unique());
+    let fetcher = MockFetcher::new()
+        .with_account(pool, owned_by(owner))
+        .with_account(vault, owned_by(token::ID))
+        .with_account(oracle, owned_by(read_only_owner))
+        .with_account(wallet, account(1_000_000_000, 0))
+        .with_account(sysvar::clock::ID, owned_by(sysvar::ID));
+    let ix = Instruction::new_with_bytes(
+        Pubkey::new_unique(),
+        &[],
+        vec![
+            AccountMeta::new(pool, false),
+            AccountMeta::new(vault, false),
+            AccountMeta::new_readonly(oracle, false),
+            AccountMeta::new(wallet, true),
+            AccountMeta::new(sysvar::clock::ID, false),
+        ],
+    );
+    let store = store(&fetcher).from_instruction(&ix).await.unwrap();
+    let mut mollusk = Mollusk::default();
+    token::add_program(&mut mollusk);
+
+    let error = store.verify_owners_loaded(&mollusk).unwrap_err();
+    let RpcError::OwnerProgramsNotLoaded(missing) = &error else {
+        panic!("expected OwnerProgramsNotLoaded, got {error}");
+    };
+    assert_eq!(*missing, [owner]);
+    assert_eq!(error.code(), "owner_programs_not_loaded");
+    let message = error.to_string();
+    assert!(message.contains(&owner.to_string()), "{message}");
+    assert!(message.contains("with_owner_programs()"), "{message}");
+}
+
+#[tokio::test]
+async fn with_owner_programs_fetches_the_owners_for_add_programs() {
+    let [(owner, program_account), (program_data, program_data_account)] = program();
+    let pool = Pubkey::new_unique();
+    let fetcher = MockFetcher::new()
+        .with_account(pool, owned_by(owner))
+        .with_account(owner, program_account)
+        .with_account(program_data, program_data_account);
+    let ix = Instruction::new_with_bytes(owner, &[], vec![AccountMeta::new(pool, false)]);
+    let mut mollusk = Mollusk::default();
+
+    let store = store(&fetcher).from_instruction(&ix).await.unwrap();
+    assert!(store.verify_owners_loaded(&mollusk).is_err());
+
+    let store = store
+        .with_owner_programs()
+        .await
+        .unwrap()
+        .add_programs(&mut mollusk)
+        .await
+        .unwrap();
+    assert!(store.cache.contains_key(&owner));
+    store.verify_owners_loaded(&mollusk).unwrap();
+}
+
+#[tokio::test]
+async fn owners_of_readonly_accounts_are_not_required() {
+    let oracle = Pubkey::new_unique();
+    let fetcher = MockFetcher::new().with_account(oracle, owned_by(Pubkey::new_unique()));
+    let ix = Instruction::new_with_bytes(
+        Pubkey::new_unique(),
+        &[],
+        vec![AccountMeta::new_readonly(oracle, false)],
+    );
+
+    let store = store(&fetcher).from_instruction(&ix).await.unwrap();
+    store.verify_owners_loaded(&Mollusk::default()).unwrap();
+}
+
+#[test]
+fn a_token_2022_account_is_not_an_spl_token_one() {
+    let vault = Pubkey::new_unique();
+    let fetcher = MockFetcher::new();
+    let store = store(&fetcher)
+        .with_accounts([(vault, owned_by(token2022::ID))])
+        .with_labels(&[(vault, "vault")]);
+
+    let error = store.assert_owner(&vault, &token::ID).unwrap_err();
+    let RpcError::WrongOwners(mismatches) = &error else {
+        panic!("expected WrongOwners, got {error}");
+    };
+    assert_eq!(mismatches.len(), 1);
+    assert_eq!((mismatches[0].expected, mismatches[0].actual), (token::ID, token2022::ID));
+    assert_eq!(
+        error.to_string(),
+        format!(
+            "[{}] Accounts have unexpected owners: {} is owned by {} (SPL Token-2022), expected \
+             {} (SPL Token)",
+            error.code(),
+            store.display_pubkey(&vault),
+            token2022::ID,
+            token::ID,
+        )
+    );
+    store.assert_owner(&vault, &token2022::ID).unwrap();
+    assert!(fetcher.calls().is_empty());
+}
+
+#[test]
+fn assert_owners_reports_every_mismatch() {
+    let [mint, vault, wallet, missing] = [(); 4].map(|()| Pubkey::new_unique());
+    let store = store(&MockFetcher::new()).with_accounts([
+        (mint, owned_by(token::ID)),
+        (vault, owned_by(token::ID)),
+        (wallet, owned_by(token2022::ID)),
+    ]);
+
+    let error = store
+        .assert_owners(&[(mint, token::ID), (vault, token2022::ID), (wallet, token::ID)])
+        .unwrap_err();
+    let RpcError::WrongOwners(mismatches) = &error else {
+        panic!("expected WrongOwners, got {error}");
+    };
+    let wrong: Vec<Pubkey> = mismatches.iter().map(|mismatch| mismatch.pubkey).collect();
+    assert_eq!(wrong, [vault, wallet]);
+
+    // Missing accounts come first
+    let error = store.assert_owners(&[(vault, token::ID), (missing, token::ID)]).unwrap_err();
+    assert!(matches!(error, RpcError::AccountNotFound(pubkey) if pubkey == missing), "{error}");
+}
+
+#[test]
+fn accounts_by_owner_queries_the_cache() {
+    let mut tokens = [(); 3].map(|()| Pubkey::new_unique());
+    let store = store(&MockFetcher::new())
+        .with_accounts(tokens.map(|pubkey| (pubkey, owned_by(token::ID))))
+        .with_accounts([(Pubkey::new_unique(), owned_by(token2022::ID))]);
+
+    tokens.sort();
+    let found: Vec<Pubkey> =
+        store.accounts_by_owner(&token::ID).into_iter().map(|(pubkey, _)| pubkey).collect();
+    assert_eq!(found, tokens);
+    assert_eq!(store.accounts_by_owner(&token2022::ID).len(), 1);
+    assert!(store.accounts_by_owner(&Pubkey::new_unique()).is_empty());
+}
@@ -0,0 +1,148 @@
+//! Which account wins when a mock and the chain collide, for every kind of mock,
+//! added before or after the fetch, with the account on-chain or not: plain mocks
+//! win over the fetch and warn when they replace it, overrides always win, and
+//! fallbacks only stand in for accounts the chain doesn't have.
+
+mod common;
+
+use {
+    common::{account, instruction, store},
+    mollusk_on_demand::{testing::MockFetcher, Provenance, RpcAccountStore, WarningCode},
+    solana_account::Account,
+    solana_pubkey::Pubkey,
+};
+
+const CHAIN: u64 = 1_000_000_000;
+const MOCK: u64 = 2_000_000_000;
+
+#[derive(Debug, Clone, Copy)]
+enum Mock {
+    Plain,
+    Override,
+    Fallback,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Order {
+    BeforeFetch,
+    AfterFetch,
+}
+
+fn add(store: RpcAccountStore, mock: Mock, pubkey: Pubkey) -> RpcAccountStore {
+    let accounts = [(pubkey, account(MOCK, 0))];
+    match mock {
+        Mock::Plain => store.with_accounts(accounts),
+        Mock::Override => store.with_accounts_override(accounts),
+        Mock::Fallback => store.with_accounts_fallback(accounts),
+    }
+}
+
+/// Add `mock` in `order` relative to a fetch of its pubkey, which is on-chain if
+/// `on_chain`, returning the store. The fetch is lenient, so a missing account the
+/// mock doesn't cover is only recorded.
+async fn collide(mock: Mock, order: Order, on_chain: bool) -> RpcAccountStore {
+    let pubkey = Pubkey::new_unique();
+    let mut fetcher = MockFetcher::new();
+    if on_chain {
+        fetcher = fetcher.with_account(pubkey, account(CHAIN, 0));
+    }
+    let mut store = store(&fetcher).lenient();
+    if let Order::BeforeFetch = order {
+        store = add(store, mock, pubkey);
+    }
+    store = store.from_instruction(&instruction(&[pubkey])).await.unwrap();
+    if let Order::AfterFetch = order {
+        store = add(store, mock, pubkey);
+    }
+    store
+}
+
+fn only(store: &RpcAccountStore) -> (Option<&Account>, Option<&Provenance>) {
+    assert!(store.cache.len() <= 1, "{:?}", store.cache);
+    let pubkey = store.cache.keys().next();
+    (
+        pubkey.map(|pubkey| &store.cache[pubkey]),
+        pubkey.and_then(|pubkey| store.provenance(pubkey)),
+    )
+}
+
+#[tokio::test]
+async fn every_ordering_has_the_expected_winner() {
+    use {Mock::*, Order::*};
+
+    // (mock, order, on-chain, winning lamports and provenance, whether the mock
+    // replaced a fetched account)
+    let cases = [
+        (Plain, BeforeFetch, true, MOCK, "mocked", false),
+        (Plain, BeforeFetch, false, MOCK, "mocked", false),
+        (Plain, AfterFetch, true, MOCK, "mocked", true),
+        (Plain, AfterFetch, false, MOCK, "mocked", false),
+        (Override, BeforeFetch, true, MOCK, "override", false),
+        (Override, BeforeFetch, false, MOCK, "override", false),
+        (Override, AfterFetch, true, MOCK, "override", false),
+        (Override, AfterFetch, false, MOCK, "override", false),
+        (Fallback, BeforeFetch, true, CHAIN, "fetched", false),
+        (Fallback, BeforeFetch, false, MOCK, "mocked", false),
+        (Fallback, AfterFetch, true, CHAIN, "fetched", false),
+        // Waits for the next fetch to check the chain
+        (Fallback, AfterFetch, false, MOCK, "fallback", false),
+    ];
+    for (mock, order, on_chain, winner, expected, replaced) in cases {
+        let case = format!("{mock:?} {order:?}, on-chain: {on_chain}");
+        let store = collide(mock, order, on_chain).await;
+        let (account, provenance) = only(&store);
+        assert_eq!(account.map(|account| account.lamports), Some(winner), "{case}");
+        let provenance = match provenance {
+            Some(Provenance::Fetched { .. }) => "fetched",
+            Some(Provenance::Override) => "override",
+            Some(Provenance::Mocked) => "mocked",
+            Some(Provenance::Fallback) => "fallback",
+            other => panic!("{case}: unexpected provenance {other:?}"),
+        };
+        assert_eq!(provenance, expected, "{case}");
+
+        let replaced_warnings = store
+            .warnings()
+            .iter()
+            .filter(|warning| warning.code == WarningCode::MockReplacesAccount)
+            .count();
+        assert_eq!(replaced_warnings, usize::from(replaced), "{case}: {:?}", store.warnings());
+    }
+}
+
+#[tokio::test]
+async fn a_replacing_mock_names_what_it_replaced() {
+    let store = collide(Mock::Plain, Order::AfterFetch, true).await;
+    let warnings = store.warnings();
+    assert_eq!(warnings.len(), 1, "{warnings:?}");
+    assert_eq!(warnings[0].message, "with_accounts replaced a fetched account");
+}
+
+#[tokio::test]
+async fn a_fallback_needs_no_allow_missing_accounts() {
+    let pubkey = Pubkey::new_unique();
+    let store = store(&MockFetcher::new())
+        .with_accounts_fallback([(pubkey, account(MOCK, 0))])
+        .from_instruction(&instruction(&[pubkey]))
+        .await
+        .unwrap();
+    assert_eq!(store.cache[&pubkey].lamports, MOCK);
+    assert!(store.errors().is_empty());
+}
+
+#[tokio::test]
+async fn an_override_survives_a_refresh() {
+    let pubkey = Pubkey::new_unique();
+    let fetcher = MockFetcher::new().with_account(pubkey, account(CHAIN, 0));
+    let mut store = store(&fetcher)
+        .with_accounts_override([(pubkey, account(MOCK, 0))])
+        .from_instruction(&instruction(&[pubkey]))
+        .await
+        .unwrap();
+
+    let report = store.verify_fresh(&[pubkey]).await.unwrap();
+    assert!(!report.is_fresh());
+    store.refresh_drifted(&report);
+    assert_eq!(store.cache[&pubkey].lamports, MOCK);
+    assert_eq!(store.provenance(&pubkey), Some(&Provenance::Override));
+}
@@ -0,0 +1,177 @@
+//! `prefetch_program_data`: an instruction reaching two BPF Loader v3 programs has
+//! their program data fetched with its accounts, in one request right after them,
+//! so `add_programs` loads both without a request of its own.
+
+mod common;
+
+use {
+    common::{account, store},
+    mollusk_on_demand::{
+        testing::{FetcherCall, MockFetcher},
+        RpcAccountStore, RpcError,
+    },
+    mollusk_svm::Mollusk,
+    mollusk_svm_programs_token::{token, token2022},
+    solana_account::Account,
+    solana_instruction::{AccountMeta, Instruction},
+    solana_pubkey::Pubkey,
+    solana_rent::Rent,
+    solana_sdk_ids::bpf_loader_upgradeable,
+};
+
+const LAMPORTS: u64 = 1_000_000_000;
+
+/// A router calling into both token programs, for a pool and its vault.
+const ROUTER: Pubkey = Pubkey::new_from_array([9; 32]);
+const POOL: Pubkey = Pubkey::new_from_array([1; 32]);
+const VAULT: Pubkey = Pubkey::new_from_array([2; 32]);
+
+fn program_data_address(program: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[program.as_ref()], &bpf_loader_upgradeable::ID).0
+}
+
+/// The program and program data accounts of a BPF Loader v3 program of `elf`.
+fn upgradeable(program: Pubkey, elf: &[u8]) -> [(Pubkey, Account); 2] {
+    let program_data = program_data_address(&program);
+    let mut data = 2u32.to_le_bytes().to_vec();
+    data.extend_from_slice(program_data.as_ref());
+    let program_account = Account {
+        data,
+        executable: true,
+        owner: bpf_loader_upgradeable::ID,
+        ..account(LAMPORTS, 0)
+    };
+    // Deployed at slot 0 without an upgrade authority
+    let mut data = 3u32.to_le_bytes().to_vec();
+    data.extend_from_slice(&[0; 9]);
+    data.resize(45, 0);
+    data.extend_from_slice(elf);
+    let program_data_account = Account {
+        lamports: Rent::default().minimum_balance(data.len()),
+        data,
+        owner: bpf_loader_upgradeable::ID,
+        ..account(0, 0)
+    };
+    [
+        (program, program_account),
+        (program_data, program_data_account),
+    ]
+}
+
+fn chain() -> MockFetcher {
+    MockFetcher::new()
+        .with_accounts(upgradeable(token::ID, token::ELF))
+        .with_accounts(upgradeable(token2022::ID, token2022::ELF))
+        .with_accounts([
+            (POOL, account(LAMPORTS, 64)),
+            (VAULT, account(LAMPORTS, 64)),
+        ])
+}
+
+fn instruction() -> Instruction {
+    Instruction::new_with_bytes(
+        ROUTER,
+        &[],
+        vec![
+            AccountMeta::new(POOL, false),
+            AccountMeta::new(VAULT, false),
+            AccountMeta::new_readonly(token::ID, false),
+            AccountMeta::new_readonly(token2022::ID, false),
+        ],
+    )
+}
+
+/// The requests of `from_instruction` and those of `add_programs` after it.
+async fn requests(store: RpcAccountStore, fetcher: &MockFetcher) -> [Vec<FetcherCall>; 2] {
+    fetcher.clear_calls();
+    let store = store.from_instruction(&instruction()).await.unwrap();
+    let fetched = fetcher.calls();
+    fetcher.clear_calls();
+    let mut mollusk = Mollusk::default();
+    store.add_programs(&mut mollusk).await.unwrap();
+    for program in [token::ID, token2022::ID] {
+        assert!(
+            mollusk.program_cache.load_program(&program).is_some(),
+            "{program}"
+        );
+    }
+    [fetched, fetcher.calls()]
+}
+
+/// The pubkeys of a `getMultipleAccounts` request, sorted.
+fn requested(call: &FetcherCall) -> Vec<Pubkey> {
+    let FetcherCall::GetMultipleAccounts(pubkeys) = call else {
+        panic!("expected getMultipleAccounts, got {call:?}");
+    };
+    let mut pubkeys = pubkeys.clone();
+    pubkeys.sort();
+    pubkeys
+}
+
+#[tokio::test]
+async fn add_programs_makes_no_request_after_a_prefetch() {
+    let fetcher = chain();
+    let accounts = [POOL, VAULT, token::ID, token2022::ID];
+    let mut program_data = [token::ID, token2022::ID].map(|program| program_data_address(&program));
+    program_data.sort();
+
+    // Without it, add_programs asks for the program data
+    let [fetched, added] = requests(store(&fetcher), &fetcher).await;
+    assert_eq!(
+        fetched,
+        [FetcherCall::GetMultipleAccounts(accounts.to_vec())]
+    );
+    assert_eq!(added.len(), 1, "{added:?}");
+    assert_eq!(requested(&added[0]), program_data);
+
+    let [fetched, added] = requests(store(&fetcher).prefetch_program_data(), &fetcher).await;
+    assert_eq!(fetched.len(), 2, "{fetched:?}");
+    assert_eq!(
+        fetched[0],
+        FetcherCall::GetMultipleAccounts(accounts.to_vec())
+    );
+    assert_eq!(requested(&fetched[1]), program_data);
+    assert!(added.is_empty(), "{added:?}");
+}
+
+#[tokio::test]
+async fn cached_program_data_isnt_prefetched_again() {
+    let fetcher = chain();
+    let (program_data, account) = upgradeable(token2022::ID, token2022::ELF)[1].clone();
+    let store = store(&fetcher)
+        .prefetch_program_data()
+        .with_accounts([(program_data, account)]);
+    let [fetched, added] = requests(store, &fetcher).await;
+    assert_eq!(
+        fetched[1],
+        FetcherCall::GetMultipleAccounts(vec![program_data_address(&token::ID)])
+    );
+    assert!(added.is_empty(), "{added:?}");
+}
+
+#[tokio::test]
+async fn missing_program_data_is_left_for_add_programs() {
+    let fetcher = chain();
+    let missing = program_data_address(&token2022::ID);
+    fetcher.remove_account(&missing);
+    let store = store(&fetcher)
+        .prefetch_program_data()
+        .from_instruction(&instruction())
+        .await
+        .unwrap();
+    assert!(!store.cache.contains_key(&missing));
+
+    let error = store
+        .add_programs(&mut Mollusk::default())
+        .await
+        .map(|_| ())
+        .unwrap_err();
+    let RpcError::ProgramDataNotFound {
+        program,
+        program_data,
+    } = error
+    else {
+        panic!("expected ProgramDataNotFound, got {error}");
+    };
+    assert_eq!((program, program_data), (token2022::ID, missing));
+}
@@ -0,0 +1,141 @@
+//! `process_instruction_with_store` and `process_instruction_against_mainnet`: the
+//! instruction runs on the store's accounts, store errors are `Err` while failed
+//! instructions are `Ok` results, and the one-shot helper fetches over JSON-RPC.
+
+mod common;
+
+use {
+    common::{account, serve_json_rpc_requests, store},
+    mollusk_on_demand::{
+        process_instruction_against_mainnet, process_instruction_with_store, testing::MockFetcher,
+        RpcError,
+    },
+    mollusk_svm::Mollusk,
+    serde_json::json,
+    solana_instruction::{AccountMeta, Instruction},
+    solana_pubkey::Pubkey,
+    solana_sdk_ids::system_program,
+    std::{collections::HashMap, net::TcpListener},
+};
+
+const PAYER_LAMPORTS: u64 = 10_000_000_000;
+const RECIPIENT_LAMPORTS: u64 = 1_000_000_000;
+
+fn transfer(from: &Pubkey, to: &Pubkey, lamports: u64) -> Instruction {
+    let mut data = 2u32.to_le_bytes().to_vec();
+    data.extend_from_slice(&lamports.to_le_bytes());
+    Instruction::new_with_bytes(
+        system_program::ID,
+        &data,
+        vec![AccountMeta::new(*from, true), AccountMeta::new(*to, false)],
+    )
+}
+
+fn payer_and_recipient() -> (MockFetcher, Pubkey, Pubkey) {
+    let (payer, recipient) = (Pubkey::new_unique(), Pubkey::new_unique());
+    let fetcher = MockFetcher::new()
+        .with_account(payer, account(PAYER_LAMPORTS, 0))
+        .with_account(recipient, account(RECIPIENT_LAMPORTS, 0));
+    (fetcher, payer, recipient)
+}
+
+/// An endpoint serving `accounts` from `getMultipleAccounts`, as system accounts
+/// without data.
+fn serve_accounts(accounts: HashMap<Pubkey, u64>) -> String {
+    serve_json_rpc_requests(move |request| {
+        assert_eq!(request["method"], "getMultipleAccounts");
+        let value: Vec<_> = request["params"][0]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|pubkey| {
+                let pubkey: Pubkey = pubkey.as_str().unwrap().parse().unwrap();
+                accounts.get(&pubkey).map(|lamports| {
+                    json!({
+                        "lamports": lamports,
+                        "data": ["", "base64"],
+                        "owner": system_program::ID.to_string(),
+                        "executable": false,
+                        "rentEpoch": 0,
+                        "space": 0,
+                    })
+                })
+            })
+            .collect();
+        let result = json!({"context": {"slot": 100}, "value": value});
+        (200, json!({"jsonrpc": "2.0", "id": 1, "result": result}).to_string())
+    })
+}
+
+#[tokio::test]
+async fn the_instruction_runs_on_the_stores_accounts() {
+    let (fetcher, payer, recipient) = payer_and_recipient();
+    let instruction = transfer(&payer, &recipient, 1_000);
+    let store = store(&fetcher).from_instruction(&instruction).await.unwrap();
+
+    let result = process_instruction_with_store(&Mollusk::default(), &store, &instruction).unwrap();
+    assert!(result.program_result.is_ok(), "{:?}", result.program_result);
+    assert_eq!(result.get_account(&recipient).unwrap().lamports, RECIPIENT_LAMPORTS + 1_000);
+}
+
+#[tokio::test]
+async fn store_errors_and_failed_instructions_stay_apart() {
+    let (fetcher, payer, recipient) = payer_and_recipient();
+    let store = store(&fetcher)
+        .from_instruction(&transfer(&payer, &recipient, 1))
+        .await
+        .unwrap();
+    let mollusk = Mollusk::default();
+
+    // More than the payer holds: the instruction fails, the call doesn't
+    let overdraft = transfer(&payer, &recipient, PAYER_LAMPORTS + 1);
+    let result = process_instruction_with_store(&mollusk, &store, &overdraft).unwrap();
+    assert!(result.program_result.is_err());
+
+    let missing = Pubkey::new_unique();
+    let error = process_instruction_with_store(&mollusk, &store, &transfer(&payer, &missing, 1))
+        .unwrap_err();
+    assert!(
+        matches!(error, RpcError::AccountNotCached { pubkey, index: 1, .. } if pubkey == missing),
+        "{error}"
+    );
+}
+
+#[tokio::test]
+async fn the_one_shot_helper_fetches_and_runs() {
+    let (payer, recipient) = (Pubkey::new_unique(), Pubkey::new_unique());
+    let url = serve_accounts(HashMap::from([
+        (payer, PAYER_LAMPORTS),
+        (recipient, RECIPIENT_LAMPORTS),
+    ]));
+
+    let mut mollusk = Mollusk::default();
+    let result =
+        process_instruction_against_mainnet(&mut mollusk, url, &transfer(&payer, &recipient, 7))
+            .await
+            .unwrap();
+    assert!(result.program_result.is_ok(), "{:?}", result.program_result);
+    assert_eq!(result.get_account(&payer).unwrap().lamports, PAYER_LAMPORTS - 7);
+}
+
+#[tokio::test]
+async fn the_one_shot_helper_reports_fetch_errors() {
+    let (payer, recipient) = (Pubkey::new_unique(), Pubkey::new_unique());
+    let instruction = transfer(&payer, &recipient, 7);
+    let url = serve_accounts(HashMap::from([(payer, PAYER_LAMPORTS)]));
+    let error = process_instruction_against_mainnet(&mut Mollusk::default(), url, &instruction)
+        .await
+        .unwrap_err();
+    let RpcError::AccountNotFoundInInstruction(missing) = &error else {
+        panic!("expected AccountNotFoundInInstruction, got {error}");
+    };
+    assert_eq!(missing.pubkey, recipient);
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let refusing = format!("http://{}", listener.local_addr().unwrap());
+    drop(listener);
+    let error = process_instruction_against_mainnet(&mut Mollusk::default(), refusing, &instruction)
+        .await
+        .unwrap_err();
+    assert_eq!(error.code(), "rpc_connect_failed", "{error}");
+}
@@ -0,0 +1,189 @@
+//! Program account queries: paginated assembly, the unpaginated fallback and how
+//! often the store probes for pagination.
+
+mod common;
+
+use {
+    common::{account, pubkeys, serve_json_rpc},
+    mollusk_on_demand::{testing::{FetcherCall, MockFetcher}, RpcAccountStore, RpcError},
+    serde_json::json,
+    solana_account::Account,
+    solana_pubkey::Pubkey,
+};
+
+/// A mock serving `count` accounts owned by `program`.
+fn program_with_accounts(program: Pubkey, count: usize) -> (MockFetcher, Vec<Pubkey>) {
+    let keys = pubkeys(count);
+    let fetcher = MockFetcher::new().with_accounts(keys.iter().map(|pubkey| {
+        let account = Account {
+            owner: program,
+            ..account(1, 4)
+        };
+        (*pubkey, account)
+    }));
+    (fetcher, keys)
+}
+
+#[tokio::test]
+async fn pages_are_assembled_in_order() {
+    let program = Pubkey::new_unique();
+    let (fetcher, keys) = program_with_accounts(program, 5);
+    let fetcher = fetcher.with_pagination(2);
+    let store = RpcAccountStore::new_with_fetcher(fetcher.clone())
+        .with_program_accounts(&program)
+        .await
+        .unwrap();
+
+    assert!(keys.iter().all(|pubkey| store.cache.contains_key(pubkey)));
+    let cursors: Vec<Option<String>> = fetcher
+        .calls()
+        .into_iter()
+        .map(|call| match call {
+            FetcherCall::GetProgramAccountsPage { cursor, .. } => cursor,
+            call => panic!("unexpected call {call:?}"),
+        })
+        .collect();
+    assert_eq!(cursors, vec![None, Some("2".to_string()), Some("4".to_string())]);
+    assert_eq!(store.stats().requests_for("getProgramAccountsV2"), 3);
+    assert_eq!(store.stats().accounts_fetched, 5);
+}
+
+#[tokio::test]
+async fn unpaginated_endpoints_are_probed_once() {
+    let program = Pubkey::new_unique();
+    let (fetcher, keys) = program_with_accounts(program, 3);
+    let store = RpcAccountStore::new_with_fetcher(fetcher.clone())
+        .with_program_accounts(&program)
+        .await
+        .unwrap()
+        .with_program_accounts(&program)
+        .await
+        .unwrap();
+
+    assert!(keys.iter().all(|pubkey| store.cache.contains_key(pubkey)));
+    assert_eq!(
+        fetcher.calls(),
+        vec![
+            FetcherCall::GetProgramAccountsPage {
+                program_id: program,
+                cursor: None,
+            },
+            FetcherCall::GetProgramAccounts(program),
+            FetcherCall::GetProgramAccounts(program),
+        ]
+    );
+    let stats = store.stats();
+    assert_eq!(stats.requests_for("getProgramAccountsV2"), 1);
+    assert_eq!(stats.requests_for("getProgramAccounts"), 2);
+    assert_eq!(stats.accounts_fetched, 6);
+}
+
+#[tokio::test]
+async fn the_limit_applies_to_unpaginated_results() {
+    let program = Pubkey::new_unique();
+    let (fetcher, _) = program_with_accounts(program, 3);
+    let error = RpcAccountStore::new_with_fetcher(fetcher)
+        .with_program_accounts_limit(2)
+        .with_program_accounts(&program)
+        .await
+        .map(|_| ())
+        .unwrap_err();
+    assert!(matches!(error, RpcError::TooManyProgramAccounts { limit: 2, .. }), "{error}");
+}
+
+/// `getProgramAccounts` result with one account of `program`.
+fn program_accounts_result(program: &Pubkey, pubkey: &Pubkey) -> String {
+    json!({
+        "jsonrpc": "2.0",
+        "id": 1,
+        "result": [{
+            "pubkey": pubkey.to_string(),
+            "account": {
+                "lamports": 1,
+                "data": ["AAAA", "base64"],
+                "owner": program.to_string(),
+                "executable": false,
+                "rentEpoch": 0,
+                "space": 3,
+            },
+        }],
+    })
+    .to_string()
+}
+
+/// Query the accounts of a program twice from an endpoint that rejects
+/// `getProgramAccountsV2` with `rejection`, returning the methods it received.
+async fn fall_back_from(rejection: (u16, String)) -> Vec<String> {
+    let (program, pubkey) = (Pubkey::new_unique(), Pubkey::new_unique());
+    let (url, methods) = serve_json_rpc(move |method| match method {
+        "getProgramAccountsV2" => rejection.clone(),
+        "getProgramAccounts" => (200, program_accounts_result(&program, &pubkey)),
+        method => panic!("unexpected method {method}"),
+    });
+    let store = RpcAccountStore::new(url)
+        .with_program_accounts(&program)
+        .await
+        .unwrap()
+        .with_program_accounts(&program)
+        .await
+        .unwrap();
+    assert_eq!(store.cache[&pubkey].data, vec![0; 3]);
+    let methods = methods.lock().unwrap().clone();
+    methods
+}
+
+#[tokio::test]
+async fn falls_back_on_method_not_found() {
+    let error = json!({
+        "jsonrpc": "2.0",
+        "id": 1,
+        "error": {"code": -32601, "message": "Method not found"},
+    });
+    let methods = fall_back_from((200, error.to_string())).await;
+    assert_eq!(
+        methods,
+        ["getProgramAccountsV2", "getProgramAccounts", "getProgramAccounts"]
+    );
+}
+
+#[tokio::test]
+async fn falls_back_on_a_method_not_found_message_under_another_code() {
+    let error = json!({
+        "jsonrpc": "2.0",
+        "id": 1,
+        "error": {"code": -32600, "message": "Method not found: getProgramAccountsV2"},
+    });
+    let methods = fall_back_from((200, error.to_string())).await;
+    assert_eq!(methods.len(), 3);
+}
+
+#[tokio::test]
+async fn falls_back_on_http_404_and_400() {
+    for status in [404, 400] {
+        let methods = fall_back_from((status, String::new())).await;
+        assert_eq!(
+            methods,
+            ["getProgramAccountsV2", "getProgramAccounts", "getProgramAccounts"],
+            "HTTP {status}"
+        );
+    }
+}
+
+#[tokio::test]
+async fn other_errors_are_not_taken_for_missing_pagination() {
+    let (url, methods) = serve_json_rpc(|_| {
+        let error = json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "error": {"code": -32005, "message": "Node is behind"},
+        });
+        (200, error.to_string())
+    });
+    let error = RpcAccountStore::new(url)
+        .with_program_accounts(&Pubkey::new_unique())
+        .await
+        .map(|_| ())
+        .unwrap_err();
+    assert!(matches!(error, RpcError::Client(_)), "{error}");
+    assert_eq!(*methods.lock().unwrap(), ["getProgramAccountsV2"]);
+}
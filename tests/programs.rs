@@ -0,0 +1,138 @@
+//! `add_programs` reports every program whose program data account is missing, and
+//! leaves programs Mollusk already has alone unless told to reload them.
+
+mod common;
+
+use {
+    common::{account, store},
+    mollusk_on_demand::{testing::MockFetcher, RpcAccountStore, RpcError},
+    mollusk_svm::Mollusk,
+    mollusk_svm_programs_token::token,
+    solana_account::Account,
+    solana_instruction::{AccountMeta, Instruction},
+    solana_pubkey::Pubkey,
+    solana_rent::Rent,
+    solana_sdk_ids::{native_loader, system_program},
+};
+
+/// A BPF Loader v3 program account pointing at `program_data`.
+fn program_account(program_data: &Pubkey) -> Account {
+    let mut data = 2u32.to_le_bytes().to_vec();
+    data.extend_from_slice(program_data.as_ref());
+    Account {
+        data,
+        executable: true,
+        owner: solana_sdk_ids::bpf_loader_upgradeable::ID,
+        ..account(1, 0)
+    }
+}
+
+/// `count` programs, with the program data addresses they point at.
+fn programs(count: usize) -> Vec<(Pubkey, Pubkey)> {
+    (0..count)
+        .map(|_| (Pubkey::new_unique(), Pubkey::new_unique()))
+        .collect()
+}
+
+async fn add_programs(programs: &[(Pubkey, Pubkey)]) -> RpcError {
+    RpcAccountStore::new_with_fetcher(MockFetcher::new())
+        .with_accounts(
+            programs
+                .iter()
+                .map(|(program, program_data)| (*program, program_account(program_data))),
+        )
+        .add_programs(&mut Mollusk::default())
+        .await
+        .map(|_| ())
+        .unwrap_err()
+}
+
+#[tokio::test]
+async fn every_missing_program_data_account_is_reported() {
+    let mut programs = programs(3);
+    let error = add_programs(&programs).await;
+
+    programs.sort();
+    let RpcError::ProgramDataAccountsNotFound(missing) = &error else {
+        panic!("expected ProgramDataAccountsNotFound, got {error}");
+    };
+    assert_eq!(*missing, programs);
+    assert_eq!(error.code(), "programdata_missing");
+    for (program, program_data) in &programs {
+        assert!(error.to_string().contains(&format!("{program_data} (program {program})")));
+    }
+}
+
+#[tokio::test]
+async fn a_single_missing_program_data_account_keeps_its_variant() {
+    let programs = programs(1);
+    let error = add_programs(&programs).await;
+
+    let RpcError::ProgramDataNotFound {
+        program,
+        program_data,
+    } = error
+    else {
+        panic!("expected ProgramDataNotFound, got {error}");
+    };
+    assert_eq!((program, program_data), programs[0]);
+}
+
+/// The token program and the system program as cloned from a cluster. Token's
+/// program data isn't served, so loading its ELF would fail.
+fn cloned_builtins() -> (MockFetcher, RpcAccountStore) {
+    let system = Account {
+        executable: true,
+        owner: native_loader::ID,
+        ..account(1, 14)
+    };
+    let fetcher = MockFetcher::new();
+    let store = store(&fetcher).with_accounts([
+        (token::ID, program_account(&Pubkey::new_unique())),
+        (system_program::ID, system),
+    ]);
+    (fetcher, store)
+}
+
+#[tokio::test]
+async fn programs_mollusk_already_has_take_no_elf_work() {
+    let mut mollusk = Mollusk::default();
+    token::add_program(&mut mollusk);
+    let (fetcher, store) = cloned_builtins();
+
+    store.add_programs(&mut mollusk).await.unwrap();
+    assert!(fetcher.calls().is_empty());
+
+    // Mollusk still runs its own token program
+    let mint = Pubkey::new_unique();
+    let mint_account = Account {
+        lamports: Rent::default().minimum_balance(82),
+        owner: token::ID,
+        ..account(0, 82)
+    };
+    let mut data = vec![20, 6];
+    data.extend_from_slice(Pubkey::new_unique().as_ref());
+    data.push(0);
+    let initialize_mint =
+        Instruction::new_with_bytes(token::ID, &data, vec![AccountMeta::new(mint, false)]);
+    let result = mollusk.process_instruction(&initialize_mint, &[(mint, mint_account)]);
+    assert!(result.program_result.is_ok(), "{:?}", result.program_result);
+}
+
+#[tokio::test]
+async fn force_reload_replaces_a_program_mollusk_has() {
+    let mut mollusk = Mollusk::default();
+    token::add_program(&mut mollusk);
+    let (_, store) = cloned_builtins();
+
+    let error = store
+        .force_reload(&[token::ID])
+        .add_programs(&mut mollusk)
+        .await
+        .map(|_| ())
+        .unwrap_err();
+    let RpcError::ProgramDataNotFound { program, .. } = error else {
+        panic!("expected ProgramDataNotFound, got {error}");
+    };
+    assert_eq!(program, token::ID);
+}
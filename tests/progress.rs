@@ -0,0 +1,71 @@
+//! `with_progress` reports across concurrently fetched chunks.
+
+mod common;
+
+use {
+    common::{instruction, pubkeys, serving, store},
+    mollusk_on_demand::FetchProgress,
+    solana_rpc_client_api::request::MAX_MULTIPLE_ACCOUNTS,
+    std::{
+        collections::HashSet,
+        sync::{Arc, Mutex},
+        time::Duration,
+    },
+};
+
+#[tokio::test(start_paused = true)]
+async fn progress_is_monotonic_and_adds_up_to_the_total() {
+    let keys = pubkeys(MAX_MULTIPLE_ACCOUNTS * 3 + 50);
+    let fetcher = serving(&keys).with_latency(Duration::from_millis(250));
+    let reports = Arc::new(Mutex::new(Vec::<FetchProgress>::new()));
+    let recorded = reports.clone();
+    store(&fetcher)
+        .with_concurrency(3)
+        .with_progress(move |progress| recorded.lock().unwrap().push(progress))
+        .from_instruction(&instruction(&keys))
+        .await
+        .unwrap();
+
+    let reports = reports.lock().unwrap();
+    assert_eq!(reports.len(), 4, "one report per chunk");
+    assert!(reports.iter().all(|report| report.total == keys.len()));
+    for pair in reports.windows(2) {
+        assert!(pair[0].fetched < pair[1].fetched, "{reports:?}");
+        assert!(pair[0].bytes_downloaded < pair[1].bytes_downloaded, "{reports:?}");
+    }
+
+    // Each increment is one chunk, and together they cover every account
+    let mut previous = 0;
+    let mut increments = Vec::new();
+    for report in reports.iter() {
+        increments.push(report.fetched - previous);
+        previous = report.fetched;
+    }
+    assert!(increments.iter().all(|chunk| *chunk <= MAX_MULTIPLE_ACCOUNTS));
+    assert_eq!(increments.iter().sum::<usize>(), keys.len());
+    let last = reports.last().unwrap();
+    assert_eq!(last.fetched, keys.len());
+    assert_eq!(last.bytes_downloaded, keys.len() * 8);
+
+    let batches: HashSet<usize> = reports.iter().map(|report| report.current_batch).collect();
+    assert_eq!(batches, (0..4).collect());
+}
+
+#[tokio::test]
+async fn cached_accounts_are_not_reported() {
+    let keys = pubkeys(3);
+    let fetcher = serving(&keys);
+    let reports = Arc::new(Mutex::new(Vec::<FetchProgress>::new()));
+    let recorded = reports.clone();
+    let store = store(&fetcher)
+        .from_instruction(&instruction(&keys[..2]))
+        .await
+        .unwrap()
+        .with_progress(move |progress| recorded.lock().unwrap().push(progress));
+    store.from_instruction(&instruction(&keys)).await.unwrap();
+
+    let reports = reports.lock().unwrap();
+    assert_eq!(reports.len(), 1);
+    assert_eq!(reports[0].fetched, 1);
+    assert_eq!(reports[0].total, 1);
+}
@@ -0,0 +1,111 @@
+//! `provenance` across a representative builder chain: mocks stay `Mocked`, fetches
+//! are `Fetched` when they happen, missing accounts are `Synthesized`, replays are
+//! `Fixture` with their file, results are `ResultApplied` in order, and the summary
+//! and diff reports say the same.
+
+mod common;
+
+use {
+    common::{account, instruction, store, temp_dir},
+    mollusk_on_demand::{process_instruction_with_store, testing::MockFetcher, Provenance},
+    mollusk_svm::Mollusk,
+    solana_instruction::{AccountMeta, Instruction},
+    solana_pubkey::Pubkey,
+    solana_sdk_ids::system_program,
+    std::time::SystemTime,
+};
+
+const LAMPORTS: u64 = 10_000_000_000;
+
+fn transfer(from: &Pubkey, to: &Pubkey, lamports: u64) -> Instruction {
+    let mut data = 2u32.to_le_bytes().to_vec();
+    data.extend_from_slice(&lamports.to_le_bytes());
+    Instruction::new_with_bytes(
+        system_program::ID,
+        &data,
+        vec![AccountMeta::new(*from, true), AccountMeta::new(*to, false)],
+    )
+}
+
+#[tokio::test]
+async fn provenance_follows_the_builder_chain() {
+    let [payer, recipient, missing] = [(); 3].map(|()| Pubkey::new_unique());
+    let fetcher = MockFetcher::new().with_account(payer, account(LAMPORTS, 0));
+    let dir = temp_dir("provenance");
+
+    let before = SystemTime::now();
+    let store = store(&fetcher)
+        .with_fixture_dir(&dir)
+        .allow_missing_accounts()
+        .with_accounts([(recipient, account(LAMPORTS, 0))])
+        .from_instruction(&instruction(&[payer, recipient, missing]))
+        .await
+        .unwrap();
+    let after = SystemTime::now();
+
+    assert_eq!(store.provenance(&recipient), Some(&Provenance::Mocked));
+    let Some(Provenance::Fetched {
+        slot,
+        at,
+        endpoint,
+        commitment,
+    }) = store.provenance(&payer)
+    else {
+        panic!("expected Fetched, got {:?}", store.provenance(&payer));
+    };
+    assert_eq!((slot, endpoint, commitment), (&None, &None, &None));
+    assert!((before..=after).contains(at));
+    let synthesized = store.provenance(&missing).unwrap();
+    assert!(matches!(synthesized, Provenance::Synthesized(_)), "{synthesized:?}");
+    assert_eq!(
+        synthesized.to_string(),
+        "synthesized: missing on-chain, created by allow_missing_accounts"
+    );
+
+    // Replayed by a later run
+    let path = dir.join(format!("{payer}.json"));
+    let mut store = common::store(&MockFetcher::new())
+        .with_fixture_dir(&dir)
+        .offline()
+        .with_accounts([(recipient, account(LAMPORTS, 0))])
+        .from_instruction(&instruction(&[payer, recipient]))
+        .await
+        .unwrap();
+    let fixture = Provenance::Fixture { path: path.clone() };
+    assert_eq!(store.provenance(&payer), Some(&fixture));
+    assert_eq!(fixture.to_string(), format!("fixture {}", path.display()));
+    let summary = store.summary().to_string();
+    assert!(summary.contains(&format!("{payer}: 0 bytes, fixture")), "{summary}");
+    assert!(summary.contains(&format!("{recipient}: 0 bytes, mocked")), "{summary}");
+
+    let mollusk = Mollusk::default();
+    for (index, lamports) in [1_000, 2_000].into_iter().enumerate() {
+        let transfer = transfer(&payer, &recipient, lamports);
+        let result = process_instruction_with_store(&mollusk, &store, &transfer).unwrap();
+        let diff = store.diff_result(&result).to_string();
+        let pre_state = if index == 0 {
+            format!("(pre-state fixture {})", path.display())
+        } else {
+            "(pre-state result of instruction 0)".to_string()
+        };
+        assert!(diff.contains(&pre_state), "{diff}");
+
+        assert_eq!(store.apply_result(&result), 2);
+        let applied = Provenance::ResultApplied {
+            instruction_index: Some(index),
+        };
+        assert_eq!(store.provenance(&payer), Some(&applied));
+        assert_eq!(store.provenance(&recipient), Some(&applied));
+    }
+}
+
+#[test]
+fn accounts_inserted_into_the_cache_directly_have_none() {
+    let pubkey = Pubkey::new_unique();
+    let mut store = store(&MockFetcher::new());
+    store.cache.insert(pubkey, account(LAMPORTS, 0));
+    assert_eq!(store.provenance(&pubkey), None);
+    assert_eq!(store.provenance(&Pubkey::new_unique()), None);
+    let summary = store.summary().to_string();
+    assert!(summary.contains(&format!("{pubkey}: 0 bytes, inserted")), "{summary}");
+}
@@ -0,0 +1,172 @@
+//! `subscribe` against a local `accountSubscribe` server: notifications reach the
+//! cache through `sync_subscriptions` and every fetch, `stop_subscriptions` ends
+//! them, and subscribing fails cleanly without an endpoint.
+
+#![cfg(feature = "pubsub")]
+
+mod common;
+
+use {
+    common::store,
+    futures::{SinkExt, StreamExt},
+    mollusk_on_demand::{testing::MockFetcher, RpcAccountStore},
+    serde_json::{json, Value},
+    solana_pubkey::Pubkey,
+    std::{collections::HashMap, net::TcpListener as StdListener, time::Duration},
+    tokio::{net::TcpListener, sync::mpsc, time::timeout},
+    tokio_tungstenite::tungstenite::Message,
+};
+
+const OWNER: Pubkey = Pubkey::new_from_array([7; 32]);
+
+/// A pubsub endpoint for one connection. Each `(pubkey, lamports)` sent on the
+/// returned channel becomes an `accountNotification` once the pubkey is subscribed.
+async fn serve_pubsub() -> (String, mpsc::UnboundedSender<(Pubkey, u64)>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("ws://{}", listener.local_addr().unwrap());
+    let (updates, mut pushed) = mpsc::unbounded_channel::<(Pubkey, u64)>();
+    tokio::spawn(async move {
+        let (stream, _) = listener.accept().await.unwrap();
+        let mut socket = tokio_tungstenite::accept_async(stream).await.unwrap();
+        let mut subscriptions = HashMap::new();
+        loop {
+            tokio::select! {
+                message = socket.next() => {
+                    let Some(Ok(Message::Text(text))) = message else {
+                        return;
+                    };
+                    let request: Value = serde_json::from_str(&text).unwrap();
+                    assert_eq!(request["method"], "accountSubscribe");
+                    assert_eq!(request["params"][1]["encoding"], "base64");
+                    let pubkey: Pubkey = request["params"][0].as_str().unwrap().parse().unwrap();
+                    let id = subscriptions.len() as u64;
+                    subscriptions.insert(pubkey, id);
+                    let reply = json!({"jsonrpc": "2.0", "result": id, "id": request["id"]});
+                    socket.send(Message::Text(reply.to_string())).await.unwrap();
+                }
+                Some((pubkey, lamports)) = pushed.recv() => {
+                    let notification = json!({
+                        "jsonrpc": "2.0",
+                        "method": "accountNotification",
+                        "params": {
+                            "subscription": subscriptions[&pubkey],
+                            "result": {
+                                "context": {"slot": 100},
+                                "value": {
+                                    "lamports": lamports,
+                                    "data": ["", "base64"],
+                                    "owner": OWNER.to_string(),
+                                    "executable": false,
+                                    "rentEpoch": 0,
+                                    "space": 0,
+                                },
+                            },
+                        },
+                    });
+                    let _ = socket.send(Message::Text(notification.to_string())).await;
+                }
+            }
+        }
+    });
+    (url, updates)
+}
+
+/// Sync `store` until `pubkey` holds `lamports`.
+async fn synced(store: &mut RpcAccountStore, pubkey: &Pubkey, lamports: u64) {
+    timeout(Duration::from_secs(10), async {
+        while store.cache.get(pubkey).map(|account| account.lamports) != Some(lamports) {
+            store.sync_subscriptions();
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+    })
+    .await
+    .expect("the notification never reached the cache");
+}
+
+#[tokio::test]
+async fn notifications_update_the_cache() {
+    let (ws_url, updates) = serve_pubsub().await;
+    let (first, second) = (Pubkey::new_unique(), Pubkey::new_unique());
+    let mut store = store(&MockFetcher::new())
+        .with_ws_url(ws_url)
+        .subscribe(&[first, second])
+        .await
+        .unwrap();
+    assert_eq!(store.stats().requests_for("accountSubscribe"), 2);
+
+    updates.send((first, 1_000)).unwrap();
+    updates.send((second, 2_000)).unwrap();
+    synced(&mut store, &first, 1_000).await;
+    synced(&mut store, &second, 2_000).await;
+    assert_eq!(store.cache[&first].owner, OWNER);
+
+    // A fetch applies what arrived since, without a request
+    updates.send((first, 1_500)).unwrap();
+    timeout(Duration::from_secs(10), async {
+        while store.get_or_fetch(&first).await.unwrap().lamports != 1_500 {
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+    })
+    .await
+    .expect("the fetch never applied the notification");
+    assert_eq!(store.stats().requests_for("getMultipleAccounts"), 0);
+}
+
+#[tokio::test]
+async fn stopping_ends_the_updates() {
+    let (ws_url, updates) = serve_pubsub().await;
+    let pubkey = Pubkey::new_unique();
+    let mut store = store(&MockFetcher::new())
+        .with_ws_url(ws_url)
+        .subscribe(&[pubkey])
+        .await
+        .unwrap();
+    updates.send((pubkey, 1_000)).unwrap();
+    synced(&mut store, &pubkey, 1_000).await;
+
+    store.stop_subscriptions();
+    updates.send((pubkey, 2_000)).unwrap();
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert_eq!(store.sync_subscriptions(), 0);
+    assert_eq!(store.cache[&pubkey].lamports, 1_000);
+}
+
+#[tokio::test]
+async fn the_websocket_url_follows_the_rpc_port() {
+    let (ws_url, updates) = serve_pubsub().await;
+    let port: u16 = ws_url.rsplit(':').next().unwrap().parse().unwrap();
+    let pubkey = Pubkey::new_unique();
+    let mut store = RpcAccountStore::new(format!("http://127.0.0.1:{}", port - 1))
+        .subscribe(&[pubkey])
+        .await
+        .unwrap();
+    updates.send((pubkey, 1_000)).unwrap();
+    synced(&mut store, &pubkey, 1_000).await;
+}
+
+#[tokio::test]
+async fn subscribing_needs_a_reachable_endpoint() {
+    let pubkey = Pubkey::new_unique();
+    let error = store(&MockFetcher::new()).subscribe(&[pubkey]).await.map(|_| ()).unwrap_err();
+    assert_eq!(error.code(), "pubsub_error", "{error}");
+    assert!(error.to_string().contains("with_ws_url"), "{error}");
+
+    let listener = StdListener::bind("127.0.0.1:0").unwrap();
+    let refusing = format!("ws://{}", listener.local_addr().unwrap());
+    drop(listener);
+    let error = store(&MockFetcher::new())
+        .with_ws_url(refusing)
+        .subscribe(&[pubkey])
+        .await
+        .map(|_| ())
+        .unwrap_err();
+    assert_eq!(error.code(), "pubsub_error", "{error}");
+
+    let error = RpcAccountStore::new("http://127.0.0.1:8899")
+        .offline()
+        .subscribe(&[pubkey])
+        .await
+        .map(|_| ())
+        .unwrap_err();
+    assert_eq!(error.code(), "offline", "{error}");
+}
@@ -0,0 +1,242 @@
+//! `refresh_pyth_price` and `set_pyth_price` on price accounts of both layouts, read
+//! from the fixtures in `tests/fixtures/pyth`: only the publish slots and times move
+//! to Mollusk's clock, the price only when it's set, and accounts of other layouts
+//! are errors naming what they hold.
+
+#![cfg(feature = "pyth")]
+
+mod common;
+
+use {
+    common::{account, store},
+    mollusk_on_demand::{testing::MockFetcher, Provenance, RpcAccountStore, RpcError},
+    mollusk_svm::Mollusk,
+    solana_account::Account,
+    solana_pubkey::Pubkey,
+    std::path::PathBuf,
+};
+
+/// The classic SOL/USD price account, 19 publishers at exponent -8.
+const CLASSIC: Pubkey = Pubkey::from_str_const("H6ARHf6YXhGYeQfUzQNGk6rDNnLBQKrenN712K4AQJEG");
+/// A fully verified SOL/USD `PriceUpdateV2` of the pull oracle.
+const PRICE_UPDATE: Pubkey = Pubkey::from_str_const("7UVimffxr9ow1uXYxsr4LHAcV58mLzhmwaeKvJ1pjLiE");
+
+/// What both fixtures were published with.
+const PRICE: i64 = 14_523_000_000;
+const CONF: u64 = 7_250_000;
+
+const SLOT: u64 = 350_000_000;
+const UNIX_TIMESTAMP: i64 = 1_760_000_000;
+
+fn fixture(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures/pyth")
+        .join(name)
+}
+
+/// A store with both fixtures loaded.
+fn prices() -> RpcAccountStore {
+    store(&MockFetcher::new())
+        .with_account_file(fixture("classic_sol_usd.json"))
+        .unwrap()
+        .with_account_file(fixture("price_update_v2_sol_usd.json"))
+        .unwrap()
+}
+
+/// A Mollusk whose clock is past both fixtures'.
+fn mollusk() -> Mollusk {
+    let mut mollusk = Mollusk::default();
+    mollusk.sysvars.clock.slot = SLOT;
+    mollusk.sysvars.clock.unix_timestamp = UNIX_TIMESTAMP;
+    mollusk
+}
+
+fn read_u64(data: &[u8], offset: usize) -> u64 {
+    u64::from_le_bytes(data[offset..offset + 8].try_into().unwrap())
+}
+
+fn read_i64(data: &[u8], offset: usize) -> i64 {
+    i64::from_le_bytes(data[offset..offset + 8].try_into().unwrap())
+}
+
+/// Check that `after` is `before` but for the 8-byte fields at `offsets`.
+fn assert_only_changed(before: &[u8], after: &[u8], offsets: &[usize]) {
+    assert_eq!(before.len(), after.len());
+    for (index, (old, new)) in before.iter().zip(after).enumerate() {
+        if !offsets
+            .iter()
+            .any(|offset| (*offset..offset + 8).contains(&index))
+        {
+            assert_eq!(old, new, "byte {index} changed");
+        }
+    }
+}
+
+fn invalid_oracle_reason(error: &RpcError, pubkey: Pubkey) -> &str {
+    let RpcError::InvalidOracleAccount {
+        pubkey: invalid,
+        oracle,
+        reason,
+    } = error
+    else {
+        panic!("expected InvalidOracleAccount, got {error}");
+    };
+    assert_eq!((*invalid, *oracle), (pubkey, "Pyth price"));
+    reason
+}
+
+#[test]
+fn a_classic_price_is_published_at_the_clock() {
+    let mut store = prices();
+    let before = store.cache[&CLASSIC].data.clone();
+    assert_eq!(
+        (read_i64(&before, 208), read_u64(&before, 216)),
+        (PRICE, CONF)
+    );
+    assert!(read_u64(&before, 32) < SLOT);
+
+    store.refresh_pyth_price(&CLASSIC, &mollusk()).unwrap();
+    let after = &store.cache[&CLASSIC].data;
+    // The last, valid and aggregate publish slots, then the timestamp
+    for offset in [32, 40, 232] {
+        assert_eq!(read_u64(after, offset), SLOT, "offset {offset}");
+    }
+    assert_eq!(read_i64(after, 96), UNIX_TIMESTAMP);
+    assert_only_changed(&before, after, &[32, 40, 96, 232]);
+    assert_eq!(
+        store.provenance(&CLASSIC),
+        Some(&Provenance::Patched("refresh_pyth_price"))
+    );
+}
+
+#[test]
+fn price_updates_are_published_at_the_clock_at_either_verification_level() {
+    let mut store = prices();
+    let full = store.cache[&PRICE_UPDATE].clone();
+    // `Partial { num_signatures }` takes a byte more, so the message starts at 42
+    let mut data = full.data.clone();
+    data[40] = 0;
+    data.insert(41, 5);
+    data.pop();
+    let partial = Pubkey::new_unique();
+    store = store.with_accounts([(
+        partial,
+        Account {
+            data,
+            ..full.clone()
+        },
+    )]);
+
+    let mollusk = mollusk();
+    for (pubkey, message) in [(PRICE_UPDATE, 41), (partial, 42)] {
+        let before = store.cache[&pubkey].data.clone();
+        assert_eq!(read_i64(&before, message + 32), PRICE, "{pubkey}");
+        store.refresh_pyth_price(&pubkey, &mollusk).unwrap();
+        let after = &store.cache[&pubkey].data;
+        assert_eq!(read_i64(after, message + 52), UNIX_TIMESTAMP, "{pubkey}");
+        assert_eq!(read_i64(after, message + 60), UNIX_TIMESTAMP, "{pubkey}");
+        assert_eq!(read_u64(after, message + 84), SLOT, "{pubkey}");
+        let patched = [message + 52, message + 60, message + 84];
+        assert_only_changed(&before, after, &patched);
+    }
+}
+
+#[test]
+fn set_pyth_price_also_writes_the_price_and_confidence() {
+    let mut store = prices();
+    let mollusk = mollusk();
+    for (pubkey, price, conf, patched) in [
+        (CLASSIC, 208, 216, vec![32, 40, 96, 208, 216, 232]),
+        (PRICE_UPDATE, 41 + 32, 41 + 40, vec![73, 81, 93, 101, 125]),
+    ] {
+        let before = store.cache[&pubkey].data.clone();
+        store
+            .set_pyth_price(&pubkey, &mollusk, -1_250_000, 3_000)
+            .unwrap();
+        let after = &store.cache[&pubkey].data;
+        assert_eq!(read_i64(after, price), -1_250_000, "{pubkey}");
+        assert_eq!(read_u64(after, conf), 3_000, "{pubkey}");
+        assert_only_changed(&before, after, &patched);
+        assert_eq!(
+            store.provenance(&pubkey),
+            Some(&Provenance::Patched("set_pyth_price"))
+        );
+    }
+}
+
+#[test]
+fn other_accounts_are_errors() {
+    let classic = prices().cache[&CLASSIC].clone();
+    let price_update = prices().cache[&PRICE_UPDATE].clone();
+    let (unknown, product, truncated, unverified) = (
+        Pubkey::new_unique(),
+        Pubkey::new_unique(),
+        Pubkey::new_unique(),
+        Pubkey::new_unique(),
+    );
+    let mut product_data = classic.data.clone();
+    product_data[8] = 2;
+    let mut unverified_data = price_update.data.clone();
+    unverified_data[40] = 7;
+    let mut store = prices().with_accounts([
+        (
+            unknown,
+            Account {
+                data: (1..=16).collect(),
+                ..account(1_000_000, 0)
+            },
+        ),
+        (
+            product,
+            Account {
+                data: product_data,
+                ..classic
+            },
+        ),
+        (
+            truncated,
+            Account {
+                data: price_update.data[..120].to_vec(),
+                ..price_update.clone()
+            },
+        ),
+        (
+            unverified,
+            Account {
+                data: unverified_data,
+                ..price_update
+            },
+        ),
+    ]);
+
+    let mollusk = mollusk();
+    for (pubkey, expected) in [
+        (
+            unknown,
+            "unknown layout: magic 0x04030201, discriminator [01 02 03 04 05 06 07 08]",
+        ),
+        (
+            product,
+            "classic Pyth account of type 2, not a price account",
+        ),
+        (truncated, "PriceUpdateV2 of 120 bytes is truncated"),
+        (
+            unverified,
+            "PriceUpdateV2 with unknown verification level 7",
+        ),
+    ] {
+        let before = store.cache[&pubkey].clone();
+        let error = store.refresh_pyth_price(&pubkey, &mollusk).unwrap_err();
+        assert_eq!(invalid_oracle_reason(&error, pubkey), expected);
+        assert_eq!(store.cache[&pubkey], before);
+    }
+
+    let missing = Pubkey::new_unique();
+    let error = store
+        .set_pyth_price(&missing, &mollusk, PRICE, CONF)
+        .unwrap_err();
+    let RpcError::AccountNotFound(pubkey) = &error else {
+        panic!("expected AccountNotFound, got {error}");
+    };
+    assert_eq!(*pubkey, missing);
+}
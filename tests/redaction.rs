@@ -0,0 +1,87 @@
+//! URL redaction: API keys and credentials in RPC URLs never reach the `Display` or
+//! `Debug` output of errors, stats or warnings, unless redaction is turned off.
+
+mod common;
+
+use {
+    common::{serve_http, HttpResponse},
+    mollusk_on_demand::{RpcAccountStore, RpcError},
+    solana_pubkey::Pubkey,
+    std::net::TcpListener,
+};
+
+const SECRETS: [&str; 4] = ["k3y-in-the-query", "pa55w0rd", "alice", "p4thk3y0123456789abcdef"];
+
+/// `base` with a key in the query, credentials and a key-like path segment.
+fn keyed_url(base: &str) -> String {
+    let authority = base.strip_prefix("http://").unwrap();
+    format!("http://alice:pa55w0rd@{authority}/v2/p4thk3y0123456789abcdef?api-key=k3y-in-the-query")
+}
+
+/// A local address nothing listens on.
+fn refusing() -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    format!("http://{}", listener.local_addr().unwrap())
+}
+
+fn unauthorized() -> String {
+    serve_http(|_| HttpResponse {
+        status: 401,
+        headers: Vec::new(),
+        body: String::new(),
+    })
+}
+
+/// Every formatting of the store's output a test log or bug report could show.
+fn outputs(store: &RpcAccountStore, error: &RpcError) -> Vec<String> {
+    vec![
+        error.to_string(),
+        format!("{error:?}"),
+        format!("{error:#?}"),
+        store.stats().to_string(),
+        format!("{:?}", store.stats()),
+        format!("{:?}", store.warnings()),
+        format!("{store:?}"),
+    ]
+}
+
+fn assert_redacted(store: &RpcAccountStore, error: &RpcError) {
+    for output in outputs(store, error) {
+        for secret in SECRETS {
+            assert!(!output.contains(secret), "{secret} leaked into {output}");
+        }
+    }
+    assert!(error.to_string().contains("...redacted..."), "{error}");
+}
+
+#[tokio::test]
+async fn failed_connections_dont_show_the_key() {
+    let mut store = RpcAccountStore::new(keyed_url(&refusing()));
+    let error = store.get_or_fetch(&Pubkey::new_unique()).await.unwrap_err();
+    assert_eq!(error.code(), "rpc_connect_failed", "{error}");
+    assert_redacted(&store, &error);
+}
+
+#[tokio::test]
+async fn http_errors_dont_show_the_key() {
+    let mut store = RpcAccountStore::new(keyed_url(&unauthorized()));
+    let error = store.get_or_fetch(&Pubkey::new_unique()).await.unwrap_err();
+    assert_eq!(error.code(), "rpc_http_error", "{error}");
+    assert_redacted(&store, &error);
+}
+
+#[tokio::test]
+async fn routed_endpoints_dont_show_the_key() {
+    let pubkey = Pubkey::new_unique();
+    let mut store = RpcAccountStore::new(refusing())
+        .with_endpoint_for(&[pubkey], &keyed_url(&unauthorized()));
+    let error = store.get_or_fetch(&pubkey).await.unwrap_err();
+    assert_redacted(&store, &error);
+}
+
+#[tokio::test]
+async fn the_key_shows_with_redaction_off() {
+    let mut store = RpcAccountStore::new(keyed_url(&unauthorized())).with_url_redaction(false);
+    let error = store.get_or_fetch(&Pubkey::new_unique()).await.unwrap_err();
+    assert!(error.to_string().contains("api-key=k3y-in-the-query"), "{error}");
+}
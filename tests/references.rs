@@ -0,0 +1,212 @@
+//! `with_referenced_accounts` on a synthetic pool referencing its two vaults and LP
+//! mint, one vault referencing an oracle: each level is looked up in one batch, the
+//! traversal stops at its depth, and exclusions, programs, cycles and the limit
+//! keep it bounded.
+
+mod common;
+
+use {
+    common::{account, store},
+    mollusk_on_demand::{testing::MockFetcher, RpcAccountStore, RpcError},
+    solana_account::Account,
+    solana_pubkey::Pubkey,
+    solana_sdk_ids::bpf_loader_upgradeable,
+};
+
+const LAMPORTS: u64 = 1_000_000_000;
+
+/// A pubkey that looks like one, a hash rather than a repeated byte, which the scan
+/// wouldn't try.
+fn key(seed: u8) -> Pubkey {
+    Pubkey::find_program_address(&[&[seed]], &Pubkey::default()).0
+}
+
+const POOL: u8 = 1;
+const VAULT_A: u8 = 2;
+const VAULT_B: u8 = 3;
+const LP_MINT: u8 = 4;
+const ORACLE: u8 = 5;
+const PROGRAM: u8 = 6;
+
+/// An account whose data is an 8-byte discriminator, `references`, then a `u64`.
+fn referencing(references: &[Pubkey]) -> Account {
+    let mut data = vec![7; 8];
+    for pubkey in references {
+        data.extend_from_slice(pubkey.as_ref());
+    }
+    data.extend_from_slice(&1_000u64.to_le_bytes());
+    Account {
+        data,
+        owner: key(9),
+        ..account(LAMPORTS, 0)
+    }
+}
+
+/// The pool, its vaults and LP mint, and the oracle only vault A references.
+fn pool_accounts() -> Vec<(Pubkey, Account)> {
+    vec![
+        (
+            key(POOL),
+            referencing(&[key(VAULT_A), key(VAULT_B), key(LP_MINT)]),
+        ),
+        (key(VAULT_A), referencing(&[key(ORACLE)])),
+        (key(VAULT_B), referencing(&[])),
+        (key(LP_MINT), referencing(&[])),
+        (key(ORACLE), referencing(&[])),
+    ]
+}
+
+fn cached(store: &RpcAccountStore) -> Vec<Pubkey> {
+    let mut cached: Vec<Pubkey> = store.cache.keys().copied().collect();
+    cached.sort();
+    cached
+}
+
+/// The pool with what it references down to `depth`.
+async fn pool_at_depth(fetcher: &MockFetcher, depth: usize) -> RpcAccountStore {
+    store(fetcher)
+        .with_referenced_accounts(&key(POOL), depth)
+        .await
+        .unwrap()
+}
+
+fn sorted<const N: usize>(bytes: [u8; N]) -> Vec<Pubkey> {
+    let mut pubkeys = bytes.map(key).to_vec();
+    pubkeys.sort();
+    pubkeys
+}
+
+#[tokio::test]
+async fn references_are_followed_down_to_the_depth() {
+    let fetcher = MockFetcher::new().with_accounts(pool_accounts());
+    let store = pool_at_depth(&fetcher, 1).await;
+    assert_eq!(cached(&store), sorted([POOL, VAULT_A, VAULT_B, LP_MINT]));
+    for (pubkey, account) in pool_accounts().into_iter().take(4) {
+        assert_eq!(store.cache[&pubkey], account, "{pubkey}");
+    }
+    // The root, then every window of its data in one batch
+    let calls = fetcher.calls();
+    assert_eq!(calls.len(), 2, "{calls:?}");
+    let requested = fetcher.requested_pubkeys();
+    assert_eq!(requested[0], key(POOL));
+    for child in [VAULT_A, VAULT_B, LP_MINT] {
+        assert_eq!(
+            requested
+                .iter()
+                .filter(|pubkey| **pubkey == key(child))
+                .count(),
+            1
+        );
+    }
+    assert!(!requested.contains(&key(ORACLE)));
+
+    let fetcher = MockFetcher::new().with_accounts(pool_accounts());
+    let store = pool_at_depth(&fetcher, 2).await;
+    assert_eq!(
+        cached(&store),
+        sorted([POOL, VAULT_A, VAULT_B, LP_MINT, ORACLE])
+    );
+    assert_eq!(fetcher.calls().len(), 3);
+
+    // Nothing left to follow after the oracle, so a deeper search stops there
+    let fetcher = MockFetcher::new().with_accounts(pool_accounts());
+    let store = pool_at_depth(&fetcher, 10).await;
+    assert_eq!(cached(&store).len(), 5);
+    assert_eq!(fetcher.calls().len(), 3);
+}
+
+#[tokio::test]
+async fn cached_and_excluded_accounts_arent_fetched() {
+    let fetcher = MockFetcher::new().with_accounts(pool_accounts());
+    let mocked_vault = referencing(&[key(ORACLE)]);
+    let store = store(&fetcher)
+        .with_accounts([(key(VAULT_A), mocked_vault.clone())])
+        .with_reference_exclusions([key(VAULT_B)])
+        .with_referenced_accounts(&key(POOL), 2)
+        .await
+        .unwrap();
+
+    // The mocked vault is still followed to the oracle
+    assert_eq!(cached(&store), sorted([POOL, VAULT_A, LP_MINT, ORACLE]));
+    assert_eq!(store.cache[&key(VAULT_A)], mocked_vault);
+    let requested = fetcher.requested_pubkeys();
+    assert!(!requested.contains(&key(VAULT_A)));
+    assert!(!requested.contains(&key(VAULT_B)));
+}
+
+#[tokio::test]
+async fn cycles_and_programs_end_the_traversal() {
+    let program = Account {
+        executable: true,
+        owner: bpf_loader_upgradeable::ID,
+        ..referencing(&[key(LP_MINT)])
+    };
+    // The oracle points back at the pool, and the pool at a program whose data
+    // names the LP mint
+    let fetcher = MockFetcher::new().with_accounts([
+        (key(POOL), referencing(&[key(ORACLE), key(PROGRAM)])),
+        (key(ORACLE), referencing(&[key(POOL)])),
+        (key(PROGRAM), program),
+        (key(LP_MINT), referencing(&[])),
+    ]);
+    let store = store(&fetcher)
+        .with_referenced_accounts(&key(POOL), 5)
+        .await
+        .unwrap();
+    assert_eq!(cached(&store), sorted([POOL, ORACLE, PROGRAM]));
+    let requested = fetcher.requested_pubkeys();
+    assert_eq!(
+        requested
+            .iter()
+            .filter(|pubkey| **pubkey == key(POOL))
+            .count(),
+        1
+    );
+    assert!(!requested.contains(&key(LP_MINT)));
+}
+
+#[tokio::test]
+async fn the_limit_bounds_each_call() {
+    // The three children fit, the oracle a level down doesn't
+    let fetcher = MockFetcher::new().with_accounts(pool_accounts());
+    let children = store(&fetcher)
+        .with_referenced_accounts_limit(3)
+        .with_referenced_accounts(&key(POOL), 1)
+        .await
+        .unwrap();
+    assert_eq!(cached(&children).len(), 4);
+
+    let error = store(&fetcher)
+        .with_referenced_accounts_limit(3)
+        .with_referenced_accounts(&key(POOL), 2)
+        .await
+        .map(|_| ())
+        .unwrap_err();
+    let RpcError::TooManyReferencedAccounts { root, limit } = &error else {
+        panic!("expected TooManyReferencedAccounts, got {error}");
+    };
+    assert_eq!((*root, *limit), (key(POOL), 3));
+    assert_eq!(
+        error.to_string(),
+        format!(
+            "[{}] Account {} references more than 3 accounts",
+            error.code(),
+            key(POOL)
+        )
+    );
+}
+
+#[tokio::test]
+async fn a_missing_root_is_an_error() {
+    let fetcher = MockFetcher::new().with_accounts(pool_accounts());
+    let error = store(&fetcher)
+        .with_referenced_accounts(&key(8), 1)
+        .await
+        .map(|_| ())
+        .unwrap_err();
+    let RpcError::AccountNotFound(pubkey) = &error else {
+        panic!("expected AccountNotFound, got {error}");
+    };
+    assert_eq!(*pubkey, key(8));
+    assert_eq!(fetcher.requested_pubkeys(), [key(8)]);
+}
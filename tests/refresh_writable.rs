@@ -0,0 +1,109 @@
+//! `refresh_writable` against a mock that changes accounts between calls: only the
+//! instruction's writable metas are re-fetched, the ones that changed are reported
+//! and recached, mocks are left alone, and the chain variant makes one request.
+
+mod common;
+
+use {
+    common::{account, store},
+    mollusk_on_demand::{testing::MockFetcher, Drift, Provenance, RpcAccountStore},
+    solana_instruction::{AccountMeta, Instruction},
+    solana_pubkey::Pubkey,
+};
+
+const LAMPORTS: u64 = 1_000_000_000;
+
+fn swap(pool: Pubkey, vault: Pubkey, oracle: Pubkey, user: Pubkey) -> Instruction {
+    Instruction::new_with_bytes(
+        Pubkey::new_unique(),
+        &[],
+        vec![
+            AccountMeta::new(pool, false),
+            AccountMeta::new(vault, false),
+            AccountMeta::new_readonly(oracle, false),
+            AccountMeta::new(user, true),
+        ],
+    )
+}
+
+/// A store that fetched `pool`, `vault` and `oracle` and mocked `user`, after which
+/// the chain moved `pool`, `oracle` and `user`.
+async fn moved() -> (MockFetcher, RpcAccountStore, [Pubkey; 4]) {
+    let keys @ [pool, vault, oracle, user] = [(); 4].map(|()| Pubkey::new_unique());
+    let fetcher =
+        MockFetcher::new().with_accounts(keys.map(|pubkey| (pubkey, account(LAMPORTS, 8))));
+    let store = store(&fetcher)
+        .with_accounts([(user, account(LAMPORTS, 8))])
+        .from_instruction(&swap(pool, vault, oracle, user))
+        .await
+        .unwrap();
+
+    for pubkey in [pool, oracle, user] {
+        fetcher.set_account(pubkey, account(LAMPORTS + 1, 8));
+    }
+    fetcher.clear_calls();
+    (fetcher, store, keys)
+}
+
+#[tokio::test]
+async fn only_writable_accounts_are_refreshed() {
+    let (fetcher, mut store, [pool, vault, oracle, user]) = moved().await;
+
+    let report = store.refresh_writable(&swap(pool, vault, oracle, user)).await.unwrap();
+    assert_eq!(fetcher.requested_pubkeys(), [pool, vault]);
+    assert_eq!(
+        report.drifted().collect::<Vec<_>>(),
+        [&(
+            pool,
+            Drift::Changed {
+                lamports: Some((LAMPORTS, LAMPORTS + 1)),
+                owner: None,
+                changed_bytes: 0,
+            }
+        )]
+    );
+    assert_eq!(report.accounts[1], (vault, Drift::Unchanged));
+
+    assert_eq!(store.cache[&pool].lamports, LAMPORTS + 1);
+    assert!(matches!(store.provenance(&pool), Some(Provenance::Fetched { .. })));
+    // The readonly oracle stays stale, the mock stays mocked
+    assert_eq!(store.cache[&oracle].lamports, LAMPORTS);
+    assert_eq!(store.cache[&user].lamports, LAMPORTS);
+    assert_eq!(store.provenance(&user), Some(&Provenance::Mocked));
+}
+
+#[tokio::test]
+async fn a_closed_writable_account_is_removed() {
+    let (fetcher, mut store, [pool, vault, oracle, user]) = moved().await;
+    fetcher.remove_account(&vault);
+
+    let report = store.refresh_writable(&swap(pool, vault, oracle, user)).await.unwrap();
+    assert_eq!(report.to_string().lines().count(), 3, "{report}");
+    assert_eq!(report.accounts[1], (vault, Drift::Missing));
+    assert!(!store.cache.contains_key(&vault));
+    assert_eq!(store.provenance(&vault), None);
+}
+
+#[tokio::test]
+async fn a_chain_is_refreshed_in_one_request() {
+    let (fetcher, mut store, [pool, vault, oracle, user]) = moved().await;
+    let deposit = Instruction::new_with_bytes(
+        Pubkey::new_unique(),
+        &[],
+        vec![AccountMeta::new(vault, false), AccountMeta::new(oracle, false)],
+    );
+
+    let chain = [swap(pool, vault, oracle, user), deposit];
+    let report = store.refresh_writable_chain(&chain).await.unwrap();
+    assert_eq!(fetcher.calls().len(), 1);
+    assert_eq!(fetcher.requested_pubkeys(), [pool, vault, oracle]);
+    let drifted: Vec<Pubkey> = report.drifted().map(|(pubkey, _)| *pubkey).collect();
+    assert_eq!(drifted, [pool, oracle]);
+    assert_eq!(store.cache[&oracle].lamports, LAMPORTS + 1);
+
+    // Nothing moved since
+    fetcher.clear_calls();
+    let report = store.refresh_writable_chain(&chain).await.unwrap();
+    assert!(report.is_fresh());
+    assert_eq!(fetcher.calls().len(), 1);
+}
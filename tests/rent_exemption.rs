@@ -0,0 +1,106 @@
+//! Cloned accounts below the rent-exempt minimum: every fetch warns about them,
+//! leaving out executables and empty placeholders, and `auto_top_up_rent` raises
+//! their lamports to the minimum and marks them as topped up in provenance.
+
+mod common;
+
+use {
+    common::{account, instruction, store},
+    mollusk_on_demand::{testing::MockFetcher, Provenance, WarningCode},
+    solana_account::Account,
+    solana_pubkey::Pubkey,
+    solana_rent::Rent,
+    solana_sdk_ids::bpf_loader_upgradeable,
+};
+
+/// The rent-exempt minimum for `data_len` bytes under the default rent.
+fn minimum(data_len: usize) -> u64 {
+    Rent::default().minimum_balance(data_len)
+}
+
+#[tokio::test]
+async fn a_legacy_account_is_reported() {
+    let (legacy, exempt) = (Pubkey::new_unique(), Pubkey::new_unique());
+    let fetcher = MockFetcher::new()
+        .with_account(legacy, account(1_000, 100))
+        .with_account(exempt, account(minimum(100), 100));
+
+    let store = store(&fetcher).from_instruction(&instruction(&[legacy, exempt])).await.unwrap();
+    let warnings = store.warnings();
+    assert_eq!(warnings.len(), 1, "{warnings:?}");
+    assert_eq!(warnings[0].code, WarningCode::NotRentExempt);
+    assert_eq!(warnings[0].pubkey, Some(legacy));
+    assert_eq!(
+        warnings[0].message,
+        format!(
+            "account holds 1000 of the {} lamports rent exemption needs; writing to it may \
+             fail Mollusk's rent checks",
+            minimum(100)
+        )
+    );
+    // Left as fetched
+    assert_eq!(store.cache[&legacy].lamports, 1_000);
+    assert!(matches!(store.provenance(&legacy), Some(Provenance::Fetched { .. })));
+}
+
+#[tokio::test]
+async fn several_offenders_are_listed_in_one_warning() {
+    let (first, second) = (Pubkey::new_unique(), Pubkey::new_unique());
+    let fetcher = MockFetcher::new()
+        .with_account(first, account(1, 10))
+        .with_account(second, account(2, 20));
+
+    let store = store(&fetcher).from_instruction(&instruction(&[first, second])).await.unwrap();
+    let warnings = store.warnings();
+    assert_eq!(warnings.len(), 1, "{warnings:?}");
+    assert_eq!(warnings[0].pubkey, None);
+    let message = &warnings[0].message;
+    assert!(message.starts_with("2 accounts hold less than the rent-exempt minimum"), "{message}");
+    assert!(message.contains(&format!("{first} (1 of {} lamports)", minimum(10))), "{message}");
+    assert!(message.contains(&format!("{second} (2 of {} lamports)", minimum(20))), "{message}");
+}
+
+#[tokio::test]
+async fn executables_and_placeholders_are_exempt() {
+    let (program, missing) = (Pubkey::new_unique(), Pubkey::new_unique());
+    let fetcher = MockFetcher::new().with_account(
+        program,
+        Account {
+            executable: true,
+            owner: bpf_loader_upgradeable::ID,
+            ..account(1, 36)
+        },
+    );
+
+    let store = store(&fetcher)
+        .allow_missing_accounts()
+        .from_instruction(&instruction(&[program, missing]))
+        .await
+        .unwrap();
+    assert_eq!(store.cache[&missing].lamports, 0);
+    assert!(store.warnings().is_empty(), "{:?}", store.warnings());
+}
+
+#[tokio::test]
+async fn auto_top_up_rent_raises_offenders_to_the_minimum() {
+    let (legacy, exempt) = (Pubkey::new_unique(), Pubkey::new_unique());
+    let fetcher = MockFetcher::new()
+        .with_account(legacy, account(1_000, 100))
+        .with_account(exempt, account(minimum(100) + 1, 100));
+
+    let store = store(&fetcher)
+        .auto_top_up_rent()
+        .from_instruction(&instruction(&[legacy, exempt]))
+        .await
+        .unwrap();
+    assert_eq!(store.cache[&legacy].lamports, minimum(100));
+    assert_eq!(store.provenance(&legacy), Some(&Provenance::RentToppedUp));
+    assert_eq!(store.cache[&exempt].lamports, minimum(100) + 1);
+    assert!(matches!(store.provenance(&exempt), Some(Provenance::Fetched { .. })));
+
+    // Still reported, with what was done about it
+    let warnings = store.warnings();
+    assert_eq!(warnings.len(), 1, "{warnings:?}");
+    assert_eq!(warnings[0].pubkey, Some(legacy));
+    assert!(warnings[0].message.ends_with("; topped up to the minimum"), "{}", warnings[0].message);
+}
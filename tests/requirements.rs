@@ -0,0 +1,114 @@
+//! `require_accounts` and `require_programs`: every account or program that isn't in
+//! place is reported at once, labeled and with why, a cached program only passes
+//! once `add_programs` loaded it, and neither check makes a request.
+
+mod common;
+
+use {
+    common::{account, instruction, store},
+    mollusk_on_demand::{testing::MockFetcher, RpcError, UnmetReason, UnmetRequirement},
+    mollusk_svm::Mollusk,
+    solana_account::Account,
+    solana_pubkey::Pubkey,
+    solana_sdk_ids::bpf_loader_upgradeable,
+};
+
+const LAMPORTS: u64 = 1_000_000_000;
+
+/// A mock serving an upgradeable program running the Token-2022 ELF.
+fn program() -> (MockFetcher, Pubkey) {
+    let (program, program_data) = (Pubkey::new_unique(), Pubkey::new_unique());
+    let mut data = 2u32.to_le_bytes().to_vec();
+    data.extend_from_slice(program_data.as_ref());
+    let program_account = Account {
+        data,
+        executable: true,
+        owner: bpf_loader_upgradeable::ID,
+        ..account(LAMPORTS, 0)
+    };
+
+    // The ProgramData header: tag, deployment slot and upgrade authority
+    let mut data = 3u32.to_le_bytes().to_vec();
+    data.extend_from_slice(&0u64.to_le_bytes());
+    data.extend_from_slice(&[0; 33]);
+    data.extend_from_slice(mollusk_svm_programs_token::token2022::ELF);
+    let program_data_account = Account {
+        data,
+        owner: bpf_loader_upgradeable::ID,
+        ..account(LAMPORTS, 0)
+    };
+    let fetcher = MockFetcher::new()
+        .with_account(program, program_account)
+        .with_account(program_data, program_data_account);
+    (fetcher, program)
+}
+
+fn unmet(error: &RpcError) -> &[UnmetRequirement] {
+    let RpcError::RequirementsNotMet(unmet) = error else {
+        panic!("expected RequirementsNotMet, got {error}");
+    };
+    unmet
+}
+
+#[test]
+fn every_missing_account_is_reported() {
+    let [pool, vault_a, vault_b, oracle] = [(); 4].map(|()| Pubkey::new_unique());
+    let fetcher = MockFetcher::new();
+    let store = store(&fetcher)
+        .with_accounts([(pool, account(LAMPORTS, 0))])
+        .with_labels(&[(vault_b, "vault_b")]);
+
+    let error = store.require_accounts(&[pool, vault_a, vault_b, oracle, vault_a]).unwrap_err();
+    assert_eq!(
+        unmet(&error),
+        [vault_a, vault_b, oracle].map(|pubkey| UnmetRequirement {
+            pubkey,
+            label: (pubkey == vault_b).then(|| "vault_b".to_string()),
+            reason: UnmetReason::NotCached,
+        })
+    );
+    assert_eq!(
+        error.to_string(),
+        format!(
+            "[{}] Required accounts are not in place: {vault_a} (not cached), {} (not cached), \
+             {oracle} (not cached)",
+            error.code(),
+            store.display_pubkey(&vault_b),
+        )
+    );
+    assert!(store.require_accounts(&[pool]).is_ok());
+    assert!(fetcher.calls().is_empty());
+}
+
+#[tokio::test]
+async fn a_program_must_be_loaded_not_just_cached() {
+    let (fetcher, program) = program();
+    let (wallet, missing) = (Pubkey::new_unique(), Pubkey::new_unique());
+    let store = store(&fetcher)
+        .with_accounts([(wallet, account(LAMPORTS, 0))])
+        .from_instruction(&instruction(&[program]))
+        .await
+        .unwrap();
+    fetcher.clear_calls();
+
+    let error = store.require_programs(&[program, wallet, missing]).unwrap_err();
+    let reasons: Vec<(Pubkey, UnmetReason)> =
+        unmet(&error).iter().map(|unmet| (unmet.pubkey, unmet.reason)).collect();
+    assert_eq!(
+        reasons,
+        [
+            (program, UnmetReason::NotLoaded),
+            (wallet, UnmetReason::NotExecutable),
+            (missing, UnmetReason::NotCached),
+        ]
+    );
+    assert!(error.to_string().contains(&format!("{program} (not loaded by add_programs)")));
+    // Cached is enough for require_accounts
+    assert!(store.require_accounts(&[program, wallet]).is_ok());
+    assert!(fetcher.calls().is_empty());
+
+    let store = store.add_programs(&mut Mollusk::default()).await.unwrap();
+    fetcher.clear_calls();
+    assert!(store.require_programs(&[program]).is_ok());
+    assert!(fetcher.calls().is_empty());
+}
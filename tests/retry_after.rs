@@ -0,0 +1,104 @@
+//! Waiting out rate limits: a `429` is retried after exactly the `Retry-After` delay,
+//! capped by `with_max_retry_after`, and every wait is counted in the stats.
+
+mod common;
+
+use {
+    common::{serve_http, HttpResponse},
+    mollusk_on_demand::RpcAccountStore,
+    mollusk_svm::Mollusk,
+    serde_json::json,
+    solana_pubkey::Pubkey,
+    std::{
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        },
+        time::{Duration, Instant},
+    },
+};
+
+/// An endpoint answering `getSlot` with `429` and `retry_after` for the first
+/// `limited` requests, and with slot 100 after. Returns the URL and the requests
+/// received so far.
+fn rate_limited(limited: usize, retry_after: &str) -> (String, Arc<AtomicUsize>) {
+    let retry_after = retry_after.to_string();
+    let requests = Arc::new(AtomicUsize::new(0));
+    let received = requests.clone();
+    let url = serve_http(move |_| {
+        if received.fetch_add(1, Ordering::SeqCst) < limited {
+            return HttpResponse {
+                status: 429,
+                headers: vec![("retry-after", retry_after.clone())],
+                body: String::new(),
+            };
+        }
+        HttpResponse {
+            status: 200,
+            headers: Vec::new(),
+            body: json!({"jsonrpc": "2.0", "id": 1, "result": 100}).to_string(),
+        }
+    });
+    (url, requests)
+}
+
+#[tokio::test]
+async fn a_429_is_retried_after_its_retry_after() {
+    let (url, requests) = rate_limited(1, "1");
+    let mut mollusk = Mollusk::default();
+    let started = Instant::now();
+    let store = RpcAccountStore::new(url)
+        .with_synced_slot(&mut mollusk)
+        .await
+        .unwrap();
+
+    assert!(started.elapsed() >= Duration::from_secs(1));
+    assert_eq!(mollusk.sysvars.clock.slot, 100);
+    assert_eq!(requests.load(Ordering::SeqCst), 2);
+    let stats = store.stats();
+    assert_eq!(stats.requests_for("getSlot"), 1);
+    assert_eq!(stats.retries, 1);
+    assert_eq!(stats.rate_limit_waits, 1);
+    assert_eq!(stats.rate_limit_wait_time, Duration::from_secs(1));
+}
+
+#[tokio::test]
+async fn long_retry_afters_are_capped() {
+    let (url, _) = rate_limited(2, "120");
+    let started = Instant::now();
+    let store = RpcAccountStore::new(url)
+        .with_max_retry_after(Duration::from_millis(100))
+        .with_synced_slot(&mut Mollusk::default())
+        .await
+        .unwrap();
+
+    assert!(started.elapsed() < Duration::from_secs(10));
+    assert_eq!(store.stats().rate_limit_waits, 2);
+    assert_eq!(store.stats().rate_limit_wait_time, Duration::from_millis(200));
+}
+
+#[tokio::test]
+async fn a_retry_after_date_is_waited_until() {
+    let date = httpdate::fmt_http_date(std::time::SystemTime::now() + Duration::from_secs(2));
+    let (url, _) = rate_limited(1, &date);
+    let store = RpcAccountStore::new(url)
+        .with_synced_slot(&mut Mollusk::default())
+        .await
+        .unwrap();
+
+    // HTTP dates have whole seconds
+    let waited = store.stats().rate_limit_wait_time;
+    assert!((Duration::from_secs(1)..=Duration::from_secs(2)).contains(&waited), "{waited:?}");
+}
+
+#[tokio::test]
+async fn a_provider_that_keeps_limiting_is_an_error() {
+    let (url, requests) = rate_limited(usize::MAX, "0");
+    let mut store = RpcAccountStore::new(url);
+    let error = store.get_or_fetch(&Pubkey::new_unique()).await.unwrap_err();
+
+    assert_eq!(error.code(), "rpc_rate_limited", "{error}");
+    assert!(error.is_retryable());
+    assert_eq!(requests.load(Ordering::SeqCst), 6);
+    assert_eq!(store.stats().rate_limit_waits, 5);
+}
@@ -0,0 +1,219 @@
+//! `with_endpoint_for` and `with_fetcher_for`: each endpoint receives exactly the
+//! pubkeys routed to it, the results land in one cache in the order they were asked
+//! for with the endpoint in their provenance, and slots sync with the endpoint
+//! `with_slot_endpoint` names.
+
+mod common;
+
+use {
+    base64::{engine::general_purpose::STANDARD as BASE64, Engine},
+    common::{account, serve_http, store, HttpResponse},
+    mollusk_on_demand::{
+        testing::{FetcherCall, MockFetcher},
+        Provenance, RpcAccountStore,
+    },
+    mollusk_svm::Mollusk,
+    serde_json::{json, Value},
+    solana_account::Account,
+    solana_pubkey::Pubkey,
+    std::{
+        collections::HashMap,
+        sync::{Arc, Mutex},
+    },
+};
+
+fn key(byte: u8) -> Pubkey {
+    Pubkey::new_from_array([byte; 32])
+}
+
+const POOL: u8 = 1;
+const VAULT: u8 = 2;
+/// An oracle's price feed, published on devnet only.
+const PRICE: u8 = 3;
+
+fn mainnet() -> MockFetcher {
+    MockFetcher::new()
+        .with_accounts([(key(POOL), account(1, 8)), (key(VAULT), account(2, 8))])
+        .with_slot(300_000_000)
+}
+
+fn devnet() -> MockFetcher {
+    MockFetcher::new()
+        .with_accounts([(key(PRICE), account(3, 8))])
+        .with_slot(350_000_000)
+}
+
+/// The endpoint `pubkey` was fetched from, per its provenance.
+fn fetched_from(store: &RpcAccountStore, pubkey: u8) -> Option<String> {
+    let provenance = store.provenance(&key(pubkey));
+    let Some(Provenance::Fetched { endpoint, .. }) = provenance else {
+        panic!("expected Fetched, got {provenance:?}");
+    };
+    endpoint.clone()
+}
+
+#[tokio::test]
+async fn each_endpoint_receives_its_routed_keys() {
+    let (own, routed) = (mainnet(), devnet());
+    let mut store = store(&own).with_fetcher_for(&[key(PRICE)], "devnet", routed.clone());
+    let lamports: Vec<u64> = store
+        .get_or_fetch_many(&[key(POOL), key(PRICE), key(VAULT)])
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|account| account.lamports)
+        .collect();
+    assert_eq!(lamports, [1, 3, 2]);
+
+    assert_eq!(
+        own.calls(),
+        [FetcherCall::GetMultipleAccounts(vec![
+            key(POOL),
+            key(VAULT)
+        ])]
+    );
+    assert_eq!(
+        routed.calls(),
+        [FetcherCall::GetMultipleAccounts(vec![key(PRICE)])]
+    );
+    assert_eq!(store.cache.len(), 3);
+    assert_eq!(fetched_from(&store, PRICE).as_deref(), Some("devnet"));
+    assert_eq!(fetched_from(&store, POOL), None);
+
+    // Cached accounts aren't asked for again, wherever they're routed
+    store
+        .get_or_fetch_many(&[key(PRICE), key(POOL)])
+        .await
+        .unwrap();
+    assert_eq!((own.calls().len(), routed.calls().len()), (1, 1));
+}
+
+#[tokio::test]
+async fn routing_a_pubkey_again_moves_it() {
+    let (own, first, second) = (mainnet(), devnet(), devnet());
+    let mut store = store(&own)
+        .with_fetcher_for(&[key(PRICE), key(VAULT)], "devnet", first.clone())
+        .with_fetcher_for(&[key(PRICE)], "staging", second.clone());
+    first.set_account(key(VAULT), account(2, 8));
+    store
+        .get_or_fetch_many(&[key(VAULT), key(PRICE)])
+        .await
+        .unwrap();
+
+    // The vault went nowhere, so the mainnet mock doesn't have to serve it
+    assert!(own.calls().is_empty());
+    assert_eq!(
+        first.calls(),
+        [FetcherCall::GetMultipleAccounts(vec![key(VAULT)])]
+    );
+    assert_eq!(
+        second.calls(),
+        [FetcherCall::GetMultipleAccounts(vec![key(PRICE)])]
+    );
+    assert_eq!(fetched_from(&store, PRICE).as_deref(), Some("staging"));
+}
+
+#[tokio::test]
+async fn slots_sync_with_the_chosen_endpoint() {
+    let (own, routed) = (mainnet(), devnet());
+    let mut mollusk = Mollusk::default();
+    let store = store(&own)
+        .with_fetcher_for(&[key(PRICE)], "devnet", routed.clone())
+        .with_synced_slot(&mut mollusk)
+        .await
+        .unwrap();
+    assert_eq!(store.synced_slot(), Some(300_000_000));
+    assert_eq!(own.calls(), [FetcherCall::GetSlot]);
+    assert!(routed.calls().is_empty());
+
+    own.clear_calls();
+    let store = store
+        .with_slot_endpoint("devnet")
+        .with_synced_slot(&mut mollusk)
+        .await
+        .unwrap();
+    assert_eq!(store.synced_slot(), Some(350_000_000));
+    assert_eq!(mollusk.sysvars.clock.slot, 350_000_000);
+    assert!(own.calls().is_empty());
+    assert_eq!(routed.calls(), [FetcherCall::GetSlot]);
+}
+
+#[test]
+#[should_panic(expected = "no endpoint was routed to as \"devnet\"")]
+fn the_slot_endpoint_must_be_routed_to() {
+    store(&mainnet()).with_slot_endpoint("devnet");
+}
+
+/// The `getMultipleAccounts` requests an endpoint received, with their
+/// `authorization` header.
+type Received = Arc<Mutex<Vec<(Vec<Pubkey>, Option<String>)>>>;
+
+/// A JSON-RPC endpoint serving `accounts`, and what it received.
+fn endpoint(accounts: impl IntoIterator<Item = (Pubkey, Account)>) -> (String, Received) {
+    let accounts: HashMap<Pubkey, Account> = accounts.into_iter().collect();
+    let received = Received::default();
+    let log = received.clone();
+    let url = serve_http(move |request| {
+        assert_eq!(request.body["method"], "getMultipleAccounts");
+        let pubkeys: Vec<Pubkey> = request.body["params"][0]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|pubkey| pubkey.as_str().unwrap().parse().unwrap())
+            .collect();
+        let value: Vec<Value> = pubkeys
+            .iter()
+            .map(|pubkey| {
+                accounts.get(pubkey).map_or(Value::Null, |account| {
+                    json!({
+                        "lamports": account.lamports,
+                        "data": [BASE64.encode(&account.data), "base64"],
+                        "owner": account.owner.to_string(),
+                        "executable": account.executable,
+                        "rentEpoch": 0,
+                        "space": account.data.len(),
+                    })
+                })
+            })
+            .collect();
+        log.lock()
+            .unwrap()
+            .push((pubkeys, request.header("authorization").map(str::to_string)));
+        let result = json!({"context": {"slot": 100}, "value": value});
+        HttpResponse {
+            status: 200,
+            headers: Vec::new(),
+            body: json!({"jsonrpc": "2.0", "id": 1, "result": result}).to_string(),
+        }
+    });
+    (url, received)
+}
+
+#[tokio::test]
+async fn routed_urls_are_redacted_and_get_no_headers() {
+    let (own_url, own) = endpoint([(key(POOL), account(1, 8))]);
+    let (routed_url, routed) = endpoint([(key(PRICE), account(3, 8))]);
+    let routed_url = format!("{routed_url}/?api-key=s3cr3t");
+    let mut store = RpcAccountStore::new(own_url)
+        .with_header("Authorization", "Bearer t0k3n")
+        .unwrap()
+        .with_endpoint_for(&[key(PRICE)], &routed_url);
+    store
+        .get_or_fetch_many(&[key(PRICE), key(POOL)])
+        .await
+        .unwrap();
+
+    assert_eq!(
+        *own.lock().unwrap(),
+        [(vec![key(POOL)], Some("Bearer t0k3n".to_string()))]
+    );
+    assert_eq!(*routed.lock().unwrap(), [(vec![key(PRICE)], None)]);
+    assert_eq!(store.cache[&key(PRICE)], account(3, 8));
+
+    let endpoint = fetched_from(&store, PRICE).unwrap();
+    assert!(!endpoint.contains("s3cr3t"), "{endpoint}");
+    assert_eq!(
+        endpoint,
+        routed_url.replace("api-key=s3cr3t", "...redacted...")
+    );
+}
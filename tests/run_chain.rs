@@ -0,0 +1,100 @@
+//! `run_chain`: the chain's accounts are fetched together, each instruction sees the
+//! previous ones' changes, and processing stops at the first failure with the
+//! results so far.
+
+mod common;
+
+use {
+    common::{account, store},
+    mollusk_on_demand::{testing::MockFetcher, RpcError},
+    mollusk_svm::Mollusk,
+    solana_instruction::{AccountMeta, Instruction},
+    solana_pubkey::Pubkey,
+    solana_sdk_ids::system_program,
+};
+
+const LAMPORTS: u64 = 1_000_000_000;
+
+fn transfer(from: &Pubkey, to: &Pubkey, lamports: u64) -> Instruction {
+    let mut data = 2u32.to_le_bytes().to_vec();
+    data.extend_from_slice(&lamports.to_le_bytes());
+    Instruction::new_with_bytes(
+        system_program::ID,
+        &data,
+        vec![AccountMeta::new(*from, true), AccountMeta::new(*to, false)],
+    )
+}
+
+/// A mock serving three system accounts of [`LAMPORTS`] each.
+fn wallets() -> (MockFetcher, [Pubkey; 3]) {
+    let wallets = [Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique()];
+    let fetcher =
+        MockFetcher::new().with_accounts(wallets.map(|pubkey| (pubkey, account(LAMPORTS, 0))));
+    (fetcher, wallets)
+}
+
+#[tokio::test]
+async fn each_instruction_sees_the_previous_ones_changes() {
+    let (fetcher, [a, b, c]) = wallets();
+    let mut store = store(&fetcher);
+    // b can only afford the second transfer after receiving the first
+    let chain = [transfer(&a, &b, LAMPORTS / 2), transfer(&b, &c, LAMPORTS + LAMPORTS / 4)];
+
+    let results = store.run_chain(&mut Mollusk::default(), &chain).await.unwrap();
+    assert_eq!(results.len(), 2);
+    for result in &results {
+        assert!(result.program_result.is_ok(), "{:?}", result.program_result);
+    }
+    assert_eq!(store.cache[&a].lamports, LAMPORTS / 2);
+    assert_eq!(store.cache[&b].lamports, LAMPORTS / 4);
+    assert_eq!(store.cache[&c].lamports, 2 * LAMPORTS + LAMPORTS / 4);
+    // One request for the whole chain
+    assert_eq!(fetcher.calls().len(), 1);
+    assert_eq!(fetcher.requested_pubkeys().len(), 3);
+}
+
+#[tokio::test]
+async fn a_failure_stops_the_chain_with_the_results_so_far() {
+    let (fetcher, [a, b, c]) = wallets();
+    let mut store = store(&fetcher);
+    let chain = [
+        transfer(&a, &b, 100),
+        transfer(&c, &a, 2 * LAMPORTS),
+        transfer(&b, &c, 100),
+    ];
+
+    let results = store.run_chain(&mut Mollusk::default(), &chain).await.unwrap();
+    assert_eq!(results.len(), 2);
+    assert!(results[0].program_result.is_ok());
+    assert!(results[1].program_result.is_err());
+    assert_eq!(store.cache[&b].lamports, LAMPORTS + 100);
+    assert_eq!(store.cache[&c].lamports, LAMPORTS);
+}
+
+#[tokio::test]
+async fn cached_accounts_are_reused_and_missing_ones_located() {
+    let (fetcher, [a, b, c]) = wallets();
+    let mut store = store(&fetcher);
+    let mut mollusk = Mollusk::default();
+    store.run_chain(&mut mollusk, &[transfer(&a, &b, 100)]).await.unwrap();
+    store.run_chain(&mut mollusk, &[transfer(&b, &c, 100)]).await.unwrap();
+    let mut requested = fetcher.requested_pubkeys();
+    requested.sort();
+    let mut expected = vec![a, b, c];
+    expected.sort();
+    assert_eq!(requested, expected);
+    assert_eq!(store.cache[&b].lamports, LAMPORTS);
+
+    let missing = Pubkey::new_unique();
+    let error = store
+        .run_chain(&mut mollusk, &[transfer(&a, &b, 100), transfer(&b, &missing, 100)])
+        .await
+        .unwrap_err();
+    let RpcError::AccountNotFoundInInstruction(located) = &error else {
+        panic!("expected AccountNotFoundInInstruction, got {error}");
+    };
+    assert_eq!(located.pubkey, missing);
+    assert_eq!((located.instruction_index, located.account_index), (1, 1));
+    // Nothing ran
+    assert_eq!(store.cache[&a].lamports, LAMPORTS - 100);
+}
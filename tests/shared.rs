@@ -0,0 +1,71 @@
+//! `SharedAccountStore` single-flight fetching: overlapping concurrent fetches
+//! request each pubkey once.
+
+mod common;
+
+use {
+    common::{pubkeys, serving},
+    mollusk_on_demand::{RpcAccountStore, SharedAccountStore},
+    std::{collections::HashMap, sync::Arc, time::Duration},
+};
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn ten_tasks_fetch_each_pubkey_once() {
+    let keys = pubkeys(50);
+    let fetcher = serving(&keys).with_latency(Duration::from_millis(20));
+    let store = Arc::new(SharedAccountStore::new(
+        RpcAccountStore::new_with_fetcher(fetcher.clone()).with_concurrency(4),
+    ));
+
+    // Every task asks for the same 50 keys, each starting at a different one
+    let tasks: Vec<_> = (0..10)
+        .map(|task| {
+            let store = store.clone();
+            let mut keys = keys.clone();
+            keys.rotate_left(task * 5);
+            tokio::spawn(async move { store.fetch_accounts(&keys).await })
+        })
+        .collect();
+    for task in tasks {
+        task.await.unwrap().unwrap();
+    }
+
+    let mut requests: HashMap<_, usize> = HashMap::new();
+    for pubkey in fetcher.requested_pubkeys() {
+        *requests.entry(pubkey).or_default() += 1;
+    }
+    assert_eq!(requests.len(), keys.len());
+    assert!(requests.values().all(|count| *count == 1), "{requests:?}");
+    for (index, pubkey) in keys.iter().enumerate() {
+        assert_eq!(store.get_account(pubkey).unwrap().lamports, index as u64 + 1);
+    }
+    let stats = store.stats();
+    assert_eq!(stats.accounts_fetched, 50);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn partly_overlapping_fetches_share_the_common_pubkeys() {
+    let keys = pubkeys(30);
+    let fetcher = serving(&keys).with_latency(Duration::from_millis(20));
+    let store = Arc::new(SharedAccountStore::new(RpcAccountStore::new_with_fetcher(
+        fetcher.clone(),
+    )));
+
+    let tasks: Vec<_> = (0..10)
+        .map(|task| {
+            let store = store.clone();
+            let window = keys[task * 2..task * 2 + 10].to_vec();
+            tokio::spawn(async move { store.fetch_accounts(&window).await })
+        })
+        .collect();
+    for task in tasks {
+        task.await.unwrap().unwrap();
+    }
+
+    let mut requested = fetcher.requested_pubkeys();
+    let total = requested.len();
+    requested.sort();
+    requested.dedup();
+    assert_eq!(requested.len(), total, "a pubkey was requested twice");
+    assert_eq!(total, 28);
+}
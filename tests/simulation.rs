@@ -0,0 +1,182 @@
+//! `compare_with_simulation`: a simulation matching Mollusk reports nothing, injected
+//! differences in status, compute units and post-state are each reported, the
+//! request skips signature checks, and an unreachable cluster leaves a Mollusk-only
+//! report instead of an error.
+
+mod common;
+
+use {
+    common::{account, serve_json_rpc_requests, store},
+    mollusk_on_demand::{
+        testing::{FetcherCall, MockFetcher},
+        Divergence, RpcAccountStore, TransactionSimulation,
+    },
+    mollusk_svm::Mollusk,
+    serde_json::json,
+    solana_instruction::{AccountMeta, Instruction},
+    solana_pubkey::Pubkey,
+    solana_sdk_ids::system_program,
+    std::{
+        net::TcpListener,
+        sync::{Arc, Mutex},
+    },
+};
+
+const LAMPORTS: u64 = 1_000_000_000;
+const AMOUNT: u64 = 1_000;
+
+fn transfer(from: &Pubkey, to: &Pubkey) -> Instruction {
+    let mut data = 2u32.to_le_bytes().to_vec();
+    data.extend_from_slice(&AMOUNT.to_le_bytes());
+    Instruction::new_with_bytes(
+        system_program::ID,
+        &data,
+        vec![AccountMeta::new(*from, true), AccountMeta::new(*to, false)],
+    )
+}
+
+fn succeeded() -> TransactionSimulation {
+    TransactionSimulation {
+        err: None,
+        logs: Vec::new(),
+        units_consumed: None,
+        accounts: Vec::new(),
+    }
+}
+
+/// A store holding both sides of a transfer, over a mock whose simulation reports
+/// `simulation` and the accounts after the transfer, as the cluster would.
+async fn simulated(
+    simulation: TransactionSimulation,
+) -> (MockFetcher, RpcAccountStore, Instruction, [Pubkey; 2]) {
+    let (from, to) = (Pubkey::new_unique(), Pubkey::new_unique());
+    let fetcher = MockFetcher::new()
+        .with_account(from, account(LAMPORTS, 0))
+        .with_account(to, account(LAMPORTS, 0))
+        .with_simulation(simulation);
+    let ix = transfer(&from, &to);
+    let store = store(&fetcher).from_instruction(&ix).await.unwrap();
+    fetcher.set_account(from, account(LAMPORTS - AMOUNT, 0));
+    fetcher.set_account(to, account(LAMPORTS + AMOUNT, 0));
+    (fetcher, store, ix, [from, to])
+}
+
+#[tokio::test]
+async fn a_matching_simulation_reports_nothing() {
+    let (fetcher, store, ix, [from, _]) = simulated(succeeded()).await;
+    let report = store
+        .compare_with_simulation(&Mollusk::default(), std::slice::from_ref(&ix), &from)
+        .await
+        .unwrap();
+
+    assert!(report.simulation.is_ok());
+    assert!(!report.diverged(), "{report}");
+    assert_eq!(report.to_string(), "Mollusk matches the simulation");
+    assert!(report.mollusk.program_result.is_ok());
+    let simulated = FetcherCall::SimulateTransaction {
+        instructions: vec![ix],
+        payer: from,
+    };
+    assert!(fetcher.calls().contains(&simulated));
+}
+
+#[tokio::test]
+async fn an_injected_divergence_is_reported() {
+    let (fetcher, store, ix, [from, to]) = simulated(TransactionSimulation {
+        units_consumed: Some(1),
+        ..succeeded()
+    })
+    .await;
+    // The cluster credited one lamport more
+    fetcher.set_account(to, account(LAMPORTS + AMOUNT + 1, 0));
+
+    let report = store.compare_with_simulation(&Mollusk::default(), &[ix], &from).await.unwrap();
+    let units = report.mollusk.compute_units_consumed;
+    assert_eq!(
+        report.divergences,
+        [
+            Divergence::ComputeUnits {
+                mollusk: units,
+                simulation: 1,
+            },
+            Divergence::Account {
+                pubkey: to,
+                mollusk: Some(account(LAMPORTS + AMOUNT, 0)),
+                simulation: Some(account(LAMPORTS + AMOUNT + 1, 0)),
+            },
+        ]
+    );
+    let shown = report.to_string();
+    assert!(shown.contains(&format!("compute units: Mollusk {units}, simulation 1")), "{shown}");
+    assert!(shown.contains(&format!("account {to}")), "{shown}");
+}
+
+#[tokio::test]
+async fn a_failed_simulation_is_a_status_divergence() {
+    let (fetcher, store, ix, [from, to]) = simulated(TransactionSimulation {
+        err: Some("InsufficientFundsForFee".to_string()),
+        ..succeeded()
+    })
+    .await;
+    fetcher.set_account(from, account(LAMPORTS, 0));
+    fetcher.set_account(to, account(LAMPORTS, 0));
+
+    let report = store.compare_with_simulation(&Mollusk::default(), &[ix], &from).await.unwrap();
+    assert_eq!(
+        report.divergences[0],
+        Divergence::Status {
+            mollusk: None,
+            simulation: Some("InsufficientFundsForFee".to_string()),
+        }
+    );
+    assert_eq!(report.divergences.len(), 3, "{report}");
+}
+
+#[tokio::test]
+async fn the_simulation_skips_signatures_and_asks_for_the_accounts() {
+    let (from, to) = (Pubkey::new_unique(), Pubkey::new_unique());
+    let received = Arc::new(Mutex::new(None));
+    let log = received.clone();
+    let url = serve_json_rpc_requests(move |request| {
+        assert_eq!(request["method"], "simulateTransaction");
+        *log.lock().unwrap() = Some(request["params"][1].clone());
+        let value = json!({"err": null, "logs": [], "accounts": null, "unitsConsumed": 150});
+        let result = json!({"context": {"slot": 100}, "value": value});
+        (200, json!({"jsonrpc": "2.0", "id": 1, "result": result}).to_string())
+    });
+    let store = RpcAccountStore::new(url)
+        .with_accounts([(from, account(LAMPORTS, 0)), (to, account(LAMPORTS, 0))]);
+
+    let report = store
+        .compare_with_simulation(&Mollusk::default(), &[transfer(&from, &to)], &from)
+        .await
+        .unwrap();
+    let config = received.lock().unwrap().clone().unwrap();
+    assert_eq!(config["sigVerify"], false);
+    assert_eq!(config["replaceRecentBlockhash"], true);
+    assert_eq!(config["accounts"]["addresses"], json!([from.to_string(), to.to_string()]));
+    assert_eq!(report.simulation.unwrap().units_consumed, Some(150));
+}
+
+#[tokio::test]
+async fn an_unreachable_cluster_degrades_to_mollusk_only() {
+    let (from, to) = (Pubkey::new_unique(), Pubkey::new_unique());
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let refusing = format!("http://{}", listener.local_addr().unwrap());
+    drop(listener);
+    let accounts = [(from, account(LAMPORTS, 0)), (to, account(LAMPORTS, 0))];
+
+    for store in [
+        RpcAccountStore::new(refusing).with_accounts(accounts.clone()),
+        RpcAccountStore::new("http://127.0.0.1:8899").offline().with_accounts(accounts.clone()),
+    ] {
+        let report = store
+            .compare_with_simulation(&Mollusk::default(), &[transfer(&from, &to)], &from)
+            .await
+            .unwrap();
+        assert!(report.mollusk.program_result.is_ok());
+        assert!(report.simulation.is_err());
+        assert!(!report.diverged());
+        assert!(report.to_string().starts_with("simulation unavailable"), "{report}");
+    }
+}
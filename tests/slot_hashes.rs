@@ -0,0 +1,117 @@
+//! `with_synced_slot_hashes`: finalized blocks, a widening `getBlocks` window and a
+//! bound on how far it widens.
+
+mod common;
+
+use {
+    common::{serve_json_rpc_requests, store},
+    mollusk_on_demand::{
+        testing::{FetcherCall, MockFetcher},
+        RpcAccountStore, RpcError,
+    },
+    mollusk_svm::Mollusk,
+    serde_json::json,
+    solana_hash::Hash,
+    std::sync::{Arc, Mutex},
+};
+
+/// The `getBlocks` windows the store asked for, as `(start_slot, end_slot)`.
+fn windows(fetcher: &MockFetcher) -> Vec<(u64, u64)> {
+    fetcher
+        .calls()
+        .into_iter()
+        .filter_map(|call| match call {
+            FetcherCall::GetBlocks {
+                start_slot,
+                end_slot,
+            } => Some((start_slot, end_slot)),
+            _ => None,
+        })
+        .collect()
+}
+
+#[tokio::test]
+async fn the_window_widens_past_skipped_slots() {
+    // A block every tenth slot
+    let hashes: Vec<_> = (1..100).map(|n| (n * 10, Hash::new_unique())).collect();
+    let fetcher = hashes
+        .iter()
+        .fold(MockFetcher::new().with_slot(1_000), |fetcher, (slot, hash)| {
+            fetcher.with_block(*slot, *hash)
+        });
+    let mut mollusk = Mollusk::default();
+    store(&fetcher)
+        .with_synced_slot_hashes(&mut mollusk, 4)
+        .await
+        .unwrap();
+
+    assert_eq!(windows(&fetcher), [(991, 999), (983, 999), (967, 999), (935, 999)]);
+    let entries: Vec<_> = mollusk.sysvars.slot_hashes.iter().copied().collect();
+    let expected: Vec<_> = hashes.iter().rev().take(4).copied().collect();
+    assert_eq!(entries, expected);
+    assert_eq!(mollusk.sysvars.clock.slot, 1_000);
+}
+
+#[tokio::test]
+async fn the_window_stops_widening_at_the_bound() {
+    let fetcher = MockFetcher::new()
+        .with_slot(100_000)
+        .with_block(10, Hash::new_unique());
+    let error = store(&fetcher)
+        .with_synced_slot_hashes(&mut Mollusk::default(), 4)
+        .await
+        .map(|_| ())
+        .unwrap_err();
+
+    let RpcError::TooFewBlocks {
+        depth,
+        found,
+        slots,
+    } = error
+    else {
+        panic!("expected TooFewBlocks, got {error}");
+    };
+    assert_eq!((depth, found, slots), (4, 0, 4 * 32));
+    assert_eq!(windows(&fetcher).len(), 5);
+    assert_eq!(error.code(), "too_few_blocks");
+}
+
+#[tokio::test]
+async fn a_short_chain_gives_the_blocks_it_has() {
+    let fetcher = MockFetcher::new()
+        .with_slot(6)
+        .with_block(2, Hash::new_unique())
+        .with_block(4, Hash::new_unique());
+    let mut mollusk = Mollusk::default();
+    store(&fetcher)
+        .with_synced_slot_hashes(&mut mollusk, 32)
+        .await
+        .unwrap();
+    assert_eq!(mollusk.sysvars.slot_hashes.len(), 2);
+}
+
+#[tokio::test]
+async fn blocks_are_listed_at_finalized_commitment() {
+    let commitments = Arc::new(Mutex::new(Vec::new()));
+    let received = commitments.clone();
+    let blockhash = Hash::new_unique();
+    let url = serve_json_rpc_requests(move |request| {
+        let result = match request["method"].as_str().unwrap() {
+            "getSlot" => json!(100),
+            "getBlocks" => {
+                received.lock().unwrap().push(request["params"][2]["commitment"].clone());
+                json!([98, 99])
+            }
+            "getBlock" => json!({"blockhash": blockhash.to_string()}),
+            method => panic!("unexpected method {method}"),
+        };
+        (200, json!({"jsonrpc": "2.0", "id": 1, "result": result}).to_string())
+    });
+    let mut mollusk = Mollusk::default();
+    RpcAccountStore::new(url)
+        .with_synced_slot_hashes(&mut mollusk, 2)
+        .await
+        .unwrap();
+    assert_eq!(*commitments.lock().unwrap(), [json!("finalized")]);
+    assert_eq!(mollusk.sysvars.slot_hashes.get(&99), Some(&blockhash));
+}
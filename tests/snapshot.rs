@@ -0,0 +1,171 @@
+//! `snapshot_json` and `snapshot_result_json`: two stores built independently, in a
+//! different order and with different rent epochs, print byte-identical snapshots,
+//! keyed by label with sorted keys, and the data shown follows the options.
+
+mod common;
+
+use {
+    common::{account, instruction, store},
+    mollusk_on_demand::{
+        process_instruction_with_store, testing::MockFetcher, RpcAccountStore, SnapshotOptions,
+    },
+    mollusk_svm::Mollusk,
+    serde_json::json,
+    solana_account::Account,
+    solana_instruction::{AccountMeta, Instruction},
+    solana_pubkey::Pubkey,
+    solana_sdk_ids::system_program,
+};
+
+const LAMPORTS: u64 = 1_000_000_000;
+
+fn key(byte: u8) -> Pubkey {
+    Pubkey::new_from_array([byte; 32])
+}
+
+const ALICE: u8 = 1;
+const BOB: u8 = 2;
+const POOL: u8 = 3;
+
+fn pool() -> Account {
+    Account {
+        data: (0..40).collect(),
+        owner: key(9),
+        ..account(LAMPORTS, 0)
+    }
+}
+
+fn transfer(from: &Pubkey, to: &Pubkey, lamports: u64) -> Instruction {
+    let mut data = 2u32.to_le_bytes().to_vec();
+    data.extend_from_slice(&lamports.to_le_bytes());
+    Instruction::new_with_bytes(
+        system_program::ID,
+        &data,
+        vec![AccountMeta::new(*from, true), AccountMeta::new(*to, false)],
+    )
+}
+
+/// The three accounts fetched, with the rent epoch the chain reports.
+async fn fetched() -> RpcAccountStore {
+    let fetcher = MockFetcher::new().with_accounts([POOL, BOB, ALICE].map(|byte| {
+        let account = if byte == POOL { pool() } else { account(LAMPORTS, 0) };
+        let account = Account {
+            rent_epoch: u64::MAX,
+            ..account
+        };
+        (key(byte), account)
+    }));
+    store(&fetcher)
+        .with_labels(&[(key(POOL), "pool"), (key(ALICE), "alice")])
+        .from_instruction(&instruction(&[key(POOL), key(BOB), key(ALICE)]))
+        .await
+        .unwrap()
+}
+
+/// The same accounts mocked in another order, with rent epoch 0.
+fn mocked() -> RpcAccountStore {
+    store(&MockFetcher::new())
+        .with_accounts([
+            (key(ALICE), account(LAMPORTS, 0)),
+            (key(BOB), account(LAMPORTS, 0)),
+            (key(POOL), pool()),
+        ])
+        .with_labels(&[(key(ALICE), "alice"), (key(POOL), "pool")])
+}
+
+#[tokio::test]
+async fn independently_built_stores_snapshot_the_same() {
+    let (fetched, mocked) = (fetched().await, mocked());
+    let pubkeys = [key(POOL), key(ALICE), key(BOB), key(7)];
+    let snapshot = serde_json::to_string_pretty(&fetched.snapshot_json(&pubkeys)).unwrap();
+    let reversed: Vec<Pubkey> = pubkeys.into_iter().rev().collect();
+    assert_eq!(snapshot, serde_json::to_string_pretty(&mocked.snapshot_json(&reversed)).unwrap());
+
+    let system = system_program::ID.to_string();
+    let expected = json!({
+        key(BOB).to_string(): {
+            "data": "",
+            "data_len": 0,
+            "data_preview": "",
+            "executable": false,
+            "lamports": LAMPORTS,
+            "owner": system,
+            "pubkey": key(BOB).to_string()
+        },
+        key(7).to_string(): null,
+        "alice": {
+            "data": "",
+            "data_len": 0,
+            "data_preview": "",
+            "executable": false,
+            "lamports": LAMPORTS,
+            "owner": system,
+            "pubkey": key(ALICE).to_string()
+        },
+        "pool": {
+            "data": "AAECAwQFBgcICQoLDA0ODxAREhMUFRYXGBkaGxwdHh8gISIjJCUmJw==",
+            "data_len": 40,
+            "data_preview": "000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f…",
+            "executable": false,
+            "lamports": LAMPORTS,
+            "owner": key(9).to_string(),
+            "pubkey": key(POOL).to_string()
+        }
+    });
+    assert_eq!(snapshot, serde_json::to_string_pretty(&expected).unwrap());
+}
+
+#[test]
+fn the_data_shown_follows_the_options() {
+    let preview_only = mocked().with_snapshot_options(SnapshotOptions {
+        preview_bytes: 4,
+        full_data: false,
+    });
+    assert_eq!(
+        preview_only.snapshot_json(&[key(POOL)])["pool"],
+        json!({
+            "data_len": 40,
+            "data_preview": "00010203…",
+            "executable": false,
+            "lamports": LAMPORTS,
+            "owner": key(9).to_string(),
+            "pubkey": key(POOL).to_string()
+        })
+    );
+
+    let full_only = mocked().with_snapshot_options(SnapshotOptions {
+        preview_bytes: 0,
+        full_data: true,
+    });
+    let pool = &full_only.snapshot_json(&[key(POOL)])["pool"];
+    assert!(pool.get("data_preview").is_none(), "{pool}");
+    assert_eq!(pool["data"], "AAECAwQFBgcICQoLDA0ODxAREhMUFRYXGBkaGxwdHh8gISIjJCUmJw==");
+}
+
+#[test]
+fn a_shared_label_falls_back_to_the_pubkey() {
+    let store = mocked().with_labels(&[(key(BOB), "alice")]);
+    let snapshot = store.snapshot_json(&[key(BOB), key(ALICE)]);
+    let keys: Vec<&String> = snapshot.as_object().unwrap().keys().collect();
+    assert_eq!(keys, [&key(ALICE).to_string(), "alice"]);
+    assert_eq!(snapshot["alice"]["pubkey"], key(BOB).to_string());
+}
+
+#[tokio::test]
+async fn result_snapshots_show_the_post_state() {
+    let mollusk = Mollusk::default();
+    let mut snapshots = Vec::new();
+    for store in [fetched().await, mocked()] {
+        let result =
+            process_instruction_with_store(&mollusk, &store, &transfer(&key(ALICE), &key(BOB), 500))
+                .unwrap();
+        assert!(result.program_result.is_ok(), "{:?}", result.program_result);
+        let snapshot = store.snapshot_result_json(&result, &[key(ALICE), key(BOB), key(POOL)]);
+        assert_eq!(snapshot["alice"]["lamports"], LAMPORTS - 500);
+        assert_eq!(snapshot[key(BOB).to_string()]["lamports"], LAMPORTS + 500);
+        // Not in the instruction, so as cached
+        assert_eq!(snapshot["pool"], store.snapshot_json(&[key(POOL)])["pool"]);
+        snapshots.push(serde_json::to_string_pretty(&snapshot).unwrap());
+    }
+    assert_eq!(snapshots[0], snapshots[1]);
+}
@@ -0,0 +1,277 @@
+//! `with_stake_pool` on a pool of the devnet stake pool program with four
+//! validators, replayed offline from the fixtures it was recorded to in
+//! `tests/fixtures/stake_pool`: the pool's own accounts and every vote, validator
+//! and transient stake account the list names are cached, and the ones missing from
+//! a pool caught mid-rebalance are warnings.
+
+#![cfg(feature = "stake-pool")]
+
+mod common;
+
+use {
+    common::{account, store},
+    mollusk_on_demand::{testing::MockFetcher, Provenance, RpcError, WarningCode},
+    solana_account::Account,
+    solana_pubkey::Pubkey,
+    solana_sdk_ids::{bpf_loader_upgradeable, stake, vote},
+    spl_stake_pool::state::{
+        AccountType, StakePool, StakeStatus, ValidatorList, ValidatorListHeader, ValidatorStakeInfo,
+    },
+    std::{collections::BTreeSet, path::PathBuf},
+};
+
+const LAMPORTS: u64 = 1_000_000_000;
+const PROGRAM: Pubkey = Pubkey::from_str_const("DPoo15wWDqpPJJtS2MUZ49aRxqz5ZaaJCJP4z8bLuib");
+
+fn key(byte: u8) -> Pubkey {
+    Pubkey::new_from_array([byte; 32])
+}
+
+const POOL: u8 = 1;
+const VALIDATOR_LIST: u8 = 2;
+const RESERVE: u8 = 3;
+const POOL_MINT: u8 = 4;
+const MANAGER_FEE: u8 = 5;
+/// Active, with only its validator stake account.
+const STEADY: u8 = 10;
+/// Active, with stake being moved through its transient stake account.
+const REBALANCING: u8 = 11;
+/// Active, listed with transient stake whose account was already merged.
+const MERGED: u8 = 12;
+/// Removed from the cluster: no vote or validator stake account left.
+const REMOVED: u8 = 13;
+
+fn fixture_dir() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/stake_pool")
+}
+
+/// The validator stake account of `vote`, derived from the program's seeds: a zero
+/// seed suffix isn't a seed.
+fn validator_stake(vote: Pubkey, seed: u32) -> Pubkey {
+    let seed = seed.to_le_bytes();
+    let suffix: &[u8] = if seed == [0; 4] { &[] } else { &seed };
+    let pool = key(POOL);
+    let seeds: &[&[u8]] = &[vote.as_ref(), pool.as_ref(), suffix];
+    Pubkey::find_program_address(seeds, &PROGRAM).0
+}
+
+fn transient_stake(vote: Pubkey, seed: u64) -> Pubkey {
+    let (pool, seed) = (key(POOL), seed.to_le_bytes());
+    let seeds: &[&[u8]] = &[b"transient", vote.as_ref(), pool.as_ref(), &seed];
+    Pubkey::find_program_address(seeds, &PROGRAM).0
+}
+
+fn owned_by(owner: Pubkey, data: Vec<u8>) -> Account {
+    Account {
+        data,
+        owner,
+        ..account(LAMPORTS, 0)
+    }
+}
+
+fn validator(vote: u8, status: StakeStatus, seed: u32, transient: u64) -> ValidatorStakeInfo {
+    ValidatorStakeInfo {
+        active_stake_lamports: (100 * LAMPORTS).into(),
+        transient_stake_lamports: transient.into(),
+        last_update_epoch: 813.into(),
+        transient_seed_suffix: 7.into(),
+        validator_seed_suffix: seed.into(),
+        status: status.into(),
+        vote_account_address: key(vote).to_bytes().into(),
+        ..ValidatorStakeInfo::default()
+    }
+}
+
+/// Every account of the pool as the chain holds it.
+fn devnet_pool() -> Vec<(Pubkey, Account)> {
+    let pool = StakePool {
+        account_type: AccountType::StakePool,
+        manager: key(20).to_bytes().into(),
+        staker: key(21).to_bytes().into(),
+        validator_list: key(VALIDATOR_LIST).to_bytes().into(),
+        reserve_stake: key(RESERVE).to_bytes().into(),
+        pool_mint: key(POOL_MINT).to_bytes().into(),
+        manager_fee_account: key(MANAGER_FEE).to_bytes().into(),
+        token_program_id: spl_token_interface::ID.to_bytes().into(),
+        total_lamports: 400 * LAMPORTS,
+        pool_token_supply: 380 * LAMPORTS,
+        last_update_epoch: 813,
+        ..StakePool::default()
+    };
+    let list = ValidatorList {
+        header: ValidatorListHeader {
+            account_type: AccountType::ValidatorList,
+            max_validators: 8,
+        },
+        validators: vec![
+            validator(STEADY, StakeStatus::Active, 0, 0),
+            validator(REBALANCING, StakeStatus::Active, 3, 5 * LAMPORTS),
+            validator(MERGED, StakeStatus::Active, 0, 2 * LAMPORTS),
+            validator(REMOVED, StakeStatus::ReadyForRemoval, 0, 0),
+        ],
+    };
+    let mut accounts = vec![
+        (key(POOL), owned_by(PROGRAM, borsh::to_vec(&pool).unwrap())),
+        (
+            key(VALIDATOR_LIST),
+            owned_by(PROGRAM, borsh::to_vec(&list).unwrap()),
+        ),
+        (key(RESERVE), owned_by(stake::ID, vec![2; 200])),
+        (
+            key(POOL_MINT),
+            owned_by(spl_token_interface::ID, vec![1; 82]),
+        ),
+        (
+            key(MANAGER_FEE),
+            owned_by(spl_token_interface::ID, vec![1; 165]),
+        ),
+        (
+            PROGRAM,
+            Account {
+                executable: true,
+                ..owned_by(bpf_loader_upgradeable::ID, vec![2, 0, 0, 0, 9])
+            },
+        ),
+    ];
+    for vote_account in [STEADY, REBALANCING, MERGED] {
+        accounts.push((key(vote_account), owned_by(vote::ID, vec![2; 3762])));
+    }
+    for stake_account in [
+        validator_stake(key(STEADY), 0),
+        validator_stake(key(REBALANCING), 3),
+        transient_stake(key(REBALANCING), 7),
+        validator_stake(key(MERGED), 0),
+    ] {
+        accounts.push((stake_account, owned_by(stake::ID, vec![2; 200])));
+    }
+    accounts
+}
+
+fn sorted(pubkeys: impl IntoIterator<Item = Pubkey>) -> BTreeSet<Pubkey> {
+    pubkeys.into_iter().collect()
+}
+
+#[tokio::test]
+async fn a_recorded_pool_replays_offline() {
+    let fetcher = MockFetcher::new();
+    let store = store(&fetcher)
+        .offline()
+        .with_fixture_dir(fixture_dir())
+        .with_stake_pool(&key(POOL))
+        .await
+        .unwrap();
+
+    // 6 accounts of the pool, 3 vote accounts and 4 stake accounts
+    let expected = devnet_pool();
+    assert_eq!(store.cache.len(), 13);
+    assert_eq!(
+        sorted(store.cache.keys().copied()),
+        sorted(expected.iter().map(|(key, _)| *key))
+    );
+    for (pubkey, account) in &expected {
+        assert_eq!(&store.cache[pubkey], account, "{pubkey}");
+        let Some(Provenance::Fixture { path }) = store.provenance(pubkey) else {
+            panic!("expected a fixture, got {:?}", store.provenance(pubkey));
+        };
+        assert_eq!(*path, fixture_dir().join(format!("{pubkey}.json")));
+    }
+    assert!(fetcher.calls().is_empty());
+
+    let warnings: Vec<(WarningCode, &str, Option<Pubkey>)> = store
+        .warnings()
+        .iter()
+        .map(|warning| (warning.code, warning.message.as_str(), warning.pubkey))
+        .collect();
+    let pool = key(POOL);
+    assert_eq!(
+        warnings,
+        [
+            (
+                WarningCode::StakePoolAccountMissing,
+                format!(
+                    "Stake pool {pool} lists a transient stake account of {} that doesn't exist",
+                    key(MERGED)
+                )
+                .as_str(),
+                Some(transient_stake(key(MERGED), 7)),
+            ),
+            (
+                WarningCode::StakePoolAccountMissing,
+                format!("Stake pool {pool} lists a vote account that doesn't exist").as_str(),
+                Some(key(REMOVED)),
+            ),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn a_pool_is_fetched_in_three_batches() {
+    let fetcher = MockFetcher::new().with_accounts(devnet_pool());
+    let cloned = store(&fetcher).with_stake_pool(&key(POOL)).await.unwrap();
+    assert_eq!(cloned.cache.len(), 13);
+    assert_eq!(cloned.warnings().len(), 2);
+
+    let calls = fetcher.calls();
+    assert_eq!(calls.len(), 3, "{calls:?}");
+    let requested = fetcher.requested_pubkeys();
+    // The pool, then its own accounts, then the validators'
+    assert_eq!(
+        requested[..6],
+        [
+            key(POOL),
+            PROGRAM,
+            key(VALIDATOR_LIST),
+            key(RESERVE),
+            key(POOL_MINT),
+            key(MANAGER_FEE),
+        ]
+    );
+    assert_eq!(requested.len(), 6 + 9);
+    // The removed validator's stake account isn't looked up
+    assert!(!requested.contains(&validator_stake(key(REMOVED), 0)));
+    assert!(requested.contains(&transient_stake(key(MERGED), 7)));
+    assert!(!requested.contains(&transient_stake(key(STEADY), 7)));
+}
+
+#[tokio::test]
+async fn accounts_that_arent_a_pool_are_errors() {
+    let mut accounts = devnet_pool();
+    accounts[0].1.data[0] = 0;
+    let fetcher = MockFetcher::new().with_accounts(accounts);
+    let error = store(&fetcher)
+        .with_stake_pool(&key(POOL))
+        .await
+        .map(|_| ())
+        .unwrap_err();
+    let RpcError::InvalidStakePool { pubkey, reason } = &error else {
+        panic!("expected InvalidStakePool, got {error}");
+    };
+    assert_eq!(
+        (*pubkey, reason.as_str()),
+        (key(POOL), "not an initialized stake pool")
+    );
+
+    let fetcher = MockFetcher::new().with_accounts(devnet_pool());
+    fetcher.set_account(key(VALIDATOR_LIST), owned_by(PROGRAM, vec![2, 8, 0]));
+    let error = store(&fetcher)
+        .with_stake_pool(&key(POOL))
+        .await
+        .map(|_| ())
+        .unwrap_err();
+    let RpcError::InvalidStakePool { pubkey, .. } = &error else {
+        panic!("expected InvalidStakePool, got {error}");
+    };
+    assert_eq!(*pubkey, key(VALIDATOR_LIST));
+
+    let fetcher = MockFetcher::new().with_accounts(devnet_pool());
+    fetcher.remove_account(&key(RESERVE));
+    let error = store(&fetcher)
+        .with_stake_pool(&key(POOL))
+        .await
+        .map(|_| ())
+        .unwrap_err();
+    let RpcError::AccountNotFound(pubkey) = &error else {
+        panic!("expected AccountNotFound, got {error}");
+    };
+    assert_eq!(*pubkey, key(RESERVE));
+}
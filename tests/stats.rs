@@ -0,0 +1,77 @@
+//! `stats`: exact counts for a builder chain against the mock, what the `Display`
+//! output reports, and `reset_stats` for stores reused across scenarios.
+
+mod common;
+
+use {
+    common::{instruction, pubkeys, serving, store},
+    mollusk_on_demand::RpcStats,
+    mollusk_svm::Mollusk,
+};
+
+#[tokio::test]
+async fn a_builder_chain_is_counted_exactly() {
+    let keys = pubkeys(4);
+    let fetcher = serving(&keys).with_slot(1_000);
+    let mut mollusk = Mollusk::default();
+    let store = store(&fetcher)
+        .from_instruction(&instruction(&keys[..3]))
+        .await
+        .unwrap()
+        // Two of these are cached already
+        .from_instruction(&instruction(&keys[1..]))
+        .await
+        .unwrap()
+        .with_synced_slot(&mut mollusk)
+        .await
+        .unwrap();
+
+    let stats = store.stats();
+    assert_eq!(stats.requests_for("getMultipleAccounts"), 2);
+    assert_eq!(stats.requests_for("getSlot"), 1);
+    assert_eq!(stats.total_requests(), 3);
+    assert_eq!(stats.accounts_fetched, 4);
+    assert_eq!(stats.accounts_from_cache, 2);
+    assert_eq!(stats.accounts_from_fixtures, 0);
+    assert_eq!(stats.bytes_downloaded, 4 * 8);
+    assert_eq!(stats.retries, 0);
+    assert_eq!(fetcher.calls().len(), 3);
+}
+
+#[tokio::test]
+async fn display_reports_every_counter() {
+    let keys = pubkeys(2);
+    let store = store(&serving(&keys))
+        .from_instruction(&instruction(&keys))
+        .await
+        .unwrap();
+
+    assert_eq!(
+        store.stats().to_string(),
+        "1 RPC requests (getMultipleAccounts: 1), 2 accounts fetched, 0 served from cache, \
+         0 replayed from fixtures, 16 bytes downloaded, 0 retries, 0 rate-limit waits (0ns)"
+    );
+    assert_eq!(
+        RpcStats::default().to_string(),
+        "0 RPC requests, 0 accounts fetched, 0 served from cache, 0 replayed from fixtures, \
+         0 bytes downloaded, 0 retries, 0 rate-limit waits (0ns)"
+    );
+}
+
+#[tokio::test]
+async fn resetting_keeps_the_cache_and_counts_from_zero() {
+    let keys = pubkeys(2);
+    let fetcher = serving(&keys);
+    let mut store = store(&fetcher)
+        .from_instruction(&instruction(&keys))
+        .await
+        .unwrap();
+    store.reset_stats();
+    assert_eq!(store.stats(), RpcStats::default());
+
+    store.get_or_fetch_many(&keys).await.unwrap();
+    let stats = store.stats();
+    assert_eq!(stats.total_requests(), 0);
+    assert_eq!(stats.accounts_from_cache, 2);
+    assert_eq!(fetcher.calls().len(), 1);
+}
@@ -0,0 +1,104 @@
+//! The store's summary, snapshot-tested against a small synthetic cache: accounts
+//! grouped by owner with known programs named, mocked and fetched accounts told
+//! apart, and long groups capped unless the summary is verbose.
+
+mod common;
+
+use {
+    common::{account, instruction, store},
+    mollusk_on_demand::{testing::MockFetcher, RpcAccountStore},
+    solana_account::Account,
+    solana_pubkey::Pubkey,
+    solana_sdk_ids::{bpf_loader_upgradeable, system_program},
+};
+
+const SPL_TOKEN: Pubkey = Pubkey::from_str_const("TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA");
+
+fn key(byte: u8) -> Pubkey {
+    Pubkey::new_from_array([byte; 32])
+}
+
+fn owned_by(owner: Pubkey, data_len: usize) -> Account {
+    Account {
+        owner,
+        ..account(1_000_000_000, data_len)
+    }
+}
+
+/// Two mocked and one fetched system account, a token account, an executable
+/// program and seven accounts of an unknown program.
+async fn synthetic() -> RpcAccountStore {
+    let fetcher = MockFetcher::new().with_account(key(3), account(1_000_000_000, 0));
+    let program = Account {
+        executable: true,
+        ..owned_by(bpf_loader_upgradeable::ID, 36)
+    };
+    store(&fetcher)
+        .with_accounts([
+            (key(1), account(1_000_000_000, 0)),
+            (key(2), account(1_000_000_000, 8)),
+            (key(4), owned_by(SPL_TOKEN, 165)),
+            (key(5), program),
+        ])
+        .with_accounts((10..17).map(|byte| (key(byte), owned_by(key(9), 10))))
+        .from_instruction(&instruction(&[key(3)]))
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn the_summary_groups_accounts_by_owner() {
+    let store = synthetic().await;
+    let expected = format!(
+        "\
+12 accounts under 4 owners
+{system} (System Program): 3 accounts, 8 bytes (1 fetched, 2 mocked)
+  {k1}: 0 bytes, mocked
+  {k2}: 8 bytes, mocked
+  {k3}: 0 bytes, fetched
+{loader} (BPF Loader v3): 1 account, 36 bytes, 1 executable (1 mocked)
+  {k5}: 36 bytes, mocked, executable
+{token} (SPL Token): 1 account, 165 bytes (1 mocked)
+  {k4}: 165 bytes, mocked
+{owner}: 7 accounts, 70 bytes (7 mocked)
+  {k10}: 10 bytes, mocked
+  {k11}: 10 bytes, mocked
+  {k12}: 10 bytes, mocked
+  {k13}: 10 bytes, mocked
+  {k14}: 10 bytes, mocked
+  ... and 2 more",
+        system = system_program::ID,
+        owner = key(9),
+        loader = bpf_loader_upgradeable::ID,
+        token = SPL_TOKEN,
+        k1 = key(1),
+        k2 = key(2),
+        k3 = key(3),
+        k4 = key(4),
+        k5 = key(5),
+        k10 = key(10),
+        k11 = key(11),
+        k12 = key(12),
+        k13 = key(13),
+        k14 = key(14),
+    );
+    assert_eq!(store.summary().to_string(), expected);
+    assert_eq!(store.to_string(), expected);
+}
+
+#[tokio::test]
+async fn a_verbose_summary_lists_every_account() {
+    let store = synthetic().await;
+    let summary = store.summary().verbose().to_string();
+    for byte in 10..17 {
+        assert!(summary.contains(&format!("\n  {}: 10 bytes, mocked", key(byte))), "{summary}");
+    }
+    assert!(!summary.contains("more"), "{summary}");
+    assert_eq!(summary.lines().count(), 17);
+}
+
+#[test]
+fn an_empty_store_says_so() {
+    let store = RpcAccountStore::new_with_fetcher(MockFetcher::new());
+    assert_eq!(store.to_string(), "no accounts cached");
+}
@@ -0,0 +1,278 @@
+//! `refresh_switchboard_feed` on the v2 aggregator and on-demand feed fixtures in
+//! `tests/fixtures/switchboard`: the latest round or the submissions and result move
+//! to Mollusk's clock, a new value replaces every response it's computed from, and
+//! the on-demand feed still reads as fresh through the Switchboard SDK's own types.
+//!
+//! The v2 SDK only builds against Solana 1.x, so the aggregator is read at the
+//! offsets of its packed `AggregatorRound` instead.
+
+#![cfg(feature = "switchboard")]
+
+mod common;
+
+use {
+    common::{account, store},
+    mollusk_on_demand::{testing::MockFetcher, Decimal, Provenance, RpcAccountStore, RpcError},
+    mollusk_svm::Mollusk,
+    solana_account::Account,
+    solana_pubkey::Pubkey,
+    std::path::PathBuf,
+    switchboard_on_demand::{OnDemandError, PullFeedAccountData},
+};
+
+/// The v2 SOL/USD aggregator, with 3 of its oracles in the latest round.
+const AGGREGATOR: Pubkey = Pubkey::from_str_const("GvDMxPzN1sCj7L26YDK2HnMRXEQmQ2aemov8YBtPS7vR");
+/// An on-demand SOL/USD feed with 3 submissions and a staleness limit of 250 slots.
+const PULL_FEED: Pubkey = Pubkey::from_str_const("792MTFi7wfczBS8a8WKtkBhjgpCipV9rGPSQ8rSJN9wp");
+
+const SLOT: u64 = 350_000_000;
+const UNIX_TIMESTAMP: i64 = 1_760_000_000;
+
+// Offsets of the aggregator's `latest_confirmed_round`, and of fields in a round
+const LATEST_ROUND: usize = 341;
+const CURRENT_ROUND: usize = LATEST_ROUND + 1097;
+const OPEN_SLOT: usize = 9;
+const OPEN_TIMESTAMP: usize = 17;
+const RESULT: usize = 25;
+const STD_DEVIATION: usize = 45;
+const MIN_RESPONSE: usize = 65;
+const MAX_RESPONSE: usize = 85;
+const MEDIANS: usize = 617;
+
+fn fixture(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures/switchboard")
+        .join(name)
+}
+
+/// A store with both fixtures loaded.
+fn feeds() -> RpcAccountStore {
+    store(&MockFetcher::new())
+        .with_account_file(fixture("v2_aggregator_sol_usd.json"))
+        .unwrap()
+        .with_account_file(fixture("pull_feed_sol_usd.json"))
+        .unwrap()
+}
+
+/// A Mollusk whose clock is past both fixtures'.
+fn mollusk() -> Mollusk {
+    let mut mollusk = Mollusk::default();
+    mollusk.sysvars.clock.slot = SLOT;
+    mollusk.sysvars.clock.unix_timestamp = UNIX_TIMESTAMP;
+    mollusk
+}
+
+/// The feed as the on-demand SDK reads it, past its discriminator.
+fn pull_feed(store: &RpcAccountStore) -> PullFeedAccountData {
+    let data = &store.cache[&PULL_FEED].data;
+    bytemuck::pod_read_unaligned(&data[8..8 + size_of::<PullFeedAccountData>()])
+}
+
+fn read_u64(data: &[u8], offset: usize) -> u64 {
+    u64::from_le_bytes(data[offset..offset + 8].try_into().unwrap())
+}
+
+/// The `SwitchboardDecimal` at `offset`: an `i128` mantissa and a `u32` scale.
+fn read_decimal(data: &[u8], offset: usize) -> Decimal {
+    let mantissa = i128::from_le_bytes(data[offset..offset + 16].try_into().unwrap());
+    let scale = u32::from_le_bytes(data[offset + 16..offset + 20].try_into().unwrap());
+    Decimal::from_i128_with_scale(mantissa, scale)
+}
+
+#[test]
+fn the_latest_aggregator_round_opens_at_the_clock() {
+    let mut store = feeds();
+    let before = store.cache[&AGGREGATOR].data.clone();
+    store
+        .refresh_switchboard_feed(&AGGREGATOR, &mollusk(), None)
+        .unwrap();
+    let after = &store.cache[&AGGREGATOR].data;
+
+    let round = LATEST_ROUND;
+    assert_eq!(read_u64(after, round + OPEN_SLOT), SLOT);
+    assert_eq!(
+        read_u64(after, round + OPEN_TIMESTAMP) as i64,
+        UNIX_TIMESTAMP
+    );
+    assert_eq!(
+        read_decimal(after, round + RESULT),
+        Decimal::new(1_452_345, 4)
+    );
+    // Everything but the open slot and timestamp is as fetched
+    let patched = round + OPEN_SLOT..round + OPEN_SLOT + 16;
+    for (index, (old, new)) in before.iter().zip(after).enumerate() {
+        if !patched.contains(&index) {
+            assert_eq!(old, new, "byte {index} changed");
+        }
+    }
+    assert_eq!(
+        store.provenance(&AGGREGATOR),
+        Some(&Provenance::Patched("refresh_switchboard_feed"))
+    );
+}
+
+#[test]
+fn a_new_aggregator_value_replaces_every_response() {
+    let mut store = feeds();
+    let before = store.cache[&AGGREGATOR].data.clone();
+    let value = Decimal::new(15_012, 2);
+    store
+        .refresh_switchboard_feed(&AGGREGATOR, &mollusk(), Some(value))
+        .unwrap();
+    let after = &store.cache[&AGGREGATOR].data;
+
+    let round = LATEST_ROUND;
+    for offset in [RESULT, MIN_RESPONSE, MAX_RESPONSE] {
+        assert_eq!(
+            read_decimal(after, round + offset),
+            value,
+            "offset {offset}"
+        );
+    }
+    assert_eq!(read_decimal(after, round + STD_DEVIATION), Decimal::ZERO);
+    for oracle in 0..3 {
+        assert_eq!(
+            read_decimal(after, round + MEDIANS + oracle * 20),
+            value,
+            "{oracle}"
+        );
+    }
+    // Only the 3 oracles that responded have medians
+    let unused = round + MEDIANS + 3 * 20..round + MEDIANS + 16 * 20;
+    assert_eq!(after[unused.clone()], before[unused]);
+    assert_eq!(after[CURRENT_ROUND..], before[CURRENT_ROUND..]);
+}
+
+#[test]
+fn a_refreshed_pull_feed_reads_as_fresh_through_the_sdk() {
+    let mut store = feeds();
+    let fetched = pull_feed(&store);
+    let price = fetched.result.value().unwrap();
+    assert_eq!(price, Decimal::new(145_231, 3));
+    assert!(matches!(
+        fetched.value(SLOT),
+        Err(OnDemandError::StaleResult)
+    ));
+
+    store
+        .refresh_switchboard_feed(&PULL_FEED, &mollusk(), None)
+        .unwrap();
+    let feed = pull_feed(&store);
+    assert_eq!(feed.value(SLOT).unwrap(), price);
+    assert_eq!(feed.get_value(SLOT, 250, 3, true).unwrap(), price);
+    assert_eq!(feed.std_dev(), fetched.std_dev());
+    assert_eq!((feed.result.min_slot, feed.result.max_slot), (SLOT, SLOT));
+    assert_eq!(feed.last_update_slot(), SLOT);
+    assert_eq!(feed.last_update_timestamp, UNIX_TIMESTAMP);
+    assert_eq!(feed.result_ts(), UNIX_TIMESTAMP);
+    assert_eq!(
+        feed.current_result_ts_range(),
+        (UNIX_TIMESTAMP, UNIX_TIMESTAMP)
+    );
+    // Submissions past the last one stay empty
+    assert!(feed.submissions[3..]
+        .iter()
+        .all(|submission| submission.is_empty()));
+    assert_eq!(
+        feed.submission_timestamps[3..],
+        fetched.submission_timestamps[3..]
+    );
+    assert_eq!(feed.max_staleness, fetched.max_staleness);
+}
+
+#[test]
+fn a_new_pull_feed_value_replaces_the_result_and_submissions() {
+    let mut store = feeds();
+    let value = Decimal::new(15_012, 2);
+    store
+        .refresh_switchboard_feed(&PULL_FEED, &mollusk(), Some(value))
+        .unwrap();
+    let feed = pull_feed(&store);
+    assert_eq!(feed.value(SLOT).unwrap(), value);
+    assert_eq!(feed.get_value(SLOT, 250, 3, true).unwrap(), value);
+    for statistic in [feed.mean(), feed.min_value(), feed.max_value()] {
+        assert_eq!(statistic, Some(value));
+    }
+    assert_eq!(
+        (feed.std_dev(), feed.range()),
+        (Some(Decimal::ZERO), Some(Decimal::ZERO))
+    );
+    for submission in &feed.submissions[..3] {
+        assert_eq!(submission.value(), value);
+    }
+
+    let error = store
+        .refresh_switchboard_feed(&PULL_FEED, &mollusk(), Some(Decimal::MAX))
+        .unwrap_err();
+    let RpcError::InvalidOracleAccount { reason, .. } = &error else {
+        panic!("expected InvalidOracleAccount, got {error}");
+    };
+    assert_eq!(
+        reason,
+        &format!("{} doesn't fit the feed's 18-decimal values", Decimal::MAX)
+    );
+    assert_eq!(pull_feed(&store).value(SLOT).unwrap(), value);
+}
+
+#[test]
+fn other_accounts_are_errors() {
+    let (unknown, short_aggregator, short_feed) = (
+        Pubkey::new_unique(),
+        Pubkey::new_unique(),
+        Pubkey::new_unique(),
+    );
+    let fixtures = feeds();
+    let truncated = |pubkey: Pubkey| Account {
+        data: fixtures.cache[&pubkey].data[..100].to_vec(),
+        ..fixtures.cache[&pubkey].clone()
+    };
+    let mut store = feeds().with_accounts([
+        (
+            unknown,
+            Account {
+                data: (1..=16).collect(),
+                ..account(1_000_000, 0)
+            },
+        ),
+        (short_aggregator, truncated(AGGREGATOR)),
+        (short_feed, truncated(PULL_FEED)),
+    ]);
+
+    let mollusk = mollusk();
+    for (pubkey, expected) in [
+        (
+            unknown,
+            "unknown layout: discriminator [01 02 03 04 05 06 07 08]",
+        ),
+        (
+            short_aggregator,
+            "v2 aggregator of 100 bytes, expected 3851",
+        ),
+        (short_feed, "on-demand feed of 100 bytes, expected 3208"),
+    ] {
+        let error = store
+            .refresh_switchboard_feed(&pubkey, &mollusk, None)
+            .unwrap_err();
+        let RpcError::InvalidOracleAccount {
+            pubkey: invalid,
+            oracle,
+            reason,
+        } = &error
+        else {
+            panic!("expected InvalidOracleAccount, got {error}");
+        };
+        assert_eq!(
+            (*invalid, *oracle, reason.as_str()),
+            (pubkey, "Switchboard feed", expected)
+        );
+    }
+
+    let missing = Pubkey::new_unique();
+    let error = store
+        .refresh_switchboard_feed(&missing, &mollusk, None)
+        .unwrap_err();
+    let RpcError::AccountNotFound(pubkey) = &error else {
+        panic!("expected AccountNotFound, got {error}");
+    };
+    assert_eq!(*pubkey, missing);
+}
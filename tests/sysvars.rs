@@ -0,0 +1,279 @@
+//! Syncing sysvars from the cluster: the cluster's values reach Mollusk, and
+//! sysvars the cluster doesn't have keep Mollusk's defaults with only a warning.
+//! The synced rent is also what the store's own rent checks use, and the stake
+//! sysvars keep their warmup and activation values. Sysvar metas get Mollusk's
+//! values rather than the cluster's unless `fetch_real_sysvars` asks for them.
+
+mod common;
+
+use {
+    common::{account, instruction, store},
+    mollusk_on_demand::{testing::MockFetcher, RpcAccountStore, RpcError, WarningCode},
+    mollusk_svm::Mollusk,
+    serde::Serialize,
+    solana_account::Account,
+    solana_epoch_rewards::EpochRewards,
+    solana_epoch_schedule::EpochSchedule,
+    solana_hash::Hash,
+    solana_last_restart_slot::LastRestartSlot,
+    solana_pubkey::Pubkey,
+    solana_rent::{Rent, DEFAULT_LAMPORTS_PER_BYTE},
+    solana_sdk_ids::sysvar,
+    solana_stake_interface::stake_history::{StakeHistory, StakeHistoryEntry},
+};
+
+/// A sysvar account holding `value` as the runtime serializes it.
+fn sysvar_account(value: &impl Serialize) -> Account {
+    let data = bincode::serialize(value).unwrap();
+    Account {
+        lamports: Rent::default().minimum_balance(data.len()),
+        data,
+        owner: sysvar::ID,
+        executable: false,
+        rent_epoch: 0,
+    }
+}
+
+/// The store configurations under which a missing sysvar account must only warn.
+fn missing_sysvar_stores() -> [RpcAccountStore; 3] {
+    let fetcher = MockFetcher::new();
+    [store(&fetcher), store(&fetcher).allow_missing_accounts(), store(&fetcher).lenient()]
+}
+
+fn assert_only_warns_sysvar_missing(store: &RpcAccountStore, sysvar: &Pubkey) {
+    let warnings = store.warnings();
+    assert_eq!(warnings.len(), 1, "{warnings:?}");
+    assert_eq!(warnings[0].code, WarningCode::SysvarMissing);
+    assert_eq!(warnings[0].pubkey, Some(*sysvar));
+}
+
+#[tokio::test]
+async fn the_clusters_epoch_rewards_reach_mollusk() {
+    let epoch_rewards = EpochRewards {
+        distribution_starting_block_height: 250_000_000,
+        num_partitions: 16,
+        parent_blockhash: Hash::new_unique(),
+        total_points: 1 << 70,
+        total_rewards: 90_000_000_000,
+        distributed_rewards: 30_000_000_000,
+        active: true,
+    };
+    assert_ne!(epoch_rewards, EpochRewards::default());
+    let fetcher =
+        MockFetcher::new().with_account(sysvar::epoch_rewards::ID, sysvar_account(&epoch_rewards));
+
+    let mut mollusk = Mollusk::default();
+    let store = store(&fetcher).with_synced_epoch_rewards(&mut mollusk).await.unwrap();
+    assert_eq!(mollusk.sysvars.epoch_rewards, epoch_rewards);
+    assert!(store.warnings().is_empty());
+}
+
+#[tokio::test]
+async fn the_clusters_last_restart_slot_reaches_mollusk() {
+    let last_restart_slot = LastRestartSlot {
+        last_restart_slot: 282_000_000,
+    };
+    let fetcher = MockFetcher::new()
+        .with_account(sysvar::last_restart_slot::ID, sysvar_account(&last_restart_slot));
+
+    let mut mollusk = Mollusk::default();
+    let store = store(&fetcher).with_synced_last_restart_slot(&mut mollusk).await.unwrap();
+    assert_eq!(mollusk.sysvars.last_restart_slot, last_restart_slot);
+    assert!(store.warnings().is_empty());
+}
+
+#[tokio::test]
+async fn a_missing_epoch_rewards_sysvar_only_warns() {
+    for store in missing_sysvar_stores() {
+        let mut mollusk = Mollusk::default();
+        let default = mollusk.sysvars.epoch_rewards.clone();
+        let store = store.with_synced_epoch_rewards(&mut mollusk).await.unwrap();
+        assert_eq!(mollusk.sysvars.epoch_rewards, default);
+        assert_only_warns_sysvar_missing(&store, &sysvar::epoch_rewards::ID);
+    }
+}
+
+#[tokio::test]
+async fn a_missing_last_restart_slot_sysvar_only_warns() {
+    for store in missing_sysvar_stores() {
+        let mut mollusk = Mollusk::default();
+        let default = mollusk.sysvars.last_restart_slot.clone();
+        let store = store.with_synced_last_restart_slot(&mut mollusk).await.unwrap();
+        assert_eq!(mollusk.sysvars.last_restart_slot, default);
+        assert_only_warns_sysvar_missing(&store, &sysvar::last_restart_slot::ID);
+    }
+}
+
+#[tokio::test]
+async fn an_unparsable_sysvar_is_an_error() {
+    let account = Account {
+        data: vec![1],
+        ..sysvar_account(&())
+    };
+    let fetcher = MockFetcher::new().with_account(sysvar::epoch_rewards::ID, account);
+
+    let error = store(&fetcher)
+        .with_synced_epoch_rewards(&mut Mollusk::default())
+        .await
+        .map(|_| ())
+        .unwrap_err();
+    assert_eq!(error.code(), "sysvar_invalid", "{error}");
+}
+
+#[tokio::test]
+async fn the_clusters_rent_reaches_mollusk_and_the_store() {
+    let rent = Rent::with_lamports_per_byte(DEFAULT_LAMPORTS_PER_BYTE * 2);
+    assert_ne!(rent, Rent::default());
+    let legacy = Pubkey::new_unique();
+    let fetcher = MockFetcher::new()
+        .with_account(sysvar::rent::ID, sysvar_account(&rent))
+        // Exempt under the default rent, but not the cluster's
+        .with_account(legacy, account(Rent::default().minimum_balance(100), 100));
+
+    let mut mollusk = Mollusk::default();
+    let mut store = store(&fetcher)
+        .auto_top_up_rent()
+        .with_synced_rent(&mut mollusk)
+        .await
+        .unwrap();
+    assert_eq!(mollusk.sysvars.rent, rent);
+    assert_eq!(store.rent(), rent);
+
+    store.get_or_fetch(&legacy).await.unwrap();
+    assert_eq!(store.cache[&legacy].lamports, rent.minimum_balance(100));
+    // The rent sysvar was fetched once, for both
+    assert_eq!(fetcher.calls().len(), 2);
+}
+
+#[tokio::test]
+async fn an_unparsable_rent_sysvar_falls_back_to_the_default() {
+    let fetcher = MockFetcher::new().with_account(sysvar::rent::ID, sysvar_account(&1u8));
+
+    let mut mollusk = Mollusk::default();
+    mollusk.sysvars.rent = Rent::with_lamports_per_byte(1);
+    let store = store(&fetcher).with_synced_rent(&mut mollusk).await.unwrap();
+    assert_eq!(mollusk.sysvars.rent, Rent::default());
+    assert_eq!(store.rent(), Rent::default());
+    let warnings = store.warnings();
+    assert_eq!(warnings.len(), 1, "{warnings:?}");
+    assert_eq!(warnings[0].code, WarningCode::RentSysvarUnparsable);
+    assert_eq!(warnings[0].pubkey, Some(sysvar::rent::ID));
+}
+
+#[tokio::test]
+async fn the_clusters_stake_sysvars_reach_mollusk() {
+    let schedule = EpochSchedule::custom(8_192, 8_192, false);
+    assert_ne!(schedule, EpochSchedule::default());
+    let mut history = StakeHistory::default();
+    history.add(
+        600,
+        StakeHistoryEntry {
+            effective: 400_000_000_000_000_000,
+            activating: 1_500_000_000_000_000,
+            deactivating: 900_000_000_000_000,
+        },
+    );
+    history.add(
+        599,
+        StakeHistoryEntry {
+            effective: 398_000_000_000_000_000,
+            activating: 2_000_000_000_000_000,
+            deactivating: 0,
+        },
+    );
+    let fetcher = MockFetcher::new()
+        .with_account(sysvar::epoch_schedule::ID, sysvar_account(&schedule))
+        .with_account(sysvar::stake_history::ID, sysvar_account(&history));
+
+    let mut mollusk = Mollusk::default();
+    let store = store(&fetcher)
+        .with_synced_epoch_schedule(&mut mollusk)
+        .await
+        .unwrap()
+        .with_synced_stake_history(&mut mollusk)
+        .await
+        .unwrap();
+    assert_eq!(mollusk.sysvars.epoch_schedule, schedule);
+    assert!(!mollusk.sysvars.epoch_schedule.warmup);
+    assert_eq!(mollusk.sysvars.stake_history, history);
+    assert_eq!(mollusk.sysvars.stake_history.get(600).unwrap().deactivating, 900_000_000_000_000);
+    assert!(store.warnings().is_empty());
+}
+
+#[tokio::test]
+async fn unparsable_stake_sysvars_are_errors_naming_them() {
+    let fetcher = MockFetcher::new()
+        .with_account(sysvar::epoch_schedule::ID, sysvar_account(&1u8))
+        .with_account(sysvar::stake_history::ID, sysvar_account(&[1u8; 7]));
+
+    let error = store(&fetcher)
+        .with_synced_epoch_schedule(&mut Mollusk::default())
+        .await
+        .map(|_| ())
+        .unwrap_err();
+    assert!(matches!(error, RpcError::InvalidSysvar { sysvar: "EpochSchedule", .. }), "{error}");
+    assert!(error.to_string().contains("EpochSchedule"), "{error}");
+
+    let error = store(&fetcher)
+        .with_synced_stake_history(&mut Mollusk::default())
+        .await
+        .map(|_| ())
+        .unwrap_err();
+    assert!(matches!(error, RpcError::InvalidSysvar { sysvar: "StakeHistory", .. }), "{error}");
+}
+
+/// A mock at slot 5 000 whose Clock account still reports slot 1, and what it serves.
+fn stale_clock() -> (MockFetcher, Pubkey, Account) {
+    let mut clock = Mollusk::default().sysvars.clock;
+    clock.slot = 1;
+    let wallet = Pubkey::new_unique();
+    let clock_account = sysvar_account(&clock);
+    let fetcher = MockFetcher::new()
+        .with_slot(5_000)
+        .with_account(wallet, account(1_000_000_000, 0))
+        .with_account(sysvar::clock::ID, clock_account.clone());
+    (fetcher, wallet, clock_account)
+}
+
+#[tokio::test]
+async fn a_clock_meta_gets_mollusks_clock() {
+    let (fetcher, wallet, _) = stale_clock();
+    let ix = instruction(&[wallet, sysvar::clock::ID]);
+    let mut mollusk = Mollusk::default();
+    let store = store(&fetcher)
+        .from_instruction(&ix)
+        .await
+        .unwrap()
+        // Syncing after the fetch still shows in the accounts
+        .with_synced_slot(&mut mollusk)
+        .await
+        .unwrap();
+    assert_eq!(fetcher.requested_pubkeys(), [wallet]);
+    assert!(!store.cache.contains_key(&sysvar::clock::ID));
+
+    let accounts = store.instruction_accounts(&mollusk, &ix).unwrap();
+    assert_eq!(mollusk.sysvars.clock.slot, 5_000);
+    assert_eq!(accounts[1].0, sysvar::clock::ID);
+    assert_eq!(accounts[1].1.data, bincode::serialize(&mollusk.sysvars.clock).unwrap());
+}
+
+#[tokio::test]
+async fn fetch_real_sysvars_passes_the_clusters_clock() {
+    let (fetcher, wallet, clock_account) = stale_clock();
+    let ix = instruction(&[wallet, sysvar::clock::ID]);
+    let mut mollusk = Mollusk::default();
+    let store = store(&fetcher)
+        .fetch_real_sysvars()
+        .from_instruction(&ix)
+        .await
+        .unwrap()
+        .with_synced_slot(&mut mollusk)
+        .await
+        .unwrap();
+    assert!(fetcher.requested_pubkeys().contains(&sysvar::clock::ID));
+
+    let accounts = store.instruction_accounts(&mollusk, &ix).unwrap();
+    assert_eq!(accounts[1].1, clock_account);
+    // Mollusk's own clock is unaffected
+    assert_eq!(mollusk.sysvars.clock.slot, 5_000);
+}
@@ -0,0 +1,208 @@
+//! Parsed token state: `get_token_account`, `get_mint` and `token_balance` decode
+//! accounts replayed from fixtures, and say which check failed for a Token-2022
+//! account read as an SPL Token one, a truncated account, a mint read as a token
+//! account and an uninitialized account.
+
+mod common;
+
+use {
+    common::{instruction, store, temp_dir},
+    mollusk_on_demand::{testing::MockFetcher, RpcAccountStore, RpcError, TokenStateError},
+    solana_account::Account,
+    solana_program_pack::Pack,
+    solana_pubkey::Pubkey,
+    solana_rent::Rent,
+    spl_token_interface::state::{Account as TokenAccount, AccountState, Mint},
+};
+
+const USDC: Pubkey = Pubkey::from_str_const("EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v");
+const SPL_TOKEN: Pubkey = spl_token_interface::ID;
+const TOKEN_2022: Pubkey = mollusk_svm_programs_token::token2022::ID;
+const AMOUNT: u64 = 1_234_567;
+
+fn token_account(owner: Pubkey, data: Vec<u8>) -> Account {
+    Account {
+        lamports: Rent::default().minimum_balance(data.len()),
+        data,
+        owner,
+        executable: false,
+        rent_epoch: 0,
+    }
+}
+
+/// A USDC token account holding [`AMOUNT`], packed as the chain stores it.
+fn usdc_account_data(wallet: Pubkey) -> Vec<u8> {
+    let mut data = vec![0; TokenAccount::LEN];
+    TokenAccount {
+        mint: USDC,
+        owner: wallet,
+        amount: AMOUNT,
+        state: AccountState::Initialized,
+        ..TokenAccount::default()
+    }
+    .pack_into_slice(&mut data);
+    data
+}
+
+fn usdc_mint_data() -> Vec<u8> {
+    let mut data = vec![0; Mint::LEN];
+    Mint {
+        supply: 10_000_000_000,
+        decimals: 6,
+        is_initialized: true,
+        ..Mint::default()
+    }
+    .pack_into_slice(&mut data);
+    data
+}
+
+/// An offline store replaying `accounts` from fixtures written by an earlier fetch.
+async fn cloned(accounts: Vec<(Pubkey, Account)>) -> RpcAccountStore {
+    let pubkeys: Vec<Pubkey> = accounts.iter().map(|(pubkey, _)| *pubkey).collect();
+    let dir = temp_dir("token");
+    store(&MockFetcher::new().with_accounts(accounts))
+        .with_fixture_dir(&dir)
+        .from_instruction(&instruction(&pubkeys))
+        .await
+        .unwrap();
+    store(&MockFetcher::new())
+        .with_fixture_dir(&dir)
+        .offline()
+        .from_instruction(&instruction(&pubkeys))
+        .await
+        .unwrap()
+}
+
+fn invalid(error: RpcError) -> (&'static str, TokenStateError) {
+    let RpcError::InvalidTokenState { state, reason, .. } = error else {
+        panic!("expected InvalidTokenState, got {error}");
+    };
+    (state, reason)
+}
+
+#[tokio::test]
+async fn cloned_accounts_and_mints_decode() {
+    let (vault, mint, wallet) = (Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+    let store = cloned(vec![
+        (vault, token_account(SPL_TOKEN, usdc_account_data(wallet))),
+        (mint, token_account(SPL_TOKEN, usdc_mint_data())),
+    ])
+    .await;
+
+    let account = store.get_token_account(&vault).unwrap();
+    assert_eq!((account.mint, account.owner, account.amount), (USDC, wallet, AMOUNT));
+    assert_eq!(account.state, AccountState::Initialized);
+    assert_eq!(store.token_balance(&vault).unwrap(), AMOUNT);
+
+    let mint = store.get_mint(&mint).unwrap();
+    assert_eq!((mint.supply, mint.decimals), (10_000_000_000, 6));
+}
+
+#[tokio::test]
+async fn a_token_2022_account_is_not_an_spl_token_one() {
+    let vault = Pubkey::new_unique();
+    let store =
+        cloned(vec![(vault, token_account(TOKEN_2022, usdc_account_data(Pubkey::new_unique())))])
+            .await;
+
+    let error = store.get_token_account(&vault).unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        format!(
+            "[{}] Account {vault} can't be read as a token account: owned by {TOKEN_2022} \
+             (SPL Token-2022), expected {SPL_TOKEN} (SPL Token)",
+            error.code()
+        )
+    );
+    assert_eq!(
+        invalid(error).1,
+        TokenStateError::WrongOwner {
+            expected: vec![SPL_TOKEN],
+            actual: TOKEN_2022
+        }
+    );
+    // Either program's accounts have a balance
+    assert_eq!(store.token_balance(&vault).unwrap(), AMOUNT);
+}
+
+#[tokio::test]
+async fn a_truncated_account_has_the_wrong_length() {
+    let vault = Pubkey::new_unique();
+    let mut data = usdc_account_data(Pubkey::new_unique());
+    data.truncate(TokenAccount::LEN - 1);
+    let store = cloned(vec![(vault, token_account(SPL_TOKEN, data))]).await;
+
+    let wrong_length = TokenStateError::WrongLength {
+        expected: TokenAccount::LEN,
+        actual: TokenAccount::LEN - 1,
+    };
+    assert_eq!(invalid(store.get_token_account(&vault).unwrap_err()).1, wrong_length);
+    assert_eq!(invalid(store.token_balance(&vault).unwrap_err()).1, wrong_length);
+    assert_eq!(wrong_length.to_string(), "164 bytes of data, expected 165");
+}
+
+#[tokio::test]
+async fn a_mint_is_not_a_token_account_or_the_reverse() {
+    let (vault, mint) = (Pubkey::new_unique(), Pubkey::new_unique());
+    let store = cloned(vec![
+        (vault, token_account(SPL_TOKEN, usdc_account_data(Pubkey::new_unique()))),
+        (mint, token_account(SPL_TOKEN, usdc_mint_data())),
+    ])
+    .await;
+
+    let (state, reason) = invalid(store.get_token_account(&mint).unwrap_err());
+    assert_eq!(state, "token account");
+    assert_eq!(
+        reason,
+        TokenStateError::WrongLength {
+            expected: TokenAccount::LEN,
+            actual: Mint::LEN
+        }
+    );
+    let (state, reason) = invalid(store.get_mint(&vault).unwrap_err());
+    assert_eq!(state, "mint");
+    assert_eq!(
+        reason,
+        TokenStateError::WrongLength {
+            expected: Mint::LEN,
+            actual: TokenAccount::LEN
+        }
+    );
+}
+
+#[tokio::test]
+async fn an_uninitialized_account_says_so() {
+    let (vault, mint) = (Pubkey::new_unique(), Pubkey::new_unique());
+    let store = cloned(vec![
+        (vault, token_account(SPL_TOKEN, vec![0; TokenAccount::LEN])),
+        (mint, token_account(SPL_TOKEN, vec![0; Mint::LEN])),
+    ])
+    .await;
+
+    assert_eq!(
+        invalid(store.get_token_account(&vault).unwrap_err()).1,
+        TokenStateError::Uninitialized
+    );
+    assert_eq!(invalid(store.get_mint(&mint).unwrap_err()).1, TokenStateError::Uninitialized);
+    let missing = store.get_mint(&Pubkey::new_unique()).unwrap_err();
+    assert!(matches!(missing, RpcError::AccountNotFound(_)), "{missing}");
+}
+
+#[cfg(feature = "token-2022")]
+#[tokio::test]
+async fn token_2022_state_decodes_with_extensions() {
+    let (vault, mint, wallet) = (Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+    let store = cloned(vec![
+        (vault, token_account(TOKEN_2022, usdc_account_data(wallet))),
+        (mint, token_account(TOKEN_2022, usdc_mint_data())),
+        (Pubkey::new_from_array([9; 32]), token_account(SPL_TOKEN, usdc_mint_data())),
+    ])
+    .await;
+
+    let account = store.get_token_2022_account(&vault).unwrap();
+    assert_eq!((account.base.owner, account.base.amount), (wallet, AMOUNT));
+    assert_eq!(store.get_mint_2022(&mint).unwrap().base.decimals, 6);
+
+    let error = store.get_mint_2022(&Pubkey::new_from_array([9; 32])).unwrap_err();
+    assert!(matches!(invalid(error).1, TokenStateError::WrongOwner { .. }));
+}
@@ -0,0 +1,177 @@
+//! `token_deltas` and `assert_token_delta` over real SPL Token and Token-2022
+//! instructions run by Mollusk: both sides of a transfer move by the amount, a mint
+//! and the wallet signing aren't token accounts, and Token-2022 accounts with
+//! extensions read like any other.
+
+mod common;
+
+use {
+    common::store,
+    mollusk_on_demand::{process_instruction_with_store, testing::MockFetcher, RpcAccountStore},
+    mollusk_svm::{result::InstructionResult, Mollusk},
+    solana_account::Account,
+    solana_instruction::{AccountMeta, Instruction},
+    solana_program_pack::Pack,
+    solana_pubkey::Pubkey,
+    solana_rent::Rent,
+    solana_sdk_ids::system_program,
+    spl_token_interface::state::{Account as TokenAccount, AccountState, Mint},
+    std::{
+        collections::HashMap,
+        panic::{catch_unwind, AssertUnwindSafe},
+    },
+};
+
+const SPL_TOKEN: Pubkey = mollusk_svm_programs_token::token::ID;
+const TOKEN_2022: Pubkey = mollusk_svm_programs_token::token2022::ID;
+
+fn key(byte: u8) -> Pubkey {
+    Pubkey::new_from_array([byte; 32])
+}
+
+const MINT: u8 = 1;
+const ALICE: u8 = 2;
+const ALICE_USDC: u8 = 3;
+const VAULT: u8 = 4;
+
+fn rent_exempt(owner: Pubkey, data: Vec<u8>) -> Account {
+    Account {
+        lamports: Rent::default().minimum_balance(data.len()),
+        data,
+        owner,
+        executable: false,
+        rent_epoch: 0,
+    }
+}
+
+/// A token account of the mint holding `amount`, with Token-2022's
+/// `ImmutableOwner` extension after the base state for `TOKEN_2022` ones.
+fn token_account(program: Pubkey, owner: Pubkey, amount: u64) -> Account {
+    let mut data = vec![0; TokenAccount::LEN];
+    TokenAccount {
+        mint: key(MINT),
+        owner,
+        amount,
+        state: AccountState::Initialized,
+        ..TokenAccount::default()
+    }
+    .pack_into_slice(&mut data);
+    if program == TOKEN_2022 {
+        // Account type 2, then the extension's type 7 and empty length
+        data.extend_from_slice(&[2, 7, 0, 0, 0]);
+    }
+    rent_exempt(program, data)
+}
+
+fn mint(program: Pubkey, authority: Pubkey) -> Account {
+    let mut data = vec![0; Mint::LEN];
+    Mint {
+        mint_authority: Some(authority).into(),
+        supply: 1_500,
+        decimals: 6,
+        is_initialized: true,
+        ..Mint::default()
+    }
+    .pack_into_slice(&mut data);
+    rent_exempt(program, data)
+}
+
+/// Alice's account holding 500 and the vault holding 1 000, with the mint and
+/// Alice's wallet, on `program`.
+fn accounts(program: Pubkey) -> RpcAccountStore {
+    store(&MockFetcher::new())
+        .with_accounts([
+            (key(MINT), mint(program, key(ALICE))),
+            (key(ALICE), rent_exempt(system_program::ID, vec![])),
+            (key(ALICE_USDC), token_account(program, key(ALICE), 500)),
+            (key(VAULT), token_account(program, key(9), 1_000)),
+        ])
+        .with_labels(&[(key(ALICE_USDC), "alice"), (key(VAULT), "vault")])
+}
+
+/// A Token instruction of `program` whose data is `tag` and `amount`, on `accounts`
+/// then Alice signing.
+fn token_instruction(program: Pubkey, tag: u8, amount: u64, accounts: [u8; 2]) -> Instruction {
+    let mut data = vec![tag];
+    data.extend_from_slice(&amount.to_le_bytes());
+    Instruction::new_with_bytes(
+        program,
+        &data,
+        vec![
+            AccountMeta::new(key(accounts[0]), false),
+            AccountMeta::new(key(accounts[1]), false),
+            AccountMeta::new_readonly(key(ALICE), true),
+        ],
+    )
+}
+
+fn transfer(program: Pubkey, amount: u64) -> Instruction {
+    token_instruction(program, 3, amount, [ALICE_USDC, VAULT])
+}
+
+fn run(store: &RpcAccountStore, instruction: &Instruction) -> InstructionResult {
+    let mut mollusk = Mollusk::default();
+    mollusk_svm_programs_token::token::add_program(&mut mollusk);
+    mollusk_svm_programs_token::token2022::add_program(&mut mollusk);
+    let result = process_instruction_with_store(&mollusk, store, instruction).unwrap();
+    assert!(result.program_result.is_ok(), "{:?}", result.program_result);
+    result
+}
+
+#[test]
+fn a_transfer_moves_both_sides_by_the_amount() {
+    for program in [SPL_TOKEN, TOKEN_2022] {
+        let store = accounts(program);
+        let result = run(&store, &transfer(program, 100));
+        // Alice's wallet isn't a token account
+        assert_eq!(
+            store.token_deltas(&result),
+            HashMap::from([(key(ALICE_USDC), -100), (key(VAULT), 100)]),
+            "{program}"
+        );
+        store.assert_token_delta(&result, &key(ALICE_USDC), -100);
+        store.assert_token_delta(&result, &key(VAULT), 100);
+    }
+}
+
+#[test]
+fn a_mint_isnt_a_token_account() {
+    for program in [SPL_TOKEN, TOKEN_2022] {
+        let store = accounts(program);
+        let mint_to = token_instruction(program, 7, 250, [MINT, VAULT]);
+        let result = run(&store, &mint_to);
+        assert_eq!(
+            store.token_deltas(&result),
+            HashMap::from([(key(VAULT), 250)]),
+            "{program}"
+        );
+    }
+}
+
+#[test]
+fn a_wrong_delta_panics_with_the_label_and_amounts() {
+    let store = accounts(TOKEN_2022);
+    let result = run(&store, &transfer(TOKEN_2022, 100));
+    let panic = catch_unwind(AssertUnwindSafe(|| {
+        store.assert_token_delta(&result, &key(ALICE_USDC), -90);
+    }))
+    .unwrap_err();
+    let alice = key(ALICE_USDC).to_string();
+    assert_eq!(
+        panic.downcast_ref::<String>().unwrap(),
+        &format!(
+            "expected the amount of alice ({}…{}) to change by -90, it changed by -100 \
+             (500 -> 400)",
+            &alice[..4],
+            &alice[alice.len() - 4..]
+        )
+    );
+}
+
+#[test]
+#[should_panic(expected = "is not a token account in the result, before or after")]
+fn a_wallet_has_no_token_delta() {
+    let store = accounts(SPL_TOKEN);
+    let result = run(&store, &transfer(SPL_TOKEN, 100));
+    store.assert_token_delta(&result, &key(ALICE), 0);
+}
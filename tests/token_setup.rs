@@ -0,0 +1,147 @@
+//! Token setup in the cache: mocked wrapped SOL accounts are valid SPL Token
+//! accounts, and their transfers move lamports with checked arithmetic, leaving both
+//! accounts untouched when they can't.
+
+mod common;
+
+use {
+    common::{account, store},
+    mollusk_on_demand::{testing::MockFetcher, RpcAccountStore, RpcError, TokenOperationError},
+    mollusk_svm::Mollusk,
+    solana_program_pack::Pack,
+    solana_pubkey::Pubkey,
+    spl_token_interface::{
+        instruction, native_mint,
+        state::{Account as TokenAccount, AccountState},
+    },
+};
+
+const LAMPORTS_PER_SOL: u64 = 1_000_000_000;
+
+/// A store with two wrapped SOL accounts of one SOL each.
+fn wsol_pair() -> (RpcAccountStore, Pubkey, Pubkey) {
+    let (from, to) = (Pubkey::new_unique(), Pubkey::new_unique());
+    let store = store(&MockFetcher::new())
+        .with_wsol_account(from, Pubkey::new_unique(), LAMPORTS_PER_SOL)
+        .unwrap()
+        .with_wsol_account(to, Pubkey::new_unique(), LAMPORTS_PER_SOL)
+        .unwrap();
+    (store, from, to)
+}
+
+fn transfer_error(store: &mut RpcAccountStore, from: &Pubkey, to: &Pubkey) -> TokenOperationError {
+    let before = store.cache.clone();
+    let error = store.transfer_tokens_in_cache(from, to, 1_000).unwrap_err();
+    assert_eq!(store.cache, before, "a failed transfer changed the cache");
+    let RpcError::TokenOperation { reason, .. } = error else {
+        panic!("expected TokenOperation, got {error}");
+    };
+    reason
+}
+
+#[test]
+fn a_mocked_wsol_account_unpacks_as_a_native_token_account() {
+    let (address, owner) = (Pubkey::new_unique(), Pubkey::new_unique());
+    let store = store(&MockFetcher::new())
+        .with_wsol_account(address, owner, LAMPORTS_PER_SOL)
+        .unwrap();
+    let reserve = store.rent().minimum_balance(TokenAccount::LEN);
+
+    let account = &store.cache[&address];
+    assert_eq!(account.owner, spl_token_interface::ID);
+    assert_eq!(account.lamports, LAMPORTS_PER_SOL);
+    let state = TokenAccount::unpack(&account.data).unwrap();
+    assert_eq!(state.mint, native_mint::ID);
+    assert_eq!(state.owner, owner);
+    assert_eq!(state.amount, LAMPORTS_PER_SOL - reserve);
+    assert_eq!(state.is_native, Some(reserve).into());
+    assert_eq!(state.state, AccountState::Initialized);
+}
+
+#[test]
+fn lamports_below_the_reserve_are_an_error() {
+    let store = store(&MockFetcher::new());
+    let reserve = store.rent().minimum_balance(TokenAccount::LEN);
+    let error = store
+        .with_wsol_account(Pubkey::new_unique(), Pubkey::new_unique(), reserve - 1)
+        .map(|_| ())
+        .unwrap_err();
+
+    let RpcError::TokenOperation { reason, .. } = &error else {
+        panic!("expected TokenOperation, got {error}");
+    };
+    assert_eq!(
+        *reason,
+        TokenOperationError::LamportsBelowAmount {
+            lamports: reserve - 1,
+            needed: reserve,
+        }
+    );
+    assert_eq!(error.code(), "token_native_lamports_short");
+}
+
+#[test]
+fn the_token_program_transfers_out_of_a_mocked_wsol_account() {
+    let (alice, bob) = (Pubkey::new_unique(), Pubkey::new_unique());
+    let (alice_wsol, bob_wsol) = (Pubkey::new_unique(), Pubkey::new_unique());
+    let mut mollusk = Mollusk::default();
+    mollusk_svm_programs_token::token::add_program(&mut mollusk);
+    let mut store = store(&MockFetcher::new())
+        .with_accounts([(alice, account(LAMPORTS_PER_SOL, 0))])
+        .with_wsol_account(alice_wsol, alice, 2 * LAMPORTS_PER_SOL)
+        .unwrap()
+        .with_wsol_account(bob_wsol, bob, LAMPORTS_PER_SOL)
+        .unwrap();
+    let before = store.token_balance(&bob_wsol).unwrap();
+
+    let transfer = instruction::transfer(
+        &spl_token_interface::ID,
+        &alice_wsol,
+        &bob_wsol,
+        &alice,
+        &[],
+        LAMPORTS_PER_SOL / 2,
+    )
+    .unwrap();
+    let accounts = store.instruction_accounts(&mollusk, &transfer).unwrap();
+    let result = mollusk.process_instruction(&transfer, &accounts);
+    assert!(result.program_result.is_ok(), "{:?}", result.program_result);
+
+    store.apply_result(&result);
+    assert_eq!(store.token_balance(&bob_wsol).unwrap(), before + LAMPORTS_PER_SOL / 2);
+    assert_eq!(store.cache[&alice_wsol].lamports, 3 * LAMPORTS_PER_SOL / 2);
+    assert_eq!(store.cache[&bob_wsol].lamports, 3 * LAMPORTS_PER_SOL / 2);
+}
+
+#[test]
+fn wrapped_sol_moves_its_lamports_with_the_amount() {
+    let (mut store, from, to) = wsol_pair();
+    store.transfer_tokens_in_cache(&from, &to, 1_000).unwrap();
+
+    assert_eq!(store.cache[&from].lamports, LAMPORTS_PER_SOL - 1_000);
+    assert_eq!(store.cache[&to].lamports, LAMPORTS_PER_SOL + 1_000);
+    let reserve = store.rent().minimum_balance(TokenAccount::LEN);
+    assert_eq!(store.token_balance(&from).unwrap(), LAMPORTS_PER_SOL - reserve - 1_000);
+    assert_eq!(store.token_balance(&to).unwrap(), LAMPORTS_PER_SOL - reserve + 1_000);
+}
+
+#[test]
+fn a_source_short_of_lamports_is_an_error() {
+    let (mut store, from, to) = wsol_pair();
+    store.cache.get_mut(&from).unwrap().lamports = 10;
+
+    let reason = transfer_error(&mut store, &from, &to);
+    let TokenOperationError::LamportsBelowAmount { lamports, needed } = reason else {
+        panic!("expected LamportsBelowAmount, got {reason}");
+    };
+    assert_eq!(lamports, 10);
+    assert_eq!(needed, LAMPORTS_PER_SOL);
+}
+
+#[test]
+fn overflowing_the_destination_lamports_is_an_error() {
+    let (mut store, from, to) = wsol_pair();
+    store.cache.get_mut(&to).unwrap().lamports = u64::MAX - 1;
+
+    assert_eq!(transfer_error(&mut store, &from, &to), TokenOperationError::Overflow);
+}
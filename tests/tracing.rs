@@ -0,0 +1,301 @@
+//! The stable span and event fields of the `tracing` feature, read back through a
+//! recording subscriber.
+
+#![cfg(feature = "tracing")]
+
+mod common;
+
+use {
+    common::{account, instruction, pubkeys, serving, store, temp_dir},
+    mollusk_on_demand::testing::MockFetcher,
+    mollusk_svm::Mollusk,
+    solana_account::Account,
+    solana_pubkey::Pubkey,
+    solana_sdk_ids::bpf_loader_upgradeable,
+    std::{
+        collections::BTreeMap,
+        fmt,
+        sync::{Arc, Mutex},
+    },
+    tracing::{
+        field::{Field, Visit},
+        span, Event, Subscriber,
+    },
+    tracing_subscriber::{layer::Context, prelude::*, registry::LookupSpan, Layer},
+};
+
+/// A span or event: its name and fields, all formatted as strings.
+#[derive(Debug, Clone)]
+struct Record {
+    name: &'static str,
+    fields: BTreeMap<&'static str, String>,
+}
+
+impl Record {
+    fn field(&self, name: &str) -> &str {
+        self.fields
+            .get(name)
+            .unwrap_or_else(|| panic!("{} has no field {name}: {self:?}", self.name))
+    }
+}
+
+struct Fields<'a>(&'a mut BTreeMap<&'static str, String>);
+
+impl Visit for Fields<'_> {
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.0.insert(field.name(), format!("{value:?}"));
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0.insert(field.name(), value.to_string());
+    }
+}
+
+#[derive(Clone, Default)]
+struct Recorder {
+    spans: Arc<Mutex<Vec<Record>>>,
+    events: Arc<Mutex<Vec<Record>>>,
+}
+
+impl Recorder {
+    fn spans(&self, name: &str) -> Vec<Record> {
+        let spans = self.spans.lock().unwrap();
+        spans.iter().filter(|span| span.name == name).cloned().collect()
+    }
+
+    fn events(&self, name: &str) -> Vec<Record> {
+        let events = self.events.lock().unwrap();
+        events.iter().filter(|event| event.name == name).cloned().collect()
+    }
+}
+
+impl<S: Subscriber + for<'a> LookupSpan<'a>> Layer<S> for Recorder {
+    fn on_new_span(&self, attrs: &span::Attributes<'_>, _: &span::Id, _: Context<'_, S>) {
+        let mut fields = BTreeMap::new();
+        attrs.record(&mut Fields(&mut fields));
+        self.spans.lock().unwrap().push(Record {
+            name: attrs.metadata().name(),
+            fields,
+        });
+    }
+
+    fn on_event(&self, event: &Event<'_>, _: Context<'_, S>) {
+        let mut fields = BTreeMap::new();
+        event.record(&mut Fields(&mut fields));
+        self.events.lock().unwrap().push(Record {
+            name: event.metadata().name(),
+            fields,
+        });
+    }
+}
+
+#[tokio::test]
+async fn fetches_emit_the_documented_fields() {
+    let recorder = Recorder::default();
+    let _guard = tracing_subscriber::registry()
+        .with(recorder.clone())
+        .set_default();
+
+    // One account mocked, one replayed from a fixture and one fetched
+    let keys = pubkeys(3);
+    let dir = temp_dir("tracing");
+    store(&MockFetcher::new())
+        .with_accounts([(keys[1], account(7, 0))])
+        .write_fixtures(&dir, &keys[1..2])
+        .unwrap();
+    let fetcher = serving(&keys[2..]);
+    store(&fetcher)
+        .with_accounts([(keys[0], account(1, 0))])
+        .with_fixture_dir(&dir)
+        .log_fetches()
+        .from_instruction(&instruction(&keys))
+        .await
+        .unwrap();
+
+    let spans = recorder.spans("from_instruction");
+    assert_eq!(spans.len(), 1);
+    assert_eq!(spans[0].field("pubkeys"), "3");
+
+    let lookups = recorder.events("cache_lookup");
+    assert_eq!(lookups.len(), 1);
+    assert_eq!(lookups[0].field("requested"), "3");
+    assert_eq!(lookups[0].field("cache_hits"), "1");
+    assert_eq!(lookups[0].field("fixtures"), "1");
+    assert_eq!(lookups[0].field("cache_misses"), "1");
+
+    let requests = recorder.events("rpc_request");
+    assert_eq!(requests.len(), 1);
+    assert_eq!(requests[0].field("method"), "getMultipleAccounts");
+    assert_eq!(requests[0].field("chunk_size"), "1");
+    assert!(requests[0].field("latency_ms").parse::<u64>().is_ok());
+
+    let fetches = recorder.events("fetch");
+    assert_eq!(fetches.len(), 1);
+    assert_eq!(fetches[0].field("cache_hits"), "1");
+    assert_eq!(fetches[0].field("fixtures"), "1");
+    assert_eq!(fetches[0].field("fetched"), "1");
+    assert_eq!(fetches[0].field("batches"), "1");
+    assert_eq!(recorder.events("fetched_pubkeys").len(), 1);
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[tokio::test]
+async fn fixtures_are_not_counted_as_cache_hits_in_stats() {
+    let keys = pubkeys(2);
+    let dir = temp_dir("tracing-stats");
+    store(&MockFetcher::new())
+        .with_accounts([(keys[0], account(7, 0))])
+        .write_fixtures(&dir, &keys[..1])
+        .unwrap();
+    let fetcher = serving(&keys[1..]);
+    let store = store(&fetcher)
+        .with_fixture_dir(&dir)
+        .from_instruction(&instruction(&keys))
+        .await
+        .unwrap();
+
+    let stats = store.stats();
+    assert_eq!(stats.accounts_from_cache, 0);
+    assert_eq!(stats.accounts_from_fixtures, 1);
+    assert_eq!(stats.accounts_fetched, 1);
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[tokio::test]
+async fn programs_mollusk_already_has_are_reported_as_skipped() {
+    let recorder = Recorder::default();
+    let _guard = tracing_subscriber::registry()
+        .with(recorder.clone())
+        .set_default();
+
+    let mut mollusk = Mollusk::default();
+    mollusk_svm_programs_token::token::add_program(&mut mollusk);
+    let mut data = 2u32.to_le_bytes().to_vec();
+    data.extend_from_slice(Pubkey::new_unique().as_ref());
+    let token = Account {
+        data,
+        executable: true,
+        owner: bpf_loader_upgradeable::ID,
+        ..account(1, 0)
+    };
+    store(&MockFetcher::new())
+        .with_accounts([(mollusk_svm_programs_token::token::ID, token)])
+        .add_programs(&mut mollusk)
+        .await
+        .unwrap();
+
+    let skipped = recorder.events("program_skipped");
+    assert_eq!(skipped.len(), 1);
+    assert_eq!(skipped[0].field("program"), mollusk_svm_programs_token::token::ID.to_string());
+    assert_eq!(skipped[0].field("loader"), bpf_loader_upgradeable::ID.to_string());
+    assert!(recorder.events("program_loaded").is_empty());
+}
+
+/// An upgradeable program holding the Token-2022 ELF, and its program data.
+fn program() -> [(Pubkey, Account); 2] {
+    let (program, program_data) = (Pubkey::new_unique(), Pubkey::new_unique());
+    let mut data = 2u32.to_le_bytes().to_vec();
+    data.extend_from_slice(program_data.as_ref());
+    let program_account = Account {
+        data,
+        executable: true,
+        owner: bpf_loader_upgradeable::ID,
+        ..account(1_000_000_000, 0)
+    };
+
+    // The ProgramData header: tag, deployment slot and upgrade authority
+    let mut data = 3u32.to_le_bytes().to_vec();
+    data.extend_from_slice(&0u64.to_le_bytes());
+    data.extend_from_slice(&[0; 33]);
+    data.extend_from_slice(mollusk_svm_programs_token::token2022::ELF);
+    let program_data_account = Account {
+        data,
+        owner: bpf_loader_upgradeable::ID,
+        ..account(1_000_000_000, 0)
+    };
+    [(program, program_account), (program_data, program_data_account)]
+}
+
+#[tokio::test]
+async fn fetch_logs_match_what_the_backend_served() {
+    let recorder = Recorder::default();
+    let _guard = tracing_subscriber::registry()
+        .with(recorder.clone())
+        .set_default();
+
+    let keys = pubkeys(6);
+    let fetcher = serving(&keys[2..]);
+    let store = store(&fetcher)
+        .with_accounts([(keys[0], account(1, 0)), (keys[1], account(2, 0))])
+        .log_fetches()
+        .from_instruction(&instruction(&keys[..4]))
+        .await
+        .unwrap();
+    // Two of these were fetched by the first call
+    store.from_instruction(&instruction(&keys[2..])).await.unwrap();
+
+    let fetches = recorder.events("fetch");
+    assert_eq!(fetches.len(), 2);
+    let fields = |index: usize, name: &str| fetches[index].field(name).parse::<usize>().unwrap();
+    assert_eq!((fields(0, "requested"), fields(0, "cache_hits"), fields(0, "fetched")), (4, 2, 2));
+    assert_eq!((fields(1, "requested"), fields(1, "cache_hits"), fields(1, "fetched")), (4, 2, 2));
+    assert_eq!(fields(0, "fetched") + fields(1, "fetched"), fetcher.requested_pubkeys().len());
+    assert_eq!(fetcher.calls().len(), 2);
+    assert!(fetches[0]
+        .field("message")
+        .starts_with("requested 4 pubkeys: 2 cached, 0 from fixtures, 2 fetched in 1 batch ("));
+
+    let logged: Vec<String> = recorder
+        .events("fetched_pubkeys")
+        .iter()
+        .map(|event| event.field("pubkeys").to_string())
+        .collect();
+    assert_eq!(logged.len(), 2);
+    for (event, pubkeys) in logged.iter().zip([&keys[2..4], &keys[4..]]) {
+        for pubkey in pubkeys {
+            assert!(event.contains(&pubkey.to_string()), "{event}");
+        }
+    }
+}
+
+#[tokio::test]
+async fn the_program_data_pass_is_logged_too() {
+    let recorder = Recorder::default();
+    let _guard = tracing_subscriber::registry()
+        .with(recorder.clone())
+        .set_default();
+
+    let [(program, program_account), (program_data, program_data_account)] = program();
+    let fetcher = MockFetcher::new().with_account(program_data, program_data_account);
+    store(&fetcher)
+        .with_accounts([(program, program_account)])
+        .log_fetches()
+        .add_programs(&mut Mollusk::default())
+        .await
+        .unwrap();
+
+    assert_eq!(recorder.spans("add_programs").len(), 1);
+    let fetches = recorder.events("fetch");
+    assert_eq!(fetches.len(), 1);
+    assert_eq!(fetches[0].field("requested"), "1");
+    assert_eq!(fetches[0].field("cache_hits"), "0");
+    assert_eq!(fetches[0].field("fetched"), "1");
+    let logged = recorder.events("fetched_pubkeys");
+    assert!(logged[0].field("pubkeys").contains(&program_data.to_string()));
+    assert_eq!(fetcher.requested_pubkeys(), [program_data]);
+}
+
+#[tokio::test]
+async fn fetches_arent_logged_unless_asked() {
+    let recorder = Recorder::default();
+    let _guard = tracing_subscriber::registry()
+        .with(recorder.clone())
+        .set_default();
+
+    let keys = pubkeys(2);
+    store(&serving(&keys)).from_instruction(&instruction(&keys)).await.unwrap();
+    assert!(recorder.events("fetch").is_empty());
+    assert!(recorder.events("fetched_pubkeys").is_empty());
+    assert_eq!(recorder.events("cache_lookup").len(), 1);
+}
@@ -0,0 +1,14 @@
+//! What `#[mollusk_test]` accepts, and the errors it gives for what it doesn't,
+//! checked by compiling the cases under `tests/ui`.
+
+#![cfg(feature = "macros")]
+
+/// The cases are built as binaries, where the `#[tokio::test]` the macro expands to
+/// is compiled out, so `pass` only covers the arguments it parses; the expansion
+/// itself runs in `tests/mollusk_test.rs`.
+#[test]
+fn mollusk_test_expansions() {
+    let cases = trybuild::TestCases::new();
+    cases.pass("tests/ui/pass/*.rs");
+    cases.compile_fail("tests/ui/fail/*.rs");
+}
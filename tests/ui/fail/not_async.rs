@@ -0,0 +1,6 @@
+use mollusk_on_demand::mollusk_test;
+
+#[mollusk_test(rpc = "localnet")]
+fn not_async(_store: &mut mollusk_on_demand::RpcAccountStore) {}
+
+fn main() {}
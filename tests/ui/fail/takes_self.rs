@@ -0,0 +1,10 @@
+use mollusk_on_demand::mollusk_test;
+
+struct Suite;
+
+impl Suite {
+    #[mollusk_test(rpc = "localnet")]
+    async fn takes_self(&self, _store: &mut mollusk_on_demand::RpcAccountStore) {}
+}
+
+fn main() {}
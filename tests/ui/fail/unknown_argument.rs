@@ -0,0 +1,6 @@
+use mollusk_on_demand::mollusk_test;
+
+#[mollusk_test(rpc = "localnet", fixture = "tests/fixtures")]
+async fn misspelled(_store: &mut mollusk_on_demand::RpcAccountStore) {}
+
+fn main() {}
@@ -0,0 +1,15 @@
+use {
+    mollusk_on_demand::{mollusk_test, RpcAccountStore},
+    mollusk_svm::Mollusk,
+};
+
+#[mollusk_test(rpc = "localnet")]
+async fn no_arguments() {}
+
+#[mollusk_test(rpc = "localnet")]
+async fn three_arguments(_mollusk: &mut Mollusk, _store: &mut RpcAccountStore, _extra: u8) {}
+
+// Keeps the imports used
+fn _types(_: Mollusk, _: RpcAccountStore) {}
+
+fn main() {}
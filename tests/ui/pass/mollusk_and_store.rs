@@ -0,0 +1,30 @@
+use {
+    mollusk_on_demand::{mollusk_test, RpcAccountStore, RpcError},
+    mollusk_svm::Mollusk,
+    solana_pubkey::Pubkey,
+};
+
+const PROGRAM: Pubkey = Pubkey::new_from_array([7; 32]);
+
+#[mollusk_test(
+    rpc = "http://localhost:8899",
+    fixtures = "tests/fixtures",
+    programs = [PROGRAM],
+    allow_warnings
+)]
+async fn takes_mollusk_and_the_store(
+    mollusk: &mut Mollusk,
+    store: &mut RpcAccountStore,
+) -> Result<(), RpcError> {
+    store.get_or_fetch(&PROGRAM).await?;
+    assert!(mollusk.program_cache.load_program(&PROGRAM).is_some());
+    Ok(())
+}
+
+#[mollusk_test(rpc = "env")]
+async fn reads_the_environment(_store: &mut RpcAccountStore) {}
+
+#[mollusk_test]
+async fn defaults_to_the_environment(_store: &mut RpcAccountStore) {}
+
+fn main() {}
@@ -0,0 +1,8 @@
+use mollusk_on_demand::{mollusk_test, RpcAccountStore};
+
+#[mollusk_test(rpc = "localnet")]
+async fn takes_the_store(store: &mut RpcAccountStore) {
+    assert!(store.cache.is_empty());
+}
+
+fn main() {}
@@ -0,0 +1,74 @@
+//! Pruning fixtures to the accounts a test handed to Mollusk, keeping programs.
+
+mod common;
+
+use {
+    common::{account, instruction, pubkeys, serving, store, temp_dir},
+    mollusk_on_demand::{testing::MockFetcher, RpcAccountStore},
+    mollusk_svm::Mollusk,
+    solana_account::Account,
+    solana_pubkey::Pubkey,
+    std::collections::HashSet,
+};
+
+/// A store that cloned five accounts and a program but handed only the first three
+/// accounts to Mollusk.
+async fn store_using_three_of_five() -> (RpcAccountStore, Vec<Pubkey>, Pubkey) {
+    let keys = pubkeys(5);
+    let program = Pubkey::new_unique();
+    let program_account = Account {
+        executable: true,
+        owner: solana_sdk_ids::bpf_loader_upgradeable::ID,
+        ..account(1, 36)
+    };
+    let fetcher = serving(&keys).with_account(program, program_account);
+    let mut store = store(&fetcher);
+    store.get_or_fetch_many(&keys).await.unwrap();
+    store.get_or_fetch(&program).await.unwrap();
+    store
+        .instruction_accounts(&Mollusk::default(), &instruction(&keys[..3]))
+        .unwrap();
+    (store, keys, program)
+}
+
+#[tokio::test]
+async fn pruning_a_fixture_file_keeps_the_used_accounts_and_programs() {
+    let (store, keys, program) = store_using_three_of_five().await;
+    let path = temp_dir("prune-file").join("swap.jsonl");
+    store.save_fixture(&path).unwrap();
+
+    let removed = store.prune_fixture_to_used(&path).unwrap();
+    let mut unused = keys[3..].to_vec();
+    unused.sort();
+    assert_eq!(removed, unused);
+
+    let replayed = RpcAccountStore::new_with_fetcher(MockFetcher::new())
+        .offline()
+        .load_fixture(&path)
+        .unwrap();
+    let cached: HashSet<Pubkey> = replayed.cache.keys().copied().collect();
+    let expected: HashSet<Pubkey> = keys[..3].iter().copied().chain([program]).collect();
+    assert_eq!(cached, expected);
+    assert_eq!(replayed.cache[&keys[0]], store.cache[&keys[0]]);
+}
+
+#[tokio::test]
+async fn pruning_a_fixture_dir_deletes_the_unused_files() {
+    let (store, keys, program) = store_using_three_of_five().await;
+    let dir = temp_dir("prune-dir");
+    let all: Vec<Pubkey> = keys.iter().copied().chain([program]).collect();
+    store.write_fixtures(&dir, &all).unwrap();
+
+    let deleted = store.prune_fixture_dir_to_used(&dir).unwrap();
+    assert_eq!(deleted.len(), 2);
+    let left: HashSet<String> = std::fs::read_dir(&dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+        .collect();
+    let expected: HashSet<String> = keys[..3]
+        .iter()
+        .chain([&program])
+        .map(|pubkey| format!("{pubkey}.json"))
+        .collect();
+    assert_eq!(left, expected);
+}
@@ -0,0 +1,204 @@
+//! Vote accounts: `with_vote_account` writes what the Vote program's own types
+//! read back, `get_vote_state` decodes cloned accounts of either stored version,
+//! and `with_vote_accounts_from_cluster` clones the validators with the most stake.
+
+mod common;
+
+use {
+    common::{account, store},
+    mollusk_on_demand::{
+        testing::{FetcherCall, MockFetcher},
+        RpcError, VoteMock,
+    },
+    solana_account::Account,
+    solana_pubkey::Pubkey,
+    solana_rent::Rent,
+    solana_sdk_ids::{system_program, vote},
+    solana_vote_interface::{
+        authorized_voters::AuthorizedVoters,
+        state::{BlockTimestamp, VoteState1_14_11, VoteStateV3, VoteStateVersions},
+    },
+};
+
+fn key(byte: u8) -> Pubkey {
+    Pubkey::new_from_array([byte; 32])
+}
+
+const VOTE_ACCOUNT: u8 = 1;
+const IDENTITY: u8 = 2;
+const WITHDRAWER: u8 = 3;
+
+/// The state of a validator that has been voting for a while: 31 votes above its
+/// root, three epochs of credits and a recent timestamp.
+fn voting_validator() -> VoteStateV3 {
+    VoteStateV3 {
+        commission: 7,
+        authorized_withdrawer: key(WITHDRAWER),
+        authorized_voters: AuthorizedVoters::new(700, key(IDENTITY)),
+        epoch_credits: vec![
+            (700, 432_000, 0),
+            (701, 860_000, 432_000),
+            (702, 1_290_000, 860_000),
+        ],
+        last_timestamp: BlockTimestamp {
+            slot: 303_264_031,
+            timestamp: 1_730_000_000,
+        },
+        ..VoteStateV3::new_rand_for_tests(key(IDENTITY), 303_264_000)
+    }
+}
+
+fn vote_owned(data: Vec<u8>) -> Account {
+    Account {
+        data,
+        owner: vote::ID,
+        ..account(1_000_000_000, 0)
+    }
+}
+
+/// `state` serialized as the Vote program stores it today.
+fn current_account(state: &VoteStateV3) -> Account {
+    let mut data = vec![0; VoteStateV3::size_of()];
+    VoteStateV3::serialize(&VoteStateVersions::new_v3(state.clone()), &mut data).unwrap();
+    vote_owned(data)
+}
+
+#[test]
+fn mocked_vote_accounts_read_back_with_the_vote_programs_types() {
+    let mock = VoteMock {
+        node_pubkey: key(IDENTITY),
+        authorized_withdrawer: key(WITHDRAWER),
+        commission: 5,
+        credits: 1_000,
+    };
+    let store = store(&MockFetcher::new()).with_vote_account(key(VOTE_ACCOUNT), mock);
+    let account = &store.cache[&key(VOTE_ACCOUNT)];
+    assert_eq!(account.owner, vote::ID);
+    assert_eq!(account.data.len(), VoteStateV3::size_of());
+    assert_eq!(
+        account.lamports,
+        Rent::default().minimum_balance(account.data.len())
+    );
+
+    let versioned: VoteStateVersions = bincode::deserialize(&account.data).unwrap();
+    let VoteStateVersions::V3(state) = versioned else {
+        panic!("expected the current version, got {versioned:?}");
+    };
+    assert_eq!(VoteStateV3::deserialize(&account.data).unwrap(), *state);
+    assert_eq!(
+        (
+            state.node_pubkey,
+            state.authorized_withdrawer,
+            state.commission
+        ),
+        (key(IDENTITY), key(WITHDRAWER), 5)
+    );
+    assert_eq!(
+        state.authorized_voters.get_authorized_voter(0),
+        Some(key(IDENTITY))
+    );
+    assert_eq!(state.credits(), 1_000);
+    assert!(state.votes.is_empty() && state.root_slot.is_none());
+    assert_eq!(store.get_vote_state(&key(VOTE_ACCOUNT)).unwrap(), *state);
+}
+
+#[tokio::test]
+async fn cloned_vote_accounts_decode_whichever_version_they_are() {
+    let state = voting_validator();
+    // Accounts not written to since 1.14.11 still hold that version
+    let mut legacy = vec![0; VoteState1_14_11::size_of()];
+    let versioned = VoteStateVersions::V1_14_11(Box::new(VoteState1_14_11::from(state.clone())));
+    bincode::serialize_into(&mut legacy[..], &versioned).unwrap();
+    let fetcher = MockFetcher::new()
+        .with_account(key(VOTE_ACCOUNT), current_account(&state))
+        .with_account(key(4), vote_owned(legacy));
+    let mut store = store(&fetcher);
+    store
+        .get_or_fetch_many(&[key(VOTE_ACCOUNT), key(4)])
+        .await
+        .unwrap();
+
+    for pubkey in [key(VOTE_ACCOUNT), key(4)] {
+        let decoded = store.get_vote_state(&pubkey).unwrap();
+        assert_eq!(decoded, state, "{pubkey}");
+        assert_eq!(decoded.credits(), 1_290_000);
+        assert_eq!(decoded.root_slot, Some(303_264_000));
+    }
+}
+
+#[test]
+fn accounts_that_arent_vote_accounts_are_errors() {
+    let store = store(&MockFetcher::new()).with_accounts([
+        (key(1), account(1_000_000_000, VoteStateV3::size_of())),
+        (key(2), vote_owned(vec![0; VoteStateV3::size_of()])),
+        (key(3), vote_owned(vec![9, 0, 0, 0])),
+    ]);
+    for (pubkey, expected) in [
+        (
+            key(1),
+            format!(
+                "owned by {} (System Program), expected {} (Vote Program)",
+                system_program::ID,
+                vote::ID
+            ),
+        ),
+        (key(2), "not initialized".to_string()),
+    ] {
+        let error = store.get_vote_state(&pubkey).unwrap_err();
+        let RpcError::InvalidVoteAccount {
+            pubkey: invalid,
+            reason,
+        } = &error
+        else {
+            panic!("expected InvalidVoteAccount, got {error}");
+        };
+        assert_eq!((invalid, reason), (&pubkey, &expected));
+    }
+    let error = store.get_vote_state(&key(3)).unwrap_err();
+    let RpcError::InvalidVoteAccount { reason, .. } = &error else {
+        panic!("expected InvalidVoteAccount, got {error}");
+    };
+    assert!(reason.starts_with("invalid value: integer `9`"), "{reason}");
+
+    let error = store.get_vote_state(&key(4)).unwrap_err();
+    let RpcError::AccountNotFound(pubkey) = &error else {
+        panic!("expected AccountNotFound, got {error}");
+    };
+    assert_eq!(*pubkey, key(4));
+}
+
+#[tokio::test]
+async fn the_validators_with_the_most_stake_are_cloned() {
+    let mut fetcher = MockFetcher::new();
+    for (byte, stake) in [(10, 500), (11, 9_000), (12, 2_000), (13, 9_000), (14, 0)] {
+        fetcher = fetcher
+            .with_account(key(byte), current_account(&voting_validator()))
+            .with_vote_stake(key(byte), stake);
+    }
+    let cloned = store(&fetcher)
+        .with_vote_accounts_from_cluster(3)
+        .await
+        .unwrap();
+
+    // Ties in pubkey order
+    assert_eq!(
+        fetcher.calls(),
+        [
+            FetcherCall::GetVoteAccounts,
+            FetcherCall::GetMultipleAccounts(vec![key(11), key(13), key(12)]),
+        ]
+    );
+    assert_eq!(cloned.cache.len(), 3);
+    assert_eq!(cloned.get_vote_state(&key(13)).unwrap(), voting_validator());
+
+    let error = store(&fetcher)
+        .offline()
+        .with_vote_accounts_from_cluster(3)
+        .await
+        .map(|_| ())
+        .unwrap_err();
+    let RpcError::Offline { method } = &error else {
+        panic!("expected Offline, got {error}");
+    };
+    assert_eq!(*method, "getVoteAccounts");
+}
@@ -0,0 +1,128 @@
+//! `wait_for_account` against a mock that starts serving the account after a few
+//! polls: it's cached and returned once it shows up, or once a predicate accepts it,
+//! and a wait that runs out fails with the time it waited.
+//!
+//! The clock is paused, so waits take no real time and poll at exact instants.
+
+mod common;
+
+use {
+    common::{account, store},
+    mollusk_on_demand::{
+        testing::{FetcherCall, MockFetcher},
+        Provenance, RpcError,
+    },
+    solana_account::Account,
+    solana_pubkey::Pubkey,
+    std::time::Duration,
+    tokio::time::Instant,
+};
+
+const POOL: Pubkey = Pubkey::new_from_array([1; 32]);
+const TIMEOUT: Duration = Duration::from_secs(1);
+const POLL_INTERVAL: Duration = Duration::from_millis(300);
+
+/// Serve `account` at the pool once `fetcher` has received `polls` requests, like
+/// a node catching up with the transaction that created it.
+fn serve_after(fetcher: &MockFetcher, polls: usize, account: Account) {
+    let fetcher = fetcher.clone();
+    tokio::spawn(async move {
+        while fetcher.calls().len() < polls {
+            tokio::time::sleep(Duration::from_millis(1)).await;
+        }
+        fetcher.set_account(POOL, account);
+    });
+}
+
+#[tokio::test(start_paused = true)]
+async fn the_account_is_cached_once_it_shows_up() {
+    let fetcher = MockFetcher::new();
+    serve_after(&fetcher, 3, account(1, 8));
+    let mut store = store(&fetcher);
+    let started = Instant::now();
+    let pool = store
+        .wait_for_account(&POOL, TIMEOUT, POLL_INTERVAL)
+        .await
+        .unwrap();
+    assert_eq!(*pool, account(1, 8));
+
+    // Found by the fourth poll, after three intervals
+    assert_eq!(started.elapsed(), 3 * POLL_INTERVAL);
+    assert_eq!(
+        fetcher.calls(),
+        vec![FetcherCall::GetMultipleAccounts(vec![POOL]); 4]
+    );
+    assert_eq!(store.cache[&POOL], account(1, 8));
+    assert!(matches!(
+        store.provenance(&POOL),
+        Some(Provenance::Fetched { .. })
+    ));
+}
+
+#[tokio::test(start_paused = true)]
+async fn a_predicate_waits_for_the_account_to_pass_it() {
+    let mut initialized = account(1, 8);
+    initialized.data[0] = 1;
+    let fetcher = MockFetcher::new().with_account(POOL, account(1, 8));
+    serve_after(&fetcher, 2, initialized.clone());
+
+    let mut store = store(&fetcher).with_accounts([(POOL, account(1, 8))]);
+    let pool = store
+        .wait_for_account_where(&POOL, TIMEOUT, POLL_INTERVAL, |pool| pool.data[0] == 1)
+        .await
+        .unwrap();
+    assert_eq!(*pool, initialized);
+    assert_eq!(fetcher.calls().len(), 3);
+    assert_eq!(store.cache[&POOL], initialized);
+}
+
+#[tokio::test(start_paused = true)]
+async fn a_wait_that_runs_out_times_out() {
+    let fetcher = MockFetcher::new();
+    serve_after(&fetcher, 10, account(1, 8));
+    let mut store = store(&fetcher);
+    let error = store
+        .wait_for_account(&POOL, TIMEOUT, POLL_INTERVAL)
+        .await
+        .map(|_| ())
+        .unwrap_err();
+    let RpcError::Timeout { pubkey, waited } = &error else {
+        panic!("expected Timeout, got {error}");
+    };
+    assert_eq!((*pubkey, *waited), (POOL, TIMEOUT));
+    assert_eq!(error.code(), "account_wait_timeout");
+
+    // At 0, 300, 600 and 900 ms, then a last one at the timeout
+    assert_eq!(fetcher.calls().len(), 5);
+    assert!(store.cache.is_empty());
+}
+
+#[tokio::test(start_paused = true)]
+async fn polls_are_requests_like_any_other() {
+    let fetcher = MockFetcher::new();
+    serve_after(&fetcher, 3, account(1, 8));
+    let error = store(&fetcher)
+        .with_rpc_budget(2)
+        .wait_for_account(&POOL, TIMEOUT, POLL_INTERVAL)
+        .await
+        .map(|_| ())
+        .unwrap_err();
+    assert!(matches!(error, RpcError::BudgetExceeded { .. }), "{error}");
+    assert_eq!(fetcher.calls().len(), 2);
+
+    // Offline, nothing is polled and nothing is waited for
+    let fetcher = MockFetcher::new().with_account(POOL, account(1, 8));
+    let started = Instant::now();
+    let error = store(&fetcher)
+        .offline()
+        .wait_for_account(&POOL, TIMEOUT, POLL_INTERVAL)
+        .await
+        .map(|_| ())
+        .unwrap_err();
+    let RpcError::Offline { method } = &error else {
+        panic!("expected Offline, got {error}");
+    };
+    assert_eq!(*method, "getMultipleAccounts");
+    assert_eq!(started.elapsed(), Duration::ZERO);
+    assert!(fetcher.calls().is_empty());
+}
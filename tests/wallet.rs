@@ -0,0 +1,188 @@
+//! `clone_wallet`: the wallet's account, its token accounts under both token
+//! programs and its accounts in each protocol program are fetched through their own
+//! requests and cached together, and the snapshot counts each kind.
+
+mod common;
+
+use {
+    common::{account, store},
+    mollusk_on_demand::{
+        testing::{FetcherCall, MockFetcher},
+        Provenance, RpcError, WalletSnapshot,
+    },
+    solana_account::Account,
+    solana_program_pack::Pack,
+    solana_pubkey::Pubkey,
+    spl_token_interface::state::{Account as TokenAccount, AccountState},
+    std::collections::BTreeSet,
+};
+
+const LAMPORTS: u64 = 1_000_000_000;
+const SPL_TOKEN: Pubkey = spl_token_interface::ID;
+const TOKEN_2022: Pubkey = mollusk_svm_programs_token::token2022::ID;
+
+fn key(byte: u8) -> Pubkey {
+    Pubkey::new_from_array([byte; 32])
+}
+
+const WALLET: u8 = 1;
+const SOMEONE_ELSE: u8 = 2;
+/// Keeps each position's owner after an 8-byte discriminator.
+const LENDING: u8 = 30;
+/// Keeps each order's owner first.
+const ORDER_BOOK: u8 = 31;
+
+/// A token account of `program` held by `owner`, with Token-2022's
+/// `ImmutableOwner` extension for Token-2022 ones.
+fn token_account(program: Pubkey, owner: u8) -> Account {
+    let mut data = vec![0; TokenAccount::LEN];
+    TokenAccount {
+        mint: key(9),
+        owner: key(owner),
+        amount: 42,
+        state: AccountState::Initialized,
+        ..TokenAccount::default()
+    }
+    .pack_into_slice(&mut data);
+    if program == TOKEN_2022 {
+        data.extend_from_slice(&[2, 7, 0, 0, 0]);
+    }
+    Account {
+        data,
+        owner: program,
+        ..account(LAMPORTS, 0)
+    }
+}
+
+/// An account of `program` holding `owner` at `offset`.
+fn protocol_account(program: u8, owner: u8, offset: usize) -> Account {
+    let mut data = vec![0; 72];
+    data[offset..offset + 32].copy_from_slice(key(owner).as_ref());
+    Account {
+        data,
+        owner: key(program),
+        ..account(LAMPORTS, 0)
+    }
+}
+
+/// The wallet with two SPL Token accounts, a Token-2022 one, two lending positions
+/// and an order, among accounts of someone else.
+fn chain() -> MockFetcher {
+    MockFetcher::new().with_accounts([
+        (key(WALLET), account(LAMPORTS, 0)),
+        (key(10), token_account(SPL_TOKEN, WALLET)),
+        (key(11), token_account(SPL_TOKEN, WALLET)),
+        (key(12), token_account(SPL_TOKEN, SOMEONE_ELSE)),
+        (key(13), token_account(TOKEN_2022, WALLET)),
+        (key(14), token_account(TOKEN_2022, SOMEONE_ELSE)),
+        (key(20), protocol_account(LENDING, WALLET, 8)),
+        (key(21), protocol_account(LENDING, WALLET, 8)),
+        (key(22), protocol_account(LENDING, SOMEONE_ELSE, 8)),
+        // The wallet, but not where lending keeps owners
+        (key(23), protocol_account(LENDING, WALLET, 40)),
+        (key(24), protocol_account(ORDER_BOOK, WALLET, 0)),
+    ])
+}
+
+const FILTERS: [(Pubkey, usize); 2] = [
+    (Pubkey::new_from_array([LENDING; 32]), 8),
+    (Pubkey::new_from_array([ORDER_BOOK; 32]), 0),
+];
+
+#[tokio::test]
+async fn every_fetch_path_is_cached_together() {
+    let fetcher = chain();
+    let mut store = store(&fetcher);
+    let wallet = store.clone_wallet(&key(WALLET), &FILTERS).await.unwrap();
+    assert_eq!(
+        wallet,
+        WalletSnapshot {
+            owner: key(WALLET),
+            has_owner_account: true,
+            token_accounts: 2,
+            token_2022_accounts: 1,
+            program_accounts: vec![(key(LENDING), 2), (key(ORDER_BOOK), 1)],
+        }
+    );
+    assert_eq!(wallet.total(), 7);
+
+    assert_eq!(
+        fetcher.calls(),
+        [
+            FetcherCall::GetMultipleAccounts(vec![key(WALLET)]),
+            FetcherCall::GetTokenAccountsByOwner {
+                owner: key(WALLET),
+                program_id: SPL_TOKEN,
+            },
+            FetcherCall::GetTokenAccountsByOwner {
+                owner: key(WALLET),
+                program_id: TOKEN_2022,
+            },
+            FetcherCall::GetProgramAccountsByMemcmp {
+                program_id: key(LENDING),
+                offset: 8,
+                bytes: key(WALLET).to_bytes().to_vec(),
+            },
+            FetcherCall::GetProgramAccountsByMemcmp {
+                program_id: key(ORDER_BOOK),
+                offset: 0,
+                bytes: key(WALLET).to_bytes().to_vec(),
+            },
+        ]
+    );
+    let cached: BTreeSet<Pubkey> = store.cache.keys().copied().collect();
+    assert_eq!(cached, [1, 10, 11, 13, 20, 21, 24].map(key).into());
+    for pubkey in cached {
+        assert!(
+            matches!(store.provenance(&pubkey), Some(Provenance::Fetched { .. })),
+            "{pubkey}"
+        );
+    }
+}
+
+#[tokio::test]
+async fn listed_accounts_replace_cached_ones() {
+    let fetcher = chain();
+    let mut store = store(&fetcher).with_accounts([(key(10), token_account(SPL_TOKEN, 5))]);
+    store.clone_wallet(&key(WALLET), &[]).await.unwrap();
+    assert_eq!(store.cache[&key(10)], token_account(SPL_TOKEN, WALLET));
+}
+
+#[tokio::test]
+async fn a_wallet_without_an_account_still_has_its_tokens() {
+    let fetcher = chain();
+    fetcher.remove_account(&key(WALLET));
+    let mut store = store(&fetcher);
+    let wallet = store
+        .clone_wallet(&key(WALLET), &FILTERS[..1])
+        .await
+        .unwrap();
+    assert!(!wallet.has_owner_account);
+    assert_eq!((wallet.token_accounts, wallet.token_2022_accounts), (2, 1));
+    assert_eq!(wallet.total(), 5);
+    assert!(!store.cache.contains_key(&key(WALLET)));
+}
+
+#[tokio::test]
+async fn lists_are_bounded_and_need_the_network() {
+    let fetcher = chain();
+    let error = store(&fetcher)
+        .with_program_accounts_limit(1)
+        .clone_wallet(&key(WALLET), &FILTERS)
+        .await
+        .unwrap_err();
+    let RpcError::TooManyProgramAccounts { program, limit } = &error else {
+        panic!("expected TooManyProgramAccounts, got {error}");
+    };
+    assert_eq!((*program, *limit), (SPL_TOKEN, 1));
+
+    let error = store(&fetcher)
+        .offline()
+        .clone_wallet(&key(WALLET), &FILTERS)
+        .await
+        .unwrap_err();
+    let RpcError::Offline { method } = &error else {
+        panic!("expected Offline, got {error}");
+    };
+    assert_eq!(*method, "getTokenAccountsByOwner");
+}
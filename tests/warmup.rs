@@ -0,0 +1,83 @@
+//! `warm_cache`: the background fetch overlaps the test's setup, fetches that need
+//! its pubkeys wait for it instead of requesting them again, and its errors surface
+//! when it's joined.
+
+mod common;
+
+use {
+    common::{instruction, pubkeys, serving, store},
+    mollusk_on_demand::RpcError,
+    solana_pubkey::Pubkey,
+    std::time::Duration,
+    tokio::time::{sleep, Instant},
+};
+
+const LATENCY: Duration = Duration::from_millis(100);
+
+#[tokio::test(start_paused = true)]
+async fn the_fetch_overlaps_setup() {
+    let keys = pubkeys(4);
+    let fetcher = serving(&keys).with_latency(LATENCY);
+    let mut store = store(&fetcher);
+    let started = Instant::now();
+    store.warm_cache(keys.clone());
+
+    // Setup that takes as long as the round trip
+    sleep(LATENCY).await;
+    let store = store.from_instruction(&instruction(&keys)).await.unwrap();
+
+    assert_eq!(started.elapsed(), LATENCY);
+    assert_eq!(fetcher.calls().len(), 1);
+    assert_eq!(store.stats().requests_for("getMultipleAccounts"), 1);
+    for (index, pubkey) in keys.iter().enumerate() {
+        assert_eq!(store.cache[pubkey].lamports, index as u64 + 1);
+    }
+}
+
+#[tokio::test(start_paused = true)]
+async fn overlapping_fetches_request_each_pubkey_once() {
+    let keys = pubkeys(6);
+    let fetcher = serving(&keys).with_latency(LATENCY);
+    let mut store = store(&fetcher);
+    let first = store.warm_cache(keys[..3].to_vec());
+    // Only the pubkeys the first warm-up doesn't fetch yet
+    let second = store.warm_cache(keys[1..5].to_vec());
+
+    let started = Instant::now();
+    let mut store = store.from_instruction(&instruction(&keys)).await.unwrap();
+    store.join_warmup(first).await.unwrap();
+    store.join_warmup(second).await.unwrap();
+
+    let mut requested = fetcher.requested_pubkeys();
+    requested.sort();
+    let mut expected = keys.clone();
+    expected.sort();
+    assert_eq!(requested, expected);
+    // Both warm-ups were in flight together; the last pubkey went after them
+    assert_eq!(fetcher.max_in_flight(), 2);
+    assert_eq!(started.elapsed(), LATENCY * 2);
+    assert_eq!(store.cache.len(), keys.len());
+}
+
+#[tokio::test]
+async fn a_failed_fetch_surfaces_when_joined() {
+    let keys = pubkeys(2);
+    let missing = Pubkey::new_unique();
+    let mut store = store(&serving(&keys));
+    let warmup = store.warm_cache(vec![keys[0], keys[1], missing]);
+
+    let error = store.join_warmup(warmup).await.unwrap_err();
+    assert!(matches!(error, RpcError::AccountNotFound(pubkey) if pubkey == missing), "{error}");
+    // Joined, even though it failed
+    store.join_warmup(warmup).await.unwrap();
+}
+
+#[tokio::test]
+async fn a_failed_fetch_surfaces_in_the_fetch_that_waits_for_it() {
+    let missing = Pubkey::new_unique();
+    let mut store = store(&serving(&[]));
+    store.warm_cache(vec![missing]);
+
+    let error = store.get_or_fetch(&missing).await.unwrap_err();
+    assert!(error.is_not_found(), "{error}");
+}
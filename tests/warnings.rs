@@ -0,0 +1,50 @@
+//! Warnings: `assert_no_warnings` fails with every recorded warning instead of
+//! panicking, and `extend` warns like `with_accounts` when it replaces an account.
+
+mod common;
+
+use {
+    common::{account, store},
+    mollusk_on_demand::{testing::MockFetcher, RpcError, WarningCode},
+    solana_pubkey::Pubkey,
+};
+
+#[test]
+fn a_store_without_warnings_passes() {
+    let store = store(&MockFetcher::new()).with_accounts([(Pubkey::new_unique(), account(1, 0))]);
+    store.assert_no_warnings().unwrap();
+}
+
+#[test]
+fn recorded_warnings_are_returned_in_the_error() {
+    let pubkey = Pubkey::new_unique();
+    let mut store = store(&MockFetcher::new())
+        .with_accounts([(pubkey, account(1, 0))])
+        .with_accounts([(pubkey, account(2, 0))]);
+
+    let error = store.assert_no_warnings().unwrap_err();
+    let RpcError::UnexpectedWarnings(warnings) = &error else {
+        panic!("expected UnexpectedWarnings, got {error}");
+    };
+    assert_eq!(warnings.as_slice(), store.warnings());
+    assert_eq!(warnings[0].code, WarningCode::MockReplacesAccount);
+    assert_eq!(error.code(), "unexpected_warnings");
+    assert!(error.to_string().contains(&pubkey.to_string()), "{error}");
+
+    store.take_warnings();
+    store.assert_no_warnings().unwrap();
+}
+
+#[test]
+fn extend_warns_when_it_replaces_an_account_like_with_accounts() {
+    let (replaced, added) = (Pubkey::new_unique(), Pubkey::new_unique());
+    let mut store = store(&MockFetcher::new()).with_accounts([(replaced, account(1, 0))]);
+    store.extend([(replaced, account(2, 0)), (added, account(3, 0))]);
+
+    let warnings = store.warnings();
+    assert_eq!(warnings.len(), 1, "{warnings:?}");
+    assert_eq!(warnings[0].code, WarningCode::MockReplacesAccount);
+    assert_eq!(warnings[0].pubkey, Some(replaced));
+    assert!(warnings[0].message.starts_with("extend replaced"), "{}", warnings[0]);
+    assert_eq!(store.cache[&replaced].lamports, 2);
+}
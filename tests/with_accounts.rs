@@ -0,0 +1,124 @@
+//! `with_accounts` input types: owned `Account` and `AccountSharedData` pairs and
+//! slices of either all seed the same mocks, and the `AccountSharedData` conversions
+//! round-trip every field, executable and rent epoch included.
+
+use {
+    mollusk_on_demand::{testing::MockFetcher, Provenance, RpcAccountStore},
+    mollusk_svm::Mollusk,
+    solana_account::{Account, AccountSharedData, ReadableAccount},
+    solana_instruction::{AccountMeta, Instruction},
+    solana_pubkey::Pubkey,
+    solana_sdk_ids::sysvar,
+    std::collections::HashMap,
+};
+
+/// Three accounts with distinct lamports, data, owners and flags.
+fn accounts() -> Vec<(Pubkey, Account)> {
+    (0..3u8)
+        .map(|index| {
+            let account = Account {
+                lamports: u64::from(index) + 1,
+                data: vec![index; usize::from(index) * 8],
+                owner: Pubkey::new_unique(),
+                executable: index == 2,
+                rent_epoch: u64::from(index),
+            };
+            (Pubkey::new_unique(), account)
+        })
+        .collect()
+}
+
+fn empty() -> RpcAccountStore {
+    RpcAccountStore::new_with_fetcher(MockFetcher::new())
+}
+
+fn assert_seeded(store: &RpcAccountStore, accounts: &[(Pubkey, Account)]) {
+    let expected: HashMap<Pubkey, Account> = accounts.iter().cloned().collect();
+    assert_eq!(store.cache, expected);
+    for (pubkey, _) in accounts {
+        assert_eq!(store.provenance(pubkey), Some(&Provenance::Mocked));
+    }
+}
+
+#[test]
+fn owned_accounts() {
+    let accounts = accounts();
+    assert_seeded(&empty().with_accounts(accounts.clone()), &accounts);
+}
+
+#[test]
+fn owned_shared_accounts() {
+    let accounts = accounts();
+    let shared: Vec<(Pubkey, AccountSharedData)> = accounts
+        .iter()
+        .map(|(pubkey, account)| (*pubkey, AccountSharedData::from(account.clone())))
+        .collect();
+    assert_seeded(&empty().with_accounts(shared), &accounts);
+}
+
+#[test]
+fn slices_of_either() {
+    let accounts = accounts();
+    assert_seeded(&empty().with_accounts(&accounts), &accounts);
+    assert_seeded(&empty().with_accounts(&accounts[..]), &accounts);
+
+    let shared: Vec<(Pubkey, AccountSharedData)> = accounts
+        .iter()
+        .map(|(pubkey, account)| (*pubkey, account.clone().into()))
+        .collect();
+    assert_seeded(&empty().with_accounts(&shared), &accounts);
+}
+
+#[test]
+fn iterators_and_arrays() {
+    let accounts = accounts();
+    let [first, second, third]: [(Pubkey, Account); 3] = accounts.clone().try_into().unwrap();
+    assert_seeded(&empty().with_accounts([first, second, third]), &accounts);
+    assert_seeded(&empty().with_accounts(accounts.iter().cloned()), &accounts);
+}
+
+#[test]
+fn shared_accounts_round_trip() {
+    let accounts = accounts();
+    let shared: Vec<(Pubkey, AccountSharedData)> = accounts
+        .iter()
+        .map(|(pubkey, account)| (*pubkey, account.clone().into()))
+        .collect();
+    let store = empty().with_shared_accounts(&shared);
+    assert_seeded(&store, &accounts);
+
+    let mut sorted = shared.clone();
+    sorted.sort_by_key(|(pubkey, _)| *pubkey);
+    let exported = store.to_shared();
+    assert_eq!(exported, sorted);
+    for (pubkey, account) in &exported {
+        let cached = &store.cache[pubkey];
+        assert_eq!(
+            (account.lamports(), account.data(), account.owner(), account.executable()),
+            (cached.lamports, &cached.data[..], &cached.owner, cached.executable)
+        );
+        assert_eq!(account.rent_epoch(), cached.rent_epoch);
+        assert_eq!(Account::from(account.clone()), *cached);
+    }
+}
+
+#[test]
+fn instruction_accounts_come_shared_too() {
+    let accounts = accounts();
+    let store = empty().with_accounts(&accounts);
+    let mut metas: Vec<AccountMeta> = accounts
+        .iter()
+        .map(|(pubkey, _)| AccountMeta::new_readonly(*pubkey, false))
+        .collect();
+    metas.push(AccountMeta::new_readonly(sysvar::clock::ID, false));
+    let instruction = Instruction::new_with_bytes(Pubkey::new_unique(), &[], metas);
+    let mollusk = Mollusk::default();
+
+    let owned = store.instruction_accounts(&mollusk, &instruction).unwrap();
+    let shared = store.instruction_accounts_shared(&mollusk, &instruction).unwrap();
+    assert_eq!(shared.len(), accounts.len() + 1);
+    for ((pubkey, account), (shared_pubkey, shared_account)) in owned.into_iter().zip(shared) {
+        assert_eq!(pubkey, shared_pubkey);
+        assert_eq!(account, Account::from(shared_account));
+    }
+}
@@ -0,0 +1,170 @@
+//! `with_workspace_programs` on a temp directory laid out like a workspace: ELFs in
+//! `target/deploy` get their ids from `Anchor.toml` or their keypair files, ELFs
+//! with neither are skipped with a warning, and `add_programs` loads the local
+//! builds instead of fetching, cached or not.
+
+mod common;
+
+use {
+    common::{account, store, temp_dir},
+    mollusk_on_demand::{testing::MockFetcher, RpcError, WarningCode},
+    mollusk_svm::{program::loader_keys::LOADER_V3, result::ProgramResult, Mollusk},
+    solana_account::Account,
+    solana_instruction::Instruction,
+    solana_program_error::ProgramError,
+    solana_pubkey::Pubkey,
+    std::path::{Path, PathBuf},
+};
+
+const ELF: &[u8] = mollusk_svm_programs_token::token::ELF;
+
+/// `TokenError::InvalidInstruction`, what the token ELF answers an empty instruction.
+const INVALID_INSTRUCTION: u32 = 12;
+
+fn key(byte: u8) -> Pubkey {
+    Pubkey::new_from_array([byte; 32])
+}
+
+/// A workspace with `target/deploy/<name>.so` for each of `names`, and an
+/// `Anchor.toml` if given.
+fn workspace(names: &[&str], anchor_toml: Option<&str>) -> PathBuf {
+    let root = temp_dir("workspace");
+    let deploy = root.join("target").join("deploy");
+    std::fs::create_dir_all(&deploy).unwrap();
+    for name in names {
+        std::fs::write(deploy.join(format!("{name}.so")), ELF).unwrap();
+    }
+    if let Some(anchor_toml) = anchor_toml {
+        std::fs::write(root.join("Anchor.toml"), anchor_toml).unwrap();
+    }
+    root
+}
+
+/// Write `target/deploy/<name>-keypair.json` for `pubkey`.
+fn write_keypair(root: &Path, name: &str, pubkey: &Pubkey) {
+    let mut bytes = vec![1u8; 32];
+    bytes.extend_from_slice(pubkey.as_ref());
+    let path = root.join("target").join("deploy").join(format!("{name}-keypair.json"));
+    std::fs::write(path, serde_json::to_string(&bytes).unwrap()).unwrap();
+}
+
+fn loaded(mollusk: &Mollusk, program: Pubkey) -> bool {
+    mollusk.program_cache.load_program(&program).is_some()
+}
+
+/// Check that `program` runs the token ELF, by what it answers an empty instruction.
+fn assert_runs_the_local_build(mollusk: &Mollusk, program: Pubkey) {
+    let instruction = Instruction::new_with_bytes(program, &[], vec![]);
+    assert_eq!(
+        mollusk.process_instruction(&instruction, &[]).program_result,
+        ProgramResult::Failure(ProgramError::Custom(INVALID_INSTRUCTION)),
+        "{program}"
+    );
+}
+
+#[tokio::test]
+async fn programs_get_their_ids_from_anchor_toml_or_keypairs() {
+    let root = workspace(
+        &["swap", "vault", "keyed", "orphan"],
+        Some(
+            r#"
+[programs.devnet]
+swap = "11111111111111111111111111111112"
+
+[programs.localnet]
+swap = "4vJ9JU1bJJE96FWSJKvHsmmFADCg4gpZQff4P3bkLKi"
+vault = { address = "8qbHbw2BbbTHBW1sbeqakYXVKRQM8Ne7pLK7m6CVfeR", idl = "vault.json" }
+"#,
+        ),
+    );
+    write_keypair(&root, "keyed", &key(3));
+    // Anchor.toml wins over the keypair file
+    write_keypair(&root, "swap", &key(9));
+
+    let fetcher = MockFetcher::new();
+    let mut mollusk = Mollusk::default();
+    let store = store(&fetcher)
+        .with_workspace_programs(&root)
+        .unwrap()
+        .add_programs(&mut mollusk)
+        .await
+        .unwrap();
+
+    for program in [key(1), key(2), key(3)] {
+        assert_runs_the_local_build(&mollusk, program);
+    }
+    assert!(!loaded(&mollusk, key(9)));
+    assert!(!loaded(&mollusk, Pubkey::from_str_const("11111111111111111111111111111112")));
+    let [warning] = store.warnings() else {
+        panic!("expected one warning, got {:?}", store.warnings());
+    };
+    assert_eq!(warning.code, WarningCode::LocalProgramWithoutId);
+    let orphan = root.join("target").join("deploy").join("orphan.so");
+    assert_eq!(
+        warning.message,
+        format!("{} has no Anchor.toml entry or keypair file", orphan.display())
+    );
+    assert!(fetcher.calls().is_empty());
+}
+
+#[tokio::test]
+async fn a_local_build_replaces_a_cached_upgradeable_program() {
+    let root = workspace(&["swap"], None);
+    write_keypair(&root, "swap", &key(1));
+    // Its program data isn't served: the local build means it's never fetched
+    let mut data = 2u32.to_le_bytes().to_vec();
+    data.extend_from_slice(key(2).as_ref());
+    let program = Account {
+        data,
+        executable: true,
+        owner: LOADER_V3,
+        ..account(1_000_000_000, 0)
+    };
+
+    let fetcher = MockFetcher::new();
+    let mut mollusk = Mollusk::default();
+    store(&fetcher)
+        .with_accounts([(key(1), program)])
+        .with_workspace_programs(&root)
+        .unwrap()
+        .add_programs(&mut mollusk)
+        .await
+        .unwrap();
+    assert_runs_the_local_build(&mollusk, key(1));
+    assert!(fetcher.calls().is_empty());
+}
+
+#[test]
+fn invalid_workspaces_are_errors() {
+    let error = store(&MockFetcher::new())
+        .with_workspace_programs(temp_dir("workspace"))
+        .map(|_| ())
+        .unwrap_err();
+    let RpcError::LocalProgram { path, .. } = &error else {
+        panic!("expected LocalProgram, got {error}");
+    };
+    assert!(path.ends_with("target/deploy"), "{}", path.display());
+
+    let root = workspace(&["swap"], None);
+    let keypair = root.join("target").join("deploy").join("swap-keypair.json");
+    std::fs::write(&keypair, "[1, 2, 3]").unwrap();
+    let error = store(&MockFetcher::new())
+        .with_workspace_programs(&root)
+        .map(|_| ())
+        .unwrap_err();
+    let RpcError::LocalProgram { path, reason } = &error else {
+        panic!("expected LocalProgram, got {error}");
+    };
+    assert_eq!((path, reason.as_str()), (&keypair, "3 bytes, expected a 64-byte keypair"));
+
+    let root = workspace(&["swap"], Some("[programs.localnet]\nswap = \"not a pubkey\"\n"));
+    let error = store(&MockFetcher::new())
+        .with_workspace_programs(&root)
+        .map(|_| ())
+        .unwrap_err();
+    let RpcError::InvalidConfig { path, reason } = &error else {
+        panic!("expected InvalidConfig, got {error}");
+    };
+    assert_eq!(*path, root.join("Anchor.toml"));
+    assert!(reason.starts_with("programs.localnet.swap is invalid: "), "{reason}");
+}